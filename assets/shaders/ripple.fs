@@ -0,0 +1,21 @@
+#version 330
+
+// Ripples an image outwards from its centre. `amount` scales the ripple;
+// `time` is set to the clip's local time.
+in vec2 fragTexCoord;
+in vec4 fragColor;
+
+uniform sampler2D texture0;
+uniform vec4 colDiffuse;
+uniform float time;
+uniform float amount;
+
+out vec4 finalColor;
+
+void main() {
+    vec2 offset = fragTexCoord - vec2(0.5);
+    float dist = length(offset);
+    vec2 dir = dist > 0.0 ? offset / dist : vec2(0.0);
+    vec2 uv = fragTexCoord + dir * sin(dist * 40.0 - time * 6.0) * 0.01 * amount;
+    finalColor = texture(texture0, uv) * colDiffuse * fragColor;
+}
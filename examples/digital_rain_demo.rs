@@ -67,7 +67,6 @@ fn main() -> Result<()> {
             color: Color::rgb(10, 10, 14),
         }),
         AnimatedTransform::constant(Transform::default()),
-        duration,
     )?);
 
     // Digital rain layer: colored squares that dim and fall.
@@ -128,7 +127,6 @@ fn main() -> Result<()> {
                         pos,
                         ..Transform::default()
                     }),
-                    duration,
                 )?);
             }
         }
@@ -138,8 +136,8 @@ fn main() -> Result<()> {
         grid = step_rain(&grid, cols, rows, dim_value);
     }
 
-    timeline.add_layer(background);
-    timeline.add_layer(rain);
+    timeline.add_layer(background)?;
+    timeline.add_layer(rain)?;
 
     // Preview uses a live window; render uses the ffmpeg pipeline.
     let preview = RaylibPreview::new(width, height, Color::rgb(10, 10, 14));
@@ -54,7 +54,6 @@ fn main() -> Result<()> {
             color: Color::rgb(12, 12, 16),
         }),
         AnimatedTransform::constant(Transform::default()),
-        duration,
     )?);
 
     // Game of Life cells are rendered as white squares.
@@ -79,7 +78,6 @@ fn main() -> Result<()> {
                     pos,
                     ..Transform::default()
                 }),
-                duration,
             )?);
         }
     }
@@ -108,14 +106,13 @@ fn main() -> Result<()> {
                         pos,
                         ..Transform::default()
                     }),
-                    duration,
                 )?);
             }
         }
     }
 
-    timeline.add_layer(background);
-    timeline.add_layer(life);
+    timeline.add_layer(background)?;
+    timeline.add_layer(life)?;
 
     // Preview uses a live window; render uses the ffmpeg pipeline.
     let preview = RaylibPreview::new(width, height, Color::rgb(12, 12, 16));
@@ -4,82 +4,60 @@ use std::env;
 use std::path::{Path, PathBuf};
 
 use script_2_script::{
-    AnimatedTransform, Clip, Color, FfmpegVideoEncoder, ImageObject, Layer, Object, RaylibPreview,
-    RaylibRender, Shape, Timeline, Transform, Vec2,
+    Color, FfmpegVideoEncoder, ImageObject, LiveControl, Object, RaylibPreview, RaylibRender,
+    Shape, TimelineBuilder, Transform, Vec2,
 };
 
 fn main() -> Result<()> {
     // 10-second timeline at 30 FPS (timeline time is always seconds).
-    let mut timeline = Timeline::new(10.0, 30)?;
-    let args = RenderArgs::from_env(timeline.duration)?;
-
-    // Background layer: a large rectangle centered on the canvas.
-    let mut background = Layer::new("background");
-    background.add_clip(Clip::new(
-        0.0,
-        8.0,
-        Object::Shape(Shape::Rect {
+    // Layer order = draw order (background → mid → top).
+    let timeline = TimelineBuilder::new(30)
+        .duration(10.0)
+        // Background layer: a large rectangle centered on the canvas.
+        // No transform = no animation (static placement at the center).
+        .layer("background")
+        .clip(Object::Shape(Shape::Rect {
             width: 700.0,
             height: 420.0,
             // Note: this clip covers the screen, so it overrides the preview clear color.
             //color: Color::rgb(128, 5, 128),
             color: Color::rgba(128, 4, 128, 255),
-        }),
-        // Constant transform = no animation (static placement).
-        AnimatedTransform::constant(Transform::default()),
-        timeline.duration,
-    )?);
-
-    // Mid layer: two shapes that overlap in time to show z-ordering.
-    let mut mid = Layer::new("mid");
-    mid.add_clip(Clip::new(
-        1.0,
-        6.5,
-        Object::Shape(Shape::Circle {
+        }))
+        .at(0.0..8.0)
+        // Mid layer: two shapes that overlap in time to show z-ordering.
+        .layer("mid")
+        .clip(Object::Shape(Shape::Circle {
             radius: 80.0,
             color: Color::rgba_css(235, 101, 80, 0.7),
-        }),
-        AnimatedTransform::constant(Transform {
+        }))
+        .at(1.0..6.5)
+        .placed(Transform {
             // Graph coords: (0,0) is center; +Y is up.
             pos: Vec2 { x: -140.0, y: 60.0 },
             ..Transform::default()
-        }),
-        timeline.duration,
-    )?);
-    mid.add_clip(Clip::new(
-        2.5,
-        8.0,
-        Object::Shape(Shape::Rect {
+        })
+        .clip(Object::Shape(Shape::Rect {
             width: 200.0,
             height: 120.0,
             color: Color::rgb(70, 140, 220),
-        }),
-        AnimatedTransform::constant(Transform {
+        }))
+        .at(2.5..8.0)
+        .placed(Transform {
             pos: Vec2 { x: 160.0, y: -40.0 },
             rotation: 12.0,
             ..Transform::default()
-        }),
-        timeline.duration,
-    )?);
-
-    // Top layer: image clip that appears later.
-    let mut top = Layer::new("top");
-    top.add_clip(Clip::new(
-        3.0,
-        8.0,
-        Object::Image(ImageObject::new("assets/logo.png")),
-        AnimatedTransform::constant(Transform {
+        })
+        // Top layer: image clip that appears later.
+        .layer("top")
+        .clip(Object::Image(ImageObject::new("assets/logo.png")))
+        .at(3.0..8.0)
+        .placed(Transform {
             pos: Vec2 { x: 0.0, y: 0.0 },
             scale: Vec2 { x: 2.0, y: 2.0 },
             ..Transform::default()
-        }),
-        timeline.duration,
-    )?);
-
-    // Layer order = draw order (background → mid → top).
-    timeline.add_layer(background);
-    timeline.add_layer(mid);
-    timeline.add_layer(top);
+        })
+        .build()?;
+    let args = RenderArgs::from_env(timeline.duration)?;
 
     // Preview window uses a clear color behind the timeline.
     let preview = RaylibPreview::new(800, 600, Color::rgb(16, 16, 20));
@@ -94,6 +72,13 @@ fn main() -> Result<()> {
             encoder.write_frame(rgba)
         })?;
         encoder.finish()
+    } else if let Some(addr) = &args.osc {
+        // OSC controllers can seek, scrub and pause the preview.
+        let control = LiveControl::new();
+        let listener = control.listen_osc(addr.as_str())?;
+        println!("listening for OSC on {}", listener.local_addr());
+        let mut clock = control.clock(args.start_time, args.end_time);
+        preview.run_clock(&timeline, &mut clock, |_| Ok(()))
     } else {
         preview.run_with(&timeline, args.start_time, args.end_time, |_| Ok(()))
     }
@@ -104,6 +89,7 @@ struct RenderArgs {
     start_time: f32,
     end_time: f32,
     output: Option<PathBuf>,
+    osc: Option<String>,
 }
 
 impl RenderArgs {
@@ -112,6 +98,7 @@ impl RenderArgs {
         let mut start_time = 0.0;
         let mut end_time = duration;
         let mut output = None;
+        let mut osc = None;
 
         let mut args = env::args().skip(1);
         while let Some(arg) = args.next() {
@@ -133,6 +120,11 @@ impl RenderArgs {
                         args.next().ok_or_else(|| anyhow::anyhow!("--output requires a value"))?;
                     output = Some(PathBuf::from(value));
                 }
+                "--osc" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--osc requires a value"))?;
+                    osc = Some(value);
+                }
                 "--preview" => {}
                 other => bail!("unknown argument: {other}"),
             }
@@ -147,6 +139,7 @@ impl RenderArgs {
             start_time,
             end_time,
             output,
+            osc,
         })
     }
 
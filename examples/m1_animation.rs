@@ -4,8 +4,9 @@ use std::path::{Path, PathBuf};
 use anyhow::{bail, Result};
 
 use script_2_script::{
-    AnimatedTransform, Clip, Color, Easing, FfmpegVideoEncoder, ImageObject, Keyframe, Layer,
-    Object, RaylibPreview, RaylibRender, Shape, Timeline, Track, Transform, Vec2,
+    AnimatedTransform, BezierPath, Clip, Color, Easing, FfmpegVideoEncoder, ImageObject, Keyframe,
+    Layer, Object, PathMotion, RaylibPreview, RaylibRender, Shape, Timeline, Track, Transform,
+    Vec2,
 };
 
 fn main() -> Result<()> {
@@ -24,21 +25,38 @@ fn main() -> Result<()> {
             color: Color::rgb(18, 18, 22),
         }),
         AnimatedTransform::default(),
-        timeline.duration,
     )?);
 
     // Motion layer: animated clips to demonstrate easing and interpolation.
     let mut motion = Layer::new("motion");
 
-    // Circle moves from left to right with EaseInOutQuad (smooth in/out).
+    // Circle moves from left to right along an S-shaped path, with
+    // EaseInOutQuad (smooth in/out) on its progress.
+    // Up over a hump to the centre, then down through a dip.
+    let (hump, dip) = (160.0, -160.0);
+    let wave = BezierPath::new(Vec2 { x: -280.0, y: 0.0 })
+        .with_curve(
+            Vec2 { x: -190.0, y: hump },
+            Vec2 { x: -90.0, y: hump },
+            Vec2::ZERO,
+        )
+        .with_curve(
+            Vec2 { x: 90.0, y: dip },
+            Vec2 { x: 190.0, y: dip },
+            Vec2 { x: 280.0, y: 0.0 },
+        );
     let circle_transform = AnimatedTransform {
-        position: Track::new(vec![
-            Keyframe::new(0.0, Vec2 { x: -280.0, y: 0.0 }, Easing::EaseInOutQuad),
-            Keyframe::new(4.0, Vec2 { x: 280.0, y: 0.0 }, Easing::Linear),
-        ])?,
+        position: Track::from_constant(Vec2::ZERO),
         scale: Track::from_constant(Vec2 { x: 1.0, y: 1.0 }),
         rotation: Track::from_constant(0.0),
         opacity: Track::from_constant(1.0),
+        path: Some(PathMotion::new(
+            wave,
+            Track::new(vec![
+                Keyframe::new(0.0, 0.0, Easing::EaseInOutQuad),
+                Keyframe::new(4.0, 1.0, Easing::Linear),
+            ])?,
+        )?),
     };
 
     motion.add_clip(Clip::new(
@@ -49,7 +67,6 @@ fn main() -> Result<()> {
             color: Color::rgb(240, 120, 90),
         }),
         circle_transform,
-        timeline.duration,
     )?);
 
     // Image rotates with EaseOutCubic and fades in/out via opacity keyframes.
@@ -66,6 +83,7 @@ fn main() -> Result<()> {
             Keyframe::new(3.2, 1.0, Easing::EaseOutCubic),
             Keyframe::new(4.0, 0.0, Easing::Linear),
         ])?,
+        path: None,
     };
 
     motion.add_clip(Clip::new(
@@ -73,14 +91,13 @@ fn main() -> Result<()> {
         4.5,
         Object::Image(ImageObject::new("assets/logo.png")),
         image_transform,
-        timeline.duration,
     )?);
 
     // Layer order controls draw order: background first, motion on top.
-    timeline.add_layer(background);
-    timeline.add_layer(motion);
+    timeline.add_layer(background)?;
+    timeline.add_layer(motion)?;
 
-    // Preview window uses fixed-dt sampling from the timeline.
+    // Preview window plays the timeline in real time.
     let preview = RaylibPreview::new(800, 600, Color::rgb(16, 16, 20));
 
     if args.render {
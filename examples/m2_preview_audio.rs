@@ -24,7 +24,6 @@ fn main() -> Result<()> {
             color: Color::rgb(18, 18, 22),
         }),
         AnimatedTransform::default(),
-        timeline.duration,
     )?);
 
     // Animated layer to give the preview something moving.
@@ -37,6 +36,7 @@ fn main() -> Result<()> {
         scale: Track::from_constant(Vec2 { x: 1.0, y: 1.0 }),
         rotation: Track::from_constant(0.0),
         opacity: Track::from_constant(1.0),
+        path: None,
     };
 
     motion.add_clip(Clip::new(
@@ -47,7 +47,6 @@ fn main() -> Result<()> {
             color: Color::rgb(235, 90, 90),
         }),
         circle_transform,
-        timeline.duration,
     )?);
 
     motion.add_clip(Clip::new(
@@ -59,11 +58,10 @@ fn main() -> Result<()> {
             scale: Vec2 { x: 2.0, y: 2.0 },
             ..Transform::default()
         }),
-        timeline.duration,
     )?);
 
-    timeline.add_layer(background);
-    timeline.add_layer(motion);
+    timeline.add_layer(background)?;
+    timeline.add_layer(motion)?;
 
     if args.render {
         let output_path = args.resolve_output("m2_preview_audio")?;
@@ -76,7 +74,7 @@ fn main() -> Result<()> {
         })?;
         encoder.finish()
     } else {
-        // Preview window uses fixed-dt sampling; audio is updated each frame.
+        // Preview window follows the wall clock; audio is updated each frame.
         let preview = RaylibPreview::new(800, 600, Color::rgb(16, 16, 20));
         let mut audio = AudioEngine::new("assets/background.mp3", "assets/border.ogg")?;
         audio.start_background(true);
@@ -1,14 +1,28 @@
 use std::env;
+use std::io::{BufRead, IsTerminal};
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 use anyhow::{bail, Context, Result};
 
 use script_2_script::{
-    AnimatedTransform, Clip, Color, Easing, FfmpegVideoEncoder, Keyframe, Layer, Object,
-    RaylibRender, Shape, Timeline, Track, Transform, Vec2,
+    exit_code, AnimatedImage, AnimatedImageEncoder, AnimatedImageFormat, AnimatedTransform,
+    AudioTrack, CancelToken, Clip, Color, ContactSheet, DebugOptions, DisplayMode, Dither, Easing,
+    Encoder, EncoderSettings, ErrorKind, FfmpegVideoEncoder, FrameHashes, GoldenCheck,
+    ImageSequenceEncoder, ImageSequenceFormat, IncrementalRender, Keyframe, Layer, Object,
+    OutputColor, OutputMetadata, RaylibPreview, RaylibRender, RenderBackend, RenderRegion,
+    ResumableRender, Shape, SoftwareRender, StickerExport, StickerFormat, StreamOutput, Timeline,
+    Track, Transform, Vec2, VideoCodec, VideoOptions,
 };
 
-fn main() -> Result<()> {
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {err:#}");
+        std::process::exit(exit_code(&err));
+    }
+}
+
+fn run() -> Result<()> {
     // 25-second timeline at 30 FPS for a longer render demo.
     let mut timeline = Timeline::new(25.0, 30)?;
     let args = RenderArgs::from_env(timeline.duration)?;
@@ -24,7 +38,6 @@ fn main() -> Result<()> {
             color: Color::rgb(18, 18, 22),
         }),
         AnimatedTransform::constant(Transform::default()),
-        timeline.duration,
     )?);
 
     // Motion layer: a bouncing ball whose color cycles over time.
@@ -35,13 +48,14 @@ fn main() -> Result<()> {
     let start_vel = Vec2 { x: 220.0, y: 170.0 };
 
     // Color cycle hits red → yellow → green → cyan → blue → magenta.
+    // Each clip is named after its color, e.g. for `--sticker cyan`.
     let colors = [
-        Color::rgb(255, 0, 0),     // red
-        Color::rgb(255, 255, 0),   // yellow
-        Color::rgb(0, 255, 0),     // green
-        Color::rgb(0, 255, 255),   // cyan
-        Color::rgb(0, 0, 255),     // blue
-        Color::rgb(255, 0, 255),   // magenta
+        ("red", Color::rgb(255, 0, 0)),
+        ("yellow", Color::rgb(255, 255, 0)),
+        ("green", Color::rgb(0, 255, 0)),
+        ("cyan", Color::rgb(0, 255, 255)),
+        ("blue", Color::rgb(0, 0, 255)),
+        ("magenta", Color::rgb(255, 0, 255)),
     ];
 
     // Precompute the full bounce path for the whole timeline.
@@ -51,7 +65,7 @@ fn main() -> Result<()> {
     let segment = timeline.duration / colors.len() as f32;
     let fade = (segment * 0.2).min(1.0);
 
-    for (i, color) in colors.iter().enumerate() {
+    for (i, (name, color)) in colors.iter().enumerate() {
         let base_start = i as f32 * segment;
         let base_end = if i == colors.len() - 1 {
             timeline.duration
@@ -80,13 +94,21 @@ fn main() -> Result<()> {
                 scale: Track::from_constant(Vec2 { x: 1.0, y: 1.0 }),
                 rotation: Track::from_constant(0.0),
                 opacity,
+                path: None,
             },
-            timeline.duration,
-        )?);
+        )?
+        .with_name(*name));
     }
 
-    timeline.add_layer(background);
-    timeline.add_layer(motion);
+    timeline.add_layer(background)?;
+    timeline.add_layer(motion)?;
+
+    // `--preview` plays the range in a window to check timing, without
+    // encoding anything.
+    if args.preview {
+        let preview = RaylibPreview::new(800, 600, Color::rgb(16, 16, 20));
+        return preview.run_range(&timeline, args.start_time, args.end_time);
+    }
 
     // Render to MP4 via ffmpeg (video only for M3).
     let output_path = args.resolve_output("m3_render_video")?;
@@ -97,24 +119,373 @@ fn main() -> Result<()> {
         output_path.clone()
     };
 
-    let mut renderer = RaylibRender::new(800, 600, Color::rgb(16, 16, 20))?;
-    let mut encoder = FfmpegVideoEncoder::start(800, 600, timeline.fps, &temp_path)?;
+    // Exports one color's clip on its own, cropped, against transparency; the
+    // container comes from the output extension (.webm, .mov or .png).
+    if let Some(name) = &args.sticker {
+        let format = StickerFormat::from_path(&output_path)
+            .context("--sticker needs a .webm, .mov or .png output")?;
+        let mut renderer = RaylibRender::builder(800, 600)
+            .with_background(Color::rgba(0, 0, 0, 0))
+            .with_display(args.display)
+            .with_render_scale(args.render_scale)
+            .build()?;
+        let sticker = StickerExport::new(name, format).export(
+            &timeline,
+            800,
+            600,
+            &output_path,
+            |timeline, start, end, on_frame| {
+                renderer.render_timeline_rgba(timeline, start, end, |_t, rgba| on_frame(rgba))
+            },
+        )?;
+        println!(
+            "sticker {}x{} at ({}, {}), {} frames",
+            sticker.width, sticker.height, sticker.center.x, sticker.center.y, sticker.frames
+        );
+        return Ok(());
+    }
+
+    // `--transparent` keeps the alpha channel, for overlay assets.
+    let bg = if args.transparent {
+        Color::rgba(0, 0, 0, 0)
+    } else {
+        Color::rgb(16, 16, 20)
+    };
+    // `--backend software` draws on the CPU, for machines with no GPU or GL;
+    // `--backend wgpu` draws through Vulkan, Metal or DX12. `--linear-light`
+    // blends and fades in linear light on every backend.
+    if args.backend != Backend::Raylib {
+        let mut renderer: Box<dyn RenderBackend> = match args.backend {
+            Backend::Software => {
+                let mut renderer = SoftwareRender::new(800, 600, bg)?;
+                renderer.set_linear_light(args.linear_light);
+                Box::new(renderer)
+            }
+            #[cfg(feature = "wgpu")]
+            Backend::Wgpu => {
+                let mut renderer = script_2_script::WgpuRender::new(800, 600, bg)?;
+                renderer.set_linear_light(args.linear_light);
+                Box::new(renderer)
+            }
+            #[cfg(not(feature = "wgpu"))]
+            Backend::Wgpu => bail!("--backend wgpu needs the wgpu feature"),
+            Backend::Raylib => unreachable!(),
+        };
+        let (mut encoder, written_to) =
+            start_encoder(&args, timeline.fps, (800, 600), &output_path, &temp_path)?;
+        encoder.set_dither(args.dither);
+        let cancel = cancel_on_quit();
+        renderer.set_cancel_token(cancel.clone());
+        let mut checks = FrameChecks::start(&args, timeline.fps, (800, 600))?;
+        renderer.render_timeline_rgba(&timeline, args.start_time, args.end_time, &mut |t, rgba| {
+            checks.frame(t, rgba)?;
+            encoder.write_frame(rgba)
+        })?;
+        finish_output(&args, encoder, &written_to, &temp_path, &output_path)?;
+        checks.finish()?;
+        return check_cancelled(cancel.is_cancelled());
+    }
+
+    // `--debug-overlay` draws frame numbers, safe areas and clip outlines
+    // over the output, to see where things are.
+    let debug = if args.debug_overlay {
+        DebugOptions::all()
+    } else {
+        DebugOptions::default()
+    };
+    let mut renderer = RaylibRender::builder(800, 600)
+        .with_background(bg)
+        .with_display(args.display)
+        .with_render_scale(args.render_scale)
+        // `--region 400,0,400,300` renders only the top-right quarter.
+        .with_region(args.region)
+        // `--tile-size 4096` for frames larger than the GPU's textures.
+        .with_tile_size(args.tile_size)
+        // `--resolution-scale 0.25` for a quick quarter-size draft.
+        .with_resolution_scale(args.resolution_scale)
+        // `--show-frames` shows the render in its window as it goes; closing
+        // the window stops it like `q` does.
+        .with_show_frames(args.show_frames)
+        .with_debug_options(debug)
+        // `--texture-budget 512` keeps at most 512 MiB of images on the GPU.
+        .with_texture_budget(args.texture_budget_mib.map(|mib| mib * 1024 * 1024))
+        // `--prefetch 2` loads images and fonts two seconds before their clips.
+        .with_prefetch(args.prefetch_secs)
+        .with_linear_light(args.linear_light)
+        .build()?;
+    let (width, height) = renderer.output_size();
+
+    // A poster frame or thumbnail, e.g. `--still 3.5 --output poster.png`.
+    if let Some(time) = args.still {
+        renderer.render_frame_to_png(&timeline, time, &output_path)?;
+        println!("still written to {}", output_path.display());
+        return Ok(());
+    }
+
+    // `--contact-sheet 12 --output sheet.png` reviews the whole timeline in
+    // one image.
+    if let Some(frames) = args.contact_sheet {
+        ContactSheet::new(frames).render(&mut renderer, &timeline, &output_path)?;
+        println!("contact sheet written to {}", output_path.display());
+        return Ok(());
+    }
+
+    // Re-renders only the chunks whose frames changed since the last run.
+    if let Some(dir) = &args.incremental
+        && args.frames.is_none()
+        && AnimatedImageFormat::from_path(&output_path).is_none()
+    {
+        let stats = IncrementalRender::new(dir).render(
+            &timeline,
+            width,
+            height,
+            bg,
+            &output_path,
+            |start, end, encoder| {
+                renderer.render_timeline_rgba(&timeline, start, end, |_t, rgba| {
+                    encoder.write_frame(rgba)
+                })
+            },
+        )?;
+        println!("rendered {} of {} chunks", stats.rendered, stats.chunks);
+        return Ok(());
+    }
 
-    renderer.render_timeline_rgba(&timeline, args.start_time, args.end_time, |_t, rgba| {
+    // Renders in 10-second segments kept in DIR; after a crash the same
+    // command carries on from the first unfinished segment.
+    if let Some(dir) = &args.resume {
+        let stats = ResumableRender::new(dir).with_options(video_options(&args)).render(
+            &timeline,
+            width,
+            height,
+            &output_path,
+            |start, end, encoder| {
+                encoder.set_dither(args.dither);
+                renderer.render_timeline_rgba(&timeline, start, end, |_t, rgba| {
+                    encoder.write_frame(rgba)
+                })
+            },
+        )?;
+        println!("resumed {} of {} segments", stats.resumed, stats.segments);
+        return Ok(());
+    }
+
+    // Numbered frames for compositing, a looping GIF or WebP, or a video;
+    // past here they all render the same way.
+    let (mut encoder, written_to) =
+        start_encoder(&args, timeline.fps, (width, height), &output_path, &temp_path)?;
+    encoder.set_dither(args.dither);
+
+    renderer.set_record_hit_regions(args.hit_regions.is_some());
+    let cancel = cancel_on_quit();
+    renderer.set_cancel_token(cancel.clone());
+    let mut checks = FrameChecks::start(&args, timeline.fps, (width, height))?;
+    renderer.render_timeline_rgba(&timeline, args.start_time, args.end_time, |t, rgba| {
+        checks.frame(t, rgba)?;
         encoder.write_frame(rgba)
     })?;
+    if let (Some(path), Some(track)) = (&args.hit_regions, renderer.last_hit_regions()) {
+        track.write_json(path)?;
+    }
+    // The report is written after finishing, so it includes the final
+    // flush and mux, and even if they fail.
+    let mut report = renderer.last_report().clone();
+    report.encoder = Some(encoder.name());
+    report.warnings.extend(encoder.warnings());
+    let finishing = Instant::now();
+    let finished = finish_output(&args, encoder, &written_to, &temp_path, &output_path);
+    if let Some(path) = &args.report {
+        report.add_finish_secs(finishing.elapsed().as_secs_f64());
+        report.write_json(path)?;
+    }
+    finished?;
+    checks.finish()?;
+    check_cancelled(renderer.last_report().cancelled)
+}
+
+// `--hashes` records a hash of every frame; `--golden` checks frames against
+// such a file, and `--golden-diffs` keeps the ones that differ as PNGs.
+struct FrameChecks {
+    hashes: Option<(FrameHashes, PathBuf)>,
+    golden: Option<GoldenCheck>,
+}
+
+impl FrameChecks {
+    fn start(args: &RenderArgs, fps: u32, (width, height): (u32, u32)) -> Result<Self> {
+        let hashes = args
+            .hashes
+            .as_ref()
+            .map(|path| (FrameHashes::new(width, height, fps), path.clone()));
+        let golden = match &args.golden {
+            Some(path) => {
+                let check = GoldenCheck::new(FrameHashes::load(path)?);
+                Some(match &args.golden_diffs {
+                    Some(dir) => check.with_mismatch_dir(dir),
+                    None => check,
+                })
+            }
+            None => None,
+        };
+        Ok(Self { hashes, golden })
+    }
+
+    fn frame(&mut self, t: f32, rgba: &[u8]) -> Result<()> {
+        if let Some((hashes, _)) = &mut self.hashes {
+            hashes.push(t, rgba);
+        }
+        match &mut self.golden {
+            Some(golden) => golden.check_frame(t, rgba),
+            None => Ok(()),
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        if let Some((hashes, path)) = &self.hashes {
+            hashes.write_json(path)?;
+            println!("frame hashes written to {}", path.display());
+        }
+        if let Some(golden) = self.golden {
+            let report = golden.finish();
+            for mismatch in &report.mismatches {
+                if let Some(saved) = &mismatch.saved {
+                    println!("frame {} differs: {}", mismatch.frame, saved.display());
+                }
+            }
+            println!(
+                "golden: {} of {} frames match",
+                report.checked as usize - report.mismatches.len(),
+                report.checked
+            );
+            report.check()?;
+        }
+        Ok(())
+    }
+}
 
+// Typing `q` and Enter in the terminal stops the render early; the frames
+// rendered so far are still encoded into a playable file.
+fn cancel_on_quit() -> CancelToken {
+    let cancel = CancelToken::new();
+    if std::io::stdin().is_terminal() {
+        println!("press q then Enter to stop early");
+        let handle = cancel.clone();
+        std::thread::spawn(move || {
+            let mut lines = std::io::stdin().lock().lines().map_while(Result::ok);
+            if lines.any(|line| line.trim() == "q") {
+                handle.cancel();
+            }
+        });
+    }
+    cancel
+}
+
+// Still exits with the cancelled code once the output is finished, so
+// scripts can tell a stopped render from a complete one.
+fn check_cancelled(cancelled: bool) -> Result<()> {
+    if cancelled {
+        bail!(ErrorKind::Cancelled.error("render stopped early; the frames so far were kept"));
+    }
+    Ok(())
+}
+
+fn finish_output(
+    args: &RenderArgs,
+    mut encoder: Box<dyn Encoder>,
+    written_to: &Path,
+    temp_path: &Path,
+    output_path: &Path,
+) -> Result<()> {
     encoder.finish()?;
 
-    if args.keep_temp {
-        std::fs::copy(&temp_path, &output_path).with_context(|| {
+    if args.keep_temp && written_to == temp_path {
+        std::fs::copy(temp_path, output_path).with_context(|| {
             format!("failed to copy temp output to {}", output_path.display())
         })?;
     }
+    if args.frames.is_some() {
+        println!("frames written to {}", written_to.display());
+    }
 
     Ok(())
 }
 
+// The encoder for the output the flags ask for, and where it writes. Frames
+// are `width` x `height`.
+fn start_encoder(
+    args: &RenderArgs,
+    fps: u32,
+    (width, height): (u32, u32),
+    output_path: &Path,
+    temp_path: &Path,
+) -> Result<(Box<dyn Encoder>, PathBuf)> {
+    // Numbered frames instead of a video; `--output` names the directory.
+    // Numbering starts at the first rendered timeline frame.
+    if let Some(format) = args.frames {
+        let dir = match &args.output {
+            Some(path) => path.clone(),
+            None => PathBuf::from("output/m3_render_video_frames"),
+        };
+        let first_frame = (args.start_time * fps as f32).round() as u32;
+        let frames = ImageSequenceEncoder::start_with_ffmpeg(
+            width,
+            height,
+            fps,
+            &dir,
+            format,
+            first_frame,
+            args.color,
+            args.ffmpeg.as_deref().unwrap_or(Path::new("ffmpeg")),
+        )?;
+        return Ok((Box::new(frames), dir));
+    }
+
+    // A looping GIF or WebP when the output ends in .gif or .webp.
+    if let Some(format) = AnimatedImageFormat::from_path(output_path) {
+        let mut settings = AnimatedImage::new(format).with_plays(args.plays);
+        if let Some(max_colors) = args.max_colors {
+            settings = settings.with_max_colors(max_colors);
+        }
+        let encoder = AnimatedImageEncoder::start(width, height, fps, output_path, &settings)?;
+        return Ok((Box::new(encoder), output_path.to_path_buf()));
+    }
+
+    let options = video_options(args);
+    // `--stream rtmp://...` or `--stream live/out.m3u8` renders to a stream
+    // instead of the output file.
+    let encoder = match &args.stream {
+        Some(stream) => FfmpegVideoEncoder::start_stream(width, height, fps, stream, &options)?,
+        None => FfmpegVideoEncoder::start_with_options(width, height, fps, temp_path, &options)?,
+    };
+    Ok((Box::new(encoder), temp_path.to_path_buf()))
+}
+
+fn video_options(args: &RenderArgs) -> VideoOptions {
+    // Falls back along `--codec` (e.g. `h264_nvenc,libx264`) if an encoder
+    // can't start on this machine.
+    let mut options = VideoOptions::default()
+        .with_codecs(args.video_codecs())
+        .with_color(args.color)
+        .with_output_metadata(&args.metadata);
+    // `--audio music.mp3` lays a track under the video, placed with
+    // `--audio-offset`, `--audio-trim` and `--audio-fade-out`.
+    if let Some(audio) = &args.audio {
+        options = options.with_audio(audio.clone());
+    }
+    if let Some(ffmpeg) = &args.ffmpeg {
+        options = options.with_ffmpeg(ffmpeg);
+    }
+    options = options
+        .with_input_args(args.ffmpeg_input_args.clone())
+        .with_output_args(args.ffmpeg_output_args.clone());
+    if args.cpu_yuv {
+        options = options.with_cpu_yuv();
+    }
+    if args.transparent {
+        options = options.with_alpha();
+    }
+    options
+}
+
 #[derive(Clone, Copy)]
 struct Bounds {
     min_x: f32,
@@ -223,6 +594,50 @@ struct RenderArgs {
     end_time: f32,
     output: Option<PathBuf>,
     keep_temp: bool,
+    report: Option<PathBuf>,
+    incremental: Option<PathBuf>,
+    codecs: Option<String>,
+    encoder: EncoderSettings,
+    sticker: Option<String>,
+    hit_regions: Option<PathBuf>,
+    frames: Option<ImageSequenceFormat>,
+    color: OutputColor,
+    plays: u32,
+    max_colors: Option<u32>,
+    dither: Dither,
+    audio: Option<AudioTrack>,
+    ffmpeg: Option<PathBuf>,
+    ffmpeg_input_args: Vec<String>,
+    ffmpeg_output_args: Vec<String>,
+    stream: Option<StreamOutput>,
+    cpu_yuv: bool,
+    transparent: bool,
+    preview: bool,
+    still: Option<f32>,
+    contact_sheet: Option<u32>,
+    resume: Option<PathBuf>,
+    metadata: OutputMetadata,
+    display: DisplayMode,
+    show_frames: bool,
+    debug_overlay: bool,
+    texture_budget_mib: Option<u64>,
+    prefetch_secs: Option<f32>,
+    linear_light: bool,
+    hashes: Option<PathBuf>,
+    golden: Option<PathBuf>,
+    golden_diffs: Option<PathBuf>,
+    render_scale: u32,
+    region: Option<RenderRegion>,
+    tile_size: Option<u32>,
+    resolution_scale: f32,
+    backend: Backend,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    Raylib,
+    Software,
+    Wgpu,
 }
 
 impl RenderArgs {
@@ -231,6 +646,48 @@ impl RenderArgs {
         let mut end_time = duration;
         let mut output = None;
         let mut keep_temp = false;
+        let mut report = None;
+        let mut incremental = None;
+        let mut codecs = None;
+        let mut settings_path = None;
+        let mut overrides = EncoderSettings::default();
+        let mut sticker = None;
+        let mut hit_regions = None;
+        let mut color = OutputColor::default();
+        let mut plays = 0;
+        let mut max_colors = None;
+        let mut dither = Dither::None;
+        let mut audio = None;
+        let mut audio_offset = 0.0;
+        let mut audio_trim = (0.0, None);
+        let mut audio_fade_out = 0.0;
+        let mut ffmpeg = None;
+        let mut ffmpeg_input_args = Vec::new();
+        let mut ffmpeg_output_args = Vec::new();
+        let mut stream = None;
+        let mut hls_live = None;
+        let mut cpu_yuv = false;
+        let mut transparent = false;
+        let mut preview = false;
+        let mut still = None;
+        let mut contact_sheet = None;
+        let mut resume = None;
+        let mut metadata = OutputMetadata::default();
+        let mut display = DisplayMode::Auto;
+        let mut show_frames = false;
+        let mut debug_overlay = false;
+        let mut texture_budget_mib = None;
+        let mut prefetch_secs = None;
+        let mut linear_light = false;
+        let mut hashes = None;
+        let mut golden = None;
+        let mut golden_diffs = None;
+        let mut render_scale = 1;
+        let mut region = None;
+        let mut region_pad = false;
+        let mut tile_size = None;
+        let mut resolution_scale = 1.0;
+        let mut backend = Backend::Raylib;
 
         let mut args = env::args().skip(1);
         while let Some(arg) = args.next() {
@@ -255,6 +712,289 @@ impl RenderArgs {
                 "--keep-temp" => {
                     keep_temp = true;
                 }
+                "--report" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--report requires a value"))?;
+                    report = Some(PathBuf::from(value));
+                }
+                "--incremental" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--incremental requires a value"))?;
+                    incremental = Some(PathBuf::from(value));
+                }
+                "--codec" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--codec requires a value"))?;
+                    codecs = Some(value);
+                }
+                "--encoder-settings" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--encoder-settings requires a value"))?;
+                    settings_path = Some(PathBuf::from(value));
+                }
+                "--crf" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--crf requires a value"))?;
+                    overrides.crf = Some(value.parse::<u32>()?);
+                }
+                "--bitrate" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--bitrate requires a value"))?;
+                    overrides.bitrate = Some(value.parse::<u32>()?);
+                }
+                "--preset" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--preset requires a value"))?;
+                    overrides.preset = Some(value);
+                }
+                "--profile" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--profile requires a value"))?;
+                    overrides.profile = Some(value);
+                }
+                "--gop" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--gop requires a value"))?;
+                    overrides.gop = Some(value.parse::<u32>()?);
+                }
+                "--pix-fmt" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--pix-fmt requires a value"))?;
+                    overrides.pix_fmt = Some(value);
+                }
+                "--sticker" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--sticker requires a value"))?;
+                    sticker = Some(value);
+                }
+                "--hit-regions" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--hit-regions requires a value"))?;
+                    hit_regions = Some(PathBuf::from(value));
+                }
+                "--frames" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--frames requires a value"))?;
+                    overrides.frames = Some(
+                        ImageSequenceFormat::from_name(&value)
+                            .ok_or_else(|| anyhow::anyhow!("--frames must be png or exr"))?,
+                    );
+                }
+                "--color" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--color requires a value"))?;
+                    color = OutputColor::from_name(&value)
+                        .ok_or_else(|| anyhow::anyhow!("--color must be srgb or p3"))?;
+                }
+                "--plays" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--plays requires a value"))?;
+                    plays = value.parse::<u32>()?;
+                }
+                "--max-colors" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--max-colors requires a value"))?;
+                    max_colors = Some(value.parse::<u32>()?);
+                }
+                "--dither" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--dither requires a value"))?;
+                    dither = Dither::from_name(&value).ok_or_else(|| {
+                        anyhow::anyhow!("--dither must be none, ordered or blue-noise")
+                    })?;
+                }
+                "--audio" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--audio requires a value"))?;
+                    audio = Some(PathBuf::from(value));
+                }
+                "--audio-offset" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--audio-offset requires a value"))?;
+                    audio_offset = value.parse::<f32>()?;
+                }
+                "--audio-trim" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--audio-trim requires a value"))?;
+                    // `START:END` in seconds into the file; `START:` to its end.
+                    let (start, end) = value
+                        .split_once(':')
+                        .ok_or_else(|| anyhow::anyhow!("--audio-trim must be START:END"))?;
+                    let end = match end {
+                        "" => None,
+                        end => Some(end.parse::<f32>()?),
+                    };
+                    audio_trim = (start.parse::<f32>()?, end);
+                }
+                "--audio-fade-out" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--audio-fade-out requires a value"))?;
+                    audio_fade_out = value.parse::<f32>()?;
+                }
+                "--ffmpeg" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--ffmpeg requires a value"))?;
+                    ffmpeg = Some(PathBuf::from(value));
+                }
+                // Space-separated, e.g. `--ffmpeg-output-args "-movflags +faststart"`.
+                "--ffmpeg-input-args" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--ffmpeg-input-args requires a value"))?;
+                    ffmpeg_input_args.extend(value.split_whitespace().map(str::to_string));
+                }
+                "--ffmpeg-output-args" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--ffmpeg-output-args requires a value"))?;
+                    ffmpeg_output_args.extend(value.split_whitespace().map(str::to_string));
+                }
+                "--stream" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--stream requires a value"))?;
+                    stream = Some(value);
+                }
+                // Keeps a live HLS playlist to the last N segments.
+                "--hls-live" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--hls-live requires a value"))?;
+                    hls_live = Some(value.parse::<u32>()?);
+                }
+                // Converts frames to YUV before piping, for 4K renders.
+                "--cpu-yuv" => {
+                    cpu_yuv = true;
+                }
+                // No background; the output needs a codec with alpha.
+                "--transparent" => {
+                    transparent = true;
+                }
+                // Plays in a window instead of rendering.
+                "--preview" => {
+                    preview = true;
+                }
+                // One frame at this time, to a .png or .jpg `--output`.
+                "--still" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--still requires a value"))?;
+                    still = Some(value.parse::<f32>()?);
+                }
+                // N evenly spaced frames tiled into one .png or .jpg `--output`.
+                "--contact-sheet" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--contact-sheet requires a value"))?;
+                    contact_sheet = Some(value.parse::<u32>()?);
+                }
+                "--resume" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--resume requires a value"))?;
+                    resume = Some(PathBuf::from(value));
+                }
+                // Container tags, shown by players and file browsers.
+                "--title" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--title requires a value"))?;
+                    metadata = metadata.with_title(value);
+                }
+                "--author" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--author requires a value"))?;
+                    metadata = metadata.with_author(value);
+                }
+                "--comment" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--comment requires a value"))?;
+                    metadata = metadata.with_comment(value);
+                }
+                // Renders without a window even when a display is available.
+                "--headless" => {
+                    display = DisplayMode::Headless;
+                }
+                "--show-frames" => {
+                    show_frames = true;
+                }
+                "--debug-overlay" => {
+                    debug_overlay = true;
+                }
+                "--texture-budget" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--texture-budget requires a value"))?;
+                    texture_budget_mib = Some(value.parse::<u64>()?);
+                }
+                "--prefetch" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--prefetch requires a value"))?;
+                    prefetch_secs = Some(value.parse::<f32>()?);
+                }
+                "--linear-light" => {
+                    linear_light = true;
+                }
+                "--hashes" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--hashes requires a value"))?;
+                    hashes = Some(PathBuf::from(value));
+                }
+                "--golden" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--golden requires a value"))?;
+                    golden = Some(PathBuf::from(value));
+                }
+                "--golden-diffs" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--golden-diffs requires a value"))?;
+                    golden_diffs = Some(PathBuf::from(value));
+                }
+                // Supersamples each frame, e.g. 2 for smoother edges.
+                "--render-scale" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--render-scale requires a value"))?;
+                    render_scale = value.parse::<u32>()?;
+                }
+                // Renders only x,y,width,height of the frame, cropped unless
+                // `--region-pad` keeps the full size.
+                "--region" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--region requires a value"))?;
+                    region = Some(RenderRegion::parse(&value)?);
+                }
+                "--region-pad" => region_pad = true,
+                // Draws frames as tiles of at most N x N pixels, for sizes
+                // past the GPU's texture limit.
+                "--tile-size" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--tile-size requires a value"))?;
+                    tile_size = Some(value.parse::<u32>()?);
+                }
+                // Draws and encodes at a fraction of the size, e.g. 0.25.
+                "--resolution-scale" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--resolution-scale requires a value"))?;
+                    resolution_scale = value.parse::<f32>()?;
+                }
+                "--backend" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--backend requires a value"))?;
+                    backend = match value.as_str() {
+                        "raylib" => Backend::Raylib,
+                        "software" => Backend::Software,
+                        "wgpu" => Backend::Wgpu,
+                        _ => bail!("--backend must be raylib, software or wgpu"),
+                    };
+                }
                 other => bail!("unknown argument: {other}"),
             }
         }
@@ -262,12 +1002,128 @@ impl RenderArgs {
         if start_time < 0.0 || end_time <= start_time || end_time > duration {
             bail!("start/end time must satisfy 0 <= start < end <= duration");
         }
+        // The other backends only render straight through to the output.
+        let raylib_only = [
+            (preview, "--preview"),
+            (sticker.is_some(), "--sticker"),
+            (still.is_some(), "--still"),
+            (contact_sheet.is_some(), "--contact-sheet"),
+            (incremental.is_some(), "--incremental"),
+            (resume.is_some(), "--resume"),
+            (hit_regions.is_some(), "--hit-regions"),
+            (report.is_some(), "--report"),
+            (render_scale != 1, "--render-scale"),
+            (region.is_some(), "--region"),
+            (tile_size.is_some(), "--tile-size"),
+            (resolution_scale != 1.0, "--resolution-scale"),
+            (show_frames, "--show-frames"),
+            (debug_overlay, "--debug-overlay"),
+            (texture_budget_mib.is_some(), "--texture-budget"),
+            (prefetch_secs.is_some(), "--prefetch"),
+        ];
+        if backend != Backend::Raylib
+            && let Some((_, flag)) = raylib_only.iter().find(|(set, _)| *set)
+        {
+            bail!("{flag} needs --backend raylib");
+        }
+        // These outputs are stitched from earlier renders or tiles at the
+        // full size.
+        let full_frame_only = [
+            (sticker.is_some(), "--sticker"),
+            (incremental.is_some(), "--incremental"),
+            (resume.is_some(), "--resume"),
+            (tile_size.is_some(), "--tile-size"),
+        ];
+        if region.is_some()
+            && let Some((_, flag)) = full_frame_only.iter().find(|(set, _)| *set)
+        {
+            bail!("--region can't be combined with {flag}");
+        }
+        // Stickers are cropped against the full-size frame.
+        if resolution_scale != 1.0 && sticker.is_some() {
+            bail!("--resolution-scale can't be combined with --sticker");
+        }
+        // Frames are only hashed on their way to a single output.
+        let other_outputs = [
+            (sticker.is_some(), "--sticker"),
+            (still.is_some(), "--still"),
+            (contact_sheet.is_some(), "--contact-sheet"),
+            (incremental.is_some(), "--incremental"),
+            (resume.is_some(), "--resume"),
+        ];
+        if (hashes.is_some() || golden.is_some())
+            && let Some((_, flag)) = other_outputs.iter().find(|(set, _)| *set)
+        {
+            bail!("--hashes and --golden can't be combined with {flag}");
+        }
+        if golden_diffs.is_some() && golden.is_none() {
+            bail!("--golden-diffs needs --golden");
+        }
+        let region = region.map(|region: RenderRegion| region.with_pad(region_pad));
+        // Flags win over the settings file.
+        let encoder = match &settings_path {
+            Some(path) => EncoderSettings::load(path)?.with_overrides(&overrides),
+            None => overrides,
+        };
+        let frames = encoder.frames;
+        let stream = match stream {
+            Some(url) if url.starts_with("rtmp") => Some(StreamOutput::rtmp(url)),
+            Some(playlist) if playlist.ends_with(".m3u8") => Some(match hls_live {
+                Some(size) => StreamOutput::live_hls(playlist, 4.0, size),
+                None => StreamOutput::hls(playlist, 4.0),
+            }),
+            Some(other) => bail!("--stream must be an rtmp:// url or an .m3u8 path: {other}"),
+            None => None,
+        };
+        let audio = audio.map(|path| {
+            AudioTrack::new(path)
+                .with_offset(audio_offset)
+                .with_trim(audio_trim.0, audio_trim.1)
+                .with_fade_out(audio_fade_out)
+        });
 
         Ok(Self {
             start_time,
             end_time,
             output,
             keep_temp,
+            report,
+            incremental,
+            codecs,
+            encoder,
+            sticker,
+            hit_regions,
+            frames,
+            color,
+            plays,
+            max_colors,
+            dither,
+            audio,
+            ffmpeg,
+            ffmpeg_input_args,
+            ffmpeg_output_args,
+            stream,
+            cpu_yuv,
+            transparent,
+            preview,
+            still,
+            contact_sheet,
+            resume,
+            metadata,
+            display,
+            show_frames,
+            debug_overlay,
+            texture_budget_mib,
+            prefetch_secs,
+            linear_light,
+            hashes,
+            golden,
+            golden_diffs,
+            render_scale,
+            region,
+            tile_size,
+            resolution_scale,
+            backend,
         })
     }
 
@@ -276,7 +1132,35 @@ impl RenderArgs {
             return Ok(path.clone());
         }
 
-        Ok(PathBuf::from(format!("output/{default_stem}.mp4")))
+        let extension = self.video_codecs()[0].extension();
+        Ok(PathBuf::from(format!("output/{default_stem}.{extension}")))
+    }
+
+    // `--codec` names presets (`vp9`, `nvenc`, ...) or ffmpeg encoders, and
+    // `auto` tries this machine's hardware encoders first; the encoder
+    // settings override their options.
+    fn video_codecs(&self) -> Vec<VideoCodec> {
+        let codecs: Vec<VideoCodec> = match &self.codecs {
+            Some(names) => names
+                .split(',')
+                .flat_map(|name| match name {
+                    "auto" => VideoCodec::hardware_or_libx264(),
+                    name => vec![VideoCodec::from_name(name)],
+                })
+                .collect(),
+            // A codec with alpha that suits the container: ProRes 4444 in a
+            // .mov, FFV1 in a .mkv, and VP9 in a .webm otherwise.
+            None if self.transparent => {
+                let extension = self.output.as_deref().and_then(Path::extension);
+                vec![match extension.and_then(|extension| extension.to_str()) {
+                    Some("mov") => VideoCodec::prores_4444(),
+                    Some("mkv") => VideoCodec::ffv1(),
+                    _ => VideoCodec::vp9_alpha(),
+                }]
+            }
+            None => vec![VideoCodec::libx264()],
+        };
+        self.encoder.apply_all(&codecs)
     }
 }
 
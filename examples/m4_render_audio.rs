@@ -1,5 +1,6 @@
 use std::env;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 use anyhow::{bail, Result};
 
@@ -7,9 +8,16 @@ use script_2_script::{
     AnimatedTransform, Clip, Color, Easing, FfmpegVideoEncoder, Keyframe, Layer, MusicTrack,
     Object, RaylibRender, Shape, SfxEvent, Timeline, Track, Transform, Vec2,
 };
-use script_2_script::{mux_video_audio, render_audio_wav, trim_audio};
+use script_2_script::{exit_code, mux_video_audio, render_audio_wav, trim_audio};
 
-fn main() -> Result<()> {
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {err:#}");
+        std::process::exit(exit_code(&err));
+    }
+}
+
+fn run() -> Result<()> {
     // 25-second timeline at 30 FPS for render with audio.
     let mut timeline = Timeline::new(25.0, 30)?;
     let args = RenderArgs::from_env(timeline.duration)?;
@@ -25,7 +33,6 @@ fn main() -> Result<()> {
             color: Color::rgb(18, 18, 22),
         }),
         AnimatedTransform::constant(Transform::default()),
-        timeline.duration,
     )?);
 
     // Motion layer: bouncing ball, segmented by color for the RGB cycle.
@@ -44,6 +51,7 @@ fn main() -> Result<()> {
         Color::rgb(0, 0, 255),
         Color::rgb(255, 0, 255),
     ];
+    let color_names = ["Red", "Yellow", "Green", "Cyan", "Blue", "Magenta"];
 
     // Precompute the full bounce path and SFX events for the whole timeline.
     let samples =
@@ -71,6 +79,9 @@ fn main() -> Result<()> {
             (i + 1) as f32 * segment
         };
 
+        // One chapter per color segment in the rendered MP4.
+        timeline.add_marker(base_start, color_names[i])?;
+
         let clip_start = (base_start - fade).max(0.0);
         let clip_end = (base_end + fade).min(timeline.duration);
         let fade_in = base_start - clip_start;
@@ -91,13 +102,13 @@ fn main() -> Result<()> {
                 scale: Track::from_constant(Vec2 { x: 1.0, y: 1.0 }),
                 rotation: Track::from_constant(0.0),
                 opacity,
+                path: None,
             },
-            timeline.duration,
         )?);
     }
 
-    timeline.add_layer(background);
-    timeline.add_layer(motion);
+    timeline.add_layer(background)?;
+    timeline.add_layer(motion)?;
 
     // Render video to a temp file, mix audio offline, then mux into final output.
     let output_path = args.resolve_output("m4_render_audio")?;
@@ -107,11 +118,20 @@ fn main() -> Result<()> {
     let audio_clip = output_path.with_file_name("audio_clip.wav");
 
     let mut renderer = RaylibRender::new(800, 600, Color::rgb(16, 16, 20))?;
-    let mut encoder = FfmpegVideoEncoder::start(800, 600, timeline.fps, &temp_video)?;
+    let chapters = timeline.chapters(args.start_time, args.end_time);
+    let mut encoder =
+        FfmpegVideoEncoder::start_with_chapters(800, 600, timeline.fps, &temp_video, &chapters)?;
     renderer.render_timeline_rgba(&timeline, args.start_time, args.end_time, |_t, rgba| {
         encoder.write_frame(rgba)
     })?;
-    encoder.finish()?;
+    let finishing = Instant::now();
+    let finished = encoder.finish();
+    if let Some(path) = &args.report {
+        let mut report = renderer.last_report().clone();
+        report.add_finish_secs(finishing.elapsed().as_secs_f64());
+        report.write_json(path)?;
+    }
+    finished?;
 
     // Background music track (looped to cover the full timeline).
     let music = MusicTrack {
@@ -149,6 +169,7 @@ struct RenderArgs {
     end_time: f32,
     output: Option<PathBuf>,
     keep_temp: bool,
+    report: Option<PathBuf>,
 }
 
 impl RenderArgs {
@@ -157,6 +178,7 @@ impl RenderArgs {
         let mut end_time = duration;
         let mut output = None;
         let mut keep_temp = false;
+        let mut report = None;
 
         let mut args = env::args().skip(1);
         while let Some(arg) = args.next() {
@@ -181,6 +203,11 @@ impl RenderArgs {
                 "--keep-temp" => {
                     keep_temp = true;
                 }
+                "--report" => {
+                    let value =
+                        args.next().ok_or_else(|| anyhow::anyhow!("--report requires a value"))?;
+                    report = Some(PathBuf::from(value));
+                }
                 other => bail!("unknown argument: {other}"),
             }
         }
@@ -194,6 +221,7 @@ impl RenderArgs {
             end_time,
             output,
             keep_temp,
+            report,
         })
     }
 
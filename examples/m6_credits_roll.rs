@@ -33,12 +33,15 @@ fn main() -> Result<()> {
             bold_italic: Some(FontSource::Path(PathBuf::from(
                 "assets/Bodoni_Moda/BodoniModa_28pt-BoldItalic.ttf",
             ))),
+            fallbacks: Vec::new(),
         },
         font_size: 28.0,
         spacing: 1.0,
         max_width: 560.0,
         color: Color::rgb(230, 230, 230),
         line_spacing: 6.0,
+        stroke: None,
+        background: None,
     };
 
     let start_y = -300.0;
@@ -53,6 +56,7 @@ fn main() -> Result<()> {
         scale: Track::from_constant(Vec2 { x: 1.0, y: 1.0 }),
         rotation: Track::from_constant(0.0),
         opacity: Track::from_constant(1.0),
+        path: None,
     };
 
     let mut layer = Layer::new("credits");
@@ -61,10 +65,9 @@ fn main() -> Result<()> {
         timeline.duration,
         Object::Text(text_block),
         text_transform,
-        timeline.duration,
     )?);
 
-    timeline.add_layer(layer);
+    timeline.add_layer(layer)?;
 
     let preview = RaylibPreview::new(800, 600, Color::rgb(16, 16, 20));
 
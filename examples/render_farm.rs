@@ -0,0 +1,260 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value};
+
+use script_2_script::{
+    compile_scene, concat_videos, exit_code, Coordinator, ErrorKind, FfmpegVideoEncoder, FileQueue,
+    LoadMode, LocalFarm, PreviewStream, Project, RaylibRender, SegmentTask, TimeRange, Watermark,
+    Worker, WorkerEvent,
+};
+
+const USAGE: &str = "usage:
+  render_farm enqueue <queue_dir> <project.json> [--segments N] [--out_dir DIR] [--incremental]
+  render_farm worker <queue_dir> [--name NAME] [--lease_timeout SECS] [--preview ADDR]
+                           [--watermark]
+  render_farm status <queue_dir>
+  render_farm merge <queue_dir> <output.mp4>
+  render_farm local <project.json> <output.mp4> [--workers N] [--segments N] [--keep]";
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {err:#}");
+        std::process::exit(exit_code(&err));
+    }
+}
+
+fn run() -> Result<()> {
+    let mut args = env::args().skip(1);
+    let command = args.next().ok_or_else(|| anyhow!(USAGE))?;
+    if command == "local" {
+        return local(&args.collect::<Vec<_>>());
+    }
+    let queue_dir = PathBuf::from(args.next().ok_or_else(|| anyhow!(USAGE))?);
+    let rest: Vec<String> = args.collect();
+
+    match command.as_str() {
+        "enqueue" => enqueue(&queue_dir, &rest),
+        "worker" => worker(&queue_dir, &rest),
+        "status" => {
+            let status = FileQueue::open(&queue_dir)?.status()?;
+            println!(
+                "pending {} leased {} done {} failed {}",
+                status.pending, status.leased, status.done, status.failed
+            );
+            Ok(())
+        }
+        "merge" => {
+            let output = rest.first().ok_or_else(|| anyhow!(USAGE))?;
+            let queue = FileQueue::open(&queue_dir)?;
+            let status = queue.status()?;
+            if !status.is_drained() || status.failed > 0 {
+                bail!(ErrorKind::InvalidProject.error(format!(
+                    "queue is not finished: {} pending, {} leased, {} failed",
+                    status.pending, status.leased, status.failed
+                )));
+            }
+            concat_videos(&queue.done_outputs()?, Path::new(output))
+        }
+        _ => bail!(USAGE),
+    }
+}
+
+fn enqueue(queue_dir: &Path, args: &[String]) -> Result<()> {
+    let project_path = PathBuf::from(args.first().ok_or_else(|| anyhow!(USAGE))?);
+    let segments = flag(args, "--segments")?.map_or(Ok(8), |value| value.parse::<u32>())?;
+    let out_dir = flag(args, "--out_dir")?
+        .map(PathBuf::from)
+        .unwrap_or_else(|| queue_dir.join("segments"));
+    let incremental = args.iter().any(|arg| arg == "--incremental");
+
+    // Workers read the project themselves, so paths must resolve on every
+    // machine (e.g. a shared mount).
+    let project_path = project_path.canonicalize().unwrap_or(project_path);
+    let source = fs::read_to_string(&project_path)?;
+    let project = Project::load(&project_path)?;
+    let total_frames = project.timeline.total_frames();
+
+    // The previous enqueue's project, to re-render only what changed since.
+    let last_path = queue_dir.join("last_render.json");
+    let changed = if incremental {
+        changed_since(&last_path, &project_path, &project, segments)?
+    } else {
+        None
+    };
+
+    std::fs::create_dir_all(&out_dir)?;
+    let queue = FileQueue::open(queue_dir)?;
+    queue.clear()?;
+    let tasks = SegmentTask::split(&project_path, total_frames, segments, &out_dir);
+    let mut queued = 0;
+    for task in &tasks {
+        match &changed {
+            Some(changed)
+                if !task.is_affected(project.timeline.fps, changed) && task.output.exists() =>
+            {
+                queue.mark_done(task)?
+            }
+            _ => {
+                queue.enqueue(task)?;
+                queued += 1;
+            }
+        }
+    }
+    // Kept as JSON, so `.scene` files are stored compiled.
+    let source: Value = if project_path.extension().is_some_and(|ext| ext == "scene") {
+        compile_scene(&source)?.doc
+    } else {
+        serde_json::from_str(&source)?
+    };
+    fs::write(&last_path, json!({ "segments": segments, "project": source }).to_string())?;
+
+    println!("queued {queued} of {} segments ({total_frames} frames)", tasks.len());
+    Ok(())
+}
+
+// Ranges that changed since the last enqueue, or `None` if everything must be
+// rendered again (no previous render, or a different canvas/frame split).
+fn changed_since(
+    last_path: &Path,
+    project_path: &Path,
+    project: &Project,
+    segments: u32,
+) -> Result<Option<Vec<TimeRange>>> {
+    let Ok(last) = fs::read_to_string(last_path) else {
+        return Ok(None);
+    };
+    let last: Value = serde_json::from_str(&last)?;
+    if last["segments"].as_u64() != Some(segments as u64) {
+        return Ok(None);
+    }
+    let base_dir = project_path.parent().unwrap_or(Path::new("."));
+    let previous =
+        Project::from_json_in(&last["project"].to_string(), base_dir, LoadMode::Strict)?;
+    if (previous.width, previous.height, previous.bg) != (project.width, project.height, project.bg)
+        || previous.timeline.total_frames() != project.timeline.total_frames()
+    {
+        return Ok(None);
+    }
+    Ok(Some(project.timeline.changed_ranges(&previous.timeline)))
+}
+
+// Renders on this machine with one worker process (and GL context) per core,
+// through the same queue and `worker` command as a farm.
+fn local(args: &[String]) -> Result<()> {
+    let project_path = PathBuf::from(args.first().ok_or_else(|| anyhow!(USAGE))?);
+    let output = PathBuf::from(args.get(1).ok_or_else(|| anyhow!(USAGE))?);
+    let cores = thread::available_parallelism().map_or(1, |cores| cores.get() as u32);
+    let workers = flag(args, "--workers")?.map_or(Ok(cores), |value| value.parse::<u32>())?;
+    let mut farm = LocalFarm::new(workers).with_keep_segments(args.iter().any(|a| a == "--keep"));
+    if let Some(segments) = flag(args, "--segments")? {
+        farm = farm.with_segments(segments.parse()?);
+    }
+
+    let exe = env::current_exe()?;
+    let status = farm.render(&project_path, &output, |queue_dir, index| {
+        let mut command = Command::new(&exe);
+        command
+            .arg("worker")
+            .arg(queue_dir)
+            .arg("--name")
+            .arg(format!("local-{index}"));
+        command
+    })?;
+    println!(
+        "rendered {} segments with {workers} workers to {}",
+        status.done,
+        output.display()
+    );
+    Ok(())
+}
+
+fn worker(queue_dir: &Path, args: &[String]) -> Result<()> {
+    let name = match flag(args, "--name")? {
+        Some(name) => name.to_string(),
+        None => format!("worker-{}", std::process::id()),
+    };
+    let lease_timeout = flag(args, "--lease_timeout")?.map_or(Ok(60.0), |v| v.parse::<f32>())?;
+    let queue =
+        FileQueue::open(queue_dir)?.with_lease_timeout(Duration::from_secs_f32(lease_timeout));
+    let label = name.clone();
+    let worker = Worker::new(queue, name.clone())
+        .with_heartbeat_interval(Duration::from_secs_f32((lease_timeout / 4.0).max(1.0)))
+        .with_event_handler(move |event| match event {
+            WorkerEvent::Claimed { task } => println!("worker {label}: rendering {task}"),
+            WorkerEvent::Finished { task } => println!("worker {label}: finished {task}"),
+            WorkerEvent::HeartbeatFailed { task, error } => {
+                eprintln!("worker {label}: heartbeat for {task} failed: {error}")
+            }
+            WorkerEvent::RenderFailed { task, error } => {
+                eprintln!("worker {label}: {task} failed: {error}")
+            }
+            WorkerEvent::ResultLost { task, error } => {
+                eprintln!("worker {label}: dropped the result of {task}: {error}")
+            }
+        });
+
+    let watermark = args.iter().any(|arg| arg == "--watermark");
+    // Live MJPEG view of the frames being rendered, for a browser tab.
+    let mut preview = match flag(args, "--preview")? {
+        Some(addr) => {
+            let preview = PreviewStream::bind(addr)?;
+            println!("preview at http://{}/", preview.local_addr());
+            Some(preview)
+        }
+        None => None,
+    };
+
+    // raylib allows one window per process, so the renderer is created for
+    // the first task's canvas and reused.
+    let mut renderer: Option<(RaylibRender, u32, u32)> = None;
+    worker.run(|task| {
+        let project = Project::load(&task.project)?;
+        if renderer.is_none() {
+            let render = RaylibRender::new(project.width, project.height, project.bg)?;
+            renderer = Some((render, project.width, project.height));
+        }
+        let (render, width, height) = renderer.as_mut().expect("renderer created above");
+        if (*width, *height) != (project.width, project.height) {
+            bail!(ErrorKind::InvalidProject.error(
+                "this worker already renders a different canvas size; start another worker"
+            ));
+        }
+
+        // Traces frames back to the segment, worker and build that made them.
+        if watermark {
+            let text = format!("{} {name} v{}", task.id, env!("CARGO_PKG_VERSION"));
+            render.set_watermark(Some(Watermark::new(text)?));
+        }
+
+        let timeline = &project.timeline;
+        let (start_time, end_time) = task.time_range(timeline.fps, timeline.duration);
+        let mut encoder =
+            FfmpegVideoEncoder::start(project.width, project.height, timeline.fps, &task.output)?;
+        render.render_timeline_rgba(timeline, start_time, end_time, |_t, rgba| {
+            // A broken preview must not fail the render.
+            if let Some(preview) = &mut preview
+                && let Err(err) = preview.publish(rgba, project.width, project.height)
+            {
+                eprintln!("preview: {err:#}");
+            }
+            encoder.write_frame(rgba)
+        })?;
+        encoder.finish()
+    })
+}
+
+fn flag<'a>(args: &'a [String], name: &str) -> Result<Option<&'a str>> {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) => match args.get(index + 1) {
+            Some(value) => Ok(Some(value.as_str())),
+            None => bail!("{name} requires a value"),
+        },
+        None => Ok(None),
+    }
+}
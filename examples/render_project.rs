@@ -0,0 +1,462 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value};
+
+use script_2_script::{
+    compile_scene, exit_code, Dither, EncoderSettings, ErrorKind, FfmpegVideoEncoder,
+    ImageSequenceEncoder, ImageSequenceFormat, IncrementalRender, LoadMode, OutputColor, Project,
+    RaylibPreview, RaylibRender, RenderManifest, TimeRange, VideoCodec, VideoOptions,
+};
+
+const USAGE: &str = "usage:
+  render_project render <project.json> <output.mp4> [--start SECS] [--end SECS]
+                        [--codec NAME] [--encoder-settings FILE] [--color srgb|p3]
+                        [--dither none|ordered|blue-noise] [--incremental DIR]
+                        [--frames png|exr]
+  render_project replay <output.mp4.manifest.json> [--output PATH]
+  render_project preview <project.json> [--start SECS] [--end SECS]
+  render_project validate <project.json>
+  render_project probe <project.json>
+
+`--scene FILE` can replace <project.json> and `--out PATH` <output.mp4>.
+`--width PX`, `--height PX`, `--fps N` and `--duration SECS` override the
+project's own values in every command but replay.";
+
+// Flags each command takes, all with a value. Anything else is rejected
+// rather than read as a positional argument.
+const PROJECT_FLAGS: &[&str] = &["--scene", "--width", "--height", "--fps", "--duration"];
+const RANGE_FLAGS: &[&str] = &["--start", "--end"];
+const RENDER_FLAGS: &[&str] = &[
+    "--out",
+    "--codec",
+    "--encoder-settings",
+    "--color",
+    "--dither",
+    "--incremental",
+    "--frames",
+];
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {err:#}");
+        std::process::exit(exit_code(&err));
+    }
+}
+
+fn run() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("render") => render(&args[1..]),
+        Some("replay") => replay(&args[1..]),
+        Some("preview") => preview(&args[1..]),
+        Some("validate") => validate(&args[1..]),
+        Some("probe") => probe(&args[1..]),
+        _ => bail!(USAGE),
+    }
+}
+
+// Renders a project file and writes `<output>.manifest.json` next to the
+// output, with the same manifest in the output's `comment` tag.
+fn render(args: &[String]) -> Result<()> {
+    check_flags(args, &[PROJECT_FLAGS, RANGE_FLAGS, RENDER_FLAGS])?;
+    let (project_path, project, mut rest) = load_project(args)?;
+    let output = match flag(args, "--out")? {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(rest.next().ok_or_else(|| anyhow!(USAGE))?),
+    };
+    let (start, end) = time_range(args, &project)?;
+
+    let codec = flag(args, "--codec")?.map_or_else(VideoCodec::libx264, VideoCodec::from_name);
+    let settings = match flag(args, "--encoder-settings")? {
+        Some(path) => EncoderSettings::load(Path::new(path))?,
+        None => EncoderSettings::default(),
+    };
+    let color = match flag(args, "--color")? {
+        Some(name) => OutputColor::from_name(name)
+            .ok_or_else(|| anyhow!("unknown --color {name} (expected srgb or p3)"))?,
+        None => OutputColor::Srgb,
+    };
+    let dither = match flag(args, "--dither")? {
+        Some(name) => Dither::from_name(name).ok_or_else(|| {
+            anyhow!("unknown --dither {name} (expected none, ordered or blue-noise)")
+        })?,
+        None => Dither::None,
+    };
+
+    // Numbered images in the output directory instead of a video.
+    let frames = match flag(args, "--frames")? {
+        Some(name) => Some(
+            ImageSequenceFormat::from_name(name)
+                .ok_or_else(|| anyhow!("unknown --frames {name} (expected png or exr)"))?,
+        ),
+        None => settings.frames,
+    };
+    if let Some(format) = frames {
+        if flag(args, "--incremental")?.is_some() {
+            bail!("--frames can't be combined with --incremental");
+        }
+        return render_frames(&project, &output, format, (start, end), color);
+    }
+
+    // The project path is stored as given, so keep it absolute for replays
+    // from another directory.
+    let project_path = project_path.canonicalize().unwrap_or(project_path);
+    if let Some(dir) = flag(args, "--incremental")? {
+        if flag(args, "--start")?.is_some() || flag(args, "--end")?.is_some() {
+            bail!(ErrorKind::InvalidProject.error("--incremental renders the whole project"));
+        }
+        let mut options = VideoOptions::default()
+            .with_codecs(vec![settings.apply(codec)])
+            .with_color(color);
+        options.metadata = project.metadata.tags();
+        return render_incremental(
+            Path::new(dir),
+            &project_path,
+            &project,
+            &output,
+            options,
+            dither,
+        );
+    }
+    let manifest = RenderManifest::record(&project_path, &project, start, end)?;
+    let mut manifest = manifest.with_encoder(vec![settings.apply(codec)], color, dither);
+    encode(&project, &manifest, &output)?;
+    manifest.record_output(&output)?;
+    let manifest_path = manifest_path(&output);
+    manifest.write(&manifest_path)?;
+    println!("manifest written to {}", manifest_path.display());
+    Ok(())
+}
+
+// Re-renders from a manifest after checking nothing it depends on has
+// changed, then compares the result with the recorded output.
+fn replay(args: &[String]) -> Result<()> {
+    check_flags(args, &[&["--output"]])?;
+    let manifest_path = PathBuf::from(positionals(args).first().ok_or_else(|| anyhow!(USAGE))?);
+    let manifest = RenderManifest::load(&manifest_path)?;
+    manifest.check_inputs()?;
+    let output = match flag(args, "--output")? {
+        Some(path) => PathBuf::from(path),
+        None => replay_path(&manifest_path),
+    };
+
+    // Overrides given to `render` were recorded in the manifest.
+    let mut project =
+        Project::load(&manifest.project)?.with_output_size(manifest.width, manifest.height);
+    project.timeline.fps = manifest.fps;
+    project.timeline.duration = manifest.duration;
+    encode(&project, &manifest, &output)?;
+    if !manifest.matches_output(&output)? {
+        bail!(ErrorKind::Encoder.error(format!(
+            "{} is not byte-identical to the recorded render",
+            output.display()
+        )));
+    }
+    println!("{} is byte-identical to the recorded render", output.display());
+    Ok(())
+}
+
+// Plays a project in a window in real time, to check it before rendering.
+fn preview(args: &[String]) -> Result<()> {
+    check_flags(args, &[PROJECT_FLAGS, RANGE_FLAGS])?;
+    let (_, project, _) = load_project(args)?;
+    let (start, end) = time_range(args, &project)?;
+    RaylibPreview::new(project.width, project.height, project.bg).run_range(
+        &project.timeline,
+        start,
+        end,
+    )
+}
+
+// Lists the notes from loading and the validation warnings, and fails with
+// the validation errors' exit code when there are any.
+fn validate(args: &[String]) -> Result<()> {
+    check_flags(args, &[PROJECT_FLAGS])?;
+    let (_, project, _) = load_project(args)?;
+    let report = project.timeline.validate();
+    for warning in &project.warnings {
+        println!("note: {warning}");
+    }
+    for issue in report.warnings() {
+        println!("{issue}");
+    }
+    // Errors are listed in the error it fails with.
+    report.check()?;
+    println!("ok ({} warning(s))", report.warnings().count());
+    Ok(())
+}
+
+// Prints what a project will render, without opening a window.
+fn probe(args: &[String]) -> Result<()> {
+    check_flags(args, &[PROJECT_FLAGS])?;
+    let (_, project, _) = load_project(args)?;
+    let timeline = &project.timeline;
+    // Counted as the render loop counts them.
+    let frames = timeline.total_frames();
+    let clips: usize = timeline.layers.iter().map(|layer| layer.clips.len()).sum();
+    println!("size: {}x{}", project.width, project.height);
+    println!("fps: {}", timeline.fps);
+    println!("duration: {:.3}s ({frames} frames)", timeline.duration);
+    println!("layers: {} ({clips} clips)", timeline.layers.len());
+    println!("markers: {}", timeline.markers.len());
+    Ok(())
+}
+
+// Loads the project given with `--scene` or as the first argument, with the
+// size, frame rate and duration flags applied. Also returns the positional
+// arguments after it.
+fn load_project(args: &[String]) -> Result<(PathBuf, Project, impl Iterator<Item = &str>)> {
+    let mut rest = positionals(args).into_iter();
+    let path = match flag(args, "--scene")? {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(rest.next().ok_or_else(|| anyhow!(USAGE))?),
+    };
+    let mut project = Project::load(&path)?;
+
+    let width = positive_flag(args, "--width")?.unwrap_or(project.width);
+    let height = positive_flag(args, "--height")?.unwrap_or(project.height);
+    if (width, height) != (project.width, project.height) {
+        project = project.with_output_size(width, height);
+    }
+    if let Some(fps) = positive_flag(args, "--fps")? {
+        project.timeline.fps = fps;
+    }
+    if let Some(duration) = positive_flag(args, "--duration")? {
+        project.timeline.duration = duration;
+    }
+    Ok((path, project, rest))
+}
+
+// `--start` and `--end`, which must lie within the project, in order.
+fn time_range(args: &[String], project: &Project) -> Result<(f32, f32)> {
+    let duration = project.timeline.duration;
+    let start = seconds_flag(args, "--start")?.unwrap_or(0.0);
+    let end = seconds_flag(args, "--end")?.unwrap_or(duration);
+    if !(0.0 <= start && start < end && end <= duration) {
+        bail!(ErrorKind::InvalidProject.error(format!(
+            "--start {start} and --end {end} must satisfy 0 <= start < end <= {duration}"
+        )));
+    }
+    Ok((start, end))
+}
+
+fn seconds_flag(args: &[String], name: &str) -> Result<Option<f32>> {
+    let Some(value) = flag(args, name)? else {
+        return Ok(None);
+    };
+    match value.parse::<f32>() {
+        Ok(secs) if secs.is_finite() => Ok(Some(secs)),
+        _ => bail!(ErrorKind::InvalidProject.error(format!("{name} must be seconds, got {value}"))),
+    }
+}
+
+fn encode(project: &Project, manifest: &RenderManifest, output: &Path) -> Result<()> {
+    if (project.width, project.height) != (manifest.width, manifest.height) {
+        bail!(ErrorKind::InvalidProject.error("project size differs from the manifest"));
+    }
+    let mut renderer = RaylibRender::new(project.width, project.height, project.bg)?;
+    let timeline = &project.timeline;
+    let mut options = manifest.video_options();
+    // The project's tags go first, so a `comment` in it gives way to the
+    // manifest's.
+    options.metadata.splice(0..0, project.metadata.tags());
+    let mut encoder = FfmpegVideoEncoder::start_with_options(
+        project.width,
+        project.height,
+        timeline.fps,
+        output,
+        &options,
+    )?;
+    encoder.set_dither(manifest.dither);
+    renderer.render_timeline_rgba(timeline, manifest.start, manifest.end, |_t, rgba| {
+        encoder.write_frame(rgba)
+    })?;
+    encoder.finish()
+}
+
+// There's no single output file to hash, so no manifest is written.
+fn render_frames(
+    project: &Project,
+    dir: &Path,
+    format: ImageSequenceFormat,
+    (start, end): (f32, f32),
+    color: OutputColor,
+) -> Result<()> {
+    let mut renderer = RaylibRender::new(project.width, project.height, project.bg)?;
+    let timeline = &project.timeline;
+    // Numbered from the first rendered frame, so ranges line up.
+    let first_frame = (start * timeline.fps as f32).round() as u32;
+    let mut frames = ImageSequenceEncoder::start_with_color(
+        project.width,
+        project.height,
+        timeline.fps,
+        dir,
+        format,
+        first_frame,
+        color,
+    )?;
+    renderer.render_timeline_rgba(timeline, start, end, |_t, rgba| frames.write_frame(rgba))?;
+    frames.finish()?;
+    println!("frames written to {}", dir.display());
+    Ok(())
+}
+
+// Renders the whole project as chunks kept in `dir`, and re-renders only the
+// chunks that the edits since the last render there reach. No manifest is
+// written, since `replay` encodes in one piece and can't match joined chunks.
+fn render_incremental(
+    dir: &Path,
+    project_path: &Path,
+    project: &Project,
+    output: &Path,
+    options: VideoOptions,
+    dither: Dither,
+) -> Result<()> {
+    let last_path = dir.join("last_render.json");
+    let mut incremental = IncrementalRender::new(dir).with_options(options);
+    if let Some(changed) = changed_since(&last_path, project_path, project)? {
+        incremental = incremental.with_changes(changed);
+    }
+    let mut renderer = RaylibRender::new(project.width, project.height, project.bg)?;
+    let timeline = &project.timeline;
+    let stats = incremental.render(
+        timeline,
+        project.width,
+        project.height,
+        project.bg,
+        output,
+        |start, end, encoder| {
+            encoder.set_dither(dither);
+            renderer
+                .render_timeline_rgba(timeline, start, end, |_t, rgba| encoder.write_frame(rgba))
+        },
+    )?;
+
+    // Kept as JSON, so `.scene` files are stored compiled.
+    let source = fs::read_to_string(project_path)?;
+    let source: Value = if project_path.extension().is_some_and(|ext| ext == "scene") {
+        compile_scene(&source)?.doc
+    } else {
+        serde_json::from_str(&source)?
+    };
+    let last = json!({
+        "project": source,
+        "width": project.width,
+        "height": project.height,
+        "fps": timeline.fps,
+        "duration": timeline.duration,
+    });
+    fs::write(&last_path, last.to_string())?;
+    println!("rendered {} of {} chunks", stats.rendered, stats.chunks);
+    Ok(())
+}
+
+// Ranges that changed since the last incremental render, or `None` if there
+// was none or it was at another size.
+fn changed_since(
+    last_path: &Path,
+    project_path: &Path,
+    project: &Project,
+) -> Result<Option<Vec<TimeRange>>> {
+    let Ok(last) = fs::read_to_string(last_path) else {
+        return Ok(None);
+    };
+    let last: Value = serde_json::from_str(&last)?;
+    let size = (last["width"].as_u64(), last["height"].as_u64());
+    if size != (Some(project.width as u64), Some(project.height as u64)) {
+        return Ok(None);
+    }
+    let base_dir = project_path.parent().unwrap_or(Path::new("."));
+    let mut previous =
+        Project::from_json_in(&last["project"].to_string(), base_dir, LoadMode::Strict)?;
+    // With the overrides it was rendered with, as `load_project` applies them.
+    if (previous.width, previous.height) != (project.width, project.height) {
+        previous = previous.with_output_size(project.width, project.height);
+    }
+    if let Some(fps) = last["fps"].as_u64() {
+        previous.timeline.fps = fps as u32;
+    }
+    if let Some(duration) = last["duration"].as_f64() {
+        previous.timeline.duration = duration as f32;
+    }
+    Ok(Some(project.timeline.changed_ranges(&previous.timeline)))
+}
+
+fn manifest_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".manifest.json");
+    PathBuf::from(path)
+}
+
+// `out.mp4.manifest.json` replays to `out.replay.mp4`.
+fn replay_path(manifest_path: &Path) -> PathBuf {
+    let output = manifest_path.with_extension("").with_extension("");
+    let stem = output
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "render".to_string());
+    let ext = output
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_else(|| "mp4".to_string());
+    output.with_file_name(format!("{stem}.replay.{ext}"))
+}
+
+// Fails on the first flag not in `known`.
+fn check_flags(args: &[String], known: &[&[&str]]) -> Result<()> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            continue;
+        }
+        if !known.iter().any(|flags| flags.contains(&arg.as_str())) {
+            bail!("unknown flag {arg}\n{USAGE}");
+        }
+        args.next();
+    }
+    Ok(())
+}
+
+// Arguments that are neither flags nor their values; every flag takes one.
+fn positionals(args: &[String]) -> Vec<&str> {
+    let mut positionals = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg.starts_with("--") {
+            args.next();
+        } else {
+            positionals.push(arg.as_str());
+        }
+    }
+    positionals
+}
+
+// Value of the flag `name`, which must be above zero when given.
+fn positive_flag<T: FromStr + PartialOrd + Default>(
+    args: &[String],
+    name: &str,
+) -> Result<Option<T>> {
+    let Some(value) = flag(args, name)? else {
+        return Ok(None);
+    };
+    match value.parse::<T>() {
+        Ok(parsed) if parsed > T::default() => Ok(Some(parsed)),
+        _ => {
+            bail!(ErrorKind::InvalidProject.error(format!("{name} must be above zero, got {value}")))
+        }
+    }
+}
+
+fn flag<'a>(args: &'a [String], name: &str) -> Result<Option<&'a str>> {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) => match args.get(index + 1) {
+            Some(value) => Ok(Some(value.as_str())),
+            None => bail!("{name} requires a value"),
+        },
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,58 @@
+use raylib::consts::BlendMode as RaylibBlendMode;
+
+pub(crate) use crate::backend::premultiply::{premultiply, unpremultiply};
+use crate::scene::Color;
+
+// Alpha policy. Every render target (the frame, compositions and isolated
+// layers) holds premultiplied colour, so targets can be filtered and drawn
+// into one another without dark fringes. Sources with straight alpha
+// (shapes, text, straight images) are drawn with `begin_straight`, which is
+// the default for every pass; premultiplied sources (targets, premultiplied
+// images) with `begin_premultiplied`. Frames leave the renderer as straight
+// RGBA (see `unpremultiply`), which is what effects and encoders expect.
+
+pub(crate) const GL_ZERO: i32 = 0;
+pub(crate) const GL_ONE: i32 = 1;
+pub(crate) const GL_ONE_MINUS_SRC_COLOR: i32 = 0x0301;
+pub(crate) const GL_SRC_ALPHA: i32 = 0x0302;
+pub(crate) const GL_ONE_MINUS_SRC_ALPHA: i32 = 0x0303;
+pub(crate) const GL_FUNC_ADD: i32 = 0x8006;
+pub(crate) const GL_FUNC_SUBTRACT: i32 = 0x800A;
+
+// Straight source over a premultiplied target. raylib's own BLEND_ALPHA
+// also multiplies the destination alpha by the source alpha, which leaves
+// transparent targets too dark once drawn.
+pub(crate) fn begin_straight() {
+    unsafe {
+        raylib::ffi::rlSetBlendFactorsSeparate(
+            GL_SRC_ALPHA,
+            GL_ONE_MINUS_SRC_ALPHA,
+            GL_ONE,
+            GL_ONE_MINUS_SRC_ALPHA,
+            GL_FUNC_ADD,
+            GL_FUNC_ADD,
+        );
+        raylib::ffi::BeginBlendMode(RaylibBlendMode::BLEND_CUSTOM_SEPARATE as i32);
+    }
+}
+
+// Premultiplied source over a premultiplied target. Go back with
+// `begin_straight` afterwards rather than `EndBlendMode`.
+pub(crate) fn begin_premultiplied() {
+    unsafe {
+        raylib::ffi::BeginBlendMode(RaylibBlendMode::BLEND_ALPHA_PREMULTIPLY as i32);
+    }
+}
+
+// Tint that draws a premultiplied texture at `opacity`; colour has to fade
+// along with alpha.
+pub(crate) fn premultiplied_tint(opacity: f32) -> raylib::prelude::Color {
+    let alpha = (opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+    raylib::prelude::Color::new(alpha, alpha, alpha, alpha)
+}
+
+// Clear colour for a premultiplied target.
+pub(crate) fn premultiplied_clear(color: Color) -> raylib::prelude::Color {
+    let scale = |channel: u8| (channel as u32 * color.a as u32 / 255) as u8;
+    raylib::prelude::Color::new(scale(color.r), scale(color.g), scale(color.b), color.a)
+}
@@ -0,0 +1,90 @@
+use crate::backend::hit_regions::HitRegion;
+use crate::scene::Vec2;
+use crate::timeline::{Assertion, AssertionRule};
+
+// Slack for antialiasing and float error, in pixels.
+const TOLERANCE: f32 = 0.5;
+
+// Why `assertion` failed at one frame, given the frame's hit regions on a
+// `width` x `height` output, or `None` if it holds. Every region of the
+// clip counts, in case more than one clip shares the name.
+pub fn check_assertion(
+    assertion: &Assertion,
+    regions: &[HitRegion],
+    width: u32,
+    height: u32,
+) -> Option<String> {
+    let clip = assertion.rule.clip();
+    let mut polygons = regions
+        .iter()
+        .filter(|region| region.id == clip)
+        .map(|region| &region.polygon);
+    match &assertion.rule {
+        AssertionRule::InsideFrame { .. } => {
+            let (width, height) = (width as f32, height as f32);
+            let outside = polygons.any(|polygon| {
+                polygon.iter().any(|point| {
+                    point.x < -TOLERANCE
+                        || point.y < -TOLERANCE
+                        || point.x > width + TOLERANCE
+                        || point.y > height + TOLERANCE
+                })
+            });
+            outside.then(|| format!("clip \"{clip}\" extends outside the frame"))
+        }
+        AssertionRule::AvoidsRegion { center, size, .. } => {
+            // The region in output pixels, y down, like the polygons.
+            let min = Vec2 {
+                x: width as f32 / 2.0 + center.x - size.x / 2.0,
+                y: height as f32 / 2.0 - center.y - size.y / 2.0,
+            };
+            let max = Vec2 {
+                x: min.x + size.x,
+                y: min.y + size.y,
+            };
+            let overlaps = polygons.any(|polygon| overlaps_box(polygon, min, max));
+            overlaps.then(|| {
+                format!(
+                    "clip \"{clip}\" overlaps the region at ({}, {}) sized {}x{}",
+                    center.x, center.y, size.x, size.y
+                )
+            })
+        }
+    }
+}
+
+// Separating-axis test; hit region outlines are convex.
+fn overlaps_box(polygon: &[Vec2], min: Vec2, max: Vec2) -> bool {
+    if polygon.is_empty() {
+        return false;
+    }
+    let corners = [
+        min,
+        Vec2 { x: max.x, y: min.y },
+        max,
+        Vec2 { x: min.x, y: max.y },
+    ];
+    let edges = polygon.iter().zip(polygon.iter().cycle().skip(1));
+    let normals = edges
+        .map(|(a, b)| Vec2 {
+            x: a.y - b.y,
+            y: b.x - a.x,
+        })
+        .chain([Vec2 { x: 1.0, y: 0.0 }, Vec2 { x: 0.0, y: 1.0 }]);
+    let project = |points: &[Vec2], axis: Vec2| {
+        points.iter().fold((f32::MAX, f32::MIN), |(low, high), point| {
+            let value = point.x * axis.x + point.y * axis.y;
+            (low.min(value), high.max(value))
+        })
+    };
+    for axis in normals {
+        let (a_low, a_high) = project(polygon, axis);
+        let (b_low, b_high) = project(&corners, axis);
+        // Touching edges don't count as overlapping.
+        let tolerance = TOLERANCE * (axis.x * axis.x + axis.y * axis.y).sqrt();
+        if a_high <= b_low + tolerance || b_high <= a_low + tolerance {
+            return false;
+        }
+    }
+    true
+}
@@ -0,0 +1,31 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// Image and font files held in memory, under the path a project refers to
+// them by, for the CPU renderers where there are no files to read (wasm32 in
+// a browser). Paths that aren't registered are read from disk.
+#[derive(Debug, Clone, Default)]
+pub struct AssetBytes {
+    files: HashMap<PathBuf, Arc<[u8]>>,
+}
+
+impl AssetBytes {
+    // Replaces any bytes already registered under `path`.
+    pub fn insert(&mut self, path: impl Into<PathBuf>, bytes: impl Into<Arc<[u8]>>) {
+        self.files.insert(path.into(), bytes.into());
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    // The registered bytes, else the file's, or none if neither exists.
+    pub fn read(&self, path: &Path) -> Option<Arc<[u8]>> {
+        match self.files.get(path) {
+            Some(bytes) => Some(bytes.clone()),
+            None => fs::read(path).ok().map(Arc::from),
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// Where the render and preview loops get "now" from. Each call to `tick`
+// advances to the next time to draw; the loop ends when it returns `None`.
+pub trait Clock {
+    fn tick(&mut self) -> Option<f32>;
+
+    // Frames `tick` will still return, if known up front (used for progress).
+    fn frame_count(&self) -> Option<u32> {
+        None
+    }
+
+    // Scrubbing requests from the preview window. Clocks driven from outside
+    // ignore them.
+    fn seek(&mut self, _time: f32) {}
+
+    fn set_paused(&mut self, _paused: bool) {}
+
+    fn is_paused(&self) -> bool {
+        false
+    }
+}
+
+// Offline rendering: time comes from the frame index, so every frame between
+// `start` and `end` is drawn exactly once however long it takes.
+pub struct FrameClock {
+    fps: u32,
+    start: f32,
+    frames: u32,
+    next: u32,
+}
+
+impl FrameClock {
+    pub fn new(fps: u32, start: f32, end: f32) -> Self {
+        Self {
+            fps,
+            start,
+            frames: ((end - start) * fps as f32).floor().max(0.0) as u32,
+            next: 0,
+        }
+    }
+}
+
+impl Clock for FrameClock {
+    fn tick(&mut self) -> Option<f32> {
+        if self.next >= self.frames {
+            return None;
+        }
+        let t = self.start + self.next as f32 / self.fps as f32;
+        self.next += 1;
+        Some(t)
+    }
+
+    fn frame_count(&self) -> Option<u32> {
+        Some(self.frames - self.next)
+    }
+}
+
+// Preview: follows the wall clock from `start`, so a slow frame is skipped
+// rather than slowing playback down. Supports pausing and seeking.
+pub struct WallClock {
+    start: f32,
+    end: f32,
+    // Timeline time at `anchor`; playback runs on from there.
+    anchor_time: f32,
+    anchor: Option<Instant>,
+    paused: bool,
+}
+
+impl WallClock {
+    pub fn new(start: f32, end: f32) -> Self {
+        Self {
+            start,
+            end,
+            anchor_time: start,
+            anchor: None,
+            paused: false,
+        }
+    }
+
+    fn now(&self) -> f32 {
+        match self.anchor {
+            Some(anchor) if !self.paused => self.anchor_time + anchor.elapsed().as_secs_f32(),
+            _ => self.anchor_time,
+        }
+    }
+}
+
+impl Clock for WallClock {
+    fn tick(&mut self) -> Option<f32> {
+        // Starts counting at the first frame, not at construction.
+        if self.anchor.is_none() {
+            self.anchor = Some(Instant::now());
+        }
+        let t = self.now();
+        (t < self.end).then_some(t)
+    }
+
+    fn seek(&mut self, time: f32) {
+        // Never seeks onto `end`, which would stop playback.
+        let last = (self.end - 1e-3).max(self.start);
+        self.anchor_time = time.clamp(self.start, last);
+        self.anchor = Some(Instant::now());
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        if paused != self.paused {
+            self.anchor_time = self.now();
+            self.anchor = Some(Instant::now());
+            self.paused = paused;
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+// External sync: time is pushed from another thread, e.g. one decoding LTC
+// audio or MIDI timecode, through the `ExternalTime` handle. `tick` returns
+// the latest time received (0 until the first), so a loop running faster
+// than the source redraws the same time.
+pub struct ExternalClock {
+    shared: Arc<Mutex<ExternalState>>,
+}
+
+#[derive(Clone)]
+pub struct ExternalTime {
+    shared: Arc<Mutex<ExternalState>>,
+    // Timeline time = timecode - offset, e.g. 3600 for sources starting at
+    // 01:00:00:00.
+    offset: f32,
+}
+
+#[derive(Default)]
+struct ExternalState {
+    time: f32,
+    stopped: bool,
+}
+
+impl ExternalClock {
+    pub fn new() -> (Self, ExternalTime) {
+        let shared = Arc::new(Mutex::new(ExternalState::default()));
+        let handle = ExternalTime {
+            shared: Arc::clone(&shared),
+            offset: 0.0,
+        };
+        (Self { shared }, handle)
+    }
+}
+
+impl Clock for ExternalClock {
+    fn tick(&mut self) -> Option<f32> {
+        let state = self.shared.lock().expect("clock lock");
+        if state.stopped {
+            return None;
+        }
+        Some(state.time)
+    }
+}
+
+impl ExternalTime {
+    pub fn with_offset(mut self, offset: f32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn set(&self, seconds: f32) {
+        self.shared.lock().expect("clock lock").time = (seconds - self.offset).max(0.0);
+    }
+
+    // SMPTE `hours:minutes:seconds:frames` at `rate` frames per second.
+    // Drop-frame counting is not handled.
+    pub fn set_timecode(&self, hours: u32, minutes: u32, seconds: u32, frames: u32, rate: f32) {
+        let whole = hours * 3600 + minutes * 60 + seconds;
+        self.set(whole as f32 + frames as f32 / rate);
+    }
+
+    // Ends the loop at its next tick.
+    pub fn stop(&self) {
+        self.shared.lock().expect("clock lock").stopped = true;
+    }
+}
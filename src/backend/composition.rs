@@ -0,0 +1,152 @@
+use std::ffi::c_void;
+
+use anyhow::Result;
+use raylib::prelude::*;
+
+use crate::backend::alpha::{
+    begin_premultiplied, begin_straight, premultiplied_clear, premultiplied_tint, premultiply,
+    unpremultiply,
+};
+use crate::backend::layers::{draw_layers, render_layer_targets};
+use crate::backend::plugin_canvas::apply_effects;
+use crate::backend::raylib_render::{capture_rgba, downsample, upsample, ScaledDraw};
+use crate::backend::resources::ResourceCache;
+use crate::backend::text_render::render_text_targets;
+use crate::scene::{Composition, Object, Transform, Vec2};
+use crate::timeline::SampledScene;
+
+// raylib texture modes don't nest, so every composition in the scene tree is
+// drawn into its own target (deepest first) before the outer frame begins.
+// Outlined and plated text, which compositions may hold, goes first.
+// `width` x `height` is the frame `scene` is drawn into.
+pub fn render_compositions(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    cache: &mut ResourceCache,
+    scene: &SampledScene,
+    width: u32,
+    height: u32,
+) -> Result<()> {
+    cache.begin_offscreen_frame();
+    render_text_targets(rl, thread, cache, scene, width, height)?;
+    render_nested(rl, thread, cache, scene)
+}
+
+fn render_nested(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    cache: &mut ResourceCache,
+    scene: &SampledScene,
+) -> Result<()> {
+    for layer in &scene.layers {
+        for clip in &layer.clips {
+            let (Object::Composition(composition), Some(nested)) = (&clip.object, &clip.nested)
+            else {
+                continue;
+            };
+            render_nested(rl, thread, cache, nested)?;
+            render_layer_targets(rl, thread, cache, nested, composition.width, composition.height)?;
+
+            let (slot, mut target) = cache.take_offscreen_target(
+                rl,
+                thread,
+                scene_key(nested),
+                composition.width,
+                composition.height,
+            )?;
+            let result =
+                draw_composition_scene(rl, thread, cache, &mut target, composition, nested);
+            cache.return_offscreen_target(slot, target);
+            result?;
+        }
+    }
+    Ok(())
+}
+
+fn draw_composition_scene(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    cache: &ResourceCache,
+    target: &mut RenderTexture2D,
+    composition: &Composition,
+    scene: &SampledScene,
+) -> Result<()> {
+    {
+        let mut d = rl.begin_texture_mode(thread, target.as_mut());
+        let _scaled = ScaledDraw::begin(cache.draw_scale());
+        d.clear_background(premultiplied_clear(composition.bg));
+        begin_straight();
+        draw_layers(&mut d, cache, composition.width, composition.height, scene)?;
+    }
+    if scene.effects.is_empty() {
+        return Ok(());
+    }
+    // The nested timeline's effects, run over its frame as for groups.
+    let scale = cache.render_scale();
+    let (width, height) = cache.scaled_size(composition.width, composition.height);
+    let mut rgba = capture_rgba(target, width * scale, height * scale)?;
+    if scale > 1 {
+        rgba = downsample(&rgba, width, height, scale);
+    }
+    unpremultiply(&mut rgba);
+    apply_effects(
+        cache.plugins()?,
+        &scene.effects,
+        scene.time,
+        &mut rgba,
+        width,
+        height,
+    )?;
+    premultiply(&mut rgba);
+    if scale > 1 {
+        rgba = upsample(&rgba, width, height, scale);
+    }
+    unsafe {
+        raylib::ffi::UpdateTexture(*target.texture().as_ref(), rgba.as_ptr() as *const c_void);
+    }
+    Ok(())
+}
+
+pub fn draw_composition(
+    d: &mut impl RaylibDraw,
+    cache: &ResourceCache,
+    width: u32,
+    height: u32,
+    nested: &SampledScene,
+    transform: &Transform,
+) -> Result<()> {
+    let target = cache.offscreen_texture(scene_key(nested))?;
+    let tex_w = target.texture().width as f32;
+    let tex_h = target.texture().height as f32;
+
+    // Targets are at the draw scale; the composition covers its own size.
+    let scale = cache.draw_scale();
+    let w = tex_w / scale * transform.scale.x;
+    let h = tex_h / scale * transform.scale.y;
+    let center = graph_to_screen(transform.pos, width, height);
+
+    // Render textures are stored bottom-up; a negative source height flips them.
+    let source = Rectangle::new(0.0, 0.0, tex_w, -tex_h);
+    let dest = Rectangle::new(center.x, center.y, w, h);
+    let origin = Vector2::new(w / 2.0, h / 2.0);
+
+    begin_premultiplied();
+    d.draw_texture_pro(
+        target.texture(),
+        source,
+        dest,
+        origin,
+        transform.rotation,
+        premultiplied_tint(transform.opacity),
+    );
+    begin_straight();
+    Ok(())
+}
+
+fn scene_key(scene: &SampledScene) -> *const () {
+    scene as *const SampledScene as *const ()
+}
+
+fn graph_to_screen(pos: Vec2, width: u32, height: u32) -> Vector2 {
+    Vector2::new(width as f32 / 2.0 + pos.x, height as f32 / 2.0 - pos.y)
+}
@@ -0,0 +1,183 @@
+use std::ffi::CString;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use crate::backend::raylib_render::{export_rgba, RaylibRender};
+use crate::error::ErrorKind;
+use crate::scene::Color;
+use crate::timeline::Timeline;
+
+// Room under each frame for its timecode, in raylib's default font.
+const LABEL_HEIGHT: u32 = 20;
+const LABEL_SIZE: i32 = 10;
+
+// Evenly spaced frames from a timeline tiled into one PNG or JPEG, each
+// labelled with its timecode, for reviewing a long render at a glance
+// instead of watching it. The first and last frames are always on it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContactSheet {
+    pub frames: u32,
+    pub columns: u32,
+    // Width of each frame on the sheet; heights keep the render's aspect.
+    pub thumb_width: u32,
+    // Space between and around the frames.
+    pub gap: u32,
+    pub background: Color,
+}
+
+impl ContactSheet {
+    pub fn new(frames: u32) -> Self {
+        Self {
+            frames,
+            columns: 4,
+            thumb_width: 320,
+            gap: 8,
+            background: Color::rgb(24, 24, 28),
+        }
+    }
+
+    pub fn with_columns(mut self, columns: u32) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    pub fn with_thumb_width(mut self, thumb_width: u32) -> Self {
+        self.thumb_width = thumb_width;
+        self
+    }
+
+    pub fn with_gap(mut self, gap: u32) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    pub fn with_background(mut self, background: Color) -> Self {
+        self.background = background;
+        self
+    }
+
+    // Timeline frame numbers on the sheet, in order. Fewer than `frames`
+    // when the timeline is shorter than that.
+    pub fn frame_numbers(&self, timeline: &Timeline) -> Vec<u32> {
+        let total = (timeline.duration * timeline.fps as f32).floor().max(1.0) as u32;
+        let count = self.frames.min(total);
+        if count <= 1 {
+            return vec![0; count as usize];
+        }
+        (0..count)
+            .map(|i| (i as f64 * (total - 1) as f64 / (count - 1) as f64).round() as u32)
+            .collect()
+    }
+
+    pub fn render(
+        &self,
+        renderer: &mut RaylibRender,
+        timeline: &Timeline,
+        path: &Path,
+    ) -> Result<()> {
+        if self.frames == 0 || self.columns == 0 || self.thumb_width == 0 {
+            bail!(ErrorKind::InvalidProject
+                .error("contact sheet frames, columns and thumb width must be > 0"));
+        }
+        let (width, height) = renderer.output_size();
+        let thumb_w = self.thumb_width;
+        let thumb_h = ((thumb_w as f32 * height as f32 / width as f32).round() as u32).max(1);
+        let frames = self.frame_numbers(timeline);
+        let columns = self.columns.min(frames.len() as u32);
+        let rows = (frames.len() as u32).div_ceil(columns);
+        let cell_w = thumb_w + self.gap;
+        let cell_h = thumb_h + LABEL_HEIGHT + self.gap;
+        let sheet_w = self.gap + columns * cell_w;
+        let sheet_h = self.gap + rows * cell_h;
+
+        let bg = [self.background.r, self.background.g, self.background.b, 255];
+        let mut sheet = bg.repeat((sheet_w * sheet_h) as usize);
+        let mut labels = Vec::with_capacity(frames.len());
+        for (i, &frame) in frames.iter().enumerate() {
+            let rgba = renderer.render_frame_rgba(timeline, frame as f32 / timeline.fps as f32)?;
+            let (column, row) = (i as u32 % columns, i as u32 / columns);
+            let (x, y) = (self.gap + column * cell_w, self.gap + row * cell_h);
+            let thumb = downscale(&rgba, width, height, thumb_w, thumb_h);
+            for (ty, line) in thumb.chunks_exact(thumb_w as usize * 4).enumerate() {
+                let start = (((y + ty as u32) * sheet_w + x) * 4) as usize;
+                let dst = &mut sheet[start..start + line.len()];
+                // Flattened onto the background, so transparency reads as it.
+                for (pixel, src) in dst.chunks_exact_mut(4).zip(line.chunks_exact(4)) {
+                    let alpha = src[3] as u32;
+                    for ((out, &value), &back) in pixel.iter_mut().zip(src).zip(&bg).take(3) {
+                        *out = ((value as u32 * alpha + back as u32 * (255 - alpha) + 127) / 255)
+                            as u8;
+                    }
+                }
+            }
+            labels.push((timecode(frame, timeline.fps), x, y + thumb_h + 5));
+        }
+
+        export_rgba(&mut sheet, sheet_w, sheet_h, path, |image| {
+            let color = raylib::ffi::Color {
+                r: 220,
+                g: 220,
+                b: 220,
+                a: 255,
+            };
+            for (text, x, y) in &labels {
+                let Ok(text) = CString::new(text.as_str()) else { continue };
+                unsafe {
+                    raylib::ffi::ImageDrawText(
+                        image,
+                        text.as_ptr(),
+                        *x as i32,
+                        *y as i32,
+                        LABEL_SIZE,
+                        color,
+                    );
+                }
+            }
+        })
+    }
+}
+
+// Bottom-up RGBA to a top-down `to_w` x `to_h` copy, each pixel the average
+// of the source pixels it covers.
+fn downscale(rgba: &[u8], width: u32, height: u32, to_w: u32, to_h: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity((to_w * to_h * 4) as usize);
+    let span = |i: u32, from: u32, to: u32| {
+        let start = (i as u64 * from as u64 / to as u64) as u32;
+        let end = (((i + 1) as u64 * from as u64).div_ceil(to as u64) as u32).max(start + 1);
+        start..end.min(from)
+    };
+    for oy in 0..to_h {
+        let ys = span(oy, height, to_h);
+        for ox in 0..to_w {
+            let xs = span(ox, width, to_w);
+            let mut sum = [0u32; 4];
+            let mut count = 0;
+            for sy in ys.clone() {
+                let row = (height - 1 - sy) * width;
+                for sx in xs.clone() {
+                    let at = ((row + sx) * 4) as usize;
+                    for (total, &value) in sum.iter_mut().zip(&rgba[at..at + 4]) {
+                        *total += value as u32;
+                    }
+                    count += 1;
+                }
+            }
+            out.extend(sum.map(|total| ((total + count / 2) / count.max(1)) as u8));
+        }
+    }
+    out
+}
+
+// `HH:MM:SS:FF` for frame `frame` at `fps`.
+pub(crate) fn timecode(frame: u32, fps: u32) -> String {
+    let fps = fps.max(1);
+    let seconds = frame / fps;
+    format!(
+        "{:02}:{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        frame % fps
+    )
+}
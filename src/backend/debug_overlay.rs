@@ -0,0 +1,162 @@
+use anyhow::Result;
+use raylib::prelude::*;
+
+use crate::backend::contact_sheet::timecode;
+use crate::backend::hit_regions::ClipOutline;
+use crate::backend::resources::{measure_text, ResourceCache};
+use crate::scene::{FontFamily, StyleFlags, Vec2};
+
+// Parts of the frame inside the action- and title-safe areas (SMPTE ST
+// 2046-1).
+const ACTION_SAFE: f32 = 0.93;
+const TITLE_SAFE: f32 = 0.90;
+// Overlay text, in raylib's default font and scene pixels.
+const TEXT_SIZE: f32 = 20.0;
+const PADDING: f32 = 6.0;
+
+const INFO_COLOR: Color = Color::new(235, 235, 240, 255);
+const PLATE_COLOR: Color = Color::new(0, 0, 0, 170);
+const ACTION_SAFE_COLOR: Color = Color::new(80, 200, 255, 200);
+const TITLE_SAFE_COLOR: Color = Color::new(255, 210, 60, 200);
+const BOUNDS_COLOR: Color = Color::new(60, 255, 120, 230);
+const OFFSCREEN_COLOR: Color = Color::new(255, 80, 80, 255);
+
+// What `RaylibRender::set_debug_options` draws over every frame, for
+// finding out why something is where it is at a given time. All off by
+// default; the overlay ends up in the output, so it's for diagnosis only.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DebugOptions {
+    // Frame number, timecode and how many clips the frame draws, top left.
+    pub frame_info: bool,
+    // The action- and title-safe rectangles and a centre mark.
+    pub safe_areas: bool,
+    // An outline around every clip, labelled with its name. Clips entirely
+    // outside the frame are labelled at the nearest edge instead.
+    pub bounds: bool,
+}
+
+impl DebugOptions {
+    pub fn all() -> Self {
+        Self {
+            frame_info: true,
+            safe_areas: true,
+            bounds: true,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.frame_info || self.safe_areas || self.bounds
+    }
+}
+
+// What the overlay says about the frame it's drawn over.
+pub(crate) struct DebugFrame<'a> {
+    pub(crate) time: f32,
+    // The timeline's, when the frame came from one, to number frames.
+    pub(crate) fps: Option<u32>,
+    pub(crate) clips: usize,
+    // Empty unless `bounds` is on.
+    pub(crate) outlines: &'a [ClipOutline],
+}
+
+// Draws the overlay in scene pixels on a `width` x `height` frame.
+pub(crate) fn draw_debug_overlay(
+    d: &mut impl RaylibDraw,
+    cache: &ResourceCache,
+    options: DebugOptions,
+    frame: &DebugFrame,
+    width: u32,
+    height: u32,
+) -> Result<()> {
+    let (w, h) = (width as f32, height as f32);
+    let font = cache.resolve_font(&FontFamily::default(), StyleFlags::PLAIN)?;
+    // raylib spaces its default font by a tenth of the size.
+    let text_width = |text: &str| measure_text(font, text, TEXT_SIZE, TEXT_SIZE / 10.0);
+
+    if options.safe_areas {
+        let areas = [
+            (ACTION_SAFE, ACTION_SAFE_COLOR),
+            (TITLE_SAFE, TITLE_SAFE_COLOR),
+        ];
+        for (part, color) in areas {
+            let (inset_x, inset_y) = (w * (1.0 - part) / 2.0, h * (1.0 - part) / 2.0);
+            let area = Rectangle::new(inset_x, inset_y, w - inset_x * 2.0, h - inset_y * 2.0);
+            d.draw_rectangle_lines_ex(area, 2.0, color);
+        }
+        let (cx, cy, arm) = (w / 2.0, h / 2.0, h / 40.0);
+        for (dx, dy) in [(arm, 0.0), (0.0, arm)] {
+            let start = Vector2::new(cx - dx, cy - dy);
+            let end = Vector2::new(cx + dx, cy + dy);
+            d.draw_line_ex(start, end, 2.0, TITLE_SAFE_COLOR);
+        }
+    }
+
+    let mut offscreen = 0;
+    if options.bounds {
+        for outline in frame.outlines {
+            let Some((min, max)) = bounding_box(&outline.polygon) else {
+                continue;
+            };
+            let name = outline.name.as_deref();
+            if max.x < 0.0 || max.y < 0.0 || min.x > w || min.y > h {
+                offscreen += 1;
+                let text = format!("{} (off-screen)", name.unwrap_or("unnamed clip"));
+                let x = min.x.min(w - text_width(&text) - PADDING * 2.0);
+                let y = min.y.min(h - TEXT_SIZE - PADDING * 2.0);
+                draw_label(d, &text, text_width(&text), x, y, OFFSCREEN_COLOR);
+                continue;
+            }
+            let points = &outline.polygon;
+            for (a, b) in points.iter().zip(points.iter().cycle().skip(1)) {
+                let (start, end) = (Vector2::new(a.x, a.y), Vector2::new(b.x, b.y));
+                d.draw_line_ex(start, end, 2.0, BOUNDS_COLOR);
+            }
+            if let Some(name) = name {
+                let y = min.y - TEXT_SIZE - PADDING * 2.0;
+                draw_label(d, name, text_width(name), min.x, y, BOUNDS_COLOR);
+            }
+        }
+    }
+
+    if options.frame_info {
+        let mut text = match frame.fps {
+            Some(fps) => {
+                let number = (frame.time * fps as f32).round() as u32;
+                format!("frame {number}  {}", timecode(number, fps))
+            }
+            None => format!("{:.3}s", frame.time),
+        };
+        text.push_str(&format!("  clips {}", frame.clips));
+        if offscreen > 0 {
+            text.push_str(&format!("  off-screen {offscreen}"));
+        }
+        draw_label(d, &text, text_width(&text), PADDING, PADDING, INFO_COLOR);
+    }
+    Ok(())
+}
+
+// `text` on a dark plate with its top-left corner at `x`, `y`, kept off the
+// frame's top and left edges.
+fn draw_label(d: &mut impl RaylibDraw, text: &str, width: f32, x: f32, y: f32, color: Color) {
+    let (x, y) = (x.max(0.0), y.max(0.0));
+    let plate = Rectangle::new(x, y, width + PADDING * 2.0, TEXT_SIZE + PADDING * 2.0);
+    d.draw_rectangle_rec(plate, PLATE_COLOR);
+    let (x, y) = ((x + PADDING).round() as i32, (y + PADDING).round() as i32);
+    d.draw_text(text, x, y, TEXT_SIZE as i32, color);
+}
+
+fn bounding_box(points: &[Vec2]) -> Option<(Vec2, Vec2)> {
+    let first = *points.first()?;
+    Some(points.iter().fold((first, first), |(min, max), point| {
+        (
+            Vec2 {
+                x: min.x.min(point.x),
+                y: min.y.min(point.y),
+            },
+            Vec2 {
+                x: max.x.max(point.x),
+                y: max.y.max(point.y),
+            },
+        )
+    }))
+}
@@ -0,0 +1,59 @@
+// EXIF orientation (1..=8) from the start of a JPEG file, or `None` when the
+// file is not a JPEG or carries no orientation tag.
+pub fn jpeg_orientation(data: &[u8]) -> Option<u16> {
+    let mut rest = data.strip_prefix(&[0xFF, 0xD8])?;
+    // Walk the marker segments up to the image data.
+    while let [0xFF, marker, high, low, ..] = *rest {
+        let len = u16::from_be_bytes([high, low]) as usize;
+        let segment = rest.get(4..2 + len)?;
+        if marker == 0xE1
+            && let Some(tiff) = segment.strip_prefix(b"Exif\0\0")
+        {
+            return tiff_orientation(tiff);
+        }
+        if marker == 0xDA {
+            return None;
+        }
+        rest = &rest[2 + len..];
+    }
+    None
+}
+
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| {
+        let bytes = [*tiff.get(at)?, *tiff.get(at + 1)?];
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let u32_at = |at: usize| {
+        let bytes = [
+            *tiff.get(at)?,
+            *tiff.get(at + 1)?,
+            *tiff.get(at + 2)?,
+            *tiff.get(at + 3)?,
+        ];
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    for entry in 0..u16_at(ifd)? as usize {
+        let at = ifd + 2 + entry * 12;
+        // Tag 0x0112, a SHORT stored inline in the value field.
+        if u16_at(at)? == 0x0112 {
+            return u16_at(at + 8).filter(|value| (1..=8).contains(value));
+        }
+    }
+    None
+}
@@ -0,0 +1,200 @@
+use std::cmp::Ordering;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+// Codepoint coverage read from a TrueType/OpenType `cmap` table. Used to pick
+// the first font in a fallback chain that actually has a glyph for a char.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontCoverage {
+    ranges: Vec<(u32, u32)>,
+}
+
+impl FontCoverage {
+    pub fn full() -> Self {
+        Self {
+            ranges: vec![(0, char::MAX as u32)],
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)
+            .with_context(|| format!("failed to read font: {}", path.display()))?;
+        Self::from_bytes(&data)
+            .with_context(|| format!("failed to parse font cmap: {}", path.display()))
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut font_offset = 0usize;
+        if data.get(0..4) == Some(b"ttcf".as_slice()) {
+            font_offset = read_u32(data, 12)? as usize;
+        }
+
+        let num_tables = read_u16(data, font_offset + 4)? as usize;
+        let mut cmap = None;
+        for i in 0..num_tables {
+            let record = font_offset + 12 + i * 16;
+            if data.get(record..record + 4) == Some(b"cmap".as_slice()) {
+                cmap = Some(read_u32(data, record + 8)? as usize);
+                break;
+            }
+        }
+        let cmap = cmap.context("font has no cmap table")?;
+
+        let count = read_u16(data, cmap + 2)? as usize;
+        let mut best: Option<(u8, usize)> = None;
+        for i in 0..count {
+            let record = cmap + 4 + i * 8;
+            let platform = read_u16(data, record)?;
+            let encoding = read_u16(data, record + 2)?;
+            let offset = cmap + read_u32(data, record + 4)? as usize;
+            let unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+            if !unicode {
+                continue;
+            }
+            let priority = match read_u16(data, offset)? {
+                12 => 2,
+                4 => 1,
+                _ => continue,
+            };
+            if best.is_none_or(|(p, _)| priority > p) {
+                best = Some((priority, offset));
+            }
+        }
+        let (_, offset) = best.context("font has no unicode cmap subtable")?;
+
+        let mut ranges = match read_u16(data, offset)? {
+            12 => parse_format12(data, offset)?,
+            _ => parse_format4(data, offset)?,
+        };
+        ranges.sort_unstable();
+        Ok(Self { ranges })
+    }
+
+    pub fn contains(&self, ch: char) -> bool {
+        let c = ch as u32;
+        self.ranges
+            .binary_search_by(|&(start, end)| {
+                if end < c {
+                    Ordering::Less
+                } else if start > c {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+}
+
+// Codepoints count as covered only if they map to a real glyph: segments
+// may map some or all of their codes to glyph 0, the missing glyph.
+fn parse_format4(data: &[u8], offset: usize) -> Result<Vec<(u32, u32)>> {
+    let seg_count = read_u16(data, offset + 6)? as usize / 2;
+    let end_codes = offset + 14;
+    let start_codes = end_codes + seg_count * 2 + 2;
+    let id_deltas = start_codes + seg_count * 2;
+    let id_range_offsets = id_deltas + seg_count * 2;
+
+    let mut ranges = Vec::with_capacity(seg_count);
+    for i in 0..seg_count {
+        let end = read_u16(data, end_codes + i * 2)? as u32;
+        let start = read_u16(data, start_codes + i * 2)? as u32;
+        // The final 0xFFFF segment is a required terminator, not a glyph.
+        if start == 0xFFFF || start > end {
+            continue;
+        }
+        let id_delta = read_u16(data, id_deltas + i * 2)?;
+        let range_offset_at = id_range_offsets + i * 2;
+        let range_offset = read_u16(data, range_offset_at)? as usize;
+        for code in start..=end {
+            let glyph = if range_offset == 0 {
+                (code as u16).wrapping_add(id_delta)
+            } else {
+                // Offsets count from the segment's own idRangeOffset entry.
+                let at = range_offset_at + range_offset + (code - start) as usize * 2;
+                match read_u16(data, at)? {
+                    0 => 0,
+                    glyph => glyph.wrapping_add(id_delta),
+                }
+            };
+            if glyph != 0 {
+                push_code(&mut ranges, code);
+            }
+        }
+    }
+    Ok(ranges)
+}
+
+fn parse_format12(data: &[u8], offset: usize) -> Result<Vec<(u32, u32)>> {
+    let groups = read_u32(data, offset + 12)? as usize;
+    let mut ranges = Vec::with_capacity(groups);
+    for i in 0..groups {
+        let group = offset + 16 + i * 12;
+        let mut start = read_u32(data, group)?;
+        let end = read_u32(data, group + 4)?;
+        // Glyphs run up from the start glyph, so only the first can be 0.
+        if read_u32(data, group + 8)? == 0 {
+            start = start.saturating_add(1);
+        }
+        if start <= end {
+            ranges.push((start, end));
+        }
+    }
+    Ok(ranges)
+}
+
+// Adds `code` to the last range if it follows on from it.
+fn push_code(ranges: &mut Vec<(u32, u32)>, code: u32) {
+    match ranges.last_mut() {
+        Some((_, end)) if *end + 1 == code => *end = code,
+        _ => ranges.push((code, code)),
+    }
+}
+
+fn read_u16(data: &[u8], at: usize) -> Result<u16> {
+    let bytes = data.get(at..at + 2).context("unexpected end of font data")?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32> {
+    let bytes = data.get(at..at + 4).context("unexpected end of font data")?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A font holding only a format 4 cmap with three segments:
+    // '@'..='C' by idDelta, where '@' lands on glyph 0; 'a'..='c' through
+    // the glyph array, where 'b' is glyph 0; and the 0xFFFF terminator.
+    fn format4_font() -> Vec<u8> {
+        let words = |values: &[u16]| -> Vec<u8> {
+            values.iter().flat_map(|value| value.to_be_bytes()).collect()
+        };
+        let mut subtable = words(&[4, 0, 0, 6, 4, 1, 2]);
+        subtable.extend(words(&[0x43, 0x63, 0xFFFF])); // endCode
+        subtable.extend(words(&[0])); // reservedPad
+        subtable.extend(words(&[0x40, 0x61, 0xFFFF])); // startCode
+        subtable.extend(words(&[0x40u16.wrapping_neg(), 0, 1])); // idDelta
+        subtable.extend(words(&[0, 4, 0])); // idRangeOffset
+        subtable.extend(words(&[5, 0, 6])); // glyphIdArray
+        let length = subtable.len() as u16;
+        subtable[2..4].copy_from_slice(&length.to_be_bytes());
+
+        let mut font = words(&[0, 1, 1, 16, 0, 0]);
+        font.extend(b"cmap");
+        font.extend(words(&[0, 0, 0, 28, 0, 0]));
+        font.extend(words(&[0, 1, 3, 1, 0, 12]));
+        font.extend(subtable);
+        font
+    }
+
+    #[test]
+    fn format4_codes_mapping_to_glyph_0_are_missing() {
+        let coverage = FontCoverage::from_bytes(&format4_font()).unwrap();
+        let covered: String = ('0'..='z').filter(|&ch| coverage.contains(ch)).collect();
+        assert_eq!(covered, "ABCac");
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+
+use crate::error::ErrorKind;
+use crate::timeline::diff::Fnv1a;
+
+// A hash of every frame of a render, for catching visual regressions: record
+// one from a render that looks right, then check later renders against it
+// with `GoldenCheck`. Hashes are exact, so a golden only holds for the
+// backend and machine it was recorded on; `SoftwareRender` draws the same
+// pixels everywhere.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameHashes {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    // By timeline frame index.
+    pub frames: BTreeMap<u32, String>,
+}
+
+impl FrameHashes {
+    pub fn new(width: u32, height: u32, fps: u32) -> Self {
+        Self {
+            width,
+            height,
+            fps,
+            frames: BTreeMap::new(),
+        }
+    }
+
+    // Records the frame at `t` seconds, as handed to `on_frame`.
+    pub fn push(&mut self, t: f32, rgba: &[u8]) {
+        self.frames.insert(frame_index(t, self.fps), frame_hash(rgba));
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = fs::read_to_string(path).with_context(|| {
+            ErrorKind::MissingAsset
+                .error(format!("failed to read frame hashes: {}", path.display()))
+        })?;
+        Self::from_json(&source).with_context(|| format!("failed to load {}", path.display()))
+    }
+
+    pub fn from_json(source: &str) -> Result<Self> {
+        let invalid = |what: &str| ErrorKind::InvalidProject.error(format!("frame hashes: {what}"));
+        let doc: Value = serde_json::from_str(source).map_err(|err| invalid(&err.to_string()))?;
+        let number = |key: &str| {
+            doc[key]
+                .as_u64()
+                .map(|value| value as u32)
+                .ok_or_else(|| invalid(&format!("\"{key}\" must be a whole number")))
+        };
+        let mut hashes = Self::new(number("width")?, number("height")?, number("fps")?);
+        let Some(frames) = doc["frames"].as_array() else {
+            bail!(invalid("\"frames\" must be an array"));
+        };
+        for item in frames {
+            let (Some(frame), Some(hash)) = (item[0].as_u64(), item[1].as_str()) else {
+                bail!(invalid("\"frames\" must hold [frame, hash] pairs"));
+            };
+            hashes.frames.insert(frame as u32, hash.to_string());
+        }
+        Ok(hashes)
+    }
+
+    pub fn to_json(&self) -> String {
+        let frames: Vec<Value> =
+            self.frames.iter().map(|(frame, hash)| json!([frame, hash])).collect();
+        let document = json!({
+            "width": self.width,
+            "height": self.height,
+            "fps": self.fps,
+            "frames": frames,
+        });
+        document.to_string()
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("failed to create frame hash directory")?;
+        }
+        fs::write(path, self.to_json())
+            .with_context(|| format!("failed to write frame hashes: {}", path.display()))
+    }
+}
+
+// FNV-1a of the frame's bytes, as 16 hex digits.
+pub fn frame_hash(rgba: &[u8]) -> String {
+    let mut hasher = Fnv1a::default();
+    hasher.write_bytes(rgba);
+    format!("{:016x}", hasher.0)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameMismatch {
+    pub frame: u32,
+    pub time: f32,
+    // None when the golden has no hash for the frame.
+    pub expected: Option<String>,
+    pub actual: String,
+    // Where the frame was written, with `GoldenCheck::with_mismatch_dir`.
+    pub saved: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GoldenReport {
+    pub checked: u32,
+    pub mismatches: Vec<FrameMismatch>,
+}
+
+impl GoldenReport {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    // Fails with `ErrorKind::AssertionFailed`, naming the first frames that
+    // differ, unless every checked frame matched.
+    pub fn check(&self) -> Result<()> {
+        if self.passed() {
+            return Ok(());
+        }
+        let frames: Vec<String> =
+            self.mismatches.iter().take(5).map(|mismatch| mismatch.frame.to_string()).collect();
+        let more = if self.mismatches.len() > 5 { ", ..." } else { "" };
+        bail!(ErrorKind::AssertionFailed.error(format!(
+            "{} of {} frames differ from the golden (frames {}{more})",
+            self.mismatches.len(),
+            self.checked,
+            frames.join(", ")
+        )));
+    }
+}
+
+// Compares frames with a golden as they're rendered, e.g. from `on_frame`.
+// Only the frames rendered are checked, so a golden of the whole timeline
+// also covers renders of part of it.
+pub struct GoldenCheck {
+    golden: FrameHashes,
+    mismatch_dir: Option<PathBuf>,
+    report: GoldenReport,
+}
+
+impl GoldenCheck {
+    pub fn new(golden: FrameHashes) -> Self {
+        Self {
+            golden,
+            mismatch_dir: None,
+            report: GoldenReport::default(),
+        }
+    }
+
+    // Writes each frame that differs to `dir` as `frame_000123.png`, to look
+    // at what changed.
+    #[cfg(feature = "raylib")]
+    pub fn with_mismatch_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.mismatch_dir = Some(dir.into());
+        self
+    }
+
+    pub fn check_frame(&mut self, t: f32, rgba: &[u8]) -> Result<()> {
+        let (width, height) = (self.golden.width, self.golden.height);
+        if rgba.len() != width as usize * height as usize * 4 {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "frame is {} bytes, but the golden was recorded at {width}x{height}",
+                rgba.len()
+            )));
+        }
+        self.report.checked += 1;
+        let frame = frame_index(t, self.golden.fps);
+        let actual = frame_hash(rgba);
+        let expected = self.golden.frames.get(&frame);
+        if expected == Some(&actual) {
+            return Ok(());
+        }
+        let saved = match &self.mismatch_dir {
+            #[cfg(feature = "raylib")]
+            Some(dir) => Some(save_frame(dir, frame, rgba, width, height)?),
+            _ => None,
+        };
+        self.report.mismatches.push(FrameMismatch {
+            frame,
+            time: t,
+            expected: expected.cloned(),
+            actual,
+            saved,
+        });
+        Ok(())
+    }
+
+    pub fn report(&self) -> &GoldenReport {
+        &self.report
+    }
+
+    pub fn finish(self) -> GoldenReport {
+        self.report
+    }
+}
+
+fn frame_index(t: f32, fps: u32) -> u32 {
+    (t * fps as f32).round() as u32
+}
+
+#[cfg(feature = "raylib")]
+fn save_frame(dir: &Path, frame: u32, rgba: &[u8], width: u32, height: u32) -> Result<PathBuf> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("failed to create mismatch directory: {}", dir.display()))?;
+    let path = dir.join(format!("frame_{frame:06}.png"));
+    crate::backend::raylib_render::write_still(rgba, width, height, &path)?;
+    Ok(path)
+}
@@ -0,0 +1,55 @@
+use std::env;
+use std::os::raw::c_int;
+
+// GLFW 3.4 init hint values (glfw3.h).
+const GLFW_PLATFORM: c_int = 0x0005_0003;
+const GLFW_PLATFORM_NULL: c_int = 0x0006_0005;
+
+unsafe extern "C" {
+    // From the GLFW that raylib builds in.
+    fn glfwInitHint(hint: c_int, value: c_int);
+}
+
+// Where `RaylibRender` gets its OpenGL context from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisplayMode {
+    // Headless on Linux when neither `DISPLAY` nor `WAYLAND_DISPLAY` is set,
+    // as on CI machines and servers; a window otherwise.
+    #[default]
+    Auto,
+    // A window on the desktop, which needs X11, Wayland, macOS or Windows.
+    Window,
+    // No window system at all: GLFW's null platform with an OSMesa context,
+    // Mesa's software renderer. Needs libOSMesa installed.
+    Headless,
+}
+
+impl DisplayMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(DisplayMode::Auto),
+            "window" => Some(DisplayMode::Window),
+            "headless" => Some(DisplayMode::Headless),
+            _ => None,
+        }
+    }
+
+    // Whether this mode renders without a window on this machine.
+    pub fn is_headless(self) -> bool {
+        match self {
+            DisplayMode::Auto => {
+                cfg!(target_os = "linux")
+                    && env::var_os("DISPLAY").is_none()
+                    && env::var_os("WAYLAND_DISPLAY").is_none()
+            }
+            DisplayMode::Window => false,
+            DisplayMode::Headless => true,
+        }
+    }
+}
+
+// Makes the next raylib init use GLFW's null platform, whose windows are
+// never shown and whose contexts come from OSMesa.
+pub(crate) fn use_null_platform() {
+    unsafe { glfwInitHint(GLFW_PLATFORM, GLFW_PLATFORM_NULL) };
+}
@@ -0,0 +1,226 @@
+use std::f32::consts::TAU;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+
+use crate::scene::{Object, Shape, Transform, Vec2};
+use crate::timeline::SampledScene;
+
+// Points in a circle's outline.
+const CIRCLE_POINTS: usize = 16;
+
+// Where a named clip can be clicked in one frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HitRegion {
+    // The clip's name.
+    pub id: String,
+    // Outline in output pixels from the top-left corner, y down, the way a
+    // player overlays it on the video.
+    pub polygon: Vec<Vec2>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HitRegionFrame {
+    pub frame: u32,
+    pub time: f32,
+    // Topmost first, so the first polygon containing a click is the one
+    // drawn on top.
+    pub regions: Vec<HitRegion>,
+}
+
+// Per-frame hit regions for a whole render, written next to the video so an
+// interactive player can put hotspots over the animated objects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HitRegionTrack {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub frames: Vec<HitRegionFrame>,
+}
+
+impl HitRegionTrack {
+    pub fn new(width: u32, height: u32, fps: u32) -> Self {
+        Self {
+            width,
+            height,
+            fps,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // Hundredths of a pixel are plenty, and keep the file readable.
+        let coord = |value: f32| ((value as f64) * 100.0).round() / 100.0;
+        let frames: Vec<Value> = self
+            .frames
+            .iter()
+            .map(|frame| {
+                let regions: Vec<Value> = frame
+                    .regions
+                    .iter()
+                    .map(|region| {
+                        let points: Vec<Value> = region
+                            .polygon
+                            .iter()
+                            .map(|point| json!([coord(point.x), coord(point.y)]))
+                            .collect();
+                        json!({ "id": region.id, "polygon": points })
+                    })
+                    .collect();
+                json!({ "frame": frame.frame, "time": frame.time, "regions": regions })
+            })
+            .collect();
+        json!({
+            "width": self.width,
+            "height": self.height,
+            "fps": self.fps,
+            "frames": frames,
+        })
+        .to_string()
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).context("failed to create hit region directory")?;
+        }
+        std::fs::write(path, self.to_json())
+            .with_context(|| format!("failed to write hit regions: {}", path.display()))
+    }
+}
+
+// Where any clip sits in one frame, named or not, for debug overlays.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipOutline {
+    pub name: Option<String>,
+    // As in `HitRegion`.
+    pub polygon: Vec<Vec2>,
+}
+
+// Outlines of the named clips in `scene` on a `width` x `height` output.
+// Shapes and compositions are outlined here; `measure` gives the drawn size
+// of anything else (images, text), or `None` to leave it out. Clips that are
+// fully transparent aren't clickable and are skipped.
+pub fn scene_hit_regions(
+    scene: &SampledScene,
+    width: u32,
+    height: u32,
+    measure: impl FnMut(&Object, &Transform) -> Result<Option<Vec2>>,
+) -> Result<Vec<HitRegion>> {
+    let mut regions: Vec<HitRegion> = clip_outlines(scene, width, height, true, measure)?
+        .into_iter()
+        .filter_map(|outline| {
+            Some(HitRegion {
+                id: outline.name?,
+                polygon: outline.polygon,
+            })
+        })
+        .collect();
+    regions.reverse();
+    Ok(regions)
+}
+
+// Outlines of every visible clip in `scene`, as `scene_hit_regions` finds
+// them, bottom-most first.
+pub fn scene_outlines(
+    scene: &SampledScene,
+    width: u32,
+    height: u32,
+    measure: impl FnMut(&Object, &Transform) -> Result<Option<Vec2>>,
+) -> Result<Vec<ClipOutline>> {
+    clip_outlines(scene, width, height, false, measure)
+}
+
+fn clip_outlines(
+    scene: &SampledScene,
+    width: u32,
+    height: u32,
+    named_only: bool,
+    mut measure: impl FnMut(&Object, &Transform) -> Result<Option<Vec2>>,
+) -> Result<Vec<ClipOutline>> {
+    let mut outlines = Vec::new();
+    for layer in &scene.layers {
+        let group_opacity = layer.group.map_or(1.0, |group| scene.groups[group].opacity);
+        for clip in &layer.clips {
+            if named_only && clip.name.is_none() {
+                continue;
+            }
+            // Placed as `draw_clip` places it.
+            let mut transform = layer.transform.compose(&clip.transform);
+            if let Some(transition) = &clip.transition {
+                let offset = transition.slide_offset(width as f32, height as f32);
+                transform.pos.x += offset.x;
+                transform.pos.y += offset.y;
+            }
+            if transform.opacity * group_opacity <= 0.0 {
+                continue;
+            }
+
+            let scale = transform.scale;
+            let local = match &clip.object {
+                Object::Shape(Shape::Circle { radius, .. }) => {
+                    let radius = radius * scale.x.max(0.0);
+                    (0..CIRCLE_POINTS)
+                        .map(|i| {
+                            let angle = i as f32 / CIRCLE_POINTS as f32 * TAU;
+                            Vec2 {
+                                x: radius * angle.cos(),
+                                y: radius * angle.sin(),
+                            }
+                        })
+                        .collect()
+                }
+                Object::Shape(Shape::Rect { width, height, .. }) => {
+                    centered_box(width * scale.x, height * scale.y)
+                }
+                Object::Composition(composition) => centered_box(
+                    composition.width as f32 * scale.x,
+                    composition.height as f32 * scale.y,
+                ),
+                // Text hangs from its position rather than centring on it.
+                Object::Text(_) => match measure(&clip.object, &transform)? {
+                    Some(size) => vec![
+                        Vec2::ZERO,
+                        Vec2 { x: size.x, y: 0.0 },
+                        size,
+                        Vec2 { x: 0.0, y: size.y },
+                    ],
+                    None => continue,
+                },
+                Object::Image(_) | Object::Plugin(_) => match measure(&clip.object, &transform)? {
+                    Some(size) => centered_box(size.x, size.y),
+                    None => continue,
+                },
+            };
+
+            // Rotation is clockwise on screen, where y points down.
+            let (sin, cos) = transform.rotation.to_radians().sin_cos();
+            let origin = Vec2 {
+                x: width as f32 / 2.0 + transform.pos.x,
+                y: height as f32 / 2.0 - transform.pos.y,
+            };
+            let polygon = local
+                .into_iter()
+                .map(|point| Vec2 {
+                    x: origin.x + point.x * cos - point.y * sin,
+                    y: origin.y + point.x * sin + point.y * cos,
+                })
+                .collect();
+            outlines.push(ClipOutline {
+                name: clip.name.clone(),
+                polygon,
+            });
+        }
+    }
+    Ok(outlines)
+}
+
+fn centered_box(width: f32, height: f32) -> Vec<Vec2> {
+    let (x, y) = (width / 2.0, height / 2.0);
+    vec![
+        Vec2 { x: -x, y: -y },
+        Vec2 { x, y: -y },
+        Vec2 { x, y },
+        Vec2 { x: -x, y },
+    ]
+}
@@ -0,0 +1,340 @@
+use std::ffi::c_void;
+
+use anyhow::Result;
+use raylib::consts::BlendMode as RaylibBlendMode;
+use raylib::prelude::*;
+
+use crate::backend::alpha::{
+    begin_premultiplied, begin_straight, premultiplied_tint, premultiply, unpremultiply,
+    GL_FUNC_ADD, GL_FUNC_SUBTRACT, GL_ONE, GL_ONE_MINUS_SRC_ALPHA, GL_ONE_MINUS_SRC_COLOR,
+    GL_SRC_ALPHA, GL_ZERO,
+};
+use crate::backend::plugin_canvas::apply_effects;
+use crate::backend::raylib_render::{capture_rgba, downsample, draw_clip, upsample, ScaledDraw};
+use crate::backend::resources::{ClipKey, ResourceCache};
+use crate::timeline::{BlendMode, SampledGroup, SampledLayer, SampledScene, TrackMatte};
+
+// Layers that blend or are matted are drawn into their own target first, so
+// the blend/matte applies to the layer as a whole rather than clip by clip.
+pub fn needs_target(layer: &SampledLayer) -> bool {
+    layer.blend != BlendMode::Normal || layer.matte.is_some()
+}
+
+pub fn layer_key(layer: &SampledLayer) -> *const () {
+    layer as *const SampledLayer as *const ()
+}
+
+pub fn group_key(group: &SampledGroup) -> *const () {
+    group as *const SampledGroup as *const ()
+}
+
+// Runs of adjacent layers in `scene` that share a group (or are all outside
+// one), each with the index of its first layer. Group members are always
+// adjacent, so each group is one run.
+pub fn layer_runs(scene: &SampledScene) -> impl Iterator<Item = (usize, &[SampledLayer])> {
+    let mut first = 0;
+    scene
+        .layers
+        .chunk_by(|a, b| a.group == b.group)
+        .map(move |run| {
+            first += run.len();
+            (first - run.len(), run)
+        })
+}
+
+// Renders every isolated layer of `scene` (and its matte source) offscreen,
+// then every layer group. Must run after `render_compositions` and before
+// the frame's texture mode.
+pub fn render_layer_targets(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    cache: &mut ResourceCache,
+    scene: &SampledScene,
+    width: u32,
+    height: u32,
+) -> Result<()> {
+    for (index, layer) in scene.layers.iter().enumerate() {
+        if !needs_target(layer) {
+            continue;
+        }
+
+        let matte = match (layer.matte, scene.layers.get(index + 1)) {
+            (Some(matte), Some(source)) => {
+                let (slot, mut target) =
+                    cache.take_offscreen_target(rl, thread, layer_key(source), width, height)?;
+                let result = draw_layer_target(
+                    rl,
+                    thread,
+                    cache,
+                    &mut target,
+                    (width, height),
+                    scene,
+                    index + 1,
+                    None,
+                );
+                cache.return_offscreen_target(slot, target);
+                result?;
+                if matches!(matte, TrackMatte::Luma | TrackMatte::LumaInverted) {
+                    cache.load_luma_matte_shader(rl, thread);
+                }
+                Some((matte, source))
+            }
+            _ => None,
+        };
+
+        let (slot, mut target) =
+            cache.take_offscreen_target(rl, thread, layer_key(layer), width, height)?;
+        let result = draw_layer_target(
+            rl,
+            thread,
+            cache,
+            &mut target,
+            (width, height),
+            scene,
+            index,
+            matte,
+        );
+        cache.return_offscreen_target(slot, target);
+        result?;
+    }
+
+    // After the layers, since members may be drawn from their own targets.
+    for (first, run) in layer_runs(scene) {
+        let Some(group) = run[0].group.map(|group| &scene.groups[group]) else {
+            continue;
+        };
+        let (slot, mut target) =
+            cache.take_offscreen_target(rl, thread, group_key(group), width, height)?;
+        let result = draw_group_target(
+            rl,
+            thread,
+            cache,
+            &mut target,
+            (width, height),
+            scene,
+            group,
+            (first, run),
+        );
+        cache.return_offscreen_target(slot, target);
+        result?;
+    }
+    Ok(())
+}
+
+// Draws a group's members together, then runs the group's effects over the
+// result.
+#[allow(clippy::too_many_arguments)]
+fn draw_group_target(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    cache: &ResourceCache,
+    target: &mut RenderTexture2D,
+    (width, height): (u32, u32),
+    scene: &SampledScene,
+    group: &SampledGroup,
+    (first, members): (usize, &[SampledLayer]),
+) -> Result<()> {
+    let scale = cache.render_scale();
+    {
+        let mut d = rl.begin_texture_mode(thread, target.as_mut());
+        let _scaled = ScaledDraw::begin(cache.draw_scale());
+        d.clear_background(Color::new(0, 0, 0, 0));
+        begin_straight();
+        draw_layer_run(&mut d, cache, width, height, scene, first, members)?;
+    }
+    if group.effects.is_empty() {
+        return Ok(());
+    }
+    // Effects work on straight alpha at the output size, like they do for
+    // whole frames.
+    let (width, height) = cache.scaled_size(width, height);
+    let mut rgba = capture_rgba(target, width * scale, height * scale)?;
+    if scale > 1 {
+        rgba = downsample(&rgba, width, height, scale);
+    }
+    unpremultiply(&mut rgba);
+    apply_effects(
+        cache.plugins()?,
+        &group.effects,
+        scene.time,
+        &mut rgba,
+        width,
+        height,
+    )?;
+    premultiply(&mut rgba);
+    if scale > 1 {
+        rgba = upsample(&rgba, width, height, scale);
+    }
+    unsafe {
+        raylib::ffi::UpdateTexture(*target.texture().as_ref(), rgba.as_ptr() as *const c_void);
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_layer_target(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    cache: &ResourceCache,
+    target: &mut RenderTexture2D,
+    (width, height): (u32, u32),
+    scene: &SampledScene,
+    index: usize,
+    matte: Option<(TrackMatte, &SampledLayer)>,
+) -> Result<()> {
+    let mut d = rl.begin_texture_mode(thread, target.as_mut());
+    let _scaled = ScaledDraw::begin(cache.draw_scale());
+    d.clear_background(Color::new(0, 0, 0, 0));
+    begin_straight();
+    let layer = &scene.layers[index];
+    for (clip_index, clip) in layer.clips.iter().enumerate() {
+        let key = ClipKey::new(scene, index, clip_index);
+        draw_clip(&mut d, cache, width, height, &layer.transform, clip, key)?;
+    }
+
+    let Some((matte, source)) = matte else {
+        return Ok(());
+    };
+    let texture = cache.offscreen_texture(layer_key(source))?;
+    // Scale the layer by the matte's alpha (or luma, which the shader writes
+    // into alpha). Color is scaled too, since the target is premultiplied.
+    let dst_alpha = match matte {
+        TrackMatte::Alpha | TrackMatte::Luma => GL_SRC_ALPHA,
+        TrackMatte::AlphaInverted | TrackMatte::LumaInverted => GL_ONE_MINUS_SRC_ALPHA,
+    };
+    let shader = match matte {
+        TrackMatte::Luma | TrackMatte::LumaInverted => cache.luma_matte_shader(),
+        TrackMatte::Alpha | TrackMatte::AlphaInverted => None,
+    };
+    unsafe {
+        raylib::ffi::rlSetBlendFactorsSeparate(
+            GL_ZERO,
+            dst_alpha,
+            GL_ZERO,
+            dst_alpha,
+            GL_FUNC_ADD,
+            GL_FUNC_ADD,
+        );
+        raylib::ffi::BeginBlendMode(RaylibBlendMode::BLEND_CUSTOM_SEPARATE as i32);
+        if let Some(shader) = shader {
+            raylib::ffi::BeginShaderMode(*shader.as_ref());
+        }
+    }
+    draw_fullscreen(&mut d, texture, width, height, Color::WHITE);
+    unsafe {
+        if shader.is_some() {
+            raylib::ffi::EndShaderMode();
+        }
+    }
+    begin_straight();
+    Ok(())
+}
+
+// Main-pass counterpart of `render_layer_targets`: groups and isolated
+// layers are drawn from their targets with their blend mode, and matte
+// sources are skipped.
+pub fn draw_layers(
+    d: &mut impl RaylibDraw,
+    cache: &ResourceCache,
+    width: u32,
+    height: u32,
+    scene: &SampledScene,
+) -> Result<()> {
+    for (first, run) in layer_runs(scene) {
+        match run[0].group {
+            Some(group) => draw_group_texture(d, cache, width, height, &scene.groups[group])?,
+            None => draw_layer_run(d, cache, width, height, scene, first, run)?,
+        }
+    }
+    Ok(())
+}
+
+fn draw_layer_run(
+    d: &mut impl RaylibDraw,
+    cache: &ResourceCache,
+    width: u32,
+    height: u32,
+    scene: &SampledScene,
+    first: usize,
+    layers: &[SampledLayer],
+) -> Result<()> {
+    let mut skip_matte_source = false;
+    for (index, layer) in (first..).zip(layers) {
+        if skip_matte_source {
+            skip_matte_source = false;
+            continue;
+        }
+        if needs_target(layer) {
+            draw_layer_texture(d, cache, width, height, layer)?;
+            skip_matte_source = layer.matte.is_some();
+            continue;
+        }
+        for (clip_index, clip) in layer.clips.iter().enumerate() {
+            let key = ClipKey::new(scene, index, clip_index);
+            draw_clip(d, cache, width, height, &layer.transform, clip, key)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn draw_layer_texture(
+    d: &mut impl RaylibDraw,
+    cache: &ResourceCache,
+    width: u32,
+    height: u32,
+    layer: &SampledLayer,
+) -> Result<()> {
+    let texture = cache.offscreen_texture(layer_key(layer))?;
+    begin_target_blend(layer.blend);
+    draw_fullscreen(d, texture, width, height, Color::WHITE);
+    begin_straight();
+    Ok(())
+}
+
+// A group's target at the group's opacity, with its blend mode.
+pub fn draw_group_texture(
+    d: &mut impl RaylibDraw,
+    cache: &ResourceCache,
+    width: u32,
+    height: u32,
+    group: &SampledGroup,
+) -> Result<()> {
+    let texture = cache.offscreen_texture(group_key(group))?;
+    begin_target_blend(group.blend);
+    draw_fullscreen(d, texture, width, height, premultiplied_tint(group.opacity));
+    begin_straight();
+    Ok(())
+}
+
+pub(crate) fn begin_target_blend(blend: BlendMode) {
+    // Targets are premultiplied, so source factors are ONE where raylib's
+    // presets (made for straight alpha) use SRC_ALPHA.
+    let custom = |src: i32, dst: i32, equation: i32| unsafe {
+        raylib::ffi::rlSetBlendFactors(src, dst, equation);
+        raylib::ffi::BeginBlendMode(RaylibBlendMode::BLEND_CUSTOM as i32);
+    };
+    match blend {
+        BlendMode::Normal => begin_premultiplied(),
+        BlendMode::Add => custom(GL_ONE, GL_ONE, GL_FUNC_ADD),
+        // dst * src + dst * (1 - src alpha) is already right for premultiplied.
+        BlendMode::Multiply => unsafe {
+            raylib::ffi::BeginBlendMode(RaylibBlendMode::BLEND_MULTIPLIED as i32);
+        },
+        BlendMode::Subtract => custom(GL_ONE, GL_ONE, GL_FUNC_SUBTRACT),
+        BlendMode::Screen => custom(GL_ONE, GL_ONE_MINUS_SRC_COLOR, GL_FUNC_ADD),
+    }
+}
+
+fn draw_fullscreen(
+    d: &mut impl RaylibDraw,
+    target: &RenderTexture2D,
+    width: u32,
+    height: u32,
+    tint: Color,
+) {
+    // Render textures are stored bottom-up; a negative source height flips them.
+    let texture = target.texture();
+    let source = Rectangle::new(0.0, 0.0, texture.width as f32, -(texture.height as f32));
+    let dest = Rectangle::new(0.0, 0.0, width as f32, height as f32);
+    d.draw_texture_pro(texture, source, dest, Vector2::zero(), 0.0, tint);
+}
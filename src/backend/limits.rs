@@ -0,0 +1,79 @@
+use crate::timeline::SampledScene;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitPolicy {
+    // Note the overrun in the render report and carry on.
+    Warn,
+    // Warn, then cut work to get back under the limit: clips past the clip
+    // limit are not drawn, cached textures are released, and effects are
+    // skipped for the rest of the render once a frame runs too long.
+    Degrade,
+    // Fail the render with `ErrorKind::LimitExceeded`.
+    Abort,
+}
+
+// Caps that keep one pathological project (e.g. user-submitted to a shared
+// render machine) from taking it over. Unset limits are not checked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderLimits {
+    // Clips drawn in one frame, nested compositions included.
+    pub max_clips_per_frame: Option<usize>,
+    // Estimated GPU memory held by cached image textures and offscreen targets.
+    pub max_texture_bytes: Option<u64>,
+    pub max_frame_secs: Option<f64>,
+    pub policy: LimitPolicy,
+}
+
+impl RenderLimits {
+    pub fn new(policy: LimitPolicy) -> Self {
+        Self {
+            max_clips_per_frame: None,
+            max_texture_bytes: None,
+            max_frame_secs: None,
+            policy,
+        }
+    }
+
+    pub fn with_max_clips_per_frame(mut self, max: usize) -> Self {
+        self.max_clips_per_frame = Some(max);
+        self
+    }
+
+    pub fn with_max_texture_bytes(mut self, max: u64) -> Self {
+        self.max_texture_bytes = Some(max);
+        self
+    }
+
+    pub fn with_max_frame_secs(mut self, max: f64) -> Self {
+        self.max_frame_secs = Some(max);
+        self
+    }
+}
+
+impl Default for RenderLimits {
+    fn default() -> Self {
+        Self::new(LimitPolicy::Warn)
+    }
+}
+
+impl SampledScene {
+    // Clips drawn for this frame, nested compositions included.
+    pub fn clip_count(&self) -> usize {
+        self.layers
+            .iter()
+            .flat_map(|layer| &layer.clips)
+            .map(|clip| 1 + clip.nested.as_ref().map_or(0, |nested| nested.clip_count()))
+            .sum()
+    }
+
+    // Keeps clips in scene order until `max` are drawn and drops the rest.
+    pub fn truncate_clips(&mut self, max: usize) {
+        let mut drawn = 0;
+        for layer in &mut self.layers {
+            layer.clips.retain(|clip| {
+                drawn += 1 + clip.nested.as_ref().map_or(0, |nested| nested.clip_count());
+                drawn <= max
+            });
+        }
+    }
+}
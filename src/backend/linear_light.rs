@@ -0,0 +1,266 @@
+use anyhow::{bail, Context, Result};
+use raylib::consts::PixelFormat;
+use raylib::prelude::*;
+
+use crate::backend::alpha::{
+    begin_premultiplied, begin_straight, premultiplied_clear, premultiplied_tint,
+};
+use crate::backend::layers::{begin_target_blend, group_key, layer_key, layer_runs, needs_target};
+use crate::backend::raylib_render::{draw_clip, ScaledDraw};
+use crate::backend::resources::{ClipKey, ResourceCache};
+use crate::error::ErrorKind;
+use crate::scene::Color;
+use crate::timeline::{BlendMode, SampledScene};
+
+// rlgl's framebuffer attachment values (rlgl.h).
+const RL_ATTACHMENT_COLOR_CHANNEL0: i32 = 0;
+const RL_ATTACHMENT_TEXTURE2D: i32 = 100;
+
+// Premultiplied sRGB in, premultiplied linear light out.
+const DECODE_FS: &str = r#"
+#version 330
+in vec2 fragTexCoord;
+in vec4 fragColor;
+uniform sampler2D texture0;
+uniform vec4 colDiffuse;
+out vec4 finalColor;
+vec3 decode(vec3 c) {
+    return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+}
+void main() {
+    vec4 texel = texture(texture0, fragTexCoord) * colDiffuse * fragColor;
+    vec3 color = texel.a > 0.0 ? texel.rgb / texel.a : vec3(0.0);
+    finalColor = vec4(decode(color) * texel.a, texel.a);
+}
+"#;
+
+// The reverse of `DECODE_FS`.
+const ENCODE_FS: &str = r#"
+#version 330
+in vec2 fragTexCoord;
+uniform sampler2D texture0;
+out vec4 finalColor;
+vec3 encode(vec3 c) {
+    c = clamp(c, 0.0, 1.0);
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+void main() {
+    vec4 texel = texture(texture0, fragTexCoord);
+    vec3 color = texel.a > 0.0 ? texel.rgb / texel.a : vec3(0.0);
+    finalColor = vec4(encode(color) * texel.a, texel.a);
+}
+"#;
+
+// Blending in linear light for `RaylibRender::set_linear_light`. GL blends
+// whatever its target holds, so the frame is built up in a half-float target
+// holding linear light instead of the 8-bit frame target. Each clip, and
+// each isolated layer or group from its own target, is drawn alone into an
+// 8-bit scratch target, then decoded from sRGB and blended into the frame.
+// The finished frame is encoded back into the frame target as premultiplied
+// sRGB, where capture, post effects and the debug overlay pick it up as
+// usual. Inside compositions, groups and isolated layers, clips still mix
+// with each other in gamma space.
+pub(crate) struct LinearLight {
+    frame: RenderTexture2D,
+    scratch: RenderTexture2D,
+    decode: Shader,
+    encode: Shader,
+}
+
+impl LinearLight {
+    // Targets the size of `target`, the frame target.
+    pub(crate) fn new(
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        target: &RenderTexture2D,
+    ) -> Result<Self> {
+        let (width, height) = (target.texture().width, target.texture().height);
+        let scratch = rl
+            .load_render_texture(thread, width as u32, height as u32)
+            .context(ErrorKind::Gpu.error("failed to create linear-light scratch texture"))?;
+        Ok(Self {
+            frame: load_half_float_target(width, height)?,
+            scratch,
+            decode: load_shader(rl, thread, DECODE_FS)?,
+            encode: load_shader(rl, thread, ENCODE_FS)?,
+        })
+    }
+
+    // Whether the targets still match the frame target, which changes size
+    // with the region, tile or scale.
+    pub(crate) fn fits(&self, target: &RenderTexture2D) -> bool {
+        let (texture, frame) = (target.texture(), self.frame.texture());
+        texture.width == frame.width && texture.height == frame.height
+    }
+
+    // Draws `scene` over `bg` into `target` (see `LinearLight`). `origin` is
+    // the scene point at the target's top-left corner, as for the main pass.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn draw_scene(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        cache: &ResourceCache,
+        target: &mut RenderTexture2D,
+        (width, height): (u32, u32),
+        bg: Color,
+        origin: (f32, f32),
+        scene: &SampledScene,
+    ) -> Result<()> {
+        let scale = cache.draw_scale();
+        let mut frame = LinearFrame {
+            target: &mut self.frame,
+            decode: &self.decode,
+            scale,
+            origin,
+        };
+        let scratch = &mut self.scratch;
+        // Layer and group targets cover the whole frame; the scratch target
+        // covers what the frame target does.
+        let whole = Rectangle::new(0.0, 0.0, width as f32, height as f32);
+        let scratch_rect = {
+            let texture = scratch.texture();
+            let (w, h) = (texture.width as f32, texture.height as f32);
+            Rectangle::new(origin.0, origin.1, w / scale, h / scale)
+        };
+        let opaque = premultiplied_tint(1.0);
+
+        // The background goes in first, like a clip under everything.
+        {
+            let mut d = rl.begin_texture_mode(thread, scratch.as_mut());
+            d.clear_background(premultiplied_clear(bg));
+        }
+        {
+            let mut d = rl.begin_texture_mode(thread, frame.target.as_mut());
+            d.clear_background(raylib::prelude::Color::new(0, 0, 0, 0));
+        }
+        frame.blend(rl, thread, scratch, scratch_rect, BlendMode::Normal, opaque);
+
+        // The same order as `draw_layers`, with every clip blended on its own.
+        for (first, run) in layer_runs(scene) {
+            if let Some(group) = run[0].group.map(|group| &scene.groups[group]) {
+                let texture = cache.offscreen_texture(group_key(group))?;
+                let tint = premultiplied_tint(group.opacity);
+                frame.blend(rl, thread, texture, whole, group.blend, tint);
+                continue;
+            }
+            let mut skip_matte_source = false;
+            for (index, layer) in (first..).zip(run) {
+                if skip_matte_source {
+                    skip_matte_source = false;
+                    continue;
+                }
+                if needs_target(layer) {
+                    let texture = cache.offscreen_texture(layer_key(layer))?;
+                    frame.blend(rl, thread, texture, whole, layer.blend, opaque);
+                    skip_matte_source = layer.matte.is_some();
+                    continue;
+                }
+                for (clip_index, clip) in layer.clips.iter().enumerate() {
+                    let key = ClipKey::new(scene, index, clip_index);
+                    {
+                        let mut d = rl.begin_texture_mode(thread, scratch.as_mut());
+                        let _scaled = ScaledDraw::begin_at(scale, origin);
+                        d.clear_background(raylib::prelude::Color::new(0, 0, 0, 0));
+                        begin_straight();
+                        draw_clip(&mut d, cache, width, height, &layer.transform, clip, key)?;
+                    }
+                    frame.blend(rl, thread, scratch, scratch_rect, BlendMode::Normal, opaque);
+                }
+            }
+        }
+
+        let mut d = rl.begin_texture_mode(thread, target.as_mut());
+        d.clear_background(raylib::prelude::Color::new(0, 0, 0, 0));
+        begin_premultiplied();
+        let texture = frame.target.texture();
+        let (w, h) = (texture.width as f32, texture.height as f32);
+        // Render textures are stored bottom-up; a negative source height flips them.
+        let source = Rectangle::new(0.0, 0.0, w, -h);
+        let dest = Rectangle::new(0.0, 0.0, w, h);
+        unsafe { raylib::ffi::BeginShaderMode(*self.encode.as_ref()) };
+        d.draw_texture_pro(texture, source, dest, Vector2::zero(), 0.0, opaque);
+        unsafe { raylib::ffi::EndShaderMode() };
+        begin_straight();
+        Ok(())
+    }
+}
+
+// The half-float frame being built up, and where scene points land on it.
+struct LinearFrame<'a> {
+    target: &'a mut RenderTexture2D,
+    decode: &'a Shader,
+    scale: f32,
+    origin: (f32, f32),
+}
+
+impl LinearFrame<'_> {
+    // Decodes premultiplied sRGB `texture`, drawn over `dest` in scene
+    // points, and blends it in with `mode`.
+    fn blend(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        texture: &RenderTexture2D,
+        dest: Rectangle,
+        mode: BlendMode,
+        tint: raylib::prelude::Color,
+    ) {
+        let mut d = rl.begin_texture_mode(thread, self.target.as_mut());
+        let _scaled = ScaledDraw::begin_at(self.scale, self.origin);
+        let texture = texture.texture();
+        let source = Rectangle::new(0.0, 0.0, texture.width as f32, -(texture.height as f32));
+        begin_target_blend(mode);
+        unsafe { raylib::ffi::BeginShaderMode(*self.decode.as_ref()) };
+        d.draw_texture_pro(texture, source, dest, Vector2::zero(), 0.0, tint);
+        unsafe { raylib::ffi::EndShaderMode() };
+        begin_straight();
+    }
+}
+
+fn load_shader(rl: &mut RaylibHandle, thread: &RaylibThread, source: &str) -> Result<Shader> {
+    let shader = rl.load_shader_from_memory(thread, None, Some(source));
+    // raylib falls back to its default shader when compiling fails.
+    if shader.as_ref().id == unsafe { raylib::ffi::rlGetShaderIdDefault() } {
+        bail!(ErrorKind::Gpu.error("linear-light shader failed to compile"));
+    }
+    Ok(shader)
+}
+
+// A render texture with a half-float colour buffer, which raylib's
+// `LoadRenderTexture` doesn't make. Linear light needs more than 8 bits, or
+// dark colours band.
+fn load_half_float_target(width: i32, height: i32) -> Result<RenderTexture2D> {
+    let format = PixelFormat::PIXELFORMAT_UNCOMPRESSED_R16G16B16A16 as i32;
+    let (target, complete) = unsafe {
+        let id = raylib::ffi::rlLoadFramebuffer();
+        let texture = raylib::ffi::rlLoadTexture(std::ptr::null(), width, height, format, 1);
+        raylib::ffi::rlEnableFramebuffer(id);
+        raylib::ffi::rlFramebufferAttach(
+            id,
+            texture,
+            RL_ATTACHMENT_COLOR_CHANNEL0,
+            RL_ATTACHMENT_TEXTURE2D,
+            0,
+        );
+        let complete = texture != 0 && raylib::ffi::rlFramebufferComplete(id);
+        raylib::ffi::rlDisableFramebuffer();
+        let raw = raylib::ffi::RenderTexture {
+            id,
+            texture: raylib::ffi::Texture {
+                id: texture,
+                width,
+                height,
+                mipmaps: 1,
+                format,
+            },
+            depth: std::mem::zeroed(),
+        };
+        (RenderTexture2D::from_raw(raw), complete)
+    };
+    // Dropping an incomplete target unloads it again.
+    if !complete {
+        bail!(ErrorKind::Gpu.error("half-float render textures aren't supported by this GPU"));
+    }
+    Ok(target)
+}
@@ -0,0 +1,323 @@
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::backend::clock::{Clock, WallClock};
+use crate::timeline::{DataSource, Variables};
+
+// Live input for the preview window from OSC (and, with the `midi` feature,
+// MIDI) controllers: values that override binding variables, plus playhead
+// seek and pause. Hand a clone to `RaylibPreview::with_data_source` and the
+// result of `clock` to `RaylibPreview::run_clock`.
+#[derive(Clone, Default)]
+pub struct LiveControl {
+    shared: Arc<Mutex<ControlState>>,
+    base: Option<Arc<dyn DataSource>>,
+}
+
+#[derive(Default)]
+struct ControlState {
+    values: Variables,
+    // Taken by the next clock tick.
+    seek: Option<Seek>,
+    paused: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Seek {
+    Seconds(f32),
+    // 0..1 across the clock's start..end.
+    Fraction(f32),
+}
+
+impl LiveControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Variables from `base` pass through unless a controller has set them.
+    pub fn with_base(mut self, base: Arc<dyn DataSource>) -> Self {
+        self.base = Some(base);
+        self
+    }
+
+    pub fn set(&self, name: &str, value: f32) {
+        self.state().values.insert(name.to_string(), value);
+    }
+
+    pub fn seek(&self, seconds: f32) {
+        self.state().seek = Some(Seek::Seconds(seconds));
+    }
+
+    pub fn scrub(&self, fraction: f32) {
+        self.state().seek = Some(Seek::Fraction(fraction.clamp(0.0, 1.0)));
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.state().paused = Some(paused);
+    }
+
+    // A wall clock over `start..end` that follows seek and pause requests.
+    pub fn clock(&self, start: f32, end: f32) -> LiveClock {
+        LiveClock {
+            control: self.clone(),
+            inner: WallClock::new(start, end),
+            start,
+            end,
+        }
+    }
+
+    // Listens for OSC messages on a UDP address such as `"0.0.0.0:9000"`:
+    //
+    //     /param/<name> <number>   override variable <name>
+    //     /seek <seconds>          jump the playhead
+    //     /scrub <0..1>            jump to a fraction of the preview range
+    //     /pause [<0|1>]           pause (or resume with 0)
+    //     /play                    resume
+    //
+    // Bundles are unpacked and their time tags ignored. Listening stops when
+    // the returned listener is dropped.
+    pub fn listen_osc(&self, addr: impl ToSocketAddrs) -> Result<OscListener> {
+        let socket = UdpSocket::bind(addr).context("failed to bind the OSC listener")?;
+        let addr = socket.local_addr()?;
+        // Polled so the thread can notice the listener being dropped.
+        socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let control = self.clone();
+        thread::spawn(move || {
+            let mut packet = [0u8; 4096];
+            while !thread_stop.load(Ordering::Relaxed) {
+                let Ok(len) = socket.recv(&mut packet) else {
+                    continue;
+                };
+                let mut messages = Vec::new();
+                osc_messages(&packet[..len], &mut messages);
+                for (address, value) in messages {
+                    control.apply_osc(&address, value);
+                }
+            }
+        });
+        Ok(OscListener { addr, stop })
+    }
+
+    fn apply_osc(&self, address: &str, value: Option<f32>) {
+        match (address, value) {
+            ("/seek", Some(seconds)) => self.seek(seconds),
+            ("/scrub", Some(fraction)) => self.scrub(fraction),
+            ("/pause", value) => self.set_paused(value.is_none_or(|value| value != 0.0)),
+            ("/play", _) => self.set_paused(false),
+            (address, Some(value)) => {
+                if let Some(name) = address.strip_prefix("/param/") {
+                    self.set(name, value);
+                }
+            }
+            // Anything else is for another application.
+            _ => {}
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ControlState> {
+        self.shared.lock().expect("control lock")
+    }
+}
+
+impl DataSource for LiveControl {
+    fn values(&self, frame: u32, t: f32) -> Result<Variables> {
+        let mut values = match &self.base {
+            Some(base) => base.values(frame, t)?,
+            None => Variables::new(),
+        };
+        values.extend(self.state().values.clone());
+        Ok(values)
+    }
+}
+
+pub struct LiveClock {
+    control: LiveControl,
+    inner: WallClock,
+    start: f32,
+    end: f32,
+}
+
+impl Clock for LiveClock {
+    fn tick(&mut self) -> Option<f32> {
+        let (seek, paused) = {
+            let mut state = self.control.state();
+            (state.seek.take(), state.paused.take())
+        };
+        if let Some(paused) = paused {
+            self.inner.set_paused(paused);
+        }
+        match seek {
+            Some(Seek::Seconds(seconds)) => self.inner.seek(seconds),
+            Some(Seek::Fraction(fraction)) => {
+                self.inner.seek(self.start + (self.end - self.start) * fraction)
+            }
+            None => {}
+        }
+        self.inner.tick()
+    }
+
+    fn seek(&mut self, time: f32) {
+        self.inner.seek(time);
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.inner.set_paused(paused);
+    }
+
+    fn is_paused(&self) -> bool {
+        self.inner.is_paused()
+    }
+}
+
+pub struct OscListener {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+}
+
+impl OscListener {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for OscListener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// Collects (address, first numeric argument) from an OSC packet. Malformed
+// packets are dropped whole or in part; OSC has no way to report them.
+fn osc_messages(packet: &[u8], out: &mut Vec<(String, Option<f32>)>) {
+    if let Some(mut rest) = packet.strip_prefix(b"#bundle\0") {
+        // Skip the time tag, then size-prefixed elements.
+        rest = rest.get(8..).unwrap_or_default();
+        while let Some(size) = rest.get(..4) {
+            let size = u32::from_be_bytes([size[0], size[1], size[2], size[3]]) as usize;
+            let Some(element) = rest.get(4..4 + size) else {
+                return;
+            };
+            osc_messages(element, out);
+            rest = &rest[4 + size..];
+        }
+        return;
+    }
+
+    let Some((address, rest)) = osc_string(packet) else {
+        return;
+    };
+    // Old senders may omit the type tags; then there are no arguments.
+    let (tags, args) = match osc_string(rest) {
+        Some((tags, args)) if tags.starts_with(',') => (&tags[1..], args),
+        _ => ("", rest),
+    };
+    let value = match (tags.chars().next(), args) {
+        (Some('f'), [a, b, c, d, ..]) => Some(f32::from_be_bytes([*a, *b, *c, *d])),
+        (Some('i'), [a, b, c, d, ..]) => Some(i32::from_be_bytes([*a, *b, *c, *d]) as f32),
+        (Some('d'), [a, b, c, d, e, f, g, h, ..]) => {
+            Some(f64::from_be_bytes([*a, *b, *c, *d, *e, *f, *g, *h]) as f32)
+        }
+        (Some('T'), _) => Some(1.0),
+        (Some('F'), _) => Some(0.0),
+        _ => None,
+    };
+    out.push((address.to_string(), value));
+}
+
+// A NUL-terminated string padded to 4 bytes, and what follows it.
+fn osc_string(data: &[u8]) -> Option<(&str, &[u8])> {
+    let end = data.iter().position(|byte| *byte == 0)?;
+    let text = std::str::from_utf8(&data[..end]).ok()?;
+    let padded = (end / 4 + 1) * 4;
+    Some((text, data.get(padded..).unwrap_or_default()))
+}
+
+#[cfg(feature = "midi")]
+pub use midi::{MidiListener, MidiMap};
+
+#[cfg(feature = "midi")]
+mod midi {
+    use anyhow::{anyhow, bail, Result};
+    use midir::{MidiInput, MidiInputConnection};
+
+    use super::LiveControl;
+    use crate::error::ErrorKind;
+
+    // Which control-change numbers drive what. CC values (0..127) are scaled
+    // onto each parameter's range; messages on every channel are accepted.
+    #[derive(Debug, Clone, Default)]
+    pub struct MidiMap {
+        params: Vec<(u8, String, f32, f32)>,
+        scrub: Option<u8>,
+    }
+
+    impl MidiMap {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn with_param(mut self, cc: u8, name: impl Into<String>, min: f32, max: f32) -> Self {
+            self.params.push((cc, name.into(), min, max));
+            self
+        }
+
+        // A fader or knob that moves the playhead across the preview range.
+        pub fn with_scrub(mut self, cc: u8) -> Self {
+            self.scrub = Some(cc);
+            self
+        }
+    }
+
+    // Keeps the MIDI connection open; dropping it disconnects.
+    pub struct MidiListener {
+        _connection: MidiInputConnection<()>,
+    }
+
+    impl LiveControl {
+        // Connects to the first MIDI input whose name contains `port`.
+        pub fn listen_midi(&self, port: &str, map: MidiMap) -> Result<MidiListener> {
+            let input = MidiInput::new("script_2_script")
+                .map_err(|err| anyhow!("failed to open MIDI input: {err}"))?;
+            let Some(found) = input.ports().into_iter().find(|candidate| {
+                input
+                    .port_name(candidate)
+                    .is_ok_and(|name| name.contains(port))
+            }) else {
+                bail!(ErrorKind::MissingAsset.error(format!("no MIDI input matching \"{port}\"")));
+            };
+            let control = self.clone();
+            let connection = input
+                .connect(
+                    &found,
+                    "preview-control",
+                    move |_, message, _| {
+                        let [status, cc, value, ..] = *message else {
+                            return;
+                        };
+                        if status & 0xF0 != 0xB0 {
+                            return;
+                        }
+                        let value = value as f32 / 127.0;
+                        if map.scrub == Some(cc) {
+                            control.scrub(value);
+                        }
+                        for (_, name, min, max) in map.params.iter().filter(|param| param.0 == cc) {
+                            control.set(name, min + (max - min) * value);
+                        }
+                    },
+                    (),
+                )
+                .map_err(|err| anyhow!("failed to connect to MIDI input \"{port}\": {err}"))?;
+            Ok(MidiListener {
+                _connection: connection,
+            })
+        }
+    }
+}
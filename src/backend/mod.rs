@@ -1,4 +1,55 @@
+#[cfg(feature = "raylib")]
+pub mod alpha;
+pub mod assertions;
+#[cfg(feature = "software")]
+pub mod asset_bytes;
+pub mod cancel;
+pub mod clock;
+#[cfg(feature = "raylib")]
+pub mod composition;
+#[cfg(feature = "raylib")]
+pub mod contact_sheet;
+#[cfg(feature = "raylib")]
+pub mod debug_overlay;
+pub mod exif;
+pub mod font_coverage;
+pub mod golden;
+#[cfg(feature = "raylib")]
+pub mod headless;
+pub mod hit_regions;
+#[cfg(feature = "raylib")]
+pub mod layers;
+pub mod limits;
+#[cfg(feature = "raylib")]
+pub mod linear_light;
+pub mod live_control;
+#[cfg(feature = "raylib")]
+pub mod plugin_canvas;
+#[cfg(feature = "raylib")]
+pub mod post;
+#[cfg(feature = "raylib")]
+pub mod prefetch;
+pub mod premultiply;
+pub mod progress;
+#[cfg(feature = "raylib")]
 pub mod raylib_preview;
+#[cfg(feature = "raylib")]
 pub mod raylib_render;
+#[cfg(feature = "raylib")]
+pub mod readback;
+pub mod region;
+#[cfg(any(feature = "raylib", feature = "software"))]
+pub mod render_backend;
+pub mod report;
+#[cfg(feature = "raylib")]
 pub mod resources;
+#[cfg(feature = "software")]
+pub mod software;
+#[cfg(feature = "software")]
+pub mod software_text;
+pub mod text_layout;
+#[cfg(feature = "raylib")]
 pub mod text_render;
+pub mod watermark;
+#[cfg(feature = "wgpu")]
+pub mod wgpu_render;
@@ -0,0 +1,101 @@
+use anyhow::Result;
+use raylib::prelude::*;
+
+use crate::backend::raylib_render::to_raylib_color;
+use crate::backend::resources::ResourceCache;
+use crate::plugin::{Canvas, FrameMut, PluginRegistry};
+use crate::scene::{Color, PluginEffect, PluginObject, Transform, Vec2};
+
+struct RaylibCanvas<'a, D: RaylibDraw> {
+    d: &'a mut D,
+    width: u32,
+    height: u32,
+    opacity: f32,
+}
+
+impl<D: RaylibDraw> Canvas for RaylibCanvas<'_, D> {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn fill_rect(&mut self, center: Vec2, width: f32, height: f32, rotation: f32, color: Color) {
+        let center = graph_to_screen(center, self.width, self.height);
+        let rect = Rectangle::new(center.x, center.y, width, height);
+        let origin = Vector2::new(width / 2.0, height / 2.0);
+        self.d
+            .draw_rectangle_pro(rect, origin, rotation, to_raylib_color(color, self.opacity));
+    }
+
+    fn fill_circle(&mut self, center: Vec2, radius: f32, color: Color) {
+        let center = graph_to_screen(center, self.width, self.height);
+        self.d
+            .draw_circle_v(center, radius, to_raylib_color(color, self.opacity));
+    }
+
+    fn fill_triangle(&mut self, a: Vec2, b: Vec2, c: Vec2, color: Color) {
+        let a = graph_to_screen(a, self.width, self.height);
+        let mut b = graph_to_screen(b, self.width, self.height);
+        let mut c = graph_to_screen(c, self.width, self.height);
+        // raylib culls triangles that aren't counter-clockwise on screen.
+        let cross = (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x);
+        if cross > 0.0 {
+            std::mem::swap(&mut b, &mut c);
+        }
+        self.d
+            .draw_triangle(a, b, c, to_raylib_color(color, self.opacity));
+    }
+
+    fn line(&mut self, from: Vec2, to: Vec2, thickness: f32, color: Color) {
+        let from = graph_to_screen(from, self.width, self.height);
+        let to = graph_to_screen(to, self.width, self.height);
+        self.d
+            .draw_line_ex(from, to, thickness, to_raylib_color(color, self.opacity));
+    }
+}
+
+pub fn draw_plugin(
+    d: &mut impl RaylibDraw,
+    cache: &ResourceCache,
+    width: u32,
+    height: u32,
+    object: &PluginObject,
+    transform: &Transform,
+    local_t: f32,
+) -> Result<()> {
+    let plugin = cache.plugins()?.object(&object.kind)?;
+    let mut canvas = RaylibCanvas {
+        d,
+        width,
+        height,
+        opacity: transform.opacity,
+    };
+    plugin.draw(&mut canvas, &object.params, transform, local_t)
+}
+
+// Runs `effects` over a frame (or layer group) captured from a render
+// texture.
+pub fn apply_effects(
+    plugins: &PluginRegistry,
+    effects: &[PluginEffect],
+    time: f32,
+    rgba: &mut [u8],
+    width: u32,
+    height: u32,
+) -> Result<()> {
+    let mut frame = FrameMut {
+        pixels: rgba,
+        width,
+        height,
+        bottom_up: true,
+    };
+    for effect in effects {
+        plugins
+            .effect(&effect.kind)?
+            .apply(&mut frame, &effect.params, time)?;
+    }
+    Ok(())
+}
+
+fn graph_to_screen(pos: Vec2, width: u32, height: u32) -> Vector2 {
+    Vector2::new(width as f32 / 2.0 + pos.x, height as f32 / 2.0 - pos.y)
+}
@@ -0,0 +1,210 @@
+use std::ffi::CString;
+
+use anyhow::Result;
+use raylib::consts::{BlendMode as RaylibBlendMode, ShaderUniformDataType as UniformType};
+use raylib::prelude::*;
+
+use crate::backend::alpha::{begin_straight, GL_FUNC_ADD, GL_ONE, GL_ZERO};
+use crate::backend::resources::ResourceCache;
+use crate::scene::PostEffect;
+
+// Every pass reads the premultiplied frame as `texture0` and writes the
+// whole target, premultiplied.
+const VIGNETTE_FS: &str = r#"
+#version 330
+in vec2 fragTexCoord;
+uniform sampler2D texture0;
+uniform float amount;
+uniform float softness;
+out vec4 finalColor;
+void main() {
+    vec4 texel = texture(texture0, fragTexCoord);
+    // 0 at the centre, 1 in the corners.
+    float r = length(fragTexCoord - 0.5) / 0.70711;
+    float shade = 1.0 - amount * smoothstep(1.0 - max(softness, 0.001), 1.0, r);
+    finalColor = vec4(texel.rgb * clamp(shade, 0.0, 1.0), texel.a);
+}
+"#;
+
+const GRAIN_FS: &str = r#"
+#version 330
+in vec2 fragTexCoord;
+uniform sampler2D texture0;
+uniform float amount;
+uniform float seed;
+uniform float scale;
+out vec4 finalColor;
+float hash(vec3 p) {
+    p = fract(p * 0.1031);
+    p += dot(p, p.zyx + 31.32);
+    return fract((p.x + p.y) * p.z);
+}
+void main() {
+    vec4 texel = texture(texture0, fragTexCoord);
+    // One grain per output pixel, whatever the render scale.
+    float noise = hash(vec3(floor(gl_FragCoord.xy / scale), seed)) - 0.5;
+    vec3 rgb = texel.rgb + noise * amount * texel.a;
+    finalColor = vec4(clamp(rgb, 0.0, texel.a), texel.a);
+}
+"#;
+
+const BLOOM_FS: &str = r#"
+#version 330
+in vec2 fragTexCoord;
+uniform sampler2D texture0;
+uniform vec2 size;
+uniform float threshold;
+uniform float intensity;
+uniform float radius;
+out vec4 finalColor;
+vec3 bright(vec2 uv) {
+    vec3 rgb = texture(texture0, uv).rgb;
+    float luma = dot(rgb, vec3(0.2126, 0.7152, 0.0722));
+    return rgb * clamp((luma - threshold) / max(1.0 - threshold, 0.0001), 0.0, 1.0);
+}
+void main() {
+    vec4 texel = texture(texture0, fragTexCoord);
+    vec3 glow = vec3(0.0);
+    float total = 0.0;
+    for (int x = -4; x <= 4; x++) {
+        for (int y = -4; y <= 4; y++) {
+            vec2 offset = vec2(x, y) / 4.0;
+            float weight = exp(-2.0 * dot(offset, offset));
+            glow += bright(fragTexCoord + offset * radius / size) * weight;
+            total += weight;
+        }
+    }
+    glow *= intensity / total;
+    float alpha = max(texel.a, min(max(glow.r, max(glow.g, glow.b)), 1.0));
+    finalColor = vec4(min(texel.rgb + glow, vec3(alpha)), alpha);
+}
+"#;
+
+const LUT_FS: &str = r#"
+#version 330
+in vec2 fragTexCoord;
+uniform sampler2D texture0;
+uniform sampler2D lut;
+uniform float lutSize;
+uniform float amount;
+out vec4 finalColor;
+void main() {
+    vec4 texel = texture(texture0, fragTexCoord);
+    // Tables map straight colour.
+    vec3 rgb = texel.a > 0.0 ? clamp(texel.rgb / texel.a, 0.0, 1.0) : vec3(0.0);
+    float n = lutSize;
+    float blue = rgb.b * (n - 1.0);
+    float slice = floor(blue);
+    float next = min(slice + 1.0, n - 1.0);
+    // Texel centres, so the bilinear filter stays inside each slice.
+    vec2 cell = rgb.rg * (n - 1.0) + 0.5;
+    vec3 low = texture(lut, vec2((slice * n + cell.x) / (n * n), cell.y / n)).rgb;
+    vec3 high = texture(lut, vec2((next * n + cell.x) / (n * n), cell.y / n)).rgb;
+    vec3 graded = mix(low, high, blue - slice);
+    finalColor = vec4(mix(rgb, graded, clamp(amount, 0.0, 1.0)) * texel.a, texel.a);
+}
+"#;
+
+// The built-in shader an effect runs, and the name it's cached under.
+pub(crate) fn post_shader_source(effect: &PostEffect) -> (&'static str, &'static str) {
+    let source = match effect {
+        PostEffect::Vignette { .. } => VIGNETTE_FS,
+        PostEffect::Grain { .. } => GRAIN_FS,
+        PostEffect::Bloom { .. } => BLOOM_FS,
+        PostEffect::Lut { .. } => LUT_FS,
+    };
+    (effect.name(), source)
+}
+
+// Runs `effects` in order over `frame`, ping-ponging through `spare`; the
+// result ends up in `frame`. Both are the same size, at the render scale.
+// `time` seeds the grain.
+pub(crate) fn apply_post_effects(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    cache: &ResourceCache,
+    frame: &mut RenderTexture2D,
+    spare: &mut RenderTexture2D,
+    effects: &[PostEffect],
+    time: f32,
+) -> Result<()> {
+    let (width, height) = (frame.texture().width, frame.texture().height);
+    let scale = cache.render_scale() as f32;
+    for effect in effects {
+        let shader = *cache.post_shader(effect.name())?.as_ref();
+        {
+            let mut d = rl.begin_texture_mode(thread, spare.as_mut());
+            d.clear_background(Color::new(0, 0, 0, 0));
+            unsafe {
+                // The pass replaces every pixel rather than blending over.
+                raylib::ffi::rlSetBlendFactors(GL_ONE, GL_ZERO, GL_FUNC_ADD);
+                raylib::ffi::BeginBlendMode(RaylibBlendMode::BLEND_CUSTOM as i32);
+                raylib::ffi::BeginShaderMode(shader);
+            }
+            match effect {
+                PostEffect::Vignette { amount, softness } => {
+                    set_float(shader, "amount", *amount);
+                    set_float(shader, "softness", *softness);
+                }
+                PostEffect::Grain { amount } => {
+                    set_float(shader, "amount", *amount);
+                    // Whole frames apart at any frame rate.
+                    set_float(shader, "seed", (time * 1000.0).round() % 4096.0);
+                    set_float(shader, "scale", scale);
+                }
+                PostEffect::Bloom {
+                    threshold,
+                    intensity,
+                    radius,
+                } => {
+                    set_uniform(
+                        shader,
+                        "size",
+                        &[width as f32, height as f32],
+                        UniformType::SHADER_UNIFORM_VEC2,
+                    );
+                    set_float(shader, "threshold", *threshold);
+                    set_float(shader, "intensity", *intensity);
+                    set_float(shader, "radius", radius * cache.draw_scale());
+                }
+                PostEffect::Lut { path, amount } => {
+                    let lut = cache.lut(path)?;
+                    set_float(shader, "lutSize", lut.height as f32);
+                    set_float(shader, "amount", *amount);
+                    if let Ok(name) = CString::new("lut") {
+                        unsafe {
+                            let location = raylib::ffi::GetShaderLocation(shader, name.as_ptr());
+                            raylib::ffi::SetShaderValueTexture(shader, location, *lut.as_ref());
+                        }
+                    }
+                }
+            }
+            // Render textures are stored bottom-up; a negative source height
+            // flips them.
+            let texture = frame.texture();
+            let source = Rectangle::new(0.0, 0.0, width as f32, -(height as f32));
+            let dest = Rectangle::new(0.0, 0.0, width as f32, height as f32);
+            d.draw_texture_pro(texture, source, dest, Vector2::zero(), 0.0, Color::WHITE);
+            unsafe {
+                raylib::ffi::EndShaderMode();
+            }
+            begin_straight();
+        }
+        std::mem::swap(frame, spare);
+    }
+    Ok(())
+}
+
+fn set_float(shader: raylib::ffi::Shader, name: &str, value: f32) {
+    set_uniform(shader, name, &[value], UniformType::SHADER_UNIFORM_FLOAT);
+}
+
+fn set_uniform(shader: raylib::ffi::Shader, name: &str, data: &[f32], kind: UniformType) {
+    let Ok(name) = CString::new(name) else { return };
+    unsafe {
+        let location = raylib::ffi::GetShaderLocation(shader, name.as_ptr());
+        if location >= 0 {
+            raylib::ffi::SetShaderValue(shader, location, data.as_ptr().cast(), kind as i32);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use crate::backend::resources::{decode_image, DecodedImage, TextureKey};
+use crate::scene::{FontSource, Object};
+use crate::timeline::Timeline;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum PrefetchAsset {
+    Image(TextureKey),
+    Font(PathBuf),
+}
+
+enum Prefetched {
+    Image(DecodedImage),
+    // The font file; glyphs are rasterized when it's uploaded.
+    Font(Vec<u8>),
+}
+
+// Loads the images and fonts of clips about to start on its own thread, so
+// a clip's first frame doesn't wait on the disk. Images are decoded and
+// adjusted there too, leaving only the upload to the render thread.
+pub(crate) struct Prefetcher {
+    lookahead: f32,
+    // Every asset the planned timeline draws, by when its clip starts.
+    schedule: Vec<(f32, PrefetchAsset)>,
+    // Sent to the thread and not taken yet.
+    requested: HashSet<PrefetchAsset>,
+    ready: HashMap<PrefetchAsset, Option<Prefetched>>,
+    requests: Sender<PrefetchAsset>,
+    results: Receiver<(PrefetchAsset, Option<Prefetched>)>,
+}
+
+impl Prefetcher {
+    // The thread stops once the prefetcher is dropped, after the asset it's
+    // loading.
+    pub(crate) fn spawn(lookahead: f32) -> Self {
+        let (requests, queue) = mpsc::channel::<PrefetchAsset>();
+        let (finished, results) = mpsc::channel();
+        thread::spawn(move || {
+            for asset in queue {
+                // Failures are left for the render thread to hit and report
+                // when it loads the asset itself.
+                let loaded = match &asset {
+                    PrefetchAsset::Image((path, options)) => {
+                        decode_image(path, *options).ok().map(Prefetched::Image)
+                    }
+                    PrefetchAsset::Font(path) => fs::read(path).ok().map(Prefetched::Font),
+                };
+                if finished.send((asset, loaded)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            lookahead,
+            schedule: Vec::new(),
+            requested: HashSet::new(),
+            ready: HashMap::new(),
+            requests,
+            results,
+        }
+    }
+
+    // Notes when each asset in `timeline` is first drawn. Assets loaded for
+    // an earlier render and never drawn are dropped.
+    pub(crate) fn plan(&mut self, timeline: &Timeline) {
+        self.schedule.clear();
+        collect_asset_starts(timeline, None, &mut self.schedule);
+        self.schedule.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (asset, _) in self.ready.drain() {
+            self.requested.remove(&asset);
+        }
+    }
+
+    // Sends the thread the assets of clips starting from `t` to `t` plus the
+    // lookahead, skipping any `loaded` says the cache already has.
+    pub(crate) fn request_ahead(&mut self, t: f32, loaded: impl Fn(&PrefetchAsset) -> bool) {
+        let first = self.schedule.partition_point(|(start, _)| *start < t);
+        for (start, asset) in &self.schedule[first..] {
+            if *start > t + self.lookahead {
+                break;
+            }
+            if self.requested.contains(asset) || loaded(asset) {
+                continue;
+            }
+            if self.requests.send(asset.clone()).is_ok() {
+                self.requested.insert(asset.clone());
+            }
+        }
+    }
+
+    pub(crate) fn take_image(&mut self, key: &TextureKey) -> Option<DecodedImage> {
+        match self.take(&PrefetchAsset::Image(key.clone()))? {
+            Prefetched::Image(image) => Some(image),
+            Prefetched::Font(_) => None,
+        }
+    }
+
+    pub(crate) fn take_font(&mut self, path: &Path) -> Option<Vec<u8>> {
+        match self.take(&PrefetchAsset::Font(path.to_path_buf()))? {
+            Prefetched::Font(data) => Some(data),
+            Prefetched::Image(_) => None,
+        }
+    }
+
+    // Hands over a requested asset, waiting for the thread if it's still
+    // loading it. None when it wasn't requested or failed to load.
+    fn take(&mut self, asset: &PrefetchAsset) -> Option<Prefetched> {
+        if !self.requested.remove(asset) {
+            return None;
+        }
+        while !self.ready.contains_key(asset) {
+            let (done, loaded) = self.results.recv().ok()?;
+            self.ready.insert(done, loaded);
+        }
+        self.ready.remove(asset).flatten()
+    }
+}
+
+// Adds each image and font file in `timeline` with the start of a clip that
+// draws it. Assets inside a composition start with its clip (`outer_start`).
+fn collect_asset_starts(
+    timeline: &Timeline,
+    outer_start: Option<f32>,
+    starts: &mut Vec<(f32, PrefetchAsset)>,
+) {
+    for clip in timeline.layers.iter().flat_map(|layer| &layer.clips) {
+        let start = outer_start.unwrap_or(clip.start);
+        match &clip.object {
+            Object::Image(image) => {
+                let key = (image.path.clone(), image.load);
+                starts.push((start, PrefetchAsset::Image(key)));
+            }
+            Object::Text(text) => {
+                for source in text.font.sources() {
+                    if let FontSource::Path(path) = source {
+                        starts.push((start, PrefetchAsset::Font(path.clone())));
+                    }
+                }
+            }
+            Object::Composition(composition) => {
+                collect_asset_starts(&composition.timeline, Some(start), starts);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// Converts premultiplied RGBA read back from a target to straight alpha in
+// place. Opaque and fully transparent pixels are left as they are.
+pub fn unpremultiply(rgba: &mut [u8]) {
+    for pixel in rgba.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u32;
+        if alpha == 0 || alpha == 255 {
+            continue;
+        }
+        for channel in &mut pixel[..3] {
+            *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+    }
+}
+
+// The reverse of `unpremultiply`, for straight RGBA going back into a target.
+#[cfg(any(feature = "raylib", feature = "software"))]
+pub(crate) fn premultiply(rgba: &mut [u8]) {
+    for pixel in rgba.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u32;
+        if alpha == 255 {
+            continue;
+        }
+        for channel in &mut pixel[..3] {
+            *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+// Straight sRGB RGBA to premultiplied linear light, sRGB-encoded the way an
+// `Rgba8UnormSrgb` texture stores it, in place. Opaque pixels are the same
+// either way.
+#[cfg(feature = "software")]
+pub(crate) fn premultiply_linear(rgba: &mut [u8]) {
+    let decode = decode_table();
+    for pixel in rgba.chunks_exact_mut(4) {
+        let alpha = pixel[3];
+        if alpha == 255 {
+            continue;
+        }
+        let scale = alpha as f32 / 255.0;
+        for channel in &mut pixel[..3] {
+            *channel = encode(decode[*channel as usize] * scale);
+        }
+    }
+}
+
+// The reverse of `premultiply_linear`, for frames read back from a linear
+// target.
+#[cfg(feature = "software")]
+pub(crate) fn unpremultiply_linear(rgba: &mut [u8]) {
+    let decode = decode_table();
+    for pixel in rgba.chunks_exact_mut(4) {
+        let alpha = pixel[3];
+        if alpha == 0 || alpha == 255 {
+            continue;
+        }
+        let scale = 255.0 / alpha as f32;
+        for channel in &mut pixel[..3] {
+            *channel = encode(decode[*channel as usize] * scale);
+        }
+    }
+}
+
+// sRGB-encoded byte to linear light, for every byte.
+#[cfg(feature = "software")]
+pub(crate) fn decode_table() -> [f32; 256] {
+    std::array::from_fn(|value| srgb_to_linear(value as f32 / 255.0))
+}
+
+#[cfg(feature = "software")]
+pub(crate) fn encode(linear: f32) -> u8 {
+    (linear_to_srgb(linear) * 255.0).round() as u8
+}
+
+// The sRGB transfer curve, on values from 0 to 1.
+#[cfg(any(feature = "software", feature = "ffmpeg"))]
+pub(crate) fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(any(feature = "software", feature = "ffmpeg"))]
+pub(crate) fn linear_to_srgb(value: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
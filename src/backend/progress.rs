@@ -0,0 +1,159 @@
+#[cfg(feature = "raylib")]
+use std::time::Instant;
+
+use crate::backend::report::FrameStats;
+
+// Where a render has got to, sent after every frame handed out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressEvent {
+    // Frames handed out so far, counting this one.
+    pub frame: u32,
+    // Frames in the render, when the clock knows.
+    pub frames: Option<u32>,
+    // The timeline's frame rate, to turn frames into video time.
+    pub fps: u32,
+    pub percent: Option<f32>,
+    pub elapsed_secs: f32,
+    // Wall time per frame over the last 100 frames, once there have been
+    // that many.
+    pub secs_per_frame: Option<f32>,
+    // Time left at that pace, when the length is known.
+    pub eta_secs: Option<f32>,
+    // Where this frame's time went.
+    pub stats: FrameStats,
+}
+
+impl ProgressEvent {
+    // Video seconds rendered so far.
+    pub fn rendered_secs(&self) -> f32 {
+        self.frame as f32 / self.fps.max(1) as f32
+    }
+
+    // Video seconds in the render, when the length is known.
+    pub fn total_secs(&self) -> Option<f32> {
+        self.frames.map(|frames| frames as f32 / self.fps.max(1) as f32)
+    }
+}
+
+// Receives progress from `RaylibRender::render_timeline_rgba_with_progress`,
+// e.g. to move a progress bar or answer a status request. Closures taking a
+// `ProgressEvent` work too.
+pub trait ProgressReporter {
+    fn on_progress(&mut self, event: ProgressEvent);
+}
+
+impl<F: FnMut(ProgressEvent)> ProgressReporter for F {
+    fn on_progress(&mut self, event: ProgressEvent) {
+        self(event)
+    }
+}
+
+// Prints a progress line every `log_every_frames` frames, e.g.
+// `frames: 300/900 (33.3%) time 00:00:10/00:00:30 eta 00:01:02`.
+#[derive(Debug, Clone, Copy)]
+pub struct ConsoleProgress {
+    pub log_every_frames: u32,
+    pub show_time: bool,
+    pub show_eta: bool,
+}
+
+impl Default for ConsoleProgress {
+    fn default() -> Self {
+        Self {
+            log_every_frames: 100,
+            show_time: true,
+            show_eta: true,
+        }
+    }
+}
+
+impl ProgressReporter for ConsoleProgress {
+    fn on_progress(&mut self, event: ProgressEvent) {
+        if !event.frame.is_multiple_of(self.log_every_frames.max(1)) {
+            return;
+        }
+        let frame = event.frame;
+        let mut line = match (event.frames, event.percent) {
+            (Some(frames), Some(percent)) => format!("frames: {frame}/{frames} ({percent:.1}%)"),
+            _ => format!("frames: {frame}"),
+        };
+        if self.show_time
+            && let Some(total_secs) = event.total_secs()
+        {
+            line.push_str(&format!(
+                " time {}/{}",
+                format_hms(event.rendered_secs()),
+                format_hms(total_secs)
+            ));
+            if self.show_eta {
+                match event.eta_secs {
+                    Some(eta) => line.push_str(&format!(" eta {}", format_hms(eta))),
+                    None => line.push_str(&format!(" elapsed {}", format_hms(event.elapsed_secs))),
+                }
+            }
+        }
+        println!("{line}");
+    }
+}
+
+// Counts the frames of one render and times them for `ProgressEvent`s.
+#[cfg(feature = "raylib")]
+pub(crate) struct ProgressTracker {
+    frames: Option<u32>,
+    fps: u32,
+    frame: u32,
+    start: Instant,
+    // The pace is measured over windows of 100 frames.
+    window_frame: u32,
+    window_start: Instant,
+    secs_per_frame: Option<f32>,
+}
+
+#[cfg(feature = "raylib")]
+impl ProgressTracker {
+    pub(crate) fn new(frames: Option<u32>, fps: u32) -> Self {
+        let now = Instant::now();
+        Self {
+            frames,
+            fps,
+            frame: 0,
+            start: now,
+            window_frame: 0,
+            window_start: now,
+            secs_per_frame: None,
+        }
+    }
+
+    // Counts a frame handed out.
+    pub(crate) fn frame_done(&mut self, stats: FrameStats) -> ProgressEvent {
+        self.frame += 1;
+        if self.frame - self.window_frame >= 100 {
+            let window = self.frame - self.window_frame;
+            self.secs_per_frame = Some(self.window_start.elapsed().as_secs_f32() / window as f32);
+            self.window_frame = self.frame;
+            self.window_start = Instant::now();
+        }
+        let eta_secs = self
+            .frames
+            .zip(self.secs_per_frame)
+            .map(|(frames, secs)| frames.saturating_sub(self.frame) as f32 * secs);
+        ProgressEvent {
+            frame: self.frame,
+            frames: self.frames,
+            fps: self.fps,
+            percent: self.frames.map(|frames| self.frame as f32 / frames.max(1) as f32 * 100.0),
+            elapsed_secs: self.start.elapsed().as_secs_f32(),
+            secs_per_frame: self.secs_per_frame,
+            eta_secs,
+            stats,
+        }
+    }
+}
+
+fn format_hms(seconds: f32) -> String {
+    let total = seconds.max(0.0).round() as u64;
+    let h = total / 3600;
+    let m = (total % 3600) / 60;
+    let s = total % 60;
+    format!("{:02}:{:02}:{:02}", h, m, s)
+}
@@ -1,19 +1,38 @@
-use std::path::Path;
+use std::ffi::c_void;
+use std::sync::Arc;
 
 use anyhow::{bail, Result};
 use raylib::consts::TraceLogLevel;
 use raylib::prelude::*;
 
-use crate::backend::resources::ResourceCache;
+use crate::backend::alpha::{
+    begin_premultiplied, begin_straight, premultiplied_clear, premultiplied_tint, premultiply,
+    unpremultiply,
+};
+use crate::backend::clock::{Clock, WallClock};
+use crate::backend::composition::{draw_composition, render_compositions};
+use crate::backend::layers::{
+    draw_group_texture, draw_layer_texture, layer_runs, needs_target, render_layer_targets,
+};
+use crate::backend::plugin_canvas::{apply_effects, draw_plugin};
+use crate::backend::raylib_render::{capture_rgba, draw_shaded};
+use crate::backend::resources::{ClipKey, ResourceCache};
 use crate::backend::text_render::draw_text_block;
-use crate::scene::{Color, Object, Shape, Transform, Vec2};
-use crate::timeline::{SampledScene, Timeline};
+use crate::error::ErrorKind;
+use crate::plugin::PluginRegistry;
+use crate::scene::{Color, ImageObject, Object, Shape, Transform, Vec2};
+use crate::timeline::{DataSource, SampledClip, SampledScene, Timeline};
+
+// Height of the status bar along the bottom of the window, in pixels.
+const BAR_HEIGHT: f32 = 24.0;
 
 pub struct RaylibPreview {
     width: u32,
     height: u32,
     bg: Color,
     log_level: TraceLogLevel,
+    plugins: Option<Arc<PluginRegistry>>,
+    data: Option<Arc<dyn DataSource>>,
 }
 
 impl RaylibPreview {
@@ -32,9 +51,25 @@ impl RaylibPreview {
             height,
             bg,
             log_level,
+            plugins: None,
+            data: None,
         }
     }
 
+    // Plugin objects are drawn live. Frames with timeline effects are drawn
+    // offscreen and read back to run them, as a render does, which costs a
+    // readback per frame.
+    pub fn with_plugins(mut self, plugins: Arc<PluginRegistry>) -> Self {
+        self.plugins = Some(plugins);
+        self
+    }
+
+    // Supplies per-frame variables for clip bindings.
+    pub fn with_data_source(mut self, data: Arc<dyn DataSource>) -> Self {
+        self.data = Some(data);
+        self
+    }
+
     pub fn run(&self, timeline: &Timeline) -> Result<()> {
         self.run_range(timeline, 0.0, timeline.duration)
     }
@@ -43,20 +78,40 @@ impl RaylibPreview {
         self.run_with(timeline, start_time, end_time, |_| Ok(()))
     }
 
+    // Plays in real time from `start_time`. Space pauses, Left/Right scrub by
+    // a second, Comma/Period step a frame, Home/End jump to either end, and
+    // clicking or dragging on the status bar scrubs; H hides the bar.
     pub fn run_with<F>(
         &self,
         timeline: &Timeline,
         start_time: f32,
         end_time: f32,
-        mut per_frame: F,
+        per_frame: F,
     ) -> Result<()>
     where
         F: FnMut(f32) -> Result<()>,
     {
         if start_time < 0.0 || end_time <= start_time || end_time > timeline.duration {
-            bail!("start/end time must satisfy 0 <= start < end <= duration");
+            bail!(
+                ErrorKind::InvalidProject
+                    .error("start/end time must satisfy 0 <= start < end <= duration")
+            );
         }
+        let mut clock = WallClock::new(start_time, end_time);
+        self.run_clock(timeline, &mut clock, per_frame)
+    }
 
+    // Draws whatever time `clock` gives each frame until it runs out or the
+    // window closes. `per_frame` gets the time drawn.
+    pub fn run_clock<F>(
+        &self,
+        timeline: &Timeline,
+        clock: &mut dyn Clock,
+        mut per_frame: F,
+    ) -> Result<()>
+    where
+        F: FnMut(f32) -> Result<()>,
+    {
         let (mut rl, thread) = raylib::init()
             .size(self.width as i32, self.height as i32)
             .log_level(self.log_level)
@@ -65,17 +120,77 @@ impl RaylibPreview {
 
         rl.set_target_fps(timeline.fps);
         let mut cache = ResourceCache::new();
-        let dt = 1.0 / timeline.fps as f32;
-        let mut t = start_time;
+        if let Some(plugins) = &self.plugins {
+            plugins.validate(timeline)?;
+            cache.set_plugins(plugins.clone());
+        }
 
-        while t < end_time {
+        let frame_secs = 1.0 / timeline.fps.max(1) as f32;
+        let mut show_bar = true;
+        let mut scrubbing = false;
+        while let Some(t) = clock.tick() {
             if rl.window_should_close() {
                 break;
             }
-            let scene = timeline.sample(t)?;
-            self.draw_scene(&mut rl, &thread, &mut cache, &scene)?;
+            if rl.is_key_pressed(KeyboardKey::KEY_SPACE) {
+                clock.set_paused(!clock.is_paused());
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_LEFT) {
+                clock.seek(t - 1.0);
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_RIGHT) {
+                clock.seek(t + 1.0);
+            }
+            // Stepping pauses, so the frame stays up to look at.
+            let step = if rl.is_key_pressed(KeyboardKey::KEY_COMMA) {
+                -1.0
+            } else if rl.is_key_pressed(KeyboardKey::KEY_PERIOD) {
+                1.0
+            } else {
+                0.0
+            };
+            if step != 0.0 {
+                clock.set_paused(true);
+                clock.seek(((t / frame_secs).round() + step) * frame_secs);
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_HOME) {
+                clock.seek(0.0);
+            }
+            // Clocks stop short of the end rather than finishing.
+            if rl.is_key_pressed(KeyboardKey::KEY_END) {
+                clock.seek(timeline.duration);
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_H) {
+                show_bar = !show_bar;
+            }
+            // The bar spans the whole timeline, whatever range is playing.
+            let mouse = rl.get_mouse_position();
+            if show_bar
+                && rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT)
+                && mouse.y >= self.height as f32 - BAR_HEIGHT
+            {
+                scrubbing = true;
+            }
+            if !rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
+                scrubbing = false;
+            }
+            if scrubbing {
+                let fraction = (mouse.x / self.width as f32).clamp(0.0, 1.0);
+                clock.seek(fraction * timeline.duration);
+            }
+
+            let scene = match &self.data {
+                Some(data) => timeline.sample_from(t, data.as_ref())?,
+                None => timeline.sample(t)?,
+            };
+            let status = show_bar.then(|| PreviewStatus {
+                time: t,
+                frame: (t / frame_secs).round() as u32,
+                duration: timeline.duration,
+                paused: clock.is_paused(),
+            });
+            self.draw_scene(&mut rl, &thread, &mut cache, &scene, status)?;
             per_frame(t)?;
-            t += dt;
         }
 
         Ok(())
@@ -87,46 +202,216 @@ impl RaylibPreview {
         thread: &RaylibThread,
         cache: &mut ResourceCache,
         scene: &SampledScene,
+        status: Option<PreviewStatus>,
     ) -> Result<()> {
         cache.preload_for_scene(rl, thread, scene)?;
+        render_compositions(rl, thread, cache, scene, self.width, self.height)?;
+        render_layer_targets(rl, thread, cache, scene, self.width, self.height)?;
+        // Effects need the frame's pixels, so the frame is drawn offscreen.
+        let effects = !scene.effects.is_empty();
+        let key = scene as *const SampledScene as *const ();
+        if effects {
+            let (slot, mut target) =
+                cache.take_offscreen_target(rl, thread, key, self.width, self.height)?;
+            let result = self.draw_effects_target(rl, thread, cache, &mut target, scene);
+            cache.return_offscreen_target(slot, target);
+            result?;
+        }
 
         let mut d = rl.begin_drawing(thread);
         d.clear_background(to_raylib_color(self.bg, 1.0));
+        begin_straight();
+        if effects {
+            let target = cache.offscreen_texture(key)?;
+            let (w, h) = (self.width as f32, self.height as f32);
+            // Render textures are stored bottom-up; a negative source height flips them.
+            let source = Rectangle::new(0.0, 0.0, w, -h);
+            let dest = Rectangle::new(0.0, 0.0, w, h);
+            begin_premultiplied();
+            let tint = premultiplied_tint(1.0);
+            d.draw_texture_pro(target.texture(), source, dest, Vector2::zero(), 0.0, tint);
+            begin_straight();
+        } else {
+            self.draw_layers(&mut d, cache, scene)?;
+        }
 
-        for layer in &scene.layers {
-            for clip in &layer.clips {
-                draw_object(
-                    &mut d,
-                    cache,
-                    self.width,
-                    self.height,
-                    &clip.object,
-                    &clip.transform,
-                )?;
-            }
+        if let Some(status) = status {
+            status.draw(&mut d, self.width, self.height);
         }
+        Ok(())
+    }
 
+    // Draws the scene into `target`, then runs the timeline's effects over
+    // it.
+    fn draw_effects_target(
+        &self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        cache: &ResourceCache,
+        target: &mut RenderTexture2D,
+        scene: &SampledScene,
+    ) -> Result<()> {
+        {
+            let mut d = rl.begin_texture_mode(thread, target.as_mut());
+            d.clear_background(premultiplied_clear(self.bg));
+            begin_straight();
+            self.draw_layers(&mut d, cache, scene)?;
+        }
+        let (width, height) = (self.width, self.height);
+        let mut rgba = capture_rgba(target, width, height)?;
+        unpremultiply(&mut rgba);
+        apply_effects(
+            cache.plugins()?,
+            &scene.effects,
+            scene.time,
+            &mut rgba,
+            width,
+            height,
+        )?;
+        premultiply(&mut rgba);
+        unsafe {
+            raylib::ffi::UpdateTexture(*target.texture().as_ref(), rgba.as_ptr() as *const c_void);
+        }
         Ok(())
     }
+
+    fn draw_layers(
+        &self,
+        d: &mut impl RaylibDraw,
+        cache: &ResourceCache,
+        scene: &SampledScene,
+    ) -> Result<()> {
+        for (first, run) in layer_runs(scene) {
+            // Groups were drawn into their own targets, members and all.
+            if let Some(group) = run[0].group {
+                draw_group_texture(d, cache, self.width, self.height, &scene.groups[group])?;
+                continue;
+            }
+            // Matte sources are only drawn into their matted layer's target.
+            let mut skip_matte_source = false;
+            for (index, layer) in (first..).zip(run) {
+                if skip_matte_source {
+                    skip_matte_source = false;
+                    continue;
+                }
+                if needs_target(layer) {
+                    draw_layer_texture(d, cache, self.width, self.height, layer)?;
+                    skip_matte_source = layer.matte.is_some();
+                    continue;
+                }
+                for (clip_index, clip) in layer.clips.iter().enumerate() {
+                    let key = ClipKey::new(scene, index, clip_index);
+                    draw_clip(d, cache, self.width, self.height, &layer.transform, clip, key)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// What the status bar shows: the time and frame drawn, and how far through
+// the timeline they are.
+struct PreviewStatus {
+    time: f32,
+    frame: u32,
+    duration: f32,
+    paused: bool,
+}
+
+impl PreviewStatus {
+    fn draw(&self, d: &mut RaylibDrawHandle, width: u32, height: u32) {
+        let top = height as f32 - BAR_HEIGHT;
+        let played = width as f32 * (self.time / self.duration.max(1e-6)).clamp(0.0, 1.0);
+        d.draw_rectangle_rec(
+            Rectangle::new(0.0, top, width as f32, BAR_HEIGHT),
+            raylib::prelude::Color::new(0, 0, 0, 160),
+        );
+        d.draw_rectangle_rec(
+            Rectangle::new(0.0, top, played, 3.0),
+            raylib::prelude::Color::new(90, 160, 255, 255),
+        );
+        let paused = if self.paused { "  paused" } else { "" };
+        let text = format!(
+            "{:.2}s / {:.2}s  frame {}{paused}",
+            self.time, self.duration, self.frame
+        );
+        let text_color = raylib::prelude::Color::new(220, 220, 225, 255);
+        d.draw_text(&text, 8, top as i32 + 7, 12, text_color);
+    }
 }
 
+fn draw_clip(
+    d: &mut impl RaylibDraw,
+    cache: &ResourceCache,
+    width: u32,
+    height: u32,
+    layer_transform: &Transform,
+    clip: &SampledClip,
+    key: ClipKey,
+) -> Result<()> {
+    let mut transform = layer_transform.compose(&clip.transform);
+    let mut scissor = None;
+    if let Some(transition) = &clip.transition {
+        let offset = transition.slide_offset(width as f32, height as f32);
+        transform.pos.x += offset.x;
+        transform.pos.y += offset.y;
+        scissor = transition.wipe_rect(width as f32, height as f32);
+    }
+
+    if let Some((x, y, w, h)) = scissor {
+        unsafe {
+            raylib::ffi::BeginScissorMode(
+                x.round() as i32,
+                y.round() as i32,
+                w.round() as i32,
+                h.round() as i32,
+            );
+        }
+    }
+    let result = draw_shaded(clip, cache, || match &clip.nested {
+        Some(nested) => draw_composition(d, cache, width, height, nested, &transform),
+        None => draw_object(
+            d,
+            cache,
+            width,
+            height,
+            &clip.object,
+            &transform,
+            clip.local_time,
+            key,
+        ),
+    });
+    if scissor.is_some() {
+        unsafe {
+            raylib::ffi::EndScissorMode();
+        }
+    }
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
 fn draw_object(
-    d: &mut RaylibDrawHandle,
+    d: &mut impl RaylibDraw,
     cache: &ResourceCache,
     width: u32,
     height: u32,
     object: &Object,
     transform: &Transform,
+    local_t: f32,
+    key: ClipKey,
 ) -> Result<()> {
     match object {
         Object::Shape(shape) => draw_shape(d, width, height, shape, transform),
-        Object::Image(image) => draw_image(d, cache, width, height, &image.path, transform),
-        Object::Text(text) => draw_text_block(d, cache, width, height, text, transform),
+        Object::Image(image) => draw_image(d, cache, width, height, image, transform),
+        Object::Text(text) => draw_text_block(d, cache, width, height, text, transform, key),
+        // Drawn from the clip's sampled `nested` scene in `draw_clip`.
+        Object::Composition(_) => Ok(()),
+        Object::Plugin(plugin) => draw_plugin(d, cache, width, height, plugin, transform, local_t),
     }
 }
 
 fn draw_shape(
-    d: &mut RaylibDrawHandle,
+    d: &mut impl RaylibDraw,
     width: u32,
     height: u32,
     shape: &Shape,
@@ -159,27 +444,34 @@ fn draw_shape(
 }
 
 fn draw_image(
-    d: &mut RaylibDrawHandle,
+    d: &mut impl RaylibDraw,
     cache: &ResourceCache,
     width: u32,
     height: u32,
-    path: &Path,
+    image: &ImageObject,
     transform: &Transform,
 ) -> Result<()> {
-    let texture = cache.get_texture(path)?;
-    let tex_w = texture.width as f32;
-    let tex_h = texture.height as f32;
+    let loaded = cache.get_texture(image)?;
+    let texture = &loaded.texture;
 
-    let w = tex_w * transform.scale.x;
-    let h = tex_h * transform.scale.y;
+    // Downscaled textures still cover the image's full size.
+    let w = loaded.width * transform.scale.x;
+    let h = loaded.height * transform.scale.y;
     let center = graph_to_screen(transform.pos, width, height);
 
-    let source = Rectangle::new(0.0, 0.0, tex_w, tex_h);
+    let source = Rectangle::new(0.0, 0.0, texture.width as f32, texture.height as f32);
     let dest = Rectangle::new(center.x, center.y, w, h);
     let origin = Vector2::new(w / 2.0, h / 2.0);
 
-    let tint = to_raylib_color(Color::WHITE, transform.opacity);
-    d.draw_texture_pro(texture, source, dest, origin, transform.rotation, tint);
+    if image.load.premultiply {
+        begin_premultiplied();
+        let tint = premultiplied_tint(transform.opacity);
+        d.draw_texture_pro(texture, source, dest, origin, transform.rotation, tint);
+        begin_straight();
+    } else {
+        let tint = to_raylib_color(Color::WHITE, transform.opacity);
+        d.draw_texture_pro(texture, source, dest, origin, transform.rotation, tint);
+    }
     Ok(())
 }
 
@@ -1,57 +1,537 @@
+use std::collections::{HashSet, VecDeque};
+use std::ffi::CString;
+use std::panic;
 use std::path::Path;
+use std::sync::Arc;
 
 use anyhow::{bail, Context, Result};
-use raylib::consts::{PixelFormat, TraceLogLevel};
+use raylib::consts::{PixelFormat, ShaderUniformDataType as UniformType, TraceLogLevel};
 use raylib::prelude::*;
 use std::time::Instant;
 
-use crate::backend::resources::ResourceCache;
-use crate::backend::text_render::draw_text_block;
-use crate::scene::{Color, Object, Shape, Transform, Vec2};
-use crate::timeline::{SampledScene, Timeline};
+use crate::backend::alpha::{
+    begin_premultiplied, begin_straight, premultiplied_clear, premultiplied_tint, unpremultiply,
+};
+use crate::backend::assertions::check_assertion;
+use crate::backend::cancel::CancelToken;
+use crate::backend::clock::{Clock, FrameClock};
+use crate::backend::composition::{draw_composition, render_compositions};
+use crate::backend::debug_overlay::{draw_debug_overlay, DebugFrame, DebugOptions};
+use crate::backend::headless::{use_null_platform, DisplayMode};
+use crate::backend::hit_regions::{
+    scene_hit_regions, scene_outlines, HitRegion, HitRegionFrame, HitRegionTrack,
+};
+use crate::backend::layers::{draw_layers, render_layer_targets};
+use crate::backend::limits::{LimitPolicy, RenderLimits};
+use crate::backend::linear_light::LinearLight;
+use crate::backend::plugin_canvas::{apply_effects, draw_plugin};
+use crate::backend::post::apply_post_effects;
+use crate::backend::progress::{ProgressReporter, ProgressTracker};
+use crate::backend::readback::Readback;
+use crate::backend::region::RenderRegion;
+use crate::backend::render_backend::{clip_placement, RenderBackend};
+use crate::backend::report::{CacheStats, FrameStats, RenderReport};
+use crate::backend::watermark::Watermark;
+use crate::backend::resources::{ClipKey, ResourceCache};
+use crate::backend::text_render::{draw_text_block, text_block_size};
+use crate::error::ErrorKind;
+use crate::plugin::PluginRegistry;
+use crate::scene::{Color, ImageObject, Object, Shape, Transform, UniformValue, Vec2};
+use crate::timeline::{DataSource, SampledClip, SampledScene, Severity, Timeline};
+
+// Beyond this the targets get too large for most GPUs at 4K.
+const MAX_RENDER_SCALE: u32 = 4;
+const TARGET_ERROR: &str =
+    "failed to create render texture; frames larger than the GPU allows need a tile size";
 
 pub struct RaylibRender {
     rl: RaylibHandle,
     thread: RaylibThread,
     render_texture: RenderTexture2D,
+    // The other half of the post effect ping-pong, made on first use.
+    post_texture: Option<RenderTexture2D>,
     width: u32,
     height: u32,
     bg: Color,
     cache: ResourceCache,
+    report: RenderReport,
+    data: Option<Arc<dyn DataSource>>,
+    cancel: Option<CancelToken>,
+    limits: RenderLimits,
+    // Set under `LimitPolicy::Degrade` once a frame runs past the time limit.
+    skip_effects: bool,
+    watermark: Option<Watermark>,
+    record_hit_regions: bool,
+    hit_regions: Option<HitRegionTrack>,
+    region: Option<RenderRegion>,
+    // Frames are drawn in tiles of at most this many pixels a side.
+    tile_size: Option<u32>,
+    // The tile being drawn, in place of the region.
+    tile: Option<RenderRegion>,
+    // No window was opened, so there's nowhere to show frames.
+    headless: bool,
+    show_frames: bool,
+    debug: DebugOptions,
+    linear_light: bool,
+    // The targets and shaders for it, made on first use.
+    linear: Option<LinearLight>,
+    // Asynchronous frame capture, when the GL context has buffer objects.
+    readback: Option<Readback>,
+    // The frame handed to `on_frame`, and a second buffer the capture steps
+    // ping-pong with. Both keep their allocations from frame to frame.
+    frame: Vec<u8>,
+    spare: Vec<u8>,
+}
+
+// Everything a `RaylibRender` can be set up with before its first frame,
+// e.g. `RaylibRender::builder(1920, 1080).with_display(DisplayMode::Headless)
+// .with_render_scale(2).build()?`. Each option is the matching `set_*` call
+// on the renderer, and `build` fails as that would.
+#[derive(Debug, Clone)]
+pub struct RaylibRenderBuilder {
+    width: u32,
+    height: u32,
+    bg: Color,
+    log_level: TraceLogLevel,
+    display: DisplayMode,
+    render_scale: u32,
+    resolution_scale: f32,
+    region: Option<RenderRegion>,
+    tile_size: Option<u32>,
+    show_frames: bool,
+    debug: DebugOptions,
+    limits: RenderLimits,
+    texture_budget: Option<u64>,
+    prefetch: Option<f32>,
+    linear_light: bool,
+}
+
+impl RaylibRenderBuilder {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            bg: Color::BLACK,
+            log_level: TraceLogLevel::LOG_ERROR,
+            display: DisplayMode::Auto,
+            render_scale: 1,
+            resolution_scale: 1.0,
+            region: None,
+            tile_size: None,
+            show_frames: false,
+            debug: DebugOptions::default(),
+            limits: RenderLimits::default(),
+            texture_budget: None,
+            prefetch: None,
+            linear_light: false,
+        }
+    }
+
+    pub fn with_background(mut self, bg: Color) -> Self {
+        self.bg = bg;
+        self
+    }
+
+    // Lower it to see raylib's output, e.g. shader compiler errors.
+    pub fn with_log_level(mut self, level: TraceLogLevel) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    // `DisplayMode::Headless` renders on machines without X11 or Wayland.
+    pub fn with_display(mut self, display: DisplayMode) -> Self {
+        self.display = display;
+        self
+    }
+
+    pub fn with_render_scale(mut self, scale: u32) -> Self {
+        self.render_scale = scale;
+        self
+    }
+
+    pub fn with_resolution_scale(mut self, scale: f32) -> Self {
+        self.resolution_scale = scale;
+        self
+    }
+
+    pub fn with_region(mut self, region: Option<RenderRegion>) -> Self {
+        self.region = region;
+        self
+    }
+
+    // Also sizes the window and first render target to a tile, so frames
+    // too large for the GPU can be rendered at all.
+    pub fn with_tile_size(mut self, size: Option<u32>) -> Self {
+        self.tile_size = size;
+        self
+    }
+
+    pub fn with_show_frames(mut self, show: bool) -> Self {
+        self.show_frames = show;
+        self
+    }
+
+    pub fn with_debug_options(mut self, options: DebugOptions) -> Self {
+        self.debug = options;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: RenderLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_texture_budget(mut self, bytes: Option<u64>) -> Self {
+        self.texture_budget = bytes;
+        self
+    }
+
+    pub fn with_prefetch(mut self, lookahead_secs: Option<f32>) -> Self {
+        self.prefetch = lookahead_secs;
+        self
+    }
+
+    pub fn with_linear_light(mut self, linear: bool) -> Self {
+        self.linear_light = linear;
+        self
+    }
+
+    pub fn build(self) -> Result<RaylibRender> {
+        let mut render = RaylibRender::open(&self)?;
+        // Each of these remakes the render target.
+        if self.render_scale != 1 || self.region.is_some() || self.resolution_scale != 1.0 {
+            render.set_render_scale(self.render_scale)?;
+            render.set_region(self.region)?;
+            render.set_resolution_scale(self.resolution_scale)?;
+        }
+        render.set_show_frames(self.show_frames)?;
+        render.set_debug_options(self.debug);
+        render.set_limits(self.limits);
+        render.set_texture_budget(self.texture_budget);
+        render.set_prefetch(self.prefetch)?;
+        render.set_linear_light(self.linear_light);
+        Ok(render)
+    }
 }
 
 impl RaylibRender {
+    // A renderer with the default options; `builder` for the rest.
     pub fn new(width: u32, height: u32, bg: Color) -> Result<Self> {
-        Self::new_with_log_level(width, height, bg, TraceLogLevel::LOG_ERROR)
+        Self::builder(width, height).with_background(bg).build()
     }
 
-    pub fn new_with_log_level(
-        width: u32,
-        height: u32,
-        bg: Color,
-        log_level: TraceLogLevel,
-    ) -> Result<Self> {
-        let (mut rl, thread) = raylib::init()
-            .size(width as i32, height as i32)
-            .log_level(log_level)
-            .title("Rust Render (offline)")
-            .build();
+    pub fn builder(width: u32, height: u32) -> RaylibRenderBuilder {
+        RaylibRenderBuilder::new(width, height)
+    }
+
+    // The window or headless context and the render target.
+    fn open(options: &RaylibRenderBuilder) -> Result<Self> {
+        let (width, height, bg) = (options.width, options.height, options.bg);
+        check_tile_size(options.tile_size)?;
+        // Only a tile is ever drawn at once.
+        let (target_w, target_h) = match options.tile_size {
+            Some(size) => (width.min(size), height.min(size)),
+            None => (width, height),
+        };
+        let log_level = options.log_level;
+        let headless = options.display.is_headless();
+        if headless {
+            use_null_platform();
+        }
+        // raylib panics when it can't create the window or context.
+        let (mut rl, thread) = panic::catch_unwind(|| {
+            raylib::init()
+                .size(target_w as i32, target_h as i32)
+                .log_level(log_level)
+                .title("Rust Render (offline)")
+                .build()
+        })
+        .map_err(|_| {
+            let reason = if headless {
+                "failed to start headless rendering; it needs libOSMesa (Mesa's software \
+                 renderer), or run under xvfb-run"
+            } else {
+                "failed to open a window to render in; without a display, use \
+                 DisplayMode::Headless"
+            };
+            ErrorKind::Gpu.error(reason)
+        })?;
 
         let render_texture = rl
-            .load_render_texture(&thread, width, height)
-            .context("failed to create render texture")?;
+            .load_render_texture(&thread, target_w, target_h)
+            .context(ErrorKind::Gpu.error(TARGET_ERROR))?;
 
         Ok(Self {
             rl,
             thread,
             render_texture,
+            post_texture: None,
             width,
             height,
             bg,
             cache: ResourceCache::new(),
+            report: RenderReport::default(),
+            data: None,
+            cancel: None,
+            limits: RenderLimits::default(),
+            skip_effects: false,
+            watermark: None,
+            record_hit_regions: false,
+            hit_regions: None,
+            region: None,
+            tile_size: options.tile_size,
+            tile: None,
+            headless,
+            show_frames: false,
+            debug: DebugOptions::default(),
+            linear_light: false,
+            linear: None,
+            readback: Readback::new(),
+            frame: Vec::new(),
+            spare: Vec::new(),
         })
     }
 
+    // Stats for the most recent `render_timeline_rgba*` call.
+    pub fn last_report(&self) -> &RenderReport {
+        &self.report
+    }
+
+    // Registry used to draw `Object::Plugin` clips and apply timeline effects.
+    pub fn set_plugins(&mut self, plugins: Arc<PluginRegistry>) {
+        self.cache.set_plugins(plugins);
+    }
+
+    // Supplies per-frame variables for clip bindings.
+    pub fn set_data_source(&mut self, data: Arc<dyn DataSource>) {
+        self.data = Some(data);
+    }
+
+    // Checked before each frame; cancelling ends the render early, without
+    // an error, once the frames already drawn are handed out.
+    pub fn set_cancel_token(&mut self, cancel: CancelToken) {
+        self.cancel = Some(cancel);
+    }
+
+    // Caps the GPU memory cached images keep; see
+    // `ResourceCache::set_texture_budget`. Timeline renders also free each
+    // image once no later clip draws it.
+    pub fn set_texture_budget(&mut self, bytes: Option<u64>) {
+        self.cache.set_texture_budget(bytes);
+    }
+
+    // Loads the images and fonts of clips starting up to `lookahead_secs`
+    // ahead on a background thread during timeline renders, so a clip's
+    // first frame doesn't wait on the disk. None turns it off.
+    pub fn set_prefetch(&mut self, lookahead_secs: Option<f32>) -> Result<()> {
+        if let Some(secs) = lookahead_secs
+            && !(secs.is_finite() && secs > 0.0)
+        {
+            bail!(ErrorKind::InvalidProject.error("prefetch lookahead must be positive"));
+        }
+        self.cache.set_prefetch(lookahead_secs);
+        Ok(())
+    }
+
+    // What the resource cache holds now, and its hits, misses and evictions
+    // so far.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    // Draws a diagnostic overlay (see `DebugOptions`) over every frame from
+    // here on. It's part of the output, hashes and all.
+    pub fn set_debug_options(&mut self, options: DebugOptions) {
+        self.debug = options;
+    }
+
+    pub fn debug_options(&self) -> DebugOptions {
+        self.debug
+    }
+
+    pub fn set_limits(&mut self, limits: RenderLimits) {
+        self.limits = limits;
+    }
+
+    // Blends and fades in linear light instead of on sRGB values, as
+    // `WgpuRender::set_linear_light` does, so antialiased edges don't get
+    // dark fringes and crossfades don't dip in brightness. Each clip then
+    // takes a pass of its own (see `LinearLight`). Off by default.
+    pub fn set_linear_light(&mut self, linear: bool) {
+        self.linear_light = linear;
+        if !linear {
+            self.linear = None;
+        }
+    }
+
+    pub fn linear_light(&self) -> bool {
+        self.linear_light
+    }
+
+    // Records where each named clip sits in every rendered frame (see
+    // `HitRegionTrack`), read back with `last_hit_regions`.
+    pub fn set_record_hit_regions(&mut self, record: bool) {
+        self.record_hit_regions = record;
+    }
+
+    // Hit regions from the most recent render, when recording.
+    pub fn last_hit_regions(&self) -> Option<&HitRegionTrack> {
+        self.hit_regions.as_ref()
+    }
+
+    // Marks every rendered frame with job metadata (see `Watermark`).
+    pub fn set_watermark(&mut self, watermark: Option<Watermark>) {
+        self.watermark = watermark;
+    }
+
+    // Shows each frame in the render window as it's drawn, to watch a long
+    // render; closing the window stops it early, as a `CancelToken` would.
+    // Costs a buffer swap per frame.
+    pub fn set_show_frames(&mut self, show: bool) -> Result<()> {
+        if show && self.headless {
+            bail!(ErrorKind::InvalidProject
+                .error("showing frames needs a window, but rendering is headless"));
+        }
+        self.show_frames = show;
+        Ok(())
+    }
+
+    // Supersampling: frames are drawn `scale` times larger in each direction
+    // and averaged down before capture, which smooths the jagged edges of
+    // circles and rotated rects. Scene coordinates, hit regions and captured
+    // frames stay at the output size. 2 costs four times the fill rate.
+    pub fn set_render_scale(&mut self, scale: u32) -> Result<()> {
+        if !(1..=MAX_RENDER_SCALE).contains(&scale) {
+            bail!(ErrorKind::InvalidProject
+                .error(format!("render scale must be between 1 and {MAX_RENDER_SCALE}")));
+        }
+        self.cache.set_render_scale(scale);
+        self.resize_target()
+    }
+
+    pub fn render_scale(&self) -> u32 {
+        self.cache.render_scale()
+    }
+
+    // Draft renders: frames are drawn and handed out `scale` times the size in
+    // each direction, e.g. 0.25 for a quarter-size proxy, while scene
+    // coordinates, regions and hit regions stay in full-size pixels, so the
+    // project doesn't change. Stacks with the render scale.
+    pub fn set_resolution_scale(&mut self, scale: f32) -> Result<()> {
+        if scale.is_nan() || scale <= 0.0 || scale > 1.0 {
+            bail!(ErrorKind::InvalidProject
+                .error(format!("resolution scale must be above 0 and at most 1, got {scale}")));
+        }
+        self.cache.set_resolution_scale(scale);
+        self.resize_target()
+    }
+
+    pub fn resolution_scale(&self) -> f32 {
+        self.cache.resolution_scale()
+    }
+
+    // Renders only `region` of the frame, e.g. to iterate on one corner of a
+    // 4K composition. Only the region is drawn into the frame, though layers
+    // with a blend mode or matte, and groups, are still drawn whole first.
+    // Post effects and effect plugins only see the region, so a vignette is
+    // centred on it. `None` goes back to full frames.
+    pub fn set_region(&mut self, region: Option<RenderRegion>) -> Result<()> {
+        if let Some(region) = &region {
+            region.check(self.width, self.height)?;
+            if self.tile_size.is_some() {
+                bail!(ErrorKind::InvalidProject.error("a region can't be rendered in tiles"));
+            }
+        }
+        self.region = region;
+        self.resize_target()
+    }
+
+    pub fn region(&self) -> Option<RenderRegion> {
+        self.region
+    }
+
+    // Draws each frame as tiles of at most `size` x `size` pixels and
+    // stitches them together, for frames larger than the GPU's texture size
+    // limit (often 8192 or 16384), e.g. 8K video or print-sized stills. The
+    // render target is a tile times the render scale. Layers with a blend
+    // mode or matte are still drawn whole, and post effects only see one
+    // tile at a time, so a vignette or blur shows the seams; effect plugins
+    // run on the stitched frame. Costs a pass over the scene per tile.
+    pub fn set_tile_size(&mut self, size: Option<u32>) -> Result<()> {
+        check_tile_size(size)?;
+        if size.is_some() && self.region.is_some() {
+            bail!(ErrorKind::InvalidProject.error("a region can't be rendered in tiles"));
+        }
+        self.tile_size = size;
+        self.resize_target()
+    }
+
+    pub fn tile_size(&self) -> Option<u32> {
+        self.tile_size
+    }
+
+    // Size of the frames handed out: the region's when rendering an unpadded
+    // region, otherwise `width` x `height`, both at the resolution scale.
+    pub fn output_size(&self) -> (u32, u32) {
+        match &self.region {
+            Some(region) if !region.pad => {
+                let (_, _, w, h) = self.output_rect();
+                (w, h)
+            }
+            _ => self.cache.scaled_size(self.width, self.height),
+        }
+    }
+
+    // The part of the frame that's drawn: x, y, width, height.
+    fn drawn_rect(&self) -> (u32, u32, u32, u32) {
+        match self.tile.as_ref().or(self.region.as_ref()) {
+            Some(region) => (region.x, region.y, region.width, region.height),
+            None => (0, 0, self.width, self.height),
+        }
+    }
+
+    // `drawn_rect` at the resolution scale. The edges are rounded rather than
+    // the size, so a region lines up with the pixels of the full frame.
+    fn output_rect(&self) -> (u32, u32, u32, u32) {
+        let (x, y, w, h) = self.drawn_rect();
+        let (frame_w, frame_h) = self.cache.scaled_size(self.width, self.height);
+        let scale = self.cache.resolution_scale();
+        let edge = |value: u32| (value as f32 * scale).round() as u32;
+        let span = |start: u32, size: u32, frame: u32| {
+            let first = edge(start).min(frame - 1);
+            (first, edge(start + size).clamp(first + 1, frame) - first)
+        };
+        let ((x, w), (y, h)) = (span(x, w, frame_w), span(y, h, frame_h));
+        (x, y, w, h)
+    }
+
+    fn resize_target(&mut self) -> Result<()> {
+        // Sized for a tile, when drawing in tiles.
+        self.tile = self.tile_size.map(|size| self.tiles(size)[0]);
+        let (w, h) = self.target_size();
+        self.tile = None;
+        self.render_texture = self
+            .rl
+            .load_render_texture(&self.thread, w, h)
+            .context(ErrorKind::Gpu.error(TARGET_ERROR))?;
+        self.post_texture = None;
+        Ok(())
+    }
+
+    // The tiles covering the frame, all the same size. The last in each row
+    // and column overlap the one before rather than run off the frame.
+    fn tiles(&self, size: u32) -> Vec<RenderRegion> {
+        let (w, h) = (size.min(self.width), size.min(self.height));
+        let starts = |frame: u32, size: u32| {
+            (0..frame)
+                .step_by(size as usize)
+                .map(move |start| start.min(frame - size))
+        };
+        starts(self.height, h)
+            .flat_map(|y| starts(self.width, w).map(move |x| RenderRegion::new(x, y, w, h)))
+            .collect()
+    }
+
     pub fn render_timeline_rgba(
         &mut self,
         timeline: &Timeline,
@@ -68,102 +548,717 @@ impl RaylibRender {
         )
     }
 
+    // `progress` hears about every frame handed out, e.g. a `ConsoleProgress`
+    // printing a line every 100 frames, or a closure updating a GUI.
     pub fn render_timeline_rgba_with_progress(
         &mut self,
         timeline: &Timeline,
         start_time: f32,
         end_time: f32,
-        progress: Option<RenderProgress>,
-        mut on_frame: impl FnMut(f32, &[u8]) -> Result<()>,
+        progress: Option<&mut dyn ProgressReporter>,
+        on_frame: impl FnMut(f32, &[u8]) -> Result<()>,
     ) -> Result<()> {
         if start_time < 0.0 || end_time <= start_time || end_time > timeline.duration {
-            bail!("start/end time must satisfy 0 <= start < end <= duration");
+            bail!(
+                ErrorKind::InvalidProject
+                    .error("start/end time must satisfy 0 <= start < end <= duration")
+            );
         }
+        let mut clock = FrameClock::new(timeline.fps, start_time, end_time);
+        self.render_with_clock(timeline, &mut clock, progress, on_frame)
+    }
 
-        let frames = ((end_time - start_time) * timeline.fps as f32).floor() as u32;
-        let progress = progress.unwrap_or_default();
-        let mut last_progress_frame = 0u32;
-        let mut last_100_frame = 0u32;
-        let mut last_100_time = Instant::now();
-        let mut per_frame_secs = None;
+    // Renders each time `clock` yields, e.g. from an `ExternalClock` following
+    // timecode. Progress events have totals only when the clock knows them.
+    pub fn render_with_clock(
+        &mut self,
+        timeline: &Timeline,
+        clock: &mut dyn Clock,
+        mut progress: Option<&mut dyn ProgressReporter>,
+        mut on_frame: impl FnMut(f32, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        if let Ok(plugins) = self.cache.plugins() {
+            plugins.validate(timeline)?;
+        }
+        let validation = timeline.validate();
+        validation.check()?;
+
+        let mut tracker = ProgressTracker::new(clock.frame_count(), timeline.fps);
         let overall_start = Instant::now();
+        self.report = RenderReport::default();
+        self.skip_effects = false;
+        self.cache.plan_releases(timeline);
+        self.cache.plan_prefetch(timeline);
+        self.hit_regions = self
+            .record_hit_regions
+            .then(|| HitRegionTrack::new(self.width, self.height, timeline.fps));
+        self.report
+            .warnings
+            .extend(validation.warnings().map(|issue| issue.to_string()));
 
-        for i in 0..frames {
-            let t = start_time + i as f32 / timeline.fps as f32;
-            let scene = timeline.sample(t)?;
-            let rgba = self.render_scene_to_rgba(&scene)?;
-            on_frame(t, &rgba)?;
-
-            if progress.enabled {
-                let frame_idx = i + 1;
-                if frame_idx - last_100_frame >= 100 {
-                    let elapsed = last_100_time.elapsed().as_secs_f32();
-                    let window = frame_idx - last_100_frame;
-                    per_frame_secs = Some(elapsed / window as f32);
-                    last_100_frame = frame_idx;
-                    last_100_time = Instant::now();
+        let mut warned_assertions = HashSet::new();
+        // With asynchronous readback a frame is copied off the GPU while the
+        // next one draws, so it's handed out once the next one is drawn or
+        // the clock runs out. Tiled frames are captured as they're drawn.
+        let depth = match &mut self.readback {
+            Some(readback) if self.tile_size.is_none() => {
+                readback.clear();
+                Readback::DEPTH
+            }
+            _ => 1,
+        };
+        let mut in_flight = VecDeque::new();
+        let mut ticking = true;
+        loop {
+            if ticking && self.stop_requested() {
+                self.report.cancelled = true;
+                ticking = false;
+            }
+            if ticking {
+                match clock.tick() {
+                    Some(t) => {
+                        let frame_start = Instant::now();
+                        let (stages, cache) = (self.report.stages, self.cache.stats());
+                        let (scene, regions) = self.queue_frame(timeline, t)?;
+                        let after = self.cache.stats();
+                        let stats = FrameStats {
+                            time: t,
+                            stages: self.report.stages.since(&stages),
+                            cache_hits: after.hits - cache.hits,
+                            cache_misses: after.misses - cache.misses,
+                            ..FrameStats::default()
+                        };
+                        in_flight.push_back((t, scene, regions, frame_start, stats));
+                    }
+                    None => ticking = false,
                 }
-
-                if frame_idx - last_progress_frame >= progress.log_every_frames {
-                    last_progress_frame = frame_idx;
-                    let percent = frame_idx as f32 / frames.max(1) as f32 * 100.0;
-                    let mut line = format!("frames: {frame_idx}/{frames} ({percent:.1}%)");
-
-                    if progress.show_time {
-                        let elapsed_secs = overall_start.elapsed().as_secs_f32();
-                        let rendered_secs = frame_idx as f32 / timeline.fps as f32;
-                        let total_secs = frames as f32 / timeline.fps as f32;
-                        line.push_str(&format!(
-                            " time {}/{}",
-                            format_hms(rendered_secs),
-                            format_hms(total_secs)
-                        ));
-                        if progress.show_eta {
-                            if let Some(pf) = per_frame_secs {
-                                let remaining_frames = frames.saturating_sub(frame_idx);
-                                let eta = remaining_frames as f32 * pf;
-                                line.push_str(&format!(" eta {}", format_hms(eta)));
-                            } else {
-                                line.push_str(&format!(" elapsed {}", format_hms(elapsed_secs)));
-                            }
-                        }
+            }
+            if ticking && in_flight.len() < depth {
+                continue;
+            }
+            let Some((t, scene, regions, frame_start, mut stats)) = in_flight.pop_front() else {
+                break;
+            };
+            let stages = self.report.stages;
+            self.collect_frame(&scene)?;
+            if let Some(regions) = regions {
+                // Warning assertions are reported at the first frame they fail.
+                for (index, assertion) in timeline.assertions.iter().enumerate() {
+                    if !assertion.applies_at(t) {
+                        continue;
+                    }
+                    let Some(message) =
+                        check_assertion(assertion, &regions, self.width, self.height)
+                    else {
+                        continue;
+                    };
+                    let message = format!("assertion failed at {t:.3}s: {message}");
+                    if assertion.severity == Severity::Error {
+                        bail!(ErrorKind::AssertionFailed.error(message));
+                    }
+                    if warned_assertions.insert(index) {
+                        self.report.warnings.push(message);
                     }
+                }
+                if let Some(track) = &mut self.hit_regions {
+                    track.frames.push(HitRegionFrame {
+                        frame: (t * timeline.fps as f32).round() as u32,
+                        time: t,
+                        regions,
+                    });
+                }
+            }
+            if let Some(max) = self.limits.max_texture_bytes {
+                let bytes = self.cache.stats().texture_bytes;
+                if bytes > max {
+                    let message = format!("textures use {bytes} bytes (max {max})");
+                    if self.over_limit("texture memory", message)? {
+                        self.cache.release_textures();
+                    }
+                }
+            }
 
-                    println!("{line}");
+            let stage = Instant::now();
+            on_frame(t, &self.frame)?;
+            self.report.stages.output_secs += stage.elapsed().as_secs_f64();
+            stats.stages.add(&self.report.stages.since(&stages));
+            stats.secs = frame_start.elapsed().as_secs_f64();
+            let slowest = self.report.slowest_frame.map(|frame| frame.secs);
+            if slowest.is_none_or(|secs| stats.secs > secs) {
+                self.report.slowest_frame = Some(stats);
+            }
+            if let Some(max) = self.limits.max_frame_secs {
+                let secs = stats.secs;
+                if secs > max {
+                    let message = format!("frame at {t:.3}s took {secs:.3}s (max {max}s)");
+                    if self.over_limit("frame time", message)? {
+                        self.skip_effects = true;
+                    }
                 }
             }
+            self.report.frames += 1;
+            self.report.wall_secs = overall_start.elapsed().as_secs_f64();
+
+            let event = tracker.frame_done(stats);
+            if let Some(progress) = &mut progress {
+                progress.on_progress(event);
+            }
         }
 
+        self.report.wall_secs = overall_start.elapsed().as_secs_f64();
+        if self.report.wall_secs > 0.0 {
+            self.report.fps_achieved = self.report.frames as f64 / self.report.wall_secs;
+        }
+        self.report.cache = self.cache.stats();
+        self.report.warnings.extend(self.cache.take_warnings());
+
         Ok(())
     }
 
+    // Applies the limit policy to an overrun of `limit`: an error under
+    // `LimitPolicy::Abort`, otherwise a warning (the first overrun of each
+    // limit per render) and whether to degrade.
+    fn over_limit(&mut self, limit: &str, message: String) -> Result<bool> {
+        let message = format!("{limit} limit exceeded: {message}");
+        if self.limits.policy == LimitPolicy::Abort {
+            bail!(ErrorKind::LimitExceeded.error(message));
+        }
+        let prefix = format!("{limit} limit exceeded:");
+        if !self.report.warnings.iter().any(|warning| warning.starts_with(&prefix)) {
+            self.report.warnings.push(message);
+        }
+        Ok(self.limits.policy == LimitPolicy::Degrade)
+    }
+
+    // Renders the single frame at `time` and writes it as a PNG or JPEG (by
+    // extension), e.g. a poster frame or thumbnail, without ffmpeg. JPEGs
+    // drop the alpha channel.
+    pub fn render_frame_to_png(
+        &mut self,
+        timeline: &Timeline,
+        time: f32,
+        path: &Path,
+    ) -> Result<()> {
+        let rgba = self.render_frame_rgba(timeline, time)?;
+        let (width, height) = self.output_size();
+        write_still(&rgba, width, height, path)
+    }
+
+    // The single frame at `time`, bottom-up straight RGBA.
+    pub fn render_frame_rgba(&mut self, timeline: &Timeline, time: f32) -> Result<Vec<u8>> {
+        if !(0.0..=timeline.duration).contains(&time) {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "frame time {time}s is outside the timeline (0 to {}s)",
+                timeline.duration
+            )));
+        }
+        if let Ok(plugins) = self.cache.plugins() {
+            plugins.validate(timeline)?;
+        }
+        timeline.validate().check()?;
+        let scene = match &self.data {
+            Some(data) => timeline.sample_from(time, data.as_ref())?,
+            None => timeline.sample(time)?,
+        };
+        let mut rgba = Vec::new();
+        self.render_into(&scene, Some(timeline.fps), &mut rgba)?;
+        Ok(rgba)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
     pub fn render_scene_to_rgba(&mut self, scene: &SampledScene) -> Result<Vec<u8>> {
+        let mut rgba = Vec::new();
+        self.render_scene_into(scene, &mut rgba)?;
+        Ok(rgba)
+    }
+
+    // Renders `scene` into `out`, reusing its allocation, so a caller
+    // rendering frame after frame doesn't allocate a new one each time.
+    pub fn render_scene_into(&mut self, scene: &SampledScene, out: &mut Vec<u8>) -> Result<()> {
+        self.render_into(scene, None, out)
+    }
+
+    // `fps` numbers the frame in the debug overlay.
+    fn render_into(
+        &mut self,
+        scene: &SampledScene,
+        fps: Option<u32>,
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        match self.tile_size {
+            Some(size) => self.draw_tiles(scene, fps, size, out)?,
+            None => self.draw_scene(scene, fps)?,
+        }
+        let stage = Instant::now();
+        let (width, height) = self.target_size();
+        let mut spare = std::mem::take(&mut self.spare);
+        // Tiles are captured as they're drawn.
+        let captured = match self.tile_size {
+            Some(_) => Ok(()),
+            None => capture_rgba_into(&self.render_texture, width, height, out),
+        };
+        let result = captured.and_then(|()| self.finish_frame(out, &mut spare, scene));
+        self.spare = spare;
+        self.report.stages.capture_secs += stage.elapsed().as_secs_f64();
+        result
+    }
+
+    // Samples and draws the frame at `t`, and starts reading it back when
+    // that's asynchronous. Also gives its hit regions, when they're recorded
+    // or an assertion checks them.
+    fn queue_frame(
+        &mut self,
+        timeline: &Timeline,
+        t: f32,
+    ) -> Result<(SampledScene, Option<Vec<HitRegion>>)> {
+        let stage = Instant::now();
+        let mut scene = match &self.data {
+            Some(data) => timeline.sample_from(t, data.as_ref())?,
+            None => timeline.sample(t)?,
+        };
+        self.report.stages.sample_secs += stage.elapsed().as_secs_f64();
+
+        if let Some(max) = self.limits.max_clips_per_frame {
+            let count = scene.clip_count();
+            if count > max {
+                let message = format!("frame at {t:.3}s draws {count} clips (max {max})");
+                if self.over_limit("clip", message)? {
+                    scene.truncate_clips(max);
+                }
+            }
+        }
+        if let Some(size) = self.tile_size {
+            let mut frame = std::mem::take(&mut self.frame);
+            let result = self.draw_tiles(&scene, Some(timeline.fps), size, &mut frame);
+            self.frame = frame;
+            result?;
+        } else {
+            self.draw_scene(&scene, Some(timeline.fps))?;
+            let (width, height) = self.target_size();
+            if let Some(readback) = &mut self.readback {
+                readback.start(&self.render_texture, width, height)?;
+            }
+            if self.show_frames {
+                self.show_frame();
+            }
+        }
+        // Images and text are measured from the resources just drawn.
+        let asserting = timeline.assertions.iter().any(|assertion| assertion.applies_at(t));
+        let regions = if self.hit_regions.is_some() || asserting {
+            let cache = &self.cache;
+            let regions = scene_hit_regions(&scene, self.width, self.height, |object, transform| {
+                measure_object(cache, object, transform)
+            })?;
+            Some(regions)
+        } else {
+            None
+        };
+        self.cache.release_ended(t);
+        Ok((scene, regions))
+    }
+
+    // Whether to stop before the next frame: the cancel token was set, or
+    // the window showing frames was closed.
+    fn stop_requested(&self) -> bool {
+        self.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
+            || (self.show_frames && self.rl.window_should_close())
+    }
+
+    // Copies the frame target into the window, fitted and centred.
+    fn show_frame(&mut self) {
+        let (w, h) = self.target_size();
+        let window_w = self.rl.get_screen_width() as f32;
+        let window_h = self.rl.get_screen_height() as f32;
+        let fit = (window_w / w as f32).min(window_h / h as f32);
+        let (fit_w, fit_h) = (w as f32 * fit, h as f32 * fit);
+        let dest = Rectangle::new((window_w - fit_w) / 2.0, (window_h - fit_h) / 2.0, fit_w, fit_h);
+        // Render textures are stored bottom-up; a negative source height flips them.
+        let source = Rectangle::new(0.0, 0.0, w as f32, -(h as f32));
+        let mut d = self.rl.begin_drawing(&self.thread);
+        d.clear_background(raylib::prelude::Color::BLACK);
+        begin_premultiplied();
+        let texture = self.render_texture.texture();
+        d.draw_texture_pro(texture, source, dest, Vector2::zero(), 0.0, premultiplied_tint(1.0));
+        begin_straight();
+    }
+
+    // Makes the oldest frame from `queue_frame` into `self.frame`.
+    fn collect_frame(&mut self, scene: &SampledScene) -> Result<()> {
+        let stage = Instant::now();
+        let (width, height) = self.target_size();
+        let (mut frame, mut spare) =
+            (std::mem::take(&mut self.frame), std::mem::take(&mut self.spare));
+        let captured = match &mut self.readback {
+            // Drawn and captured already by `queue_frame`.
+            _ if self.tile_size.is_some() => Ok(()),
+            Some(readback) => readback.finish_into(&mut frame),
+            None => capture_rgba_into(&self.render_texture, width, height, &mut frame),
+        };
+        let result = captured.and_then(|()| self.finish_frame(&mut frame, &mut spare, scene));
+        (self.frame, self.spare) = (frame, spare);
+        self.report.stages.capture_secs += stage.elapsed().as_secs_f64();
+        result
+    }
+
+    // Size of the frame target: the drawn part at the output resolution,
+    // supersampled.
+    fn target_size(&self) -> (u32, u32) {
+        let scale = self.cache.render_scale();
+        let (_, _, w, h) = self.output_rect();
+        (w * scale, h * scale)
+    }
+
+    // Draws `scene` and its post effects into the frame target, then the
+    // debug overlay when it's on.
+    fn draw_scene(&mut self, scene: &SampledScene, fps: Option<u32>) -> Result<()> {
+        let stage = Instant::now();
+        let post = !scene.post.is_empty() && !self.skip_effects;
         self.cache.preload_for_scene(&mut self.rl, &self.thread, scene)?;
+        if post {
+            self.cache.preload_post(&mut self.rl, &self.thread, &scene.post)?;
+        }
+        render_compositions(
+            &mut self.rl,
+            &self.thread,
+            &mut self.cache,
+            scene,
+            self.width,
+            self.height,
+        )?;
+        render_layer_targets(
+            &mut self.rl,
+            &self.thread,
+            &mut self.cache,
+            scene,
+            self.width,
+            self.height,
+        )?;
+        self.report.stages.preload_secs += stage.elapsed().as_secs_f64();
 
-        {
+        let stage = Instant::now();
+        let (x, y, _, _) = self.output_rect();
+        // Scene coordinates stay those of the whole frame; the region is
+        // moved to the target's corner.
+        let resolution = self.cache.resolution_scale();
+        let origin = (x as f32 / resolution, y as f32 / resolution);
+        self.cache.set_draw_origin(origin);
+        let result = if self.linear_light {
+            self.draw_linear(scene, origin)
+        } else {
             let mut d = self
                 .rl
                 .begin_texture_mode(&self.thread, self.render_texture.as_mut());
-            d.clear_background(to_raylib_color(self.bg, 1.0));
-
-            for layer in &scene.layers {
-                for clip in &layer.clips {
-                    draw_object(
-                        &mut d,
-                        &self.cache,
-                        self.width,
-                        self.height,
-                        &clip.object,
-                        &clip.transform,
-                    )?;
-                }
+            let _scaled = ScaledDraw::begin_at(self.cache.draw_scale(), origin);
+            d.clear_background(premultiplied_clear(self.bg));
+            begin_straight();
+            draw_layers(&mut d, &self.cache, self.width, self.height, scene)
+        };
+        self.cache.set_draw_origin((0.0, 0.0));
+        result?;
+        if post {
+            let (w, h) = self.target_size();
+            let reusable = self.post_texture.take().filter(|target| {
+                target.texture().width == w as i32 && target.texture().height == h as i32
+            });
+            let mut spare = match reusable {
+                Some(target) => target,
+                None => self
+                    .rl
+                    .load_render_texture(&self.thread, w, h)
+                    .context(ErrorKind::Gpu.error("failed to create post effect texture"))?,
+            };
+            let result = apply_post_effects(
+                &mut self.rl,
+                &self.thread,
+                &self.cache,
+                &mut self.render_texture,
+                &mut spare,
+                &scene.post,
+                scene.time,
+            );
+            self.post_texture = Some(spare);
+            result?;
+        }
+        if self.debug.is_enabled() {
+            self.draw_debug(scene, fps, origin)?;
+        }
+        self.report.stages.draw_secs += stage.elapsed().as_secs_f64();
+        Ok(())
+    }
+
+    // The main pass with `set_linear_light`, through targets that follow the
+    // frame target's size.
+    fn draw_linear(&mut self, scene: &SampledScene, origin: (f32, f32)) -> Result<()> {
+        let linear = match &mut self.linear {
+            Some(linear) if linear.fits(&self.render_texture) => linear,
+            slot => slot.insert(LinearLight::new(
+                &mut self.rl,
+                &self.thread,
+                &self.render_texture,
+            )?),
+        };
+        linear.draw_scene(
+            &mut self.rl,
+            &self.thread,
+            &self.cache,
+            &mut self.render_texture,
+            (self.width, self.height),
+            self.bg,
+            origin,
+            scene,
+        )
+    }
+
+    // Draws `scene` a tile at a time, stitching the captured tiles into `out`
+    // as the premultiplied frame `finish_frame` expects.
+    fn draw_tiles(
+        &mut self,
+        scene: &SampledScene,
+        fps: Option<u32>,
+        size: u32,
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        let scale = self.cache.render_scale();
+        let (frame_w, frame_h) = self.cache.scaled_size(self.width, self.height);
+        out.clear();
+        out.resize(frame_w as usize * frame_h as usize * 4, 0);
+        let (mut rgba, mut spare) = (Vec::new(), std::mem::take(&mut self.spare));
+        let result = self.tiles(size).into_iter().try_for_each(|tile| {
+            self.tile = Some(tile);
+            let (target_w, target_h) = self.target_size();
+            let texture = self.render_texture.texture();
+            // With a resolution scale, tiles can round a pixel apart.
+            if texture.width != target_w as i32 || texture.height != target_h as i32 {
+                self.render_texture = self
+                    .rl
+                    .load_render_texture(&self.thread, target_w, target_h)
+                    .context(ErrorKind::Gpu.error(TARGET_ERROR))?;
+                self.post_texture = None;
+            }
+            self.draw_scene(scene, fps)?;
+            if self.show_frames {
+                self.show_frame();
             }
+            let stage = Instant::now();
+            let (x, y, w, h) = self.output_rect();
+            capture_rgba_into(&self.render_texture, target_w, target_h, &mut rgba)?;
+            if scale > 1 {
+                downsample_into(&rgba, w, h, scale, &mut spare);
+                std::mem::swap(&mut rgba, &mut spare);
+            }
+            RenderRegion::new(x, y, w, h).paste_rgba(&rgba, frame_w, frame_h, out);
+            self.report.stages.capture_secs += stage.elapsed().as_secs_f64();
+            Ok(())
+        });
+        self.tile = None;
+        self.spare = spare;
+        result
+    }
+
+    // Draws the debug overlay over the finished frame target, after post
+    // effects so they don't blur or grade it.
+    fn draw_debug(
+        &mut self,
+        scene: &SampledScene,
+        fps: Option<u32>,
+        origin: (f32, f32),
+    ) -> Result<()> {
+        let outlines = if self.debug.bounds {
+            let cache = &self.cache;
+            scene_outlines(scene, self.width, self.height, |object, transform| {
+                measure_object(cache, object, transform)
+            })?
+        } else {
+            Vec::new()
+        };
+        let frame = DebugFrame {
+            time: scene.time,
+            fps,
+            clips: scene.clip_count(),
+            outlines: &outlines,
+        };
+        let mut d = self
+            .rl
+            .begin_texture_mode(&self.thread, self.render_texture.as_mut());
+        let _scaled = ScaledDraw::begin_at(self.cache.draw_scale(), origin);
+        begin_straight();
+        draw_debug_overlay(&mut d, &self.cache, self.debug, &frame, self.width, self.height)
+    }
+
+    // Turns the captured frame target in `rgba` into a frame in place:
+    // bottom-up straight RGBA at the output size, with effect plugins,
+    // padding and the watermark. Steps that change the size write to `spare`
+    // and swap it in.
+    fn finish_frame(
+        &self,
+        rgba: &mut Vec<u8>,
+        spare: &mut Vec<u8>,
+        scene: &SampledScene,
+    ) -> Result<()> {
+        let scale = self.cache.render_scale();
+        let (x, y, w, h) = self.output_rect();
+        // Averaged while still premultiplied, so transparent pixels don't
+        // darken the edges next to them. Tiles are averaged as they're
+        // stitched.
+        if scale > 1 && self.tile_size.is_none() {
+            downsample_into(rgba, w, h, scale, spare);
+            std::mem::swap(rgba, spare);
+        }
+        unpremultiply(rgba);
+        if !scene.effects.is_empty() && !self.skip_effects {
+            apply_effects(self.cache.plugins()?, &scene.effects, scene.time, rgba, w, h)?;
+        }
+        if self.region.is_some_and(|region| region.pad) {
+            let (frame_w, frame_h) = self.cache.scaled_size(self.width, self.height);
+            RenderRegion::new(x, y, w, h).pad_rgba_into(rgba, frame_w, frame_h, spare);
+            std::mem::swap(rgba, spare);
+        }
+        if let Some(watermark) = &self.watermark {
+            watermark.embed(rgba);
+        }
+        Ok(())
+    }
+}
+
+// Fields drop in declaration order, after this, and `rl` closes the GL
+// context the pixel buffers and linear-light targets live in.
+impl Drop for RaylibRender {
+    fn drop(&mut self) {
+        self.readback = None;
+        self.linear = None;
+    }
+}
+
+impl RenderBackend for RaylibRender {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn set_plugins(&mut self, plugins: Arc<PluginRegistry>) {
+        RaylibRender::set_plugins(self, plugins);
+    }
+
+    fn set_data_source(&mut self, data: Arc<dyn DataSource>) {
+        RaylibRender::set_data_source(self, data);
+    }
+
+    fn set_cancel_token(&mut self, cancel: CancelToken) {
+        RaylibRender::set_cancel_token(self, cancel);
+    }
+
+    fn render_scene_to_rgba(&mut self, scene: &SampledScene) -> Result<Vec<u8>> {
+        RaylibRender::render_scene_to_rgba(self, scene)
+    }
+
+    fn render_frame_rgba(&mut self, timeline: &Timeline, time: f32) -> Result<Vec<u8>> {
+        RaylibRender::render_frame_rgba(self, timeline, time)
+    }
+
+    fn render_timeline_rgba(
+        &mut self,
+        timeline: &Timeline,
+        start_time: f32,
+        end_time: f32,
+        on_frame: &mut dyn FnMut(f32, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        RaylibRender::render_timeline_rgba(self, timeline, start_time, end_time, on_frame)
+    }
+}
+
+pub(crate) fn draw_clip(
+    d: &mut impl RaylibDraw,
+    cache: &ResourceCache,
+    width: u32,
+    height: u32,
+    layer_transform: &Transform,
+    clip: &SampledClip,
+    key: ClipKey,
+) -> Result<()> {
+    let (transform, scissor) = clip_placement(layer_transform, clip, width, height);
+
+    if let Some((x, y, w, h)) = scissor {
+        // Scissor rects are in target pixels, which the draw scale and
+        // origin miss.
+        let scale = cache.draw_scale();
+        let (origin_x, origin_y) = cache.draw_origin();
+        unsafe {
+            raylib::ffi::BeginScissorMode(
+                ((x - origin_x) * scale).round() as i32,
+                ((y - origin_y) * scale).round() as i32,
+                (w * scale).round() as i32,
+                (h * scale).round() as i32,
+            );
+        }
+    }
+    let result = draw_shaded(clip, cache, || match &clip.nested {
+        Some(nested) => draw_composition(d, cache, width, height, nested, &transform),
+        None => draw_object(
+            d,
+            cache,
+            width,
+            height,
+            &clip.object,
+            &transform,
+            clip.local_time,
+            key,
+        ),
+    });
+    if scissor.is_some() {
+        unsafe {
+            raylib::ffi::EndScissorMode();
         }
+    }
+    result
+}
 
-        capture_rgba(&self.render_texture, self.width, self.height)
+// Runs `draw` through the clip's shader, if it has one, with its uniforms set
+// for this frame and `time` set to the clip's local time.
+pub(crate) fn draw_shaded(
+    clip: &SampledClip,
+    cache: &ResourceCache,
+    draw: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let Some(sampled) = &clip.shader else {
+        return draw();
+    };
+    let shader = *cache.shader(&sampled.path)?.as_ref();
+    let time = ("time".to_string(), UniformValue::Float(clip.local_time));
+    unsafe {
+        // Also draws what's batched so far without the shader.
+        raylib::ffi::BeginShaderMode(shader);
+        for (name, value) in sampled.uniforms.iter().chain([&time]) {
+            let Ok(name) = CString::new(name.as_str()) else { continue };
+            let location = raylib::ffi::GetShaderLocation(shader, name.as_ptr());
+            if location < 0 {
+                continue;
+            }
+            let (data, kind) = match *value {
+                UniformValue::Float(x) => ([x, 0.0, 0.0, 0.0], UniformType::SHADER_UNIFORM_FLOAT),
+                UniformValue::Vec2(v) => ([v.x, v.y, 0.0, 0.0], UniformType::SHADER_UNIFORM_VEC2),
+                UniformValue::Vec4(v) => (v, UniformType::SHADER_UNIFORM_VEC4),
+            };
+            raylib::ffi::SetShaderValue(shader, location, data.as_ptr().cast(), kind as i32);
+        }
     }
+    let result = draw();
+    // Ending the mode draws the batch, so the next clip's uniforms can't
+    // apply to this one.
+    unsafe { raylib::ffi::EndShaderMode() };
+    result
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_object(
     d: &mut impl RaylibDraw,
     cache: &ResourceCache,
@@ -171,11 +1266,16 @@ fn draw_object(
     height: u32,
     object: &Object,
     transform: &Transform,
+    local_t: f32,
+    key: ClipKey,
 ) -> Result<()> {
     match object {
         Object::Shape(shape) => draw_shape(d, width, height, shape, transform),
-        Object::Image(image) => draw_image(d, cache, width, height, &image.path, transform),
-        Object::Text(text) => draw_text_block(d, cache, width, height, text, transform),
+        Object::Image(image) => draw_image(d, cache, width, height, image, transform),
+        Object::Text(text) => draw_text_block(d, cache, width, height, text, transform, key),
+        // Drawn from the clip's sampled `nested` scene in `draw_clip`.
+        Object::Composition(_) => Ok(()),
+        Object::Plugin(plugin) => draw_plugin(d, cache, width, height, plugin, transform, local_t),
     }
 }
 
@@ -217,23 +1317,62 @@ fn draw_image(
     cache: &ResourceCache,
     width: u32,
     height: u32,
-    path: &Path,
+    image: &ImageObject,
     transform: &Transform,
 ) -> Result<()> {
-    let texture = cache.get_texture(path)?;
-    let tex_w = texture.width as f32;
-    let tex_h = texture.height as f32;
+    let loaded = cache.get_texture(image)?;
+    let texture = &loaded.texture;
 
-    let w = tex_w * transform.scale.x;
-    let h = tex_h * transform.scale.y;
+    // Downscaled textures still cover the image's full size.
+    let w = loaded.width * transform.scale.x;
+    let h = loaded.height * transform.scale.y;
     let center = graph_to_screen(transform.pos, width, height);
 
-    let source = Rectangle::new(0.0, 0.0, tex_w, tex_h);
+    let source = Rectangle::new(0.0, 0.0, texture.width as f32, texture.height as f32);
     let dest = Rectangle::new(center.x, center.y, w, h);
     let origin = Vector2::new(w / 2.0, h / 2.0);
 
-    let tint = to_raylib_color(Color::WHITE, transform.opacity);
-    d.draw_texture_pro(texture, source, dest, origin, transform.rotation, tint);
+    if image.load.premultiply {
+        begin_premultiplied();
+        let tint = premultiplied_tint(transform.opacity);
+        d.draw_texture_pro(texture, source, dest, origin, transform.rotation, tint);
+        begin_straight();
+    } else {
+        let tint = to_raylib_color(Color::WHITE, transform.opacity);
+        d.draw_texture_pro(texture, source, dest, origin, transform.rotation, tint);
+    }
+    Ok(())
+}
+
+// Drawn size of images and text, for hit regions.
+fn measure_object(
+    cache: &ResourceCache,
+    object: &Object,
+    transform: &Transform,
+) -> Result<Option<Vec2>> {
+    match object {
+        Object::Image(image) => {
+            let loaded = cache.get_texture(image)?;
+            Ok(Some(Vec2 {
+                x: loaded.width * transform.scale.x,
+                y: loaded.height * transform.scale.y,
+            }))
+        }
+        Object::Text(text) => {
+            let (width, height) = text_block_size(cache, text, transform)?;
+            Ok(Some(Vec2 {
+                x: width,
+                y: height,
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn check_tile_size(size: Option<u32>) -> Result<()> {
+    if size == Some(0) {
+        bail!(ErrorKind::InvalidProject.error("tile size must be positive"));
+    }
     Ok(())
 }
 
@@ -241,19 +1380,35 @@ fn graph_to_screen(pos: Vec2, width: u32, height: u32) -> Vector2 {
     Vector2::new(width as f32 / 2.0 + pos.x, height as f32 / 2.0 - pos.y)
 }
 
-fn to_raylib_color(color: Color, opacity: f32) -> raylib::prelude::Color {
+pub(crate) fn to_raylib_color(color: Color, opacity: f32) -> raylib::prelude::Color {
     let alpha = (color.a as f32 * opacity.clamp(0.0, 1.0))
         .round()
         .clamp(0.0, 255.0) as u8;
     raylib::prelude::Color::new(color.r, color.g, color.b, alpha)
 }
 
-fn capture_rgba(render_texture: &RenderTexture2D, expected_w: u32, expected_h: u32) -> Result<Vec<u8>> {
+pub(crate) fn capture_rgba(
+    render_texture: &RenderTexture2D,
+    expected_w: u32,
+    expected_h: u32,
+) -> Result<Vec<u8>> {
+    let mut rgba = Vec::new();
+    capture_rgba_into(render_texture, expected_w, expected_h, &mut rgba)?;
+    Ok(rgba)
+}
+
+// `capture_rgba` into `out`, reusing its allocation.
+pub(crate) fn capture_rgba_into(
+    render_texture: &RenderTexture2D,
+    expected_w: u32,
+    expected_h: u32,
+    out: &mut Vec<u8>,
+) -> Result<()> {
     let mut image = unsafe { raylib::ffi::LoadImageFromTexture(*render_texture.texture().as_ref()) };
 
     let result = (|| {
         if image.data.is_null() {
-            bail!("raylib returned null image data");
+            bail!(ErrorKind::Gpu.error("raylib returned null image data"));
         }
 
         if image.format != PixelFormat::PIXELFORMAT_UNCOMPRESSED_R8G8B8A8 as i32 {
@@ -266,24 +1421,26 @@ fn capture_rgba(render_texture: &RenderTexture2D, expected_w: u32, expected_h: u
         }
 
         if image.data.is_null() {
-            bail!("image data was null after format conversion");
+            bail!(ErrorKind::Gpu.error("image data was null after format conversion"));
         }
 
         let width = image.width as u32;
         let height = image.height as u32;
         if width != expected_w || height != expected_h {
-            bail!(
+            bail!(ErrorKind::Gpu.error(format!(
                 "capture size mismatch: got {}x{}, expected {}x{}",
                 width,
                 height,
                 expected_w,
                 expected_h
-            );
+            )));
         }
 
         let len = (width * height * 4) as usize;
         let bytes = unsafe { std::slice::from_raw_parts(image.data as *const u8, len) };
-        Ok(bytes.to_vec())
+        out.clear();
+        out.extend_from_slice(bytes);
+        Ok(())
     })();
 
     unsafe {
@@ -293,29 +1450,187 @@ fn capture_rgba(render_texture: &RenderTexture2D, expected_w: u32, expected_h: u
     result
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct RenderProgress {
-    pub enabled: bool,
-    pub log_every_frames: u32,
-    pub show_time: bool,
-    pub show_eta: bool,
+// Scales drawing by the draw scale (see `ResourceCache::draw_scale`) while
+// it's alive, so a target at the output resolution and render scale is drawn
+// in scene pixels. Create it after the texture mode begins, so it's dropped
+// before the mode ends.
+pub(crate) struct ScaledDraw(bool);
+
+impl ScaledDraw {
+    pub(crate) fn begin(scale: f32) -> Self {
+        Self::begin_at(scale, (0.0, 0.0))
+    }
+
+    // Also moves `origin` (in scene pixels) to the target's top-left corner,
+    // for targets that only cover a region of the frame.
+    pub(crate) fn begin_at(scale: f32, (x, y): (f32, f32)) -> Self {
+        let transformed = scale != 1.0 || x != 0.0 || y != 0.0;
+        if transformed {
+            unsafe {
+                raylib::ffi::rlPushMatrix();
+                raylib::ffi::rlScalef(scale, scale, 1.0);
+                raylib::ffi::rlTranslatef(-x, -y, 0.0);
+            }
+        }
+        Self(transformed)
+    }
 }
 
-impl Default for RenderProgress {
-    fn default() -> Self {
-        Self {
-            enabled: false,
-            log_every_frames: 100,
-            show_time: true,
-            show_eta: true,
+impl Drop for ScaledDraw {
+    fn drop(&mut self) {
+        if self.0 {
+            unsafe { raylib::ffi::rlPopMatrix() };
         }
     }
 }
 
-fn format_hms(seconds: f32) -> String {
-    let total = seconds.max(0.0).round() as u64;
-    let h = total / 3600;
-    let m = (total % 3600) / 60;
-    let s = total % 60;
-    format!("{:02}:{:02}:{:02}", h, m, s)
+// Averages each `scale` x `scale` block of an RGBA image `scale` times the
+// size of `width` x `height`.
+pub(crate) fn downsample(rgba: &[u8], width: u32, height: u32, scale: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    downsample_into(rgba, width, height, scale, &mut out);
+    out
+}
+
+// `downsample` into `out`, reusing its allocation.
+pub(crate) fn downsample_into(rgba: &[u8], width: u32, height: u32, scale: u32, out: &mut Vec<u8>) {
+    let (width, height, scale) = (width as usize, height as usize, scale as usize);
+    let row = width * scale * 4;
+    let count = (scale * scale) as u32;
+    out.clear();
+    out.reserve(width * height * 4);
+    for y in 0..height {
+        let block = &rgba[y * scale * row..][..scale * row];
+        for x in 0..width {
+            let mut sum = [0u32; 4];
+            for line in block.chunks_exact(row) {
+                for pixel in line[x * scale * 4..][..scale * 4].chunks_exact(4) {
+                    for (total, &value) in sum.iter_mut().zip(pixel) {
+                        *total += value as u32;
+                    }
+                }
+            }
+            out.extend(sum.map(|total| ((total + count / 2) / count) as u8));
+        }
+    }
+}
+
+// Repeats each pixel of `width` x `height` RGBA into a `scale` x `scale`
+// block, undoing `downsample` for pixels worked on at the output size.
+pub(crate) fn upsample(rgba: &[u8], width: u32, height: u32, scale: u32) -> Vec<u8> {
+    let (width, scale) = (width as usize, scale as usize);
+    let mut out = Vec::with_capacity(rgba.len() * scale * scale);
+    for line in rgba.chunks_exact(width * 4).take(height as usize) {
+        let start = out.len();
+        for pixel in line.chunks_exact(4) {
+            for _ in 0..scale {
+                out.extend_from_slice(pixel);
+            }
+        }
+        let scaled_row = out.len() - start;
+        for _ in 1..scale {
+            out.extend_from_within(start..start + scaled_row);
+        }
+    }
+    out
+}
+
+// Writes bottom-up RGBA through raylib's image export.
+pub(crate) fn write_still(rgba: &[u8], width: u32, height: u32, path: &Path) -> Result<()> {
+    let mut pixels: Vec<u8> =
+        rgba.chunks_exact(width as usize * 4).rev().flatten().copied().collect();
+    export_rgba(&mut pixels, width, height, path, |_| {})
+}
+
+// Writes top-down RGBA as a PNG or JPEG, after `draw` (e.g. `ImageDrawText`)
+// has had the image. `pixels` stays owned here; raylib only draws into it.
+pub(crate) fn export_rgba(
+    pixels: &mut [u8],
+    width: u32,
+    height: u32,
+    path: &Path,
+    draw: impl FnOnce(&mut raylib::ffi::Image),
+) -> Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    if !matches!(extension.as_deref(), Some("png" | "jpg" | "jpeg")) {
+        bail!(ErrorKind::InvalidProject.error(format!(
+            "stills are written as .png or .jpg: {}",
+            path.display()
+        )));
+    }
+    let file_name = CString::new(path.to_string_lossy().as_bytes())
+        .context(ErrorKind::InvalidProject.error("output path contains a nul byte"))?;
+    let mut image = raylib::ffi::Image {
+        data: pixels.as_mut_ptr().cast(),
+        width: width as i32,
+        height: height as i32,
+        mipmaps: 1,
+        format: PixelFormat::PIXELFORMAT_UNCOMPRESSED_R8G8B8A8 as i32,
+    };
+    draw(&mut image);
+    if !unsafe { raylib::ffi::ExportImage(image, file_name.as_ptr()) } {
+        bail!(ErrorKind::Encoder.error(format!("failed to write {}", path.display())));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::{AnimatedTransform, Easing, Keyframe, Track};
+    use crate::timeline::{Clip, Layer};
+
+    // A white rect over the whole frame fading in from black, so each frame
+    // is brighter than the one before.
+    fn fade_in() -> Timeline {
+        let rect = Object::Shape(Shape::Rect {
+            width: 64.0,
+            height: 64.0,
+            color: Color::WHITE,
+        });
+        let mut transform = AnimatedTransform::constant(Transform::default());
+        transform.opacity = Track::new(vec![
+            Keyframe::new(0.0, 0.0, Easing::Linear),
+            Keyframe::new(1.0, 1.0, Easing::Linear),
+        ])
+        .unwrap();
+        let mut layer = Layer::new("fade");
+        layer.add_clip(Clip::new(0.0, 1.0, rect, transform).unwrap());
+        let mut timeline = Timeline::new(1.0, 30).unwrap();
+        timeline.add_layer(layer).unwrap();
+        timeline
+    }
+
+    #[test]
+    fn cancelling_from_on_frame_hands_out_frames_in_order() {
+        let timeline = fade_in();
+        let mut renderer = RaylibRender::builder(64, 64)
+            .with_display(DisplayMode::Headless)
+            .build()
+            .unwrap();
+        let cancel = CancelToken::new();
+        renderer.set_cancel_token(cancel.clone());
+
+        let mut frames = Vec::new();
+        renderer
+            .render_timeline_rgba(&timeline, 0.0, 1.0, |t, rgba| {
+                frames.push((t, rgba[0]));
+                if frames.len() == 5 {
+                    cancel.cancel();
+                }
+                Ok(())
+            })
+            .unwrap();
+
+        assert!(renderer.last_report().cancelled);
+        // Frames already being read back are still handed out, none skipped.
+        assert!((5..5 + Readback::DEPTH).contains(&frames.len()));
+        for (index, &(t, _)) in frames.iter().enumerate() {
+            assert_eq!(t, index as f32 / 30.0);
+        }
+        assert!(frames.windows(2).all(|pair| pair[0].1 < pair[1].1));
+    }
 }
@@ -0,0 +1,178 @@
+use std::collections::VecDeque;
+use std::ffi::{c_void, CString};
+use std::os::raw::{c_char, c_int, c_uint};
+
+use anyhow::{bail, Result};
+use raylib::prelude::*;
+
+use crate::error::ErrorKind;
+
+// OpenGL enum values (glcorearb.h).
+const GL_PIXEL_PACK_BUFFER: c_uint = 0x88EB;
+const GL_STREAM_READ: c_uint = 0x88E1;
+const GL_MAP_READ_BIT: c_uint = 0x0001;
+const GL_RGBA: c_uint = 0x1908;
+const GL_UNSIGNED_BYTE: c_uint = 0x1401;
+
+unsafe extern "C" {
+    // From the GLFW that raylib builds in.
+    fn glfwGetProcAddress(name: *const c_char) -> *const c_void;
+}
+
+// The buffer object calls, which rlgl doesn't wrap.
+struct Gl {
+    gen_buffers: unsafe extern "system" fn(c_int, *mut c_uint),
+    delete_buffers: unsafe extern "system" fn(c_int, *const c_uint),
+    bind_buffer: unsafe extern "system" fn(c_uint, c_uint),
+    buffer_data: unsafe extern "system" fn(c_uint, isize, *const c_void, c_uint),
+    map_buffer_range: unsafe extern "system" fn(c_uint, isize, isize, c_uint) -> *mut c_void,
+    unmap_buffer: unsafe extern "system" fn(c_uint) -> u8,
+    read_pixels: unsafe extern "system" fn(c_int, c_int, c_int, c_int, c_uint, c_uint, *mut c_void),
+}
+
+impl Gl {
+    // None when the context lacks any of them (OpenGL ES 2).
+    fn load() -> Option<Self> {
+        Some(Self {
+            gen_buffers: proc_address("glGenBuffers")?,
+            delete_buffers: proc_address("glDeleteBuffers")?,
+            bind_buffer: proc_address("glBindBuffer")?,
+            buffer_data: proc_address("glBufferData")?,
+            map_buffer_range: proc_address("glMapBufferRange")?,
+            unmap_buffer: proc_address("glUnmapBuffer")?,
+            read_pixels: proc_address("glReadPixels")?,
+        })
+    }
+}
+
+// `T` must be the function pointer type of the GL call `name`.
+fn proc_address<T: Copy>(name: &str) -> Option<T> {
+    let name = CString::new(name).ok()?;
+    let address = unsafe { glfwGetProcAddress(name.as_ptr()) };
+    (!address.is_null()).then(|| unsafe { std::mem::transmute_copy::<*const c_void, T>(&address) })
+}
+
+// Reads the frame target back through two pixel buffer objects. `start`
+// queues a copy of the target into a buffer and returns without waiting;
+// `finish` maps the oldest queued buffer, stalling only if the GPU hasn't
+// got to it yet. A frame's copy then overlaps with drawing the next one,
+// rather than each capture waiting for the whole pipeline to drain.
+pub(crate) struct Readback {
+    gl: Gl,
+    buffers: [c_uint; 2],
+    // Size of the frames the buffers are allocated for.
+    size: (u32, u32),
+    // Buffers with a copy queued, oldest first.
+    queued: VecDeque<usize>,
+    next: usize,
+}
+
+impl Readback {
+    // Frames that can be in flight at once.
+    pub(crate) const DEPTH: usize = 2;
+
+    // Needs the GL context current. None without buffer objects, in which
+    // case frames are captured synchronously.
+    pub(crate) fn new() -> Option<Self> {
+        let gl = Gl::load()?;
+        let mut buffers = [0; 2];
+        unsafe { (gl.gen_buffers)(buffers.len() as c_int, buffers.as_mut_ptr()) };
+        if buffers.contains(&0) {
+            unsafe { (gl.delete_buffers)(buffers.len() as c_int, buffers.as_ptr()) };
+            return None;
+        }
+        Some(Self {
+            gl,
+            buffers,
+            size: (0, 0),
+            queued: VecDeque::new(),
+            next: 0,
+        })
+    }
+
+    // Queues a copy of `target`, which is `width` x `height`. Drawing into it
+    // afterwards is fine; GL orders the copy first.
+    pub(crate) fn start(
+        &mut self,
+        target: &RenderTexture2D,
+        width: u32,
+        height: u32,
+    ) -> Result<()> {
+        if self.queued.len() == self.buffers.len() {
+            bail!(ErrorKind::Gpu.error("every readback buffer already holds a frame"));
+        }
+        if self.size != (width, height) {
+            if !self.queued.is_empty() {
+                bail!(ErrorKind::Gpu.error("readback size changed with frames in flight"));
+            }
+            let bytes = width as isize * height as isize * 4;
+            for &buffer in &self.buffers {
+                unsafe {
+                    (self.gl.bind_buffer)(GL_PIXEL_PACK_BUFFER, buffer);
+                    (self.gl.buffer_data)(
+                        GL_PIXEL_PACK_BUFFER,
+                        bytes,
+                        std::ptr::null(),
+                        GL_STREAM_READ,
+                    );
+                }
+            }
+            self.size = (width, height);
+        }
+        unsafe {
+            (self.gl.bind_buffer)(GL_PIXEL_PACK_BUFFER, self.buffers[self.next]);
+            raylib::ffi::rlEnableFramebuffer(target.id);
+            // With a pack buffer bound the pointer is an offset into it.
+            (self.gl.read_pixels)(
+                0,
+                0,
+                width as c_int,
+                height as c_int,
+                GL_RGBA,
+                GL_UNSIGNED_BYTE,
+                std::ptr::null_mut(),
+            );
+            raylib::ffi::rlDisableFramebuffer();
+            (self.gl.bind_buffer)(GL_PIXEL_PACK_BUFFER, 0);
+        }
+        self.queued.push_back(self.next);
+        self.next = (self.next + 1) % self.buffers.len();
+        Ok(())
+    }
+
+    // Copies the oldest queued frame into `out` as bottom-up premultiplied
+    // RGBA, like `capture_rgba_into`.
+    pub(crate) fn finish_into(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        let Some(index) = self.queued.pop_front() else {
+            bail!(ErrorKind::Gpu.error("no frame is being read back"));
+        };
+        let len = self.size.0 as usize * self.size.1 as usize * 4;
+        unsafe {
+            (self.gl.bind_buffer)(GL_PIXEL_PACK_BUFFER, self.buffers[index]);
+            let data =
+                (self.gl.map_buffer_range)(GL_PIXEL_PACK_BUFFER, 0, len as isize, GL_MAP_READ_BIT);
+            let mapped = !data.is_null();
+            if mapped {
+                out.clear();
+                out.extend_from_slice(std::slice::from_raw_parts(data as *const u8, len));
+                (self.gl.unmap_buffer)(GL_PIXEL_PACK_BUFFER);
+            }
+            (self.gl.bind_buffer)(GL_PIXEL_PACK_BUFFER, 0);
+            if !mapped {
+                bail!(ErrorKind::Gpu.error("failed to map the readback buffer"));
+            }
+        }
+        Ok(())
+    }
+
+    // Forgets queued copies, e.g. those of a render that failed part way.
+    pub(crate) fn clear(&mut self) {
+        self.queued.clear();
+    }
+}
+
+impl Drop for Readback {
+    fn drop(&mut self) {
+        unsafe { (self.gl.delete_buffers)(self.buffers.len() as c_int, self.buffers.as_ptr()) };
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{bail, Result};
+
+use crate::error::ErrorKind;
+
+// A rectangle of the frame to render on its own (see
+// `RaylibRender::set_region`), in output pixels from the top-left corner.
+// Frames come out at the region's size, or at the full size with everything
+// outside the region transparent when `pad` is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub pad: bool,
+}
+
+impl RenderRegion {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            pad: false,
+        }
+    }
+
+    // Keeps frames at the full size, so they can go to the same encoder as a
+    // full render.
+    pub fn with_pad(mut self, pad: bool) -> Self {
+        self.pad = pad;
+        self
+    }
+
+    // Parses `x,y,width,height`, e.g. from a command line flag.
+    pub fn parse(value: &str) -> Result<Self> {
+        let parts: Vec<_> = value.split(',').map(|part| part.trim().parse::<u32>()).collect();
+        match parts.as_slice() {
+            [Ok(x), Ok(y), Ok(width), Ok(height)] => Ok(Self::new(*x, *y, *width, *height)),
+            _ => bail!(ErrorKind::InvalidProject
+                .error(format!("region must be x,y,width,height in pixels: {value}"))),
+        }
+    }
+
+    // Fails unless the region is non-empty and inside the frame.
+    pub fn check(&self, frame_width: u32, frame_height: u32) -> Result<()> {
+        let inside = self.x.checked_add(self.width).is_some_and(|right| right <= frame_width)
+            && self.y.checked_add(self.height).is_some_and(|bottom| bottom <= frame_height);
+        if self.width == 0 || self.height == 0 || !inside {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "region {}x{} at ({}, {}) must be non-empty and inside the {frame_width}x\
+                 {frame_height} frame",
+                self.width, self.height, self.x, self.y
+            )));
+        }
+        Ok(())
+    }
+
+    // Size of the frames handed out.
+    pub fn output_size(&self, frame_width: u32, frame_height: u32) -> (u32, u32) {
+        if self.pad {
+            (frame_width, frame_height)
+        } else {
+            (self.width, self.height)
+        }
+    }
+
+    // Places the region's bottom-up RGBA in a transparent bottom-up frame of
+    // the full size.
+    pub fn pad_rgba(&self, rgba: &[u8], frame_width: u32, frame_height: u32) -> Vec<u8> {
+        let mut frame = Vec::new();
+        self.pad_rgba_into(rgba, frame_width, frame_height, &mut frame);
+        frame
+    }
+
+    // `pad_rgba` into `frame`, reusing its allocation.
+    pub fn pad_rgba_into(
+        &self,
+        rgba: &[u8],
+        frame_width: u32,
+        frame_height: u32,
+        frame: &mut Vec<u8>,
+    ) {
+        frame.clear();
+        frame.resize(frame_width as usize * frame_height as usize * 4, 0);
+        self.paste_rgba(rgba, frame_width, frame_height, frame);
+    }
+
+    // Copies the region's bottom-up RGBA over its place in a bottom-up frame
+    // of the full size, e.g. to stitch tiles.
+    pub(crate) fn paste_rgba(
+        &self,
+        rgba: &[u8],
+        frame_width: u32,
+        frame_height: u32,
+        frame: &mut [u8],
+    ) {
+        let row = self.width as usize * 4;
+        let frame_row = frame_width as usize * 4;
+        // The region's bottom row is frame row `y + height - 1` from the top.
+        let bottom = (frame_height - self.y - self.height) as usize;
+        for (index, line) in rgba.chunks_exact(row).enumerate() {
+            let start = (bottom + index) * frame_row + self.x as usize * 4;
+            frame[start..start + row].copy_from_slice(line);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+
+use crate::backend::assertions::check_assertion;
+#[cfg(feature = "software")]
+use crate::backend::asset_bytes::AssetBytes;
+use crate::backend::cancel::CancelToken;
+use crate::backend::hit_regions::scene_hit_regions;
+use crate::error::ErrorKind;
+use crate::plugin::PluginRegistry;
+use crate::scene::{Object, Transform, Vec2};
+use crate::timeline::{DataSource, SampledClip, SampledScene, Severity, Timeline};
+
+// What every renderer (`RaylibRender`, `SoftwareRender`, `WgpuRender`)
+// offers, so callers can pick one at runtime as a `Box<dyn RenderBackend>`.
+// Frames are bottom-up straight RGBA on all of them. Sampling, layout and
+// clip placement are shared, so a timeline comes out the same on each, up
+// to antialiasing and glyph rasterization.
+pub trait RenderBackend {
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+    fn set_plugins(&mut self, plugins: Arc<PluginRegistry>);
+    fn set_data_source(&mut self, data: Arc<dyn DataSource>);
+    fn set_cancel_token(&mut self, cancel: CancelToken);
+    fn render_scene_to_rgba(&mut self, scene: &SampledScene) -> Result<Vec<u8>>;
+    fn render_frame_rgba(&mut self, timeline: &Timeline, time: f32) -> Result<Vec<u8>>;
+    fn render_timeline_rgba(
+        &mut self,
+        timeline: &Timeline,
+        start_time: f32,
+        end_time: f32,
+        on_frame: &mut dyn FnMut(f32, &[u8]) -> Result<()>,
+    ) -> Result<()>;
+}
+
+// Where a clip draws this frame: its transform on top of the layer's, moved
+// by a slide transition, and the frame rectangle (x, y, w, h, top-down
+// pixels) a wipe transition clips it to.
+pub(crate) fn clip_placement(
+    layer_transform: &Transform,
+    clip: &SampledClip,
+    width: u32,
+    height: u32,
+) -> (Transform, Option<(f32, f32, f32, f32)>) {
+    let mut transform = layer_transform.compose(&clip.transform);
+    let Some(transition) = &clip.transition else {
+        return (transform, None);
+    };
+    let offset = transition.slide_offset(width as f32, height as f32);
+    transform.pos.x += offset.x;
+    transform.pos.y += offset.y;
+    (transform, transition.wipe_rect(width as f32, height as f32))
+}
+
+pub(crate) fn check_time_range(timeline: &Timeline, start_time: f32, end_time: f32) -> Result<()> {
+    if start_time < 0.0 || end_time <= start_time || end_time > timeline.duration {
+        bail!(
+            ErrorKind::InvalidProject
+                .error("start/end time must satisfy 0 <= start < end <= duration")
+        );
+    }
+    Ok(())
+}
+
+pub(crate) fn check_frame_time(timeline: &Timeline, time: f32) -> Result<()> {
+    if !(0.0..=timeline.duration).contains(&time) {
+        bail!(ErrorKind::InvalidProject.error(format!(
+            "frame time {time}s is outside the timeline (0 to {}s)",
+            timeline.duration
+        )));
+    }
+    Ok(())
+}
+
+// Assets registered in `assets` count as present.
+#[cfg(feature = "software")]
+pub(crate) fn validate_for_render(
+    timeline: &Timeline,
+    plugins: Option<&PluginRegistry>,
+    assets: &AssetBytes,
+) -> Result<()> {
+    if let Some(plugins) = plugins {
+        plugins.validate(timeline)?;
+    }
+    timeline.validate_with_assets(&|path| assets.contains(path)).check()
+}
+
+pub(crate) fn sample_scene(
+    timeline: &Timeline,
+    data: Option<&Arc<dyn DataSource>>,
+    time: f32,
+) -> Result<SampledScene> {
+    match data {
+        Some(data) => timeline.sample_from(time, data.as_ref()),
+        None => timeline.sample(time),
+    }
+}
+
+// Fails on the first error assertion `scene` breaks. Backends without a
+// render report have nowhere to put warnings, so those aren't checked.
+pub(crate) fn check_error_assertions(
+    timeline: &Timeline,
+    scene: &SampledScene,
+    (width, height): (u32, u32),
+    measure: impl FnMut(&Object, &Transform) -> Result<Option<Vec2>>,
+) -> Result<()> {
+    let t = scene.time;
+    let assertions: Vec<_> = timeline
+        .assertions
+        .iter()
+        .filter(|assertion| assertion.severity == Severity::Error && assertion.applies_at(t))
+        .collect();
+    if assertions.is_empty() {
+        return Ok(());
+    }
+    let regions = scene_hit_regions(scene, width, height, measure)?;
+    for assertion in assertions {
+        if let Some(message) = check_assertion(assertion, &regions, width, height) {
+            let message = format!("assertion failed at {t:.3}s: {message}");
+            bail!(ErrorKind::AssertionFailed.error(message));
+        }
+    }
+    Ok(())
+}
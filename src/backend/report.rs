@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheStats {
+    pub textures: usize,
+    pub fonts: usize,
+    // Estimated from texture sizes at 4 bytes per pixel.
+    pub texture_bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    // Textures freed to stay within a budget or limit, or because nothing
+    // drew them again.
+    pub evicted: u64,
+    // Images and fonts loaded ahead by the prefetch thread, when it's on.
+    pub prefetched: u64,
+}
+
+// Seconds spent in each stage of the frame loop, summed over the render
+// (or one frame, in `FrameStats`).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StageTimes {
+    pub sample_secs: f64,
+    pub preload_secs: f64,
+    pub draw_secs: f64,
+    pub capture_secs: f64,
+    // In the caller's `on_frame`, which for a video is mostly waiting for the
+    // encoder to take the frame.
+    pub output_secs: f64,
+}
+
+#[cfg(feature = "raylib")]
+impl StageTimes {
+    // The time added to each stage since the times were `earlier`.
+    pub(crate) fn since(&self, earlier: &StageTimes) -> StageTimes {
+        StageTimes {
+            sample_secs: self.sample_secs - earlier.sample_secs,
+            preload_secs: self.preload_secs - earlier.preload_secs,
+            draw_secs: self.draw_secs - earlier.draw_secs,
+            capture_secs: self.capture_secs - earlier.capture_secs,
+            output_secs: self.output_secs - earlier.output_secs,
+        }
+    }
+
+    pub(crate) fn add(&mut self, other: &StageTimes) {
+        self.sample_secs += other.sample_secs;
+        self.preload_secs += other.preload_secs;
+        self.draw_secs += other.draw_secs;
+        self.capture_secs += other.capture_secs;
+        self.output_secs += other.output_secs;
+    }
+}
+
+// What one frame of a timeline render cost, sent with its `ProgressEvent`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameStats {
+    pub time: f32,
+    // From sampling the frame to `on_frame` returning. With asynchronous
+    // readback this overlaps drawing the next frame, so it's more than the
+    // sum of the stages.
+    pub secs: f64,
+    pub stages: StageTimes,
+    // Image and font lookups while the frame was drawn.
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderReport {
+    pub frames: u32,
+    // The render was stopped through its `CancelToken` after `frames`.
+    pub cancelled: bool,
+    pub wall_secs: f64,
+    pub fps_achieved: f64,
+    pub stages: StageTimes,
+    // The frame that took longest, to know where to look.
+    pub slowest_frame: Option<FrameStats>,
+    pub cache: CacheStats,
+    // Video codec the output was encoded with, when the caller records it
+    // (see `FfmpegVideoEncoder::codec`).
+    pub encoder: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+impl RenderReport {
+    // Counts `secs` spent after the last frame, finishing the output
+    // (flushing the encoder, muxing audio), in the wall time and the output
+    // stage.
+    pub fn add_finish_secs(&mut self, secs: f64) {
+        self.wall_secs += secs;
+        self.stages.output_secs += secs;
+        if self.wall_secs > 0.0 {
+            self.fps_achieved = self.frames as f64 / self.wall_secs;
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let slowest_frame = self.slowest_frame.map(|frame| {
+            json!({
+                "time": frame.time,
+                "secs": frame.secs,
+                "stages": stages_json(&frame.stages),
+                "cache_hits": frame.cache_hits,
+                "cache_misses": frame.cache_misses,
+            })
+        });
+        let report = json!({
+            "frames": self.frames,
+            "cancelled": self.cancelled,
+            "wall_secs": self.wall_secs,
+            "fps_achieved": self.fps_achieved,
+            "stages": stages_json(&self.stages),
+            "slowest_frame": slowest_frame,
+            "cache": {
+                "textures": self.cache.textures,
+                "fonts": self.cache.fonts,
+                "texture_bytes": self.cache.texture_bytes,
+                "hits": self.cache.hits,
+                "misses": self.cache.misses,
+                "evicted": self.cache.evicted,
+                "prefetched": self.cache.prefetched,
+            },
+            "encoder": self.encoder,
+            "warnings": self.warnings,
+        });
+        format!("{report:#}\n")
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).context("failed to create report directory")?;
+        }
+        std::fs::write(path, self.to_json())
+            .with_context(|| format!("failed to write render report: {}", path.display()))
+    }
+}
+
+fn stages_json(stages: &StageTimes) -> Value {
+    json!({
+        "sample_secs": stages.sample_secs,
+        "preload_secs": stages.preload_secs,
+        "draw_secs": stages.draw_secs,
+        "capture_secs": stages.capture_secs,
+        "output_secs": stages.output_secs,
+    })
+}
@@ -1,96 +1,746 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::{bail, Context, Result};
 use raylib::prelude::*;
 
-use crate::scene::{FontFamily, FontSource, Object, StyleFlags};
-use crate::timeline::SampledScene;
+use crate::backend::exif::jpeg_orientation;
+use crate::backend::font_coverage::FontCoverage;
+use crate::backend::post::post_shader_source;
+use crate::backend::prefetch::{PrefetchAsset, Prefetcher};
+use crate::backend::report::CacheStats;
+use crate::error::ErrorKind;
+use crate::plugin::PluginRegistry;
+use crate::scene::{
+    FontFamily, FontSource, ImageLoadOptions, ImageObject, Object, PostEffect, StyleFlags,
+    TextObject,
+};
+use crate::timeline::{SampledScene, Timeline};
+
+// Matches raylib's LoadFont default so glyph quality is unchanged.
+const FONT_LOAD_SIZE: i32 = 32;
+
+const LUMA_MATTE_FS: &str = r#"
+#version 330
+in vec2 fragTexCoord;
+in vec4 fragColor;
+uniform sampler2D texture0;
+uniform vec4 colDiffuse;
+out vec4 finalColor;
+void main() {
+    // Targets are premultiplied, so the luma is already scaled by alpha.
+    vec4 texel = texture(texture0, fragTexCoord) * colDiffuse * fragColor;
+    float luma = dot(texel.rgb, vec3(0.2126, 0.7152, 0.0722));
+    finalColor = vec4(1.0, 1.0, 1.0, luma);
+}
+"#;
+
+// Only the start of a file is read for its EXIF orientation; APP1 comes
+// before the image data and is capped at 64 KiB.
+const EXIF_SCAN_BYTES: u64 = 128 * 1024;
+
+pub struct LoadedImage {
+    pub texture: Texture2D,
+    // Size the image is drawn at: the file's size after orientation, before
+    // any `max_dimension` downscale.
+    pub width: f32,
+    pub height: f32,
+}
+
+impl LoadedImage {
+    // GPU memory the texture takes, at 4 bytes per pixel.
+    fn bytes(&self) -> u64 {
+        self.texture.width.max(0) as u64 * self.texture.height.max(0) as u64 * 4
+    }
+}
+
+pub(crate) type TextureKey = (PathBuf, ImageLoadOptions);
+
+// Names an offscreen target within a frame. Composition, layer and group
+// targets go by the address of the sampled scene/layer/group they draw;
+// outlined and plated text goes by its clip's place in its scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OffscreenKey {
+    Address(*const ()),
+    TextBlock(ClipKey),
+    TextStroke(ClipKey),
+}
+
+impl From<*const ()> for OffscreenKey {
+    fn from(address: *const ()) -> Self {
+        Self::Address(address)
+    }
+}
+
+// A clip's layer and clip index in the sampled scene it belongs to, which
+// is told apart from the others in the frame as composition targets are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClipKey {
+    scene: *const (),
+    layer: usize,
+    clip: usize,
+}
+
+impl ClipKey {
+    pub fn new(scene: &SampledScene, layer: usize, clip: usize) -> Self {
+        Self {
+            scene: scene as *const SampledScene as *const (),
+            layer,
+            clip,
+        }
+    }
+}
+
+struct CachedTexture {
+    image: LoadedImage,
+    // The `frame` that last drew it.
+    last_used: u64,
+}
 
 pub struct ResourceCache {
-    textures: HashMap<PathBuf, Texture2D>,
+    textures: HashMap<TextureKey, CachedTexture>,
+    // Counts `preload_for_scene` calls, to find the least recently drawn
+    // textures.
+    frame: u64,
+    // Image texture bytes to keep loaded (see `set_texture_budget`).
+    texture_budget: Option<u64>,
+    warned_budget: bool,
+    // When each image is last drawn in the timeline being rendered (see
+    // `plan_releases`).
+    last_needed: HashMap<TextureKey, f32>,
+    evicted: u64,
+    // Loads assets ahead of the frames that draw them (see `set_prefetch`).
+    prefetch: Option<Prefetcher>,
+    prefetched: u64,
+    // Files of fonts the prefetcher read, kept for reloads with new glyphs.
+    font_files: HashMap<PathBuf, Vec<u8>>,
     fonts: HashMap<PathBuf, Font>,
+    font_glyphs: HashMap<PathBuf, BTreeSet<char>>,
+    font_coverage: HashMap<PathBuf, FontCoverage>,
     default_font: Option<WeakFont>,
+    hits: u64,
+    misses: u64,
+    warnings: Vec<String>,
+    // Offscreen targets for nested compositions, isolated layers and
+    // outlined text, reused frame to frame. The slot map is only valid for
+    // the frame currently being drawn.
+    offscreen_targets: Vec<Option<RenderTexture2D>>,
+    offscreen_slots: HashMap<OffscreenKey, usize>,
+    luma_matte_shader: Option<Shader>,
+    // Clip shaders (see `ObjectShader`), compiled on first use.
+    shaders: HashMap<PathBuf, Shader>,
+    // Built-in post effect shaders by effect name, and lookup tables.
+    post_shaders: HashMap<&'static str, Shader>,
+    luts: HashMap<PathBuf, Texture2D>,
+    plugins: Option<Arc<PluginRegistry>>,
+    // Offscreen targets are this many times the size asked for, for
+    // supersampling (see `RaylibRender::set_render_scale`).
+    render_scale: u32,
+    // Output pixels per scene pixel, below 1 for draft renders (see
+    // `RaylibRender::set_resolution_scale`).
+    resolution_scale: f32,
+    // Frame position of the drawn target's top-left corner, while drawing a
+    // region of the frame (see `RaylibRender::set_region`).
+    draw_origin: (f32, f32),
 }
 
 impl ResourceCache {
     pub fn new() -> Self {
         Self {
             textures: HashMap::new(),
+            frame: 0,
+            texture_budget: None,
+            warned_budget: false,
+            last_needed: HashMap::new(),
+            evicted: 0,
+            prefetch: None,
+            prefetched: 0,
+            font_files: HashMap::new(),
             fonts: HashMap::new(),
+            font_glyphs: HashMap::new(),
+            font_coverage: HashMap::new(),
             default_font: None,
+            hits: 0,
+            misses: 0,
+            warnings: Vec::new(),
+            offscreen_targets: Vec::new(),
+            offscreen_slots: HashMap::new(),
+            luma_matte_shader: None,
+            shaders: HashMap::new(),
+            post_shaders: HashMap::new(),
+            luts: HashMap::new(),
+            plugins: None,
+            render_scale: 1,
+            resolution_scale: 1.0,
+            draw_origin: (0.0, 0.0),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let images = self
+            .textures
+            .values()
+            .map(|cached| (cached.image.texture.width, cached.image.texture.height));
+        let targets = self
+            .offscreen_targets
+            .iter()
+            .flatten()
+            .map(|target| (target.texture().width, target.texture().height));
+        let texture_bytes = images
+            .chain(targets)
+            .map(|(width, height)| width.max(0) as u64 * height.max(0) as u64 * 4)
+            .sum();
+        CacheStats {
+            textures: self.textures.len(),
+            fonts: self.fonts.len(),
+            texture_bytes,
+            hits: self.hits,
+            misses: self.misses,
+            evicted: self.evicted,
+            prefetched: self.prefetched,
         }
     }
 
+    // Frees every cached image texture; frames reload what they draw.
+    pub fn release_textures(&mut self) {
+        self.evicted += self.textures.len() as u64;
+        self.textures.clear();
+    }
+
+    // Caps the memory image textures keep loaded. Past it, the least recently
+    // drawn images are freed before each frame, to be reloaded if drawn
+    // again. Images the frame draws stay loaded even over the budget.
+    pub fn set_texture_budget(&mut self, bytes: Option<u64>) {
+        self.texture_budget = bytes;
+        self.warned_budget = false;
+    }
+
+    pub fn texture_budget(&self) -> Option<u64> {
+        self.texture_budget
+    }
+
+    // Notes when each image in `timeline` is last drawn: at the end of its
+    // last clip, or of the composition clip it's nested in.
+    pub(crate) fn plan_releases(&mut self, timeline: &Timeline) {
+        self.last_needed.clear();
+        collect_image_ends(timeline, None, &mut self.last_needed);
+    }
+
+    // Frees textures `plan_releases` says nothing draws after `t`. Renders
+    // going forward never need them again; one going back reloads them.
+    pub(crate) fn release_ended(&mut self, t: f32) {
+        let before = self.textures.len();
+        let last_needed = &self.last_needed;
+        self.textures
+            .retain(|key, _| last_needed.get(key).is_none_or(|end| *end > t));
+        self.evicted += (before - self.textures.len()) as u64;
+    }
+
+    // Loads the images and fonts of clips starting up to `lookahead` seconds
+    // ahead on a background thread, once `plan_prefetch` has seen the
+    // timeline. Images are decoded there; frames only upload them.
+    pub fn set_prefetch(&mut self, lookahead: Option<f32>) {
+        self.prefetch = lookahead.map(Prefetcher::spawn);
+    }
+
+    pub(crate) fn plan_prefetch(&mut self, timeline: &Timeline) {
+        if let Some(prefetch) = &mut self.prefetch {
+            prefetch.plan(timeline);
+        }
+    }
+
+    // Non-fatal problems noticed while loading, drained by the render report.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    pub fn set_plugins(&mut self, plugins: Arc<PluginRegistry>) {
+        self.plugins = Some(plugins);
+    }
+
+    pub(crate) fn set_render_scale(&mut self, scale: u32) {
+        self.render_scale = scale;
+    }
+
+    pub fn render_scale(&self) -> u32 {
+        self.render_scale
+    }
+
+    pub(crate) fn set_resolution_scale(&mut self, scale: f32) {
+        self.resolution_scale = scale;
+    }
+
+    pub fn resolution_scale(&self) -> f32 {
+        self.resolution_scale
+    }
+
+    // Target pixels per scene pixel: the resolution scale, supersampled.
+    pub fn draw_scale(&self) -> f32 {
+        self.resolution_scale * self.render_scale as f32
+    }
+
+    // Output size of something `width` x `height` in scene pixels.
+    pub fn scaled_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let scale = |value: u32| ((value as f32 * self.resolution_scale).round() as u32).max(1);
+        (scale(width), scale(height))
+    }
+
+    pub(crate) fn set_draw_origin(&mut self, origin: (f32, f32)) {
+        self.draw_origin = origin;
+    }
+
+    pub(crate) fn draw_origin(&self) -> (f32, f32) {
+        self.draw_origin
+    }
+
+    pub fn plugins(&self) -> Result<&PluginRegistry> {
+        self.plugins
+            .as_deref()
+            .context(ErrorKind::InvalidProject.error("scene uses plugins but no registry was set"))
+    }
+
     pub fn set_default_font(&mut self, rl: &RaylibHandle) {
         self.default_font = Some(rl.get_font_default());
     }
 
-    pub fn get_texture(&self, path: &Path) -> Result<&Texture2D> {
+    pub fn get_texture(&self, image: &ImageObject) -> Result<&LoadedImage> {
+        let path = &image.path;
         if !path.exists() {
-            bail!("image asset not found: {}", path.display());
+            bail!(
+                ErrorKind::MissingAsset.error(format!("image asset not found: {}", path.display()))
+            );
         }
-        Ok(self.textures.get(path).expect("texture cache missing"))
+        Ok(&self
+            .textures
+            .get(&(path.clone(), image.load))
+            .expect("texture cache missing")
+            .image)
     }
 
+    // Loads what `scene` draws, then keeps image textures within the budget.
     pub fn preload_for_scene(
         &mut self,
         rl: &mut RaylibHandle,
         thread: &RaylibThread,
         scene: &SampledScene,
     ) -> Result<()> {
+        self.frame += 1;
+        if let Some(prefetch) = &mut self.prefetch {
+            let (textures, fonts) = (&self.textures, &self.fonts);
+            prefetch.request_ahead(scene.time, |asset| match asset {
+                PrefetchAsset::Image(key) => textures.contains_key(key),
+                PrefetchAsset::Font(path) => fonts.contains_key(path),
+            });
+        }
         self.set_default_font(rl);
+        self.preload_scene(rl, thread, scene)?;
+        self.evict_over_budget(scene.time);
+        Ok(())
+    }
+
+    fn preload_scene(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        scene: &SampledScene,
+    ) -> Result<()> {
         for layer in &scene.layers {
             for clip in &layer.clips {
                 if let Object::Image(image) = &clip.object {
                     let path = &image.path;
                     if !path.exists() {
-                        bail!("image asset not found: {}", path.display());
+                        bail!(
+                            ErrorKind::MissingAsset
+                                .error(format!("image asset not found: {}", path.display()))
+                        );
                     }
-                    if !self.textures.contains_key(path) {
-                        let tex = rl
-                            .load_texture(thread, path.to_string_lossy().as_ref())
-                            .context("failed to load texture")?;
-                        self.textures.insert(path.to_path_buf(), tex);
+                    let key = (path.to_path_buf(), image.load);
+                    match self.textures.get_mut(&key) {
+                        Some(cached) => {
+                            self.hits += 1;
+                            cached.last_used = self.frame;
+                        }
+                        None => {
+                            self.misses += 1;
+                            let image = self.load_texture(rl, thread, &key)?;
+                            let last_used = self.frame;
+                            self.textures
+                                .insert(key, CachedTexture { image, last_used });
+                        }
                     }
                 }
                 if let Object::Text(text) = &clip.object {
-                    self.preload_font_family(rl, thread, &text.font)?;
+                    self.preload_text(rl, thread, text)?;
+                }
+                if let Some(shader) = &clip.shader {
+                    self.load_shader(rl, thread, &shader.path)?;
+                }
+                if let Some(nested) = &clip.nested {
+                    self.preload_scene(rl, thread, nested)?;
                 }
             }
         }
         Ok(())
     }
 
+    // Uploads the image the prefetcher decoded for `key`, or decodes it now.
+    fn load_texture(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        key: &TextureKey,
+    ) -> Result<LoadedImage> {
+        let prefetched = self
+            .prefetch
+            .as_mut()
+            .and_then(|prefetch| prefetch.take_image(key));
+        let decoded = match prefetched {
+            Some(decoded) => {
+                self.prefetched += 1;
+                decoded
+            }
+            None => decode_image(&key.0, key.1)?,
+        };
+        upload_image(rl, thread, decoded)
+    }
+
+    // Frees the least recently drawn image textures until they fit the
+    // budget, keeping those the current frame draws.
+    fn evict_over_budget(&mut self, time: f32) {
+        let Some(budget) = self.texture_budget else {
+            return;
+        };
+        let mut bytes: u64 = self
+            .textures
+            .values()
+            .map(|cached| cached.image.bytes())
+            .sum();
+        if bytes <= budget {
+            return;
+        }
+        let mut idle: Vec<(u64, TextureKey)> = self
+            .textures
+            .iter()
+            .filter(|(_, cached)| cached.last_used < self.frame)
+            .map(|(key, cached)| (cached.last_used, key.clone()))
+            .collect();
+        idle.sort_by_key(|(last_used, _)| *last_used);
+        for (_, key) in idle {
+            if bytes <= budget {
+                break;
+            }
+            if let Some(cached) = self.textures.remove(&key) {
+                bytes -= cached.image.bytes();
+                self.evicted += 1;
+            }
+        }
+        if bytes > budget && !self.warned_budget {
+            self.warned_budget = true;
+            self.warnings.push(format!(
+                "frame at {time:.3}s draws {bytes} bytes of images, over the texture budget of \
+                 {budget}"
+            ));
+        }
+    }
+
+    pub(crate) fn begin_offscreen_frame(&mut self) {
+        self.offscreen_slots.clear();
+    }
+
+    // Hands out the next offscreen target (resized if needed) for `key`, at
+    // `width` x `height` scene pixels at the output resolution, times the
+    // render scale. The caller draws into it and gives it back with
+    // `return_offscreen_target`.
+    pub(crate) fn take_offscreen_target(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        key: impl Into<OffscreenKey>,
+        width: u32,
+        height: u32,
+    ) -> Result<(usize, RenderTexture2D)> {
+        let (width, height) = self.scaled_size(width, height);
+        let (width, height) = (width * self.render_scale, height * self.render_scale);
+        let slot = self.offscreen_slots.len();
+        self.offscreen_slots.insert(key.into(), slot);
+        if self.offscreen_targets.len() <= slot {
+            self.offscreen_targets.resize_with(slot + 1, || None);
+        }
+
+        let reusable = self.offscreen_targets[slot].take().filter(|target| {
+            target.texture().width == width as i32 && target.texture().height == height as i32
+        });
+        let target = match reusable {
+            Some(target) => target,
+            None => rl
+                .load_render_texture(thread, width, height)
+                .context(ErrorKind::Gpu.error("failed to create offscreen render texture"))?,
+        };
+        Ok((slot, target))
+    }
+
+    pub(crate) fn return_offscreen_target(&mut self, slot: usize, target: RenderTexture2D) {
+        self.offscreen_targets[slot] = Some(target);
+    }
+
+    pub fn offscreen_texture(&self, key: impl Into<OffscreenKey>) -> Result<&RenderTexture2D> {
+        let slot = self
+            .offscreen_slots
+            .get(&key.into())
+            .context("offscreen target was not rendered this frame")?;
+        self.offscreen_targets[*slot]
+            .as_ref()
+            .context("offscreen target missing")
+    }
+
+    // Turns a texture's luma into alpha, for luma track mattes. Loaded on
+    // first use, outside any texture mode.
+    pub(crate) fn load_luma_matte_shader(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread) {
+        if self.luma_matte_shader.is_none() {
+            let shader = rl.load_shader_from_memory(thread, None, Some(LUMA_MATTE_FS));
+            self.luma_matte_shader = Some(shader);
+        }
+    }
+
+    pub(crate) fn luma_matte_shader(&self) -> Option<&Shader> {
+        self.luma_matte_shader.as_ref()
+    }
+
+    fn load_shader(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        path: &Path,
+    ) -> Result<()> {
+        if self.shaders.contains_key(path) {
+            return Ok(());
+        }
+        let source = fs::read_to_string(path).with_context(|| {
+            ErrorKind::MissingAsset.error(format!("shader not found: {}", path.display()))
+        })?;
+        let shader = rl.load_shader_from_memory(thread, None, Some(&source));
+        // raylib falls back to its default shader, logging why, when the
+        // source doesn't compile.
+        if shader.as_ref().id == unsafe { raylib::ffi::rlGetShaderIdDefault() } {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "shader failed to compile (see raylib's warnings): {}",
+                path.display()
+            )));
+        }
+        self.shaders.insert(path.to_path_buf(), shader);
+        Ok(())
+    }
+
+    pub(crate) fn shader(&self, path: &Path) -> Result<&Shader> {
+        self.shaders
+            .get(path)
+            .with_context(|| format!("shader was not preloaded: {}", path.display()))
+    }
+
+    // Compiles the shaders and loads the lookup tables `effects` need.
+    pub(crate) fn preload_post(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        effects: &[PostEffect],
+    ) -> Result<()> {
+        for effect in effects {
+            let (name, source) = post_shader_source(effect);
+            if !self.post_shaders.contains_key(name) {
+                let shader = rl.load_shader_from_memory(thread, None, Some(source));
+                self.post_shaders.insert(name, shader);
+            }
+            if let PostEffect::Lut { path, .. } = effect
+                && !self.luts.contains_key(path)
+            {
+                let lut = load_lut(rl, thread, path)?;
+                self.luts.insert(path.clone(), lut);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn post_shader(&self, name: &str) -> Result<&Shader> {
+        self.post_shaders
+            .get(name)
+            .with_context(|| format!("{name} shader was not preloaded"))
+    }
+
+    pub(crate) fn lut(&self, path: &Path) -> Result<&Texture2D> {
+        self.luts
+            .get(path)
+            .with_context(|| format!("lookup table was not preloaded: {}", path.display()))
+    }
+
     pub fn preload_font_family(
         &mut self,
         rl: &mut RaylibHandle,
         thread: &RaylibThread,
         family: &FontFamily,
     ) -> Result<()> {
-        for source in [
-            Some(&family.regular),
-            family.bold.as_ref(),
-            family.italic.as_ref(),
-            family.bold_italic.as_ref(),
-        ]
-        .into_iter()
-        .flatten()
-        {
+        self.load_family_glyphs(rl, thread, family, &BTreeSet::new())
+    }
+
+    // Loads every font in the family (fallbacks included) with the glyphs
+    // the text needs, reloading a font when new codepoints show up.
+    pub fn preload_text(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        text: &TextObject,
+    ) -> Result<()> {
+        let chars: BTreeSet<char> = text
+            .text
+            .runs
+            .iter()
+            .flat_map(|run| run.text.chars())
+            .filter(|ch| !ch.is_control())
+            .collect();
+        self.load_family_glyphs(rl, thread, &text.font, &chars)
+    }
+
+    fn load_family_glyphs(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        family: &FontFamily,
+        chars: &BTreeSet<char>,
+    ) -> Result<()> {
+        for source in family.sources() {
             if let FontSource::Path(path) = source {
-                if !self.fonts.contains_key(path) {
-                    let font = rl
-                        .load_font(thread, path.to_string_lossy().as_ref())
-                        .context("failed to load font")?;
-                    self.fonts.insert(path.clone(), font);
+                self.load_font_glyphs(rl, thread, path, chars)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn load_font_glyphs(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        path: &Path,
+        chars: &BTreeSet<char>,
+    ) -> Result<()> {
+        if !self.fonts.contains_key(path)
+            && !self.font_files.contains_key(path)
+            && let Some(data) = self
+                .prefetch
+                .as_mut()
+                .and_then(|prefetch| prefetch.take_font(path))
+        {
+            self.prefetched += 1;
+            self.font_files.insert(path.to_path_buf(), data);
+        }
+        if !self.font_coverage.contains_key(path) {
+            let coverage = match self.font_files.get(path) {
+                Some(data) => FontCoverage::from_bytes(data)
+                    .with_context(|| format!("failed to parse font cmap: {}", path.display())),
+                None => FontCoverage::from_file(path),
+            };
+            // Fonts we can't parse are assumed to cover everything, which is
+            // what single-font rendering did before fallbacks existed.
+            let coverage = match coverage {
+                Ok(coverage) => coverage,
+                Err(err) => {
+                    self.warnings.push(format!(
+                        "{err:#}; assuming the font covers every glyph"
+                    ));
+                    FontCoverage::full()
                 }
+            };
+            self.font_coverage.insert(path.to_path_buf(), coverage);
+        }
+        let coverage = &self.font_coverage[path];
+
+        let loaded = self.font_glyphs.entry(path.to_path_buf()).or_default();
+        let missing: Vec<char> = chars
+            .iter()
+            .copied()
+            .filter(|ch| coverage.contains(*ch) && !loaded.contains(ch))
+            .collect();
+        if self.fonts.contains_key(path) && missing.is_empty() {
+            self.hits += 1;
+            return Ok(());
+        }
+        self.misses += 1;
+
+        loaded.extend((' '..='~').chain(missing));
+        let codepoints: String = loaded.iter().collect();
+        let font = match self.font_files.get(path) {
+            Some(data) => {
+                // raylib picks the font loader by extension.
+                let extension = path.extension().unwrap_or_default().to_string_lossy();
+                rl.load_font_from_memory(
+                    thread,
+                    &format!(".{extension}"),
+                    data,
+                    FONT_LOAD_SIZE,
+                    Some(&codepoints),
+                )
             }
+            None => rl.load_font_ex(
+                thread,
+                path.to_string_lossy().as_ref(),
+                FONT_LOAD_SIZE,
+                Some(&codepoints),
+            ),
         }
+        .context(ErrorKind::MissingAsset.error("failed to load font"))?;
+        self.fonts.insert(path.to_path_buf(), font);
         Ok(())
     }
 
     pub fn resolve_font(&self, family: &FontFamily, style: StyleFlags) -> Result<FontRef<'_>> {
-        match family.resolve(style) {
+        self.font_ref(family.resolve(style))
+    }
+
+    // Splits `text` into consecutive segments, each drawn with the first font
+    // in the family's chain that has glyphs for it.
+    pub fn resolve_font_runs<'a>(
+        &self,
+        family: &FontFamily,
+        style: StyleFlags,
+        text: &'a str,
+    ) -> Result<Vec<(FontRef<'_>, &'a str)>> {
+        let chain = family.chain(style);
+        let mut segments: Vec<(usize, usize, usize)> = Vec::new();
+        for (start, ch) in text.char_indices() {
+            let end = start + ch.len_utf8();
+            let source = if ch.is_whitespace() && !segments.is_empty() {
+                segments[segments.len() - 1].0
+            } else {
+                chain
+                    .iter()
+                    .position(|source| self.covers(source, ch))
+                    .unwrap_or(0)
+            };
+            match segments.last_mut() {
+                Some(last) if last.0 == source => last.2 = end,
+                _ => segments.push((source, start, end)),
+            }
+        }
+
+        segments
+            .into_iter()
+            .map(|(source, start, end)| Ok((self.font_ref(chain[source])?, &text[start..end])))
+            .collect()
+    }
+
+    fn covers(&self, source: &FontSource, ch: char) -> bool {
+        match source {
+            FontSource::Default => ch == ' ' || ch.is_ascii_graphic(),
+            FontSource::Path(path) => self
+                .font_coverage
+                .get(path)
+                .is_none_or(|coverage| coverage.contains(ch)),
+        }
+    }
+
+    fn font_ref(&self, source: &FontSource) -> Result<FontRef<'_>> {
+        match source {
             FontSource::Default => {
                 let font = self
                     .default_font
@@ -106,6 +756,114 @@ impl ResourceCache {
     }
 }
 
+// An image decoded with its load options applied, ready to upload.
+pub(crate) struct DecodedImage {
+    image: Image,
+    width: f32,
+    height: f32,
+    mipmaps: bool,
+}
+
+// A raylib `Image` is pixels on the heap with no tie to the GL context, so
+// one decoded on the prefetch thread can be uploaded on the render thread.
+unsafe impl Send for DecodedImage {}
+
+// Decodes an image file and applies `options` on the CPU. Needs no GL
+// context, so it also runs on the prefetch thread.
+pub(crate) fn decode_image(path: &Path, options: ImageLoadOptions) -> Result<DecodedImage> {
+    let mut image = Image::load_image(path.to_string_lossy().as_ref())
+        .context(ErrorKind::MissingAsset.error("failed to load texture"))?;
+
+    if options.orientation {
+        let orientation = File::open(path).ok().and_then(|file| {
+            let mut head = Vec::new();
+            file.take(EXIF_SCAN_BYTES).read_to_end(&mut head).ok()?;
+            jpeg_orientation(&head)
+        });
+        // 2-8 are the mirrorings and quarter turns that make the stored
+        // pixels upright.
+        match orientation.unwrap_or(1) {
+            2 => image.flip_horizontal(),
+            3 => {
+                image.rotate_cw();
+                image.rotate_cw();
+            }
+            4 => image.flip_vertical(),
+            5 => {
+                image.flip_horizontal();
+                image.rotate_ccw();
+            }
+            6 => image.rotate_cw(),
+            7 => {
+                image.flip_horizontal();
+                image.rotate_cw();
+            }
+            8 => image.rotate_ccw(),
+            _ => {}
+        }
+    }
+
+    let (width, height) = (image.width, image.height);
+    if let Some(max) = options.max_dimension {
+        let longest = width.max(height);
+        if max > 0 && longest > max as i32 {
+            let scale = max as f32 / longest as f32;
+            let scaled = |side: i32| ((side as f32 * scale).round() as i32).max(1);
+            image.resize(scaled(width), scaled(height));
+        }
+    }
+    if options.premultiply {
+        image.alpha_premultiply();
+    }
+    Ok(DecodedImage {
+        image,
+        width: width as f32,
+        height: height as f32,
+        mipmaps: options.mipmaps,
+    })
+}
+
+fn upload_image(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    decoded: DecodedImage,
+) -> Result<LoadedImage> {
+    let mut texture = rl
+        .load_texture_from_image(thread, &decoded.image)
+        .context(ErrorKind::MissingAsset.error("failed to load texture"))?;
+    if decoded.mipmaps {
+        texture.gen_texture_mipmaps();
+        texture.set_texture_filter(thread, TextureFilter::TEXTURE_FILTER_TRILINEAR);
+    }
+    Ok(LoadedImage {
+        texture,
+        width: decoded.width,
+        height: decoded.height,
+    })
+}
+
+// A strip lookup table (see `PostEffect::Lut`), filtered so colours between
+// its entries are interpolated.
+fn load_lut(rl: &mut RaylibHandle, thread: &RaylibThread, path: &Path) -> Result<Texture2D> {
+    let image = Image::load_image(path.to_string_lossy().as_ref()).with_context(|| {
+        ErrorKind::MissingAsset.error(format!("failed to load lookup table: {}", path.display()))
+    })?;
+    let size = image.height;
+    if size < 2 || image.width != size * size {
+        bail!(ErrorKind::InvalidProject.error(format!(
+            "lookup table {} is {}x{}; it must be N*N wide and N high",
+            path.display(),
+            image.width,
+            image.height
+        )));
+    }
+    let mut texture = rl.load_texture_from_image(thread, &image).with_context(|| {
+        ErrorKind::Gpu.error(format!("failed to upload lookup table: {}", path.display()))
+    })?;
+    texture.set_texture_filter(thread, TextureFilter::TEXTURE_FILTER_BILINEAR);
+    Ok(texture)
+}
+
 #[derive(Clone, Copy)]
 pub enum FontRef<'a> {
     Default(&'a WeakFont),
@@ -118,3 +876,26 @@ pub fn measure_text(font: FontRef<'_>, text: &str, font_size: f32, spacing: f32)
         FontRef::Loaded(font) => font.measure_text(text, font_size, spacing).x,
     }
 }
+
+// Adds to `ends` when each image in `timeline` is last drawn, in the outer
+// timeline's seconds. Images inside a composition end with its clip
+// (`outer_end`), since its own times are local to the clip.
+fn collect_image_ends(
+    timeline: &Timeline,
+    outer_end: Option<f32>,
+    ends: &mut HashMap<TextureKey, f32>,
+) {
+    for clip in timeline.layers.iter().flat_map(|layer| &layer.clips) {
+        let end = outer_end.unwrap_or(clip.end);
+        match &clip.object {
+            Object::Image(image) => {
+                let last = ends.entry((image.path.clone(), image.load)).or_insert(end);
+                *last = last.max(end);
+            }
+            Object::Composition(composition) => {
+                collect_image_ends(&composition.timeline, Some(end), ends);
+            }
+            _ => {}
+        }
+    }
+}
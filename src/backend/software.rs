@@ -0,0 +1,745 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use tiny_skia::{
+    ColorU8, FillRule, FilterQuality, Mask, Paint, PathBuilder, Pixmap, PixmapPaint, Rect, Stroke,
+};
+
+use crate::backend::asset_bytes::AssetBytes;
+use crate::backend::cancel::CancelToken;
+use crate::backend::clock::{Clock, FrameClock};
+use crate::backend::premultiply::{
+    decode_table, encode, premultiply, premultiply_linear, unpremultiply, unpremultiply_linear,
+};
+use crate::backend::render_backend::{
+    check_error_assertions, check_frame_time, check_time_range, clip_placement, sample_scene,
+    validate_for_render, RenderBackend,
+};
+use crate::backend::software_text::SoftwareFonts;
+use crate::error::ErrorKind;
+use crate::plugin::{Canvas, FrameMut, PluginRegistry};
+use crate::scene::{Color, ImageObject, Object, PluginEffect, Shape, Transform, Vec2};
+use crate::timeline::{
+    BlendMode, DataSource, SampledClip, SampledLayer, SampledScene, Timeline, TrackMatte,
+};
+
+// Draws timelines on the CPU with tiny-skia, for machines with no GPU or GL
+// at all and for tests that need the same pixels on every machine. Frames
+// come out as `RaylibRender`'s do (bottom-up straight RGBA) and look the
+// same, though antialiasing and glyphs differ slightly. Images must be PNGs
+// and text needs font files (or their bytes, see `add_asset`); clip shaders
+// and post effects need raylib.
+pub struct SoftwareRender {
+    width: u32,
+    height: u32,
+    bg: Color,
+    // Blend in linear light; see `set_linear_light`.
+    linear: bool,
+    plugins: Option<Arc<PluginRegistry>>,
+    data: Option<Arc<dyn DataSource>>,
+    cancel: Option<CancelToken>,
+    assets: AssetBytes,
+    images: HashMap<PathBuf, Pixmap>,
+    fonts: SoftwareFonts,
+}
+
+impl SoftwareRender {
+    pub fn new(width: u32, height: u32, bg: Color) -> Result<Self> {
+        if width == 0 || height == 0 {
+            bail!(ErrorKind::InvalidProject.error("frame width and height must be > 0"));
+        }
+        Ok(Self {
+            width,
+            height,
+            bg,
+            linear: false,
+            plugins: None,
+            data: None,
+            cancel: None,
+            assets: AssetBytes::default(),
+            images: HashMap::new(),
+            fonts: SoftwareFonts::default(),
+        })
+    }
+
+    // Registry used to draw `Object::Plugin` clips and apply timeline effects.
+    pub fn set_plugins(&mut self, plugins: Arc<PluginRegistry>) {
+        self.plugins = Some(plugins);
+    }
+
+    // Supplies per-frame variables for clip bindings.
+    pub fn set_data_source(&mut self, data: Arc<dyn DataSource>) {
+        self.data = Some(data);
+    }
+
+    // Checked before each frame; cancelling ends the render early, without
+    // an error.
+    pub fn set_cancel_token(&mut self, cancel: CancelToken) {
+        self.cancel = Some(cancel);
+    }
+
+    // Serves an image or font file from `bytes` wherever a project refers to
+    // `path`, e.g. on wasm32, where there are no files to read.
+    pub fn add_asset(&mut self, path: impl Into<PathBuf>, bytes: impl Into<Arc<[u8]>>) {
+        let path = path.into();
+        self.images.remove(&path);
+        self.fonts.forget(&path);
+        self.assets.insert(path, bytes);
+    }
+
+    // Blends and fades in linear light instead of on sRGB values, as
+    // `WgpuRender::set_linear_light` does. Every clip is then drawn on its
+    // own and blended into the frame, which is slower. Off by default.
+    pub fn set_linear_light(&mut self, linear: bool) {
+        self.linear = linear;
+    }
+
+    pub fn linear_light(&self) -> bool {
+        self.linear
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn render_timeline_rgba(
+        &mut self,
+        timeline: &Timeline,
+        start_time: f32,
+        end_time: f32,
+        mut on_frame: impl FnMut(f32, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        check_time_range(timeline, start_time, end_time)?;
+        validate_for_render(timeline, self.plugins.as_deref(), &self.assets)?;
+        let mut clock = FrameClock::new(timeline.fps, start_time, end_time);
+        while let Some(t) = clock.tick() {
+            if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                break;
+            }
+            let scene = sample_scene(timeline, self.data.as_ref(), t)?;
+            let rgba = self.render_scene_to_rgba(&scene)?;
+            check_error_assertions(timeline, &scene, (self.width, self.height), |object, at| {
+                self.measure_object(object, at)
+            })?;
+            on_frame(t, &rgba)?;
+        }
+        Ok(())
+    }
+
+    // The single frame at `time`, bottom-up straight RGBA.
+    pub fn render_frame_rgba(&mut self, timeline: &Timeline, time: f32) -> Result<Vec<u8>> {
+        check_frame_time(timeline, time)?;
+        validate_for_render(timeline, self.plugins.as_deref(), &self.assets)?;
+        let scene = sample_scene(timeline, self.data.as_ref(), time)?;
+        self.render_scene_to_rgba(&scene)
+    }
+
+    pub fn render_scene_to_rgba(&mut self, scene: &SampledScene) -> Result<Vec<u8>> {
+        if !scene.post.is_empty() {
+            bail!(ErrorKind::InvalidProject
+                .error("post effects aren't supported by the software renderer"));
+        }
+        self.preload(scene)?;
+        let mut frame = self.filled(self.width, self.height, self.bg)?;
+        self.draw_layers(&mut frame, scene, self.width, self.height)?;
+
+        let mut rgba = self.straight_rgba(&frame);
+        rgba = rgba
+            .chunks_exact(self.width as usize * 4)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        if !scene.effects.is_empty() {
+            let mut frame = FrameMut {
+                pixels: &mut rgba,
+                width: self.width,
+                height: self.height,
+                bottom_up: true,
+            };
+            self.apply_effects(&scene.effects, scene.time, &mut frame)?;
+        }
+        Ok(rgba)
+    }
+
+    fn measure_object(&self, object: &Object, transform: &Transform) -> Result<Option<Vec2>> {
+        match object {
+            Object::Image(image) => {
+                let pixmap = self.image(image)?;
+                Ok(Some(Vec2 {
+                    x: pixmap.width() as f32 * transform.scale.x,
+                    y: pixmap.height() as f32 * transform.scale.y,
+                }))
+            }
+            Object::Text(text) => {
+                let (x, y) = self.fonts.block_size(text, transform)?;
+                Ok(Some(Vec2 { x, y }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn preload(&mut self, scene: &SampledScene) -> Result<()> {
+        for clip in scene.layers.iter().flat_map(|layer| &layer.clips) {
+            if clip.shader.is_some() {
+                bail!(ErrorKind::InvalidProject
+                    .error("clip shaders aren't supported by the software renderer"));
+            }
+            match &clip.object {
+                Object::Image(image) => self.load_image(image)?,
+                Object::Text(text) => self.fonts.preload(&text.font, &self.assets)?,
+                _ => {}
+            }
+            if let Some(nested) = &clip.nested {
+                self.preload(nested)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn load_image(&mut self, image: &ImageObject) -> Result<()> {
+        let path = &image.path;
+        if self.images.contains_key(path) {
+            return Ok(());
+        }
+        let Some(bytes) = self.assets.read(path) else {
+            bail!(
+                ErrorKind::MissingAsset.error(format!("image asset not found: {}", path.display()))
+            );
+        };
+        let pixmap = Pixmap::decode_png(&bytes).map_err(|err| {
+            ErrorKind::MissingAsset.error(format!(
+                "failed to load {} (the software renderer reads PNGs only): {err}",
+                path.display()
+            ))
+        })?;
+        self.images.insert(path.clone(), pixmap);
+        Ok(())
+    }
+
+    fn image(&self, image: &ImageObject) -> Result<&Pixmap> {
+        self.images
+            .get(&image.path)
+            .with_context(|| format!("image was not preloaded: {}", image.path.display()))
+    }
+
+    fn plugins(&self) -> Result<&PluginRegistry> {
+        self.plugins
+            .as_deref()
+            .context(ErrorKind::InvalidProject.error("scene uses plugins but no registry was set"))
+    }
+
+    fn apply_effects(
+        &self,
+        effects: &[PluginEffect],
+        time: f32,
+        frame: &mut FrameMut<'_>,
+    ) -> Result<()> {
+        let plugins = self.plugins()?;
+        for effect in effects {
+            plugins.effect(&effect.kind)?.apply(frame, &effect.params, time)?;
+        }
+        Ok(())
+    }
+
+    // Same structure as the raylib backend's `draw_layers`: runs of layers
+    // sharing a group are drawn together and composited at the group's
+    // opacity, and blended or matted layers are drawn on their own first.
+    fn draw_layers(
+        &self,
+        pixmap: &mut Pixmap,
+        scene: &SampledScene,
+        width: u32,
+        height: u32,
+    ) -> Result<()> {
+        for run in scene.layers.chunk_by(|a, b| a.group == b.group) {
+            let Some(group) = run[0].group.map(|group| &scene.groups[group]) else {
+                self.draw_layer_run(pixmap, run, width, height)?;
+                continue;
+            };
+            let mut target = blank(width, height)?;
+            self.draw_layer_run(&mut target, run, width, height)?;
+            if !group.effects.is_empty() {
+                let mut rgba = self.straight_rgba(&target);
+                let mut frame = FrameMut {
+                    pixels: &mut rgba,
+                    width,
+                    height,
+                    bottom_up: false,
+                };
+                self.apply_effects(&group.effects, scene.time, &mut frame)?;
+                self.load_straight(&mut target, &rgba);
+            }
+            self.composite(pixmap, &target, group.blend, group.opacity);
+        }
+        Ok(())
+    }
+
+    fn draw_layer_run(
+        &self,
+        pixmap: &mut Pixmap,
+        layers: &[SampledLayer],
+        width: u32,
+        height: u32,
+    ) -> Result<()> {
+        let mut skip_matte_source = false;
+        for (index, layer) in layers.iter().enumerate() {
+            if skip_matte_source {
+                skip_matte_source = false;
+                continue;
+            }
+            if layer.blend == BlendMode::Normal && layer.matte.is_none() {
+                self.draw_clips(pixmap, layer, width, height)?;
+                continue;
+            }
+            let mut target = blank(width, height)?;
+            self.draw_clips(&mut target, layer, width, height)?;
+            if let (Some(matte), Some(source)) = (layer.matte, layers.get(index + 1)) {
+                let mut source_target = blank(width, height)?;
+                self.draw_clips(&mut source_target, source, width, height)?;
+                if self.linear {
+                    delinearize(&mut target);
+                    delinearize(&mut source_target);
+                }
+                apply_matte(&mut target, &source_target, matte);
+                if self.linear {
+                    linearize(&mut target);
+                }
+            }
+            skip_matte_source = layer.matte.is_some();
+            self.composite(pixmap, &target, layer.blend, 1.0);
+        }
+        Ok(())
+    }
+
+    fn draw_clips(
+        &self,
+        pixmap: &mut Pixmap,
+        layer: &SampledLayer,
+        width: u32,
+        height: u32,
+    ) -> Result<()> {
+        for clip in &layer.clips {
+            if !self.linear {
+                self.draw_clip(pixmap, &layer.transform, clip, width, height)?;
+                continue;
+            }
+            // Drawn on its own, then blended in linear light.
+            let mut target = blank(width, height)?;
+            self.draw_clip(&mut target, &layer.transform, clip, width, height)?;
+            linearize(&mut target);
+            composite_linear(pixmap, &target, BlendMode::Normal, 1.0);
+        }
+        Ok(())
+    }
+
+    // A frame or composition target cleared to `bg`.
+    fn filled(&self, width: u32, height: u32, bg: Color) -> Result<Pixmap> {
+        let mut pixmap = blank(width, height)?;
+        pixmap.fill(sk_color(bg, 1.0));
+        if self.linear {
+            linearize(&mut pixmap);
+        }
+        Ok(pixmap)
+    }
+
+    // Top-down straight RGBA of a frame or target.
+    fn straight_rgba(&self, pixmap: &Pixmap) -> Vec<u8> {
+        if !self.linear {
+            return straight_rgba(pixmap);
+        }
+        let mut rgba = pixmap.data().to_vec();
+        unpremultiply_linear(&mut rgba);
+        rgba
+    }
+
+    // The reverse of `straight_rgba`.
+    fn load_straight(&self, pixmap: &mut Pixmap, rgba: &[u8]) {
+        if !self.linear {
+            load_straight(pixmap, rgba);
+            return;
+        }
+        let data = pixmap.data_mut();
+        data.copy_from_slice(rgba);
+        premultiply_linear(data);
+    }
+
+    fn composite(&self, dst: &mut Pixmap, src: &Pixmap, blend: BlendMode, opacity: f32) {
+        if self.linear {
+            composite_linear(dst, src, blend, opacity);
+        } else {
+            composite(dst, src, blend, opacity);
+        }
+    }
+
+    fn draw_clip(
+        &self,
+        pixmap: &mut Pixmap,
+        layer_transform: &Transform,
+        clip: &SampledClip,
+        width: u32,
+        height: u32,
+    ) -> Result<()> {
+        let (transform, wipe) = clip_placement(layer_transform, clip, width, height);
+        let mut mask = None;
+        if let Some((x, y, w, h)) = wipe {
+            let mut wipe =
+                Mask::new(pixmap.width(), pixmap.height()).context("failed to create wipe mask")?;
+            if let Some(rect) = Rect::from_xywh(x, y, w, h) {
+                let path = PathBuilder::from_rect(rect);
+                let identity = tiny_skia::Transform::identity();
+                wipe.fill_path(&path, FillRule::Winding, false, identity);
+            }
+            mask = Some(wipe);
+        }
+        let mask = mask.as_ref();
+
+        let center = graph_to_screen(transform.pos, width, height);
+        let place = tiny_skia::Transform::from_translate(center.0, center.1)
+            .pre_rotate(transform.rotation);
+        if let (Object::Composition(composition), Some(nested)) = (&clip.object, &clip.nested) {
+            let mut target = self.filled(composition.width, composition.height, composition.bg)?;
+            self.draw_layers(&mut target, nested, composition.width, composition.height)?;
+            if !nested.effects.is_empty() {
+                let mut rgba = self.straight_rgba(&target);
+                let mut frame = FrameMut {
+                    pixels: &mut rgba,
+                    width: composition.width,
+                    height: composition.height,
+                    bottom_up: false,
+                };
+                self.apply_effects(&nested.effects, nested.time, &mut frame)?;
+                self.load_straight(&mut target, &rgba);
+            }
+            // Drawn like an image; it's blended in linear light once placed.
+            if self.linear {
+                delinearize(&mut target);
+            }
+            let at = place.pre_scale(transform.scale.x, transform.scale.y).pre_translate(
+                -(composition.width as f32) / 2.0,
+                -(composition.height as f32) / 2.0,
+            );
+            pixmap.draw_pixmap(0, 0, target.as_ref(), &image_paint(transform.opacity), at, mask);
+            return Ok(());
+        }
+
+        match &clip.object {
+            Object::Shape(Shape::Circle { radius, color }) => {
+                let radius = radius * transform.scale.x.max(0.0);
+                if let Some(path) = PathBuilder::from_circle(center.0, center.1, radius) {
+                    let paint = solid(*color, transform.opacity);
+                    let identity = tiny_skia::Transform::identity();
+                    pixmap.fill_path(&path, &paint, FillRule::Winding, identity, mask);
+                }
+            }
+            Object::Shape(Shape::Rect {
+                width: w,
+                height: h,
+                color,
+            }) => {
+                let (w, h) = ((w * transform.scale.x).abs(), (h * transform.scale.y).abs());
+                if let Some(rect) = Rect::from_xywh(-w / 2.0, -h / 2.0, w, h) {
+                    pixmap.fill_rect(rect, &solid(*color, transform.opacity), place, mask);
+                }
+            }
+            Object::Image(image) => {
+                let source = self.image(image)?;
+                let at = place.pre_scale(transform.scale.x, transform.scale.y).pre_translate(
+                    -(source.width() as f32) / 2.0,
+                    -(source.height() as f32) / 2.0,
+                );
+                let paint = image_paint(transform.opacity);
+                pixmap.draw_pixmap(0, 0, source.as_ref(), &paint, at, mask);
+            }
+            Object::Text(text) => self.fonts.draw(pixmap, text, &transform, center, mask)?,
+            Object::Plugin(plugin) => {
+                let plugin_impl = self.plugins()?.object(&plugin.kind)?;
+                let mut canvas = SkiaCanvas {
+                    pixmap,
+                    width,
+                    height,
+                    opacity: transform.opacity,
+                    mask,
+                };
+                plugin_impl.draw(&mut canvas, &plugin.params, &transform, clip.local_time)?;
+            }
+            // Drawn from the clip's sampled `nested` scene above.
+            Object::Composition(_) => {}
+        }
+        Ok(())
+    }
+}
+
+impl RenderBackend for SoftwareRender {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn set_plugins(&mut self, plugins: Arc<PluginRegistry>) {
+        SoftwareRender::set_plugins(self, plugins);
+    }
+
+    fn set_data_source(&mut self, data: Arc<dyn DataSource>) {
+        SoftwareRender::set_data_source(self, data);
+    }
+
+    fn set_cancel_token(&mut self, cancel: CancelToken) {
+        SoftwareRender::set_cancel_token(self, cancel);
+    }
+
+    fn render_scene_to_rgba(&mut self, scene: &SampledScene) -> Result<Vec<u8>> {
+        SoftwareRender::render_scene_to_rgba(self, scene)
+    }
+
+    fn render_frame_rgba(&mut self, timeline: &Timeline, time: f32) -> Result<Vec<u8>> {
+        SoftwareRender::render_frame_rgba(self, timeline, time)
+    }
+
+    fn render_timeline_rgba(
+        &mut self,
+        timeline: &Timeline,
+        start_time: f32,
+        end_time: f32,
+        on_frame: &mut dyn FnMut(f32, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        SoftwareRender::render_timeline_rgba(self, timeline, start_time, end_time, on_frame)
+    }
+}
+
+struct SkiaCanvas<'a> {
+    pixmap: &'a mut Pixmap,
+    width: u32,
+    height: u32,
+    opacity: f32,
+    mask: Option<&'a Mask>,
+}
+
+impl Canvas for SkiaCanvas<'_> {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn fill_rect(&mut self, center: Vec2, width: f32, height: f32, rotation: f32, color: Color) {
+        let (x, y) = graph_to_screen(center, self.width, self.height);
+        let Some(rect) = Rect::from_xywh(-width / 2.0, -height / 2.0, width, height) else {
+            return;
+        };
+        let place = tiny_skia::Transform::from_translate(x, y).pre_rotate(rotation);
+        self.pixmap
+            .fill_rect(rect, &solid(color, self.opacity), place, self.mask);
+    }
+
+    fn fill_circle(&mut self, center: Vec2, radius: f32, color: Color) {
+        let (x, y) = graph_to_screen(center, self.width, self.height);
+        if let Some(path) = PathBuilder::from_circle(x, y, radius) {
+            self.fill_path(&path, color);
+        }
+    }
+
+    fn fill_triangle(&mut self, a: Vec2, b: Vec2, c: Vec2, color: Color) {
+        let mut path = PathBuilder::new();
+        for (i, point) in [a, b, c].into_iter().enumerate() {
+            let (x, y) = graph_to_screen(point, self.width, self.height);
+            if i == 0 {
+                path.move_to(x, y);
+            } else {
+                path.line_to(x, y);
+            }
+        }
+        path.close();
+        if let Some(path) = path.finish() {
+            self.fill_path(&path, color);
+        }
+    }
+
+    fn line(&mut self, from: Vec2, to: Vec2, thickness: f32, color: Color) {
+        let (x1, y1) = graph_to_screen(from, self.width, self.height);
+        let (x2, y2) = graph_to_screen(to, self.width, self.height);
+        let mut path = PathBuilder::new();
+        path.move_to(x1, y1);
+        path.line_to(x2, y2);
+        let Some(path) = path.finish() else {
+            return;
+        };
+        let stroke = Stroke {
+            width: thickness,
+            ..Stroke::default()
+        };
+        let identity = tiny_skia::Transform::identity();
+        self.pixmap
+            .stroke_path(&path, &solid(color, self.opacity), &stroke, identity, self.mask);
+    }
+}
+
+impl SkiaCanvas<'_> {
+    fn fill_path(&mut self, path: &tiny_skia::Path, color: Color) {
+        let paint = solid(color, self.opacity);
+        let identity = tiny_skia::Transform::identity();
+        self.pixmap
+            .fill_path(path, &paint, FillRule::Winding, identity, self.mask);
+    }
+}
+
+fn blank(width: u32, height: u32) -> Result<Pixmap> {
+    Pixmap::new(width, height).with_context(|| {
+        ErrorKind::InvalidProject.error(format!("can't draw a {width}x{height} frame"))
+    })
+}
+
+fn graph_to_screen(pos: Vec2, width: u32, height: u32) -> (f32, f32) {
+    (width as f32 / 2.0 + pos.x, height as f32 / 2.0 - pos.y)
+}
+
+pub(crate) fn sk_color(color: Color, opacity: f32) -> tiny_skia::Color {
+    let alpha = (color.a as f32 * opacity.clamp(0.0, 1.0))
+        .round()
+        .clamp(0.0, 255.0) as u8;
+    tiny_skia::Color::from_rgba8(color.r, color.g, color.b, alpha)
+}
+
+// `color` at `alpha` as premultiplied RGBA bytes.
+pub(crate) fn premultiplied(color: Color, alpha: u8) -> [u8; 4] {
+    let scale = |channel: u8| mul(channel as u32, alpha as u32) as u8;
+    [scale(color.r), scale(color.g), scale(color.b), alpha]
+}
+
+fn solid(color: Color, opacity: f32) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(sk_color(color, opacity));
+    paint.anti_alias = true;
+    paint
+}
+
+fn image_paint(opacity: f32) -> PixmapPaint {
+    PixmapPaint {
+        opacity: opacity.clamp(0.0, 1.0),
+        quality: FilterQuality::Bilinear,
+        ..PixmapPaint::default()
+    }
+}
+
+// Top-down straight RGBA of a pixmap, which holds premultiplied colour.
+fn straight_rgba(pixmap: &Pixmap) -> Vec<u8> {
+    pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect()
+}
+
+// The reverse of `straight_rgba`.
+fn load_straight(pixmap: &mut Pixmap, rgba: &[u8]) {
+    for (pixel, src) in pixmap.pixels_mut().iter_mut().zip(rgba.chunks_exact(4)) {
+        *pixel = ColorU8::from_rgba(src[0], src[1], src[2], src[3]).premultiply();
+    }
+}
+
+// Draws premultiplied `src` over `dst` with the same equations the raylib
+// backend sets up for its targets (see `begin_target_blend`).
+fn composite(dst: &mut Pixmap, src: &Pixmap, blend: BlendMode, opacity: f32) {
+    let opacity = (opacity.clamp(0.0, 1.0) * 255.0).round() as u32;
+    for (out, pixel) in dst.data_mut().chunks_exact_mut(4).zip(src.data().chunks_exact(4)) {
+        let src: [u32; 4] = std::array::from_fn(|i| mul(pixel[i] as u32, opacity));
+        let src_alpha = src[3];
+        for (channel, &s) in out.iter_mut().zip(&src) {
+            let d = *channel as u32;
+            let value = match blend {
+                BlendMode::Normal => s + mul(d, 255 - src_alpha),
+                BlendMode::Add => s + d,
+                BlendMode::Multiply => mul(s, d) + mul(d, 255 - src_alpha),
+                // GL_FUNC_SUBTRACT: source minus destination.
+                BlendMode::Subtract => s.saturating_sub(d),
+                BlendMode::Screen => s + mul(d, 255 - s),
+            };
+            *channel = value.min(255) as u8;
+        }
+    }
+}
+
+// With `set_linear_light`, frames and targets hold premultiplied linear
+// light, sRGB-encoded as an `Rgba8UnormSrgb` texture stores it. Everything
+// is drawn with tiny-skia on plain premultiplied sRGB first and converted.
+fn linearize(pixmap: &mut Pixmap) {
+    let data = pixmap.data_mut();
+    unpremultiply(data);
+    premultiply_linear(data);
+}
+
+// The reverse of `linearize`.
+fn delinearize(pixmap: &mut Pixmap) {
+    let data = pixmap.data_mut();
+    unpremultiply_linear(data);
+    premultiply(data);
+}
+
+// `composite` in linear light, on linearized pixmaps.
+fn composite_linear(dst: &mut Pixmap, src: &Pixmap, blend: BlendMode, opacity: f32) {
+    let decode = decode_table();
+    let opacity = opacity.clamp(0.0, 1.0);
+    // Alpha is linear already.
+    let unit = |value: u8, channel: usize| match channel {
+        3 => value as f32 / 255.0,
+        _ => decode[value as usize],
+    };
+    for (out, pixel) in dst.data_mut().chunks_exact_mut(4).zip(src.data().chunks_exact(4)) {
+        // Leaves the destination as it is, and most of a clip drawn alone is
+        // transparent.
+        if pixel == [0; 4] && blend != BlendMode::Subtract {
+            continue;
+        }
+        let src_alpha = unit(pixel[3], 3) * opacity;
+        for (channel, value) in out.iter_mut().enumerate() {
+            let s = unit(pixel[channel], channel) * opacity;
+            let d = unit(*value, channel);
+            let blended = match blend {
+                BlendMode::Normal => s + d * (1.0 - src_alpha),
+                BlendMode::Add => s + d,
+                BlendMode::Multiply => s * d + d * (1.0 - src_alpha),
+                BlendMode::Subtract => (s - d).max(0.0),
+                BlendMode::Screen => s + d * (1.0 - s),
+            };
+            *value = match channel {
+                3 => (blended.clamp(0.0, 1.0) * 255.0).round() as u8,
+                _ => encode(blended),
+            };
+        }
+    }
+}
+
+// Scales `layer` by the matte source's alpha or luma (inverted or not).
+fn apply_matte(layer: &mut Pixmap, source: &Pixmap, matte: TrackMatte) {
+    for (out, pixel) in layer.data_mut().chunks_exact_mut(4).zip(source.data().chunks_exact(4)) {
+        // Premultiplied, so the luma is already scaled by alpha.
+        let value = match matte {
+            TrackMatte::Alpha | TrackMatte::AlphaInverted => pixel[3] as u32,
+            TrackMatte::Luma | TrackMatte::LumaInverted => (0.2126 * pixel[0] as f32
+                + 0.7152 * pixel[1] as f32
+                + 0.0722 * pixel[2] as f32)
+                .round() as u32,
+        };
+        let factor = match matte {
+            TrackMatte::Alpha | TrackMatte::Luma => value,
+            TrackMatte::AlphaInverted | TrackMatte::LumaInverted => 255 - value.min(255),
+        };
+        for channel in out.iter_mut() {
+            *channel = mul(*channel as u32, factor) as u8;
+        }
+    }
+}
+
+fn mul(a: u32, b: u32) -> u32 {
+    (a * b + 127) / 255
+}
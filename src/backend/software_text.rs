@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use fontdue::{Font, FontSettings};
+use tiny_skia::{FillRule, FilterQuality, Mask, Paint, PathBuilder, Pixmap, PixmapPaint, Rect};
+
+use crate::backend::asset_bytes::AssetBytes;
+use crate::backend::software::{premultiplied, sk_color};
+use crate::backend::text_layout::{layout_text, stroke_offsets, LineLayout};
+use crate::error::ErrorKind;
+use crate::scene::{Color, FontFamily, FontSource, StyleFlags, TextObject, Transform};
+
+// Text for `SoftwareRender`, rasterized with fontdue and laid out like the
+// raylib backend's. Only font files work; raylib's built-in font isn't
+// available here.
+#[derive(Default)]
+pub struct SoftwareFonts {
+    fonts: HashMap<PathBuf, Font>,
+}
+
+impl SoftwareFonts {
+    pub fn preload(&mut self, family: &FontFamily, assets: &AssetBytes) -> Result<()> {
+        let styled = [&family.bold, &family.italic, &family.bold_italic];
+        let sources = [&family.regular]
+            .into_iter()
+            .chain(styled.into_iter().flatten())
+            .chain(&family.fallbacks);
+        for source in sources {
+            let FontSource::Path(path) = source else {
+                continue;
+            };
+            if self.fonts.contains_key(path) {
+                continue;
+            }
+            let bytes = assets.read(path).with_context(|| {
+                ErrorKind::MissingAsset.error(format!("font not found: {}", path.display()))
+            })?;
+            let font = Font::from_bytes(bytes, FontSettings::default()).map_err(|err| {
+                ErrorKind::InvalidProject
+                    .error(format!("failed to load font {}: {err}", path.display()))
+            })?;
+            self.fonts.insert(path.clone(), font);
+        }
+        Ok(())
+    }
+
+    // Drops the font loaded from `path`, so its bytes are read again.
+    pub fn forget(&mut self, path: &Path) {
+        self.fonts.remove(path);
+    }
+
+    // Size of the block as `draw` lays it out, without the background plate.
+    pub fn block_size(&self, text: &TextObject, transform: &Transform) -> Result<(f32, f32)> {
+        let font_size = text.font_size * transform.scale.y.max(0.0);
+        let lines = self.layout(text, font_size)?;
+        self.lines_size(text, &lines, font_size)
+    }
+
+    // Draws the block with its top-left corner at `origin` (pixels), rotated
+    // about that corner. Plated or outlined blocks at partial opacity are
+    // drawn opaque on a layer of their own and put down once, so the plate
+    // and outline don't show through the fill.
+    pub fn draw(
+        &self,
+        pixmap: &mut Pixmap,
+        text: &TextObject,
+        transform: &Transform,
+        origin: (f32, f32),
+        mask: Option<&Mask>,
+    ) -> Result<()> {
+        let opacity = transform.opacity.clamp(0.0, 1.0);
+        let layered = text.background.is_some() || text.stroke.is_some();
+        if !layered || opacity >= 1.0 {
+            return self.draw_block(pixmap, text, transform, origin, opacity, mask);
+        }
+        let mut layer = Pixmap::new(pixmap.width(), pixmap.height())
+            .context("failed to allocate a text layer")?;
+        self.draw_block(&mut layer, text, transform, origin, 1.0, None)?;
+        let paint = PixmapPaint {
+            opacity,
+            ..PixmapPaint::default()
+        };
+        let identity = tiny_skia::Transform::identity();
+        pixmap.draw_pixmap(0, 0, layer.as_ref(), &paint, identity, mask);
+        Ok(())
+    }
+
+    fn draw_block(
+        &self,
+        pixmap: &mut Pixmap,
+        text: &TextObject,
+        transform: &Transform,
+        origin: (f32, f32),
+        opacity: f32,
+        mask: Option<&Mask>,
+    ) -> Result<()> {
+        let scale = transform.scale.y.max(0.0);
+        let font_size = text.font_size * scale;
+        let lines = self.layout(text, font_size)?;
+        let place = |dx: f32, dy: f32| {
+            tiny_skia::Transform::from_translate(origin.0 + dx, origin.1 + dy)
+                .pre_rotate(transform.rotation)
+        };
+
+        if let Some(background) = &text.background {
+            let (block_w, block_h) = self.lines_size(text, &lines, font_size)?;
+            let padding = background.padding * scale;
+            let (w, h) = (block_w + padding * 2.0, block_h + padding * 2.0);
+            let radius = (background.corner_radius * scale).clamp(0.0, w.min(h) / 2.0);
+            if let Some(path) = rounded_rect(-padding, -padding, w, h, radius) {
+                let mut paint = Paint::default();
+                paint.set_color(sk_color(background.color, opacity));
+                pixmap.fill_path(&path, &paint, FillRule::Winding, place(0.0, 0.0), mask);
+            }
+        }
+
+        // The stroke is stamped once per offset, so its alpha would add up
+        // where stamps overlap: it's drawn opaque on a layer and put down once
+        // at its colour's alpha. The whole outline goes down first so
+        // neighbouring runs never paint it over already drawn fill.
+        if let Some(stroke) = &text.stroke {
+            let width = stroke.width * scale;
+            if width > 0.0 {
+                let mut layer = Pixmap::new(pixmap.width(), pixmap.height())
+                    .context("failed to allocate a text stroke layer")?;
+                let opaque = Color {
+                    a: 255,
+                    ..stroke.color
+                };
+                for (dx, dy) in stroke_offsets(width) {
+                    let at = place(dx, dy);
+                    self.draw_lines(&mut layer, text, &lines, font_size, at, (opaque, 1.0), None)?;
+                }
+                let paint = PixmapPaint {
+                    opacity: stroke.color.a as f32 / 255.0 * opacity,
+                    ..PixmapPaint::default()
+                };
+                let identity = tiny_skia::Transform::identity();
+                pixmap.draw_pixmap(0, 0, layer.as_ref(), &paint, identity, mask);
+            }
+        }
+        let fill = (text.color, opacity);
+        self.draw_lines(pixmap, text, &lines, font_size, place(0.0, 0.0), fill, mask)
+    }
+
+    fn layout(&self, text: &TextObject, font_size: f32) -> Result<Vec<LineLayout>> {
+        layout_text(text, |style, token| {
+            self.measure(&text.font, style, token, font_size, text.spacing)
+        })
+    }
+
+    fn lines_size(
+        &self,
+        text: &TextObject,
+        lines: &[LineLayout],
+        font_size: f32,
+    ) -> Result<(f32, f32)> {
+        let mut width: f32 = 0.0;
+        for line in lines {
+            let mut line_width = 0.0;
+            for run in &line.runs {
+                line_width +=
+                    self.measure(&text.font, run.style, &run.text, font_size, text.spacing)?;
+            }
+            width = width.max(line_width);
+        }
+        let line_height = font_size + text.line_spacing;
+        let height = (lines.len() as f32 * line_height - text.line_spacing).max(font_size);
+        Ok((width, height))
+    }
+
+    // Pen advances like raylib's `MeasureTextEx`: `spacing` between glyphs,
+    // none after the last.
+    fn measure(
+        &self,
+        family: &FontFamily,
+        style: StyleFlags,
+        text: &str,
+        font_size: f32,
+        spacing: f32,
+    ) -> Result<f32> {
+        let mut width = 0.0;
+        for (font, segment) in self.segments(family, style, text)? {
+            let count = segment.chars().count();
+            let advance: f32 = segment
+                .chars()
+                .map(|ch| font.metrics(ch, font_size).advance_width)
+                .sum();
+            width += advance + count.saturating_sub(1) as f32 * spacing;
+        }
+        Ok(width)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_lines(
+        &self,
+        pixmap: &mut Pixmap,
+        text: &TextObject,
+        lines: &[LineLayout],
+        font_size: f32,
+        block: tiny_skia::Transform,
+        (color, opacity): (Color, f32),
+        mask: Option<&Mask>,
+    ) -> Result<()> {
+        // Unrotated glyphs land on whole pixels, unfiltered.
+        let quality = if block.is_translate() {
+            FilterQuality::Nearest
+        } else {
+            FilterQuality::Bilinear
+        };
+        let glyph_paint = PixmapPaint {
+            quality,
+            ..PixmapPaint::default()
+        };
+        let mut line_paint = Paint::default();
+        line_paint.set_color(sk_color(color, opacity));
+
+        let line_height = font_size + text.line_spacing;
+        let mut y = 0.0;
+        for line in lines {
+            let mut x = 0.0;
+            for run in &line.runs {
+                let run_x = x;
+                for (font, segment) in self.segments(&text.font, run.style, &run.text)? {
+                    let ascent = font
+                        .horizontal_line_metrics(font_size)
+                        .map_or(font_size * 0.8, |metrics| metrics.ascent);
+                    for ch in segment.chars() {
+                        let (metrics, coverage) = font.rasterize(ch, font_size);
+                        if let Some(glyph) =
+                            glyph_pixmap(metrics.width, metrics.height, &coverage, color, opacity)
+                        {
+                            let left = x + metrics.xmin as f32;
+                            let top = y + ascent - (metrics.height as i32 + metrics.ymin) as f32;
+                            let at = block.pre_translate(left, top);
+                            pixmap.draw_pixmap(0, 0, glyph.as_ref(), &glyph_paint, at, mask);
+                        }
+                        x += metrics.advance_width + text.spacing;
+                    }
+                    // The measured width has no spacing after a segment.
+                    x -= text.spacing;
+                }
+
+                let underline = Rect::from_xywh(run_x, y + font_size * 0.9 - 1.0, x - run_x, 2.0);
+                if run.style.underline
+                    && let Some(rect) = underline
+                {
+                    pixmap.fill_rect(rect, &line_paint, block, mask);
+                }
+            }
+            y += line_height;
+        }
+        Ok(())
+    }
+
+    // Splits `text` into consecutive segments, each drawn with the first font
+    // in the family's chain that has glyphs for it, as the raylib backend
+    // does.
+    fn segments<'a>(
+        &self,
+        family: &FontFamily,
+        style: StyleFlags,
+        text: &'a str,
+    ) -> Result<Vec<(&Font, &'a str)>> {
+        let chain = family
+            .chain(style)
+            .into_iter()
+            .map(|source| self.font(source))
+            .collect::<Result<Vec<_>>>()?;
+        let mut segments: Vec<(usize, usize, usize)> = Vec::new();
+        for (start, ch) in text.char_indices() {
+            let end = start + ch.len_utf8();
+            let source = match segments.last() {
+                Some(last) if ch.is_whitespace() => last.0,
+                _ => chain
+                    .iter()
+                    .position(|font| font.lookup_glyph_index(ch) != 0)
+                    .unwrap_or(0),
+            };
+            match segments.last_mut() {
+                Some(last) if last.0 == source => last.2 = end,
+                _ => segments.push((source, start, end)),
+            }
+        }
+        Ok(segments
+            .into_iter()
+            .map(|(source, start, end)| (chain[source], &text[start..end]))
+            .collect())
+    }
+
+    fn font(&self, source: &FontSource) -> Result<&Font> {
+        match source {
+            FontSource::Default => bail!(ErrorKind::InvalidProject.error(
+                "the software renderer has no built-in font; give text a font file"
+            )),
+            FontSource::Path(path) => self
+                .fonts
+                .get(path)
+                .with_context(|| format!("font was not preloaded: {}", path.display())),
+        }
+    }
+}
+
+// A glyph's coverage as a premultiplied `color` pixmap.
+fn glyph_pixmap(
+    width: usize,
+    height: usize,
+    coverage: &[u8],
+    color: Color,
+    opacity: f32,
+) -> Option<Pixmap> {
+    let mut glyph = Pixmap::new(width as u32, height as u32)?;
+    for (pixel, &cover) in glyph.data_mut().chunks_exact_mut(4).zip(coverage) {
+        let alpha = (color.a as f32 * opacity.clamp(0.0, 1.0) * cover as f32 / 255.0).round();
+        pixel.copy_from_slice(&premultiplied(color, alpha as u8));
+    }
+    Some(glyph)
+}
+
+fn rounded_rect(x: f32, y: f32, w: f32, h: f32, r: f32) -> Option<tiny_skia::Path> {
+    if r <= 0.0 {
+        return Some(PathBuilder::from_rect(Rect::from_xywh(x, y, w, h)?));
+    }
+    let (right, bottom) = (x + w, y + h);
+    let mut path = PathBuilder::new();
+    path.move_to(x + r, y);
+    path.line_to(right - r, y);
+    path.quad_to(right, y, right, y + r);
+    path.line_to(right, bottom - r);
+    path.quad_to(right, bottom, right - r, bottom);
+    path.line_to(x + r, bottom);
+    path.quad_to(x, bottom, x, bottom - r);
+    path.line_to(x, y + r);
+    path.quad_to(x, y, x + r, y);
+    path.close();
+    path.finish()
+}
@@ -0,0 +1,128 @@
+use anyhow::Result;
+
+use crate::scene::{StyleFlags, TextObject, TextRun};
+
+pub struct LineLayout {
+    pub runs: Vec<TextRun>,
+}
+
+// Offsets a block is redrawn at, in the stroke colour, to outline it.
+pub fn stroke_offsets(width: f32) -> Vec<(f32, f32)> {
+    let steps = if width > 2.0 { 16 } else { 8 };
+    (0..steps)
+        .map(|i| {
+            let angle = i as f32 / steps as f32 * std::f32::consts::TAU;
+            (angle.cos() * width, angle.sin() * width)
+        })
+        .collect()
+}
+
+// Breaks `text` into lines no wider than its `max_width`, measuring tokens
+// of each style with `measure`.
+pub fn layout_text(
+    text: &TextObject,
+    mut measure: impl FnMut(StyleFlags, &str) -> Result<f32>,
+) -> Result<Vec<LineLayout>> {
+    let max_width = if text.max_width <= 0.0 {
+        f32::INFINITY
+    } else {
+        text.max_width
+    };
+    let mut lines = Vec::new();
+    let mut current = LineLayout { runs: Vec::new() };
+    let mut line_width = 0.0;
+
+    for run in &text.text.runs {
+        let parts = split_newlines(&run.text);
+        for (idx, part) in parts.iter().enumerate() {
+            if idx > 0 {
+                lines.push(current);
+                current = LineLayout { runs: Vec::new() };
+                line_width = 0.0;
+            }
+
+            for token in split_tokens(part) {
+                let token_width = measure(run.style, &token)?;
+
+                if line_width + token_width <= max_width || line_width == 0.0 {
+                    push_run(&mut current.runs, run.style, &token);
+                    line_width += token_width;
+                    continue;
+                }
+
+                if token_width > max_width && line_width == 0.0 {
+                    for ch in token.chars() {
+                        let s = ch.to_string();
+                        let w = measure(run.style, &s)?;
+                        if line_width + w > max_width && line_width > 0.0 {
+                            lines.push(current);
+                            current = LineLayout { runs: Vec::new() };
+                            line_width = 0.0;
+                        }
+                        push_run(&mut current.runs, run.style, &s);
+                        line_width += w;
+                    }
+                    continue;
+                }
+
+                lines.push(current);
+                current = LineLayout { runs: Vec::new() };
+                line_width = 0.0;
+                if token.trim().is_empty() {
+                    continue;
+                }
+                push_run(&mut current.runs, run.style, &token);
+                line_width += token_width;
+            }
+        }
+    }
+
+    lines.push(current);
+    Ok(lines)
+}
+
+fn push_run(runs: &mut Vec<TextRun>, style: StyleFlags, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    if let Some(last) = runs.last_mut()
+        && last.style == style
+    {
+        last.text.push_str(text);
+        return;
+    }
+    runs.push(TextRun {
+        text: text.to_string(),
+        style,
+    });
+}
+
+fn split_newlines(text: &str) -> Vec<String> {
+    text.split('\n').map(|s| s.to_string()).collect()
+}
+
+fn split_tokens(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut buf = String::new();
+    let mut last_space = None;
+    for ch in text.chars() {
+        let is_space = ch.is_whitespace();
+        match last_space {
+            None => {
+                last_space = Some(is_space);
+                buf.push(ch);
+            }
+            Some(was_space) if was_space == is_space => buf.push(ch),
+            Some(_) => {
+                tokens.push(buf.clone());
+                buf.clear();
+                buf.push(ch);
+                last_space = Some(is_space);
+            }
+        }
+    }
+    if !buf.is_empty() {
+        tokens.push(buf);
+    }
+    tokens
+}
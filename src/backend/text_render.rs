@@ -1,11 +1,35 @@
 use anyhow::Result;
 use raylib::prelude::*;
 
-use crate::backend::resources::{measure_text, FontRef, ResourceCache};
-use crate::scene::{StyleFlags, TextObject, TextRun, Transform, Vec2};
+use crate::backend::alpha::{begin_premultiplied, begin_straight, premultiplied_tint};
+use crate::backend::raylib_render::ScaledDraw;
+use crate::backend::render_backend::clip_placement;
+use crate::backend::resources::{measure_text, ClipKey, FontRef, OffscreenKey, ResourceCache};
+use crate::backend::text_layout::{layout_text, stroke_offsets, LineLayout};
+use crate::scene::{Object, StyleFlags, TextObject, TextStroke, Transform, Vec2};
+use crate::timeline::SampledScene;
 
-pub struct LineLayout {
-    pub runs: Vec<TextRun>,
+// Laid out block at one scale, shared by the direct draw and the targets.
+struct Block {
+    lines: Vec<LineLayout>,
+    scale: f32,
+    font_size: f32,
+    line_height: f32,
+}
+
+impl Block {
+    fn new(cache: &ResourceCache, text: &TextObject, scale: f32) -> Result<Self> {
+        let font_size = text.font_size * scale;
+        let lines = layout_text(text, |style, token| {
+            measure_token(cache, text, style, token, font_size, text.spacing)
+        })?;
+        Ok(Self {
+            lines,
+            scale,
+            font_size,
+            line_height: font_size + text.line_spacing,
+        })
+    }
 }
 
 pub fn draw_text_block(
@@ -15,162 +39,308 @@ pub fn draw_text_block(
     height: u32,
     text: &TextObject,
     transform: &Transform,
+    key: ClipKey,
 ) -> Result<()> {
     let origin = graph_to_screen(transform.pos, width, height);
-    let font_size = text.font_size * transform.scale.y.max(0.0);
-    let spacing = text.spacing;
-    let line_height = font_size + text.line_spacing;
+    let scale = transform.scale.y.max(0.0);
+    if needs_target(text) {
+        return draw_block_target(d, cache, text, transform, scale, origin, key);
+    }
+
+    let block = Block::new(cache, text, scale)?;
+    let tint = to_raylib_color(text.color, transform.opacity);
+    draw_lines(d, cache, text, &block, origin, transform.rotation, tint)
+}
 
-    let lines = layout_text(text, cache, font_size, spacing)?;
+// Outlined or plated text goes through targets drawn before the frame
+// begins, like compositions. The stroke is stamped once per offset, so
+// stamped straight into the frame its alpha would add up: it's drawn opaque
+// into a target of its own and put down once at its colour's alpha. Plate,
+// stroke and fill then go into a second target at full opacity, which
+// `draw_text_block` turns with the text and draws once at the clip's
+// opacity. Must run before anything that draws the text; `width` x `height`
+// is the size `scene` is drawn at.
+pub fn render_text_targets(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    cache: &mut ResourceCache,
+    scene: &SampledScene,
+    width: u32,
+    height: u32,
+) -> Result<()> {
+    for (layer_index, layer) in scene.layers.iter().enumerate() {
+        for (clip_index, clip) in layer.clips.iter().enumerate() {
+            if let (Object::Composition(composition), Some(nested)) = (&clip.object, &clip.nested) {
+                render_text_targets(
+                    rl,
+                    thread,
+                    cache,
+                    nested,
+                    composition.width,
+                    composition.height,
+                )?;
+            }
+            let Object::Text(text) = &clip.object else {
+                continue;
+            };
+            if !needs_target(text) {
+                continue;
+            }
+            // Same scale `draw_clip` places the text at.
+            let (transform, _) = clip_placement(&layer.transform, clip, width, height);
+            let scale = transform.scale.y.max(0.0);
+            let key = ClipKey::new(scene, layer_index, clip_index);
+            render_block_targets(rl, thread, cache, text, scale, key)?;
+        }
+    }
+    Ok(())
+}
+
+fn render_block_targets(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    cache: &mut ResourceCache,
+    text: &TextObject,
+    scale: f32,
+    key: ClipKey,
+) -> Result<()> {
+    let block = Block::new(cache, text, scale)?;
+    let (block_w, block_h) = block_size(text, cache, &block)?;
+    let margin = target_margin(text, scale);
+    let width = (block_w + margin * 2.0).ceil() as u32;
+    let height = (block_h + margin * 2.0).ceil() as u32;
+
+    if let Some(stroke) = visible_stroke(text) {
+        let (slot, mut target) = cache.take_offscreen_target(
+            rl,
+            thread,
+            OffscreenKey::TextStroke(key),
+            width,
+            height,
+        )?;
+        let result = draw_stroke_target(rl, thread, cache, &mut target, text, &block, stroke);
+        cache.return_offscreen_target(slot, target);
+        result?;
+    }
+
+    let (slot, mut target) =
+        cache.take_offscreen_target(rl, thread, OffscreenKey::TextBlock(key), width, height)?;
+    let result = draw_block_into(rl, thread, cache, &mut target, text, &block, margin, key);
+    cache.return_offscreen_target(slot, target);
+    result
+}
 
+fn draw_stroke_target(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    cache: &ResourceCache,
+    target: &mut RenderTexture2D,
+    text: &TextObject,
+    block: &Block,
+    stroke: &TextStroke,
+) -> Result<()> {
+    let mut d = rl.begin_texture_mode(thread, target.as_mut());
+    let _scaled = ScaledDraw::begin(cache.draw_scale());
+    d.clear_background(Color::BLANK);
+    begin_straight();
+    let margin = target_margin(text, block.scale);
+    let tint = to_raylib_color(
+        crate::scene::Color {
+            a: 255,
+            ..stroke.color
+        },
+        1.0,
+    );
+    for (dx, dy) in stroke_offsets(stroke.width * block.scale) {
+        let origin = Vector2::new(margin + dx, margin + dy);
+        draw_lines(&mut d, cache, text, block, origin, 0.0, tint)?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_block_into(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    cache: &ResourceCache,
+    target: &mut RenderTexture2D,
+    text: &TextObject,
+    block: &Block,
+    margin: f32,
+    key: ClipKey,
+) -> Result<()> {
+    let mut d = rl.begin_texture_mode(thread, target.as_mut());
+    let _scaled = ScaledDraw::begin(cache.draw_scale());
+    d.clear_background(Color::BLANK);
+    begin_straight();
+    let origin = Vector2::new(margin, margin);
+
+    if let Some(background) = &text.background {
+        let (block_w, block_h) = block_size(text, cache, block)?;
+        let padding = background.padding * block.scale;
+        let rec = Rectangle::new(
+            origin.x - padding,
+            origin.y - padding,
+            block_w + padding * 2.0,
+            block_h + padding * 2.0,
+        );
+        let short_side = rec.width.min(rec.height);
+        let roundness = if short_side > 0.0 {
+            (background.corner_radius * block.scale * 2.0 / short_side).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let plate = to_raylib_color(background.color, 1.0);
+        if roundness > 0.0 {
+            d.draw_rectangle_rounded(rec, roundness, 8, plate);
+        } else {
+            d.draw_rectangle_rec(rec, plate);
+        }
+    }
+
+    // The whole outline goes down before any fill so neighbouring runs never
+    // paint their outline over already drawn fill.
+    if let Some(stroke) = visible_stroke(text) {
+        let stroke_target = cache.offscreen_texture(OffscreenKey::TextStroke(key))?;
+        let (tex_w, tex_h) = texture_size(stroke_target);
+        let scale = cache.draw_scale();
+        begin_premultiplied();
+        d.draw_texture_pro(
+            stroke_target.texture(),
+            Rectangle::new(0.0, 0.0, tex_w, -tex_h),
+            Rectangle::new(0.0, 0.0, tex_w / scale, tex_h / scale),
+            Vector2::new(0.0, 0.0),
+            0.0,
+            premultiplied_tint(stroke.color.a as f32 / 255.0),
+        );
+        begin_straight();
+    }
+
+    let tint = to_raylib_color(text.color, 1.0);
+    draw_lines(&mut d, cache, text, block, origin, 0.0, tint)
+}
+
+fn draw_block_target(
+    d: &mut impl RaylibDraw,
+    cache: &ResourceCache,
+    text: &TextObject,
+    transform: &Transform,
+    scale: f32,
+    origin: Vector2,
+    key: ClipKey,
+) -> Result<()> {
+    let target = cache.offscreen_texture(OffscreenKey::TextBlock(key))?;
+    let (tex_w, tex_h) = texture_size(target);
+    let draw_scale = cache.draw_scale();
+    let margin = target_margin(text, scale);
+
+    // Render textures are stored bottom-up; a negative source height flips
+    // them. The block turns about its top-left corner, as unplated text does.
+    begin_premultiplied();
+    d.draw_texture_pro(
+        target.texture(),
+        Rectangle::new(0.0, 0.0, tex_w, -tex_h),
+        Rectangle::new(origin.x, origin.y, tex_w / draw_scale, tex_h / draw_scale),
+        Vector2::new(margin, margin),
+        transform.rotation,
+        premultiplied_tint(transform.opacity),
+    );
+    begin_straight();
+    Ok(())
+}
+
+fn needs_target(text: &TextObject) -> bool {
+    text.background.is_some() || visible_stroke(text).is_some()
+}
+
+fn visible_stroke(text: &TextObject) -> Option<&TextStroke> {
+    text.stroke.as_ref().filter(|stroke| stroke.width > 0.0)
+}
+
+// Room around the laid out block for the plate and the outline, plus a
+// little for glyphs that overhang their advance.
+fn target_margin(text: &TextObject, scale: f32) -> f32 {
+    let padding = text.background.map_or(0.0, |background| background.padding);
+    let stroke = visible_stroke(text).map_or(0.0, |stroke| stroke.width);
+    (padding.max(stroke) * scale + 2.0).ceil()
+}
+
+fn texture_size(target: &RenderTexture2D) -> (f32, f32) {
+    let texture = target.texture();
+    (texture.width as f32, texture.height as f32)
+}
+
+fn draw_lines(
+    d: &mut impl RaylibDraw,
+    cache: &ResourceCache,
+    text: &TextObject,
+    block: &Block,
+    origin: Vector2,
+    rotation: f32,
+    tint: raylib::prelude::Color,
+) -> Result<()> {
+    let (font_size, spacing) = (block.font_size, text.spacing);
     let mut y = origin.y;
-    for line in lines {
+    for line in &block.lines {
         let mut x = origin.x;
-        for run in line.runs {
-            let font = cache.resolve_font(&text.font, run.style)?;
-            let tint = to_raylib_color(text.color, transform.opacity);
-            let position = Vector2::new(x, y);
-            let origin_vec = Vector2::new(0.0, 0.0);
-            draw_text_pro(
-                d,
-                font,
-                &run.text,
-                position,
-                origin_vec,
-                transform.rotation,
-                font_size,
-                spacing,
-                tint,
-            );
-
-            let width = measure_text(font, &run.text, font_size, spacing);
+        for run in &line.runs {
+            let run_x = x;
+            for (font, segment) in cache.resolve_font_runs(&text.font, run.style, &run.text)? {
+                let position = Vector2::new(x, y);
+                let origin_vec = Vector2::new(0.0, 0.0);
+                draw_text_pro(
+                    d,
+                    font,
+                    segment,
+                    position,
+                    origin_vec,
+                    rotation,
+                    font_size,
+                    spacing,
+                    tint,
+                );
+                x += measure_text(font, segment, font_size, spacing);
+            }
+
             if run.style.underline {
                 let underline_y = y + font_size * 0.9;
                 d.draw_line_ex(
+                    Vector2::new(run_x, underline_y),
                     Vector2::new(x, underline_y),
-                    Vector2::new(x + width, underline_y),
                     2.0,
                     tint,
                 );
             }
-
-            x += width;
         }
-        y += line_height;
+        y += block.line_height;
     }
 
     Ok(())
 }
 
-pub fn layout_text(
-    text: &TextObject,
+// Size of the block as `draw_text_block` lays it out, without the background
+// plate.
+pub fn text_block_size(
     cache: &ResourceCache,
-    font_size: f32,
-    spacing: f32,
-) -> Result<Vec<LineLayout>> {
-    let max_width = if text.max_width <= 0.0 {
-        f32::INFINITY
-    } else {
-        text.max_width
-    };
-    let mut lines = Vec::new();
-    let mut current = LineLayout { runs: Vec::new() };
-    let mut line_width = 0.0;
-
-    for run in &text.text.runs {
-        let parts = split_newlines(&run.text);
-        for (idx, part) in parts.iter().enumerate() {
-            if idx > 0 {
-                lines.push(current);
-                current = LineLayout { runs: Vec::new() };
-                line_width = 0.0;
-            }
-
-            for token in split_tokens(part) {
-                let token_width =
-                    measure_token(cache, &text, run.style, &token, font_size, spacing)?;
-
-                if line_width + token_width <= max_width || line_width == 0.0 {
-                    push_run(&mut current.runs, run.style, &token);
-                    line_width += token_width;
-                    continue;
-                }
-
-                if token_width > max_width && line_width == 0.0 {
-                    for ch in token.chars() {
-                        let s = ch.to_string();
-                        let w = measure_token(cache, &text, run.style, &s, font_size, spacing)?;
-                        if line_width + w > max_width && line_width > 0.0 {
-                            lines.push(current);
-                            current = LineLayout { runs: Vec::new() };
-                            line_width = 0.0;
-                        }
-                        push_run(&mut current.runs, run.style, &s);
-                        line_width += w;
-                    }
-                    continue;
-                }
-
-                lines.push(current);
-                current = LineLayout { runs: Vec::new() };
-                line_width = 0.0;
-                if token.trim().is_empty() {
-                    continue;
-                }
-                push_run(&mut current.runs, run.style, &token);
-                line_width += token_width;
-            }
-        }
-    }
-
-    lines.push(current);
-    Ok(lines)
+    text: &TextObject,
+    transform: &Transform,
+) -> Result<(f32, f32)> {
+    let block = Block::new(cache, text, transform.scale.y.max(0.0))?;
+    block_size(text, cache, &block)
 }
 
-fn push_run(runs: &mut Vec<TextRun>, style: StyleFlags, text: &str) {
-    if text.is_empty() {
-        return;
-    }
-    if let Some(last) = runs.last_mut() {
-        if last.style == style {
-            last.text.push_str(text);
-            return;
+fn block_size(text: &TextObject, cache: &ResourceCache, block: &Block) -> Result<(f32, f32)> {
+    let (font_size, spacing) = (block.font_size, text.spacing);
+    let mut width: f32 = 0.0;
+    for line in &block.lines {
+        let mut line_width = 0.0;
+        for run in &line.runs {
+            line_width += measure_token(cache, text, run.style, &run.text, font_size, spacing)?;
         }
+        width = width.max(line_width);
     }
-    runs.push(TextRun {
-        text: text.to_string(),
-        style,
-    });
-}
-
-fn split_newlines(text: &str) -> Vec<String> {
-    text.split('\n').map(|s| s.to_string()).collect()
-}
-
-fn split_tokens(text: &str) -> Vec<String> {
-    let mut tokens = Vec::new();
-    let mut buf = String::new();
-    let mut last_space = None;
-    for ch in text.chars() {
-        let is_space = ch.is_whitespace();
-        match last_space {
-            None => {
-                last_space = Some(is_space);
-                buf.push(ch);
-            }
-            Some(was_space) if was_space == is_space => buf.push(ch),
-            Some(_) => {
-                tokens.push(buf.clone());
-                buf.clear();
-                buf.push(ch);
-                last_space = Some(is_space);
-            }
-        }
-    }
-    if !buf.is_empty() {
-        tokens.push(buf);
-    }
-    tokens
+    let height = (block.lines.len() as f32 * block.line_height - text.line_spacing).max(font_size);
+    Ok((width, height))
 }
 
 fn measure_token(
@@ -181,8 +351,11 @@ fn measure_token(
     font_size: f32,
     spacing: f32,
 ) -> Result<f32> {
-    let font = cache.resolve_font(&text.font, style)?;
-    Ok(measure_text(font, token, font_size, spacing))
+    let mut width = 0.0;
+    for (font, segment) in cache.resolve_font_runs(&text.font, style, token)? {
+        width += measure_text(font, segment, font_size, spacing);
+    }
+    Ok(width)
 }
 
 fn draw_text_pro(
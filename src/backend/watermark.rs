@@ -0,0 +1,100 @@
+use anyhow::{bail, Result};
+
+use crate::error::ErrorKind;
+use crate::timeline::diff::Fnv1a;
+
+const MAGIC: &[u8; 4] = b"S2SW";
+// Magic, u16 length, then the text and a 32-bit checksum.
+const HEADER_LEN: usize = 6;
+const CHECKSUM_LEN: usize = 4;
+
+// Job metadata (job id, version, ...) hidden in the least significant bits
+// of each frame's RGB channels, so a leaked or misattributed render can be
+// traced back to the job that made it. Each channel changes by at most one
+// level. Lossy encoding (like the default H.264 output) destroys the marks,
+// so they only survive frames kept before encoding or lossless output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watermark {
+    packet: Vec<u8>,
+}
+
+impl Watermark {
+    pub fn new(text: impl Into<String>) -> Result<Self> {
+        let text = text.into();
+        let Ok(len) = u16::try_from(text.len()) else {
+            bail!(ErrorKind::InvalidProject.error("watermark text must be under 64 KiB"));
+        };
+        let mut packet = Vec::with_capacity(HEADER_LEN + text.len() + CHECKSUM_LEN);
+        packet.extend_from_slice(MAGIC);
+        packet.extend_from_slice(&len.to_le_bytes());
+        packet.extend_from_slice(text.as_bytes());
+        packet.extend_from_slice(&checksum(text.as_bytes()).to_le_bytes());
+        Ok(Self { packet })
+    }
+
+    // Writes the mark over the whole frame, repeated back to back; reading
+    // it back takes the first copy whose checksum matches.
+    pub fn embed(&self, rgba: &mut [u8]) {
+        let bits = self.packet.len() * 8;
+        let channels = rgba.chunks_exact_mut(4).flat_map(|pixel| &mut pixel[..3]);
+        for (index, channel) in channels.enumerate() {
+            let bit = index % bits;
+            let value = (self.packet[bit / 8] >> (7 - bit % 8)) & 1;
+            *channel = (*channel & !1) | value;
+        }
+    }
+
+    // Reads a mark back from a frame in either row order (as rendered, or
+    // flipped as in the encoded video).
+    pub fn extract(rgba: &[u8], width: u32, height: u32) -> Option<String> {
+        let bits: Vec<u8> = lsb_bits(rgba.iter().copied());
+        if let Some(text) = read_packets(&bits) {
+            return Some(text);
+        }
+        let row = width as usize * 4;
+        let flipped = (0..height as usize)
+            .rev()
+            .flat_map(|y| rgba.get(y * row..(y + 1) * row).unwrap_or_default())
+            .copied();
+        read_packets(&lsb_bits(flipped))
+    }
+}
+
+fn lsb_bits(rgba: impl Iterator<Item = u8>) -> Vec<u8> {
+    rgba.enumerate()
+        .filter(|(index, _)| index % 4 != 3)
+        .map(|(_, channel)| channel & 1)
+        .collect()
+}
+
+fn read_packets(bits: &[u8]) -> Option<String> {
+    let byte_at = |offset: usize| -> Option<u8> {
+        let bits = bits.get(offset * 8..offset * 8 + 8)?;
+        Some(bits.iter().fold(0, |byte, bit| (byte << 1) | bit))
+    };
+    let header: Option<Vec<u8>> = (0..HEADER_LEN).map(byte_at).collect();
+    let header = header?;
+    if &header[..4] != MAGIC {
+        return None;
+    }
+    let len = u16::from_le_bytes([header[4], header[5]]) as usize;
+    let packet_len = HEADER_LEN + len + CHECKSUM_LEN;
+    (0..bits.len() / (packet_len * 8)).find_map(|copy| {
+        let start = copy * packet_len;
+        let packet: Vec<u8> = (start..start + packet_len)
+            .map(byte_at)
+            .collect::<Option<_>>()?;
+        let text = &packet[HEADER_LEN..HEADER_LEN + len];
+        let stored = u32::from_le_bytes(packet[HEADER_LEN + len..].try_into().ok()?);
+        if &packet[..4] != MAGIC || stored != checksum(text) {
+            return None;
+        }
+        String::from_utf8(text.to_vec()).ok()
+    })
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    let mut hasher = Fnv1a::default();
+    hasher.write_bytes(bytes);
+    hasher.0 as u32
+}
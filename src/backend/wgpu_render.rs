@@ -0,0 +1,1191 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+
+use anyhow::{bail, Context, Result};
+use tiny_skia::Pixmap;
+use wgpu::util::DeviceExt;
+
+use crate::backend::asset_bytes::AssetBytes;
+use crate::backend::cancel::CancelToken;
+use crate::backend::clock::{Clock, FrameClock};
+use crate::backend::premultiply::{
+    premultiply, premultiply_linear, srgb_to_linear, unpremultiply, unpremultiply_linear,
+};
+use crate::backend::render_backend::{
+    check_error_assertions, check_frame_time, check_time_range, clip_placement, sample_scene,
+    validate_for_render, RenderBackend,
+};
+use crate::backend::software_text::SoftwareFonts;
+use crate::error::ErrorKind;
+use crate::plugin::{Canvas, FrameMut, PluginRegistry};
+use crate::scene::{Color, ImageObject, Object, PluginEffect, Shape, Transform, Vec2};
+use crate::timeline::{
+    BlendMode, DataSource, SampledClip, SampledLayer, SampledScene, Timeline, TrackMatte,
+};
+
+// Vertices are in clip space, with texture coordinates and a premultiplied
+// tint. Shapes sample a white texel, so one shader draws everything.
+const SHADER: &str = r#"
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+
+@vertex
+fn vs_main(
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+) -> VertexOut {
+    var out: VertexOut;
+    out.position = vec4<f32>(position, 0.0, 1.0);
+    out.uv = uv;
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, in.uv) * in.color;
+}
+
+// Targets are premultiplied, so the luma is already scaled by alpha.
+@fragment
+fn fs_luma(in: VertexOut) -> @location(0) vec4<f32> {
+    let texel = textureSample(source, source_sampler, in.uv) * in.color;
+    let luma = dot(texel.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+    return vec4<f32>(1.0, 1.0, 1.0, luma);
+}
+"#;
+
+// Not sRGB: colours blend in the same (gamma) space as raylib's.
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+// With `set_linear_light`: the GPU decodes texels to linear light when
+// sampling and blending, and encodes the result when storing it.
+const LINEAR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const VERTEX_FLOATS: usize = 8;
+// `DrawCircleV` always uses 36 segments; the same here keeps circles from
+// looking different on raylib.
+const CIRCLE_SEGMENTS: u32 = 36;
+
+// How a draw lands on its target, which is always premultiplied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Blend {
+    Over,
+    Add,
+    Multiply,
+    Subtract,
+    Screen,
+    // Scales the target by the source's alpha (or one minus it).
+    Matte,
+    MatteInverted,
+}
+
+impl From<BlendMode> for Blend {
+    fn from(blend: BlendMode) -> Self {
+        match blend {
+            BlendMode::Normal => Blend::Over,
+            BlendMode::Add => Blend::Add,
+            BlendMode::Multiply => Blend::Multiply,
+            BlendMode::Subtract => Blend::Subtract,
+            BlendMode::Screen => Blend::Screen,
+        }
+    }
+}
+
+impl Blend {
+    // The factors the raylib backend sets up for the same modes (see
+    // `begin_target_blend`), applied to colour and alpha alike.
+    fn state(self) -> wgpu::BlendState {
+        use wgpu::{BlendFactor as F, BlendOperation as Op};
+        let (src, dst, operation) = match self {
+            Blend::Over => (F::One, F::OneMinusSrcAlpha, Op::Add),
+            Blend::Add => (F::One, F::One, Op::Add),
+            Blend::Multiply => (F::Dst, F::OneMinusSrcAlpha, Op::Add),
+            Blend::Subtract => (F::One, F::One, Op::Subtract),
+            Blend::Screen => (F::One, F::OneMinusSrc, Op::Add),
+            Blend::Matte => (F::Zero, F::SrcAlpha, Op::Add),
+            Blend::MatteInverted => (F::Zero, F::OneMinusSrcAlpha, Op::Add),
+        };
+        let component = wgpu::BlendComponent {
+            src_factor: src,
+            dst_factor: dst,
+            operation,
+        };
+        wgpu::BlendState {
+            color: component,
+            alpha: component,
+        }
+    }
+}
+
+const BLENDS: [Blend; 7] = [
+    Blend::Over,
+    Blend::Add,
+    Blend::Multiply,
+    Blend::Subtract,
+    Blend::Screen,
+    Blend::Matte,
+    Blend::MatteInverted,
+];
+
+// A pipeline is the blend plus whether the fragment writes luma as alpha.
+type PipelineKey = (Blend, bool);
+
+// A vertex as screen pixels and texture coordinates.
+type Point = ((f32, f32), (f32, f32));
+
+struct GpuTexture {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    width: u32,
+    height: u32,
+}
+
+// Draws timelines through wgpu (Vulkan, Metal, DX12 or GL, whichever the
+// machine has), offscreen. Frames come out as `RaylibRender`'s do
+// (bottom-up straight RGBA), drawn with the same blend equations and
+// unantialiased geometry. Text and images are rasterized and decoded on the
+// CPU as `SoftwareRender` does, so the same limits apply: PNG images, font
+// files for text, and no clip shaders or post effects.
+pub struct WgpuRender {
+    width: u32,
+    height: u32,
+    bg: Color,
+    adapter: wgpu::AdapterInfo,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+    white: GpuTexture,
+    plugins: Option<Arc<PluginRegistry>>,
+    data: Option<Arc<dyn DataSource>>,
+    cancel: Option<CancelToken>,
+    // Blending and opacity in linear light (see `set_linear_light`).
+    linear: bool,
+    assets: AssetBytes,
+    images: HashMap<PathBuf, GpuTexture>,
+    fonts: SoftwareFonts,
+}
+
+impl WgpuRender {
+    pub fn new(width: u32, height: u32, bg: Color) -> Result<Self> {
+        if width == 0 || height == 0 {
+            bail!(ErrorKind::InvalidProject.error("frame width and height must be > 0"));
+        }
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .context(ErrorKind::Gpu.error("no wgpu adapter found"))?;
+        let descriptor = wgpu::DeviceDescriptor {
+            label: Some("script_2_script"),
+            required_limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+            ..Default::default()
+        };
+        let (device, queue) = pollster::block_on(adapter.request_device(&descriptor, None))
+            .map_err(|err| ErrorKind::Gpu.error(format!("failed to open wgpu device: {err}")))?;
+
+        let max = device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            bail!(ErrorKind::Gpu.error(format!(
+                "{width}x{height} frames are larger than this GPU's {max}px texture limit"
+            )));
+        }
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("source"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("bilinear"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let pipelines = create_pipelines(&device, &layout, FORMAT);
+        let white = upload_texture(
+            &device,
+            &queue,
+            &layout,
+            &sampler,
+            FORMAT,
+            (1, 1),
+            &[255; 4],
+        );
+
+        Ok(Self {
+            width,
+            height,
+            bg,
+            adapter: adapter.get_info(),
+            white,
+            device,
+            queue,
+            layout,
+            sampler,
+            pipelines,
+            plugins: None,
+            data: None,
+            cancel: None,
+            linear: false,
+            assets: AssetBytes::default(),
+            images: HashMap::new(),
+            fonts: SoftwareFonts::default(),
+        })
+    }
+
+    // Registry used to draw `Object::Plugin` clips and apply timeline effects.
+    pub fn set_plugins(&mut self, plugins: Arc<PluginRegistry>) {
+        self.plugins = Some(plugins);
+    }
+
+    // Supplies per-frame variables for clip bindings.
+    pub fn set_data_source(&mut self, data: Arc<dyn DataSource>) {
+        self.data = Some(data);
+    }
+
+    // Checked before each frame; cancelling ends the render early, without
+    // an error.
+    pub fn set_cancel_token(&mut self, cancel: CancelToken) {
+        self.cancel = Some(cancel);
+    }
+
+    // Serves an image or font file from `bytes` wherever a project refers to
+    // `path`, e.g. on wasm32, where there are no files to read.
+    pub fn add_asset(&mut self, path: impl Into<PathBuf>, bytes: impl Into<Arc<[u8]>>) {
+        let path = path.into();
+        self.images.remove(&path);
+        self.fonts.forget(&path);
+        self.assets.insert(path, bytes);
+    }
+
+    // Blends, fades and filters in linear light instead of on sRGB values,
+    // so antialiased edges don't get dark fringes and crossfades don't dip
+    // in brightness. Colours and images are decoded from sRGB on the way
+    // in, and frames are encoded back to straight sRGB on the way out, so
+    // only how things mix changes. Off by default, which matches raylib.
+    pub fn set_linear_light(&mut self, linear: bool) {
+        if self.linear == linear {
+            return;
+        }
+        self.linear = linear;
+        self.pipelines = create_pipelines(&self.device, &self.layout, self.format());
+        // Uploaded in the other format.
+        self.images.clear();
+    }
+
+    pub fn linear_light(&self) -> bool {
+        self.linear
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // The adapter frames are drawn on, e.g. for logging which GPU and API
+    // (`backend`) a render used.
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter
+    }
+
+    pub fn render_timeline_rgba(
+        &mut self,
+        timeline: &Timeline,
+        start_time: f32,
+        end_time: f32,
+        mut on_frame: impl FnMut(f32, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        check_time_range(timeline, start_time, end_time)?;
+        validate_for_render(timeline, self.plugins.as_deref(), &self.assets)?;
+        let mut clock = FrameClock::new(timeline.fps, start_time, end_time);
+        while let Some(t) = clock.tick() {
+            if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                break;
+            }
+            let scene = sample_scene(timeline, self.data.as_ref(), t)?;
+            let rgba = self.render_scene_to_rgba(&scene)?;
+            check_error_assertions(timeline, &scene, (self.width, self.height), |object, at| {
+                self.measure_object(object, at)
+            })?;
+            on_frame(t, &rgba)?;
+        }
+        Ok(())
+    }
+
+    // The single frame at `time`, bottom-up straight RGBA.
+    pub fn render_frame_rgba(&mut self, timeline: &Timeline, time: f32) -> Result<Vec<u8>> {
+        check_frame_time(timeline, time)?;
+        validate_for_render(timeline, self.plugins.as_deref(), &self.assets)?;
+        let scene = sample_scene(timeline, self.data.as_ref(), time)?;
+        self.render_scene_to_rgba(&scene)
+    }
+
+    pub fn render_scene_to_rgba(&mut self, scene: &SampledScene) -> Result<Vec<u8>> {
+        if !scene.post.is_empty() {
+            bail!(ErrorKind::InvalidProject
+                .error("post effects aren't supported by the wgpu renderer"));
+        }
+        self.preload(scene)?;
+        let frame = self.target(self.width, self.height)?;
+        let mut encoder = self.encoder();
+        self.draw_scene(&mut encoder, &frame, scene, self.bg)?;
+
+        let mut rgba = self.read_target(encoder, &frame)?;
+        self.unpremultiply(&mut rgba);
+        rgba = rgba
+            .chunks_exact(self.width as usize * 4)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        if !scene.effects.is_empty() {
+            let mut frame = FrameMut {
+                pixels: &mut rgba,
+                width: self.width,
+                height: self.height,
+                bottom_up: true,
+            };
+            self.apply_effects(&scene.effects, scene.time, &mut frame)?;
+        }
+        Ok(rgba)
+    }
+
+    fn measure_object(&self, object: &Object, transform: &Transform) -> Result<Option<Vec2>> {
+        match object {
+            Object::Image(image) => {
+                let texture = self.image(image)?;
+                Ok(Some(Vec2 {
+                    x: texture.width as f32 * transform.scale.x,
+                    y: texture.height as f32 * transform.scale.y,
+                }))
+            }
+            Object::Text(text) => {
+                let (x, y) = self.fonts.block_size(text, transform)?;
+                Ok(Some(Vec2 { x, y }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn preload(&mut self, scene: &SampledScene) -> Result<()> {
+        for clip in scene.layers.iter().flat_map(|layer| &layer.clips) {
+            if clip.shader.is_some() {
+                bail!(ErrorKind::InvalidProject
+                    .error("clip shaders aren't supported by the wgpu renderer"));
+            }
+            match &clip.object {
+                Object::Image(image) => self.load_image(image)?,
+                Object::Text(text) => self.fonts.preload(&text.font, &self.assets)?,
+                _ => {}
+            }
+            if let Some(nested) = &clip.nested {
+                self.preload(nested)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn load_image(&mut self, image: &ImageObject) -> Result<()> {
+        let path = &image.path;
+        if self.images.contains_key(path) {
+            return Ok(());
+        }
+        let Some(bytes) = self.assets.read(path) else {
+            bail!(
+                ErrorKind::MissingAsset.error(format!("image asset not found: {}", path.display()))
+            );
+        };
+        // Decoded premultiplied, as targets are.
+        let pixmap = Pixmap::decode_png(&bytes).map_err(|err| {
+            ErrorKind::MissingAsset.error(format!(
+                "failed to load {} (the wgpu renderer reads PNGs only): {err}",
+                path.display()
+            ))
+        })?;
+        let texture = self.upload(pixmap.width(), pixmap.height(), pixmap.data());
+        self.images.insert(path.clone(), texture);
+        Ok(())
+    }
+
+    fn image(&self, image: &ImageObject) -> Result<&GpuTexture> {
+        self.images
+            .get(&image.path)
+            .with_context(|| format!("image was not preloaded: {}", image.path.display()))
+    }
+
+    fn plugins(&self) -> Result<&PluginRegistry> {
+        self.plugins
+            .as_deref()
+            .context(ErrorKind::InvalidProject.error("scene uses plugins but no registry was set"))
+    }
+
+    fn apply_effects(
+        &self,
+        effects: &[PluginEffect],
+        time: f32,
+        frame: &mut FrameMut<'_>,
+    ) -> Result<()> {
+        let plugins = self.plugins()?;
+        for effect in effects {
+            plugins.effect(&effect.kind)?.apply(frame, &effect.params, time)?;
+        }
+        Ok(())
+    }
+
+    fn encoder(&self) -> wgpu::CommandEncoder {
+        self.device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None })
+    }
+
+    // A transparent target that can be drawn into, drawn from and read back.
+    fn target(&self, width: u32, height: u32) -> Result<GpuTexture> {
+        let max = self.device.limits().max_texture_dimension_2d;
+        if width == 0 || height == 0 || width > max || height > max {
+            bail!(ErrorKind::Gpu.error(format!("can't create a {width}x{height} target")));
+        }
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC
+            | wgpu::TextureUsages::COPY_DST;
+        let descriptor = texture_descriptor(width, height, self.format(), usage);
+        let texture = self.device.create_texture(&descriptor);
+        Ok(bind_texture(&self.device, &self.layout, &self.sampler, texture))
+    }
+
+    fn format(&self) -> wgpu::TextureFormat {
+        if self.linear { LINEAR_FORMAT } else { FORMAT }
+    }
+
+    // A texture from `rgba` premultiplied on sRGB values, as tiny-skia
+    // decodes and draws, converted to linear light when that's on.
+    fn upload(&self, width: u32, height: u32, rgba: &[u8]) -> GpuTexture {
+        let (device, queue) = (&self.device, &self.queue);
+        let upload = |rgba: &[u8]| {
+            let size = (width, height);
+            upload_texture(device, queue, &self.layout, &self.sampler, self.format(), size, rgba)
+        };
+        if !self.linear {
+            return upload(rgba);
+        }
+        let mut rgba = rgba.to_vec();
+        unpremultiply(&mut rgba);
+        premultiply_linear(&mut rgba);
+        upload(&rgba)
+    }
+
+    // Premultiplied pixels read back from a target to straight sRGB.
+    fn unpremultiply(&self, rgba: &mut [u8]) {
+        if self.linear {
+            unpremultiply_linear(rgba);
+        } else {
+            unpremultiply(rgba);
+        }
+    }
+
+    // Submits `encoder` and the copy of `target` after it, and returns the
+    // target's pixels: top-down, premultiplied.
+    fn read_target(
+        &self,
+        mut encoder: wgpu::CommandEncoder,
+        target: &GpuTexture,
+    ) -> Result<Vec<u8>> {
+        let (width, height) = (target.width, target.height);
+        let row = width * 4;
+        // Copies need rows aligned to 256 bytes.
+        let padded = row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: padded as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            target.texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded),
+                    rows_per_image: Some(height),
+                },
+            },
+            target.texture.size(),
+        );
+        self.queue.submit([encoder.finish()]);
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .context("wgpu dropped the readback")?
+            .map_err(|err| ErrorKind::Gpu.error(format!("failed to read back frame: {err}")))?;
+        let mapped = slice.get_mapped_range();
+        let mut rgba = Vec::with_capacity((row * height) as usize);
+        for line in mapped.chunks_exact(padded as usize) {
+            rgba.extend_from_slice(&line[..row as usize]);
+        }
+        drop(mapped);
+        buffer.unmap();
+        Ok(rgba)
+    }
+
+    // Records the passes for `scene` into `target`: every offscreen target
+    // it needs first, then the target itself. Same structure as the raylib
+    // backend's `draw_layers`.
+    fn draw_scene(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &GpuTexture,
+        scene: &SampledScene,
+        clear: Color,
+    ) -> Result<()> {
+        let (width, height) = (target.width, target.height);
+        let mut list = DrawList::new(width, height);
+        for run in scene.layers.chunk_by(|a, b| a.group == b.group) {
+            let Some(group) = run[0].group.map(|group| &scene.groups[group]) else {
+                self.draw_layer_run(encoder, &mut list, run)?;
+                continue;
+            };
+            let group_target = self.target(width, height)?;
+            let mut members = DrawList::new(width, height);
+            self.draw_layer_run(encoder, &mut members, run)?;
+            self.record(encoder, &group_target, Color::rgba(0, 0, 0, 0), &members);
+            if !group.effects.is_empty() {
+                self.apply_target_effects(encoder, &group_target, &group.effects, scene.time)?;
+            }
+            list.fullscreen(&group_target, group.blend.into(), false, group.opacity);
+        }
+        self.record(encoder, target, clear, &list);
+        Ok(())
+    }
+
+    // Runs effects over a target mid-frame: everything recorded so far is
+    // submitted so the target can be read back, and the result is written
+    // back before the rest of the frame draws from it.
+    fn apply_target_effects(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &GpuTexture,
+        effects: &[PluginEffect],
+        time: f32,
+    ) -> Result<()> {
+        let recorded = std::mem::replace(encoder, self.encoder());
+        let mut rgba = self.read_target(recorded, target)?;
+        self.unpremultiply(&mut rgba);
+        let mut frame = FrameMut {
+            pixels: &mut rgba,
+            width: target.width,
+            height: target.height,
+            bottom_up: false,
+        };
+        self.apply_effects(effects, time, &mut frame)?;
+        if self.linear {
+            premultiply_linear(&mut rgba);
+        } else {
+            premultiply(&mut rgba);
+        }
+        self.queue.write_texture(
+            target.texture.as_image_copy(),
+            &rgba,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(target.width * 4),
+                rows_per_image: Some(target.height),
+            },
+            target.texture.size(),
+        );
+        Ok(())
+    }
+
+    fn draw_layer_run(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        list: &mut DrawList,
+        layers: &[SampledLayer],
+    ) -> Result<()> {
+        let (width, height) = (list.width as u32, list.height as u32);
+        let mut skip_matte_source = false;
+        for (index, layer) in layers.iter().enumerate() {
+            if skip_matte_source {
+                skip_matte_source = false;
+                continue;
+            }
+            if layer.blend == BlendMode::Normal && layer.matte.is_none() {
+                self.draw_clips(encoder, list, layer)?;
+                continue;
+            }
+            let layer_target = self.target(width, height)?;
+            let mut clips = DrawList::new(width, height);
+            self.draw_clips(encoder, &mut clips, layer)?;
+            if let (Some(matte), Some(source)) = (layer.matte, layers.get(index + 1)) {
+                let source_target = self.target(width, height)?;
+                let mut source_clips = DrawList::new(width, height);
+                self.draw_clips(encoder, &mut source_clips, source)?;
+                self.record(encoder, &source_target, Color::rgba(0, 0, 0, 0), &source_clips);
+                let (blend, luma) = match matte {
+                    TrackMatte::Alpha => (Blend::Matte, false),
+                    TrackMatte::AlphaInverted => (Blend::MatteInverted, false),
+                    TrackMatte::Luma => (Blend::Matte, true),
+                    TrackMatte::LumaInverted => (Blend::MatteInverted, true),
+                };
+                clips.fullscreen(&source_target, blend, luma, 1.0);
+            }
+            skip_matte_source = layer.matte.is_some();
+            self.record(encoder, &layer_target, Color::rgba(0, 0, 0, 0), &clips);
+            list.fullscreen(&layer_target, layer.blend.into(), false, 1.0);
+        }
+        Ok(())
+    }
+
+    fn draw_clips(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        list: &mut DrawList,
+        layer: &SampledLayer,
+    ) -> Result<()> {
+        for clip in &layer.clips {
+            self.draw_clip(encoder, list, &layer.transform, clip)?;
+        }
+        Ok(())
+    }
+
+    fn draw_clip(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        list: &mut DrawList,
+        layer_transform: &Transform,
+        clip: &SampledClip,
+    ) -> Result<()> {
+        let (width, height) = (list.width as u32, list.height as u32);
+        let (transform, wipe) = clip_placement(layer_transform, clip, width, height);
+        list.scissor = wipe.map(|rect| list.clamp_rect(rect));
+        let result = self.draw_object(encoder, list, clip, &transform);
+        list.scissor = None;
+        result
+    }
+
+    fn draw_object(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        list: &mut DrawList,
+        clip: &SampledClip,
+        transform: &Transform,
+    ) -> Result<()> {
+        let center = list.graph_to_screen(transform.pos);
+        let scale = transform.scale;
+        if let (Object::Composition(composition), Some(nested)) = (&clip.object, &clip.nested) {
+            let target = self.target(composition.width, composition.height)?;
+            self.draw_scene(encoder, &target, nested, composition.bg)?;
+            if !nested.effects.is_empty() {
+                self.apply_target_effects(encoder, &target, &nested.effects, nested.time)?;
+            }
+            let (w, h) = (target.width as f32 * scale.x, target.height as f32 * scale.y);
+            let tint = premultiplied_tint(Color::WHITE, transform.opacity, self.linear);
+            list.quad(&target, center, (-w / 2.0, -h / 2.0, w, h), transform.rotation, tint);
+            return Ok(());
+        }
+
+        match &clip.object {
+            Object::Shape(Shape::Circle { radius, color }) => {
+                let radius = radius * scale.x.max(0.0);
+                let tint = premultiplied_tint(*color, transform.opacity, self.linear);
+                list.circle(&self.white, center, radius, tint);
+            }
+            Object::Shape(Shape::Rect {
+                width: w,
+                height: h,
+                color,
+            }) => {
+                let (w, h) = (w * scale.x, h * scale.y);
+                let rect = (-w / 2.0, -h / 2.0, w, h);
+                let tint = premultiplied_tint(*color, transform.opacity, self.linear);
+                list.quad(&self.white, center, rect, transform.rotation, tint);
+            }
+            Object::Image(image) => {
+                let texture = self.image(image)?;
+                let (w, h) = (texture.width as f32 * scale.x, texture.height as f32 * scale.y);
+                let tint = premultiplied_tint(Color::WHITE, transform.opacity, self.linear);
+                list.quad(texture, center, (-w / 2.0, -h / 2.0, w, h), transform.rotation, tint);
+            }
+            Object::Text(text) => {
+                // Rasterized unrotated with room for the plate, stroke and
+                // overhanging glyphs, then drawn as one rotated quad.
+                let (block_w, block_h) = self.fonts.block_size(text, transform)?;
+                let font_scale = scale.y.max(0.0);
+                let plate = text.background.as_ref().map_or(0.0, |b| b.padding * font_scale);
+                let stroke = text.stroke.as_ref().map_or(0.0, |s| s.width * font_scale);
+                let margin = (plate.max(stroke) + text.font_size * font_scale).ceil();
+                let (w, h) = (
+                    (block_w + margin * 2.0).ceil() as u32,
+                    (block_h + margin * 2.0).ceil() as u32,
+                );
+                let Some(mut pixmap) = Pixmap::new(w, h) else {
+                    return Ok(());
+                };
+                let flat = Transform {
+                    rotation: 0.0,
+                    ..*transform
+                };
+                self.fonts.draw(&mut pixmap, text, &flat, (margin, margin), None)?;
+                let texture = self.upload(w, h, pixmap.data());
+                // Unrotated text lands on whole pixels, unfiltered.
+                let origin = if transform.rotation == 0.0 {
+                    (center.0.round(), center.1.round())
+                } else {
+                    center
+                };
+                let rect = (-margin, -margin, w as f32, h as f32);
+                list.quad(&texture, origin, rect, transform.rotation, [1.0; 4]);
+            }
+            Object::Plugin(plugin) => {
+                let plugin_impl = self.plugins()?.object(&plugin.kind)?;
+                let mut canvas = WgpuCanvas {
+                    list,
+                    white: &self.white,
+                    opacity: transform.opacity,
+                    linear: self.linear,
+                };
+                plugin_impl.draw(&mut canvas, &plugin.params, transform, clip.local_time)?;
+            }
+            // Drawn from the clip's sampled `nested` scene above.
+            Object::Composition(_) => {}
+        }
+        Ok(())
+    }
+
+    // One pass over `target`: cleared to `clear`, then `list` drawn in order.
+    fn record(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &GpuTexture,
+        clear: Color,
+        list: &DrawList,
+    ) {
+        let view = target.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // Clear colours are linear for sRGB targets.
+        let [r, g, b, a] = premultiplied_tint(clear, 1.0, self.linear);
+        let vertices = (!list.vertices.is_empty()).then(|| {
+            let bytes: Vec<u8> = list.vertices.iter().flat_map(|v| v.to_ne_bytes()).collect();
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("vertices"),
+                    contents: &bytes,
+                    usage: wgpu::BufferUsages::VERTEX,
+                })
+        });
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: r as f64,
+                        g: g as f64,
+                        b: b as f64,
+                        a: a as f64,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        let Some(vertices) = &vertices else {
+            return;
+        };
+        pass.set_vertex_buffer(0, vertices.slice(..));
+        for batch in &list.batches {
+            let [x, y, w, h] = batch.scissor.unwrap_or([0, 0, target.width, target.height]);
+            if w == 0 || h == 0 {
+                continue;
+            }
+            pass.set_scissor_rect(x, y, w, h);
+            pass.set_pipeline(&self.pipelines[&batch.pipeline]);
+            pass.set_bind_group(0, &batch.bind_group, &[]);
+            pass.draw(batch.vertices.clone(), 0..1);
+        }
+    }
+}
+
+impl RenderBackend for WgpuRender {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn set_plugins(&mut self, plugins: Arc<PluginRegistry>) {
+        WgpuRender::set_plugins(self, plugins);
+    }
+
+    fn set_data_source(&mut self, data: Arc<dyn DataSource>) {
+        WgpuRender::set_data_source(self, data);
+    }
+
+    fn set_cancel_token(&mut self, cancel: CancelToken) {
+        WgpuRender::set_cancel_token(self, cancel);
+    }
+
+    fn render_scene_to_rgba(&mut self, scene: &SampledScene) -> Result<Vec<u8>> {
+        WgpuRender::render_scene_to_rgba(self, scene)
+    }
+
+    fn render_frame_rgba(&mut self, timeline: &Timeline, time: f32) -> Result<Vec<u8>> {
+        WgpuRender::render_frame_rgba(self, timeline, time)
+    }
+
+    fn render_timeline_rgba(
+        &mut self,
+        timeline: &Timeline,
+        start_time: f32,
+        end_time: f32,
+        on_frame: &mut dyn FnMut(f32, &[u8]) -> Result<()>,
+    ) -> Result<()> {
+        WgpuRender::render_timeline_rgba(self, timeline, start_time, end_time, on_frame)
+    }
+}
+
+struct Batch {
+    pipeline: PipelineKey,
+    bind_group: wgpu::BindGroup,
+    scissor: Option<[u32; 4]>,
+    vertices: Range<u32>,
+}
+
+// Triangles for one target, in draw order. Consecutive draws with the same
+// pipeline, texture and scissor share a batch.
+struct DrawList {
+    width: f32,
+    height: f32,
+    vertices: Vec<f32>,
+    batches: Vec<Batch>,
+    // Applies to what's pushed while it's set.
+    scissor: Option<[u32; 4]>,
+}
+
+impl DrawList {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width: width as f32,
+            height: height as f32,
+            vertices: Vec::new(),
+            batches: Vec::new(),
+            scissor: None,
+        }
+    }
+
+    fn graph_to_screen(&self, pos: Vec2) -> (f32, f32) {
+        (self.width / 2.0 + pos.x, self.height / 2.0 - pos.y)
+    }
+
+    // A wipe rectangle in whole pixels inside the target.
+    fn clamp_rect(&self, (x, y, w, h): (f32, f32, f32, f32)) -> [u32; 4] {
+        let left = x.round().clamp(0.0, self.width);
+        let top = y.round().clamp(0.0, self.height);
+        let right = (x + w).round().clamp(left, self.width);
+        let bottom = (y + h).round().clamp(top, self.height);
+        [left as u32, top as u32, (right - left) as u32, (bottom - top) as u32]
+    }
+
+    fn push(
+        &mut self,
+        texture: &GpuTexture,
+        pipeline: PipelineKey,
+        points: &[Point],
+        tint: [f32; 4],
+    ) {
+        let start = (self.vertices.len() / VERTEX_FLOATS) as u32;
+        for &((x, y), (u, v)) in points {
+            let position = [x / self.width * 2.0 - 1.0, 1.0 - y / self.height * 2.0];
+            self.vertices.extend(position.into_iter().chain([u, v]).chain(tint));
+        }
+        let end = (self.vertices.len() / VERTEX_FLOATS) as u32;
+        if let Some(last) = self.batches.last_mut()
+            && last.pipeline == pipeline
+            && last.bind_group == texture.bind_group
+            && last.scissor == self.scissor
+            && last.vertices.end == start
+        {
+            last.vertices.end = end;
+            return;
+        }
+        self.batches.push(Batch {
+            pipeline,
+            bind_group: texture.bind_group.clone(),
+            scissor: self.scissor,
+            vertices: start..end,
+        });
+    }
+
+    // `texture` stretched over `rect` (x, y, w, h around `origin`), rotated
+    // clockwise by `rotation` degrees about `origin`.
+    fn quad(
+        &mut self,
+        texture: &GpuTexture,
+        origin: (f32, f32),
+        (x, y, w, h): (f32, f32, f32, f32),
+        rotation: f32,
+        tint: [f32; 4],
+    ) {
+        let (sin, cos) = rotation.to_radians().sin_cos();
+        let corner = |dx: f32, dy: f32, uv: (f32, f32)| {
+            let point = (origin.0 + dx * cos - dy * sin, origin.1 + dx * sin + dy * cos);
+            (point, uv)
+        };
+        let top_left = corner(x, y, (0.0, 0.0));
+        let top_right = corner(x + w, y, (1.0, 0.0));
+        let bottom_left = corner(x, y + h, (0.0, 1.0));
+        let bottom_right = corner(x + w, y + h, (1.0, 1.0));
+        let points = [top_left, top_right, bottom_left, bottom_left, top_right, bottom_right];
+        self.push(texture, (Blend::Over, false), &points, tint);
+    }
+
+    fn circle(&mut self, white: &GpuTexture, center: (f32, f32), radius: f32, tint: [f32; 4]) {
+        let point = |i: u32| {
+            let angle = (i as f32 / CIRCLE_SEGMENTS as f32) * std::f32::consts::TAU;
+            let (sin, cos) = angle.sin_cos();
+            ((center.0 + cos * radius, center.1 + sin * radius), (0.5, 0.5))
+        };
+        let points: Vec<_> = (0..CIRCLE_SEGMENTS)
+            .flat_map(|i| [(center, (0.5, 0.5)), point(i), point(i + 1)])
+            .collect();
+        self.push(white, (Blend::Over, false), &points, tint);
+    }
+
+    // A whole target drawn over this one.
+    fn fullscreen(&mut self, source: &GpuTexture, blend: Blend, luma: bool, opacity: f32) {
+        let (w, h) = (self.width, self.height);
+        let points = [
+            ((0.0, 0.0), (0.0, 0.0)),
+            ((w, 0.0), (1.0, 0.0)),
+            ((0.0, h), (0.0, 1.0)),
+            ((0.0, h), (0.0, 1.0)),
+            ((w, 0.0), (1.0, 0.0)),
+            ((w, h), (1.0, 1.0)),
+        ];
+        // White is the same in either space.
+        let tint = premultiplied_tint(Color::WHITE, opacity, false);
+        self.push(source, (blend, luma), &points, tint);
+    }
+}
+
+struct WgpuCanvas<'a> {
+    list: &'a mut DrawList,
+    white: &'a GpuTexture,
+    opacity: f32,
+    linear: bool,
+}
+
+impl Canvas for WgpuCanvas<'_> {
+    fn size(&self) -> (u32, u32) {
+        (self.list.width as u32, self.list.height as u32)
+    }
+
+    fn fill_rect(&mut self, center: Vec2, width: f32, height: f32, rotation: f32, color: Color) {
+        let center = self.list.graph_to_screen(center);
+        let rect = (-width / 2.0, -height / 2.0, width, height);
+        let tint = premultiplied_tint(color, self.opacity, self.linear);
+        self.list.quad(self.white, center, rect, rotation, tint);
+    }
+
+    fn fill_circle(&mut self, center: Vec2, radius: f32, color: Color) {
+        let center = self.list.graph_to_screen(center);
+        let tint = premultiplied_tint(color, self.opacity, self.linear);
+        self.list.circle(self.white, center, radius, tint);
+    }
+
+    fn fill_triangle(&mut self, a: Vec2, b: Vec2, c: Vec2, color: Color) {
+        let points = [a, b, c].map(|point| (self.list.graph_to_screen(point), (0.5, 0.5)));
+        let tint = premultiplied_tint(color, self.opacity, self.linear);
+        self.list.push(self.white, (Blend::Over, false), &points, tint);
+    }
+
+    fn line(&mut self, from: Vec2, to: Vec2, thickness: f32, color: Color) {
+        let from = self.list.graph_to_screen(from);
+        let to = self.list.graph_to_screen(to);
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        let rotation = dy.atan2(dx).to_degrees();
+        let rect = (0.0, -thickness / 2.0, length, thickness);
+        let tint = premultiplied_tint(color, self.opacity, self.linear);
+        self.list.quad(self.white, from, rect, rotation, tint);
+    }
+}
+
+fn create_pipelines(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+) -> HashMap<PipelineKey, wgpu::RenderPipeline> {
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("draw"),
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("draw"),
+        bind_group_layouts: &[layout],
+        push_constant_ranges: &[],
+    });
+    let attributes = wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+    let vertex_layout = wgpu::VertexBufferLayout {
+        array_stride: (VERTEX_FLOATS * 4) as u64,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &attributes,
+    };
+    let mut pipelines = HashMap::new();
+    for blend in BLENDS {
+        for luma in [false, true] {
+            let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("draw"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &module,
+                    entry_point: Some("vs_main"),
+                    compilation_options: Default::default(),
+                    buffers: std::slice::from_ref(&vertex_layout),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: Some(if luma { "fs_luma" } else { "fs_main" }),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(blend.state()),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+            pipelines.insert((blend, luma), pipeline);
+        }
+    }
+    pipelines
+}
+
+fn texture_descriptor(
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+) -> wgpu::TextureDescriptor<'static> {
+    wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage,
+        view_formats: &[],
+    }
+}
+
+fn bind_texture(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    texture: wgpu::Texture,
+) -> GpuTexture {
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    });
+    GpuTexture {
+        width: texture.width(),
+        height: texture.height(),
+        texture,
+        bind_group,
+    }
+}
+
+// A texture holding premultiplied `rgba`.
+fn upload_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    format: wgpu::TextureFormat,
+    (width, height): (u32, u32),
+    rgba: &[u8],
+) -> GpuTexture {
+    let usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+    let texture = device.create_texture_with_data(
+        queue,
+        &texture_descriptor(width, height, format, usage),
+        wgpu::util::TextureDataOrder::LayerMajor,
+        rgba,
+    );
+    bind_texture(device, layout, sampler, texture)
+}
+
+// `color` at `opacity` as a premultiplied tint, in linear light if `linear`.
+fn premultiplied_tint(color: Color, opacity: f32, linear: bool) -> [f32; 4] {
+    let alpha = color.a as f32 / 255.0 * opacity.clamp(0.0, 1.0);
+    let scale = |channel: u8| {
+        let value = channel as f32 / 255.0;
+        let value = if linear { srgb_to_linear(value) } else { value };
+        value * alpha
+    };
+    [scale(color.r), scale(color.g), scale(color.b), alpha]
+}
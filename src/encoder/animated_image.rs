@@ -0,0 +1,255 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+
+use crate::encoder::ffmpeg_log::StderrLog;
+use crate::encoder::Encoder;
+use crate::error::ErrorKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimatedImageFormat {
+    // Up to 256 colours per file, from a palette built over every frame.
+    Gif,
+    // Lossy animated WebP; full colour and much smaller than a GIF.
+    WebP,
+}
+
+impl AnimatedImageFormat {
+    // From the output extension: `.gif` or `.webp`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gif" => Some(AnimatedImageFormat::Gif),
+            "webp" => Some(AnimatedImageFormat::WebP),
+            _ => None,
+        }
+    }
+}
+
+// Settings for short looping clips for docs and social posts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimatedImage {
+    pub format: AnimatedImageFormat,
+    // Times the animation plays; 0 loops forever.
+    pub plays: u32,
+    // GIF palette size, 2 to 256. Fewer colours make smaller files.
+    pub max_colors: u32,
+    // Error-diffusion dithering for GIFs, hiding banding in gradients at
+    // the cost of some size.
+    pub dither: bool,
+    // WebP quality, 0 to 100.
+    pub quality: u32,
+    // Drops frames down to this rate, e.g. 15 for a smaller GIF.
+    pub fps: Option<u32>,
+}
+
+impl AnimatedImage {
+    pub fn new(format: AnimatedImageFormat) -> Self {
+        Self {
+            format,
+            plays: 0,
+            max_colors: 256,
+            dither: true,
+            quality: 80,
+            fps: None,
+        }
+    }
+
+    pub fn with_plays(mut self, plays: u32) -> Self {
+        self.plays = plays;
+        self
+    }
+
+    pub fn with_max_colors(mut self, max_colors: u32) -> Self {
+        self.max_colors = max_colors;
+        self
+    }
+
+    pub fn with_dither(mut self, dither: bool) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    pub fn with_quality(mut self, quality: u32) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    pub fn with_fps(mut self, fps: u32) -> Self {
+        self.fps = Some(fps);
+        self
+    }
+
+    // (filtergraph, encoder and muxer args) after the input.
+    fn ffmpeg_args(&self) -> Result<(String, Vec<String>)> {
+        let mut filters = String::from("vflip");
+        if let Some(fps) = self.fps {
+            filters.push_str(&format!(",fps={fps}"));
+        }
+        match self.format {
+            AnimatedImageFormat::Gif => {
+                if !(2..=256).contains(&self.max_colors) {
+                    bail!(ErrorKind::InvalidProject.error(format!(
+                        "GIF max colors must be 2 to 256, got {}",
+                        self.max_colors
+                    )));
+                }
+                let dither = if self.dither { "sierra2_4a" } else { "none" };
+                // One palette for the whole clip, weighted towards what
+                // changes between frames; only changed areas are redrawn.
+                filters.push_str(&format!(
+                    ",split[a][b];[a]palettegen=max_colors={}:stats_mode=diff[p];\
+                     [b][p]paletteuse=dither={dither}:diff_mode=rectangle",
+                    self.max_colors
+                ));
+                // The GIF loop count is repeats after the first play, with
+                // -1 for none.
+                let repeats = match self.plays {
+                    0 => 0,
+                    1 => -1,
+                    plays => plays as i64 - 1,
+                };
+                let mut args: Vec<String> =
+                    ["-c:v", "gif", "-f", "gif", "-loop"].map(String::from).to_vec();
+                args.push(repeats.to_string());
+                Ok((filters, args))
+            }
+            AnimatedImageFormat::WebP => {
+                if self.quality > 100 {
+                    bail!(ErrorKind::InvalidProject.error(format!(
+                        "WebP quality must be 0 to 100, got {}",
+                        self.quality
+                    )));
+                }
+                let mut args: Vec<String> =
+                    ["-c:v", "libwebp_anim", "-pix_fmt", "yuva420p", "-f", "webp"]
+                        .map(String::from)
+                        .to_vec();
+                args.extend(["-quality".to_string(), self.quality.to_string()]);
+                args.extend(["-loop".to_string(), self.plays.to_string()]);
+                Ok((filters, args))
+            }
+        }
+    }
+}
+
+// Encodes bottom-up RGBA frames into a GIF or animated WebP, like
+// `FfmpegVideoEncoder` does a video. Transparent pixels stay transparent.
+pub struct AnimatedImageEncoder {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    log: StderrLog,
+    width: u32,
+    height: u32,
+    format: AnimatedImageFormat,
+}
+
+impl AnimatedImageEncoder {
+    pub fn start(
+        width: u32,
+        height: u32,
+        fps: u32,
+        output_path: &Path,
+        settings: &AnimatedImage,
+    ) -> Result<Self> {
+        let (filters, args) = settings.ffmpeg_args()?;
+
+        let mut child = Command::new("ffmpeg")
+            .arg("-y")
+            .arg("-loglevel")
+            .arg("error")
+            .arg("-f")
+            .arg("rawvideo")
+            .arg("-pix_fmt")
+            .arg("rgba")
+            .arg("-s")
+            .arg(format!("{}x{}", width, height))
+            .arg("-r")
+            .arg(fps.to_string())
+            .arg("-i")
+            .arg("-")
+            .arg("-filter_complex")
+            .arg(filters)
+            .args(args)
+            .arg(output_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context(ErrorKind::Encoder.error("failed to spawn ffmpeg (is it on PATH?)"))?;
+        let stdin = child
+            .stdin
+            .take()
+            .context(ErrorKind::Encoder.error("failed to open ffmpeg stdin"))?;
+        let log = StderrLog::capture(&mut child);
+
+        Ok(Self {
+            child,
+            stdin: Some(stdin),
+            log,
+            width,
+            height,
+            format: settings.format,
+        })
+    }
+
+    pub fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        let expected = (self.width * self.height * 4) as usize;
+        if frame.len() != expected {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "frame size mismatch: got {}, expected {}",
+                frame.len(),
+                expected
+            )));
+        }
+
+        let stdin = self
+            .stdin
+            .as_mut()
+            .context(ErrorKind::Encoder.error("ffmpeg stdin already closed"))?;
+        stdin.write_all(frame).context(ErrorKind::Encoder.error("failed to write frame"))?;
+        Ok(())
+    }
+
+    // GIF palettes are built from every frame, so most of the work happens
+    // here.
+    pub fn finish(mut self) -> Result<()> {
+        if let Some(mut stdin) = self.stdin.take() {
+            let _ = stdin.flush();
+        }
+
+        let status = self
+            .child
+            .wait()
+            .context(ErrorKind::Encoder.error("failed to wait for ffmpeg"))?;
+        self.log.join();
+        if !status.success() {
+            bail!(ErrorKind::Encoder.error(format!(
+                "ffmpeg exited with status {status}; last output:\n{}",
+                self.log.tail()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl Encoder for AnimatedImageEncoder {
+    fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        AnimatedImageEncoder::write_frame(self, frame)
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        AnimatedImageEncoder::finish(*self)
+    }
+
+    fn name(&self) -> String {
+        match self.format {
+            AnimatedImageFormat::Gif => "gif",
+            AnimatedImageFormat::WebP => "webp",
+        }
+        .to_string()
+    }
+}
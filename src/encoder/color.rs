@@ -0,0 +1,81 @@
+use crate::backend::premultiply::{linear_to_srgb, srgb_to_linear};
+
+// Colour space outputs are encoded in and tagged with, so players and
+// browsers show the same colours instead of guessing. Frames are rendered
+// in sRGB either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputColor {
+    // Rec.709 video (the same primaries as sRGB) and sRGB stills.
+    #[default]
+    Srgb,
+    // Display P3 (DCI-P3 primaries, D65 white, sRGB curve), as shown by
+    // recent phones and Macs. Frames are converted from sRGB, so colours
+    // look the same as the sRGB output on P3 screens.
+    DisplayP3,
+}
+
+impl OutputColor {
+    // `srgb` (or `rec709`) or `p3` (or `display-p3`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "srgb" | "rec709" | "bt709" => Some(OutputColor::Srgb),
+            "p3" | "display-p3" | "display_p3" => Some(OutputColor::DisplayP3),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputColor::Srgb => "srgb",
+            OutputColor::DisplayP3 => "display-p3",
+        }
+    }
+
+    // (filters to run after `vflip`, tagging args) for an output encoded
+    // as `pix_fmt`. YUV outputs use the BT.709 matrix; ffmpeg otherwise
+    // converts RGB with BT.601 and the colours shift in every player.
+    pub(crate) fn ffmpeg_args(self, pix_fmt: &str) -> (&'static str, Vec<&'static str>) {
+        // VA-API frames are uploaded as NV12 after these filters.
+        let yuv = pix_fmt.starts_with("yuv") || matches!(pix_fmt, "nv12" | "vaapi");
+        let primaries = match self {
+            OutputColor::Srgb => "bt709",
+            OutputColor::DisplayP3 => "smpte432",
+        };
+        // Rec.709 video carries the BT.709 curve tag players expect; stills
+        // and P3 use the sRGB curve.
+        let transfer = match self {
+            OutputColor::Srgb if yuv => "bt709",
+            _ => "iec61966-2-1",
+        };
+        let mut args = vec!["-color_primaries", primaries, "-color_trc", transfer];
+        if !yuv {
+            return ("", args);
+        }
+        args.extend(["-colorspace", "bt709", "-color_range", "tv"]);
+        (",scale=out_color_matrix=bt709:out_range=tv", args)
+    }
+
+    // Converts straight sRGB RGBA to this colour space in place.
+    pub fn convert(self, rgba: &mut [u8]) {
+        if self == OutputColor::Srgb {
+            return;
+        }
+        let decode: Vec<f32> = (0..=255)
+            .map(|value| srgb_to_linear(value as f32 / 255.0))
+            .collect();
+        for pixel in rgba.chunks_exact_mut(4) {
+            let [r, g, b] = [0, 1, 2].map(|channel| decode[pixel[channel] as usize]);
+            for (channel, row) in SRGB_TO_P3.iter().enumerate() {
+                let linear = row[0] * r + row[1] * g + row[2] * b;
+                pixel[channel] = (linear_to_srgb(linear) * 255.0).round() as u8;
+            }
+        }
+    }
+}
+
+// Linear sRGB to linear Display P3, both D65.
+const SRGB_TO_P3: [[f32; 3]; 3] = [
+    [0.822_462, 0.177_538, 0.0],
+    [0.033_194, 0.966_806, 0.0],
+    [0.017_083, 0.072_397, 0.910_520],
+];
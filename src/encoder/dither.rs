@@ -0,0 +1,80 @@
+// Dithering added to frames just before they're encoded. Soft gradients
+// and vignettes are a step of one 8-bit level every few pixels, and H.264
+// smooths those steps into wide visible bands; a pattern of one level up or
+// down breaks the steps up so the encoder keeps them as texture instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dither {
+    #[default]
+    None,
+    // A fixed 8x8 Bayer pattern; compresses best but can show a faint grid.
+    Ordered,
+    // Interleaved gradient noise, which like blue noise has no low
+    // frequencies, so it reads as fine grain rather than a pattern.
+    BlueNoise,
+}
+
+impl Dither {
+    // `none`, `ordered` (or `bayer`) or `blue-noise` (or `noise`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "none" | "off" => Some(Dither::None),
+            "ordered" | "bayer" => Some(Dither::Ordered),
+            "blue-noise" | "blue_noise" | "noise" => Some(Dither::BlueNoise),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Dither::None => "none",
+            Dither::Ordered => "ordered",
+            Dither::BlueNoise => "blue-noise",
+        }
+    }
+
+    // Offsets each colour channel of straight RGBA rows `width` wide by at
+    // most one level. Pure black and white are left alone so text and
+    // backgrounds stay clean.
+    pub fn apply(self, rgba: &mut [u8], width: u32) {
+        if self == Dither::None || width == 0 {
+            return;
+        }
+        for (index, pixel) in rgba.chunks_exact_mut(4).enumerate() {
+            let (x, y) = (index as u32 % width, index as u32 / width);
+            let offset = (self.threshold(x, y) * 2.0).round() as i32;
+            if offset == 0 {
+                continue;
+            }
+            for channel in &mut pixel[..3] {
+                if *channel != 0 && *channel != 255 {
+                    *channel = (*channel as i32 + offset).clamp(1, 254) as u8;
+                }
+            }
+        }
+    }
+
+    // In -0.5..0.5.
+    fn threshold(self, x: u32, y: u32) -> f32 {
+        match self {
+            Dither::None => 0.0,
+            Dither::Ordered => {
+                (BAYER_8X8[(y % 8) as usize][(x % 8) as usize] as f32 + 0.5) / 64.0 - 0.5
+            }
+            Dither::BlueNoise => {
+                let (x, y) = (x as f32, y as f32);
+                (52.982_918 * (0.067_110_56 * x + 0.005_837_15 * y).fract()).fract() - 0.5
+            }
+        }
+    }
+}
+
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
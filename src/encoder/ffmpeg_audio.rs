@@ -1,10 +1,11 @@
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 use anyhow::{bail, Context, Result};
 
 use crate::audio::{MusicTrack, SfxEvent};
+use crate::error::ErrorKind;
 
 pub fn render_audio_wav(
     music: &MusicTrack,
@@ -47,10 +48,10 @@ pub fn render_audio_wav(
 
     let mut child = cmd
         .spawn()
-        .context("failed to spawn ffmpeg for audio render")?;
+        .context(ErrorKind::Encoder.error("failed to spawn ffmpeg for audio render"))?;
     let status = child
         .wait()
-        .context("failed to wait for ffmpeg audio render")?;
+        .context(ErrorKind::Encoder.error("failed to wait for ffmpeg audio render"))?;
 
     if !status.success() {
         let stderr = child
@@ -62,7 +63,11 @@ pub fn render_audio_wav(
                 buf
             })
             .unwrap_or_default();
-        bail!("ffmpeg audio render failed with status {}: {}", status, stderr.trim());
+        bail!(ErrorKind::Encoder.error(format!(
+            "ffmpeg audio render failed with status {}: {}",
+            status,
+            stderr.trim()
+        )));
     }
 
     Ok(())
@@ -91,10 +96,10 @@ pub fn trim_audio(
 
     let mut child = cmd
         .spawn()
-        .context("failed to spawn ffmpeg for audio trim")?;
+        .context(ErrorKind::Encoder.error("failed to spawn ffmpeg for audio trim"))?;
     let status = child
         .wait()
-        .context("failed to wait for ffmpeg audio trim")?;
+        .context(ErrorKind::Encoder.error("failed to wait for ffmpeg audio trim"))?;
 
     if !status.success() {
         let stderr = child
@@ -106,7 +111,11 @@ pub fn trim_audio(
                 buf
             })
             .unwrap_or_default();
-        bail!("ffmpeg audio trim failed with status {}: {}", status, stderr.trim());
+        bail!(ErrorKind::Encoder.error(format!(
+            "ffmpeg audio trim failed with status {}: {}",
+            status,
+            stderr.trim()
+        )));
     }
 
     Ok(())
@@ -132,8 +141,8 @@ pub fn mux_video_audio(video_path: &Path, audio_path: &Path, output_path: &Path)
 
     let mut child = cmd
         .spawn()
-        .context("failed to spawn ffmpeg for mux")?;
-    let status = child.wait().context("failed to wait for ffmpeg mux")?;
+        .context(ErrorKind::Encoder.error("failed to spawn ffmpeg for mux"))?;
+    let status = child.wait().context(ErrorKind::Encoder.error("failed to wait for ffmpeg mux"))?;
     if !status.success() {
         let stderr = child
             .stderr
@@ -144,7 +153,155 @@ pub fn mux_video_audio(video_path: &Path, audio_path: &Path, output_path: &Path)
                 buf
             })
             .unwrap_or_default();
-        bail!("ffmpeg mux failed with status {}: {}", status, stderr.trim());
+        bail!(ErrorKind::Encoder.error(format!(
+            "ffmpeg mux failed with status {}: {}",
+            status,
+            stderr.trim()
+        )));
+    }
+
+    Ok(())
+}
+
+// Music or a voiceover to lay under a video (see `VideoOptions::with_audio`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioTrack {
+    pub path: PathBuf,
+    // Seconds into the video where the audio starts.
+    pub offset: f32,
+    // The part of the file to use, in seconds; `None` plays to its end.
+    pub trim_start: f32,
+    pub trim_end: Option<f32>,
+    // Seconds of fade before the video ends; 0 cuts off.
+    pub fade_out: f32,
+}
+
+impl AudioTrack {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            offset: 0.0,
+            trim_start: 0.0,
+            trim_end: None,
+            fade_out: 0.0,
+        }
+    }
+
+    pub fn with_offset(mut self, seconds: f32) -> Self {
+        self.offset = seconds;
+        self
+    }
+
+    pub fn with_trim(mut self, start: f32, end: Option<f32>) -> Self {
+        self.trim_start = start;
+        self.trim_end = end;
+        self
+    }
+
+    pub fn with_fade_out(mut self, seconds: f32) -> Self {
+        self.fade_out = seconds;
+        self
+    }
+
+    // The `-af` chain placing the track under a video `duration` long.
+    fn filter(&self, duration: f32) -> Result<String> {
+        if self.offset < 0.0 || self.trim_start < 0.0 || self.fade_out < 0.0 {
+            bail!(ErrorKind::InvalidProject.error("audio offset, trim and fade must be >= 0"));
+        }
+        let mut filter = format!("atrim=start={:.3}", self.trim_start);
+        if let Some(end) = self.trim_end {
+            if end <= self.trim_start {
+                bail!(ErrorKind::InvalidProject.error("audio trim end must be > start"));
+            }
+            filter.push_str(&format!(":end={end:.3}"));
+        }
+        filter.push_str(",asetpts=PTS-STARTPTS");
+        if self.offset > 0.0 {
+            let delay_ms = (self.offset * 1000.0).round() as i64;
+            filter.push_str(&format!(",adelay={delay_ms}:all=1"));
+        }
+        if self.fade_out > 0.0 {
+            let fade = self.fade_out.min(duration);
+            filter.push_str(&format!(",afade=t=out:st={:.3}:d={fade:.3}", duration - fade));
+        }
+        Ok(filter)
+    }
+}
+
+// Copies the video stream of `video_path` to `output_path` with `track`
+// added as its audio, cut to the video's `duration` in seconds. WebM gets
+// Opus, everything else AAC.
+pub fn mux_audio_track(
+    video_path: &Path,
+    track: &AudioTrack,
+    duration: f32,
+    output_path: &Path,
+) -> Result<()> {
+    mux_audio_track_with(Path::new("ffmpeg"), video_path, track, duration, output_path, &[])
+}
+
+// `mux_audio_track` run by a given ffmpeg binary, with `output_args` added
+// before the output path.
+pub(crate) fn mux_audio_track_with(
+    ffmpeg: &Path,
+    video_path: &Path,
+    track: &AudioTrack,
+    duration: f32,
+    output_path: &Path,
+    output_args: &[String],
+) -> Result<()> {
+    let audio_codec = match output_path.extension().and_then(|ext| ext.to_str()) {
+        Some("webm") => "libopus",
+        _ => "aac",
+    };
+    let mut cmd = Command::new(ffmpeg);
+    cmd.arg("-y")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-i")
+        .arg(video_path)
+        .arg("-i")
+        .arg(&track.path)
+        .arg("-map")
+        .arg("0:v")
+        .arg("-map")
+        .arg("1:a:0")
+        .arg("-af")
+        .arg(track.filter(duration)?)
+        .arg("-c:v")
+        .arg("copy")
+        .arg("-c:a")
+        .arg(audio_codec)
+        .arg("-t")
+        .arg(format!("{duration:.3}"))
+        .args(output_args)
+        .arg(output_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+
+    let mut child = cmd
+        .spawn()
+        .context(ErrorKind::Encoder.error("failed to spawn ffmpeg for audio mux"))?;
+    let status = child
+        .wait()
+        .context(ErrorKind::Encoder.error("failed to wait for ffmpeg audio mux"))?;
+    if !status.success() {
+        let stderr = child
+            .stderr
+            .take()
+            .map(|mut s| {
+                let mut buf = String::new();
+                let _ = s.read_to_string(&mut buf);
+                buf
+            })
+            .unwrap_or_default();
+        bail!(ErrorKind::Encoder.error(format!(
+            "ffmpeg audio mux of {} failed with status {}: {}",
+            track.path.display(),
+            status,
+            stderr.trim()
+        )));
     }
 
     Ok(())
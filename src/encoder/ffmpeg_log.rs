@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::process::Child;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+// Lines of ffmpeg's own output kept for error messages.
+const TAIL_LINES: usize = 20;
+
+// How far a running encode has got, from ffmpeg's `-progress` output.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EncodeProgress {
+    // Frames encoded so far.
+    pub frame: u64,
+    // Encoding rate in frames per second.
+    pub fps: f32,
+    // Seconds of output written.
+    pub out_time: f32,
+    // Relative to real time, e.g. 2.5 for 2.5x; `None` until ffmpeg knows.
+    pub speed: Option<f32>,
+}
+
+#[derive(Default)]
+struct LogState {
+    tail: VecDeque<String>,
+    warnings: Vec<String>,
+    progress: Option<EncodeProgress>,
+    // The `-progress` block being read; published at its `progress=` line.
+    pending: EncodeProgress,
+}
+
+impl LogState {
+    fn read_line(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        if let Some((key, value)) = line.split_once('=')
+            && self.read_progress(key, value.trim())
+        {
+            return;
+        }
+        if self.tail.len() == TAIL_LINES {
+            self.tail.pop_front();
+        }
+        self.tail.push_back(line.to_string());
+        // At `-loglevel warning` everything else is a warning or an error.
+        if !line.to_ascii_lowercase().contains("error") {
+            self.warnings.push(line.to_string());
+        }
+    }
+
+    // Whether `key` was one of the `-progress` fields.
+    fn read_progress(&mut self, key: &str, value: &str) -> bool {
+        match key {
+            "frame" => self.pending.frame = value.parse().unwrap_or(self.pending.frame),
+            "fps" => self.pending.fps = value.parse().unwrap_or(self.pending.fps),
+            "out_time_us" | "out_time_ms" => {
+                // Both are in microseconds.
+                if let Ok(micros) = value.parse::<i64>() {
+                    self.pending.out_time = micros.max(0) as f32 / 1_000_000.0;
+                }
+            }
+            "speed" => self.pending.speed = value.trim_end_matches('x').trim().parse().ok(),
+            "progress" => self.progress = Some(self.pending),
+            "bitrate" | "total_size" | "out_time" | "dup_frames" | "drop_frames" => {}
+            _ if key.starts_with("stream_") => {}
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Reads an ffmpeg process's stderr on its own thread, so a chatty encode
+// can't fill the pipe and stall, keeping progress, warnings and the last
+// lines for errors.
+pub(crate) struct StderrLog {
+    state: Arc<Mutex<LogState>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StderrLog {
+    // Takes `child`'s stderr, which must be piped.
+    pub(crate) fn capture(child: &mut Child) -> Self {
+        let state = Arc::new(Mutex::new(LogState::default()));
+        let thread = child.stderr.take().map(|stderr| {
+            let state = Arc::clone(&state);
+            thread::spawn(move || {
+                for line in BufReader::new(stderr).lines() {
+                    let Ok(line) = line else { break };
+                    if let Ok(mut state) = state.lock() {
+                        state.read_line(&line);
+                    }
+                }
+            })
+        });
+        Self { state, thread }
+    }
+
+    pub(crate) fn progress(&self) -> Option<EncodeProgress> {
+        self.state.lock().ok().and_then(|state| state.progress)
+    }
+
+    pub(crate) fn warnings(&self) -> Vec<String> {
+        self.state.lock().map(|state| state.warnings.clone()).unwrap_or_default()
+    }
+
+    // The last lines ffmpeg wrote, oldest first.
+    pub(crate) fn tail(&self) -> String {
+        self.state
+            .lock()
+            .map(|state| Vec::from(state.tail.clone()).join("\n"))
+            .unwrap_or_default()
+    }
+
+    // Waits for ffmpeg to close stderr, i.e. to exit, so nothing is missed.
+    pub(crate) fn join(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -1,99 +1,878 @@
-use std::io::{Read, Write};
-use std::path::Path;
-use std::process::{Child, ChildStdin, Command, Stdio};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
 
 use anyhow::{bail, Context, Result};
 
+use crate::encoder::ffmpeg_audio::mux_audio_track_with;
+use crate::encoder::ffmpeg_log::{EncodeProgress, StderrLog};
+use crate::encoder::frame_writer::FrameWriter;
+use crate::encoder::pcm::WavWriter;
+use crate::encoder::{AudioTrack, Dither, Encoder, OutputColor, PcmFormat, StreamOutput};
+use crate::error::ErrorKind;
+use crate::project::OutputMetadata;
+use crate::timeline::Chapter;
+
+// An ffmpeg video encoder (`-c:v`) with its pixel format and options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoCodec {
+    pub name: String,
+    pub pix_fmt: String,
+    pub args: Vec<String>,
+    // Before the input, e.g. the GPU to encode on.
+    pub device_args: Vec<String>,
+    // Appended to the filter chain, e.g. uploading frames to the GPU.
+    pub filters: Option<String>,
+}
+
+impl VideoCodec {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            pix_fmt: "yuv420p".to_string(),
+            args: Vec::new(),
+            device_args: Vec::new(),
+            filters: None,
+        }
+    }
+
+    // The default: software H.264 at CRF 18.
+    pub fn libx264() -> Self {
+        Self::new("libx264").with_args(["-crf", "18"])
+    }
+
+    // VP9 for a .webm output, at constant quality.
+    pub fn vp9() -> Self {
+        Self::new("libvpx-vp9").with_args(["-row-mt", "1"]).with_crf(31)
+    }
+
+    // VP9 keeping the alpha channel, for overlays on web pages. Render
+    // against a transparent background.
+    pub fn vp9_alpha() -> Self {
+        Self::vp9().with_pix_fmt("yuva420p")
+    }
+
+    // AV1 for a .webm output: smaller than VP9 at the same quality, but
+    // much slower to encode.
+    pub fn av1() -> Self {
+        Self::new("libaom-av1").with_args(["-cpu-used", "6", "-row-mt", "1"]).with_crf(34)
+    }
+
+    // The lossless presets below are for renders that get edited further,
+    // so they don't pick up generation loss. They encode RGB with alpha, so
+    // no YUV conversion either, and the files are many times larger.
+
+    // FFV1 in Matroska, the archival lossless codec. Every frame is a
+    // keyframe and each slice carries a checksum.
+    pub fn ffv1() -> Self {
+        Self::new("ffv1")
+            .with_pix_fmt("bgra")
+            .with_args(["-level", "3", "-g", "1", "-slices", "16", "-slicecrc", "1"])
+    }
+
+    // Ut Video in Matroska: fast to encode and decode, for editing.
+    pub fn utvideo() -> Self {
+        Self::new("utvideo").with_pix_fmt("gbrap").with_args(["-pred", "median"])
+    }
+
+    // QuickTime Animation in a .mov, for tools that only read QuickTime.
+    pub fn qtrle() -> Self {
+        Self::new("qtrle").with_pix_fmt("argb")
+    }
+
+    // ProRes 4444 in a .mov: near-lossless with alpha, for overlays that go
+    // into editing software.
+    pub fn prores_4444() -> Self {
+        Self::new("prores_ks").with_pix_fmt("yuva444p10le").with_args(["-profile:v", "4444"])
+    }
+
+    // H.264 on an NVIDIA GPU.
+    pub fn nvenc() -> Self {
+        Self::new("h264_nvenc").with_args(["-preset", "p5", "-rc", "vbr"]).with_crf(19)
+    }
+
+    // H.264 on Apple's media engine (macOS).
+    pub fn videotoolbox() -> Self {
+        Self::new("h264_videotoolbox").with_crf(18)
+    }
+
+    // H.264 through VA-API (Intel and AMD GPUs on Linux). Frames are
+    // uploaded to the GPU as NV12 after the colour conversion.
+    pub fn vaapi() -> Self {
+        let mut codec = Self::new("h264_vaapi").with_pix_fmt("vaapi").with_crf(20);
+        codec.device_args = ["-vaapi_device", "/dev/dri/renderD128"].map(String::from).to_vec();
+        codec.filters = Some("format=nv12,hwupload".to_string());
+        codec
+    }
+
+    // The hardware encoders this platform may have, fastest first, then
+    // `libx264`. Passed to `FfmpegVideoEncoder::start_with_codecs`, the
+    // first one that opens is used.
+    pub fn hardware_or_libx264() -> Vec<Self> {
+        let mut codecs = Vec::new();
+        if cfg!(target_os = "macos") {
+            codecs.push(Self::videotoolbox());
+        } else {
+            codecs.push(Self::nvenc());
+            if cfg!(target_os = "linux") {
+                codecs.push(Self::vaapi());
+            }
+        }
+        codecs.push(Self::libx264());
+        codecs
+    }
+
+    // `h264`, `vp9`, `vp9-alpha`, `av1`, `ffv1`, `utvideo`, `qtrle`,
+    // `prores-4444`, `nvenc`, `videotoolbox` or `vaapi` for the presets
+    // above; anything else is used as an ffmpeg encoder name with default
+    // options.
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "h264" | "libx264" => Self::libx264(),
+            "vp9" => Self::vp9(),
+            "vp9-alpha" => Self::vp9_alpha(),
+            "av1" => Self::av1(),
+            "ffv1" => Self::ffv1(),
+            "utvideo" => Self::utvideo(),
+            "qtrle" => Self::qtrle(),
+            "prores-4444" => Self::prores_4444(),
+            "nvenc" => Self::nvenc(),
+            "videotoolbox" => Self::videotoolbox(),
+            "vaapi" => Self::vaapi(),
+            _ => Self::new(name),
+        }
+    }
+
+    // The output extension this codec is meant for.
+    pub fn extension(&self) -> &'static str {
+        match self.name.as_str() {
+            "ffv1" | "utvideo" => "mkv",
+            "qtrle" | "prores_ks" => "mov",
+            _ if self.is_webm() => "webm",
+            _ => "mp4",
+        }
+    }
+
+    // Whether the pixel format keeps an alpha channel, so transparent frames
+    // stay transparent.
+    pub fn has_alpha(&self) -> bool {
+        ["yuva", "rgba", "bgra", "argb", "abgr", "gbrap", "ya"]
+            .iter()
+            .any(|prefix| self.pix_fmt.starts_with(prefix))
+    }
+
+    fn is_webm(&self) -> bool {
+        matches!(self.name.as_str(), "libvpx" | "libvpx-vp9" | "libaom-av1" | "libsvtav1")
+    }
+
+    // Constant quality; lower is better and larger. Replaces any bitrate.
+    // Hardware encoders get their own constant-quality option on a
+    // similar scale.
+    pub fn with_crf(mut self, crf: u32) -> Self {
+        self.clear_rate();
+        let (flag, value) = if self.name.ends_with("_nvenc") {
+            ("-cq", crf)
+        } else if self.name.ends_with("_vaapi") {
+            ("-qp", crf)
+        } else if self.name.ends_with("_videotoolbox") {
+            // 1 to 100, higher is better.
+            ("-q:v", 100u32.saturating_sub(crf * 2).max(1))
+        } else {
+            ("-crf", crf)
+        };
+        self.args.extend([flag.to_string(), value.to_string()]);
+        // These otherwise cap the bitrate at their default.
+        if self.is_webm() || self.name.ends_with("_nvenc") {
+            self.args.extend(["-b:v".to_string(), "0".to_string()]);
+        }
+        self
+    }
+
+    // Average bitrate in kbit/s, for size limits. Replaces any CRF.
+    pub fn with_bitrate(mut self, kbps: u32) -> Self {
+        self.clear_rate();
+        self.args.extend(["-b:v".to_string(), format!("{kbps}k")]);
+        self
+    }
+
+    // Sets an encoder option such as `-preset`, replacing its value if the
+    // codec already has one.
+    pub fn with_option(mut self, flag: &str, value: impl Into<String>) -> Self {
+        let value = value.into();
+        match self.args.iter().position(|arg| arg == flag) {
+            Some(index) if index + 1 < self.args.len() => self.args[index + 1] = value,
+            _ => self.args.extend([flag.to_string(), value]),
+        }
+        self
+    }
+
+    fn clear_rate(&mut self) {
+        let mut args = std::mem::take(&mut self.args).into_iter();
+        while let Some(arg) = args.next() {
+            // Each flag's value goes with it.
+            if matches!(arg.as_str(), "-crf" | "-cq" | "-qp" | "-q:v" | "-b:v") {
+                args.next();
+            } else {
+                self.args.push(arg);
+            }
+        }
+    }
+
+    pub fn with_pix_fmt(mut self, pix_fmt: impl Into<String>) -> Self {
+        self.pix_fmt = pix_fmt.into();
+        self
+    }
+
+    pub fn with_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    // Encodes one blank frame at this size to see whether the encoder opens
+    // at all (a missing GPU, a build without the codec, a rejected pix_fmt).
+    fn probe(&self, ffmpeg: &Path, width: u32, height: u32, fps: u32) -> Result<()> {
+        let mut cmd = Command::new(ffmpeg);
+        cmd.arg("-loglevel")
+            .arg("error")
+            .args(&self.device_args)
+            .arg("-f")
+            .arg("lavfi")
+            .arg("-i")
+            .arg(format!("color=c=black:s={width}x{height}:r={fps}"));
+        if let Some(filters) = &self.filters {
+            cmd.arg("-vf").arg(filters);
+        }
+        let output = cmd
+            .arg("-frames:v")
+            .arg("1")
+            .arg("-c:v")
+            .arg(&self.name)
+            .arg("-pix_fmt")
+            .arg(&self.pix_fmt)
+            .args(&self.args)
+            .arg("-f")
+            .arg("null")
+            .arg("-")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .with_context(|| {
+                ErrorKind::Encoder.error(format!(
+                    "failed to spawn {} (is it on PATH?)",
+                    ffmpeg.display()
+                ))
+            })?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr.lines().next().unwrap_or("no output").trim();
+            bail!(ErrorKind::Encoder.error(format!("{}: {reason}", self.name)));
+        }
+        Ok(())
+    }
+}
+
+// Everything about an encode besides its size and frame rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoOptions {
+    // Written as MP4 chapter metadata (see `Timeline::chapters`).
+    pub chapters: Vec<Chapter>,
+    // Fallback chain; see `FfmpegVideoEncoder::start_with_codecs`.
+    pub codecs: Vec<VideoCodec>,
+    pub color: OutputColor,
+    // Container tags, e.g. `("title", "Intro")`.
+    pub metadata: Vec<(String, String)>,
+    // Muxed in once the video is finished.
+    pub audio: Option<AudioTrack>,
+    // Audio given to `FfmpegVideoEncoder::write_audio` instead of a file.
+    pub pcm: Option<PcmFormat>,
+    // The ffmpeg binary, for a build other than the one on PATH.
+    pub ffmpeg: PathBuf,
+    // Before the frame input, e.g. `-thread_queue_size 512`.
+    pub input_args: Vec<String>,
+    // Before the output path, e.g. `-movflags +faststart` or `-vf` filters.
+    // With audio these go to the final mux, which copies the video stream,
+    // so video filters belong in `VideoCodec::filters` instead.
+    pub output_args: Vec<String>,
+    // Converts frames to YUV 4:2:0 before piping them, sending 3/8 of the
+    // bytes and sparing ffmpeg the conversion; worth it at 4K. Only applies
+    // to yuv420p codecs without `filters` at even sizes, otherwise RGBA is
+    // piped as usual.
+    pub cpu_yuv: bool,
+    // Frames are transparent (rendered against a background with alpha 0)
+    // and the output keeps them so, e.g. for overlays. Codecs whose pixel
+    // format has no alpha are left out of the fallback chain rather than
+    // silently dropping it.
+    pub alpha: bool,
+}
+
+impl Default for VideoOptions {
+    fn default() -> Self {
+        Self {
+            chapters: Vec::new(),
+            codecs: vec![VideoCodec::libx264()],
+            color: OutputColor::default(),
+            metadata: Vec::new(),
+            audio: None,
+            pcm: None,
+            ffmpeg: PathBuf::from("ffmpeg"),
+            input_args: Vec::new(),
+            output_args: Vec::new(),
+            cpu_yuv: false,
+            alpha: false,
+        }
+    }
+}
+
+impl VideoOptions {
+    pub fn with_chapters(mut self, chapters: Vec<Chapter>) -> Self {
+        self.chapters = chapters;
+        self
+    }
+
+    pub fn with_codecs(mut self, codecs: Vec<VideoCodec>) -> Self {
+        self.codecs = codecs;
+        self
+    }
+
+    pub fn with_color(mut self, color: OutputColor) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.push((key.into(), value.into()));
+        self
+    }
+
+    // Adds the title, author, comment and creation time that are set. ffmpeg
+    // keeps the last value given for a key.
+    pub fn with_output_metadata(mut self, metadata: &OutputMetadata) -> Self {
+        self.metadata.extend(metadata.tags());
+        self
+    }
+
+    pub fn with_audio(mut self, audio: AudioTrack) -> Self {
+        self.audio = Some(audio);
+        self
+    }
+
+    pub fn with_pcm_audio(mut self, format: PcmFormat) -> Self {
+        self.pcm = Some(format);
+        self
+    }
+
+    pub fn with_ffmpeg(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ffmpeg = path.into();
+        self
+    }
+
+    pub fn with_input_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.input_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_output_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.output_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_cpu_yuv(mut self) -> Self {
+        self.cpu_yuv = true;
+        self
+    }
+
+    pub fn with_alpha(mut self) -> Self {
+        self.alpha = true;
+        self
+    }
+}
+
 pub struct FfmpegVideoEncoder {
     child: Child,
-    stdin: Option<ChildStdin>,
+    // Converts and pipes frames on its own thread; taken when the pipe closes.
+    writer: Option<FrameWriter>,
     width: u32,
     height: u32,
+    // ffmetadata file feeding chapters to ffmpeg.
+    metadata: Option<TempFile>,
+    codec: String,
+    fallbacks: Vec<String>,
+    dither: Dither,
+    fps: u32,
+    frames: u32,
+    // The track and final output path when the video goes to a temp file
+    // first; the fade-out needs the video's length.
+    audio: Option<(AudioTrack, PathBuf)>,
+    // Samples from `write_audio`, going to the track's file.
+    pcm: Option<WavWriter>,
+    // The video-only file and generated audio, removed however the encode
+    // ends.
+    temp_files: Vec<TempFile>,
+    ffmpeg: PathBuf,
+    // For the audio mux; otherwise already given to the encode.
+    output_args: Vec<String>,
+    log: StderrLog,
 }
 
 impl FfmpegVideoEncoder {
     pub fn start(width: u32, height: u32, fps: u32, output_path: &Path) -> Result<Self> {
-        let mut cmd = Command::new("ffmpeg");
+        Self::start_with_chapters(width, height, fps, output_path, &[])
+    }
+
+    // Chapters are written as MP4 chapter metadata (see `Timeline::chapters`).
+    pub fn start_with_chapters(
+        width: u32,
+        height: u32,
+        fps: u32,
+        output_path: &Path,
+        chapters: &[Chapter],
+    ) -> Result<Self> {
+        Self::start_with_codecs(
+            width,
+            height,
+            fps,
+            output_path,
+            chapters,
+            &[VideoCodec::libx264()],
+        )
+    }
+
+    // Uses the first codec in `codecs` that works here, e.g. `h264_nvenc`
+    // then `libx264`. Each one but the last is tried on a blank frame first;
+    // why skipped codecs failed is kept in `fallbacks`.
+    pub fn start_with_codecs(
+        width: u32,
+        height: u32,
+        fps: u32,
+        output_path: &Path,
+        chapters: &[Chapter],
+        codecs: &[VideoCodec],
+    ) -> Result<Self> {
+        Self::start_with_color(
+            width,
+            height,
+            fps,
+            output_path,
+            chapters,
+            codecs,
+            OutputColor::default(),
+        )
+    }
+
+    // Encodes in and tags the output with `color`.
+    pub fn start_with_color(
+        width: u32,
+        height: u32,
+        fps: u32,
+        output_path: &Path,
+        chapters: &[Chapter],
+        codecs: &[VideoCodec],
+        color: OutputColor,
+    ) -> Result<Self> {
+        let options = VideoOptions::default()
+            .with_chapters(chapters.to_vec())
+            .with_codecs(codecs.to_vec())
+            .with_color(color);
+        Self::start_with_options(width, height, fps, output_path, &options)
+    }
+
+    pub fn start_with_options(
+        width: u32,
+        height: u32,
+        fps: u32,
+        output_path: &Path,
+        options: &VideoOptions,
+    ) -> Result<Self> {
+        Self::start_to(width, height, fps, output_path, None, options)
+    }
+
+    // Streams to `stream` instead of writing a file. Audio and chapters
+    // need a file, so they must be unset in `options`.
+    pub fn start_stream(
+        width: u32,
+        height: u32,
+        fps: u32,
+        stream: &StreamOutput,
+        options: &VideoOptions,
+    ) -> Result<Self> {
+        if options.audio.is_some() || options.pcm.is_some() || !options.chapters.is_empty() {
+            bail!(ErrorKind::InvalidProject.error("streams can't have audio or chapters yet"));
+        }
+        let target = Path::new(stream.target());
+        Self::start_to(width, height, fps, target, Some(stream), options)
+    }
+
+    fn start_to(
+        width: u32,
+        height: u32,
+        fps: u32,
+        output_path: &Path,
+        stream: Option<&StreamOutput>,
+        options: &VideoOptions,
+    ) -> Result<Self> {
+        let (chapters, color) = (&options.chapters, options.color);
+        if let Some(track) = &options.audio
+            && !track.path.exists()
+        {
+            bail!(ErrorKind::MissingAsset.error(format!(
+                "audio file not found: {}",
+                track.path.display()
+            )));
+        }
+        if options.audio.is_some() && options.pcm.is_some() {
+            bail!(ErrorKind::InvalidProject.error("set an audio file or pcm audio, not both"));
+        }
+        let final_path = output_path.to_path_buf();
+        let temp_path = video_only_path(output_path);
+        let output_path = if options.audio.is_some() || options.pcm.is_some() {
+            temp_path.as_path()
+        } else {
+            output_path
+        };
+        let mut fallbacks = Vec::new();
+        let mut codecs = Vec::new();
+        for codec in &options.codecs {
+            if options.alpha && !codec.has_alpha() {
+                fallbacks.push(format!(
+                    "video codec skipped, {} has no alpha: {}",
+                    codec.pix_fmt, codec.name
+                ));
+            } else {
+                codecs.push(codec);
+            }
+        }
+        let Some((last, candidates)) = codecs.split_last() else {
+            if options.alpha {
+                bail!(ErrorKind::InvalidProject.error(
+                    "transparent output needs a codec with alpha, e.g. vp9-alpha, prores-4444, \
+                     ffv1, utvideo or qtrle"
+                ));
+            }
+            bail!(ErrorKind::Encoder.error("no video codecs to try"));
+        };
+        let codec = candidates
+            .iter()
+            .find(|codec| match codec.probe(&options.ffmpeg, width, height, fps) {
+                Ok(()) => true,
+                Err(err) => {
+                    fallbacks.push(format!("video codec unavailable, skipped: {err:#}"));
+                    false
+                }
+            })
+            .unwrap_or(last);
+
+        let metadata = if chapters.is_empty() {
+            None
+        } else {
+            let path = output_path.with_extension("chapters.txt");
+            fs::write(&path, ffmetadata(chapters)).with_context(|| {
+                ErrorKind::Encoder.error(format!(
+                    "failed to write chapter metadata: {}",
+                    path.display()
+                ))
+            })?;
+            Some(TempFile(path))
+        };
+
+        // Created before ffmpeg starts, so a failure here leaves no process
+        // behind.
+        let mut temp_files = Vec::new();
+        if options.audio.is_some() || options.pcm.is_some() {
+            temp_files.push(TempFile(temp_path.clone()));
+        }
+        let (audio, pcm) = match (&options.audio, options.pcm) {
+            (Some(track), _) => (Some((track.clone(), final_path)), None),
+            // Collected next to the output and muxed in like an audio file.
+            (None, Some(format)) => {
+                let wav_path = final_path.with_extension("audio.wav");
+                let writer = WavWriter::create(&wav_path, format)?;
+                temp_files.push(TempFile(wav_path.clone()));
+                (Some((AudioTrack::new(wav_path), final_path)), Some(writer))
+            }
+            (None, None) => (None, None),
+        };
+
+        let cpu_yuv = options.cpu_yuv
+            && codec.pix_fmt == "yuv420p"
+            && codec.filters.is_none()
+            && width.is_multiple_of(2)
+            && height.is_multiple_of(2);
+
+        let mut cmd = Command::new(&options.ffmpeg);
         cmd.arg("-y")
             .arg("-loglevel")
-            .arg("error")
-            .arg("-f")
+            .arg("warning")
+            .arg("-nostats")
+            .arg("-progress")
+            .arg("pipe:2")
+            .args(&codec.device_args)
+            .args(&options.input_args);
+        if stream.is_some_and(StreamOutput::realtime) {
+            cmd.arg("-re");
+        }
+        cmd.arg("-f")
             .arg("rawvideo")
             .arg("-pix_fmt")
-            .arg("rgba")
+            .arg(if cpu_yuv { "yuv420p" } else { "rgba" })
             .arg("-s")
             .arg(format!("{}x{}", width, height))
             .arg("-r")
             .arg(fps.to_string())
             .arg("-i")
-            .arg("-")
-            .arg("-vf")
-            .arg("vflip")
-            .arg("-c:v")
-            .arg("libx264")
+            .arg("-");
+        if let Some(TempFile(path)) = &metadata {
+            cmd.arg("-i")
+                .arg(path)
+                .arg("-map")
+                .arg("0:v")
+                .arg("-map_metadata")
+                .arg("1")
+                .arg("-map_chapters")
+                .arg("1");
+        }
+        let (filters, tags) = color.ffmpeg_args(&codec.pix_fmt);
+        // Frames are flipped before piping; YUV ones are converted already.
+        let mut vf = Vec::new();
+        if !cpu_yuv {
+            vf.extend(filters.strip_prefix(','));
+        }
+        vf.extend(codec.filters.as_deref());
+        if !vf.is_empty() {
+            cmd.arg("-vf").arg(vf.join(","));
+        }
+        cmd.arg("-c:v")
+            .arg(&codec.name)
             .arg("-pix_fmt")
-            .arg("yuv420p")
-            .arg("-crf")
-            .arg("18")
-            .arg(output_path)
+            .arg(&codec.pix_fmt)
+            .args(&codec.args)
+            .args(tags);
+        for (key, value) in &options.metadata {
+            cmd.arg("-metadata").arg(format!("{key}={value}"));
+        }
+        if options.audio.is_none() && options.pcm.is_none() {
+            cmd.args(&options.output_args);
+        }
+        if let Some(stream) = stream {
+            cmd.args(stream.ffmpeg_args()?);
+        }
+        cmd.arg(output_path)
             .stdin(Stdio::piped())
             .stdout(Stdio::null())
             .stderr(Stdio::piped());
 
-        let mut child = cmd
-            .spawn()
-            .context("failed to spawn ffmpeg (is it on PATH?)")?;
+        let mut child = cmd.spawn().with_context(|| {
+            ErrorKind::Encoder.error(format!(
+                "failed to spawn {} (is it on PATH?)",
+                options.ffmpeg.display()
+            ))
+        })?;
         let stdin = child
             .stdin
             .take()
-            .context("failed to open ffmpeg stdin")?;
+            .context(ErrorKind::Encoder.error("failed to open ffmpeg stdin"))?;
+        let log = StderrLog::capture(&mut child);
 
         Ok(Self {
             child,
-            stdin: Some(stdin),
+            writer: Some(FrameWriter::spawn(stdin, width, height, color, cpu_yuv)),
             width,
             height,
+            metadata,
+            codec: codec.name.clone(),
+            fallbacks,
+            dither: Dither::None,
+            fps,
+            frames: 0,
+            audio,
+            pcm,
+            temp_files,
+            ffmpeg: options.ffmpeg.clone(),
+            output_args: options.output_args.clone(),
+            log,
         })
     }
 
+    // Dithers frames from here on, against banding in gradients.
+    pub fn set_dither(&mut self, dither: Dither) {
+        self.dither = dither;
+    }
+
+    // The codec actually encoding, after any fallback.
+    pub fn codec(&self) -> &str {
+        &self.codec
+    }
+
+    pub fn fallbacks(&self) -> &[String] {
+        &self.fallbacks
+    }
+
+    // ffmpeg's latest progress report, about twice a second.
+    pub fn progress(&self) -> Option<EncodeProgress> {
+        self.log.progress()
+    }
+
+    // Warnings ffmpeg has printed so far, e.g. about deprecated options.
+    pub fn warnings(&self) -> Vec<String> {
+        self.log.warnings()
+    }
+
     pub fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
         let expected = (self.width * self.height * 4) as usize;
         if frame.len() != expected {
-            bail!(
+            bail!(ErrorKind::InvalidProject.error(format!(
                 "frame size mismatch: got {}, expected {}",
                 frame.len(),
                 expected
-            );
+            )));
         }
 
-        let stdin = self
-            .stdin
+        let writer = self
+            .writer
             .as_mut()
-            .context("ffmpeg stdin already closed")?;
-        stdin.write_all(frame).context("failed to write frame")?;
+            .context(ErrorKind::Encoder.error("ffmpeg stdin already closed"))?;
+        if let Err(err) = writer.write(frame, self.dither) {
+            // Usually ffmpeg has exited; its output says why.
+            self.writer = None;
+            let _ = self.child.wait();
+            self.log.join();
+            bail!(ErrorKind::Encoder.error(format!(
+                "failed to write frame ({err}); ffmpeg output:\n{}",
+                self.log.tail()
+            )));
+        }
+        self.frames += 1;
         Ok(())
     }
 
+    // Interleaved samples in -1..1 for a render started with
+    // `VideoOptions::with_pcm_audio`. They play from the first frame on, so
+    // writing `PcmFormat::samples_for_frame` samples per frame keeps them in
+    // sync; anything past the last frame is cut.
+    pub fn write_audio(&mut self, samples: &[f32]) -> Result<()> {
+        let Some(pcm) = &mut self.pcm else {
+            bail!(ErrorKind::InvalidProject.error("encoder was started without pcm audio"));
+        };
+        pcm.write(samples)
+    }
+
     pub fn finish(mut self) -> Result<()> {
-        if let Some(mut stdin) = self.stdin.take() {
-            let _ = stdin.flush();
-        }
+        let written = self.writer.take().map_or(Ok(()), |mut writer| writer.finish());
 
-        let status = self.child.wait().context("failed to wait for ffmpeg")?;
+        let status = self
+            .child
+            .wait()
+            .context(ErrorKind::Encoder.error("failed to wait for ffmpeg"))?;
+        self.metadata = None;
+        self.log.join();
         if !status.success() {
-            let stderr = self
-                .child
-                .stderr
-                .take()
-                .map(|mut s| {
-                    let mut buf = String::new();
-                    let _ = s.read_to_string(&mut buf);
-                    buf
-                })
-                .unwrap_or_default();
-            bail!("ffmpeg exited with status {}: {}", status, stderr.trim());
+            bail!(ErrorKind::Encoder.error(format!(
+                "ffmpeg exited with status {status}; last output:\n{}",
+                self.log.tail()
+            )));
+        }
+        if let Err(err) = written {
+            bail!(ErrorKind::Encoder.error(format!(
+                "failed to write frames ({err}); ffmpeg output:\n{}",
+                self.log.tail()
+            )));
         }
 
+        self.pcm.take().map(WavWriter::finish).transpose()?;
+        if let Some((track, output_path)) = &self.audio {
+            let duration = self.frames as f32 / self.fps as f32;
+            mux_audio_track_with(
+                &self.ffmpeg,
+                &video_only_path(output_path),
+                track,
+                duration,
+                output_path,
+                &self.output_args,
+            )?;
+        }
+        self.temp_files.clear();
         Ok(())
     }
 }
+
+impl Encoder for FfmpegVideoEncoder {
+    fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        FfmpegVideoEncoder::write_frame(self, frame)
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        FfmpegVideoEncoder::finish(*self)
+    }
+
+    fn name(&self) -> String {
+        self.codec.clone()
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        let mut warnings = self.fallbacks.clone();
+        warnings.extend(FfmpegVideoEncoder::warnings(self));
+        warnings
+    }
+
+    fn progress(&self) -> Option<EncodeProgress> {
+        FfmpegVideoEncoder::progress(self)
+    }
+
+    fn set_dither(&mut self, dither: Dither) {
+        FfmpegVideoEncoder::set_dither(self, dither);
+    }
+}
+
+// `out.mp4` -> `out.video.mp4`, keeping the container.
+fn video_only_path(output_path: &Path) -> PathBuf {
+    match output_path.extension() {
+        Some(ext) => output_path.with_extension(format!("video.{}", ext.to_string_lossy())),
+        None => output_path.with_extension("video"),
+    }
+}
+
+// A file ffmpeg reads or writes next to the output, removed once dropped, so
+// a failed start, a failed write or a cancelled render doesn't leave it
+// behind.
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn ffmetadata(chapters: &[Chapter]) -> String {
+    let mut out = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        out.push_str("[CHAPTER]\nTIMEBASE=1/1000\n");
+        out.push_str(&format!("START={}\n", (chapter.start * 1000.0).round() as u64));
+        out.push_str(&format!("END={}\n", (chapter.end * 1000.0).round() as u64));
+        out.push_str(&format!("title={}\n", escape_metadata(&chapter.title)));
+    }
+    out
+}
+
+// ffmetadata treats these as syntax; they must be backslash-escaped.
+fn escape_metadata(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(ch, '=' | ';' | '#' | '\\' | '\n') {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
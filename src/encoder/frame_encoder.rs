@@ -0,0 +1,31 @@
+use anyhow::Result;
+
+use crate::encoder::{Dither, EncodeProgress};
+
+// Somewhere rendered frames go, so a render loop can write a video, an image
+// sequence or an animated image without knowing which; new output formats
+// implement this. Frames are bottom-up straight RGBA at the size the encoder
+// was started with.
+pub trait Encoder {
+    fn write_frame(&mut self, frame: &[u8]) -> Result<()>;
+
+    // Flushes and closes the output. Boxed so a `Box<dyn Encoder>` can finish.
+    fn finish(self: Box<Self>) -> Result<()>;
+
+    // What the frames are encoded as, e.g. the video codec, for reports.
+    fn name(&self) -> String;
+
+    // Anything a report should mention, like codec fallbacks or warnings
+    // from ffmpeg.
+    fn warnings(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn progress(&self) -> Option<EncodeProgress> {
+        None
+    }
+
+    // Dithers frames from here on. Formats without banding to hide, or with
+    // their own dithering, ignore it.
+    fn set_dither(&mut self, _dither: Dither) {}
+}
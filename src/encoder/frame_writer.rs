@@ -0,0 +1,89 @@
+use std::io::{self, Write};
+use std::process::ChildStdin;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use crate::encoder::yuv;
+use crate::encoder::{Dither, OutputColor};
+
+// Frames queued behind the one being written. Enough for the renderer to
+// run ahead of ffmpeg without holding many 4K frames in memory.
+const QUEUE_FRAMES: usize = 2;
+
+struct QueuedFrame {
+    rgba: Vec<u8>,
+    dither: Dither,
+}
+
+// Converts and pipes frames to ffmpeg on its own thread, so rendering frame
+// N+1 overlaps with writing and encoding frame N instead of waiting on it.
+pub(crate) struct FrameWriter {
+    queue: Option<SyncSender<QueuedFrame>>,
+    // Buffers handed back once written, so frames aren't reallocated.
+    spare: Receiver<Vec<u8>>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl FrameWriter {
+    // Frames are converted to `color`, dithered and flipped, then converted
+    // to YUV 4:2:0 with `cpu_yuv`, before they're written.
+    pub(crate) fn spawn(
+        mut stdin: ChildStdin,
+        width: u32,
+        height: u32,
+        color: OutputColor,
+        cpu_yuv: bool,
+    ) -> Self {
+        let (queue, frames) = mpsc::sync_channel::<QueuedFrame>(QUEUE_FRAMES);
+        let (recycle, spare) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut piped = Vec::new();
+            for QueuedFrame { mut rgba, dither } in frames {
+                color.convert(&mut rgba);
+                dither.apply(&mut rgba, width);
+                if cpu_yuv {
+                    yuv::rgba_to_yuv420p(&rgba, width, height, &mut piped);
+                } else {
+                    yuv::flip_rgba(&rgba, width, &mut piped);
+                }
+                stdin.write_all(&piped)?;
+                let _ = recycle.send(rgba);
+            }
+            stdin.flush()
+        });
+        Self {
+            queue: Some(queue),
+            spare,
+            thread: Some(thread),
+        }
+    }
+
+    // Queues a copy of `frame`, waiting while the queue is full. A failed
+    // write shows up here on a later frame, or from `finish`.
+    pub(crate) fn write(&mut self, frame: &[u8], dither: Dither) -> io::Result<()> {
+        let mut rgba = self.spare.try_recv().unwrap_or_default();
+        rgba.clear();
+        rgba.extend_from_slice(frame);
+        let sent = self
+            .queue
+            .as_ref()
+            .is_some_and(|queue| queue.send(QueuedFrame { rgba, dither }).is_ok());
+        if sent {
+            return Ok(());
+        }
+        // The thread only stops early when a write fails.
+        self.finish()?;
+        Err(io::Error::other("frame writer stopped"))
+    }
+
+    // Writes whatever is queued, then closes ffmpeg's stdin.
+    pub(crate) fn finish(&mut self) -> io::Result<()> {
+        self.queue = None;
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("frame writer panicked"))),
+            None => Ok(()),
+        }
+    }
+}
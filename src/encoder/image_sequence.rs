@@ -0,0 +1,307 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+
+use crate::backend::premultiply::srgb_to_linear;
+use crate::encoder::ffmpeg_log::StderrLog;
+use crate::encoder::{Encoder, OutputColor};
+use crate::error::ErrorKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageSequenceFormat {
+    // 8-bit RGBA, straight alpha.
+    Png,
+    // 32-bit float RGBA, ZIP compressed. Linear light with premultiplied
+    // alpha, as compositing tools expect of EXRs.
+    Exr,
+}
+
+impl ImageSequenceFormat {
+    // `png` or `exr`, e.g. from a command line or config file.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(ImageSequenceFormat::Png),
+            "exr" => Some(ImageSequenceFormat::Exr),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageSequenceFormat::Png => "png",
+            ImageSequenceFormat::Exr => "exr",
+        }
+    }
+
+    // What frames are piped to ffmpeg as.
+    fn input_pix_fmt(self) -> &'static str {
+        match self {
+            ImageSequenceFormat::Png => "rgba",
+            ImageSequenceFormat::Exr => "rgbaf32le",
+        }
+    }
+
+    fn pix_fmt(self) -> &'static str {
+        match self {
+            ImageSequenceFormat::Png => "rgba",
+            ImageSequenceFormat::Exr => "gbrapf32le",
+        }
+    }
+
+    // Codec args after the pixel format.
+    fn codec_args(self) -> &'static [&'static str] {
+        match self {
+            ImageSequenceFormat::Png => &["-c:v", "png"],
+            ImageSequenceFormat::Exr => {
+                &["-c:v", "exr", "-format", "float", "-compression", "zip16"]
+            }
+        }
+    }
+}
+
+// Writes each frame as a numbered image (`frame_000000.png`, ...) in a
+// directory instead of encoding a video, for compositing tools that read
+// image sequences. Takes the same bottom-up RGBA frames as
+// `FfmpegVideoEncoder`, so the two can be swapped in a render loop.
+pub struct ImageSequenceEncoder {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    log: StderrLog,
+    width: u32,
+    height: u32,
+    dir: PathBuf,
+    format: ImageSequenceFormat,
+    first_frame: u32,
+    color: OutputColor,
+    // Frames converted to `color`, reused between frames.
+    converted: Vec<u8>,
+    // sRGB byte to linear light, and frames as linear floats, for EXRs.
+    decode: Vec<f32>,
+    linear: Vec<u8>,
+}
+
+impl ImageSequenceEncoder {
+    pub fn start(
+        width: u32,
+        height: u32,
+        fps: u32,
+        dir: &Path,
+        format: ImageSequenceFormat,
+    ) -> Result<Self> {
+        Self::start_numbered(width, height, fps, dir, format, 0)
+    }
+
+    // Numbers files from `first_frame`, e.g. the timeline frame a partial
+    // render starts at, so ranges rendered separately line up. Files from an
+    // earlier render with the same numbers are overwritten; others are left.
+    pub fn start_numbered(
+        width: u32,
+        height: u32,
+        fps: u32,
+        dir: &Path,
+        format: ImageSequenceFormat,
+        first_frame: u32,
+    ) -> Result<Self> {
+        Self::start_with_color(
+            width,
+            height,
+            fps,
+            dir,
+            format,
+            first_frame,
+            OutputColor::default(),
+        )
+    }
+
+    // Converts frames to `color` and tags the files with it (PNG colour
+    // chunks; EXRs hold the converted values).
+    pub fn start_with_color(
+        width: u32,
+        height: u32,
+        fps: u32,
+        dir: &Path,
+        format: ImageSequenceFormat,
+        first_frame: u32,
+        color: OutputColor,
+    ) -> Result<Self> {
+        Self::start_with_ffmpeg(
+            width,
+            height,
+            fps,
+            dir,
+            format,
+            first_frame,
+            color,
+            Path::new("ffmpeg"),
+        )
+    }
+
+    // Runs `ffmpeg`, for a build other than the one on PATH.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_with_ffmpeg(
+        width: u32,
+        height: u32,
+        fps: u32,
+        dir: &Path,
+        format: ImageSequenceFormat,
+        first_frame: u32,
+        color: OutputColor,
+        ffmpeg: &Path,
+    ) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| {
+            ErrorKind::Encoder.error(format!("failed to create {}", dir.display()))
+        })?;
+        let pattern = dir.join(format!("frame_%06d.{}", format.extension()));
+        let (filters, mut tags) = color.ffmpeg_args(format.pix_fmt());
+        if format == ImageSequenceFormat::Exr
+            && let Some(at) = tags.iter().position(|arg| *arg == "-color_trc")
+        {
+            tags[at + 1] = "linear";
+        }
+
+        let mut child = Command::new(ffmpeg)
+            .arg("-y")
+            .arg("-loglevel")
+            .arg("error")
+            .arg("-f")
+            .arg("rawvideo")
+            .arg("-pix_fmt")
+            .arg(format.input_pix_fmt())
+            .arg("-s")
+            .arg(format!("{}x{}", width, height))
+            .arg("-r")
+            .arg(fps.to_string())
+            .arg("-i")
+            .arg("-")
+            .arg("-vf")
+            .arg(format!("vflip{filters},format={}", format.pix_fmt()))
+            .arg("-pix_fmt")
+            .arg(format.pix_fmt())
+            .args(format.codec_args())
+            .args(tags)
+            .arg("-start_number")
+            .arg(first_frame.to_string())
+            .arg("-f")
+            .arg("image2")
+            .arg(&pattern)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| {
+                ErrorKind::Encoder.error(format!(
+                    "failed to spawn {} (is it on PATH?)",
+                    ffmpeg.display()
+                ))
+            })?;
+        let stdin = child
+            .stdin
+            .take()
+            .context(ErrorKind::Encoder.error("failed to open ffmpeg stdin"))?;
+        let log = StderrLog::capture(&mut child);
+        let decode = match format {
+            ImageSequenceFormat::Png => Vec::new(),
+            ImageSequenceFormat::Exr => (0..=255)
+                .map(|value| srgb_to_linear(value as f32 / 255.0))
+                .collect(),
+        };
+
+        Ok(Self {
+            child,
+            stdin: Some(stdin),
+            log,
+            width,
+            height,
+            dir: dir.to_path_buf(),
+            format,
+            first_frame,
+            color,
+            converted: Vec::new(),
+            decode,
+            linear: Vec::new(),
+        })
+    }
+
+    // Where frame `frame` (counted like `first_frame`) is written.
+    pub fn frame_path(&self, frame: u32) -> PathBuf {
+        self.dir.join(format!("frame_{frame:06}.{}", self.format.extension()))
+    }
+
+    pub fn first_frame(&self) -> u32 {
+        self.first_frame
+    }
+
+    pub fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        let expected = (self.width * self.height * 4) as usize;
+        if frame.len() != expected {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "frame size mismatch: got {}, expected {}",
+                frame.len(),
+                expected
+            )));
+        }
+
+        let mut frame = frame;
+        if self.color != OutputColor::Srgb {
+            self.converted.clear();
+            self.converted.extend_from_slice(frame);
+            self.color.convert(&mut self.converted);
+            frame = &self.converted;
+        }
+        if self.format == ImageSequenceFormat::Exr {
+            self.linear.clear();
+            for pixel in frame.chunks_exact(4) {
+                let alpha = pixel[3] as f32 / 255.0;
+                let [r, g, b] =
+                    [0, 1, 2].map(|channel| self.decode[pixel[channel] as usize] * alpha);
+                for value in [r, g, b, alpha] {
+                    self.linear.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            frame = &self.linear;
+        }
+        let stdin = self
+            .stdin
+            .as_mut()
+            .context(ErrorKind::Encoder.error("ffmpeg stdin already closed"))?;
+        stdin.write_all(frame).context(ErrorKind::Encoder.error("failed to write frame"))?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        if let Some(mut stdin) = self.stdin.take() {
+            let _ = stdin.flush();
+        }
+
+        let status = self
+            .child
+            .wait()
+            .context(ErrorKind::Encoder.error("failed to wait for ffmpeg"))?;
+        self.log.join();
+        if !status.success() {
+            bail!(ErrorKind::Encoder.error(format!(
+                "ffmpeg exited with status {status}; last output:\n{}",
+                self.log.tail()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl Encoder for ImageSequenceEncoder {
+    fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        ImageSequenceEncoder::write_frame(self, frame)
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        ImageSequenceEncoder::finish(*self)
+    }
+
+    fn name(&self) -> String {
+        format!("{} sequence", self.format.extension())
+    }
+}
@@ -0,0 +1,205 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+
+use crate::encoder::{concat_videos, FfmpegVideoEncoder, VideoOptions};
+use crate::error::ErrorKind;
+use crate::scene::Color;
+use crate::timeline::diff::Fnv1a;
+use crate::timeline::{DataSource, TimeRange, Timeline};
+
+const MANIFEST: &str = "manifest.json";
+
+// Renders a timeline as fixed-length chunks kept in `dir` and joins them into
+// the output. Each chunk is stored with a hash of its sampled frames, so the
+// next run re-renders only chunks whose content changed and reuses the rest
+// without re-encoding. Given the ranges that changed since the last run,
+// chunks outside them are reused without sampling their frames at all.
+// Changes the sampled scene can't show (an asset edited in place, new plugin
+// code) need `clear()` first.
+pub struct IncrementalRender {
+    pub dir: PathBuf,
+    pub chunk_secs: f32,
+    // For every chunk, which must all match to be joined.
+    pub options: VideoOptions,
+    data: Option<Arc<dyn DataSource>>,
+    changed: Option<Vec<TimeRange>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IncrementalStats {
+    pub chunks: u32,
+    // Chunks rendered this run; the others were reused.
+    pub rendered: u32,
+}
+
+impl IncrementalRender {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            chunk_secs: 2.0,
+            options: VideoOptions::default(),
+            data: None,
+            changed: None,
+        }
+    }
+
+    // Shorter chunks redo less around an edit but give the encoder less to
+    // work with at each boundary.
+    pub fn with_chunk_secs(mut self, chunk_secs: f32) -> Self {
+        self.chunk_secs = chunk_secs;
+        self
+    }
+
+    pub fn with_options(mut self, options: VideoOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // What changed since the last render, from `Timeline::changed_ranges`.
+    pub fn with_changes(mut self, changed: Vec<TimeRange>) -> Self {
+        self.changed = Some(changed);
+        self
+    }
+
+    // Must match the renderer's data source so bound clips hash as drawn.
+    pub fn with_data_source(mut self, data: Arc<dyn DataSource>) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    // Renders every changed chunk with `render(start_time, end_time, encoder)`,
+    // e.g. through `RaylibRender::render_timeline_rgba`, then writes `output`.
+    pub fn render(
+        &self,
+        timeline: &Timeline,
+        width: u32,
+        height: u32,
+        bg: Color,
+        output: &Path,
+        mut render: impl FnMut(f32, f32, &mut FfmpegVideoEncoder) -> Result<()>,
+    ) -> Result<IncrementalStats> {
+        if self.options.audio.is_some() || self.options.pcm.is_some() {
+            bail!(ErrorKind::InvalidProject.error("incremental renders can't carry audio yet"));
+        }
+        if !self.options.chapters.is_empty() {
+            bail!(ErrorKind::InvalidProject.error("incremental renders can't carry chapters yet"));
+        }
+        fs::create_dir_all(&self.dir).with_context(|| {
+            ErrorKind::Encoder.error(format!("failed to create {}", self.dir.display()))
+        })?;
+        let fps = timeline.fps;
+        let total_frames = timeline.total_frames();
+        let chunk_frames = ((self.chunk_secs * fps as f32).round() as u32).max(1);
+        // Chunks from a different canvas, chunking or encoder can't be reused.
+        let mut hasher = Fnv1a::default();
+        let _ = write!(hasher, "{:?}", self.options);
+        let options = hasher.0;
+        let canvas = format!("{width}x{height} {bg:?} {fps}fps {chunk_frames} {options:016x}");
+        let mut hashes = self.read_manifest(&canvas);
+
+        let mut stats = IncrementalStats::default();
+        let mut chunks = Vec::new();
+        for (index, start_frame) in (0..total_frames).step_by(chunk_frames as usize).enumerate() {
+            let end_frame = (start_frame + chunk_frames).min(total_frames);
+            let path = self.dir.join(format!("chunk_{index:05}.mp4"));
+            chunks.push(path.clone());
+            stats.chunks += 1;
+            let start = start_frame as f32 / fps as f32;
+            let stored = hashes.get(index).is_some_and(|hash| !hash.is_empty()) && path.exists();
+            // The stored hash still holds for a chunk no change reaches.
+            let untouched = self.changed.as_ref().is_some_and(|changed| {
+                let end = end_frame as f32 / fps as f32;
+                !changed.iter().any(|range| range.overlaps(start, end))
+            });
+            if stored && untouched {
+                continue;
+            }
+            let hash = self.chunk_hash(timeline, start_frame, end_frame)?;
+            if stored && hashes[index] == hash {
+                continue;
+            }
+
+            // Encoded under another name so an interrupted run never leaves a
+            // partial chunk behind an up-to-date hash.
+            let partial = path.with_extension("partial.mp4");
+            let end = ((end_frame as f32 + 0.5) / fps as f32).min(timeline.duration);
+            let mut encoder = FfmpegVideoEncoder::start_with_options(
+                width,
+                height,
+                fps,
+                &partial,
+                &self.options,
+            )?;
+            render(start, end, &mut encoder)?;
+            encoder.finish()?;
+            fs::rename(&partial, &path).with_context(|| {
+                ErrorKind::Encoder.error(format!("failed to store {}", path.display()))
+            })?;
+            stats.rendered += 1;
+
+            if hashes.len() <= index {
+                hashes.resize(index + 1, String::new());
+            }
+            hashes[index] = hash;
+            self.write_manifest(&canvas, &hashes)?;
+        }
+        concat_videos(&chunks, output)?;
+        Ok(stats)
+    }
+
+    // Forgets every stored chunk, so the next render starts from scratch.
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_file(self.dir.join(MANIFEST)) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err).context("failed to clear the render manifest"),
+        }
+    }
+
+    fn chunk_hash(&self, timeline: &Timeline, start_frame: u32, end_frame: u32) -> Result<String> {
+        let mut hasher = Fnv1a::default();
+        for frame in start_frame..end_frame {
+            let t = frame as f32 / timeline.fps as f32;
+            let scene = match &self.data {
+                Some(data) => timeline.sample_from(t, data.as_ref())?,
+                None => timeline.sample(t)?,
+            };
+            hasher.write_bytes(&scene.fingerprint().to_le_bytes());
+        }
+        Ok(format!("{:016x}", hasher.0))
+    }
+
+    // Chunk hashes from the previous run, or none if it used another canvas.
+    fn read_manifest(&self, canvas: &str) -> Vec<String> {
+        let Ok(source) = fs::read_to_string(self.dir.join(MANIFEST)) else {
+            return Vec::new();
+        };
+        let Ok(manifest) = serde_json::from_str::<Value>(&source) else {
+            return Vec::new();
+        };
+        if manifest["canvas"].as_str() != Some(canvas) {
+            return Vec::new();
+        }
+        manifest["chunks"]
+            .as_array()
+            .map(|chunks| {
+                chunks
+                    .iter()
+                    .map(|hash| hash.as_str().unwrap_or_default().to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn write_manifest(&self, canvas: &str, hashes: &[String]) -> Result<()> {
+        let path = self.dir.join(MANIFEST);
+        let manifest = json!({ "canvas": canvas, "chunks": hashes });
+        fs::write(&path, manifest.to_string())
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
@@ -1,7 +1,47 @@
+pub mod animated_image;
+pub mod color;
+pub mod dither;
+pub mod frame_encoder;
 pub mod ffmpeg_video;
 pub mod ffmpeg_audio;
+pub mod ffmpeg_log;
+pub mod image_sequence;
+pub mod incremental;
+#[cfg(feature = "ffmpeg-native")]
+pub mod native;
+pub mod pcm;
+pub mod preview_stream;
+pub mod repro;
+pub mod resumable;
+pub mod review;
+pub mod settings;
+pub mod sticker;
+pub mod stream;
 pub mod video_clips;
+pub(crate) mod frame_writer;
+pub(crate) mod yuv;
 
-pub use ffmpeg_video::FfmpegVideoEncoder;
-pub use ffmpeg_audio::{mux_video_audio, render_audio_wav, trim_audio};
-pub use video_clips::{build_base_video, ffprobe_metadata, normalize_if_needed, VideoMetadata};
+pub use animated_image::{AnimatedImage, AnimatedImageEncoder, AnimatedImageFormat};
+pub use color::OutputColor;
+pub use dither::Dither;
+pub use frame_encoder::Encoder;
+pub use ffmpeg_video::{FfmpegVideoEncoder, VideoCodec, VideoOptions};
+pub use ffmpeg_audio::{
+    mux_audio_track, mux_video_audio, render_audio_wav, trim_audio, AudioTrack,
+};
+pub use ffmpeg_log::EncodeProgress;
+pub use image_sequence::{ImageSequenceEncoder, ImageSequenceFormat};
+pub use incremental::{IncrementalRender, IncrementalStats};
+#[cfg(feature = "ffmpeg-native")]
+pub use native::NativeVideoEncoder;
+pub use pcm::PcmFormat;
+pub use preview_stream::PreviewStream;
+pub use repro::RenderManifest;
+pub use resumable::{ResumableRender, ResumableStats};
+pub use review::{ProxyReview, ReviewStats, ReviewStatus};
+pub use settings::EncoderSettings;
+pub use sticker::{Sticker, StickerExport, StickerFormat};
+pub use stream::StreamOutput;
+pub use video_clips::{
+    build_base_video, concat_videos, ffprobe_metadata, normalize_if_needed, VideoMetadata,
+};
@@ -0,0 +1,241 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use ffmpeg_next as ffmpeg;
+use ffmpeg::format::context::Output;
+use ffmpeg::software::scaling;
+use ffmpeg::util::format::Pixel;
+use ffmpeg::{codec, encoder, format, frame, Dictionary, Packet, Rational};
+
+use crate::encoder::{Dither, Encoder, VideoCodec};
+use crate::error::{ErrorKind, RenderError};
+
+// Encodes through the ffmpeg libraries linked into this binary instead of
+// piping raw frames to an `ffmpeg` process, for containers and machines
+// without the CLI installed. Takes the same frames as `FfmpegVideoEncoder`
+// (bottom-up straight RGBA) and a `VideoCodec` for the encoder, pixel
+// format and options; its `-flag value` pairs become encoder options, so
+// `VideoCodec::libx264().with_crf(20)` works as-is. Hardware presets that
+// need `device_args` or `filters` aren't supported. Output is tagged and
+// converted as BT.709, like the CLI encoder's default `OutputColor::Srgb`.
+pub struct NativeVideoEncoder {
+    output: Output,
+    encoder: encoder::Video,
+    scaler: scaling::Context,
+    stream_index: usize,
+    encoder_time_base: Rational,
+    stream_time_base: Rational,
+    width: u32,
+    height: u32,
+    codec: String,
+    // Reused between frames: the dithered input, the flipped RGBA frame and
+    // its conversion.
+    dithered: Vec<u8>,
+    rgba: frame::Video,
+    converted: frame::Video,
+    frames: i64,
+    dither: Dither,
+}
+
+impl NativeVideoEncoder {
+    pub fn start(
+        width: u32,
+        height: u32,
+        fps: u32,
+        output_path: &Path,
+        codec: &VideoCodec,
+    ) -> Result<Self> {
+        if !codec.device_args.is_empty() || codec.filters.is_some() {
+            bail!(ErrorKind::Encoder.error(format!(
+                "{} needs the ffmpeg CLI; use FfmpegVideoEncoder",
+                codec.name
+            )));
+        }
+        ffmpeg::init().map_err(failed("initialise ffmpeg"))?;
+
+        let pix_fmt: Pixel = codec.pix_fmt.parse().map_err(|_| {
+            ErrorKind::Encoder.error(format!("unknown pixel format {}", codec.pix_fmt))
+        })?;
+        let found = encoder::find_by_name(&codec.name).ok_or_else(|| {
+            ErrorKind::Encoder.error(format!("{} is not in this ffmpeg build", codec.name))
+        })?;
+
+        let mut output = format::output(&output_path).map_err(failed("create output"))?;
+        let global_header = output.format().flags().contains(format::Flags::GLOBAL_HEADER);
+        let mut stream = output.add_stream(found).map_err(failed("add a video stream"))?;
+        let stream_index = stream.index();
+
+        let time_base = Rational::new(1, fps as i32);
+        let mut video = codec::context::Context::new_with_codec(found)
+            .encoder()
+            .video()
+            .map_err(failed("create the video encoder"))?;
+        video.set_width(width);
+        video.set_height(height);
+        video.set_format(pix_fmt);
+        video.set_time_base(time_base);
+        video.set_frame_rate(Some(Rational::new(fps as i32, 1)));
+        video.set_colorspace(ffmpeg::color::Space::BT709);
+        video.set_color_range(ffmpeg::color::Range::MPEG);
+        // Without primaries and transfer tags some players guess BT.601 or
+        // sRGB and shift the colours.
+        unsafe {
+            let context = video.as_mut_ptr();
+            (*context).color_primaries = ffmpeg::ffi::AVColorPrimaries::AVCOL_PRI_BT709;
+            (*context).color_trc = ffmpeg::ffi::AVColorTransferCharacteristic::AVCOL_TRC_BT709;
+        }
+        if global_header {
+            video.set_flags(codec::Flags::GLOBAL_HEADER);
+        }
+        let encoder = video
+            .open_as_with(found, encoder_options(&codec.args))
+            .map_err(failed(&format!("open {}", codec.name)))?;
+        stream.set_parameters(&encoder);
+        stream.set_time_base(time_base);
+
+        output.write_header().map_err(failed("write the output header"))?;
+        // The muxer may pick its own time base in `write_header`.
+        let stream_time_base = output
+            .stream(stream_index)
+            .map_or(time_base, |stream| stream.time_base());
+
+        let mut scaler = scaling::Context::get(
+            Pixel::RGBA,
+            width,
+            height,
+            pix_fmt,
+            width,
+            height,
+            scaling::Flags::BILINEAR,
+        )
+        .map_err(failed("create the pixel format converter"))?;
+        // swscale defaults to BT.601 coefficients; match the tags.
+        unsafe {
+            let bt709 = ffmpeg::ffi::sws_getCoefficients(ffmpeg::ffi::SWS_CS_ITU709 as i32);
+            ffmpeg::ffi::sws_setColorspaceDetails(
+                scaler.as_mut_ptr(),
+                bt709,
+                1,
+                bt709,
+                0,
+                0,
+                1 << 16,
+                1 << 16,
+            );
+        }
+
+        Ok(Self {
+            output,
+            encoder,
+            scaler,
+            stream_index,
+            encoder_time_base: time_base,
+            stream_time_base,
+            width,
+            height,
+            codec: codec.name.clone(),
+            dithered: Vec::new(),
+            rgba: frame::Video::new(Pixel::RGBA, width, height),
+            converted: frame::Video::new(pix_fmt, width, height),
+            frames: 0,
+            dither: Dither::None,
+        })
+    }
+
+    pub fn set_dither(&mut self, dither: Dither) {
+        self.dither = dither;
+    }
+
+    pub fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        let expected = (self.width * self.height * 4) as usize;
+        if frame.len() != expected {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "frame size mismatch: got {}, expected {}",
+                frame.len(),
+                expected
+            )));
+        }
+
+        let frame = if self.dither == Dither::None {
+            frame
+        } else {
+            self.dithered.clear();
+            self.dithered.extend_from_slice(frame);
+            self.dither.apply(&mut self.dithered, self.width);
+            &self.dithered
+        };
+        // Flipped to top-down while copying into ffmpeg's padded rows.
+        let row = (self.width * 4) as usize;
+        let stride = self.rgba.stride(0);
+        let data = self.rgba.data_mut(0);
+        for (y, src) in frame.chunks_exact(row).rev().enumerate() {
+            data[y * stride..y * stride + row].copy_from_slice(src);
+        }
+
+        self.scaler
+            .run(&self.rgba, &mut self.converted)
+            .map_err(failed("convert a frame"))?;
+        self.converted.set_pts(Some(self.frames));
+        self.frames += 1;
+        self.encoder
+            .send_frame(&self.converted)
+            .map_err(failed("encode a frame"))?;
+        self.write_packets()
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.encoder.send_eof().map_err(failed("flush the encoder"))?;
+        self.write_packets()?;
+        self.output.write_trailer().map_err(failed("finish the output"))?;
+        Ok(())
+    }
+
+    fn write_packets(&mut self) -> Result<()> {
+        let mut packet = Packet::empty();
+        while self.encoder.receive_packet(&mut packet).is_ok() {
+            packet.set_stream(self.stream_index);
+            packet.rescale_ts(self.encoder_time_base, self.stream_time_base);
+            packet
+                .write_interleaved(&mut self.output)
+                .map_err(failed("write a packet"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Encoder for NativeVideoEncoder {
+    fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        NativeVideoEncoder::write_frame(self, frame)
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        NativeVideoEncoder::finish(*self)
+    }
+
+    fn name(&self) -> String {
+        self.codec.clone()
+    }
+
+    fn set_dither(&mut self, dither: Dither) {
+        NativeVideoEncoder::set_dither(self, dither);
+    }
+}
+
+// `-crf 20 -preset slow -b:v 0` as encoder options `crf`, `preset` and `b`.
+// Codec args are always flag/value pairs.
+fn encoder_options(args: &[String]) -> Dictionary<'static> {
+    let mut options = Dictionary::new();
+    for pair in args.chunks(2) {
+        if let [flag, value] = pair
+            && let Some(name) = flag.strip_prefix('-')
+        {
+            let name = name.strip_suffix(":v").unwrap_or(name);
+            options.set(name, value);
+        }
+    }
+    options
+}
+
+fn failed(what: &str) -> impl Fn(ffmpeg::Error) -> RenderError + '_ {
+    move |err| ErrorKind::Encoder.error(format!("failed to {what}: {err}"))
+}
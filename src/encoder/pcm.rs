@@ -0,0 +1,116 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::error::ErrorKind;
+
+// Generated audio given to `FfmpegVideoEncoder::write_audio` as interleaved
+// f32 samples in -1..1, e.g. tones synced to the animation or TTS output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcmFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl PcmFormat {
+    pub fn new(sample_rate: u32, channels: u16) -> Result<Self> {
+        if sample_rate == 0 || channels == 0 {
+            bail!(ErrorKind::InvalidProject.error("pcm sample rate and channels must be > 0"));
+        }
+        Ok(Self {
+            sample_rate,
+            channels,
+        })
+    }
+
+    // Samples (per channel) that line up with one video frame at `fps`, for
+    // frame `index` of the render. Rounds so a whole render doesn't drift.
+    pub fn samples_for_frame(&self, index: u32, fps: u32) -> usize {
+        let at = |frame: u32| (frame as u64 * self.sample_rate as u64 / fps as u64) as usize;
+        at(index + 1) - at(index)
+    }
+}
+
+// A 16-bit WAV file written as samples arrive; the sizes in the header are
+// filled in by `finish`.
+pub(crate) struct WavWriter {
+    out: BufWriter<File>,
+    format: PcmFormat,
+    bytes: u32,
+}
+
+impl WavWriter {
+    pub(crate) fn create(path: &Path, format: PcmFormat) -> Result<Self> {
+        let file = File::create(path).with_context(|| {
+            ErrorKind::Encoder.error(format!("failed to create {}", path.display()))
+        })?;
+        let mut writer = Self {
+            out: BufWriter::new(file),
+            format,
+            bytes: 0,
+        };
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    pub(crate) fn write(&mut self, samples: &[f32]) -> Result<()> {
+        if !samples.len().is_multiple_of(self.format.channels as usize) {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "pcm samples must be whole frames of {} channels",
+                self.format.channels
+            )));
+        }
+        // The header's sizes are 32-bit, and the RIFF one counts 36 more
+        // bytes than the data.
+        let bytes = u32::try_from(samples.len() * 2)
+            .ok()
+            .and_then(|added| self.bytes.checked_add(added))
+            .filter(|bytes| bytes.checked_add(36).is_some());
+        let Some(bytes) = bytes else {
+            bail!(ErrorKind::Encoder.error("pcm audio is too long for a wav file (4 GiB)"));
+        };
+        for sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+            self.out
+                .write_all(&value.to_le_bytes())
+                .context(ErrorKind::Encoder.error("failed to write pcm audio"))?;
+        }
+        self.bytes = bytes;
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> Result<()> {
+        self.out
+            .seek(SeekFrom::Start(0))
+            .context(ErrorKind::Encoder.error("failed to finish pcm audio"))?;
+        self.write_header()?;
+        self.out.flush().context(ErrorKind::Encoder.error("failed to finish pcm audio"))
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let PcmFormat {
+            sample_rate,
+            channels,
+        } = self.format;
+        let block_align = channels * 2;
+        let mut header = Vec::with_capacity(44);
+        header.extend_from_slice(b"RIFF");
+        // `write` keeps this in range.
+        header.extend_from_slice(&(36 + self.bytes).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&channels.to_le_bytes());
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&16u16.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&self.bytes.to_le_bytes());
+        self.out
+            .write_all(&header)
+            .context(ErrorKind::Encoder.error("failed to write pcm audio"))
+    }
+}
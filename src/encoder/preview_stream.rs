@@ -0,0 +1,340 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+
+use crate::error::ErrorKind;
+
+const BOUNDARY: &str = "frame";
+// How long a viewer has to send its request before it's dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Serves a downscaled copy of the frames being rendered as an MJPEG stream
+// (`multipart/x-mixed-replace`), so a long render can be watched in a browser
+// tab. Frames are dropped rather than queued: a slow viewer or encoder only
+// means fewer of them, and nothing is encoded while nobody is watching. JPEGs
+// are encoded off the render thread by one long-lived ffmpeg.
+pub struct PreviewStream {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    // Downscaled frames for the encoding thread, which holds at most one.
+    frames: Option<SyncSender<SmallFrame>>,
+    max_width: u32,
+    min_interval: Duration,
+    last_publish: Option<Instant>,
+}
+
+#[derive(Default)]
+struct Latest {
+    // Bumped per published frame so viewers can wait for the next one.
+    sequence: u64,
+    jpeg: Arc<Vec<u8>>,
+    viewers: usize,
+    closed: bool,
+    // Why the encoding thread stopped, if it did.
+    failed: Option<String>,
+}
+
+#[derive(Default)]
+struct Shared {
+    latest: Mutex<Latest>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn publish(&self, jpeg: Vec<u8>) {
+        let mut latest = self.latest.lock().expect("preview lock");
+        latest.sequence += 1;
+        latest.jpeg = Arc::new(jpeg);
+        self.changed.notify_all();
+    }
+}
+
+struct SmallFrame {
+    rgba: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+impl PreviewStream {
+    // Binds e.g. `"0.0.0.0:8090"` and serves any path on it.
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr).context("failed to bind the preview stream")?;
+        let addr = listener.local_addr()?;
+        // Polled so the thread can notice the stream being dropped.
+        listener.set_nonblocking(true)?;
+        let shared = Arc::new(Shared::default());
+        let accept_shared = Arc::clone(&shared);
+        thread::spawn(move || accept_loop(listener, accept_shared));
+        let (frames, receiver) = mpsc::sync_channel(1);
+        let encode_shared = Arc::clone(&shared);
+        thread::spawn(move || encode_loop(receiver, encode_shared));
+        Ok(Self {
+            addr,
+            shared,
+            frames: Some(frames),
+            max_width: 480,
+            min_interval: Duration::from_millis(200),
+            last_publish: None,
+        })
+    }
+
+    pub fn with_max_width(mut self, max_width: u32) -> Self {
+        self.max_width = max_width.max(1);
+        self
+    }
+
+    pub fn with_max_fps(mut self, max_fps: f32) -> Self {
+        self.min_interval = Duration::from_secs_f32(1.0 / max_fps.max(0.01));
+        self
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // Offers one rendered frame (bottom-up RGBA, as `RaylibRender` produces
+    // it). Returns quickly without downscaling when no one is watching or the
+    // previous frame went out less than `1 / max_fps` ago, and never waits
+    // for the encoder: a frame it isn't ready for is dropped. Fails once
+    // encoding has failed, e.g. without ffmpeg.
+    pub fn publish(&mut self, rgba: &[u8], width: u32, height: u32) -> Result<()> {
+        if rgba.len() != (width * height * 4) as usize {
+            bail!(ErrorKind::InvalidProject.error("preview frame size does not match"));
+        }
+        let viewers = {
+            let latest = self.shared.latest.lock().expect("preview lock");
+            if let Some(err) = &latest.failed {
+                bail!(ErrorKind::Encoder.error(format!("preview encoding failed: {err}")));
+            }
+            latest.viewers
+        };
+        let due = self
+            .last_publish
+            .is_none_or(|last| last.elapsed() >= self.min_interval);
+        if viewers == 0 || !due {
+            return Ok(());
+        }
+        self.last_publish = Some(Instant::now());
+
+        let (small, small_width, small_height) = downscale(rgba, width, height, self.max_width);
+        if let Some(frames) = &self.frames {
+            let _ = frames.try_send(SmallFrame {
+                rgba: small,
+                width: small_width,
+                height: small_height,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PreviewStream {
+    fn drop(&mut self) {
+        // Ends the encoding thread, which stops its ffmpeg.
+        self.frames = None;
+        self.shared.latest.lock().expect("preview lock").closed = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+fn accept_loop(listener: TcpListener, shared: Arc<Shared>) {
+    loop {
+        if shared.latest.lock().expect("preview lock").closed {
+            return;
+        }
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let shared = Arc::clone(&shared);
+                // Any error means the viewer went away.
+                thread::spawn(move || serve(stream, &shared));
+            }
+            Err(_) => thread::sleep(Duration::from_millis(100)),
+        }
+    }
+}
+
+// Counts the viewer as watching only once its request is in, so connections
+// that never send one don't keep frames being encoded.
+fn serve(mut stream: TcpStream, shared: &Shared) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    // The request itself doesn't matter; read it so the browser isn't reset.
+    let mut request = [0u8; 4096];
+    if stream.read(&mut request)? == 0 {
+        return Ok(());
+    }
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={BOUNDARY}\r\n\
+         Cache-Control: no-cache\r\nConnection: close\r\n\r\n"
+    )?;
+
+    shared.latest.lock().expect("preview lock").viewers += 1;
+    let result = send_frames(&mut stream, shared);
+    shared.latest.lock().expect("preview lock").viewers -= 1;
+    result
+}
+
+fn send_frames(stream: &mut TcpStream, shared: &Shared) -> Result<()> {
+    let mut seen = 0;
+    loop {
+        let jpeg = {
+            let mut latest = shared.latest.lock().expect("preview lock");
+            while latest.sequence == seen && !latest.closed {
+                latest = shared.changed.wait(latest).expect("preview lock");
+            }
+            if latest.closed {
+                return Ok(());
+            }
+            seen = latest.sequence;
+            Arc::clone(&latest.jpeg)
+        };
+        write!(
+            stream,
+            "--{BOUNDARY}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+            jpeg.len()
+        )?;
+        stream.write_all(&jpeg)?;
+        stream.write_all(b"\r\n")?;
+        stream.flush()?;
+    }
+}
+
+// Nearest-neighbour downscale to at most `max_width`, flipped top-down.
+fn downscale(rgba: &[u8], width: u32, height: u32, max_width: u32) -> (Vec<u8>, u32, u32) {
+    let out_width = width.min(max_width);
+    let out_height = ((height as u64 * out_width as u64 / width as u64) as u32).max(1);
+    let mut out = Vec::with_capacity((out_width * out_height * 4) as usize);
+    for y in 0..out_height {
+        let src_y = height - 1 - (y as u64 * height as u64 / out_height as u64) as u32;
+        for x in 0..out_width {
+            let src_x = (x as u64 * width as u64 / out_width as u64) as u32;
+            let i = ((src_y * width + src_x) * 4) as usize;
+            out.extend_from_slice(&rgba[i..i + 4]);
+        }
+    }
+    (out, out_width, out_height)
+}
+
+// Encodes frames as they arrive, through one ffmpeg that's restarted only
+// when the frame size changes.
+fn encode_loop(frames: Receiver<SmallFrame>, shared: Arc<Shared>) {
+    let mut pipe: Option<JpegPipe> = None;
+    for frame in frames {
+        if let Err(err) = encode_frame(&mut pipe, &frame, &shared) {
+            shared.latest.lock().expect("preview lock").failed = Some(format!("{err:#}"));
+            return;
+        }
+    }
+}
+
+fn encode_frame(
+    pipe: &mut Option<JpegPipe>,
+    frame: &SmallFrame,
+    shared: &Arc<Shared>,
+) -> Result<()> {
+    let size = (frame.width, frame.height);
+    if pipe.as_ref().is_some_and(|pipe| pipe.size != size) {
+        *pipe = None;
+    }
+    if pipe.is_none() {
+        *pipe = Some(JpegPipe::spawn(size, Arc::clone(shared))?);
+    }
+    pipe.as_mut()
+        .expect("pipe spawned above")
+        .write(&frame.rgba)
+}
+
+// An ffmpeg turning raw frames into a stream of JPEGs, each published as
+// soon as it's read back.
+struct JpegPipe {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    size: (u32, u32),
+    reader: Option<JoinHandle<()>>,
+}
+
+impl JpegPipe {
+    fn spawn((width, height): (u32, u32), shared: Arc<Shared>) -> Result<Self> {
+        let mut child = Command::new("ffmpeg")
+            .arg("-loglevel")
+            .arg("error")
+            .arg("-f")
+            .arg("rawvideo")
+            .arg("-pix_fmt")
+            .arg("rgba")
+            .arg("-s")
+            .arg(format!("{width}x{height}"))
+            .arg("-i")
+            .arg("-")
+            .arg("-q:v")
+            .arg("5")
+            .arg("-flush_packets")
+            .arg("1")
+            .arg("-f")
+            .arg("mjpeg")
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context(ErrorKind::Encoder.error("failed to spawn ffmpeg (is it on PATH?)"))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let reader = thread::spawn(move || read_jpegs(stdout, &shared));
+        Ok(Self {
+            child,
+            stdin: Some(stdin),
+            size: (width, height),
+            reader: Some(reader),
+        })
+    }
+
+    fn write(&mut self, rgba: &[u8]) -> Result<()> {
+        self.stdin
+            .as_mut()
+            .expect("stdin is open until drop")
+            .write_all(rgba)
+            .context(ErrorKind::Encoder.error("failed to send preview frame to ffmpeg"))
+    }
+}
+
+impl Drop for JpegPipe {
+    fn drop(&mut self) {
+        // Closing stdin lets ffmpeg finish the last JPEG and exit.
+        self.stdin = None;
+        let _ = self.child.wait();
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+fn read_jpegs(mut stdout: ChildStdout, shared: &Shared) {
+    let mut pending = Vec::new();
+    let mut chunk = vec![0u8; 64 * 1024];
+    loop {
+        match stdout.read(&mut chunk) {
+            Ok(0) | Err(_) => return,
+            Ok(read) => pending.extend_from_slice(&chunk[..read]),
+        }
+        while let Some(end) = jpeg_len(&pending) {
+            shared.publish(pending.drain(..end).collect());
+        }
+    }
+}
+
+// Length of the first whole JPEG in `data`, through its end-of-image marker.
+// Encoded data never holds the marker's bytes, and ffmpeg writes no
+// thumbnails that could.
+fn jpeg_len(data: &[u8]) -> Option<usize> {
+    data.windows(2)
+        .position(|pair| pair == [0xFF, 0xD9])
+        .map(|at| at + 2)
+}
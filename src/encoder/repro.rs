@@ -0,0 +1,308 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+
+use crate::encoder::{Dither, OutputColor, VideoCodec, VideoOptions};
+use crate::error::ErrorKind;
+use crate::project::Project;
+use crate::scene::{FontSource, Object};
+use crate::timeline::diff::Fnv1a;
+use crate::timeline::Timeline;
+
+// Everything a render of a project file depended on: the project and asset
+// contents (by hash), the range and encoder settings, and the library and
+// ffmpeg versions. Written next to the output and into its `comment` tag,
+// so the render can be repeated exactly later (see `check_inputs`).
+// Procedural seeds live in the project file and are covered by its hash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderManifest {
+    pub version: String,
+    // First line of `ffmpeg -version`, if ffmpeg runs.
+    pub ffmpeg: Option<String>,
+    pub project: PathBuf,
+    pub project_hash: String,
+    // Images and fonts the project uses, with their hashes.
+    pub assets: Vec<(PathBuf, String)>,
+    // Canvas size, frame rate and duration rendered, which may differ from
+    // the project file's when they were overridden.
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub duration: f32,
+    pub start: f32,
+    pub end: f32,
+    pub codecs: Vec<VideoCodec>,
+    pub color: OutputColor,
+    pub dither: Dither,
+    // Hash of the finished output, once set with `record_output`.
+    pub output_hash: Option<String>,
+}
+
+impl RenderManifest {
+    // For rendering `start..end` of the project loaded from `project_path`.
+    pub fn record(project_path: &Path, project: &Project, start: f32, end: f32) -> Result<Self> {
+        let mut assets = Vec::new();
+        collect_assets(&project.timeline, &mut assets);
+        assets.sort();
+        assets.dedup();
+        let assets = assets
+            .into_iter()
+            .map(|path| {
+                let hash = hash_file(&path)?;
+                Ok((path, hash))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            ffmpeg: ffmpeg_version(),
+            project: project_path.to_path_buf(),
+            project_hash: hash_file(project_path)?,
+            assets,
+            width: project.width,
+            height: project.height,
+            fps: project.timeline.fps,
+            duration: project.timeline.duration,
+            start,
+            end,
+            codecs: vec![VideoCodec::libx264()],
+            color: OutputColor::default(),
+            dither: Dither::None,
+            output_hash: None,
+        })
+    }
+
+    pub fn with_encoder(
+        mut self,
+        codecs: Vec<VideoCodec>,
+        color: OutputColor,
+        dither: Dither,
+    ) -> Self {
+        self.codecs = codecs;
+        self.color = color;
+        self.dither = dither;
+        self
+    }
+
+    // Options to encode with: the recorded codecs and colour, with the
+    // manifest (without the output hash) as the `comment` tag.
+    pub fn video_options(&self) -> VideoOptions {
+        let mut embedded = self.clone();
+        embedded.output_hash = None;
+        VideoOptions::default()
+            .with_codecs(self.codecs.clone())
+            .with_color(self.color)
+            .with_metadata("comment", embedded.to_json())
+    }
+
+    pub fn record_output(&mut self, output: &Path) -> Result<()> {
+        self.output_hash = Some(hash_file(output)?);
+        Ok(())
+    }
+
+    // Fails listing everything that differs from when the manifest was
+    // written; any difference may change the output.
+    pub fn check_inputs(&self) -> Result<()> {
+        let mut differences = Vec::new();
+        if self.version != env!("CARGO_PKG_VERSION") {
+            differences.push(format!(
+                "library version {} (recorded {})",
+                env!("CARGO_PKG_VERSION"),
+                self.version
+            ));
+        }
+        let ffmpeg = ffmpeg_version();
+        if ffmpeg != self.ffmpeg {
+            differences.push(format!(
+                "ffmpeg {} (recorded {})",
+                ffmpeg.as_deref().unwrap_or("unavailable"),
+                self.ffmpeg.as_deref().unwrap_or("unavailable")
+            ));
+        }
+        let files = [(&self.project, &self.project_hash)]
+            .into_iter()
+            .chain(self.assets.iter().map(|(path, hash)| (path, hash)));
+        for (path, recorded) in files {
+            match hash_file(path) {
+                Ok(hash) if hash == *recorded => {}
+                Ok(_) => differences.push(format!("{} has changed", path.display())),
+                Err(_) => differences.push(format!("{} is missing", path.display())),
+            }
+        }
+        if !differences.is_empty() {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "inputs differ from the manifest:\n{}",
+                differences.join("\n")
+            )));
+        }
+        Ok(())
+    }
+
+    // Whether `output` is byte-identical to the recorded output.
+    pub fn matches_output(&self, output: &Path) -> Result<bool> {
+        Ok(self.output_hash.as_deref() == Some(hash_file(output)?.as_str()))
+    }
+
+    pub fn to_json(&self) -> String {
+        let assets: Vec<Value> = self
+            .assets
+            .iter()
+            .map(|(path, hash)| json!({ "path": path, "hash": hash }))
+            .collect();
+        let codecs: Vec<Value> = self
+            .codecs
+            .iter()
+            .map(|codec| {
+                json!({
+                    "name": codec.name,
+                    "pix_fmt": codec.pix_fmt,
+                    "args": codec.args,
+                    "device_args": codec.device_args,
+                    "filters": codec.filters,
+                })
+            })
+            .collect();
+        json!({
+            "version": self.version,
+            "ffmpeg": self.ffmpeg,
+            "project": self.project,
+            "project_hash": self.project_hash,
+            "assets": assets,
+            "width": self.width,
+            "height": self.height,
+            "fps": self.fps,
+            "duration": self.duration,
+            "start": self.start,
+            "end": self.end,
+            "codecs": codecs,
+            "color": self.color.name(),
+            "dither": self.dither.name(),
+            "output_hash": self.output_hash,
+        })
+        .to_string()
+    }
+
+    pub fn from_json(source: &str) -> Result<Self> {
+        let invalid = |what: &str| ErrorKind::InvalidProject.error(format!("manifest: {what}"));
+        let doc: Value = serde_json::from_str(source).map_err(|err| invalid(&err.to_string()))?;
+        let string = |value: &Value, key: &str| {
+            value[key]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| invalid(&format!("\"{key}\" must be a string")))
+        };
+        let number = |key: &str| {
+            doc[key]
+                .as_f64()
+                .ok_or_else(|| invalid(&format!("\"{key}\" must be a number")))
+        };
+        let strings = |value: &Value, key: &str| -> Result<Vec<String>> {
+            let Some(items) = value[key].as_array() else {
+                bail!(invalid(&format!("\"{key}\" must be an array")));
+            };
+            items
+                .iter()
+                .map(|item| {
+                    item.as_str()
+                        .map(str::to_string)
+                        .ok_or_else(|| invalid(&format!("\"{key}\" must hold strings")).into())
+                })
+                .collect()
+        };
+
+        let mut assets = Vec::new();
+        let array = |key: &str| {
+            doc[key]
+                .as_array()
+                .ok_or_else(|| invalid(&format!("\"{key}\" must be an array")))
+        };
+        for asset in array("assets")? {
+            let path = PathBuf::from(string(asset, "path")?);
+            assets.push((path, string(asset, "hash")?));
+        }
+        let mut codecs = Vec::new();
+        for codec in array("codecs")? {
+            let mut parsed = VideoCodec::new(string(codec, "name")?)
+                .with_pix_fmt(string(codec, "pix_fmt")?)
+                .with_args(strings(codec, "args")?);
+            parsed.device_args = strings(codec, "device_args")?;
+            parsed.filters = codec["filters"].as_str().map(str::to_string);
+            codecs.push(parsed);
+        }
+        let color = OutputColor::from_name(&string(&doc, "color")?)
+            .ok_or_else(|| invalid("unknown \"color\""))?;
+        let dither = Dither::from_name(&string(&doc, "dither")?)
+            .ok_or_else(|| invalid("unknown \"dither\""))?;
+
+        Ok(Self {
+            version: string(&doc, "version")?,
+            ffmpeg: doc["ffmpeg"].as_str().map(str::to_string),
+            project: PathBuf::from(string(&doc, "project")?),
+            project_hash: string(&doc, "project_hash")?,
+            assets,
+            width: number("width")? as u32,
+            height: number("height")? as u32,
+            fps: number("fps")? as u32,
+            duration: number("duration")? as f32,
+            start: number("start")? as f32,
+            end: number("end")? as f32,
+            codecs,
+            color,
+            dither,
+            output_hash: doc["output_hash"].as_str().map(str::to_string),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = fs::read_to_string(path).with_context(|| {
+            ErrorKind::MissingAsset.error(format!("failed to read manifest: {}", path.display()))
+        })?;
+        Self::from_json(&source).with_context(|| format!("failed to load {}", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_json())
+            .with_context(|| format!("failed to write manifest: {}", path.display()))
+    }
+}
+
+fn collect_assets(timeline: &Timeline, assets: &mut Vec<PathBuf>) {
+    for clip in timeline.layers.iter().flat_map(|layer| &layer.clips) {
+        match &clip.object {
+            Object::Image(image) => assets.push(image.path.clone()),
+            Object::Text(text) => {
+                let family = &text.font;
+                let sources = [
+                    Some(&family.regular),
+                    family.bold.as_ref(),
+                    family.italic.as_ref(),
+                    family.bold_italic.as_ref(),
+                ];
+                for source in sources.into_iter().flatten().chain(&family.fallbacks) {
+                    if let FontSource::Path(path) = source {
+                        assets.push(path.clone());
+                    }
+                }
+            }
+            Object::Composition(composition) => collect_assets(&composition.timeline, assets),
+            Object::Shape(_) | Object::Plugin(_) => {}
+        }
+    }
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).with_context(|| {
+        ErrorKind::MissingAsset.error(format!("failed to read {}", path.display()))
+    })?;
+    let mut hasher = Fnv1a::default();
+    hasher.write_bytes(&bytes);
+    Ok(format!("{:016x}", hasher.0))
+}
+
+fn ffmpeg_version() -> Option<String> {
+    let output = Command::new("ffmpeg").arg("-version").output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.lines().next().map(|line| line.trim().to_string())
+}
@@ -0,0 +1,206 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+
+use crate::encoder::ffmpeg_audio::mux_audio_track_with;
+use crate::encoder::{concat_videos, FfmpegVideoEncoder, VideoOptions};
+use crate::error::ErrorKind;
+use crate::timeline::diff::Fnv1a;
+use crate::timeline::Timeline;
+
+const CHECKPOINT: &str = "checkpoint.json";
+
+// Renders a timeline as fixed-length segments kept in `dir`, recording each
+// one as it finishes, so a render that crashes or is killed an hour in
+// carries on from the first unfinished segment when run again. The segments
+// are joined without re-encoding at the end. A checkpoint left by another
+// timeline, size or set of options is ignored and everything is rendered.
+pub struct ResumableRender {
+    pub dir: PathBuf,
+    pub segment_secs: f32,
+    // For every segment; an audio track is muxed in once they're joined.
+    pub options: VideoOptions,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResumableStats {
+    pub segments: u32,
+    // Segments finished by an earlier run and reused.
+    pub resumed: u32,
+}
+
+#[derive(Default)]
+struct Checkpoint {
+    // The codec the finished segments were encoded with, after any fallback;
+    // the rest must match to be joined.
+    codec: Option<String>,
+    done: Vec<usize>,
+}
+
+impl ResumableRender {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            segment_secs: 10.0,
+            options: VideoOptions::default(),
+        }
+    }
+
+    // Shorter segments lose less to a crash but give the encoder less to
+    // work with at each boundary.
+    pub fn with_segment_secs(mut self, segment_secs: f32) -> Self {
+        self.segment_secs = segment_secs;
+        self
+    }
+
+    pub fn with_options(mut self, options: VideoOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // Renders every unfinished segment with `render(start_time, end_time,
+    // encoder)`, e.g. through `RaylibRender::render_timeline_rgba`, then
+    // writes `output`.
+    pub fn render(
+        &self,
+        timeline: &Timeline,
+        width: u32,
+        height: u32,
+        output: &Path,
+        mut render: impl FnMut(f32, f32, &mut FfmpegVideoEncoder) -> Result<()>,
+    ) -> Result<ResumableStats> {
+        if self.options.pcm.is_some() || !self.options.chapters.is_empty() {
+            bail!(ErrorKind::InvalidProject
+                .error("resumable renders can't carry pcm audio or chapters yet"));
+        }
+        if self.segment_secs <= 0.0 {
+            bail!(ErrorKind::InvalidProject.error("segment length must be > 0"));
+        }
+        fs::create_dir_all(&self.dir).with_context(|| {
+            ErrorKind::Encoder.error(format!("failed to create {}", self.dir.display()))
+        })?;
+        let fps = timeline.fps;
+        let total_frames = timeline.total_frames();
+        let segment_frames = ((self.segment_secs * fps as f32).round() as u32).max(1);
+        let extension = output
+            .extension()
+            .map_or_else(|| "mp4".to_string(), |ext| ext.to_string_lossy().to_string());
+        let job = self.job(timeline, width, height, segment_frames);
+        let mut checkpoint = self.read_checkpoint(&job);
+
+        // With audio, the output args belong to the final mux, as they do for
+        // a single encode.
+        let mut options = VideoOptions {
+            audio: None,
+            ..self.options.clone()
+        };
+        if self.options.audio.is_some() {
+            options.output_args.clear();
+        }
+        let mut stats = ResumableStats::default();
+        let mut segments = Vec::new();
+        for (index, start_frame) in (0..total_frames).step_by(segment_frames as usize).enumerate() {
+            let end_frame = (start_frame + segment_frames).min(total_frames);
+            let path = self.dir.join(format!("segment_{index:05}.{extension}"));
+            segments.push(path.clone());
+            stats.segments += 1;
+            if checkpoint.done.contains(&index) && path.exists() {
+                stats.resumed += 1;
+                continue;
+            }
+
+            if let Some(name) = &checkpoint.codec
+                && let Some(codec) = self.options.codecs.iter().find(|codec| &codec.name == name)
+            {
+                options.codecs = vec![codec.clone()];
+            }
+            // Encoded under another name so a killed run never leaves a
+            // partial segment that looks finished.
+            let partial = path.with_extension(format!("partial.{extension}"));
+            let start = start_frame as f32 / fps as f32;
+            let end = ((end_frame as f32 + 0.5) / fps as f32).min(timeline.duration);
+            let mut encoder =
+                FfmpegVideoEncoder::start_with_options(width, height, fps, &partial, &options)?;
+            render(start, end, &mut encoder)?;
+            let codec = encoder.codec().to_string();
+            encoder.finish()?;
+            fs::rename(&partial, &path).with_context(|| {
+                ErrorKind::Encoder.error(format!("failed to store {}", path.display()))
+            })?;
+
+            checkpoint.codec = Some(codec);
+            checkpoint.done.push(index);
+            self.write_checkpoint(&job, &checkpoint)?;
+        }
+
+        match &self.options.audio {
+            Some(track) => {
+                let joined = self.dir.join(format!("joined.{extension}"));
+                concat_videos(&segments, &joined)?;
+                let duration = total_frames as f32 / fps as f32;
+                let muxed = mux_audio_track_with(
+                    &self.options.ffmpeg,
+                    &joined,
+                    track,
+                    duration,
+                    output,
+                    &self.options.output_args,
+                );
+                let _ = fs::remove_file(&joined);
+                muxed?;
+            }
+            None => concat_videos(&segments, output)?,
+        }
+        Ok(stats)
+    }
+
+    // Forgets every finished segment, so the next render starts over.
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_file(self.dir.join(CHECKPOINT)) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err).context("failed to clear the render checkpoint"),
+        }
+    }
+
+    // Identifies what's being rendered, so segments of anything else aren't
+    // reused.
+    fn job(&self, timeline: &Timeline, width: u32, height: u32, segment_frames: u32) -> String {
+        let mut hasher = Fnv1a::default();
+        let _ = write!(hasher, "{timeline:?} {:?}", self.options);
+        format!("{width}x{height} {segment_frames} {:016x}", hasher.0)
+    }
+
+    fn read_checkpoint(&self, job: &str) -> Checkpoint {
+        let Ok(source) = fs::read_to_string(self.dir.join(CHECKPOINT)) else {
+            return Checkpoint::default();
+        };
+        let Ok(checkpoint) = serde_json::from_str::<Value>(&source) else {
+            return Checkpoint::default();
+        };
+        if checkpoint["job"].as_str() != Some(job) {
+            return Checkpoint::default();
+        }
+        Checkpoint {
+            codec: checkpoint["codec"].as_str().map(str::to_string),
+            done: checkpoint["done"]
+                .as_array()
+                .map(|done| {
+                    done.iter()
+                        .filter_map(|index| index.as_u64().map(|index| index as usize))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    fn write_checkpoint(&self, job: &str, checkpoint: &Checkpoint) -> Result<()> {
+        let path = self.dir.join(CHECKPOINT);
+        let value = json!({ "job": job, "codec": checkpoint.codec, "done": checkpoint.done });
+        fs::write(&path, value.to_string())
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
@@ -0,0 +1,369 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+
+use crate::encoder::{concat_videos, FfmpegVideoEncoder};
+use crate::error::ErrorKind;
+use crate::scene::Color;
+use crate::timeline::diff::Fnv1a;
+use crate::timeline::{DataSource, Timeline};
+
+const MANIFEST: &str = "review.json";
+const PROXY: &str = "proxy.mp4";
+
+// A two-stage review kept in `dir`: render a quick proxy, approve the ranges
+// that look right, then render only those at full quality and splice them
+// together. Each approval stores a hash of the frames it covers, so a range
+// edited after it was approved drops out of the final render until it's
+// approved again, and final segments are reused while their range and
+// content stay the same.
+pub struct ProxyReview {
+    pub dir: PathBuf,
+    data: Option<Arc<dyn DataSource>>,
+}
+
+// Where the review stands, in seconds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReviewStatus {
+    pub approved: Vec<(f32, f32)>,
+    // Approved, but edited since; these need another look at the proxy.
+    pub changed: Vec<(f32, f32)>,
+    // Everything not currently approved, changed ranges included.
+    pub pending: Vec<(f32, f32)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReviewStats {
+    pub segments: u32,
+    // Segments rendered this run; the others were reused.
+    pub rendered: u32,
+    // Approved ranges left out because they were edited since.
+    pub changed: Vec<(f32, f32)>,
+}
+
+// Frames `start..end` and the hash of what they showed when approved (or,
+// for final segments, when rendered).
+#[derive(Debug, Clone, PartialEq)]
+struct Range {
+    start: u32,
+    end: u32,
+    hash: String,
+}
+
+impl ProxyReview {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            data: None,
+        }
+    }
+
+    // Must match the renderer's data source so bound clips hash as drawn.
+    pub fn with_data_source(mut self, data: Arc<dyn DataSource>) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    // Renders the whole timeline as the proxy with
+    // `render(start_time, end_time, encoder)`, typically a small
+    // `RaylibRender` with expensive effects off, and returns its path.
+    pub fn render_proxy(
+        &self,
+        timeline: &Timeline,
+        width: u32,
+        height: u32,
+        mut render: impl FnMut(f32, f32, &mut FfmpegVideoEncoder) -> Result<()>,
+    ) -> Result<PathBuf> {
+        self.create_dir()?;
+        let path = self.dir.join(PROXY);
+        let mut encoder = FfmpegVideoEncoder::start(width, height, timeline.fps, &path)?;
+        render(0.0, timeline.duration, &mut encoder)?;
+        encoder.finish()?;
+        Ok(path)
+    }
+
+    // Marks `start..end` seconds as good, merging it with the approved ranges
+    // it touches. Changed approvals it overlaps are dropped first, so nothing
+    // outside what was just looked at is approved by accident.
+    pub fn approve(&self, timeline: &Timeline, start: f32, end: f32) -> Result<()> {
+        let (start, end) = self.frames(timeline, start, end)?;
+        let mut manifest = self.read_manifest(timeline.fps);
+        let (current, mut changed) = self.split_changed(timeline, manifest.approved)?;
+        changed.retain(|range| range.end <= start || range.start >= end);
+
+        let mut spans: Vec<(u32, u32)> =
+            current.iter().map(|range| (range.start, range.end)).collect();
+        spans.push((start, end));
+        spans.sort_by_key(|span| span.0);
+        let mut merged: Vec<(u32, u32)> = Vec::new();
+        for (start, end) in spans {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+
+        manifest.approved = changed;
+        for (start, end) in merged {
+            let hash = self.range_hash(timeline, start, end)?;
+            manifest.approved.push(Range { start, end, hash });
+        }
+        manifest.approved.sort_by_key(|range| range.start);
+        self.write_manifest(timeline.fps, &manifest)
+    }
+
+    // Takes `start..end` seconds back out of the approved ranges.
+    pub fn revoke(&self, timeline: &Timeline, start: f32, end: f32) -> Result<()> {
+        let (start, end) = self.frames(timeline, start, end)?;
+        let mut manifest = self.read_manifest(timeline.fps);
+        let (current, mut changed) = self.split_changed(timeline, manifest.approved)?;
+        changed.retain(|range| range.end <= start || range.start >= end);
+
+        manifest.approved = changed;
+        for range in current {
+            let pieces = [(range.start, range.end.min(start)), (range.start.max(end), range.end)];
+            for (piece_start, piece_end) in pieces {
+                if piece_end <= piece_start {
+                    continue;
+                }
+                let hash = if (piece_start, piece_end) == (range.start, range.end) {
+                    range.hash.clone()
+                } else {
+                    self.range_hash(timeline, piece_start, piece_end)?
+                };
+                manifest.approved.push(Range {
+                    start: piece_start,
+                    end: piece_end,
+                    hash,
+                });
+            }
+        }
+        manifest.approved.sort_by_key(|range| range.start);
+        self.write_manifest(timeline.fps, &manifest)
+    }
+
+    pub fn status(&self, timeline: &Timeline) -> Result<ReviewStatus> {
+        let manifest = self.read_manifest(timeline.fps);
+        let (current, changed) = self.split_changed(timeline, manifest.approved)?;
+        let seconds = |start: u32, end: u32| self.seconds(timeline, start, end);
+
+        let mut pending = Vec::new();
+        let mut cursor = 0;
+        for range in &current {
+            if range.start > cursor {
+                pending.push(seconds(cursor, range.start));
+            }
+            cursor = range.end;
+        }
+        if cursor < timeline.total_frames() {
+            pending.push(seconds(cursor, timeline.total_frames()));
+        }
+        Ok(ReviewStatus {
+            approved: current.iter().map(|range| seconds(range.start, range.end)).collect(),
+            changed: changed.iter().map(|range| seconds(range.start, range.end)).collect(),
+            pending,
+        })
+    }
+
+    // Renders each approved range with `render(start_time, end_time, encoder)`
+    // at full quality, reusing segments from an earlier run whose range and
+    // frames haven't changed, then splices them into `output` in order. Gaps
+    // between approved ranges are left out, so `output` is the whole
+    // timeline once `status` has nothing pending.
+    pub fn render_final(
+        &self,
+        timeline: &Timeline,
+        width: u32,
+        height: u32,
+        bg: Color,
+        output: &Path,
+        mut render: impl FnMut(f32, f32, &mut FfmpegVideoEncoder) -> Result<()>,
+    ) -> Result<ReviewStats> {
+        self.create_dir()?;
+        let fps = timeline.fps;
+        let mut manifest = self.read_manifest(fps);
+        let (current, changed) = self.split_changed(timeline, manifest.approved.clone())?;
+        if current.is_empty() {
+            bail!(ErrorKind::InvalidProject.error("no approved ranges to render"));
+        }
+        // Segments from a different canvas can't be reused.
+        let canvas = format!("{width}x{height} {bg:?}");
+        if manifest.canvas.as_deref() != Some(canvas.as_str()) {
+            manifest.finals.clear();
+        }
+
+        let mut stats = ReviewStats {
+            changed: changed
+                .iter()
+                .map(|range| self.seconds(timeline, range.start, range.end))
+                .collect(),
+            ..ReviewStats::default()
+        };
+        let mut finals = Vec::with_capacity(current.len());
+        let mut segments = Vec::with_capacity(current.len());
+        for range in current {
+            let path = self.dir.join(format!("final_{:06}_{:06}.mp4", range.start, range.end));
+            segments.push(path.clone());
+            stats.segments += 1;
+            if manifest.finals.contains(&range) && path.exists() {
+                finals.push(range);
+                continue;
+            }
+
+            // Encoded under another name so an interrupted run never leaves a
+            // partial segment behind an up-to-date entry.
+            let partial = path.with_extension("partial.mp4");
+            let start = range.start as f32 / fps as f32;
+            let end = ((range.end as f32 + 0.5) / fps as f32).min(timeline.duration);
+            let mut encoder = FfmpegVideoEncoder::start(width, height, fps, &partial)?;
+            render(start, end, &mut encoder)?;
+            encoder.finish()?;
+            fs::rename(&partial, &path).with_context(|| {
+                ErrorKind::Encoder.error(format!("failed to store {}", path.display()))
+            })?;
+            stats.rendered += 1;
+            finals.push(range);
+
+            manifest.canvas = Some(canvas.clone());
+            manifest.finals = finals.clone();
+            self.write_manifest(fps, &manifest)?;
+        }
+        manifest.canvas = Some(canvas);
+        manifest.finals = finals;
+        self.write_manifest(fps, &manifest)?;
+        concat_videos(&segments, output)?;
+        Ok(stats)
+    }
+
+    // Forgets every approval and final segment.
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_file(self.dir.join(MANIFEST)) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err).context("failed to clear the review manifest"),
+        }
+    }
+
+    fn create_dir(&self) -> Result<()> {
+        fs::create_dir_all(&self.dir).with_context(|| {
+            ErrorKind::Encoder.error(format!("failed to create {}", self.dir.display()))
+        })
+    }
+
+    // `start..end` seconds as whole frames within the timeline.
+    fn frames(&self, timeline: &Timeline, start: f32, end: f32) -> Result<(u32, u32)> {
+        let fps = timeline.fps as f32;
+        let start = (start.max(0.0) * fps).round() as u32;
+        let end = ((end.max(0.0) * fps).round() as u32).min(timeline.total_frames());
+        if end <= start {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "review range must cover at least one frame, got frames {start}..{end}"
+            )));
+        }
+        Ok((start, end))
+    }
+
+    fn seconds(&self, timeline: &Timeline, start: u32, end: u32) -> (f32, f32) {
+        let fps = timeline.fps as f32;
+        (start as f32 / fps, end as f32 / fps)
+    }
+
+    // (still as approved, edited since).
+    fn split_changed(
+        &self,
+        timeline: &Timeline,
+        ranges: Vec<Range>,
+    ) -> Result<(Vec<Range>, Vec<Range>)> {
+        let mut current = Vec::new();
+        let mut changed = Vec::new();
+        for range in ranges {
+            if range.end <= timeline.total_frames()
+                && self.range_hash(timeline, range.start, range.end)? == range.hash
+            {
+                current.push(range);
+            } else {
+                changed.push(range);
+            }
+        }
+        Ok((current, changed))
+    }
+
+    fn range_hash(&self, timeline: &Timeline, start_frame: u32, end_frame: u32) -> Result<String> {
+        let mut hasher = Fnv1a::default();
+        for frame in start_frame..end_frame {
+            let t = frame as f32 / timeline.fps as f32;
+            let scene = match &self.data {
+                Some(data) => timeline.sample_from(t, data.as_ref())?,
+                None => timeline.sample(t)?,
+            };
+            hasher.write_bytes(&scene.fingerprint().to_le_bytes());
+        }
+        Ok(format!("{:016x}", hasher.0))
+    }
+
+    // The stored review, or an empty one if there is none or it was made at
+    // another frame rate.
+    fn read_manifest(&self, fps: u32) -> Manifest {
+        let Ok(source) = fs::read_to_string(self.dir.join(MANIFEST)) else {
+            return Manifest::default();
+        };
+        let Ok(manifest) = serde_json::from_str::<Value>(&source) else {
+            return Manifest::default();
+        };
+        if manifest["fps"].as_u64() != Some(fps as u64) {
+            return Manifest::default();
+        }
+        Manifest {
+            approved: ranges_from_json(&manifest["approved"]),
+            canvas: manifest["canvas"].as_str().map(str::to_string),
+            finals: ranges_from_json(&manifest["finals"]),
+        }
+    }
+
+    fn write_manifest(&self, fps: u32, manifest: &Manifest) -> Result<()> {
+        self.create_dir()?;
+        let path = self.dir.join(MANIFEST);
+        let document = json!({
+            "fps": fps,
+            "approved": ranges_to_json(&manifest.approved),
+            "canvas": manifest.canvas,
+            "finals": ranges_to_json(&manifest.finals),
+        });
+        fs::write(&path, document.to_string())
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+#[derive(Debug, Default)]
+struct Manifest {
+    approved: Vec<Range>,
+    // What the final segments were rendered at.
+    canvas: Option<String>,
+    finals: Vec<Range>,
+}
+
+fn ranges_to_json(ranges: &[Range]) -> Value {
+    ranges
+        .iter()
+        .map(|range| json!([range.start, range.end, range.hash]))
+        .collect()
+}
+
+fn ranges_from_json(value: &Value) -> Vec<Range> {
+    let Some(items) = value.as_array() else {
+        return Vec::new();
+    };
+    items
+        .iter()
+        .filter_map(|item| {
+            Some(Range {
+                start: item[0].as_u64()? as u32,
+                end: item[1].as_u64()? as u32,
+                hash: item[2].as_str()?.to_string(),
+            })
+        })
+        .collect()
+}
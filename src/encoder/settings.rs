@@ -0,0 +1,170 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::encoder::{ImageSequenceFormat, VideoCodec};
+use crate::error::ErrorKind;
+
+// Encoder options for one delivery target, e.g. a high-bitrate master, a
+// web upload or a broadcast spec with a fixed GOP. Each field left unset
+// keeps the codec preset's own choice.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncoderSettings {
+    // Constant quality; lower is better and larger.
+    pub crf: Option<u32>,
+    // Average bitrate in kbit/s. Takes precedence over `crf`.
+    pub bitrate: Option<u32>,
+    // Speed/size trade-off, e.g. `slow` for x264 or `p6` for NVENC.
+    pub preset: Option<String>,
+    // e.g. `high` or `main` for H.264, for players that need one.
+    pub profile: Option<String>,
+    // Frames between keyframes.
+    pub gop: Option<u32>,
+    // e.g. `yuv444p` or `yuv420p10le`.
+    pub pix_fmt: Option<String>,
+    // Numbered images in this format instead of a video.
+    pub frames: Option<ImageSequenceFormat>,
+}
+
+impl EncoderSettings {
+    // A JSON file like `{ "crf": 20, "preset": "slow", "gop": 60 }`, with
+    // any of `crf`, `bitrate`, `preset`, `profile`, `gop`, `pix_fmt` and
+    // `frames` (`png` or `exr`).
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = fs::read_to_string(path).with_context(|| {
+            ErrorKind::MissingAsset.error(format!(
+                "failed to read encoder settings: {}",
+                path.display()
+            ))
+        })?;
+        Self::from_json(&source)
+            .with_context(|| format!("failed to load encoder settings {}", path.display()))
+    }
+
+    pub fn from_json(source: &str) -> Result<Self> {
+        let doc: Value = serde_json::from_str(source).map_err(|err| {
+            ErrorKind::InvalidProject.error(format!("encoder settings are not valid json: {err}"))
+        })?;
+        let Some(fields) = doc.as_object() else {
+            bail!(ErrorKind::InvalidProject.error("encoder settings must be an object"));
+        };
+
+        let mut settings = Self::default();
+        for (key, value) in fields {
+            let number = || {
+                value.as_u64().map(|n| n as u32).ok_or_else(|| {
+                    ErrorKind::InvalidProject.error(format!("{key} must be a whole number"))
+                })
+            };
+            let string = || {
+                value.as_str().map(str::to_string).ok_or_else(|| {
+                    ErrorKind::InvalidProject.error(format!("{key} must be a string"))
+                })
+            };
+            match key.as_str() {
+                "crf" => settings.crf = Some(number()?),
+                "bitrate" => settings.bitrate = Some(number()?),
+                "preset" => settings.preset = Some(string()?),
+                "profile" => settings.profile = Some(string()?),
+                "gop" => settings.gop = Some(number()?),
+                "pix_fmt" => settings.pix_fmt = Some(string()?),
+                "frames" => {
+                    let name = string()?;
+                    let format = ImageSequenceFormat::from_name(&name).ok_or_else(|| {
+                        ErrorKind::InvalidProject
+                            .error(format!("frames must be png or exr, got {name}"))
+                    })?;
+                    settings.frames = Some(format);
+                }
+                _ => bail!(ErrorKind::InvalidProject.error(format!("unknown field \"{key}\""))),
+            }
+        }
+        Ok(settings)
+    }
+
+    pub fn with_crf(mut self, crf: u32) -> Self {
+        self.crf = Some(crf);
+        self
+    }
+
+    pub fn with_bitrate(mut self, kbps: u32) -> Self {
+        self.bitrate = Some(kbps);
+        self
+    }
+
+    pub fn with_preset(mut self, preset: impl Into<String>) -> Self {
+        self.preset = Some(preset.into());
+        self
+    }
+
+    pub fn with_profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    pub fn with_gop(mut self, gop: u32) -> Self {
+        self.gop = Some(gop);
+        self
+    }
+
+    pub fn with_pix_fmt(mut self, pix_fmt: impl Into<String>) -> Self {
+        self.pix_fmt = Some(pix_fmt.into());
+        self
+    }
+
+    pub fn with_frames(mut self, format: ImageSequenceFormat) -> Self {
+        self.frames = Some(format);
+        self
+    }
+
+    // These settings with each one `overrides` sets replaced, e.g. command
+    // line flags over a settings file. A CRF or bitrate there replaces both.
+    pub fn with_overrides(self, overrides: &EncoderSettings) -> Self {
+        let (crf, bitrate) = if overrides.crf.is_some() || overrides.bitrate.is_some() {
+            (overrides.crf, overrides.bitrate)
+        } else {
+            (self.crf, self.bitrate)
+        };
+        Self {
+            crf,
+            bitrate,
+            preset: overrides.preset.clone().or(self.preset),
+            profile: overrides.profile.clone().or(self.profile),
+            gop: overrides.gop.or(self.gop),
+            pix_fmt: overrides.pix_fmt.clone().or(self.pix_fmt),
+            frames: overrides.frames.or(self.frames),
+        }
+    }
+
+    // `codec` with these settings in place of its own.
+    pub fn apply(&self, codec: VideoCodec) -> VideoCodec {
+        let mut codec = match (self.bitrate, self.crf) {
+            (Some(kbps), _) => codec.with_bitrate(kbps),
+            (None, Some(crf)) => codec.with_crf(crf),
+            (None, None) => codec,
+        };
+        if let Some(preset) = &self.preset {
+            codec = codec.with_option("-preset", preset);
+        }
+        if let Some(profile) = &self.profile {
+            codec = codec.with_option("-profile:v", profile);
+        }
+        if let Some(gop) = self.gop {
+            codec = codec.with_option("-g", gop.to_string());
+        }
+        // Hardware frames keep their own format.
+        if let Some(pix_fmt) = &self.pix_fmt
+            && codec.filters.is_none()
+        {
+            codec = codec.with_pix_fmt(pix_fmt);
+        }
+        codec
+    }
+
+    // `apply` to each codec of a fallback chain.
+    pub fn apply_all(&self, codecs: &[VideoCodec]) -> Vec<VideoCodec> {
+        codecs.iter().map(|codec| self.apply(codec.clone())).collect()
+    }
+}
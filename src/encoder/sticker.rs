@@ -0,0 +1,179 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use crate::encoder::{FfmpegVideoEncoder, VideoCodec};
+use crate::error::ErrorKind;
+use crate::scene::Vec2;
+use crate::timeline::Timeline;
+
+// Containers that keep an alpha channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StickerFormat {
+    // VP9 with alpha; small, and plays in browsers.
+    WebM,
+    // ProRes 4444 in a .mov, for editing software.
+    ProRes,
+    // Animated PNG, looping forever; lossless but large.
+    Apng,
+}
+
+impl StickerFormat {
+    // From the output extension: `.webm`, `.mov`, `.png` or `.apng`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "webm" => Some(StickerFormat::WebM),
+            "mov" => Some(StickerFormat::ProRes),
+            "png" | "apng" => Some(StickerFormat::Apng),
+            _ => None,
+        }
+    }
+
+    pub fn codec(self) -> VideoCodec {
+        match self {
+            StickerFormat::WebM => VideoCodec::vp9_alpha().with_crf(30),
+            StickerFormat::ProRes => VideoCodec::prores_4444(),
+            StickerFormat::Apng => VideoCodec::new("apng")
+                .with_pix_fmt("rgba")
+                .with_args(["-plays", "0", "-f", "apng"]),
+        }
+    }
+}
+
+// Renders one named clip, with its animation, on its own against
+// transparency and cropped to the area it covers, so an element built in a
+// project can be reused as a standalone animated asset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StickerExport {
+    pub clip: String,
+    pub format: StickerFormat,
+    // Transparent margin around the clip's pixels.
+    pub padding: u32,
+}
+
+// What was written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sticker {
+    pub width: u32,
+    pub height: u32,
+    // Centre of the cropped area in the project's graph coords, for placing
+    // the sticker back where the clip was.
+    pub center: Vec2,
+    pub frames: u32,
+}
+
+impl StickerExport {
+    pub fn new(clip: impl Into<String>, format: StickerFormat) -> Self {
+        Self {
+            clip: clip.into(),
+            format,
+            padding: 2,
+        }
+    }
+
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    // Renders the clip's time range twice through
+    // `render(timeline, start, end, on_frame)`: once to find the area it ever
+    // draws on, then to encode that area. Use a renderer with a transparent
+    // background, e.g. `RaylibRender::new(w, h, Color::rgba(0, 0, 0, 0))`
+    // driving `render_timeline_rgba`.
+    pub fn export<R>(
+        &self,
+        timeline: &Timeline,
+        width: u32,
+        height: u32,
+        output: &Path,
+        mut render: R,
+    ) -> Result<Sticker>
+    where
+        R: FnMut(&Timeline, f32, f32, &mut dyn FnMut(&[u8]) -> Result<()>) -> Result<()>,
+    {
+        let isolated = timeline.isolate_clip(&self.clip)?;
+        let clip = &isolated.layers[0].clips[0];
+        let (start, end) = (clip.start, clip.end.min(isolated.duration));
+
+        // Drawn area over every frame, in bottom-up rows: (x0, y0, x1, y1).
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+        let mut frames = 0u32;
+        render(&isolated, start, end, &mut |rgba| {
+            if frames == 0 && rgba.chunks_exact(4).all(|pixel| pixel[3] == 255) {
+                bail!(ErrorKind::InvalidProject.error(
+                    "sticker frames are opaque; render them with a transparent background"
+                ));
+            }
+            frames += 1;
+            for (index, pixel) in rgba.chunks_exact(4).enumerate() {
+                if pixel[3] == 0 {
+                    continue;
+                }
+                let (x, y) = (index as u32 % width, index as u32 / width);
+                bounds = Some(match bounds {
+                    Some((x0, y0, x1, y1)) => {
+                        (x0.min(x), y0.min(y), x1.max(x + 1), y1.max(y + 1))
+                    }
+                    None => (x, y, x + 1, y + 1),
+                });
+            }
+            Ok(())
+        })?;
+        let Some((x0, y0, x1, y1)) = bounds else {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "clip \"{}\" draws nothing to export",
+                self.clip
+            )));
+        };
+
+        let (x0, x1) = padded(x0, x1, self.padding, width);
+        let (y0, y1) = padded(y0, y1, self.padding, height);
+        let (crop_width, crop_height) = (x1 - x0, y1 - y0);
+        let mut encoder = FfmpegVideoEncoder::start_with_codecs(
+            crop_width,
+            crop_height,
+            isolated.fps,
+            output,
+            &[],
+            &[self.format.codec()],
+        )?;
+        let mut cropped = Vec::with_capacity((crop_width * crop_height * 4) as usize);
+        render(&isolated, start, end, &mut |rgba| {
+            cropped.clear();
+            for row in y0..y1 {
+                let from = ((row * width + x0) * 4) as usize;
+                cropped.extend_from_slice(&rgba[from..from + crop_width as usize * 4]);
+            }
+            encoder.write_frame(&cropped)
+        })?;
+        encoder.finish()?;
+
+        Ok(Sticker {
+            width: crop_width,
+            height: crop_height,
+            // Rows count up from the bottom, as graph y does.
+            center: Vec2 {
+                x: (x0 + x1) as f32 / 2.0 - width as f32 / 2.0,
+                y: (y0 + y1) as f32 / 2.0 - height as f32 / 2.0,
+            },
+            frames,
+        })
+    }
+}
+
+// Widens `start..end` by `padding` within `0..limit`, then to an even length
+// where it can, since 4:2:0 video needs even sizes.
+fn padded(start: u32, end: u32, padding: u32, limit: u32) -> (u32, u32) {
+    let mut start = start.saturating_sub(padding);
+    let mut end = (end + padding).min(limit);
+    if (end - start) % 2 == 1 {
+        if end < limit {
+            end += 1;
+        } else {
+            start = start.saturating_sub(1);
+        }
+    }
+    (start, end)
+}
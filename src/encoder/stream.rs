@@ -0,0 +1,126 @@
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+
+use crate::error::ErrorKind;
+
+// Somewhere other than a single video file to send an encode to, for
+// rendering live, e.g. an animated dashboard (see
+// `FfmpegVideoEncoder::start_stream`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamOutput {
+    // An RTMP ingest URL such as `rtmp://live.example.com/app/<key>`, as
+    // FLV. Frames are sent at the frame rate, however fast they render.
+    Rtmp { url: String },
+    // A playlist plus numbered `.ts` segments next to it, each about
+    // `segment_seconds` long. With a `list_size`, the playlist is a live
+    // window of that many segments, older ones are deleted, and frames are
+    // sent at the frame rate; without one it lists every segment once
+    // rendering ends.
+    Hls {
+        playlist: PathBuf,
+        segment_seconds: f32,
+        list_size: Option<u32>,
+    },
+}
+
+impl StreamOutput {
+    pub fn rtmp(url: impl Into<String>) -> Self {
+        StreamOutput::Rtmp { url: url.into() }
+    }
+
+    pub fn hls(playlist: impl Into<PathBuf>, segment_seconds: f32) -> Self {
+        StreamOutput::Hls {
+            playlist: playlist.into(),
+            segment_seconds,
+            list_size: None,
+        }
+    }
+
+    pub fn live_hls(playlist: impl Into<PathBuf>, segment_seconds: f32, list_size: u32) -> Self {
+        StreamOutput::Hls {
+            playlist: playlist.into(),
+            segment_seconds,
+            list_size: Some(list_size),
+        }
+    }
+
+    // Whether frames must be held back to the frame rate.
+    pub(crate) fn realtime(&self) -> bool {
+        match self {
+            StreamOutput::Rtmp { .. } => true,
+            StreamOutput::Hls { list_size, .. } => list_size.is_some(),
+        }
+    }
+
+    // Output options, before `target`.
+    pub(crate) fn ffmpeg_args(&self) -> Result<Vec<String>> {
+        let args = match self {
+            StreamOutput::Rtmp { url } => {
+                if !url.starts_with("rtmp://") && !url.starts_with("rtmps://") {
+                    bail!(ErrorKind::InvalidProject.error(format!("not an RTMP url: {url}")));
+                }
+                // Players joining mid-stream wait for the next keyframe.
+                vec![
+                    "-force_key_frames".to_string(),
+                    "expr:gte(t,n_forced*2)".to_string(),
+                    "-f".to_string(),
+                    "flv".to_string(),
+                ]
+            }
+            StreamOutput::Hls {
+                playlist,
+                segment_seconds,
+                list_size,
+            } => {
+                if *segment_seconds <= 0.0 {
+                    bail!(ErrorKind::InvalidProject.error("hls segment length must be > 0"));
+                }
+                if list_size == &Some(0) {
+                    bail!(ErrorKind::InvalidProject.error("live hls list size must be > 0"));
+                }
+                let stem = playlist
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "stream".to_string());
+                let segments = playlist.with_file_name(format!("{stem}_%05d.ts"));
+                let mut args = vec![
+                    // Segments can only start on a keyframe.
+                    "-force_key_frames".to_string(),
+                    format!("expr:gte(t,n_forced*{segment_seconds})"),
+                    "-f".to_string(),
+                    "hls".to_string(),
+                    "-hls_time".to_string(),
+                    segment_seconds.to_string(),
+                    "-hls_segment_filename".to_string(),
+                    segments.to_string_lossy().to_string(),
+                ];
+                match list_size {
+                    Some(size) => args.extend([
+                        "-hls_list_size".to_string(),
+                        size.to_string(),
+                        "-hls_flags".to_string(),
+                        "delete_segments+independent_segments".to_string(),
+                    ]),
+                    None => args.extend([
+                        "-hls_list_size".to_string(),
+                        "0".to_string(),
+                        "-hls_playlist_type".to_string(),
+                        "vod".to_string(),
+                    ]),
+                }
+                args
+            }
+        };
+        Ok(args)
+    }
+
+    // What ffmpeg writes to in place of an output path.
+    pub(crate) fn target(&self) -> &OsStr {
+        match self {
+            StreamOutput::Rtmp { url } => OsStr::new(url),
+            StreamOutput::Hls { playlist, .. } => playlist.as_os_str(),
+        }
+    }
+}
@@ -1,11 +1,13 @@
 use std::fs::File;
-use std::io::Write;
+use std::io::{ErrorKind as IoErrorKind, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
 
 use anyhow::{bail, Context, Result};
 
-use crate::video::{resolve_segments, VideoClip};
+use crate::error::ErrorKind;
+use crate::timeline::ValidationReport;
+use crate::video::{resolve_segments, VideoClip, VideoSegment};
 
 #[derive(Debug, Clone, Copy)]
 pub struct VideoMetadata {
@@ -26,13 +28,13 @@ pub fn ffprobe_metadata(path: &Path) -> Result<VideoMetadata> {
         .arg("default=nw=1")
         .arg(path)
         .output()
-        .context("failed to run ffprobe")?;
+        .context(ErrorKind::Encoder.error("failed to run ffprobe"))?;
 
     if !output.status.success() {
-        bail!(
+        bail!(ErrorKind::Encoder.error(format!(
             "ffprobe failed: {}",
             String::from_utf8_lossy(&output.stderr)
-        );
+        )));
     }
 
     let stdout = String::from_utf8_lossy(&output.stdout);
@@ -50,9 +52,9 @@ pub fn ffprobe_metadata(path: &Path) -> Result<VideoMetadata> {
     }
 
     Ok(VideoMetadata {
-        width: width.context("ffprobe missing width")?,
-        height: height.context("ffprobe missing height")?,
-        fps: fps.context("ffprobe missing fps")?,
+        width: width.context(ErrorKind::Encoder.error("ffprobe missing width"))?,
+        height: height.context(ErrorKind::Encoder.error("ffprobe missing height"))?,
+        fps: fps.context(ErrorKind::Encoder.error("ffprobe missing fps"))?,
     })
 }
 
@@ -90,10 +92,10 @@ pub fn normalize_if_needed(
         .arg(&output)
         .stderr(Stdio::piped())
         .status()
-        .context("failed to run ffmpeg normalize")?;
+        .context(ErrorKind::Encoder.error("failed to run ffmpeg normalize"))?;
 
     if !status.success() {
-        bail!("ffmpeg normalize failed for {}", input.display());
+        bail!(ErrorKind::Encoder.error(format!("ffmpeg normalize failed for {}", input.display())));
     }
 
     Ok(output)
@@ -108,9 +110,13 @@ pub fn build_base_video(
     temp_dir: &Path,
     keep_temp: bool,
 ) -> Result<()> {
+    // A source deleted since its clip was made fails here, before any work.
+    ValidationReport::default()
+        .with_video_clips(clips)
+        .check()?;
     let segments = resolve_segments(clips);
     if segments.is_empty() {
-        bail!("no video segments to render");
+        bail!(ErrorKind::InvalidProject.error("no video segments to render"));
     }
 
     std::fs::create_dir_all(temp_dir).context("failed to create temp dir")?;
@@ -135,6 +141,12 @@ pub fn build_base_video(
 
         let seg_duration = segment.timeline_end - segment.timeline_start;
         let seg_output = temp_dir.join(format!("segment_{:03}.mp4", seg_index));
+        if clip.time_map.is_some() {
+            let size = (target_width, target_height);
+            render_remapped_segment(&normalized, clip, segment, size, target_fps, &seg_output)?;
+            segment_paths.push(seg_output);
+            continue;
+        }
 
         let mut cmd = Command::new("ffmpeg");
         cmd.arg("-y")
@@ -164,9 +176,11 @@ pub fn build_base_video(
                 .arg(&seg_output);
         }
 
-        let status = cmd.status().context("failed to run ffmpeg segment")?;
+        let status = cmd
+            .status()
+            .context(ErrorKind::Encoder.error("failed to run ffmpeg segment"))?;
         if !status.success() {
-            bail!("ffmpeg segment render failed");
+            bail!(ErrorKind::Encoder.error("ffmpeg segment render failed"));
         }
 
         segment_paths.push(seg_output);
@@ -174,7 +188,194 @@ pub fn build_base_video(
 
     let list_path = temp_dir.join("concat_list.txt");
     write_concat_list(&list_path, &segment_paths)?;
+    run_concat(&list_path, output_path)?;
+
+    if !keep_temp {
+        let _ = std::fs::remove_file(&list_path);
+        for path in &segment_paths {
+            let _ = std::fs::remove_file(path);
+        }
+        for path in normalized_cache {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    Ok(())
+}
+
+// Encodes a segment of a clip with a `time_map`, which `-ss`/`-t` can't
+// express, by picking the source frame for every output frame: frames are
+// decoded in order and repeated to slow down or hold, skipped to speed up,
+// and decoded again from an earlier point to play backwards.
+fn render_remapped_segment(
+    source: &Path,
+    clip: &VideoClip,
+    segment: &VideoSegment,
+    (width, height): (u32, u32),
+    fps: u32,
+    output: &Path,
+) -> Result<()> {
+    let mut encoder = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-f")
+        .arg("rawvideo")
+        .arg("-pix_fmt")
+        .arg("rgba")
+        .arg("-s")
+        .arg(format!("{width}x{height}"))
+        .arg("-r")
+        .arg(fps.to_string())
+        .arg("-i")
+        .arg("-")
+        .arg("-c:v")
+        .arg("libx264")
+        .arg("-pix_fmt")
+        .arg("yuv420p")
+        .arg(output)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context(ErrorKind::Encoder.error("failed to run ffmpeg segment"))?;
+    let stdin = encoder
+        .stdin
+        .take()
+        .context(ErrorKind::Encoder.error("failed to open ffmpeg stdin"))?;
+    let piped = pipe_remapped_frames(stdin, source, clip, segment, (width, height), fps);
+    if piped.is_err() {
+        let _ = encoder.kill();
+    }
+    let status = encoder
+        .wait()
+        .context(ErrorKind::Encoder.error("failed to wait for ffmpeg"))?;
+    piped?;
+    if !status.success() {
+        bail!(ErrorKind::Encoder.error("ffmpeg segment render failed"));
+    }
+    Ok(())
+}
+
+fn pipe_remapped_frames(
+    mut stdin: ChildStdin,
+    source: &Path,
+    clip: &VideoClip,
+    segment: &VideoSegment,
+    (width, height): (u32, u32),
+    fps: u32,
+) -> Result<()> {
+    let fps_f = fps as f32;
+    let frames = ((segment.timeline_end - segment.timeline_start) * fps_f).round() as u32;
+    let mut frame = vec![0; width as usize * height as usize * 4];
+    let mut decoder: Option<SourceFrames> = None;
+    let mut have_frame = false;
+    for index in 0..frames {
+        let local_t = segment.timeline_start - clip.start_time + index as f32 / fps_f;
+        let wanted = (clip.source_time(local_t) * fps_f).round() as u64;
+        // The frame in hand is `next - 1`; anything earlier means seeking back.
+        let decoder = match &mut decoder {
+            Some(frames) if wanted + 1 >= frames.next => frames,
+            _ => decoder.insert(SourceFrames::start(source, wanted, (width, height), fps)?),
+        };
+        have_frame |= decoder.read_to(wanted, &mut frame)?;
+        if !have_frame {
+            bail!(ErrorKind::Encoder.error(format!(
+                "no frame at {:.3}s in {}",
+                wanted as f32 / fps_f,
+                source.display()
+            )));
+        }
+        stdin
+            .write_all(&frame)
+            .context(ErrorKind::Encoder.error("failed to write frame to ffmpeg"))?;
+    }
+    Ok(())
+}
+
+// Raw RGBA frames of a source file from a given frame on, as `fps` frames
+// per second.
+struct SourceFrames {
+    child: Child,
+    stdout: ChildStdout,
+    // Index of the frame the next read returns.
+    next: u64,
+    ended: bool,
+}
+
+impl SourceFrames {
+    fn start(source: &Path, from: u64, (width, height): (u32, u32), fps: u32) -> Result<Self> {
+        let mut child = Command::new("ffmpeg")
+            .arg("-loglevel")
+            .arg("error")
+            .arg("-ss")
+            .arg(format!("{:.3}", from as f32 / fps as f32))
+            .arg("-i")
+            .arg(source)
+            .arg("-an")
+            .arg("-vf")
+            .arg(format!("scale={}x{}", width, height))
+            .arg("-r")
+            .arg(fps.to_string())
+            .arg("-f")
+            .arg("rawvideo")
+            .arg("-pix_fmt")
+            .arg("rgba")
+            .arg("-")
+            .stdout(Stdio::piped())
+            .spawn()
+            .context(ErrorKind::Encoder.error("failed to run ffmpeg decode"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .context(ErrorKind::Encoder.error("failed to open ffmpeg stdout"))?;
+        Ok(Self {
+            child,
+            stdout,
+            next: from,
+            ended: false,
+        })
+    }
+
+    // Reads up to frame `index` into `frame`, returning whether it read any.
+    // Past the end of the source, the last frame read stays in `frame`.
+    fn read_to(&mut self, index: u64, frame: &mut [u8]) -> Result<bool> {
+        let mut read = false;
+        while !self.ended && self.next <= index {
+            match self.stdout.read_exact(frame) {
+                Ok(()) => {
+                    self.next += 1;
+                    read = true;
+                }
+                Err(err) if err.kind() == IoErrorKind::UnexpectedEof => self.ended = true,
+                Err(err) => {
+                    return Err(err).context(ErrorKind::Encoder.error("failed to decode frames"));
+                }
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl Drop for SourceFrames {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
 
+// Joins videos with identical encoding settings (e.g. segments rendered by
+// farm workers) without re-encoding.
+pub fn concat_videos(segments: &[PathBuf], output_path: &Path) -> Result<()> {
+    if segments.is_empty() {
+        bail!(ErrorKind::InvalidProject.error("no videos to concat"));
+    }
+    let list_path = output_path.with_extension("concat.txt");
+    write_concat_list(&list_path, segments)?;
+    let result = run_concat(&list_path, output_path);
+    let _ = std::fs::remove_file(&list_path);
+    result
+}
+
+fn run_concat(list_path: &Path, output_path: &Path) -> Result<()> {
     let status = Command::new("ffmpeg")
         .arg("-y")
         .arg("-loglevel")
@@ -184,27 +385,16 @@ pub fn build_base_video(
         .arg("-safe")
         .arg("0")
         .arg("-i")
-        .arg(&list_path)
+        .arg(list_path)
         .arg("-c")
         .arg("copy")
         .arg(output_path)
         .status()
-        .context("failed to run ffmpeg concat")?;
+        .context(ErrorKind::Encoder.error("failed to run ffmpeg concat"))?;
 
     if !status.success() {
-        bail!("ffmpeg concat failed");
+        bail!(ErrorKind::Encoder.error("ffmpeg concat failed"));
     }
-
-    if !keep_temp {
-        let _ = std::fs::remove_file(&list_path);
-        for path in &segment_paths {
-            let _ = std::fs::remove_file(path);
-        }
-        for path in normalized_cache {
-            let _ = std::fs::remove_file(path);
-        }
-    }
-
     Ok(())
 }
 
@@ -0,0 +1,54 @@
+// Copies bottom-up RGBA (OpenGL's row order) into `out` top-down, the way
+// ffmpeg reads it.
+pub(crate) fn flip_rgba(frame: &[u8], width: u32, out: &mut Vec<u8>) {
+    out.clear();
+    out.reserve(frame.len());
+    for row in frame.chunks_exact(width as usize * 4).rev() {
+        out.extend_from_slice(row);
+    }
+}
+
+// Converts bottom-up RGBA to top-down planar YUV 4:2:0 (Y, then U, then V),
+// BT.709 limited range to match the tags `OutputColor::ffmpeg_args` writes.
+// Alpha is dropped and each chroma sample averages a 2x2 block, so `width`
+// and `height` must be even. 3/8 the size of the RGBA it replaces.
+pub(crate) fn rgba_to_yuv420p(frame: &[u8], width: u32, height: u32, out: &mut Vec<u8>) {
+    let (width, height) = (width as usize, height as usize);
+    let luma = width * height;
+    let chroma = luma / 4;
+    out.clear();
+    out.resize(luma + chroma * 2, 0);
+    let (y_plane, uv) = out.split_at_mut(luma);
+    let (u_plane, v_plane) = uv.split_at_mut(chroma);
+
+    let row = width * 4;
+    for y in 0..height {
+        let src = &frame[(height - 1 - y) * row..][..row];
+        let dst = &mut y_plane[y * width..][..width];
+        for (px, out) in src.chunks_exact(4).zip(dst) {
+            *out = luma_of(px[0] as i32, px[1] as i32, px[2] as i32);
+        }
+    }
+    for cy in 0..height / 2 {
+        let top = &frame[(height - 1 - cy * 2) * row..][..row];
+        let bottom = &frame[(height - 2 - cy * 2) * row..][..row];
+        for cx in 0..width / 2 {
+            let at = cx * 8;
+            let sum = |c: usize| {
+                top[at + c] as i32
+                    + top[at + 4 + c] as i32
+                    + bottom[at + c] as i32
+                    + bottom[at + 4 + c] as i32
+            };
+            let (r, g, b) = ((sum(0) + 2) / 4, (sum(1) + 2) / 4, (sum(2) + 2) / 4);
+            let i = cy * (width / 2) + cx;
+            // 8-bit fixed point; each row sums to 0 so greys stay neutral.
+            u_plane[i] = (128 + ((-26 * r - 86 * g + 112 * b + 128) >> 8)) as u8;
+            v_plane[i] = (128 + ((112 * r - 102 * g - 10 * b + 128) >> 8)) as u8;
+        }
+    }
+}
+
+fn luma_of(r: i32, g: i32, b: i32) -> u8 {
+    (16 + ((47 * r + 157 * g + 16 * b + 128) >> 8)) as u8
+}
@@ -0,0 +1,77 @@
+use std::fmt;
+
+// Broad failure categories so wrappers (scripts, CI, farm workers) can branch
+// on why a render failed without parsing messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    InvalidProject,
+    MissingAsset,
+    Encoder,
+    Gpu,
+    Cancelled,
+    // A `RenderLimits` cap was hit under `LimitPolicy::Abort`.
+    LimitExceeded,
+    // A timeline `Assertion` failed on a rendered frame.
+    AssertionFailed,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::InvalidProject => 2,
+            ErrorKind::MissingAsset => 3,
+            ErrorKind::Encoder => 4,
+            ErrorKind::Gpu => 5,
+            ErrorKind::LimitExceeded => 6,
+            ErrorKind::AssertionFailed => 7,
+            // Same code a shell reports for a process stopped by Ctrl-C.
+            ErrorKind::Cancelled => 130,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::InvalidProject => "invalid project",
+            ErrorKind::MissingAsset => "missing asset",
+            ErrorKind::Encoder => "encoder failure",
+            ErrorKind::Gpu => "gpu failure",
+            ErrorKind::Cancelled => "cancelled",
+            ErrorKind::LimitExceeded => "limit exceeded",
+            ErrorKind::AssertionFailed => "assertion failed",
+        }
+    }
+
+    pub fn error(self, message: impl Into<String>) -> RenderError {
+        RenderError {
+            kind: self,
+            message: message.into(),
+        }
+    }
+}
+
+// Exit code for errors that carry no `RenderError` anywhere in their chain.
+pub const EXIT_FAILURE: i32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+// Finds the outermost `RenderError`, whether it was the root error or
+// attached with `.context(...)`.
+pub fn error_kind(err: &anyhow::Error) -> Option<ErrorKind> {
+    err.downcast_ref::<RenderError>().map(|err| err.kind)
+}
+
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    error_kind(err).map_or(EXIT_FAILURE, ErrorKind::exit_code)
+}
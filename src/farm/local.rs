@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+
+use anyhow::{bail, Context, Result};
+
+use crate::encoder::concat_videos;
+use crate::error::ErrorKind;
+use crate::farm::queue::{Coordinator, FileQueue, QueueStatus, SegmentTask};
+use crate::project::Project;
+
+// Renders a project with several worker processes on this machine. GL
+// contexts are single-threaded, so parallel rendering needs one process (and
+// one context) per worker. The frame range is split into segments on a
+// `FileQueue` in a work directory, the workers render them, and the finished
+// segments are joined without re-encoding.
+#[derive(Debug, Clone)]
+pub struct LocalFarm {
+    workers: u32,
+    segments: Option<u32>,
+    work_dir: Option<PathBuf>,
+    keep_segments: bool,
+}
+
+impl LocalFarm {
+    pub fn new(workers: u32) -> Self {
+        Self {
+            workers: workers.max(1),
+            segments: None,
+            work_dir: None,
+            keep_segments: false,
+        }
+    }
+
+    // Defaults to two per worker, so a worker that finishes early picks up
+    // more of the timeline rather than sitting idle.
+    pub fn with_segments(mut self, segments: u32) -> Self {
+        self.segments = Some(segments.max(1));
+        self
+    }
+
+    // Holds the queue and segment files. Defaults to `<output>.farm/`.
+    pub fn with_work_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.work_dir = Some(dir.into());
+        self
+    }
+
+    // Keeps the work directory after a successful render. It's always kept
+    // after a failed one, for the errors in `queue/failed/`.
+    pub fn with_keep_segments(mut self, keep: bool) -> Self {
+        self.keep_segments = keep;
+        self
+    }
+
+    // Renders `project_path` to `output`. `spawn(queue_dir, index)` builds the
+    // command for one worker process, which must run a `Worker` on
+    // `FileQueue::open(queue_dir)` (e.g. `render_farm worker <queue_dir>`).
+    // Workers that die are covered by the others once their lease expires.
+    pub fn render(
+        &self,
+        project_path: &Path,
+        output: &Path,
+        mut spawn: impl FnMut(&Path, u32) -> Command,
+    ) -> Result<QueueStatus> {
+        let project = Project::load(project_path)?;
+        let project_path = project_path
+            .canonicalize()
+            .unwrap_or_else(|_| project_path.to_path_buf());
+        let work_dir = match &self.work_dir {
+            Some(dir) => dir.clone(),
+            None => output.with_extension("farm"),
+        };
+        let queue_dir = work_dir.join("queue");
+        let segment_dir = work_dir.join("segments");
+        fs::create_dir_all(&segment_dir).with_context(|| {
+            format!("failed to create segment directory: {}", segment_dir.display())
+        })?;
+
+        let queue = FileQueue::open(&queue_dir)?;
+        queue.clear()?;
+        let segments = self.segments.unwrap_or(self.workers * 2);
+        let total_frames = project.timeline.total_frames();
+        let tasks = SegmentTask::split(&project_path, total_frames, segments, &segment_dir);
+        for task in &tasks {
+            queue.enqueue(task)?;
+        }
+
+        let workers = self.workers.min(tasks.len() as u32);
+        let mut children: Vec<Child> = Vec::new();
+        for index in 0..workers {
+            match spawn(&queue_dir, index).spawn() {
+                Ok(child) => children.push(child),
+                Err(err) => {
+                    for child in &mut children {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                    }
+                    return Err(err).context("failed to start a farm worker");
+                }
+            }
+        }
+        let mut crashed = 0;
+        for child in &mut children {
+            let exit = child.wait().context("failed to wait for a farm worker")?;
+            if !exit.success() {
+                crashed += 1;
+            }
+        }
+
+        let status = queue.status()?;
+        if !status.is_drained() || status.failed > 0 {
+            bail!(ErrorKind::Encoder.error(format!(
+                "{} of {} segments didn't render ({crashed} of {workers} workers exited with \
+                 an error); see {}",
+                tasks.len() - status.done,
+                tasks.len(),
+                queue_dir.join("failed").display()
+            )));
+        }
+        concat_videos(&queue.done_outputs()?, output)?;
+        if !self.keep_segments {
+            fs::remove_dir_all(&work_dir).with_context(|| {
+                format!("failed to remove farm work directory: {}", work_dir.display())
+            })?;
+        }
+        Ok(status)
+    }
+}
@@ -0,0 +1,9 @@
+#[cfg(feature = "ffmpeg")]
+pub mod local;
+pub mod queue;
+pub mod worker;
+
+#[cfg(feature = "ffmpeg")]
+pub use local::LocalFarm;
+pub use queue::{Coordinator, FileQueue, Lease, QueueStatus, SegmentTask};
+pub use worker::{Worker, WorkerEvent};
@@ -0,0 +1,378 @@
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Map, Value};
+
+use crate::error::ErrorKind;
+use crate::timeline::TimeRange;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentTask {
+    pub id: String,
+    pub project: PathBuf,
+    // Frames `start_frame..end_frame` of the project's timeline.
+    pub start_frame: u32,
+    pub end_frame: u32,
+    pub output: PathBuf,
+    // Failed or expired leases so far.
+    pub attempts: u32,
+}
+
+impl SegmentTask {
+    // Splits `total_frames` into `count` contiguous segments, each rendered to
+    // its own file in `out_dir`. Ids sort in timeline order.
+    pub fn split(project: &Path, total_frames: u32, count: u32, out_dir: &Path) -> Vec<Self> {
+        let count = count.clamp(1, total_frames.max(1));
+        (0..count)
+            .map(|index| {
+                let id = format!("segment_{index:04}");
+                Self {
+                    output: out_dir.join(format!("{id}.mp4")),
+                    id,
+                    project: project.to_path_buf(),
+                    start_frame: (total_frames as u64 * index as u64 / count as u64) as u32,
+                    end_frame: (total_frames as u64 * (index as u64 + 1) / count as u64) as u32,
+                    attempts: 0,
+                }
+            })
+            .filter(|task| task.end_frame > task.start_frame)
+            .collect()
+    }
+
+    // Render range in seconds. The end is padded by half a frame so float
+    // rounding can't drop the segment's last frame.
+    pub fn time_range(&self, fps: u32, duration: f32) -> (f32, f32) {
+        let fps = fps as f32;
+        let start = self.start_frame as f32 / fps;
+        let end = ((self.end_frame as f32 + 0.5) / fps).min(duration);
+        (start, end)
+    }
+
+    // Whether any frame of the segment falls in one of `changed` (see
+    // `Timeline::changed_ranges`).
+    pub fn is_affected(&self, fps: u32, changed: &[TimeRange]) -> bool {
+        let start = self.start_frame as f32 / fps as f32;
+        let end = self.end_frame as f32 / fps as f32;
+        changed.iter().any(|range| range.overlaps(start, end))
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "project": self.project.to_string_lossy(),
+            "start_frame": self.start_frame,
+            "end_frame": self.end_frame,
+            "output": self.output.to_string_lossy(),
+            "attempts": self.attempts,
+        })
+    }
+
+    fn from_json(fields: &Map<String, Value>) -> Result<Self> {
+        let missing = |key: &str| ErrorKind::InvalidProject.error(format!("task is missing {key}"));
+        let text = |key: &str| {
+            fields
+                .get(key)
+                .and_then(Value::as_str)
+                .with_context(|| missing(key))
+        };
+        let count = |key: &str| {
+            fields
+                .get(key)
+                .and_then(Value::as_u64)
+                .map(|value| value as u32)
+                .with_context(|| missing(key))
+        };
+        Ok(Self {
+            id: text("id")?.to_string(),
+            project: PathBuf::from(text("project")?),
+            start_frame: count("start_frame")?,
+            end_frame: count("end_frame")?,
+            output: PathBuf::from(text("output")?),
+            attempts: count("attempts")?,
+        })
+    }
+}
+
+// A task claimed by `worker`. It stays claimed only while the worker keeps
+// sending heartbeats.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lease {
+    pub task: SegmentTask,
+    pub worker: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueStatus {
+    pub pending: usize,
+    pub leased: usize,
+    pub done: usize,
+    pub failed: usize,
+}
+
+impl QueueStatus {
+    // Nothing left to claim and nothing in flight.
+    pub fn is_drained(&self) -> bool {
+        self.pending == 0 && self.leased == 0
+    }
+}
+
+// Hands out segment tasks to workers. `FileQueue` is the built-in
+// implementation; an HTTP coordinator only needs to implement these calls.
+pub trait Coordinator: Send + Sync {
+    fn claim(&self, worker: &str) -> Result<Option<Lease>>;
+    fn heartbeat(&self, lease: &Lease) -> Result<()>;
+    fn complete(&self, lease: &Lease) -> Result<()>;
+    // Re-queues the task, or gives up on it after too many attempts.
+    fn fail(&self, lease: &Lease, error: &str) -> Result<()>;
+    fn status(&self) -> Result<QueueStatus>;
+}
+
+// A coordinator on a shared directory (local disk or a network mount). Each
+// task is a JSON file that moves between `pending/`, `leased/`, `done/` and
+// `failed/`; claims are atomic renames, and a lease's heartbeat is its file's
+// modification time.
+#[derive(Debug, Clone)]
+pub struct FileQueue {
+    root: PathBuf,
+    lease_timeout: Duration,
+    max_attempts: u32,
+}
+
+const PENDING: &str = "pending";
+const LEASED: &str = "leased";
+const DONE: &str = "done";
+const FAILED: &str = "failed";
+
+impl FileQueue {
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        for dir in [PENDING, LEASED, DONE, FAILED] {
+            let path = root.join(dir);
+            fs::create_dir_all(&path)
+                .with_context(|| format!("failed to create queue directory: {}", path.display()))?;
+        }
+        Ok(Self {
+            root,
+            lease_timeout: Duration::from_secs(60),
+            max_attempts: 3,
+        })
+    }
+
+    // Leases without a heartbeat for this long are handed to another worker.
+    pub fn with_lease_timeout(mut self, timeout: Duration) -> Self {
+        self.lease_timeout = timeout;
+        self
+    }
+
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    pub fn enqueue(&self, task: &SegmentTask) -> Result<()> {
+        self.write(PENDING, &task.id, task.to_json())
+    }
+
+    // Records a task as done without rendering it, for segments whose output
+    // from an earlier render is still valid.
+    pub fn mark_done(&self, task: &SegmentTask) -> Result<()> {
+        self.write(DONE, &task.id, task.to_json())
+    }
+
+    // Drops every task, e.g. before queueing a new render into the same
+    // directory. Segment outputs are left in place.
+    pub fn clear(&self) -> Result<()> {
+        for dir in [PENDING, LEASED, DONE, FAILED] {
+            for id in self.ids(dir)? {
+                let path = self.path(dir, &id);
+                fs::remove_file(&path)
+                    .with_context(|| format!("failed to remove task: {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Finished segments in timeline order, ready for `concat_videos`.
+    pub fn done_outputs(&self) -> Result<Vec<PathBuf>> {
+        self.ids(DONE)?
+            .iter()
+            .map(|id| Ok(self.read(DONE, id)?.0.output))
+            .collect()
+    }
+
+    // Moves leases whose worker stopped sending heartbeats back to pending.
+    pub fn requeue_expired(&self) -> Result<usize> {
+        let mut requeued = 0;
+        for id in self.ids(LEASED)? {
+            let path = self.path(LEASED, &id);
+            let Ok(modified) = fs::metadata(&path).and_then(|meta| meta.modified()) else {
+                continue;
+            };
+            let age = SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default();
+            if age < self.lease_timeout {
+                continue;
+            }
+            let Ok((task, _)) = self.read(LEASED, &id) else {
+                continue;
+            };
+            if self.release(task, "lease expired")? {
+                requeued += 1;
+            }
+        }
+        Ok(requeued)
+    }
+
+    // Moves a leased task back to pending (or to failed once it is out of
+    // attempts). Returns false if another worker released it first.
+    fn release(&self, mut task: SegmentTask, error: &str) -> Result<bool> {
+        task.attempts += 1;
+        let target = if task.attempts >= self.max_attempts {
+            FAILED
+        } else {
+            PENDING
+        };
+        // Staged under a name `ids` ignores, so the task can't be claimed
+        // before its attempt count is updated. Only one rename out of
+        // `leased/` can win.
+        let staged = self.root.join(target).join(format!("{}.json.moving", task.id));
+        if fs::rename(self.path(LEASED, &task.id), &staged).is_err() {
+            return Ok(false);
+        }
+        let mut value = task.to_json();
+        value["error"] = json!(error);
+        fs::write(&staged, format!("{value:#}\n"))
+            .with_context(|| format!("failed to write task: {}", staged.display()))?;
+        fs::rename(&staged, self.path(target, &task.id))
+            .with_context(|| format!("failed to re-queue task {}", task.id))?;
+        Ok(true)
+    }
+
+    fn path(&self, dir: &str, id: &str) -> PathBuf {
+        self.root.join(dir).join(format!("{id}.json"))
+    }
+
+    fn ids(&self, dir: &str) -> Result<Vec<String>> {
+        let path = self.root.join(dir);
+        let entries = fs::read_dir(&path)
+            .with_context(|| format!("failed to list queue directory: {}", path.display()))?;
+        let mut ids: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name.strip_suffix(".json").map(str::to_string)
+            })
+            .collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn read(&self, dir: &str, id: &str) -> Result<(SegmentTask, Option<String>)> {
+        read_task(&self.path(dir, id))
+    }
+
+    // Writes through a temp file so readers never see a partial task.
+    fn write(&self, dir: &str, id: &str, value: Value) -> Result<()> {
+        let path = self.path(dir, id);
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, format!("{value:#}\n"))
+            .with_context(|| format!("failed to write task: {}", temp.display()))?;
+        fs::rename(&temp, &path)
+            .with_context(|| format!("failed to write task: {}", path.display()))
+    }
+
+    fn check_owner(&self, lease: &Lease) -> Result<()> {
+        let owner = match self.read(LEASED, &lease.task.id) {
+            Ok((_, owner)) => owner,
+            Err(_) => None,
+        };
+        if owner.as_deref() != Some(lease.worker.as_str()) {
+            bail!("lease on {} was lost (expired and re-queued)", lease.task.id);
+        }
+        Ok(())
+    }
+}
+
+impl Coordinator for FileQueue {
+    fn claim(&self, worker: &str) -> Result<Option<Lease>> {
+        self.requeue_expired()?;
+        for id in self.ids(PENDING)? {
+            // Staged like `release` does: the owner and a fresh modification
+            // time are written before the lease appears in `leased/`, so
+            // `requeue_expired` never sees it with the enqueue time.
+            let staged = self.root.join(LEASED).join(format!("{id}.json.moving"));
+            if fs::rename(self.path(PENDING, &id), &staged).is_err() {
+                // Claimed by another worker first.
+                continue;
+            }
+            let (task, _) = read_task(&staged)?;
+            let mut value = task.to_json();
+            value["worker"] = json!(worker);
+            fs::write(&staged, format!("{value:#}\n"))
+                .with_context(|| format!("failed to write task: {}", staged.display()))?;
+            fs::rename(&staged, self.path(LEASED, &id))
+                .with_context(|| format!("failed to lease task {id}"))?;
+            return Ok(Some(Lease {
+                task,
+                worker: worker.to_string(),
+            }));
+        }
+        Ok(None)
+    }
+
+    // Only bumps the lease file's modification time. Rewriting the file
+    // could bring it back after `release` had moved it away, leaving the
+    // task both pending and leased.
+    fn heartbeat(&self, lease: &Lease) -> Result<()> {
+        let path = self.path(LEASED, &lease.task.id);
+        let file = OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("lease on {} was lost (expired and re-queued)", lease.task.id))?;
+        self.check_owner(lease)?;
+        file.set_modified(SystemTime::now())
+            .with_context(|| format!("failed to renew lease: {}", path.display()))
+    }
+
+    fn complete(&self, lease: &Lease) -> Result<()> {
+        self.check_owner(lease)?;
+        fs::rename(
+            self.path(LEASED, &lease.task.id),
+            self.path(DONE, &lease.task.id),
+        )
+        .with_context(|| format!("failed to complete task {}", lease.task.id))
+    }
+
+    fn fail(&self, lease: &Lease, error: &str) -> Result<()> {
+        self.check_owner(lease)?;
+        self.release(lease.task.clone(), error)?;
+        Ok(())
+    }
+
+    fn status(&self) -> Result<QueueStatus> {
+        Ok(QueueStatus {
+            pending: self.ids(PENDING)?.len(),
+            leased: self.ids(LEASED)?.len(),
+            done: self.ids(DONE)?.len(),
+            failed: self.ids(FAILED)?.len(),
+        })
+    }
+}
+
+fn read_task(path: &Path) -> Result<(SegmentTask, Option<String>)> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("failed to read task: {}", path.display()))?;
+    let invalid = || ErrorKind::InvalidProject.error(format!("invalid task: {}", path.display()));
+    let value: Value = serde_json::from_str(&source).with_context(invalid)?;
+    let Some(fields) = value.as_object() else {
+        bail!(invalid());
+    };
+    let worker = fields
+        .get("worker")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    Ok((SegmentTask::from_json(fields)?, worker))
+}
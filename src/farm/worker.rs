@@ -0,0 +1,142 @@
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::farm::queue::{Coordinator, SegmentTask};
+
+// What a worker is doing, for whoever runs it to log; the worker itself
+// prints nothing. `task` is the segment id.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkerEvent {
+    Claimed { task: String },
+    Finished { task: String },
+    HeartbeatFailed { task: String, error: String },
+    // The render failed and the task was handed back to the queue.
+    RenderFailed { task: String, error: String },
+    // The task's outcome couldn't be recorded, usually because its lease
+    // expired and it was re-queued meanwhile. Another worker renders it
+    // again; this one carries on with the next task.
+    ResultLost { task: String, error: String },
+}
+
+type EventHandler = Box<dyn Fn(WorkerEvent) + Send + Sync>;
+
+pub struct Worker<C: Coordinator> {
+    coordinator: C,
+    name: String,
+    heartbeat_interval: Duration,
+    poll_interval: Duration,
+    on_event: Option<EventHandler>,
+}
+
+impl<C: Coordinator> Worker<C> {
+    pub fn new(coordinator: C, name: impl Into<String>) -> Self {
+        Self {
+            coordinator,
+            name: name.into(),
+            heartbeat_interval: Duration::from_secs(10),
+            poll_interval: Duration::from_secs(2),
+            on_event: None,
+        }
+    }
+
+    // Must stay well under the coordinator's lease timeout.
+    pub fn with_heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.heartbeat_interval = interval;
+        self
+    }
+
+    // How long to wait before asking again when every remaining task is
+    // leased to another worker.
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    // Called for every `WorkerEvent`, from the heartbeat thread as well.
+    pub fn with_event_handler(
+        mut self,
+        on_event: impl Fn(WorkerEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_event = Some(Box::new(on_event));
+        self
+    }
+
+    pub fn coordinator(&self) -> &C {
+        &self.coordinator
+    }
+
+    // Claims one task and renders it with `render`, heartbeating from a
+    // background thread meanwhile. A failed render re-queues the task rather
+    // than stopping the worker, and so does losing the lease before the
+    // outcome is recorded. Returns false if there was nothing to claim.
+    pub fn run_once(&self, mut render: impl FnMut(&SegmentTask) -> Result<()>) -> Result<bool> {
+        let Some(lease) = self.coordinator.claim(&self.name)? else {
+            return Ok(false);
+        };
+        let task = lease.task.id.clone();
+        self.emit(WorkerEvent::Claimed { task: task.clone() });
+
+        let result = thread::scope(|scope| {
+            let (stop, stopped) = mpsc::channel::<()>();
+            let lease = &lease;
+            scope.spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) =
+                    stopped.recv_timeout(self.heartbeat_interval)
+                {
+                    if let Err(err) = self.coordinator.heartbeat(lease) {
+                        self.emit(WorkerEvent::HeartbeatFailed {
+                            task: lease.task.id.clone(),
+                            error: format!("{err:#}"),
+                        });
+                    }
+                }
+            });
+            let result = render(&lease.task);
+            drop(stop);
+            result
+        });
+
+        let recorded = match result {
+            Ok(()) => self
+                .coordinator
+                .complete(&lease)
+                .map(|()| WorkerEvent::Finished { task: task.clone() }),
+            Err(err) => {
+                let error = format!("{err:#}");
+                let failed = WorkerEvent::RenderFailed {
+                    task: task.clone(),
+                    error: error.clone(),
+                };
+                self.coordinator.fail(&lease, &error).map(|()| failed)
+            }
+        };
+        self.emit(recorded.unwrap_or_else(|err| WorkerEvent::ResultLost {
+            task,
+            error: format!("{err:#}"),
+        }));
+        Ok(true)
+    }
+
+    // Renders tasks until the queue has nothing pending or in flight.
+    pub fn run(&self, mut render: impl FnMut(&SegmentTask) -> Result<()>) -> Result<()> {
+        loop {
+            if self.run_once(&mut render)? {
+                continue;
+            }
+            if self.coordinator.status()?.is_drained() {
+                return Ok(());
+            }
+            // Other workers hold the remaining leases; wait in case one expires.
+            thread::sleep(self.poll_interval);
+        }
+    }
+
+    fn emit(&self, event: WorkerEvent) {
+        if let Some(on_event) = &self.on_event {
+            on_event(event);
+        }
+    }
+}
@@ -1,20 +1,84 @@
 pub mod audio;
 pub mod backend;
+#[cfg(feature = "ffmpeg")]
 pub mod encoder;
+pub mod error;
+pub mod farm;
+pub mod plugin;
+pub mod project;
 pub mod scene;
 pub mod timeline;
 pub mod video;
 
-pub use audio::{AudioEngine, MusicTrack, SfxEvent};
+#[cfg(feature = "raylib")]
+pub use audio::AudioEngine;
+pub use audio::{MusicTrack, SfxEvent};
+#[cfg(feature = "raylib")]
+pub use backend::contact_sheet::ContactSheet;
+#[cfg(feature = "raylib")]
+pub use backend::debug_overlay::DebugOptions;
+#[cfg(feature = "raylib")]
+pub use backend::headless::DisplayMode;
+#[cfg(feature = "raylib")]
 pub use backend::raylib_preview::RaylibPreview;
-pub use backend::raylib_render::{RaylibRender, RenderProgress};
+#[cfg(feature = "raylib")]
+pub use backend::raylib_render::{RaylibRender, RaylibRenderBuilder};
+pub use backend::cancel::CancelToken;
+pub use backend::clock::{Clock, ExternalClock, ExternalTime, FrameClock, WallClock};
+pub use backend::assertions::check_assertion;
+pub use backend::golden::{frame_hash, FrameHashes, FrameMismatch, GoldenCheck, GoldenReport};
+pub use backend::hit_regions::{
+    scene_hit_regions, scene_outlines, ClipOutline, HitRegion, HitRegionFrame, HitRegionTrack,
+};
+pub use backend::limits::{LimitPolicy, RenderLimits};
+pub use backend::live_control::{LiveClock, LiveControl, OscListener};
+#[cfg(feature = "midi")]
+pub use backend::live_control::{MidiListener, MidiMap};
+pub use backend::progress::{ConsoleProgress, ProgressEvent, ProgressReporter};
+pub use backend::region::RenderRegion;
+#[cfg(any(feature = "raylib", feature = "software"))]
+pub use backend::render_backend::RenderBackend;
+pub use backend::report::{CacheStats, FrameStats, RenderReport, StageTimes};
+#[cfg(feature = "software")]
+pub use backend::asset_bytes::AssetBytes;
+#[cfg(feature = "software")]
+pub use backend::software::SoftwareRender;
+pub use backend::watermark::Watermark;
+#[cfg(feature = "wgpu")]
+pub use backend::wgpu_render::WgpuRender;
+#[cfg(feature = "ffmpeg")]
 pub use encoder::{
-    build_base_video, mux_video_audio, render_audio_wav, trim_audio, FfmpegVideoEncoder,
+    build_base_video, concat_videos, mux_audio_track, mux_video_audio, render_audio_wav, trim_audio,
+    AnimatedImage, AnimatedImageEncoder, AnimatedImageFormat, AudioTrack, Dither, EncodeProgress,
+    Encoder, EncoderSettings, FfmpegVideoEncoder, ImageSequenceEncoder, ImageSequenceFormat,
+    IncrementalRender, IncrementalStats, OutputColor, PcmFormat, PreviewStream, ProxyReview,
+    RenderManifest, ResumableRender, ResumableStats, ReviewStats, ReviewStatus, Sticker,
+    StickerExport, StickerFormat, StreamOutput, VideoCodec, VideoOptions,
+};
+#[cfg(feature = "ffmpeg-native")]
+pub use encoder::NativeVideoEncoder;
+pub use error::{exit_code, ErrorKind, RenderError};
+#[cfg(feature = "ffmpeg")]
+pub use farm::LocalFarm;
+pub use farm::{Coordinator, FileQueue, Lease, QueueStatus, SegmentTask, Worker, WorkerEvent};
+pub use plugin::{Canvas, EffectPlugin, FrameMut, ObjectPlugin, PluginRegistry};
+pub use project::{
+    compile_scene, LoadMode, OutputMetadata, Project, SceneDocument, SourceSpan,
 };
 pub use video::{resolve_segments, VideoClip, VideoSegment};
 pub use scene::{
-    AnimatedTransform, Color, Easing, FontFamily, FontSource, ImageObject, Keyframe, Object, Shape,
-    StyleFlags, StyledText, TextObject, TextRun, Track, Transform, Vec2,
+    readable_text_on, AnimatedTransform, BezierPath, Color, Composition, Easing, Extrapolation,
+    FontFamily, FontSource, Harmony, Hsl, ImageLoadOptions, ImageObject, Interpolation, Keyframe,
+    NoiseTrack, Object, ObjectShader, Palette, ParamValue, Params, PathMotion, PluginEffect,
+    PluginObject, PostEffect, Procedural, SampledShader, Shape, SineTrack, Spring, StyleFlags,
+    StyledText, TextBackground, TextObject, TextRun, TextStroke, Track, TrackOp, Transform,
+    UniformTrack, UniformValue, Vec2,
+};
+pub use timeline::{
+    Aspect, AspectOverride, Assertion, AssertionRule, BindTarget, Binding, BlendMode, Chapter,
+    Clip, CsvTable, DataSource, DrawOrderEntry, FrameTable, IssueKind, Layer, LayerGroup,
+    LoopMode, Marker, Severity, TimeRange, Timeline, TimelineBuilder, TrackMatte, Transition,
+    TransitionDirection, TransitionKind, ValidationIssue, ValidationReport, Variables,
 };
-pub use timeline::{Clip, Layer, Timeline};
+#[cfg(feature = "raylib")]
 pub use raylib::consts::TraceLogLevel;
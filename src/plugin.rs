@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+
+use crate::error::ErrorKind;
+use crate::scene::{Color, Object, ParamValue, Params, Transform, Vec2};
+use crate::timeline::Timeline;
+
+// Backend-neutral drawing surface handed to object plugins. Coordinates are
+// graph coords (center origin, +Y up), same as the rest of the public API.
+pub trait Canvas {
+    fn size(&self) -> (u32, u32);
+    fn fill_rect(&mut self, center: Vec2, width: f32, height: f32, rotation: f32, color: Color);
+    fn fill_circle(&mut self, center: Vec2, radius: f32, color: Color);
+    fn fill_triangle(&mut self, a: Vec2, b: Vec2, c: Vec2, color: Color);
+    fn line(&mut self, from: Vec2, to: Vec2, thickness: f32, color: Color);
+}
+
+pub trait ObjectPlugin: Send + Sync {
+    // Interprets/validates the object's params; called by
+    // `PluginRegistry::validate` before a render starts.
+    fn parse(&self, _params: &Params) -> Result<()> {
+        Ok(())
+    }
+
+    fn draw(
+        &self,
+        canvas: &mut dyn Canvas,
+        params: &Params,
+        transform: &Transform,
+        local_t: f32,
+    ) -> Result<()>;
+}
+
+// Tightly packed RGBA pixels of a captured frame, with straight alpha.
+pub struct FrameMut<'a> {
+    pub pixels: &'a mut [u8],
+    pub width: u32,
+    pub height: u32,
+    // GPU readback is bottom-up; effects that care about direction check this.
+    pub bottom_up: bool,
+}
+
+pub trait EffectPlugin: Send + Sync {
+    fn parse(&self, _params: &Params) -> Result<()> {
+        Ok(())
+    }
+
+    fn apply(&self, frame: &mut FrameMut<'_>, params: &Params, t: f32) -> Result<()>;
+}
+
+#[derive(Default, Clone)]
+pub struct PluginRegistry {
+    objects: HashMap<String, Arc<dyn ObjectPlugin>>,
+    effects: HashMap<String, Arc<dyn EffectPlugin>>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register_object(
+        &mut self,
+        kind: impl Into<String>,
+        plugin: impl ObjectPlugin + 'static,
+    ) -> Result<()> {
+        let kind = kind.into();
+        if self.objects.contains_key(&kind) {
+            bail!("object plugin already registered: {kind}");
+        }
+        self.objects.insert(kind, Arc::new(plugin));
+        Ok(())
+    }
+
+    pub fn register_effect(
+        &mut self,
+        kind: impl Into<String>,
+        plugin: impl EffectPlugin + 'static,
+    ) -> Result<()> {
+        let kind = kind.into();
+        if self.effects.contains_key(&kind) {
+            bail!("effect plugin already registered: {kind}");
+        }
+        self.effects.insert(kind, Arc::new(plugin));
+        Ok(())
+    }
+
+    pub fn object(&self, kind: &str) -> Result<&dyn ObjectPlugin> {
+        self.objects
+            .get(kind)
+            .map(|plugin| plugin.as_ref())
+            .with_context(|| {
+                ErrorKind::InvalidProject.error(format!("unknown object kind: {kind}"))
+            })
+    }
+
+    pub fn effect(&self, kind: &str) -> Result<&dyn EffectPlugin> {
+        self.effects
+            .get(kind)
+            .map(|plugin| plugin.as_ref())
+            .with_context(|| {
+                ErrorKind::InvalidProject.error(format!("unknown effect kind: {kind}"))
+            })
+    }
+
+    // Checks that every plugin object/effect in the timeline (nested
+    // compositions included) has a registered plugin that accepts its params.
+    pub fn validate(&self, timeline: &Timeline) -> Result<()> {
+        let group_effects = timeline.groups.iter().flat_map(|group| &group.effects);
+        for effect in timeline.effects.iter().chain(group_effects) {
+            self.effect(&effect.kind)?
+                .parse(&effect.params)
+                .with_context(|| format!("invalid params for effect {}", effect.kind))?;
+        }
+        for layer in &timeline.layers {
+            for clip in &layer.clips {
+                match &clip.object {
+                    Object::Plugin(object) => {
+                        self.object(&object.kind)?
+                            .parse(&object.params)
+                            .with_context(|| {
+                                format!("invalid params for object {}", object.kind)
+                            })?;
+                    }
+                    Object::Composition(composition) => self.validate(&composition.timeline)?,
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn param_number(params: &Params, key: &str) -> Result<f32> {
+    match params.get(key) {
+        Some(ParamValue::Number(value)) => Ok(*value),
+        Some(_) => bail!("param {key} must be a number"),
+        None => bail!("missing param {key}"),
+    }
+}
+
+pub fn param_color(params: &Params, key: &str) -> Result<Color> {
+    match params.get(key) {
+        Some(ParamValue::Color(value)) => Ok(*value),
+        Some(_) => bail!("param {key} must be a color"),
+        None => bail!("missing param {key}"),
+    }
+}
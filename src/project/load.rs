@@ -0,0 +1,1298 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{Map, Value};
+
+use crate::error::ErrorKind;
+use crate::project::scene_text::compile_scene;
+use crate::project::schema::migrate;
+use crate::scene::animation::Lerp;
+use crate::scene::{
+    AnimatedTransform, BezierPath, Color, Composition, Easing, Extrapolation, FontFamily,
+    FontSource, ImageLoadOptions, ImageObject, Interpolation, Keyframe, NoiseTrack, Object,
+    ObjectShader, ParamValue, Params, PathMotion, PluginEffect, PluginObject, PostEffect,
+    Procedural, Shape, SineTrack, Spring, StyledText, TextBackground, TextObject, TextStroke,
+    Track, TrackOp, UniformTrack, Vec2,
+};
+use crate::timeline::{
+    Aspect, AspectOverride, Assertion, AssertionRule, BindTarget, Binding, BlendMode, Clip,
+    CsvTable, Layer, LayerGroup, LoopMode, Timeline, TrackMatte, Transition, TransitionDirection,
+    TransitionKind,
+};
+
+type Fields = Map<String, Value>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadMode {
+    // Unknown fields and object types are errors, so typos and files written
+    // for a newer library fail loudly.
+    Strict,
+    // Unknown fields are ignored and clips with unknown object types are
+    // skipped, each with a warning.
+    Lenient,
+}
+
+// A scene loaded from a JSON project file, or its `.scene` text form (see
+// `compile_scene`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Project {
+    pub width: u32,
+    pub height: u32,
+    pub bg: Color,
+    pub timeline: Timeline,
+    // Tags for the rendered file, from the optional `"metadata"` object.
+    pub metadata: OutputMetadata,
+    // Non-fatal notes from loading (migrations, assumed defaults).
+    pub warnings: Vec<String>,
+}
+
+// Descriptive tags written into a rendered video's container, which players
+// and file browsers show (see `VideoOptions::with_output_metadata`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutputMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub comment: Option<String>,
+    pub creation_time: Option<DateTime<Utc>>,
+}
+
+impl OutputMetadata {
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub fn with_comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    pub fn with_creation_time(mut self, creation_time: DateTime<Utc>) -> Self {
+        self.creation_time = Some(creation_time);
+        self
+    }
+
+    // As ffmpeg `-metadata` keys. The author goes in `artist`, the key MP4,
+    // MKV and WebM players display.
+    pub fn tags(&self) -> Vec<(String, String)> {
+        let mut tags = Vec::new();
+        let text = [("title", &self.title), ("artist", &self.author), ("comment", &self.comment)];
+        for (key, value) in text {
+            if let Some(value) = value {
+                tags.push((key.to_string(), value.clone()));
+            }
+        }
+        if let Some(time) = self.creation_time {
+            let time = time.to_rfc3339_opts(SecondsFormat::Micros, true);
+            tags.push(("creation_time".to_string(), time));
+        }
+        tags
+    }
+}
+
+impl Project {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::load_with(path, LoadMode::Strict)
+    }
+
+    pub fn load_with(path: impl AsRef<Path>, mode: LoadMode) -> Result<Self> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).with_context(|| {
+            ErrorKind::MissingAsset.error(format!("failed to read project: {}", path.display()))
+        })?;
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        let project = if path
+            .extension()
+            .is_some_and(|extension| extension == "scene")
+        {
+            Self::from_scene_in(&source, base_dir, mode)
+        } else {
+            Self::from_json_in(&source, base_dir, mode)
+        };
+        project.with_context(|| format!("failed to load project {}", path.display()))
+    }
+
+    pub fn from_json(source: &str) -> Result<Self> {
+        Self::from_json_in(source, Path::new(""), LoadMode::Strict)
+    }
+
+    // Relative asset paths in the document resolve against `base_dir`.
+    pub fn from_json_in(source: &str, base_dir: &Path, mode: LoadMode) -> Result<Self> {
+        let doc: Value = serde_json::from_str(source).map_err(|err| {
+            ErrorKind::InvalidProject.error(format!("project is not valid json: {err}"))
+        })?;
+        Self::from_document(doc, base_dir, mode)
+    }
+
+    pub fn from_scene(source: &str) -> Result<Self> {
+        Self::from_scene_in(source, Path::new(""), LoadMode::Strict)
+    }
+
+    // As `from_json_in`, for the `.scene` text form. Errors found while
+    // loading the compiled document point back at the node's line and column.
+    pub fn from_scene_in(source: &str, base_dir: &Path, mode: LoadMode) -> Result<Self> {
+        let scene = compile_scene(source)?;
+        Self::from_document(scene.doc.clone(), base_dir, mode).map_err(|err| {
+            match scene.locate(&err) {
+                Some(span) => err.context(
+                    ErrorKind::InvalidProject
+                        .error(format!("line {}, column {}", span.line, span.column)),
+                ),
+                None => err,
+            }
+        })
+    }
+
+    fn from_document(mut doc: Value, base_dir: &Path, mode: LoadMode) -> Result<Self> {
+        let mut warnings = Vec::new();
+        migrate(&mut doc, &mut warnings)?;
+
+        let mut loader = Loader {
+            base_dir,
+            mode,
+            warnings,
+        };
+        let root = as_object(&doc, "project")?;
+        loader.check_fields(root, ROOT_FIELDS, "project")?;
+        let mut timeline = loader.timeline(root, "project")?;
+        // Only checked on the frames of the top-level timeline.
+        for (index, value) in array_field(root, "assertions", "project")?.iter().enumerate() {
+            let ctx = format!("project.assertions[{index}]");
+            let assertion = loader.assertion(as_object(value, &ctx)?, timeline.duration, &ctx)?;
+            timeline.add_assertion(assertion);
+        }
+        for (index, value) in array_field(root, "post", "project")?.iter().enumerate() {
+            let ctx = format!("project.post[{index}]");
+            timeline.add_post_effect(loader.post_effect(as_object(value, &ctx)?, &ctx)?);
+        }
+        let project = Self {
+            width: u32_field(root, "width", "project")?,
+            height: u32_field(root, "height", "project")?,
+            bg: match root.get("bg") {
+                Some(value) => color(value, "project.bg")?,
+                None => Color::BLACK,
+            },
+            timeline,
+            metadata: match root.get("metadata") {
+                Some(value) => loader.metadata(as_object(value, "project.metadata")?)?,
+                None => OutputMetadata::default(),
+            },
+            warnings: loader.warnings,
+        };
+        let (width, height) = (project.width, project.height);
+        Ok(project.with_output_size(width, height))
+    }
+
+    // Renders at `width` x `height` instead of the size the file declares,
+    // with clip overrides for that size's aspect (see `AspectOverride`).
+    pub fn with_output_size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self.timeline.set_aspect(Some(Aspect::of(width, height)));
+        self
+    }
+}
+
+const ROOT_FIELDS: &[&str] = &[
+    "schema_version",
+    "width",
+    "height",
+    "bg",
+    "duration",
+    "fps",
+    "layers",
+    "groups",
+    "effects",
+    "markers",
+    "assertions",
+    "post",
+    "metadata",
+];
+const METADATA_FIELDS: &[&str] = &["title", "author", "comment", "creation_time"];
+const VIGNETTE_FIELDS: &[&str] = &["kind", "amount", "softness"];
+const GRAIN_FIELDS: &[&str] = &["kind", "amount"];
+const BLOOM_FIELDS: &[&str] = &["kind", "threshold", "intensity", "radius"];
+const LUT_FIELDS: &[&str] = &["kind", "path", "amount"];
+const LAYER_FIELDS: &[&str] = &["name", "z", "transform", "blend", "matte", "group", "clips"];
+const GROUP_FIELDS: &[&str] = &["name", "opacity", "blend", "effects"];
+const CLIP_FIELDS: &[&str] = &[
+    "name",
+    "start",
+    "end",
+    "object",
+    "transform",
+    "transition_in",
+    "time_map",
+    "loop",
+    "freeze_at",
+    "bindings",
+    "responsive",
+    "shader",
+];
+const SHADER_FIELDS: &[&str] = &["path", "uniforms"];
+const OVERRIDE_FIELDS: &[&str] = &["offset", "scale", "visible"];
+const TRANSFORM_FIELDS: &[&str] = &["position", "scale", "rotation", "opacity", "path"];
+const PATH_FIELDS: &[&str] = &["start", "curves", "progress", "orient"];
+const CURVE_FIELDS: &[&str] = &["to", "control1", "control2"];
+const TRACK_FIELDS: &[&str] = &[
+    "interpolation",
+    "tension",
+    "continuity",
+    "bias",
+    "pre",
+    "post",
+    "add",
+    "multiply",
+    "time_offset",
+    "time_scale",
+    "keyframes",
+];
+const TRACK_OPERAND_FIELDS: &[&str] = &["type", "track"];
+const SINE_FIELDS: &[&str] = &["type", "amplitude", "frequency", "phase"];
+const NOISE_FIELDS: &[&str] = &["type", "seed", "frequency", "amplitude", "octaves"];
+const KEYFRAME_FIELDS: &[&str] = &["time", "value", "easing"];
+const CSV_FIELDS: &[&str] = &["csv", "time", "value", "easing"];
+const TRANSITION_FIELDS: &[&str] = &["kind", "direction", "amount", "easing"];
+const EFFECT_FIELDS: &[&str] = &["kind", "params"];
+const SPRING_FIELDS: &[&str] = &["type", "stiffness", "damping", "mass"];
+const BINDING_FIELDS: &[&str] = &["variable", "target", "scale", "offset", "precision"];
+const MARKER_FIELDS: &[&str] = &["time", "name"];
+const ASSERTION_FIELDS: &[&str] = &["type", "clip", "start", "end", "center", "size", "severity"];
+const FONT_FIELDS: &[&str] = &["regular", "bold", "italic", "bold_italic", "fallbacks"];
+const STROKE_FIELDS: &[&str] = &["width", "color"];
+const BACKGROUND_FIELDS: &[&str] = &["padding", "corner_radius", "color"];
+
+fn object_fields(kind: &str) -> Option<&'static [&'static str]> {
+    let fields: &[&str] = match kind {
+        "rect" => &["type", "width", "height", "color"],
+        "circle" => &["type", "radius", "color"],
+        "image" => &[
+            "type",
+            "path",
+            "max_dimension",
+            "orientation",
+            "premultiply",
+            "mipmaps",
+        ],
+        "text" => &[
+            "type",
+            "text",
+            "font",
+            "font_size",
+            "spacing",
+            "max_width",
+            "color",
+            "line_spacing",
+            "stroke",
+            "background",
+        ],
+        "composition" => &[
+            "type", "width", "height", "bg", "duration", "fps", "layers", "groups", "effects",
+            "markers",
+        ],
+        "plugin" => &["type", "kind", "params"],
+        _ => return None,
+    };
+    Some(fields)
+}
+
+struct Loader<'a> {
+    base_dir: &'a Path,
+    mode: LoadMode,
+    warnings: Vec<String>,
+}
+
+impl Loader<'_> {
+    fn check_fields(&mut self, fields: &Fields, known: &[&str], ctx: &str) -> Result<()> {
+        for key in fields.keys() {
+            if known.contains(&key.as_str()) {
+                continue;
+            }
+            match self.mode {
+                LoadMode::Strict => bail!(invalid(ctx, &format!("unknown field \"{key}\""))),
+                LoadMode::Lenient => {
+                    self.warnings
+                        .push(format!("{ctx}: ignoring unknown field \"{key}\""));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn timeline(&mut self, fields: &Fields, ctx: &str) -> Result<Timeline> {
+        // Without a declared duration the timeline runs to its last clip end.
+        let fps = u32_field(fields, "fps", ctx)?;
+        let mut timeline = match opt_number_field(fields, "duration", ctx)? {
+            Some(duration) => Timeline::new(duration, fps),
+            None => Timeline::auto_duration(fps),
+        }
+        .with_context(|| ctx.to_string())?;
+
+        // Groups first, so layers naming one that doesn't exist are caught
+        // with their own location.
+        for (index, value) in array_field(fields, "groups", ctx)?.iter().enumerate() {
+            let ctx = format!("{ctx}.groups[{index}]");
+            let group = self.group(as_object(value, &ctx)?, &ctx)?;
+            timeline.add_group(group).with_context(|| ctx.clone())?;
+        }
+        for (index, value) in array_field(fields, "layers", ctx)?.iter().enumerate() {
+            let ctx = format!("{ctx}.layers[{index}]");
+            let layer = self.layer(as_object(value, &ctx)?, &ctx)?;
+            if let Some(group) = &layer.group
+                && timeline.group(group).is_none()
+            {
+                bail!(invalid(&ctx, &format!("unknown group \"{group}\"")));
+            }
+            timeline.add_layer(layer).with_context(|| ctx.clone())?;
+        }
+        for (index, value) in array_field(fields, "effects", ctx)?.iter().enumerate() {
+            let ctx = format!("{ctx}.effects[{index}]");
+            timeline.add_effect(self.effect(value, &ctx)?);
+        }
+        for (index, value) in array_field(fields, "markers", ctx)?.iter().enumerate() {
+            let ctx = format!("{ctx}.markers[{index}]");
+            let fields = as_object(value, &ctx)?;
+            self.check_fields(fields, MARKER_FIELDS, &ctx)?;
+            timeline
+                .add_marker(
+                    number_field(fields, "time", &ctx)?,
+                    string_field(fields, "name", &ctx)?,
+                )
+                .with_context(|| ctx.clone())?;
+        }
+        Ok(timeline)
+    }
+
+    // `{ "title", "author", "comment", "creation_time" }`, each optional; the
+    // time is RFC 3339, e.g. `"2024-05-01T12:00:00Z"`.
+    fn metadata(&mut self, fields: &Fields) -> Result<OutputMetadata> {
+        let ctx = "project.metadata";
+        self.check_fields(fields, METADATA_FIELDS, ctx)?;
+        let text = |key: &str| -> Result<Option<String>> {
+            match fields.get(key) {
+                Some(_) => Ok(Some(string_field(fields, key, ctx)?.to_string())),
+                None => Ok(None),
+            }
+        };
+        let creation_time = match text("creation_time")? {
+            Some(time) => Some(
+                DateTime::parse_from_rfc3339(&time)
+                    .map_err(|err| {
+                        invalid(ctx, &format!("\"creation_time\" must be an RFC 3339 time: {err}"))
+                    })?
+                    .with_timezone(&Utc),
+            ),
+            None => None,
+        };
+        Ok(OutputMetadata {
+            title: text("title")?,
+            author: text("author")?,
+            comment: text("comment")?,
+            creation_time,
+        })
+    }
+
+    // `{ "type": "inside_frame" | "avoids_region", "clip": name, "start",
+    // "end", "center": [x, y], "size": [w, h], "severity": "error" |
+    // "warning" }`. The times default to the whole timeline.
+    fn assertion(&mut self, fields: &Fields, duration: f32, ctx: &str) -> Result<Assertion> {
+        self.check_fields(fields, ASSERTION_FIELDS, ctx)?;
+        let clip = string_field(fields, "clip", ctx)?.to_string();
+        let rule = match string_field(fields, "type", ctx)? {
+            "inside_frame" => AssertionRule::InsideFrame { clip },
+            "avoids_region" => AssertionRule::AvoidsRegion {
+                clip,
+                center: vec2(field(fields, "center", ctx)?, &format!("{ctx}.center"))?,
+                size: vec2(field(fields, "size", ctx)?, &format!("{ctx}.size"))?,
+            },
+            other => bail!(invalid(
+                ctx,
+                &format!("unknown assertion type \"{other}\" (use inside_frame or avoids_region)")
+            )),
+        };
+        let start = opt_number_field(fields, "start", ctx)?.unwrap_or(0.0);
+        let end = opt_number_field(fields, "end", ctx)?.unwrap_or(duration);
+        let assertion = Assertion::new(start, end, rule).with_context(|| ctx.to_string())?;
+        match fields.get("severity").map(Value::as_str) {
+            None | Some(Some("error")) => Ok(assertion),
+            Some(Some("warning")) => Ok(assertion.warn_only()),
+            Some(_) => bail!(invalid(ctx, "severity must be \"error\" or \"warning\"")),
+        }
+    }
+
+    fn layer(&mut self, fields: &Fields, ctx: &str) -> Result<Layer> {
+        self.check_fields(fields, LAYER_FIELDS, ctx)?;
+        let mut layer = Layer::new(string_field(fields, "name", ctx)?);
+        if let Some(value) = fields.get("z") {
+            let z = value
+                .as_i64()
+                .ok_or_else(|| invalid(ctx, "z must be an integer"))?;
+            layer = layer.with_z_override(z as i32);
+        }
+        if let Some(value) = fields.get("transform") {
+            let ctx = format!("{ctx}.transform");
+            layer = layer.with_transform(self.transform(as_object(value, &ctx)?, &ctx)?);
+        }
+        if fields.contains_key("blend") {
+            layer = layer.with_blend(blend_mode(fields, ctx)?);
+        }
+        if fields.contains_key("matte") {
+            layer = layer.with_matte(track_matte(fields, ctx)?);
+        }
+        if fields.contains_key("group") {
+            layer = layer.with_group(string_field(fields, "group", ctx)?);
+        }
+        for (index, value) in array_field(fields, "clips", ctx)?.iter().enumerate() {
+            let ctx = format!("{ctx}.clips[{index}]");
+            if let Some(clip) = self.clip(as_object(value, &ctx)?, &ctx)? {
+                layer.add_clip(clip);
+            }
+        }
+        Ok(layer)
+    }
+
+    fn group(&mut self, fields: &Fields, ctx: &str) -> Result<LayerGroup> {
+        self.check_fields(fields, GROUP_FIELDS, ctx)?;
+        let mut group = LayerGroup::new(string_field(fields, "name", ctx)?).with_opacity(
+            self.track(fields.get("opacity"), 1.0, &format!("{ctx}.opacity"), number)?,
+        );
+        if fields.contains_key("blend") {
+            group = group.with_blend(blend_mode(fields, ctx)?);
+        }
+        for (index, value) in array_field(fields, "effects", ctx)?.iter().enumerate() {
+            group = group.with_effect(self.effect(value, &format!("{ctx}.effects[{index}]"))?);
+        }
+        Ok(group)
+    }
+
+    fn effect(&mut self, value: &Value, ctx: &str) -> Result<PluginEffect> {
+        let fields = as_object(value, ctx)?;
+        self.check_fields(fields, EFFECT_FIELDS, ctx)?;
+        Ok(PluginEffect {
+            kind: string_field(fields, "kind", ctx)?.to_string(),
+            params: params(fields, ctx)?,
+        })
+    }
+
+    // `{ "kind": "vignette", "amount": 0.7 }` and so on. Every setting but a
+    // lut's path has a default.
+    fn post_effect(&mut self, fields: &Fields, ctx: &str) -> Result<PostEffect> {
+        let kind = string_field(fields, "kind", ctx)?;
+        let known = match kind {
+            "vignette" => VIGNETTE_FIELDS,
+            "grain" => GRAIN_FIELDS,
+            "bloom" => BLOOM_FIELDS,
+            "lut" => LUT_FIELDS,
+            _ => bail!(invalid(
+                ctx,
+                &format!("unknown post effect \"{kind}\" (use vignette, grain, bloom or lut)")
+            )),
+        };
+        self.check_fields(fields, known, ctx)?;
+        let setting = |key: &str, default: f32| -> Result<f32> {
+            let value = opt_number_field(fields, key, ctx)?.unwrap_or(default);
+            if value < 0.0 {
+                bail!(invalid(ctx, &format!("\"{key}\" must be >= 0")));
+            }
+            Ok(value)
+        };
+        Ok(match kind {
+            "vignette" => PostEffect::Vignette {
+                amount: setting("amount", 0.5)?,
+                softness: setting("softness", 0.5)?,
+            },
+            "grain" => PostEffect::Grain {
+                amount: setting("amount", 0.08)?,
+            },
+            "bloom" => PostEffect::Bloom {
+                threshold: setting("threshold", 0.8)?,
+                intensity: setting("intensity", 0.6)?,
+                radius: setting("radius", 8.0)?,
+            },
+            _ => PostEffect::Lut {
+                path: self.path(fields, "path", ctx)?,
+                amount: setting("amount", 1.0)?,
+            },
+        })
+    }
+
+    fn clip(&mut self, fields: &Fields, ctx: &str) -> Result<Option<Clip>> {
+        self.check_fields(fields, CLIP_FIELDS, ctx)?;
+        let object_ctx = format!("{ctx}.object");
+        let object_fields = as_object(field(fields, "object", ctx)?, &object_ctx)?;
+        let Some(object) = self.object(object_fields, &object_ctx)? else {
+            return Ok(None);
+        };
+        let transform = match fields.get("transform") {
+            Some(value) => {
+                let ctx = format!("{ctx}.transform");
+                self.transform(as_object(value, &ctx)?, &ctx)?
+            }
+            None => AnimatedTransform::default(),
+        };
+        let mut clip = Clip::new(
+            number_field(fields, "start", ctx)?,
+            number_field(fields, "end", ctx)?,
+            object,
+            transform,
+        )
+        .with_context(|| ctx.to_string())?;
+
+        if fields.contains_key("name") {
+            clip = clip.with_name(string_field(fields, "name", ctx)?);
+        }
+        if let Some(value) = fields.get("transition_in") {
+            let ctx = format!("{ctx}.transition_in");
+            clip = clip.with_transition_in(self.transition(as_object(value, &ctx)?, &ctx)?);
+        }
+        if let Some(value) = fields.get("time_map") {
+            let ctx = format!("{ctx}.time_map");
+            clip = clip.with_time_map(self.track(Some(value), 0.0, &ctx, number)?);
+        }
+        if let Some(value) = fields.get("loop") {
+            let mode = match value.as_str() {
+                Some("once") => LoopMode::Once,
+                Some("loop") => LoopMode::Loop,
+                Some("ping_pong") => LoopMode::PingPong,
+                _ => bail!(invalid(
+                    ctx,
+                    "loop must be \"once\", \"loop\" or \"ping_pong\""
+                )),
+            };
+            clip = clip.with_loop_mode(mode);
+        }
+        if let Some(freeze_at) = opt_number_field(fields, "freeze_at", ctx)? {
+            clip = clip.freeze_at(freeze_at);
+        }
+        for (index, value) in array_field(fields, "bindings", ctx)?.iter().enumerate() {
+            let ctx = format!("{ctx}.bindings[{index}]");
+            clip = clip.with_binding(self.binding(as_object(value, &ctx)?, &ctx)?);
+        }
+        if let Some(value) = fields.get("responsive") {
+            let ctx = format!("{ctx}.responsive");
+            for (name, value) in as_object(value, &ctx)? {
+                let ctx = format!("{ctx}.{name}");
+                clip = clip.with_override(self.aspect_override(name, value, &ctx)?);
+            }
+        }
+        if let Some(value) = fields.get("shader") {
+            let ctx = format!("{ctx}.shader");
+            clip = clip.with_shader(self.shader(as_object(value, &ctx)?, &ctx)?);
+        }
+        Ok(Some(clip))
+    }
+
+    // `{ "path": "ripple.fs", "uniforms": { name: value } }`. A uniform is a
+    // colour string, or a number or `[x, y]` track in any form `track` takes.
+    fn shader(&mut self, fields: &Fields, ctx: &str) -> Result<ObjectShader> {
+        self.check_fields(fields, SHADER_FIELDS, ctx)?;
+        let mut shader = ObjectShader::new(self.path(fields, "path", ctx)?);
+        if let Some(value) = fields.get("uniforms") {
+            let ctx = format!("{ctx}.uniforms");
+            for (name, value) in as_object(value, &ctx)? {
+                let ctx = format!("{ctx}.{name}");
+                let uniform = if value.is_string() {
+                    UniformTrack::Color(color(value, &ctx)?)
+                } else if is_vec2_track(value) {
+                    UniformTrack::Vec2(self.track(Some(value), Vec2::ZERO, &ctx, vec2)?)
+                } else {
+                    UniformTrack::Float(self.track(Some(value), 0.0, &ctx, number)?)
+                };
+                shader = shader.with_uniform(name, uniform);
+            }
+        }
+        Ok(shader)
+    }
+
+    // `"9:16": { "offset": [x, y], "scale": s or [x, y], "visible": bool }`.
+    fn aspect_override(&mut self, name: &str, value: &Value, ctx: &str) -> Result<AspectOverride> {
+        let Some(aspect) = Aspect::from_name(name) else {
+            bail!(invalid(
+                ctx,
+                &format!("unknown aspect \"{name}\" (use 16:9, 9:16 or 1:1)")
+            ));
+        };
+        let fields = as_object(value, ctx)?;
+        self.check_fields(fields, OVERRIDE_FIELDS, ctx)?;
+        let mut rule = AspectOverride::new(aspect);
+        if let Some(value) = fields.get("offset") {
+            rule = rule.with_offset(vec2(value, &format!("{ctx}.offset"))?);
+        }
+        if let Some(value) = fields.get("scale") {
+            let ctx = format!("{ctx}.scale");
+            let scale = match value.as_f64() {
+                Some(scale) => Vec2 {
+                    x: scale as f32,
+                    y: scale as f32,
+                },
+                None => vec2(value, &ctx)?,
+            };
+            rule = rule.with_scale(scale);
+        }
+        if opt_bool_field(fields, "visible", ctx)? == Some(false) {
+            rule = rule.hidden();
+        }
+        Ok(rule)
+    }
+
+    fn object(&mut self, fields: &Fields, ctx: &str) -> Result<Option<Object>> {
+        let kind = string_field(fields, "type", ctx)?;
+        let Some(known) = object_fields(kind) else {
+            match self.mode {
+                LoadMode::Strict => {
+                    bail!(invalid(ctx, &format!("unknown object type \"{kind}\"")))
+                }
+                LoadMode::Lenient => {
+                    self.warnings.push(format!(
+                        "{ctx}: skipping clip with unknown object type \"{kind}\""
+                    ));
+                    return Ok(None);
+                }
+            }
+        };
+        self.check_fields(fields, known, ctx)?;
+
+        let object = match kind {
+            "rect" => Object::Shape(Shape::Rect {
+                width: number_field(fields, "width", ctx)?,
+                height: number_field(fields, "height", ctx)?,
+                color: color(field(fields, "color", ctx)?, ctx)?,
+            }),
+            "circle" => Object::Shape(Shape::Circle {
+                radius: number_field(fields, "radius", ctx)?,
+                color: color(field(fields, "color", ctx)?, ctx)?,
+            }),
+            "image" => {
+                let defaults = ImageLoadOptions::default();
+                let max_dimension = match fields.get("max_dimension") {
+                    Some(_) => Some(u32_field(fields, "max_dimension", ctx)?),
+                    None => None,
+                };
+                let load = ImageLoadOptions {
+                    max_dimension,
+                    orientation: opt_bool_field(fields, "orientation", ctx)?
+                        .unwrap_or(defaults.orientation),
+                    premultiply: opt_bool_field(fields, "premultiply", ctx)?
+                        .unwrap_or(defaults.premultiply),
+                    mipmaps: opt_bool_field(fields, "mipmaps", ctx)?.unwrap_or(defaults.mipmaps),
+                };
+                Object::Image(
+                    ImageObject::new(self.path(fields, "path", ctx)?).with_load_options(load),
+                )
+            }
+            "text" => Object::Text(self.text(fields, ctx)?),
+            "composition" => {
+                let mut composition = Composition::new(
+                    self.timeline(fields, ctx)?,
+                    u32_field(fields, "width", ctx)?,
+                    u32_field(fields, "height", ctx)?,
+                );
+                if let Some(value) = fields.get("bg") {
+                    composition = composition.with_bg(color(value, ctx)?);
+                }
+                Object::Composition(Box::new(composition))
+            }
+            "plugin" => Object::Plugin(PluginObject {
+                kind: string_field(fields, "kind", ctx)?.to_string(),
+                params: params(fields, ctx)?,
+            }),
+            _ => unreachable!("object_fields covers every object type"),
+        };
+        Ok(Some(object))
+    }
+
+    fn text(&mut self, fields: &Fields, ctx: &str) -> Result<TextObject> {
+        let font = match fields.get("font") {
+            Some(value) => {
+                let ctx = format!("{ctx}.font");
+                self.font_family(as_object(value, &ctx)?, &ctx)?
+            }
+            None => FontFamily::default(),
+        };
+        Ok(TextObject {
+            text: StyledText::from_markdown(string_field(fields, "text", ctx)?),
+            font,
+            font_size: opt_number_field(fields, "font_size", ctx)?.unwrap_or(32.0),
+            spacing: opt_number_field(fields, "spacing", ctx)?.unwrap_or(1.0),
+            // <= 0 disables wrapping.
+            max_width: opt_number_field(fields, "max_width", ctx)?.unwrap_or(0.0),
+            color: match fields.get("color") {
+                Some(value) => color(value, ctx)?,
+                None => Color::WHITE,
+            },
+            line_spacing: opt_number_field(fields, "line_spacing", ctx)?.unwrap_or(0.0),
+            stroke: match fields.get("stroke") {
+                Some(value) => Some(self.text_stroke(value, &format!("{ctx}.stroke"))?),
+                None => None,
+            },
+            background: match fields.get("background") {
+                Some(value) => Some(self.text_background(value, &format!("{ctx}.background"))?),
+                None => None,
+            },
+        })
+    }
+
+    fn text_stroke(&mut self, value: &Value, ctx: &str) -> Result<TextStroke> {
+        let fields = as_object(value, ctx)?;
+        self.check_fields(fields, STROKE_FIELDS, ctx)?;
+        Ok(TextStroke {
+            width: non_negative_field(fields, "width", ctx)?,
+            color: color(field(fields, "color", ctx)?, ctx)?,
+        })
+    }
+
+    fn text_background(&mut self, value: &Value, ctx: &str) -> Result<TextBackground> {
+        let fields = as_object(value, ctx)?;
+        self.check_fields(fields, BACKGROUND_FIELDS, ctx)?;
+        let opt_non_negative = |key: &str| -> Result<f32> {
+            match fields.get(key) {
+                Some(_) => non_negative_field(fields, key, ctx),
+                None => Ok(0.0),
+            }
+        };
+        Ok(TextBackground {
+            padding: opt_non_negative("padding")?,
+            corner_radius: opt_non_negative("corner_radius")?,
+            color: color(field(fields, "color", ctx)?, ctx)?,
+        })
+    }
+
+    fn font_family(&mut self, fields: &Fields, ctx: &str) -> Result<FontFamily> {
+        self.check_fields(fields, FONT_FIELDS, ctx)?;
+        let mut family = FontFamily::default();
+        if fields.contains_key("regular") {
+            family.regular = FontSource::Path(self.path(fields, "regular", ctx)?);
+        }
+        if fields.contains_key("bold") {
+            family.bold = Some(FontSource::Path(self.path(fields, "bold", ctx)?));
+        }
+        if fields.contains_key("italic") {
+            family.italic = Some(FontSource::Path(self.path(fields, "italic", ctx)?));
+        }
+        if fields.contains_key("bold_italic") {
+            family.bold_italic = Some(FontSource::Path(self.path(fields, "bold_italic", ctx)?));
+        }
+        for (index, value) in array_field(fields, "fallbacks", ctx)?.iter().enumerate() {
+            let path = value
+                .as_str()
+                .ok_or_else(|| invalid(ctx, &format!("fallbacks[{index}] must be a path")))?;
+            family = family.with_fallback(FontSource::Path(self.base_dir.join(path)));
+        }
+        Ok(family)
+    }
+
+    fn transform(&mut self, fields: &Fields, ctx: &str) -> Result<AnimatedTransform> {
+        self.check_fields(fields, TRANSFORM_FIELDS, ctx)?;
+        Ok(AnimatedTransform {
+            position: self.track(
+                fields.get("position"),
+                Vec2::ZERO,
+                &format!("{ctx}.position"),
+                vec2,
+            )?,
+            scale: self.track(
+                fields.get("scale"),
+                Vec2::ONE,
+                &format!("{ctx}.scale"),
+                vec2,
+            )?,
+            rotation: self.rotation_track(fields.get("rotation"), &format!("{ctx}.rotation"))?,
+            opacity: self.track(
+                fields.get("opacity"),
+                1.0,
+                &format!("{ctx}.opacity"),
+                number,
+            )?,
+            path: match fields.get("path") {
+                Some(value) => Some(self.path_motion(value, &format!("{ctx}.path"))?),
+                None => None,
+            },
+        })
+    }
+
+    // `{ "start": [x, y], "curves": [...], "progress": ..., "orient": ... }`,
+    // where each curve is `{ "to", "control1", "control2" }` and a curve
+    // without control points is a straight line. `progress` is a track.
+    fn path_motion(&mut self, value: &Value, ctx: &str) -> Result<PathMotion> {
+        let fields = as_object(value, ctx)?;
+        self.check_fields(fields, PATH_FIELDS, ctx)?;
+        let start = vec2(field(fields, "start", ctx)?, &format!("{ctx}.start"))?;
+        let mut path = BezierPath::new(start);
+        for (index, item) in array_field(fields, "curves", ctx)?.iter().enumerate() {
+            let ctx = format!("{ctx}.curves[{index}]");
+            let curve = as_object(item, &ctx)?;
+            self.check_fields(curve, CURVE_FIELDS, &ctx)?;
+            let point = |key: &str| vec2(field(curve, key, &ctx)?, &format!("{ctx}.{key}"));
+            let to = point("to")?;
+            path = match (curve.contains_key("control1"), curve.contains_key("control2")) {
+                (false, false) => path.with_line(to),
+                _ => path.with_curve(point("control1")?, point("control2")?, to),
+            };
+        }
+        let progress = field(fields, "progress", ctx)?;
+        let progress = self.track(Some(progress), 0.0, &format!("{ctx}.progress"), number)?;
+        let orient = opt_bool_field(fields, "orient", ctx)?.unwrap_or(false);
+        Ok(PathMotion::new(path, progress)
+            .with_context(|| ctx.to_string())?
+            .with_orient(orient))
+    }
+
+    // A property is either a constant value or a list of
+    // `{ "time", "value", "easing" }` keyframes, optionally wrapped as
+    // `{ "interpolation": ..., "pre": ..., "post": ..., "add": [...],
+    // "multiply": [...], "time_offset": ..., "time_scale": ...,
+    // "keyframes": ... }`. `add` takes procedural layers and
+    // `{ "type": "track", "track": ... }`; `multiply` takes tracks. Retiming
+    // applies to the whole property, combined tracks included.
+    fn track<T: Lerp>(
+        &mut self,
+        value: Option<&Value>,
+        default: T,
+        ctx: &str,
+        parse: fn(&Value, &str) -> Result<T>,
+    ) -> Result<Track<T>> {
+        let Some(mut value) = value else {
+            return Ok(Track::from_constant(default));
+        };
+        let mut interpolation = Interpolation::Linear;
+        let mut extrapolation = (Extrapolation::Hold, Extrapolation::Hold);
+        let mut added = Vec::new();
+        let mut combined = Vec::new();
+        let mut retime = (None, None);
+        // A CSV source stands in for the keyframe list, wrapped or not.
+        if let Some(fields) = value.as_object()
+            && !fields.contains_key("csv")
+        {
+            self.check_fields(fields, TRACK_FIELDS, ctx)?;
+            interpolation = match fields.get("interpolation").map(|kind| (kind, kind.as_str())) {
+                None | Some((_, Some("linear"))) => Interpolation::Linear,
+                Some((_, Some("catmull_rom"))) => Interpolation::CatmullRom,
+                Some((_, Some("tcb"))) => Interpolation::Tcb {
+                    tension: opt_number_field(fields, "tension", ctx)?.unwrap_or(0.0),
+                    continuity: opt_number_field(fields, "continuity", ctx)?.unwrap_or(0.0),
+                    bias: opt_number_field(fields, "bias", ctx)?.unwrap_or(0.0),
+                },
+                Some((other, _)) => bail!(invalid(ctx, &format!("unknown interpolation {other}"))),
+            };
+            let mode = |key: &str| match fields.get(key) {
+                None => Ok(Extrapolation::Hold),
+                Some(value) => value
+                    .as_str()
+                    .and_then(Extrapolation::from_name)
+                    .ok_or_else(|| {
+                        let names = "\"hold\", \"loop\", \"ping_pong\" or \"continue\"";
+                        invalid(ctx, &format!("{key} must be {names}"))
+                    }),
+            };
+            extrapolation = (mode("pre")?, mode("post")?);
+            for (index, item) in array_field(fields, "add", ctx)?.iter().enumerate() {
+                let ctx = format!("{ctx}.add[{index}]");
+                let item = as_object(item, &ctx)?;
+                if item.get("type").and_then(Value::as_str) != Some("track") {
+                    added.push(self.procedural(item, &ctx, parse)?);
+                    continue;
+                }
+                self.check_fields(item, TRACK_OPERAND_FIELDS, &ctx)?;
+                let operand = field(item, "track", &ctx)?;
+                let track = self.track(Some(operand), default, &format!("{ctx}.track"), parse)?;
+                combined.push(TrackOp::Add(track));
+            }
+            for (index, item) in array_field(fields, "multiply", ctx)?.iter().enumerate() {
+                let ctx = format!("{ctx}.multiply[{index}]");
+                combined.push(TrackOp::Multiply(self.track(Some(item), default, &ctx, parse)?));
+            }
+            retime = (
+                opt_number_field(fields, "time_scale", ctx)?,
+                opt_number_field(fields, "time_offset", ctx)?,
+            );
+            value = field(fields, "keyframes", ctx)?;
+        }
+        let track = self
+            .keyframe_track(value, ctx, parse)?
+            .with_interpolation(interpolation)
+            .with_extrapolation(extrapolation.0, extrapolation.1);
+        let track = added.into_iter().fold(track, Track::with_added);
+        let mut track = combined.into_iter().fold(track, |track, op| match op {
+            TrackOp::Add(other) => track + other,
+            TrackOp::Multiply(other) => track * other,
+        });
+        // Scaled first, so the offset is in project seconds.
+        if let Some(scale) = retime.0 {
+            track = track.with_time_scale(scale);
+        }
+        if let Some(offset) = retime.1 {
+            track = track.with_time_offset(offset);
+        }
+        Ok(track)
+    }
+
+    // A track as in `track`, which may also set `"shortest_arc": true` to turn
+    // between keyframes the short way round.
+    fn rotation_track(&mut self, value: Option<&Value>, ctx: &str) -> Result<Track<f32>> {
+        let Some(fields) = value.and_then(Value::as_object) else {
+            return self.track(value, 0.0, ctx, number);
+        };
+        let shortest_arc = opt_bool_field(fields, "shortest_arc", ctx)?.unwrap_or(false);
+        let mut fields = fields.clone();
+        fields.remove("shortest_arc");
+        let track = self.track(Some(&Value::Object(fields)), 0.0, ctx, number)?;
+        Ok(if shortest_arc { track.with_shortest_arc() } else { track })
+    }
+
+    // A constant or a keyframe list, as in `track`.
+    fn keyframe_track<T: Lerp>(
+        &mut self,
+        value: &Value,
+        ctx: &str,
+        parse: fn(&Value, &str) -> Result<T>,
+    ) -> Result<Track<T>> {
+        if let Some(fields) = value.as_object() {
+            return self.csv_track(fields, ctx, parse);
+        }
+        let keyframes = match value.as_array() {
+            Some(items) if items.iter().all(Value::is_object) && !items.is_empty() => items,
+            _ => return Ok(Track::from_constant(parse(value, ctx)?)),
+        };
+
+        let mut parsed = Vec::with_capacity(keyframes.len());
+        for (index, item) in keyframes.iter().enumerate() {
+            let ctx = format!("{ctx}[{index}]");
+            let fields = as_object(item, &ctx)?;
+            self.check_fields(fields, KEYFRAME_FIELDS, &ctx)?;
+            let easing = match fields.get("easing") {
+                Some(value) => self.easing(value, &ctx)?,
+                None => Easing::Linear,
+            };
+            parsed.push(Keyframe::new(
+                number_field(fields, "time", &ctx)?,
+                parse(field(fields, "value", &ctx)?, &ctx)?,
+                easing,
+            ));
+        }
+        Track::new(parsed).with_context(|| ctx.to_string())
+    }
+
+    // `{ "csv": path, "time": column, "value": column, "easing": ... }`, a
+    // keyframe per row. `value` names two columns (`["x", "y"]`) for a
+    // position or scale.
+    fn csv_track<T: Lerp>(
+        &mut self,
+        fields: &Fields,
+        ctx: &str,
+        parse: fn(&Value, &str) -> Result<T>,
+    ) -> Result<Track<T>> {
+        self.check_fields(fields, CSV_FIELDS, ctx)?;
+        let path = self.base_dir.join(string_field(fields, "csv", ctx)?);
+        let time = string_field(fields, "time", ctx)?;
+        let columns: Option<Vec<&str>> = match field(fields, "value", ctx)? {
+            Value::String(column) => Some(vec![column.as_str()]),
+            Value::Array(items) => items.iter().map(Value::as_str).collect(),
+            _ => None,
+        };
+        let columns = columns
+            .ok_or_else(|| invalid(ctx, "\"value\" must be a column name or a list of them"))?;
+        let easing = match fields.get("easing") {
+            Some(value) => self.easing(value, ctx)?,
+            None => Easing::Linear,
+        };
+        let value_ctx = format!("{ctx}.value");
+        CsvTable::load(&path)
+            .and_then(|table| {
+                table.track_with(time, &columns, easing, |numbers| match numbers {
+                    [number] => parse(&Value::from(*number), &value_ctx),
+                    _ => parse(&Value::from(numbers.to_vec()), &value_ctx),
+                })
+            })
+            .with_context(|| format!("{ctx}: {}", path.display()))
+    }
+
+    // `{ "type": "sine", "amplitude", "frequency", "phase" }` or
+    // `{ "type": "noise", "seed", "frequency", "amplitude", "octaves" }`.
+    fn procedural<T: Lerp>(
+        &mut self,
+        fields: &Fields,
+        ctx: &str,
+        parse: fn(&Value, &str) -> Result<T>,
+    ) -> Result<Procedural<T>> {
+        let kind = string_field(fields, "type", ctx)?;
+        let known = match kind {
+            "sine" => SINE_FIELDS,
+            "noise" => NOISE_FIELDS,
+            other => bail!(invalid(ctx, &format!("unknown procedural type \"{other}\""))),
+        };
+        self.check_fields(fields, known, ctx)?;
+        let amplitude = parse(field(fields, "amplitude", ctx)?, &format!("{ctx}.amplitude"))?;
+        let frequency = number_field(fields, "frequency", ctx)?;
+        if kind == "sine" {
+            let phase = opt_number_field(fields, "phase", ctx)?.unwrap_or(0.0);
+            return Ok(SineTrack::new(amplitude, frequency).with_phase(phase).into());
+        }
+        let seed = field(fields, "seed", ctx)?
+            .as_u64()
+            .ok_or_else(|| invalid(ctx, "\"seed\" must be a non-negative integer"))?;
+        let mut noise = NoiseTrack::new(seed, frequency, amplitude);
+        if fields.contains_key("octaves") {
+            noise = noise.with_octaves(u32_field(fields, "octaves", ctx)?);
+        }
+        Ok(noise.into())
+    }
+
+    fn transition(&mut self, fields: &Fields, ctx: &str) -> Result<Transition> {
+        self.check_fields(fields, TRANSITION_FIELDS, ctx)?;
+        let kind = match string_field(fields, "kind", ctx)? {
+            "crossfade" => TransitionKind::Crossfade,
+            "slide" => TransitionKind::Slide {
+                direction: direction(fields, ctx)?,
+            },
+            "wipe" => TransitionKind::Wipe {
+                direction: direction(fields, ctx)?,
+            },
+            "zoom" => TransitionKind::Zoom {
+                amount: opt_number_field(fields, "amount", ctx)?.unwrap_or(0.2),
+            },
+            other => bail!(invalid(
+                ctx,
+                &format!("unknown transition kind \"{other}\"")
+            )),
+        };
+        let mut transition = Transition::new(kind);
+        if let Some(value) = fields.get("easing") {
+            transition = transition.with_easing(self.easing(value, ctx)?);
+        }
+        Ok(transition)
+    }
+
+    fn binding(&mut self, fields: &Fields, ctx: &str) -> Result<Binding> {
+        self.check_fields(fields, BINDING_FIELDS, ctx)?;
+        let target = match string_field(fields, "target", ctx)? {
+            "position_x" => BindTarget::PositionX,
+            "position_y" => BindTarget::PositionY,
+            "scale_x" => BindTarget::ScaleX,
+            "scale_y" => BindTarget::ScaleY,
+            "rotation" => BindTarget::Rotation,
+            "opacity" => BindTarget::Opacity,
+            "text" => BindTarget::Text {
+                precision: match fields.get("precision") {
+                    Some(_) => u32_field(fields, "precision", ctx)? as usize,
+                    None => 0,
+                },
+            },
+            other => bail!(invalid(ctx, &format!("unknown binding target \"{other}\""))),
+        };
+        Ok(Binding::new(string_field(fields, "variable", ctx)?, target).with_mapping(
+            opt_number_field(fields, "scale", ctx)?.unwrap_or(1.0),
+            opt_number_field(fields, "offset", ctx)?.unwrap_or(0.0),
+        ))
+    }
+
+    // A name such as `"ease_out_back"`, or a spring object.
+    fn easing(&mut self, value: &Value, ctx: &str) -> Result<Easing> {
+        let ctx = format!("{ctx}.easing");
+        let Some(fields) = value.as_object() else {
+            return match value.as_str().and_then(Easing::from_name) {
+                Some(easing) => Ok(easing),
+                None => bail!(invalid(&ctx, &format!("unknown easing {value}"))),
+            };
+        };
+        self.check_fields(fields, SPRING_FIELDS, &ctx)?;
+        if string_field(fields, "type", &ctx)? != "spring" {
+            bail!(invalid(&ctx, "only \"spring\" easings can be objects"));
+        }
+        let default = Spring::default();
+        let spring = Spring::new(
+            opt_number_field(fields, "stiffness", &ctx)?.unwrap_or(default.stiffness),
+            opt_number_field(fields, "damping", &ctx)?.unwrap_or(default.damping),
+            opt_number_field(fields, "mass", &ctx)?.unwrap_or(default.mass),
+        )
+        .with_context(|| ctx.clone())?;
+        Ok(Easing::Spring(spring))
+    }
+
+    fn path(&self, fields: &Fields, key: &str, ctx: &str) -> Result<PathBuf> {
+        Ok(self.base_dir.join(string_field(fields, key, ctx)?))
+    }
+}
+
+// Whether a uniform's track holds `[x, y]` values rather than numbers,
+// judged by its first value.
+fn is_vec2_track(value: &Value) -> bool {
+    match value {
+        Value::Array(items) => match items.first() {
+            Some(Value::Object(keyframe)) => keyframe.get("value").is_some_and(is_vec2_track),
+            _ => true,
+        },
+        Value::Object(fields) => match fields.get("csv") {
+            Some(_) => fields.get("value").is_some_and(Value::is_array),
+            None => fields.get("keyframes").is_some_and(is_vec2_track),
+        },
+        _ => false,
+    }
+}
+
+fn direction(fields: &Fields, ctx: &str) -> Result<TransitionDirection> {
+    let direction = match string_field(fields, "direction", ctx)? {
+        "left" => TransitionDirection::Left,
+        "right" => TransitionDirection::Right,
+        "up" => TransitionDirection::Up,
+        "down" => TransitionDirection::Down,
+        other => bail!(invalid(ctx, &format!("unknown direction \"{other}\""))),
+    };
+    Ok(direction)
+}
+
+fn blend_mode(fields: &Fields, ctx: &str) -> Result<BlendMode> {
+    let blend = match string_field(fields, "blend", ctx)? {
+        "normal" => BlendMode::Normal,
+        "add" => BlendMode::Add,
+        "multiply" => BlendMode::Multiply,
+        "screen" => BlendMode::Screen,
+        "subtract" => BlendMode::Subtract,
+        other => bail!(invalid(ctx, &format!("unknown blend mode \"{other}\""))),
+    };
+    Ok(blend)
+}
+
+fn track_matte(fields: &Fields, ctx: &str) -> Result<TrackMatte> {
+    let matte = match string_field(fields, "matte", ctx)? {
+        "alpha" => TrackMatte::Alpha,
+        "alpha_inverted" => TrackMatte::AlphaInverted,
+        "luma" => TrackMatte::Luma,
+        "luma_inverted" => TrackMatte::LumaInverted,
+        other => bail!(invalid(ctx, &format!("unknown matte \"{other}\""))),
+    };
+    Ok(matte)
+}
+
+// Plugin params: numbers, bools, `[x, y]` pairs, `#rrggbb[aa]` colors, and
+// any other string as text.
+fn params(fields: &Fields, ctx: &str) -> Result<Params> {
+    let mut params = Params::new();
+    let Some(value) = fields.get("params") else {
+        return Ok(params);
+    };
+    let ctx = format!("{ctx}.params");
+    for (key, value) in as_object(value, &ctx)? {
+        let ctx = format!("{ctx}.{key}");
+        let param = match value {
+            Value::Number(_) => ParamValue::Number(number(value, &ctx)?),
+            Value::Bool(flag) => ParamValue::Bool(*flag),
+            Value::Array(_) => ParamValue::Vec2(vec2(value, &ctx)?),
+            Value::String(text) => match parse_hex_color(text) {
+                Some(color) => ParamValue::Color(color),
+                None => ParamValue::Text(text.clone()),
+            },
+            _ => bail!(invalid(&ctx, "unsupported param value")),
+        };
+        params.insert(key.clone(), param);
+    }
+    Ok(params)
+}
+
+fn field<'a>(fields: &'a Fields, key: &str, ctx: &str) -> Result<&'a Value> {
+    fields
+        .get(key)
+        .ok_or_else(|| invalid(ctx, &format!("missing field \"{key}\"")))
+}
+
+fn as_object<'a>(value: &'a Value, ctx: &str) -> Result<&'a Fields> {
+    value
+        .as_object()
+        .ok_or_else(|| invalid(ctx, "expected an object"))
+}
+
+fn array_field<'a>(fields: &'a Fields, key: &str, ctx: &str) -> Result<&'a [Value]> {
+    match fields.get(key) {
+        Some(value) => value
+            .as_array()
+            .map(Vec::as_slice)
+            .ok_or_else(|| invalid(ctx, &format!("\"{key}\" must be an array"))),
+        None => Ok(&[]),
+    }
+}
+
+fn string_field<'a>(fields: &'a Fields, key: &str, ctx: &str) -> Result<&'a str> {
+    field(fields, key, ctx)?
+        .as_str()
+        .ok_or_else(|| invalid(ctx, &format!("\"{key}\" must be a string")))
+}
+
+fn number(value: &Value, ctx: &str) -> Result<f32> {
+    value
+        .as_f64()
+        .map(|value| value as f32)
+        .ok_or_else(|| invalid(ctx, "expected a number"))
+}
+
+fn number_field(fields: &Fields, key: &str, ctx: &str) -> Result<f32> {
+    number(field(fields, key, ctx)?, &format!("{ctx}.{key}"))
+}
+
+fn non_negative_field(fields: &Fields, key: &str, ctx: &str) -> Result<f32> {
+    let value = number_field(fields, key, ctx)?;
+    if value < 0.0 {
+        bail!(invalid(ctx, &format!("\"{key}\" must be >= 0")));
+    }
+    Ok(value)
+}
+
+fn opt_number_field(fields: &Fields, key: &str, ctx: &str) -> Result<Option<f32>> {
+    match fields.get(key) {
+        Some(value) => Ok(Some(number(value, &format!("{ctx}.{key}"))?)),
+        None => Ok(None),
+    }
+}
+
+fn opt_bool_field(fields: &Fields, key: &str, ctx: &str) -> Result<Option<bool>> {
+    match fields.get(key) {
+        Some(value) => value
+            .as_bool()
+            .map(Some)
+            .ok_or_else(|| invalid(ctx, &format!("\"{key}\" must be true or false"))),
+        None => Ok(None),
+    }
+}
+
+fn u32_field(fields: &Fields, key: &str, ctx: &str) -> Result<u32> {
+    field(fields, key, ctx)?
+        .as_u64()
+        .and_then(|value| u32::try_from(value).ok())
+        .ok_or_else(|| invalid(ctx, &format!("\"{key}\" must be a non-negative integer")))
+}
+
+fn vec2(value: &Value, ctx: &str) -> Result<Vec2> {
+    match value.as_array().map(Vec::as_slice) {
+        Some([x, y]) => Ok(Vec2 {
+            x: number(x, ctx)?,
+            y: number(y, ctx)?,
+        }),
+        _ => bail!(invalid(ctx, "expected [x, y]")),
+    }
+}
+
+fn color(value: &Value, ctx: &str) -> Result<Color> {
+    value
+        .as_str()
+        .and_then(parse_hex_color)
+        .ok_or_else(|| invalid(ctx, "color must be \"#rrggbb\" or \"#rrggbbaa\""))
+}
+
+fn parse_hex_color(text: &str) -> Option<Color> {
+    let hex = text.strip_prefix('#')?;
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+    Some(Color::rgba(channel(0)?, channel(2)?, channel(4)?, alpha))
+}
+
+fn invalid(ctx: &str, message: &str) -> anyhow::Error {
+    anyhow!(ErrorKind::InvalidProject.error(format!("{ctx}: {message}")))
+}
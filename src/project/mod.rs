@@ -0,0 +1,7 @@
+pub mod load;
+pub mod scene_text;
+pub mod schema;
+
+pub use load::{LoadMode, OutputMetadata, Project};
+pub use scene_text::{compile_scene, SceneDocument, SourceSpan};
+pub use schema::{migrate, SCHEMA_VERSION};
@@ -0,0 +1,546 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Map, Value};
+
+use crate::error::ErrorKind;
+use crate::project::schema::SCHEMA_VERSION;
+
+// A terser, hand-editable form of the JSON project file (`.scene`). Each
+// line is a node: a name, then values, then `key=value` properties, then an
+// optional `{ ... }` block of child nodes:
+//
+//     width 800; height 600; fps 30; bg #101014
+//     layer "title" {
+//         clip 0 4 name="logo" {
+//             text "Hello" font_size=48 color=#ffffff
+//             position { key 0 (0, -40) ease_out_back; key 0.6 (0, 0) }
+//             opacity { key 0 0; key 0.3 1 }
+//         }
+//     }
+//
+// A node becomes the JSON field of the same name; a lone value is the field's
+// value, several become an array. `layer`, `group`, `clip`, `effect`,
+// `marker`, `binding`, `key` (keyframe), `add` and `multiply` can repeat and
+// fill the matching array; a layer joins a group with its `group=` property.
+// Inside a clip, object nodes (`rect`, `text`, ...) fill `object`, and
+// transform properties may skip the `transform` block. Values are numbers,
+// "strings", bare words, #colors, `(x, y)` pairs and `[...]` lists; `//`
+// starts a comment.
+
+// Where a node starts in the source, counted from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceSpan {
+    pub line: u32,
+    pub column: u32,
+}
+
+// A compiled scene file: the JSON document it describes, plus where each
+// part of it came from, keyed by the paths the loader reports
+// (`project.layers[0].clips[1]`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneDocument {
+    pub doc: Value,
+    spans: HashMap<String, SourceSpan>,
+}
+
+impl SceneDocument {
+    // Span of the deepest part of `path` the source spelled out.
+    pub fn span_of(&self, path: &str) -> Option<SourceSpan> {
+        let mut path = path;
+        loop {
+            if let Some(span) = self.spans.get(path) {
+                return Some(*span);
+            }
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
+
+    // Span of the most specific project path named in `err`'s messages.
+    pub fn locate(&self, err: &anyhow::Error) -> Option<SourceSpan> {
+        err.chain()
+            .filter_map(|cause| {
+                let message = cause.to_string();
+                let path = message.split(": ").next()?.to_string();
+                path.starts_with("project").then_some(path)
+            })
+            .max_by_key(String::len)
+            .and_then(|path| self.span_of(&path))
+    }
+}
+
+pub fn compile_scene(source: &str) -> Result<SceneDocument> {
+    let tokens = tokenize(source)?;
+    let nodes = Parser {
+        tokens: &tokens,
+        next: 0,
+    }
+    .nodes(None)?;
+
+    let mut compiler = Compiler {
+        spans: HashMap::new(),
+    };
+    compiler
+        .spans
+        .insert("project".to_string(), SourceSpan { line: 1, column: 1 });
+    let mut root = Map::new();
+    for node in nodes {
+        compiler.insert(&mut root, node, "project", Parent::Root)?;
+    }
+    root.entry("schema_version")
+        .or_insert_with(|| Value::from(SCHEMA_VERSION));
+    Ok(SceneDocument {
+        doc: Value::Object(root),
+        spans: compiler.spans,
+    })
+}
+
+fn error_at(span: SourceSpan, message: &str) -> anyhow::Error {
+    anyhow!(ErrorKind::InvalidProject.error(format!(
+        "line {}, column {}: {message}",
+        span.line, span.column
+    )))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Value(Value),
+    Open(char),
+    Close(char),
+    Equals,
+    Comma,
+    // A newline or `;` ending a node.
+    End,
+}
+
+struct Cursor<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    span: SourceSpan,
+}
+
+impl Cursor<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.span.line += 1;
+            self.span.column = 1;
+        } else {
+            self.span.column += 1;
+        }
+        c
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, SourceSpan)>> {
+    let mut tokens = Vec::new();
+    let mut cursor = Cursor {
+        chars: source.chars().peekable(),
+        span: SourceSpan { line: 1, column: 1 },
+    };
+    // Newlines inside `(...)` and `[...]` don't end the node.
+    let mut nesting = 0u32;
+    while let Some(c) = cursor.peek() {
+        let span = cursor.span;
+        let token = match c {
+            '\n' | ';' => {
+                cursor.bump();
+                if nesting > 0 {
+                    continue;
+                }
+                Token::End
+            }
+            c if c.is_whitespace() => {
+                cursor.bump();
+                continue;
+            }
+            '/' => {
+                cursor.bump();
+                if cursor.bump() != Some('/') {
+                    bail!(error_at(span, "expected \"//\" to start a comment"));
+                }
+                while !matches!(cursor.peek(), None | Some('\n')) {
+                    cursor.bump();
+                }
+                continue;
+            }
+            '{' => {
+                cursor.bump();
+                Token::Open(c)
+            }
+            '}' => {
+                cursor.bump();
+                Token::Close(c)
+            }
+            '(' | '[' => {
+                cursor.bump();
+                nesting += 1;
+                Token::Open(c)
+            }
+            ')' | ']' => {
+                cursor.bump();
+                nesting = nesting.saturating_sub(1);
+                Token::Close(c)
+            }
+            '=' => {
+                cursor.bump();
+                Token::Equals
+            }
+            ',' => {
+                cursor.bump();
+                Token::Comma
+            }
+            '"' => {
+                cursor.bump();
+                let mut text = String::new();
+                loop {
+                    match cursor.bump() {
+                        Some('"') => break,
+                        Some('\\') => match cursor.bump() {
+                            Some('n') => text.push('\n'),
+                            Some('t') => text.push('\t'),
+                            Some(c @ ('"' | '\\')) => text.push(c),
+                            _ => bail!(error_at(span, "unknown escape in string")),
+                        },
+                        Some(c) => text.push(c),
+                        None => bail!(error_at(span, "unterminated string")),
+                    }
+                }
+                Token::Value(Value::String(text))
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = cursor.peek()
+                    && (c.is_alphanumeric() || matches!(c, '_' | '#' | '.' | '-' | '+'))
+                {
+                    word.push(c);
+                    cursor.bump();
+                }
+                if word.is_empty() {
+                    bail!(error_at(span, &format!("unexpected \"{c}\"")));
+                }
+                word_token(&word, span)?
+            }
+        };
+        tokens.push((token, span));
+    }
+    Ok(tokens)
+}
+
+fn word_token(word: &str, span: SourceSpan) -> Result<Token> {
+    let first = word.chars().next().unwrap_or_default();
+    if first == '#' {
+        return Ok(Token::Value(Value::String(word.to_string())));
+    }
+    if first.is_ascii_digit() || matches!(first, '-' | '+' | '.') {
+        // Whole numbers stay integers, for fields like `width` and `fps`.
+        let number = match word.parse::<i64>() {
+            Ok(int) => Some(Value::from(int)),
+            Err(_) => word.parse::<f64>().ok().map(Value::from),
+        };
+        return number
+            .map(Token::Value)
+            .ok_or_else(|| error_at(span, &format!("\"{word}\" is not a number")));
+    }
+    match word {
+        "true" => Ok(Token::Value(Value::Bool(true))),
+        "false" => Ok(Token::Value(Value::Bool(false))),
+        _ => Ok(Token::Ident(word.to_string())),
+    }
+}
+
+#[derive(Debug)]
+struct Node {
+    name: String,
+    span: SourceSpan,
+    args: Vec<(Value, SourceSpan)>,
+    props: Vec<(String, Value, SourceSpan)>,
+    children: Vec<Node>,
+}
+
+struct Parser<'a> {
+    tokens: &'a [(Token, SourceSpan)],
+    next: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+
+    // Span of the next token, or just past the last one at the end.
+    fn span(&self) -> SourceSpan {
+        match self.tokens.get(self.next).or(self.tokens.last()) {
+            Some((_, span)) => *span,
+            None => SourceSpan { line: 1, column: 1 },
+        }
+    }
+
+    fn bump(&mut self) -> Option<(Token, SourceSpan)> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    // Nodes up to the end of input, or up to the `}` closing the block opened
+    // at `open`.
+    fn nodes(&mut self, open: Option<SourceSpan>) -> Result<Vec<Node>> {
+        let mut nodes = Vec::new();
+        loop {
+            match (self.peek(), open) {
+                (Some(Token::End), _) => {
+                    self.bump();
+                }
+                (Some(Token::Close('}')), Some(_)) => {
+                    self.bump();
+                    return Ok(nodes);
+                }
+                (None, None) => return Ok(nodes),
+                (None, Some(open)) => bail!(error_at(open, "\"{\" is never closed")),
+                (Some(Token::Ident(_)), _) => nodes.push(self.node()?),
+                _ => bail!(error_at(self.span(), "expected a node name")),
+            }
+        }
+    }
+
+    fn node(&mut self) -> Result<Node> {
+        let Some((Token::Ident(name), span)) = self.bump() else {
+            unreachable!("`nodes` only calls `node` on a name");
+        };
+        let mut node = Node {
+            name,
+            span,
+            args: Vec::new(),
+            props: Vec::new(),
+            children: Vec::new(),
+        };
+        loop {
+            let span = self.span();
+            match self.peek() {
+                None | Some(Token::End) | Some(Token::Close('}')) => return Ok(node),
+                Some(Token::Open('{')) => {
+                    self.bump();
+                    node.children = self.nodes(Some(span))?;
+                    return Ok(node);
+                }
+                Some(Token::Ident(key))
+                    if self.tokens.get(self.next + 1).map(|(token, _)| token)
+                        == Some(&Token::Equals) =>
+                {
+                    let key = key.clone();
+                    self.next += 2;
+                    node.props.push((key, self.value()?, span));
+                }
+                _ => {
+                    if !node.props.is_empty() {
+                        bail!(error_at(span, "values must come before properties"));
+                    }
+                    node.args.push((self.value()?, span));
+                }
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        let span = self.span();
+        match self.bump() {
+            Some((Token::Value(value), _)) => Ok(value),
+            // Bare words, e.g. easing names.
+            Some((Token::Ident(word), _)) => Ok(Value::String(word)),
+            Some((Token::Open(open @ ('(' | '[')), _)) => {
+                let close = if open == '(' { ')' } else { ']' };
+                let mut items = Vec::new();
+                loop {
+                    match self.peek() {
+                        Some(Token::Close(c)) if *c == close => {
+                            self.bump();
+                            return Ok(Value::Array(items));
+                        }
+                        Some(Token::Comma) => {
+                            self.bump();
+                        }
+                        None => {
+                            bail!(error_at(span, &format!("\"{open}\" is never closed")));
+                        }
+                        _ => items.push(self.value()?),
+                    }
+                }
+            }
+            _ => bail!(error_at(span, "expected a value")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Parent {
+    Root,
+    Layer,
+    Clip,
+    Other,
+}
+
+// Nodes that may repeat, and the array field each one fills.
+fn list_field(name: &str) -> Option<&'static str> {
+    let field = match name {
+        "layer" => "layers",
+        "group" => "groups",
+        "clip" => "clips",
+        "effect" => "effects",
+        "marker" => "markers",
+        "binding" => "bindings",
+        "key" => "keyframes",
+        "add" => "add",
+        "multiply" => "multiply",
+        "curve" => "curves",
+        _ => return None,
+    };
+    Some(field)
+}
+
+// Fields filled by a node's leading values, in order, and whether the node is
+// always an object (rather than collapsing to a lone value).
+fn positional(name: &str) -> (&'static [&'static str], bool) {
+    match name {
+        "layer" => (&["name"], true),
+        "group" => (&["name"], true),
+        "clip" => (&["start", "end"], true),
+        "effect" => (&["kind"], true),
+        "marker" => (&["time", "name"], true),
+        "binding" => (&["variable", "target"], true),
+        "key" => (&["time", "value", "easing"], true),
+        "add" => (&["type", "track"], true),
+        "curve" => (&["to", "control1", "control2"], true),
+        "transition_in" => (&["kind"], true),
+        "easing" => (&["type"], false),
+        "rect" => (&["width", "height", "color"], true),
+        "circle" => (&["radius", "color"], true),
+        "image" => (&["path"], true),
+        "text" => (&["text"], true),
+        "composition" => (&["width", "height"], true),
+        "plugin" => (&["kind"], true),
+        _ => (&[], false),
+    }
+}
+
+const OBJECT_TYPES: &[&str] = &["rect", "circle", "image", "text", "composition", "plugin"];
+const TRANSFORM_FIELDS: &[&str] = &["position", "scale", "rotation", "opacity", "path"];
+
+struct Compiler {
+    spans: HashMap<String, SourceSpan>,
+}
+
+impl Compiler {
+    fn insert(
+        &mut self,
+        map: &mut Map<String, Value>,
+        node: Node,
+        path: &str,
+        parent: Parent,
+    ) -> Result<()> {
+        let name = node.name.clone();
+        if let Some(list) = list_field(&name) {
+            let Value::Array(items) = map.entry(list).or_insert_with(|| Value::Array(Vec::new()))
+            else {
+                bail!(error_at(node.span, &format!("\"{list}\" is already set")));
+            };
+            let index = items.len();
+            // The loader reports keyframes without the `keyframes` step.
+            if name == "key" {
+                self.spans.insert(format!("{path}[{index}]"), node.span);
+            }
+            items.push(self.node_value(node, &format!("{path}.{list}[{index}]"))?);
+            return Ok(());
+        }
+
+        let (map, path, key) = if parent == Parent::Clip && OBJECT_TYPES.contains(&name.as_str()) {
+            (map, path.to_string(), "object".to_string())
+        } else if matches!(parent, Parent::Clip | Parent::Layer)
+            && TRANSFORM_FIELDS.contains(&name.as_str())
+        {
+            let Value::Object(transform) = map
+                .entry("transform")
+                .or_insert_with(|| Value::Object(Map::new()))
+            else {
+                bail!(error_at(node.span, "\"transform\" is already set"));
+            };
+            (transform, format!("{path}.transform"), name.clone())
+        } else {
+            (map, path.to_string(), name.clone())
+        };
+        if map.contains_key(&key) {
+            bail!(error_at(node.span, &format!("\"{key}\" is already set")));
+        }
+        let value = self.node_value(node, &format!("{path}.{key}"))?;
+        map.insert(key, value);
+        Ok(())
+    }
+
+    fn node_value(&mut self, node: Node, path: &str) -> Result<Value> {
+        self.spans.insert(path.to_string(), node.span);
+        let (fields, always_object) = positional(&node.name);
+        if !always_object && node.props.is_empty() && node.children.is_empty() {
+            let mut args: Vec<Value> = node.args.into_iter().map(|(value, _)| value).collect();
+            return match args.len() {
+                0 => bail!(error_at(
+                    node.span,
+                    &format!("\"{}\" needs a value", node.name)
+                )),
+                1 => Ok(args.remove(0)),
+                _ => Ok(Value::Array(args)),
+            };
+        }
+
+        let mut map = Map::new();
+        if OBJECT_TYPES.contains(&node.name.as_str()) {
+            map.insert("type".to_string(), Value::String(node.name.clone()));
+        }
+        if let Some((_, span)) = node.args.get(fields.len()) {
+            bail!(error_at(
+                *span,
+                &format!("too many values for \"{}\"", node.name)
+            ));
+        }
+        for (field, (value, span)) in fields.iter().zip(node.args) {
+            self.spans.insert(format!("{path}.{field}"), span);
+            map.insert(field.to_string(), value);
+        }
+        for (key, value, span) in node.props {
+            if map.contains_key(&key) {
+                bail!(error_at(span, &format!("\"{key}\" is already set")));
+            }
+            self.spans.insert(format!("{path}.{key}"), span);
+            map.insert(key, value);
+        }
+        let parent = match node.name.as_str() {
+            "layer" => Parent::Layer,
+            "clip" => Parent::Clip,
+            _ => Parent::Other,
+        };
+        for child in node.children {
+            self.insert(&mut map, child, path, parent)?;
+        }
+
+        // `add track { key ... }`: everything but the type is the track.
+        if node.name == "add" && map.get("type").and_then(Value::as_str) == Some("track") {
+            let mut track = map;
+            map = Map::new();
+            map.insert("type".to_string(), track.remove("type").unwrap_or_default());
+            let track = match track.remove("track") {
+                Some(value) if track.is_empty() => value,
+                Some(_) => {
+                    bail!(error_at(
+                        node.span,
+                        "give the added track as a value or a block"
+                    ));
+                }
+                None => Value::Object(track),
+            };
+            map.insert("track".to_string(), track);
+        }
+        Ok(Value::Object(map))
+    }
+}
@@ -0,0 +1,51 @@
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::error::ErrorKind;
+
+// Bump when the project file layout changes, and append the step that
+// upgrades documents from the previous version to `MIGRATIONS`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+type Migration = fn(&mut Value) -> Result<()>;
+
+// `MIGRATIONS[n]` upgrades a version `n + 1` document to version `n + 2`.
+const MIGRATIONS: &[Migration] = &[];
+
+// Upgrades `doc` in place to `SCHEMA_VERSION`, noting each step in `warnings`.
+pub fn migrate(doc: &mut Value, warnings: &mut Vec<String>) -> Result<()> {
+    let version = match doc.get("schema_version") {
+        Some(value) => {
+            let version = value.as_u64().filter(|version| *version >= 1).context(
+                ErrorKind::InvalidProject.error("schema_version must be a positive integer"),
+            )?;
+            version as u32
+        }
+        None => {
+            warnings.push(format!(
+                "project has no schema_version; assuming version {SCHEMA_VERSION}"
+            ));
+            SCHEMA_VERSION
+        }
+    };
+
+    if version > SCHEMA_VERSION {
+        bail!(ErrorKind::InvalidProject.error(format!(
+            "project schema_version {version} is newer than supported version {SCHEMA_VERSION}"
+        )));
+    }
+
+    for from in version..SCHEMA_VERSION {
+        let step = MIGRATIONS[(from - 1) as usize];
+        step(doc).with_context(|| format!("failed to migrate project from version {from}"))?;
+        warnings.push(format!(
+            "migrated project from schema version {from} to {}",
+            from + 1
+        ));
+    }
+
+    if let Some(root) = doc.as_object_mut() {
+        root.insert("schema_version".to_string(), Value::from(SCHEMA_VERSION));
+    }
+    Ok(())
+}
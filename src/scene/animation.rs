@@ -1,28 +1,316 @@
+use std::f32::consts::PI;
+use std::ops::{Add, Mul};
+
 use anyhow::{bail, Result};
 
+use crate::error::ErrorKind;
+use crate::scene::procedural::Procedural;
 use crate::scene::Vec2;
 
+// The standard (Penner) easing set, plus springs. Back, elastic and
+// underdamped springs overshoot: their output leaves 0..1 partway through,
+// so values swing past the keyframes.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Easing {
     Linear,
+    // Keeps the keyframe's value until the next keyframe, then jumps.
+    Hold,
+    EaseInSine,
+    EaseOutSine,
+    EaseInOutSine,
+    EaseInQuad,
+    EaseOutQuad,
     EaseInOutQuad,
+    EaseInCubic,
     EaseOutCubic,
+    EaseInOutCubic,
+    EaseInQuart,
+    EaseOutQuart,
+    EaseInOutQuart,
+    EaseInQuint,
+    EaseOutQuint,
+    EaseInOutQuint,
+    EaseInExpo,
+    EaseOutExpo,
+    EaseInOutExpo,
+    EaseInCirc,
+    EaseOutCirc,
+    EaseInOutCirc,
+    EaseInBack,
+    EaseOutBack,
+    EaseInOutBack,
+    EaseInElastic,
+    EaseOutElastic,
+    EaseInOutElastic,
+    EaseInBounce,
+    EaseOutBounce,
+    EaseInOutBounce,
+    Spring(Spring),
 }
 
 impl Easing {
+    // Project-file name, e.g. `"ease_in_out_back"`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let easing = match name {
+            "linear" => Easing::Linear,
+            "hold" => Easing::Hold,
+            "ease_in_sine" => Easing::EaseInSine,
+            "ease_out_sine" => Easing::EaseOutSine,
+            "ease_in_out_sine" => Easing::EaseInOutSine,
+            "ease_in_quad" => Easing::EaseInQuad,
+            "ease_out_quad" => Easing::EaseOutQuad,
+            "ease_in_out_quad" => Easing::EaseInOutQuad,
+            "ease_in_cubic" => Easing::EaseInCubic,
+            "ease_out_cubic" => Easing::EaseOutCubic,
+            "ease_in_out_cubic" => Easing::EaseInOutCubic,
+            "ease_in_quart" => Easing::EaseInQuart,
+            "ease_out_quart" => Easing::EaseOutQuart,
+            "ease_in_out_quart" => Easing::EaseInOutQuart,
+            "ease_in_quint" => Easing::EaseInQuint,
+            "ease_out_quint" => Easing::EaseOutQuint,
+            "ease_in_out_quint" => Easing::EaseInOutQuint,
+            "ease_in_expo" => Easing::EaseInExpo,
+            "ease_out_expo" => Easing::EaseOutExpo,
+            "ease_in_out_expo" => Easing::EaseInOutExpo,
+            "ease_in_circ" => Easing::EaseInCirc,
+            "ease_out_circ" => Easing::EaseOutCirc,
+            "ease_in_out_circ" => Easing::EaseInOutCirc,
+            "ease_in_back" => Easing::EaseInBack,
+            "ease_out_back" => Easing::EaseOutBack,
+            "ease_in_out_back" => Easing::EaseInOutBack,
+            "ease_in_elastic" => Easing::EaseInElastic,
+            "ease_out_elastic" => Easing::EaseOutElastic,
+            "ease_in_out_elastic" => Easing::EaseInOutElastic,
+            "ease_in_bounce" => Easing::EaseInBounce,
+            "ease_out_bounce" => Easing::EaseOutBounce,
+            "ease_in_out_bounce" => Easing::EaseInOutBounce,
+            "spring" => Easing::Spring(Spring::default()),
+            _ => return None,
+        };
+        Some(easing)
+    }
+
+    // Eased progress `t` through a segment lasting `span` seconds. Springs run
+    // in real time from the segment start, so their motion doesn't stretch
+    // with the segment; every other easing only sees `t`.
+    pub fn apply_over(self, t: f32, span: f32) -> f32 {
+        match self {
+            Easing::Spring(spring) => {
+                let t = t.clamp(0.0, 1.0);
+                // Lands exactly on the next keyframe even if still moving.
+                if t == 1.0 { 1.0 } else { spring.value(t * span) }
+            }
+            _ => self.apply(t),
+        }
+    }
+
+    // Springs are taken to run over one second here; see `apply_over`.
     pub fn apply(self, t: f32) -> f32 {
         let t = t.clamp(0.0, 1.0);
         match self {
             Easing::Linear => t,
-            Easing::EaseInOutQuad => {
+            Easing::Hold => {
+                if t < 1.0 { 0.0 } else { 1.0 }
+            }
+            Easing::EaseInSine => 1.0 - (t * PI / 2.0).cos(),
+            Easing::EaseOutSine => (t * PI / 2.0).sin(),
+            Easing::EaseInOutSine => -((PI * t).cos() - 1.0) / 2.0,
+            Easing::EaseInQuad => t.powi(2),
+            Easing::EaseOutQuad => 1.0 - (1.0 - t).powi(2),
+            Easing::EaseInOutQuad => in_out_pow(t, 2),
+            Easing::EaseInCubic => t.powi(3),
+            Easing::EaseOutCubic => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOutCubic => in_out_pow(t, 3),
+            Easing::EaseInQuart => t.powi(4),
+            Easing::EaseOutQuart => 1.0 - (1.0 - t).powi(4),
+            Easing::EaseInOutQuart => in_out_pow(t, 4),
+            Easing::EaseInQuint => t.powi(5),
+            Easing::EaseOutQuint => 1.0 - (1.0 - t).powi(5),
+            Easing::EaseInOutQuint => in_out_pow(t, 5),
+            Easing::EaseInExpo => {
+                if t == 0.0 {
+                    0.0
+                } else {
+                    2f32.powf(10.0 * t - 10.0)
+                }
+            }
+            Easing::EaseOutExpo => {
+                if t == 1.0 {
+                    1.0
+                } else {
+                    1.0 - 2f32.powf(-10.0 * t)
+                }
+            }
+            Easing::EaseInOutExpo => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else if t < 0.5 {
+                    2f32.powf(20.0 * t - 10.0) / 2.0
+                } else {
+                    (2.0 - 2f32.powf(-20.0 * t + 10.0)) / 2.0
+                }
+            }
+            Easing::EaseInCirc => 1.0 - (1.0 - t.powi(2)).sqrt(),
+            Easing::EaseOutCirc => (1.0 - (t - 1.0).powi(2)).sqrt(),
+            Easing::EaseInOutCirc => {
                 if t < 0.5 {
-                    2.0 * t * t
+                    (1.0 - (1.0 - (2.0 * t).powi(2)).sqrt()) / 2.0
                 } else {
-                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                    ((1.0 - (-2.0 * t + 2.0).powi(2)).sqrt() + 1.0) / 2.0
                 }
             }
-            Easing::EaseOutCubic => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInBack => BACK_C3 * t.powi(3) - BACK_C1 * t.powi(2),
+            Easing::EaseOutBack => 1.0 + BACK_C3 * (t - 1.0).powi(3) + BACK_C1 * (t - 1.0).powi(2),
+            Easing::EaseInOutBack => {
+                let c2 = BACK_C1 * 1.525;
+                if t < 0.5 {
+                    (2.0 * t).powi(2) * ((c2 + 1.0) * 2.0 * t - c2) / 2.0
+                } else {
+                    ((2.0 * t - 2.0).powi(2) * ((c2 + 1.0) * (2.0 * t - 2.0) + c2) + 2.0) / 2.0
+                }
+            }
+            Easing::EaseInElastic => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    -(2f32.powf(10.0 * t - 10.0) * ((10.0 * t - 10.75) * ELASTIC_C4).sin())
+                }
+            }
+            Easing::EaseOutElastic => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    2f32.powf(-10.0 * t) * ((10.0 * t - 0.75) * ELASTIC_C4).sin() + 1.0
+                }
+            }
+            Easing::EaseInOutElastic => {
+                let wave = ((20.0 * t - 11.125) * ELASTIC_C5).sin();
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else if t < 0.5 {
+                    -(2f32.powf(20.0 * t - 10.0) * wave) / 2.0
+                } else {
+                    2f32.powf(-20.0 * t + 10.0) * wave / 2.0 + 1.0
+                }
+            }
+            Easing::EaseInBounce => 1.0 - bounce_out(1.0 - t),
+            Easing::EaseOutBounce => bounce_out(t),
+            Easing::EaseInOutBounce => {
+                if t < 0.5 {
+                    (1.0 - bounce_out(1.0 - 2.0 * t)) / 2.0
+                } else {
+                    (1.0 + bounce_out(2.0 * t - 1.0)) / 2.0
+                }
+            }
+            Easing::Spring(_) => self.apply_over(t, 1.0),
+        }
+    }
+}
+
+// A damped spring pulling from 0 towards 1, starting at rest. It is solved in
+// closed form rather than stepped, so any time can be sampled on its own and
+// every render gives the same values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spring {
+    pub stiffness: f32,
+    pub damping: f32,
+    pub mass: f32,
+}
+
+impl Default for Spring {
+    // Overshoots by about 2% and settles in about 0.7 seconds.
+    fn default() -> Self {
+        Self {
+            stiffness: 170.0,
+            damping: 20.0,
+            mass: 1.0,
+        }
+    }
+}
+
+impl Spring {
+    pub fn new(stiffness: f32, damping: f32, mass: f32) -> Result<Self> {
+        if !(stiffness > 0.0 && mass > 0.0 && damping >= 0.0) {
+            bail!(
+                ErrorKind::InvalidProject
+                    .error("spring needs stiffness > 0, mass > 0 and damping >= 0")
+            );
         }
+        Ok(Self {
+            stiffness,
+            damping,
+            mass,
+        })
+    }
+
+    // Damping ratio: below 1 the spring overshoots and oscillates, at 1 it
+    // arrives as fast as possible without overshooting, above 1 it creeps in.
+    pub fn damping_ratio(&self) -> f32 {
+        self.damping / (2.0 * (self.stiffness * self.mass).sqrt())
+    }
+
+    // Position `seconds` after release.
+    pub fn value(&self, seconds: f32) -> f32 {
+        let t = seconds.max(0.0);
+        let omega = (self.stiffness / self.mass).sqrt();
+        let zeta = self.damping_ratio();
+        // Offset from the target, starting at -1 with no velocity.
+        let offset = if (zeta - 1.0).abs() < 1e-4 {
+            -(1.0 + omega * t) * (-omega * t).exp()
+        } else if zeta < 1.0 {
+            let damped = omega * (1.0 - zeta * zeta).sqrt();
+            let decay = (-zeta * omega * t).exp();
+            -decay * ((damped * t).cos() + zeta * omega / damped * (damped * t).sin())
+        } else {
+            let root = omega * (zeta * zeta - 1.0).sqrt();
+            let (fast, slow) = (-zeta * omega - root, -zeta * omega + root);
+            let fast_part = slow / (fast - slow);
+            fast_part * (fast * t).exp() - (1.0 + fast_part) * (slow * t).exp()
+        };
+        1.0 + offset
+    }
+
+    // Roughly when the motion stays within 0.1% of the target; keyframe
+    // segments shorter than this end with a jump.
+    pub fn settle_time(&self) -> f32 {
+        let omega = (self.stiffness / self.mass).sqrt();
+        let zeta = self.damping_ratio();
+        let rate = if zeta < 1.0 {
+            zeta * omega
+        } else {
+            omega * (zeta - (zeta * zeta - 1.0).sqrt())
+        };
+        if rate > 0.0 { 1000f32.ln() / rate } else { f32::INFINITY }
+    }
+}
+
+const BACK_C1: f32 = 1.70158;
+const BACK_C3: f32 = BACK_C1 + 1.0;
+const ELASTIC_C4: f32 = 2.0 * PI / 3.0;
+const ELASTIC_C5: f32 = 2.0 * PI / 4.5;
+
+fn in_out_pow(t: f32, power: i32) -> f32 {
+    if t < 0.5 {
+        2f32.powi(power - 1) * t.powi(power)
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(power) / 2.0
+    }
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N1: f32 = 7.5625;
+    const D1: f32 = 2.75;
+    if t < 1.0 / D1 {
+        N1 * t * t
+    } else if t < 2.0 / D1 {
+        let t = t - 1.5 / D1;
+        N1 * t * t + 0.75
+    } else if t < 2.5 / D1 {
+        let t = t - 2.25 / D1;
+        N1 * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D1;
+        N1 * t * t + 0.984375
     }
 }
 
@@ -45,12 +333,27 @@ impl<T> Keyframe<T> {
 
 pub trait Lerp: Sized + Copy {
     fn lerp(a: Self, b: Self, t: f32) -> Self;
+
+    // `self + amplitude * wave(axis)` per axis (x = 0, y = 1), for procedural
+    // motion that moves each axis separately.
+    fn add_wave(self, amplitude: Self, wave: impl Fn(u32) -> f32) -> Self;
+
+    // `op` applied axis by axis to `self` and `other`.
+    fn zip_with(self, other: Self, op: impl Fn(f32, f32) -> f32) -> Self;
 }
 
 impl Lerp for f32 {
     fn lerp(a: Self, b: Self, t: f32) -> Self {
         a + (b - a) * t
     }
+
+    fn add_wave(self, amplitude: Self, wave: impl Fn(u32) -> f32) -> Self {
+        self + amplitude * wave(0)
+    }
+
+    fn zip_with(self, other: Self, op: impl Fn(f32, f32) -> f32) -> Self {
+        op(self, other)
+    }
 }
 
 impl Lerp for Vec2 {
@@ -60,35 +363,306 @@ impl Lerp for Vec2 {
             y: a.y + (b.y - a.y) * t,
         }
     }
+
+    fn add_wave(self, amplitude: Self, wave: impl Fn(u32) -> f32) -> Self {
+        Vec2 {
+            x: self.x + amplitude.x * wave(0),
+            y: self.y + amplitude.y * wave(1),
+        }
+    }
+
+    fn zip_with(self, other: Self, op: impl Fn(f32, f32) -> f32) -> Self {
+        Vec2 {
+            x: op(self.x, other.x),
+            y: op(self.y, other.y),
+        }
+    }
+}
+
+// The path a track takes through its keyframe values; each keyframe's
+// easing still sets the timing along it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    // Straight segments that change direction at each keyframe.
+    Linear,
+    // A smooth curve through every keyframe.
+    CatmullRom,
+    // Kochanek-Bartels, each in -1..1: tension tightens (1) or loosens (-1)
+    // the curve, continuity adds corners at the keyframes, and bias leans
+    // the curve towards overshooting (1) or undershooting (-1) each keyframe.
+    // All zero is Catmull-Rom.
+    Tcb {
+        tension: f32,
+        continuity: f32,
+        bias: f32,
+    },
+}
+
+// What a track does before its first keyframe (pre) or after its last
+// (post).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extrapolation {
+    // Keeps the first or last value.
+    Hold,
+    // Repeats the keyframes end to end.
+    Loop,
+    // Plays the keyframes forwards, then backwards.
+    PingPong,
+    // Carries on in a straight line at the speed the track has at that end.
+    Continue,
+}
+
+impl Extrapolation {
+    // Project-file name, e.g. `"ping_pong"`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let extrapolation = match name {
+            "hold" => Extrapolation::Hold,
+            "loop" => Extrapolation::Loop,
+            "ping_pong" => Extrapolation::PingPong,
+            "continue" => Extrapolation::Continue,
+            _ => return None,
+        };
+        Some(extrapolation)
+    }
+}
+
+// Another track combined into a composite one (`base + shake`,
+// `base * pulse`), applied to the value in order. Vec2 tracks combine axis by
+// axis.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackOp<T> {
+    Add(Track<T>),
+    Multiply(Track<T>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Track<T> {
     keyframes: Vec<Keyframe<T>>,
+    interpolation: Interpolation,
+    pre: Extrapolation,
+    post: Extrapolation,
+    // Summed onto the keyframed value, in order.
+    added: Vec<Procedural<T>>,
+    // Keyframes and `added` are sampled at `t * time_scale + time_shift`.
+    time_scale: f32,
+    time_shift: f32,
+    combined: Vec<TrackOp<T>>,
 }
 
 impl<T: Lerp> Track<T> {
     pub fn new(keyframes: Vec<Keyframe<T>>) -> Result<Self> {
         if keyframes.is_empty() {
-            bail!("track must have at least one keyframe");
+            bail!(ErrorKind::InvalidProject.error("track must have at least one keyframe"));
         }
 
         for i in 1..keyframes.len() {
             if keyframes[i].time <= keyframes[i - 1].time {
-                bail!("keyframe times must be strictly increasing");
+                bail!(
+                    ErrorKind::InvalidProject.error("keyframe times must be strictly increasing")
+                );
             }
         }
 
-        Ok(Self { keyframes })
+        Ok(Self {
+            keyframes,
+            interpolation: Interpolation::Linear,
+            pre: Extrapolation::Hold,
+            post: Extrapolation::Hold,
+            added: Vec::new(),
+            time_scale: 1.0,
+            time_shift: 0.0,
+            combined: Vec::new(),
+        })
     }
 
     pub fn from_constant(value: T) -> Self {
         Self {
             keyframes: vec![Keyframe::new(0.0, value, Easing::Linear)],
+            interpolation: Interpolation::Linear,
+            pre: Extrapolation::Hold,
+            post: Extrapolation::Hold,
+            added: Vec::new(),
+            time_scale: 1.0,
+            time_shift: 0.0,
+            combined: Vec::new(),
         }
     }
 
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    pub fn with_extrapolation(mut self, pre: Extrapolation, post: Extrapolation) -> Self {
+        self.pre = pre;
+        self.post = post;
+        self
+    }
+
+    // (pre, post).
+    pub fn extrapolation(&self) -> (Extrapolation, Extrapolation) {
+        (self.pre, self.post)
+    }
+
+    // Adds procedural motion (`SineTrack`, `NoiseTrack`) on top of the
+    // keyframes, e.g. shake over a keyframed camera move.
+    pub fn with_added(mut self, procedural: impl Into<Procedural<T>>) -> Self {
+        self.added.push(procedural.into());
+        self
+    }
+
+    pub fn added(&self) -> &[Procedural<T>] {
+        &self.added
+    }
+
+    pub fn combined(&self) -> &[TrackOp<T>] {
+        &self.combined
+    }
+
+    // Delays everything combined so far by `seconds` (negative starts it
+    // early). Tracks combined afterwards keep their own timing.
+    pub fn with_time_offset(mut self, seconds: f32) -> Self {
+        self.time_shift -= seconds * self.time_scale;
+        self.combined = self
+            .combined
+            .into_iter()
+            .map(|op| op.map_track(|track| track.with_time_offset(seconds)))
+            .collect();
+        self
+    }
+
+    // Plays everything combined so far `scale` times as fast; 0 freezes it
+    // at time 0 and a negative scale plays it backwards from there.
+    pub fn with_time_scale(mut self, scale: f32) -> Self {
+        self.time_scale *= scale;
+        self.combined = self
+            .combined
+            .into_iter()
+            .map(|op| op.map_track(|track| track.with_time_scale(scale)))
+            .collect();
+        self
+    }
+
+    // When the first keyframe of this track or any track combined into it
+    // falls, after retiming; for a single track, pre-extrapolation applies
+    // before this.
+    pub fn start_time(&self) -> f32 {
+        self.combined
+            .iter()
+            .fold(self.own_span().0, |start, op| start.min(op.track().start_time()))
+    }
+
+    // Likewise for the last keyframe.
+    pub fn end_time(&self) -> f32 {
+        self.combined
+            .iter()
+            .fold(self.own_span().1, |end, op| end.max(op.track().end_time()))
+    }
+
     pub fn sample(&self, t: f32) -> T {
+        let local = t * self.time_scale + self.time_shift;
+        let value = self.sample_keyframes(local);
+        let value = self
+            .added
+            .iter()
+            .fold(value, |value, procedural| procedural.add_to(value, local));
+        self.combined.iter().fold(value, |value, op| match op {
+            TrackOp::Add(track) => value.zip_with(track.sample(t), |a, b| a + b),
+            TrackOp::Multiply(track) => value.zip_with(track.sample(t), |a, b| a * b),
+        })
+    }
+
+    // Samples the whole track, combined tracks and procedural motion included,
+    // once per frame from `start_time` to `end_time`, as a linear keyframe
+    // track with the same pre/post extrapolation. Sampling it then costs a
+    // lookup and a lerp however expensive the original was; it matches the
+    // original exactly at `start_time + n / fps`, which lines up with the
+    // render's frames when the clip starts on a frame.
+    pub fn bake(&self, fps: u32) -> Result<Track<T>> {
+        if fps == 0 {
+            bail!(ErrorKind::InvalidProject.error("bake fps must be > 0"));
+        }
+        let (start, end) = (self.start_time(), self.end_time());
+        if end <= start {
+            return Ok(self.clone());
+        }
+        let frames = ((end - start) * fps as f32).ceil() as usize;
+        let mut keyframes: Vec<Keyframe<T>> = (0..=frames)
+            .map(|frame| {
+                let time = (start + frame as f32 / fps as f32).min(end);
+                Keyframe::new(time, self.sample(time), Easing::Linear)
+            })
+            .collect();
+        // Rounding can land the second-to-last frame on `end` as well.
+        keyframes.dedup_by(|later, earlier| later.time <= earlier.time);
+        Ok(Track::new(keyframes)?.with_extrapolation(self.pre, self.post))
+    }
+
+    // This track's own keyframe range in outer time. A frozen track counts
+    // as a constant, which spans 0..0.
+    fn own_span(&self) -> (f32, f32) {
+        if self.time_scale == 0.0 {
+            return (0.0, 0.0);
+        }
+        let (first, last) = self.keyframe_span();
+        let outer = |local: f32| (local - self.time_shift) / self.time_scale;
+        let (a, b) = (outer(first), outer(last));
+        (a.min(b), a.max(b))
+    }
+
+    fn keyframe_span(&self) -> (f32, f32) {
+        (self.keyframes[0].time, self.keyframes[self.keyframes.len() - 1].time)
+    }
+
+    fn sample_keyframes(&self, t: f32) -> T {
+        let (start, end) = self.keyframe_span();
+        let length = end - start;
+        let mode = if t < start {
+            self.pre
+        } else if t > end {
+            self.post
+        } else {
+            Extrapolation::Hold
+        };
+        match mode {
+            // A single keyframe has nothing to repeat or carry on from.
+            _ if length <= 0.0 => self.sample_within(t),
+            Extrapolation::Hold => self.sample_within(t),
+            Extrapolation::Loop => self.sample_within(start + (t - start).rem_euclid(length)),
+            Extrapolation::PingPong => {
+                let cycle = (t - start).rem_euclid(2.0 * length);
+                let offset = if cycle <= length { cycle } else { 2.0 * length - cycle };
+                self.sample_within(start + offset)
+            }
+            Extrapolation::Continue => {
+                let count = self.keyframes.len();
+                let (edge, segment) = if t < start {
+                    (start, &self.keyframes[..2])
+                } else {
+                    (end, &self.keyframes[count - 2..])
+                };
+                // A held segment jumps at its end rather than moving.
+                if segment[0].easing_to_next == Easing::Hold {
+                    return self.sample_within(t);
+                }
+                // Speed measured over the last 1% of the end segment.
+                let step = (segment[1].time - segment[0].time) * 0.01;
+                let inward = if t < start { edge + step } else { edge - step };
+                T::lerp(
+                    self.sample_within(edge),
+                    self.sample_within(inward),
+                    (t - edge) / (inward - edge),
+                )
+            }
+        }
+    }
+
+    // Samples inside the keyframe range, holding the end values outside it.
+    fn sample_within(&self, t: f32) -> T {
         let first = &self.keyframes[0];
         let last = &self.keyframes[self.keyframes.len() - 1];
 
@@ -99,19 +673,122 @@ impl<T: Lerp> Track<T> {
             return last.value;
         }
 
-        let mut idx = 0;
-        for i in 0..self.keyframes.len() - 1 {
-            if t >= self.keyframes[i].time && t < self.keyframes[i + 1].time {
-                idx = i;
-                break;
-            }
-        }
-
+        // The segment starting at or before `t`; `t` is strictly inside.
+        let idx = self.keyframes.partition_point(|key| key.time <= t) - 1;
         let k0 = &self.keyframes[idx];
         let k1 = &self.keyframes[idx + 1];
         let span = k1.time - k0.time;
         let u = if span > 0.0 { (t - k0.time) / span } else { 0.0 };
-        let eased = k0.easing_to_next.apply(u);
-        T::lerp(k0.value, k1.value, eased)
+        let eased = k0.easing_to_next.apply_over(u, span);
+        let (tension, continuity, bias) = match self.interpolation {
+            Interpolation::Linear => return T::lerp(k0.value, k1.value, eased),
+            Interpolation::CatmullRom => (0.0, 0.0, 0.0),
+            Interpolation::Tcb {
+                tension,
+                continuity,
+                bias,
+            } => (tension, continuity, bias),
+        };
+
+        // The segment as a cubic Bezier. Tangents come from the neighbouring
+        // keyframes (mirrored past either end) and are scaled for uneven
+        // keyframe spacing, so speed stays continuous across each keyframe.
+        let (p1, p2) = (k0.value, k1.value);
+        let (p0, before) = match idx.checked_sub(1).map(|i| &self.keyframes[i]) {
+            Some(prev) => (prev.value, k0.time - prev.time),
+            None => (T::lerp(p2, p1, 2.0), span),
+        };
+        let (p3, after) = match self.keyframes.get(idx + 2) {
+            Some(next) => (next.value, next.time - k1.time),
+            None => (T::lerp(p1, p2, 2.0), span),
+        };
+        let weight = |bias: f32, continuity: f32, scale: f32| {
+            (1.0 - tension) * (1.0 + bias) * (1.0 + continuity) / 2.0 * scale / 3.0
+        };
+        let out_scale = 2.0 * span / (before + span);
+        let in_scale = 2.0 * span / (span + after);
+        // c1 = p1 + (p1 - p0) * a + (p2 - p1) * b, with lerps alone so any
+        // `Lerp` type works; likewise c2 = p2 - (p2 - p1) * c - (p3 - p2) * d.
+        let c1 = offset(
+            p1,
+            T::lerp(p0, p1, 1.0 + weight(bias, continuity, out_scale)),
+            T::lerp(p1, p2, weight(-bias, -continuity, out_scale)),
+        );
+        let c2 = offset(
+            p2,
+            T::lerp(p2, p1, weight(bias, -continuity, in_scale)),
+            T::lerp(p2, p3, -weight(-bias, continuity, in_scale)),
+        );
+
+        let a = T::lerp(p1, c1, eased);
+        let b = T::lerp(c1, c2, eased);
+        let c = T::lerp(c2, p2, eased);
+        T::lerp(T::lerp(a, b, eased), T::lerp(b, c, eased), eased)
+    }
+}
+
+impl Track<f32> {
+    // Treats the keyframes as angles in degrees that turn the short way
+    // round: 350 -> 10 turns 20 degrees forwards rather than 340 back. Whole
+    // turns in a step are kept, so 0 -> 720 still spins twice and 0 -> 370
+    // spins once and a bit. Keyframe values are rewritten, so values after
+    // the first may end up outside 0..360.
+    pub fn with_shortest_arc(mut self) -> Self {
+        for i in 1..self.keyframes.len() {
+            let previous = self.keyframes[i - 1].value;
+            let step = self.keyframes[i].value - previous;
+            let turns = (step / 360.0).trunc() * 360.0;
+            let rest = step - turns;
+            let rest = if rest > 180.0 {
+                rest - 360.0
+            } else if rest < -180.0 {
+                rest + 360.0
+            } else {
+                rest
+            };
+            self.keyframes[i].value = previous + turns + rest;
+        }
+        self
     }
 }
+
+impl<T> TrackOp<T> {
+    pub fn track(&self) -> &Track<T> {
+        match self {
+            TrackOp::Add(track) | TrackOp::Multiply(track) => track,
+        }
+    }
+
+    fn map_track(self, f: impl FnOnce(Track<T>) -> Track<T>) -> Self {
+        match self {
+            TrackOp::Add(track) => TrackOp::Add(f(track)),
+            TrackOp::Multiply(track) => TrackOp::Multiply(f(track)),
+        }
+    }
+}
+
+// `base + shake`: the two values summed at every time.
+impl<T: Lerp> Add for Track<T> {
+    type Output = Track<T>;
+
+    fn add(mut self, other: Track<T>) -> Track<T> {
+        self.combined.push(TrackOp::Add(other));
+        self
+    }
+}
+
+// `base * pulse`: the two values multiplied at every time, e.g. a scale
+// track times a breathing factor.
+impl<T: Lerp> Mul for Track<T> {
+    type Output = Track<T>;
+
+    fn mul(mut self, other: Track<T>) -> Track<T> {
+        self.combined.push(TrackOp::Multiply(other));
+        self
+    }
+}
+
+// `base + (a - base) + (b - base)`.
+fn offset<T: Lerp>(base: T, a: T, b: T) -> T {
+    T::lerp(base, T::lerp(a, b, 0.5), 2.0)
+}
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::scene::Color;
+use crate::timeline::{SampledScene, Timeline, Variables};
+
+// A timeline embedded as an object. It is rendered offscreen at its own size
+// and composited like an image with the outer clip's transform. The timeline
+// is shared, so the copy each sampled frame takes of its clip's object
+// doesn't copy the whole nested timeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Composition {
+    pub timeline: Arc<Timeline>,
+    pub width: u32,
+    pub height: u32,
+    pub bg: Color,
+}
+
+impl Composition {
+    pub fn new(timeline: Timeline, width: u32, height: u32) -> Self {
+        Self {
+            timeline: Arc::new(timeline),
+            width,
+            height,
+            bg: Color::rgba(0, 0, 0, 0),
+        }
+    }
+
+    pub fn with_bg(mut self, bg: Color) -> Self {
+        self.bg = bg;
+        self
+    }
+
+    // Outer local time maps 1:1 onto the inner timeline, holding its last
+    // frame if the clip outlasts it.
+    pub fn sample(&self, local_t: f32) -> Result<SampledScene> {
+        self.sample_with(local_t, &Variables::new())
+    }
+
+    // Inner bindings see the same variables as the outer timeline.
+    pub fn sample_with(&self, local_t: f32, vars: &Variables) -> Result<SampledScene> {
+        self.timeline
+            .sample_with(local_t.clamp(0.0, self.timeline.duration), vars)
+    }
+}
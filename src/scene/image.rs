@@ -3,10 +3,48 @@ use std::path::PathBuf;
 #[derive(Debug, Clone, PartialEq)]
 pub struct ImageObject {
     pub path: PathBuf,
+    pub load: ImageLoadOptions,
 }
 
 impl ImageObject {
     pub fn new(path: impl Into<PathBuf>) -> Self {
-        Self { path: path.into() }
+        Self {
+            path: path.into(),
+            load: ImageLoadOptions::default(),
+        }
+    }
+
+    pub fn with_load_options(mut self, load: ImageLoadOptions) -> Self {
+        self.load = load;
+        self
+    }
+}
+
+// How an image file is prepared before it becomes a texture. The same file
+// loaded with different options is cached as separate textures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageLoadOptions {
+    // Longest side kept in VRAM, e.g. 2048 for camera photos. Larger images
+    // are downscaled on load but still drawn at their full size.
+    pub max_dimension: Option<u32>,
+    // Turns JPEGs upright as their EXIF orientation tag says.
+    pub orientation: bool,
+    // Stores colour multiplied by alpha, which keeps dark fringes off
+    // transparent edges when the texture is filtered. On by default; `false`
+    // uploads the file's straight alpha unchanged.
+    pub premultiply: bool,
+    // Builds mipmaps with trilinear filtering, for images drawn much smaller
+    // than their size.
+    pub mipmaps: bool,
+}
+
+impl Default for ImageLoadOptions {
+    fn default() -> Self {
+        Self {
+            max_dimension: None,
+            orientation: true,
+            premultiply: true,
+            mipmaps: false,
+        }
     }
 }
@@ -1,13 +1,29 @@
+pub mod composition;
 pub mod image;
 pub mod object;
+pub mod palette;
+pub mod path;
+pub mod plugin;
+pub mod post;
+pub mod procedural;
+pub mod shader;
 pub mod shape;
 pub mod transform;
 pub mod animation;
 pub mod text;
 
-pub use image::ImageObject;
+pub use composition::Composition;
+pub use image::{ImageLoadOptions, ImageObject};
 pub use object::Object;
+pub use palette::{readable_text_on, Harmony, Hsl, Palette};
+pub use path::{BezierPath, PathMotion};
+pub use plugin::{ParamValue, Params, PluginEffect, PluginObject};
+pub use post::PostEffect;
+pub use procedural::{NoiseTrack, Procedural, SineTrack};
+pub use shader::{ObjectShader, SampledShader, UniformTrack, UniformValue};
 pub use shape::Shape;
 pub use transform::{AnimatedTransform, Color, Transform, Vec2};
-pub use animation::{Easing, Keyframe, Track};
-pub use text::{FontFamily, FontSource, StyleFlags, StyledText, TextObject, TextRun};
+pub use animation::{Easing, Extrapolation, Interpolation, Keyframe, Spring, Track, TrackOp};
+pub use text::{
+    FontFamily, FontSource, StyleFlags, StyledText, TextBackground, TextObject, TextRun, TextStroke,
+};
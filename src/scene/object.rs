@@ -1,8 +1,10 @@
-use crate::scene::{ImageObject, Shape, TextObject};
+use crate::scene::{Composition, ImageObject, PluginObject, Shape, TextObject};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Object {
     Shape(Shape),
     Image(ImageObject),
     Text(TextObject),
+    Composition(Box<Composition>),
+    Plugin(PluginObject),
 }
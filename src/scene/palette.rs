@@ -0,0 +1,154 @@
+use crate::scene::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsl {
+    // Degrees in 0.0..360.0.
+    pub h: f32,
+    pub s: f32,
+    pub l: f32,
+}
+
+impl Color {
+    pub fn to_hsl(self) -> Hsl {
+        let r = self.r as f32 / 255.0;
+        let g = self.g as f32 / 255.0;
+        let b = self.b as f32 / 255.0;
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let l = (max + min) / 2.0;
+        let delta = max - min;
+        if delta == 0.0 {
+            return Hsl { h: 0.0, s: 0.0, l };
+        }
+
+        let s = delta / (1.0 - (2.0 * l - 1.0).abs());
+        let h = if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        Hsl { h, s, l }
+    }
+
+    pub fn from_hsl(hsl: Hsl, a: u8) -> Self {
+        let h = hsl.h.rem_euclid(360.0);
+        let s = hsl.s.clamp(0.0, 1.0);
+        let l = hsl.l.clamp(0.0, 1.0);
+        let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        let x = c * (1.0 - ((h / 60.0).rem_euclid(2.0) - 1.0).abs());
+        let m = l - c / 2.0;
+        let (r, g, b) = match (h / 60.0) as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let channel = |v: f32| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+        Color::rgba(channel(r), channel(g), channel(b), a)
+    }
+
+    pub fn rotate_hue(self, degrees: f32) -> Self {
+        let mut hsl = self.to_hsl();
+        hsl.h += degrees;
+        Color::from_hsl(hsl, self.a)
+    }
+
+    pub fn with_lightness(self, l: f32) -> Self {
+        let mut hsl = self.to_hsl();
+        hsl.l = l;
+        Color::from_hsl(hsl, self.a)
+    }
+
+    // WCAG relative luminance (sRGB, alpha ignored).
+    pub fn luminance(self) -> f32 {
+        let linear = |c: u8| {
+            let c = c as f32 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * linear(self.r) + 0.7152 * linear(self.g) + 0.0722 * linear(self.b)
+    }
+
+    // WCAG contrast ratio, 1.0..=21.0.
+    pub fn contrast_ratio(self, other: Color) -> f32 {
+        let a = self.luminance();
+        let b = other.luminance();
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Harmony {
+    Complementary,
+    // `count` hues spread `spread` degrees apart, centered on the seed.
+    Analogous { count: usize, spread: f32 },
+    Triadic,
+    SplitComplementary,
+    Tetradic,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    // The seed comes first (or in the middle, for analogous palettes).
+    pub colors: Vec<Color>,
+}
+
+impl Palette {
+    pub fn from_seed(seed: Color, harmony: Harmony) -> Self {
+        let offsets: Vec<f32> = match harmony {
+            Harmony::Complementary => vec![0.0, 180.0],
+            Harmony::Analogous { count, spread } => {
+                let center = count.saturating_sub(1) as f32 / 2.0;
+                (0..count).map(|i| (i as f32 - center) * spread).collect()
+            }
+            Harmony::Triadic => vec![0.0, 120.0, 240.0],
+            Harmony::SplitComplementary => vec![0.0, 150.0, 210.0],
+            Harmony::Tetradic => vec![0.0, 90.0, 180.0, 270.0],
+        };
+        Self {
+            colors: offsets
+                .into_iter()
+                .map(|offset| seed.rotate_hue(offset))
+                .collect(),
+        }
+    }
+
+    // Lighter and darker variants of `seed` for backgrounds, borders, etc.
+    pub fn shades(seed: Color, count: usize) -> Self {
+        let step = 1.0 / (count + 1) as f32;
+        Self {
+            colors: (1..=count)
+                .map(|i| seed.with_lightness(step * i as f32))
+                .collect(),
+        }
+    }
+
+    // The palette color with the best contrast against `background`, falling
+    // back to black or white if none reaches `min_contrast`.
+    pub fn readable_on(&self, background: Color, min_contrast: f32) -> Color {
+        let best = self.colors.iter().copied().max_by(|a, b| {
+            a.contrast_ratio(background)
+                .total_cmp(&b.contrast_ratio(background))
+        });
+        match best {
+            Some(color) if color.contrast_ratio(background) >= min_contrast => color,
+            _ => readable_text_on(background),
+        }
+    }
+}
+
+// Black or white, whichever contrasts more with `background`.
+pub fn readable_text_on(background: Color) -> Color {
+    if Color::BLACK.contrast_ratio(background) >= Color::WHITE.contrast_ratio(background) {
+        Color::BLACK
+    } else {
+        Color::WHITE
+    }
+}
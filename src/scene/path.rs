@@ -0,0 +1,183 @@
+use anyhow::{bail, Result};
+
+use crate::error::ErrorKind;
+use crate::scene::animation::{Lerp, Track};
+use crate::scene::Vec2;
+
+// Points measured along each curve to map distance to curve position.
+const SAMPLES_PER_CURVE: usize = 32;
+
+// Connected cubic Bezier curves in graph coords, walked at constant speed:
+// progress 0.5 is halfway along the path's length, however the curves are
+// split up or their control points spaced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BezierPath {
+    pub start: Vec2,
+    curves: Vec<[Vec2; 3]>,
+    // Distance from the start to each sample, `SAMPLES_PER_CURVE + 1` per
+    // curve.
+    lengths: Vec<f32>,
+}
+
+impl BezierPath {
+    pub fn new(start: Vec2) -> Self {
+        Self {
+            start,
+            curves: Vec::new(),
+            lengths: Vec::new(),
+        }
+    }
+
+    // A curve from the current end to `to`, pulled towards `control1` as it
+    // leaves and `control2` as it arrives.
+    pub fn with_curve(mut self, control1: Vec2, control2: Vec2, to: Vec2) -> Self {
+        let from = self.end();
+        let mut length = self.length();
+        let mut previous = from;
+        self.lengths.push(length);
+        for step in 1..=SAMPLES_PER_CURVE {
+            let u = step as f32 / SAMPLES_PER_CURVE as f32;
+            let point = cubic(from, [control1, control2, to], u);
+            length += distance(previous, point);
+            self.lengths.push(length);
+            previous = point;
+        }
+        self.curves.push([control1, control2, to]);
+        self
+    }
+
+    // A straight line from the current end to `to`.
+    pub fn with_line(self, to: Vec2) -> Self {
+        let from = self.end();
+        let control1 = Vec2::lerp(from, to, 1.0 / 3.0);
+        let control2 = Vec2::lerp(from, to, 2.0 / 3.0);
+        self.with_curve(control1, control2, to)
+    }
+
+    pub fn curves(&self) -> &[[Vec2; 3]] {
+        &self.curves
+    }
+
+    pub fn end(&self) -> Vec2 {
+        self.curves.last().map_or(self.start, |curve| curve[2])
+    }
+
+    pub fn length(&self) -> f32 {
+        self.lengths.last().copied().unwrap_or(0.0)
+    }
+
+    // The point `progress` of the way along (clamped to 0..1), and the
+    // direction of travel there in degrees, clockwise on screen from +x like
+    // `Transform::rotation`.
+    pub fn sample(&self, progress: f32) -> (Vec2, f32) {
+        let Some((index, u)) = self.locate(progress) else {
+            return (self.start, 0.0);
+        };
+        let from = if index == 0 {
+            self.start
+        } else {
+            self.curves[index - 1][2]
+        };
+        let curve = self.curves[index];
+        let point = cubic(from, curve, u);
+        let mut tangent = cubic_tangent(from, curve, u);
+        // Control points on top of an end point leave no direction there;
+        // look at where the curve goes next instead.
+        if tangent.x.abs() < 1e-6 && tangent.y.abs() < 1e-6 {
+            let (a, b) = if u < 0.5 {
+                (point, cubic(from, curve, u + 0.01))
+            } else {
+                (cubic(from, curve, u - 0.01), point)
+            };
+            tangent = Vec2 {
+                x: b.x - a.x,
+                y: b.y - a.y,
+            };
+        }
+        // Graph y points up, so the on-screen clockwise angle is negated.
+        (point, -tangent.y.atan2(tangent.x).to_degrees())
+    }
+
+    // The curve at `progress` of the path's length and the parameter within
+    // it, or `None` for a path with no curves.
+    fn locate(&self, progress: f32) -> Option<(usize, f32)> {
+        if self.curves.is_empty() {
+            return None;
+        }
+        let target = progress.clamp(0.0, 1.0) * self.length();
+        // The first sample past `target`, or the last one at the very end.
+        // Never the first of a curve, which repeats the previous curve's end.
+        let next = self
+            .lengths
+            .partition_point(|length| *length <= target)
+            .clamp(1, self.lengths.len() - 1);
+        let per_curve = SAMPLES_PER_CURVE + 1;
+        let (index, step) = (next / per_curve, next % per_curve);
+        let (before, after) = (self.lengths[next - 1], self.lengths[next]);
+        let within = if after > before {
+            (target - before) / (after - before)
+        } else {
+            1.0
+        };
+        let u = (step as f32 - 1.0 + within.clamp(0.0, 1.0)) / SAMPLES_PER_CURVE as f32;
+        Some((index, u))
+    }
+}
+
+// Position driven along a `BezierPath`, e.g. a car on a winding road,
+// instead of dozens of position keyframes. `progress` runs 0..1 along the
+// path, and its keyframes' easings set the pace. With `orient`, the path's
+// direction is added to the rotation, so an object drawn facing +x turns to
+// face where it's going.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathMotion {
+    pub path: BezierPath,
+    pub progress: Track<f32>,
+    pub orient: bool,
+}
+
+impl PathMotion {
+    pub fn new(path: BezierPath, progress: Track<f32>) -> Result<Self> {
+        if path.curves().is_empty() {
+            bail!(ErrorKind::InvalidProject.error("motion path must have at least one curve"));
+        }
+        Ok(Self {
+            path,
+            progress,
+            orient: false,
+        })
+    }
+
+    pub fn with_orient(mut self, orient: bool) -> Self {
+        self.orient = orient;
+        self
+    }
+
+    // (position, rotation to add).
+    pub fn sample(&self, t: f32) -> (Vec2, f32) {
+        let (point, angle) = self.path.sample(self.progress.sample(t));
+        (point, if self.orient { angle } else { 0.0 })
+    }
+}
+
+fn cubic(from: Vec2, [c1, c2, to]: [Vec2; 3], u: f32) -> Vec2 {
+    let v = 1.0 - u;
+    let (a, b, c, d) = (v * v * v, 3.0 * v * v * u, 3.0 * v * u * u, u * u * u);
+    Vec2 {
+        x: a * from.x + b * c1.x + c * c2.x + d * to.x,
+        y: a * from.y + b * c1.y + c * c2.y + d * to.y,
+    }
+}
+
+fn cubic_tangent(from: Vec2, [c1, c2, to]: [Vec2; 3], u: f32) -> Vec2 {
+    let v = 1.0 - u;
+    let (a, b, c) = (3.0 * v * v, 6.0 * v * u, 3.0 * u * u);
+    Vec2 {
+        x: a * (c1.x - from.x) + b * (c2.x - c1.x) + c * (to.x - c2.x),
+        y: a * (c1.y - from.y) + b * (c2.y - c1.y) + c * (to.y - c2.y),
+    }
+}
+
+fn distance(a: Vec2, b: Vec2) -> f32 {
+    (b.x - a.x).hypot(b.y - a.y)
+}
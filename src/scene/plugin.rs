@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+use crate::scene::{Color, Vec2};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamValue {
+    Number(f32),
+    Bool(bool),
+    Text(String),
+    Color(Color),
+    Vec2(Vec2),
+}
+
+impl From<f32> for ParamValue {
+    fn from(value: f32) -> Self {
+        ParamValue::Number(value)
+    }
+}
+
+impl From<bool> for ParamValue {
+    fn from(value: bool) -> Self {
+        ParamValue::Bool(value)
+    }
+}
+
+impl From<&str> for ParamValue {
+    fn from(value: &str) -> Self {
+        ParamValue::Text(value.to_string())
+    }
+}
+
+impl From<String> for ParamValue {
+    fn from(value: String) -> Self {
+        ParamValue::Text(value)
+    }
+}
+
+impl From<Color> for ParamValue {
+    fn from(value: Color) -> Self {
+        ParamValue::Color(value)
+    }
+}
+
+impl From<Vec2> for ParamValue {
+    fn from(value: Vec2) -> Self {
+        ParamValue::Vec2(value)
+    }
+}
+
+pub type Params = BTreeMap<String, ParamValue>;
+
+// An object whose drawing is provided by a plugin registered under `kind`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginObject {
+    pub kind: String,
+    pub params: Params,
+}
+
+impl PluginObject {
+    pub fn new(kind: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            params: Params::new(),
+        }
+    }
+
+    pub fn with_param(mut self, key: impl Into<String>, value: impl Into<ParamValue>) -> Self {
+        self.params.insert(key.into(), value.into());
+        self
+    }
+}
+
+// A full-frame effect provided by a plugin registered under `kind`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginEffect {
+    pub kind: String,
+    pub params: Params,
+}
+
+impl PluginEffect {
+    pub fn new(kind: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            params: Params::new(),
+        }
+    }
+
+    pub fn with_param(mut self, key: impl Into<String>, value: impl Into<ParamValue>) -> Self {
+        self.params.insert(key.into(), value.into());
+        self
+    }
+}
@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+
+// A screen-space effect run on the GPU over the whole frame, after every
+// layer is composited and before the frame is captured. Unlike
+// `PluginEffect`s, which see the captured pixels, these run at the render
+// scale and cost little. Only the top-level timeline's are applied.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PostEffect {
+    // Darkens toward the corners: by `amount` (0..1) at the corners, falling
+    // off over `softness`, the fraction of the way out from the centre where
+    // darkening starts.
+    Vignette { amount: f32, softness: f32 },
+    // Film grain of up to `amount` (0..1) per pixel. Different every frame,
+    // but the same every time a given frame is rendered.
+    Grain { amount: f32 },
+    // Glow around pixels brighter than `threshold` (0..1 luma), spread over
+    // `radius` output pixels and added at `intensity`.
+    Bloom {
+        threshold: f32,
+        intensity: f32,
+        radius: f32,
+    },
+    // A colour grade through a lookup table, mixed in by `amount` (0..1).
+    // The table is a strip image N*N wide and N high: N slices of blue side
+    // by side, red across each slice and green down it, as colour grading
+    // tools export (e.g. 256x16 or 1024x32).
+    Lut { path: PathBuf, amount: f32 },
+}
+
+impl PostEffect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PostEffect::Vignette { .. } => "vignette",
+            PostEffect::Grain { .. } => "grain",
+            PostEffect::Bloom { .. } => "bloom",
+            PostEffect::Lut { .. } => "lut",
+        }
+    }
+}
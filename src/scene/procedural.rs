@@ -0,0 +1,134 @@
+use std::f32::consts::TAU;
+
+use crate::scene::animation::Lerp;
+
+// Motion added on top of a track's keyframes (`Track::with_added`). Each is
+// a function of time alone, so any frame samples the same value however the
+// render is split up or ordered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Procedural<T> {
+    Sine(SineTrack<T>),
+    Noise(NoiseTrack<T>),
+}
+
+impl<T: Lerp> Procedural<T> {
+    pub fn add_to(&self, value: T, t: f32) -> T {
+        match self {
+            Procedural::Sine(sine) => value.add_wave(sine.amplitude, |_| sine.wave(t)),
+            Procedural::Noise(noise) => {
+                value.add_wave(noise.amplitude, |axis| noise.wave(t, axis))
+            }
+        }
+    }
+}
+
+impl<T> From<SineTrack<T>> for Procedural<T> {
+    fn from(sine: SineTrack<T>) -> Self {
+        Procedural::Sine(sine)
+    }
+}
+
+impl<T> From<NoiseTrack<T>> for Procedural<T> {
+    fn from(noise: NoiseTrack<T>) -> Self {
+        Procedural::Noise(noise)
+    }
+}
+
+// A steady oscillation, e.g. idle bobbing. Every axis moves in step; give
+// an axis zero amplitude to keep it still.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SineTrack<T> {
+    pub amplitude: T,
+    // Cycles per second.
+    pub frequency: f32,
+    // Offset into the cycle, in cycles (0.25 starts at the peak).
+    pub phase: f32,
+}
+
+impl<T> SineTrack<T> {
+    pub fn new(amplitude: T, frequency: f32) -> Self {
+        Self {
+            amplitude,
+            frequency,
+            phase: 0.0,
+        }
+    }
+
+    pub fn with_phase(mut self, phase: f32) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    fn wave(&self, t: f32) -> f32 {
+        (TAU * (self.frequency * t + self.phase)).sin()
+    }
+}
+
+// Smooth random wandering in -1..1 times `amplitude` ("wiggle"), e.g. camera
+// shake. The same seed always gives the same motion; each axis wanders on
+// its own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseTrack<T> {
+    pub seed: u64,
+    // Roughly how many direction changes per second.
+    pub frequency: f32,
+    pub amplitude: T,
+    // Layers of finer detail, each at twice the frequency and half the
+    // strength of the one before. 1 is plain smooth noise.
+    pub octaves: u32,
+}
+
+impl<T> NoiseTrack<T> {
+    pub fn new(seed: u64, frequency: f32, amplitude: T) -> Self {
+        Self {
+            seed,
+            frequency,
+            amplitude,
+            octaves: 1,
+        }
+    }
+
+    pub fn with_octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves.max(1);
+        self
+    }
+
+    fn wave(&self, t: f32, axis: u32) -> f32 {
+        let (mut sum, mut total, mut strength, mut frequency) = (0.0, 0.0, 1.0, self.frequency);
+        for octave in 0..self.octaves.max(1) {
+            let stream = self.seed ^ ((axis as u64) << 32 | octave as u64);
+            sum += gradient_noise(stream, t * frequency) * strength;
+            total += strength;
+            strength *= 0.5;
+            frequency *= 2.0;
+        }
+        sum / total
+    }
+}
+
+// 1D gradient (Perlin) noise in -1..1. Integer hashing keeps it identical
+// across platforms.
+fn gradient_noise(stream: u64, x: f32) -> f32 {
+    // Plain gradient noise is zero at whole numbers; shifting each stream
+    // keeps octaves and axes from all passing through zero together.
+    let x = x + (splitmix64(!stream) >> 40) as f32 / (1u64 << 24) as f32;
+    let cell = x.floor();
+    let offset = x - cell;
+    let gradient = |i: i64| {
+        let hash = splitmix64(stream.wrapping_add(splitmix64(i as u64)));
+        (hash >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    };
+    let from = gradient(cell as i64) * offset;
+    let to = gradient(cell as i64 + 1) * (offset - 1.0);
+    // Quintic fade, so the motion has no visible kinks at cell edges.
+    let fade = offset * offset * offset * (offset * (offset * 6.0 - 15.0) + 10.0);
+    // Gradient noise peaks at 0.5 in 1D.
+    2.0 * (from + (to - from) * fade)
+}
+
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::scene::{Color, Track, Vec2};
+
+// A uniform's value over clip-local time. Colours are constant and reach the
+// shader as a 0..1 `vec4`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UniformTrack {
+    Float(Track<f32>),
+    Vec2(Track<Vec2>),
+    Color(Color),
+}
+
+impl From<Track<f32>> for UniformTrack {
+    fn from(track: Track<f32>) -> Self {
+        UniformTrack::Float(track)
+    }
+}
+
+impl From<Track<Vec2>> for UniformTrack {
+    fn from(track: Track<Vec2>) -> Self {
+        UniformTrack::Vec2(track)
+    }
+}
+
+impl From<f32> for UniformTrack {
+    fn from(value: f32) -> Self {
+        UniformTrack::Float(Track::from_constant(value))
+    }
+}
+
+impl From<Vec2> for UniformTrack {
+    fn from(value: Vec2) -> Self {
+        UniformTrack::Vec2(Track::from_constant(value))
+    }
+}
+
+impl From<Color> for UniformTrack {
+    fn from(value: Color) -> Self {
+        UniformTrack::Color(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UniformValue {
+    Float(f32),
+    Vec2(Vec2),
+    Vec4([f32; 4]),
+}
+
+impl UniformTrack {
+    pub fn sample(&self, t: f32) -> UniformValue {
+        match self {
+            UniformTrack::Float(track) => UniformValue::Float(track.sample(t)),
+            UniformTrack::Vec2(track) => UniformValue::Vec2(track.sample(t)),
+            UniformTrack::Color(color) => UniformValue::Vec4(
+                [color.r, color.g, color.b, color.a].map(|channel| channel as f32 / 255.0),
+            ),
+        }
+    }
+}
+
+// A GLSL fragment shader the clip's object is drawn through, for effects
+// like ripples, dissolves and chromatic aberration. The file is a raylib
+// fragment shader (GLSL 330: `fragTexCoord`, `fragColor`, `texture0` and
+// `colDiffuse` in, `finalColor` out). Uniforms are set by name each frame,
+// plus `time` in clip-local seconds when the shader declares it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectShader {
+    pub path: PathBuf,
+    pub uniforms: BTreeMap<String, UniformTrack>,
+}
+
+impl ObjectShader {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            uniforms: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_uniform(mut self, name: impl Into<String>, value: impl Into<UniformTrack>) -> Self {
+        self.uniforms.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn sample(&self, t: f32) -> SampledShader {
+        SampledShader {
+            path: self.path.clone(),
+            uniforms: self
+                .uniforms
+                .iter()
+                .map(|(name, track)| (name.clone(), track.sample(t)))
+                .collect(),
+        }
+    }
+}
+
+// An `ObjectShader` with its uniforms sampled for one frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampledShader {
+    pub path: PathBuf,
+    pub uniforms: Vec<(String, UniformValue)>,
+}
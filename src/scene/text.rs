@@ -98,6 +98,8 @@ pub struct FontFamily {
     pub bold: Option<FontSource>,
     pub italic: Option<FontSource>,
     pub bold_italic: Option<FontSource>,
+    // Tried in order for glyphs the styled font does not cover.
+    pub fallbacks: Vec<FontSource>,
 }
 
 impl FontFamily {
@@ -107,9 +109,34 @@ impl FontFamily {
             bold: None,
             italic: None,
             bold_italic: None,
+            fallbacks: Vec::new(),
         }
     }
 
+    pub fn with_fallback(mut self, font: FontSource) -> Self {
+        self.fallbacks.push(font);
+        self
+    }
+
+    pub fn chain(&self, style: StyleFlags) -> Vec<&FontSource> {
+        let mut chain = vec![self.resolve(style)];
+        chain.extend(self.fallbacks.iter());
+        chain
+    }
+
+    // Every font in the family: the styles, then the fallbacks.
+    pub fn sources(&self) -> impl Iterator<Item = &FontSource> {
+        [
+            Some(&self.regular),
+            self.bold.as_ref(),
+            self.italic.as_ref(),
+            self.bold_italic.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(self.fallbacks.iter())
+    }
+
     pub fn resolve(&self, style: StyleFlags) -> &FontSource {
         if style.bold && style.italic {
             if let Some(font) = &self.bold_italic {
@@ -130,6 +157,20 @@ impl FontFamily {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStroke {
+    pub width: f32,
+    pub color: crate::scene::Color,
+}
+
+// Plate drawn behind the whole text block; padding and radius are in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextBackground {
+    pub padding: f32,
+    pub corner_radius: f32,
+    pub color: crate::scene::Color,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct TextObject {
     pub text: StyledText,
@@ -139,4 +180,6 @@ pub struct TextObject {
     pub max_width: f32,
     pub color: crate::scene::Color,
     pub line_spacing: f32,
+    pub stroke: Option<TextStroke>,
+    pub background: Option<TextBackground>,
 }
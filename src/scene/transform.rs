@@ -1,4 +1,5 @@
 use crate::scene::animation::Track;
+use crate::scene::path::PathMotion;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vec2 {
@@ -56,6 +57,28 @@ pub struct Transform {
     pub opacity: f32,
 }
 
+impl Transform {
+    // `child` expressed in this transform's space: scaled, rotated (clockwise
+    // on screen, like clip rotation) and offset, with opacities multiplied.
+    pub fn compose(&self, child: &Transform) -> Transform {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let x = child.pos.x * self.scale.x;
+        let y = child.pos.y * self.scale.y;
+        Transform {
+            pos: Vec2 {
+                x: self.pos.x + x * cos + y * sin,
+                y: self.pos.y - x * sin + y * cos,
+            },
+            scale: Vec2 {
+                x: self.scale.x * child.scale.x,
+                y: self.scale.y * child.scale.y,
+            },
+            rotation: self.rotation + child.rotation,
+            opacity: self.opacity * child.opacity,
+        }
+    }
+}
+
 impl Default for Transform {
     fn default() -> Self {
         Self {
@@ -73,6 +96,8 @@ pub struct AnimatedTransform {
     pub scale: Track<Vec2>,
     pub rotation: Track<f32>,
     pub opacity: Track<f32>,
+    // Moves along a path; `position` is added on top as an offset.
+    pub path: Option<PathMotion>,
 }
 
 impl AnimatedTransform {
@@ -82,16 +107,39 @@ impl AnimatedTransform {
             scale: Track::from_constant(transform.scale),
             rotation: Track::from_constant(transform.rotation),
             opacity: Track::from_constant(transform.opacity),
+            path: None,
         }
     }
 
+    pub fn with_path(mut self, path: PathMotion) -> Self {
+        self.path = Some(path);
+        self
+    }
+
     pub fn sample(&self, t: f32) -> Transform {
-        Transform {
+        let mut transform = Transform {
             pos: self.position.sample(t),
             scale: self.scale.sample(t),
             rotation: self.rotation.sample(t),
             opacity: self.opacity.sample(t),
+        };
+        if let Some(path) = &self.path {
+            let (point, angle) = path.sample(t);
+            transform.pos.x += point.x;
+            transform.pos.y += point.y;
+            transform.rotation += angle;
         }
+        transform
+    }
+
+    pub fn end_time(&self) -> f32 {
+        let path_end = self.path.as_ref().map_or(0.0, |path| path.progress.end_time());
+        self.position
+            .end_time()
+            .max(self.scale.end_time())
+            .max(self.rotation.end_time())
+            .max(self.opacity.end_time())
+            .max(path_end)
     }
 }
 
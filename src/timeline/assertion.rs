@@ -0,0 +1,85 @@
+use anyhow::{bail, Result};
+
+use crate::error::ErrorKind;
+use crate::scene::Vec2;
+use crate::timeline::Severity;
+
+// What an assertion requires of a named clip while it applies.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssertionRule {
+    // The clip is drawn entirely within the output frame.
+    InsideFrame { clip: String },
+    // The clip doesn't touch a box, e.g. a logo's safe area or where a
+    // platform puts its own buttons. Centre and size in graph coords.
+    AvoidsRegion {
+        clip: String,
+        center: Vec2,
+        size: Vec2,
+    },
+}
+
+impl AssertionRule {
+    pub fn clip(&self) -> &str {
+        match self {
+            AssertionRule::InsideFrame { clip } | AssertionRule::AvoidsRegion { clip, .. } => clip,
+        }
+    }
+}
+
+// A check run on every rendered frame between `start` and `end`, so a
+// template edit that pushes a title off screen or under a watermark fails
+// the render instead of waiting for someone to spot it. Nothing is drawn.
+// Clips that aren't drawn at a frame (not started, fully faded) pass.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assertion {
+    pub start: f32,
+    pub end: f32,
+    pub rule: AssertionRule,
+    // `Error` fails the render; `Warning` adds to the report's warnings.
+    pub severity: Severity,
+}
+
+impl Assertion {
+    pub fn new(start: f32, end: f32, rule: AssertionRule) -> Result<Self> {
+        if end <= start {
+            bail!(ErrorKind::InvalidProject.error("assertion end must be > start"));
+        }
+        Ok(Self {
+            start,
+            end,
+            rule,
+            severity: Severity::Error,
+        })
+    }
+
+    pub fn inside_frame(start: f32, end: f32, clip: impl Into<String>) -> Result<Self> {
+        Self::new(start, end, AssertionRule::InsideFrame { clip: clip.into() })
+    }
+
+    pub fn avoids_region(
+        start: f32,
+        end: f32,
+        clip: impl Into<String>,
+        center: Vec2,
+        size: Vec2,
+    ) -> Result<Self> {
+        Self::new(
+            start,
+            end,
+            AssertionRule::AvoidsRegion {
+                clip: clip.into(),
+                center,
+                size,
+            },
+        )
+    }
+
+    pub fn warn_only(mut self) -> Self {
+        self.severity = Severity::Warning;
+        self
+    }
+
+    pub fn applies_at(&self, time: f32) -> bool {
+        time >= self.start && time <= self.end
+    }
+}
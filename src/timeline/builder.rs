@@ -0,0 +1,247 @@
+use std::ops::Range;
+
+use anyhow::{bail, Context, Result};
+
+use crate::error::ErrorKind;
+use crate::scene::{AnimatedTransform, Object, PluginEffect, Transform};
+use crate::timeline::{
+    AspectOverride, BlendMode, Clip, Layer, LayerGroup, LoopMode, Timeline, TrackMatte,
+    Transition,
+};
+
+// Fluent construction of a timeline:
+//
+//     TimelineBuilder::new(30)
+//         .layer("titles")
+//         .clip(title).at(0.0..3.0).placed(Transform::default())
+//         .clip(subtitle).then(2.0)
+//         .build()?
+//
+// Calls apply to the most recent `layer` / `clip`. Misuse (a clip before any
+// layer, a clip without a time range) is reported by `build`, along with the
+// usual clip and duration checks.
+pub struct TimelineBuilder {
+    fps: u32,
+    duration: Option<f32>,
+    layers: Vec<(Layer, Vec<PendingClip>)>,
+    groups: Vec<LayerGroup>,
+    effects: Vec<PluginEffect>,
+    markers: Vec<(f32, String)>,
+    error: Option<String>,
+}
+
+struct PendingClip {
+    name: Option<String>,
+    object: Object,
+    range: Option<Range<f32>>,
+    transform: AnimatedTransform,
+    transition_in: Option<Transition>,
+    loop_mode: LoopMode,
+    freeze_at: Option<f32>,
+    responsive: Vec<AspectOverride>,
+}
+
+impl TimelineBuilder {
+    // Without `duration`, the timeline runs to its last clip end.
+    pub fn new(fps: u32) -> Self {
+        Self {
+            fps,
+            duration: None,
+            layers: Vec::new(),
+            groups: Vec::new(),
+            effects: Vec::new(),
+            markers: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn duration(mut self, duration: f32) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    pub fn layer(mut self, name: impl Into<String>) -> Self {
+        self.layers.push((Layer::new(name), Vec::new()));
+        self
+    }
+
+    pub fn blend(self, blend: BlendMode) -> Self {
+        self.map_layer(|layer| layer.blend = blend)
+    }
+
+    pub fn matte(self, matte: TrackMatte) -> Self {
+        self.map_layer(|layer| layer.matte = Some(matte))
+    }
+
+    pub fn z(self, z: i32) -> Self {
+        self.map_layer(|layer| layer.z_override = Some(z))
+    }
+
+    // Puts the current layer in the group named `name`, defined with `group`.
+    pub fn in_group(self, name: impl Into<String>) -> Self {
+        self.map_layer(|layer| layer.group = Some(name.into()))
+    }
+
+    pub fn layer_transform(self, transform: AnimatedTransform) -> Self {
+        self.map_layer(|layer| layer.transform = transform)
+    }
+
+    // Adds a clip to the current layer; place it with `at` or `then`.
+    pub fn clip(mut self, object: Object) -> Self {
+        match self.layers.last_mut() {
+            Some((_, clips)) => clips.push(PendingClip {
+                name: None,
+                object,
+                range: None,
+                transform: AnimatedTransform::default(),
+                transition_in: None,
+                loop_mode: LoopMode::Once,
+                freeze_at: None,
+                responsive: Vec::new(),
+            }),
+            None => self.fail("clip() called before layer()"),
+        }
+        self
+    }
+
+    pub fn at(self, range: Range<f32>) -> Self {
+        self.map_clip(|clip, _| clip.range = Some(range))
+    }
+
+    // Starts the clip where the layer's earlier clips end (or at 0) and runs
+    // it for `duration` seconds.
+    pub fn then(self, duration: f32) -> Self {
+        self.map_clip(|clip, previous_end| {
+            clip.range = Some(previous_end..previous_end + duration);
+        })
+    }
+
+    pub fn named(self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        self.map_clip(|clip, _| clip.name = Some(name))
+    }
+
+    pub fn transform(self, transform: AnimatedTransform) -> Self {
+        self.map_clip(|clip, _| clip.transform = transform)
+    }
+
+    // A constant transform for the current clip.
+    pub fn placed(self, transform: Transform) -> Self {
+        self.transform(AnimatedTransform::constant(transform))
+    }
+
+    pub fn transition_in(self, transition: Transition) -> Self {
+        self.map_clip(|clip, _| clip.transition_in = Some(transition))
+    }
+
+    pub fn loop_mode(self, loop_mode: LoopMode) -> Self {
+        self.map_clip(|clip, _| clip.loop_mode = loop_mode)
+    }
+
+    pub fn freeze_at(self, local_time: f32) -> Self {
+        self.map_clip(|clip, _| clip.freeze_at = Some(local_time))
+    }
+
+    // Adjusts the current clip when rendering for the rule's aspect.
+    pub fn responsive(self, rule: AspectOverride) -> Self {
+        self.map_clip(|clip, _| clip.responsive.push(rule))
+    }
+
+    pub fn group(mut self, group: LayerGroup) -> Self {
+        self.groups.push(group);
+        self
+    }
+
+    pub fn effect(mut self, effect: PluginEffect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    pub fn marker(mut self, time: f32, name: impl Into<String>) -> Self {
+        self.markers.push((time, name.into()));
+        self
+    }
+
+    pub fn build(self) -> Result<Timeline> {
+        if let Some(error) = self.error {
+            bail!(ErrorKind::InvalidProject.error(format!("timeline builder: {error}")));
+        }
+        let mut timeline = match self.duration {
+            Some(duration) => Timeline::new(duration, self.fps)?,
+            None => Timeline::auto_duration(self.fps)?,
+        };
+        for group in self.groups {
+            timeline.add_group(group)?;
+        }
+        for (mut layer, clips) in self.layers {
+            if let Some(group) = &layer.group
+                && timeline.group(group).is_none()
+            {
+                bail!(ErrorKind::InvalidProject.error(format!(
+                    "layer \"{}\" is in unknown group \"{group}\"",
+                    layer.name
+                )));
+            }
+            for (index, pending) in clips.into_iter().enumerate() {
+                let ctx = format!("layer \"{}\" clip {index}", layer.name);
+                let Some(range) = pending.range else {
+                    bail!(ErrorKind::InvalidProject.error(format!("{ctx} has no time range")));
+                };
+                let mut clip = Clip::new(range.start, range.end, pending.object, pending.transform)
+                    .with_context(|| ctx.clone())?
+                    .with_loop_mode(pending.loop_mode);
+                if let Some(name) = pending.name {
+                    clip = clip.with_name(name);
+                }
+                if let Some(transition) = pending.transition_in {
+                    clip = clip.with_transition_in(transition);
+                }
+                if let Some(freeze_at) = pending.freeze_at {
+                    clip = clip.freeze_at(freeze_at);
+                }
+                for rule in pending.responsive {
+                    clip = clip.with_override(rule);
+                }
+                layer.add_clip(clip);
+            }
+            timeline.add_layer(layer)?;
+        }
+        for effect in self.effects {
+            timeline.add_effect(effect);
+        }
+        for (time, name) in self.markers {
+            timeline.add_marker(time, name)?;
+        }
+        Ok(timeline)
+    }
+
+    fn map_layer(mut self, f: impl FnOnce(&mut Layer)) -> Self {
+        match self.layers.last_mut() {
+            Some((layer, _)) => f(layer),
+            None => self.fail("layer setting used before layer()"),
+        }
+        self
+    }
+
+    fn map_clip(mut self, f: impl FnOnce(&mut PendingClip, f32)) -> Self {
+        let Some((_, clips)) = self.layers.last_mut() else {
+            self.fail("clip setting used before layer()");
+            return self;
+        };
+        let Some((clip, earlier)) = clips.split_last_mut() else {
+            self.fail("clip setting used before clip()");
+            return self;
+        };
+        let previous_end = earlier
+            .iter()
+            .filter_map(|clip| clip.range.as_ref().map(|range| range.end))
+            .fold(0.0, f32::max);
+        f(clip, previous_end);
+        self
+    }
+
+    // Keeps the first misuse; `build` reports it.
+    fn fail(&mut self, error: &str) {
+        self.error.get_or_insert_with(|| error.to_string());
+    }
+}
@@ -1,37 +1,139 @@
 use anyhow::{bail, Result};
 
-use crate::scene::{AnimatedTransform, Object};
+use crate::error::ErrorKind;
+use crate::scene::{AnimatedTransform, Object, ObjectShader, Track};
+use crate::timeline::{Aspect, AspectOverride, Binding, Transition};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopMode {
+    Once,
+    Loop,
+    PingPong,
+}
+
+impl LoopMode {
+    pub fn wrap(self, t: f32, period: f32) -> f32 {
+        if period <= 0.0 {
+            return t;
+        }
+        match self {
+            LoopMode::Once => t,
+            LoopMode::Loop => t.rem_euclid(period),
+            LoopMode::PingPong => {
+                let cycle = t.rem_euclid(2.0 * period);
+                if cycle <= period {
+                    cycle
+                } else {
+                    2.0 * period - cycle
+                }
+            }
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Clip {
+    // Optional handle for finding the clip again, e.g. `Timeline::isolate_clip`.
+    pub name: Option<String>,
     pub start: f32,
     pub end: f32,
     pub object: Object,
     pub transform: AnimatedTransform,
+    pub transition_in: Option<Transition>,
+    // Maps clip-local time to content time (slow motion, speed ramps,
+    // freeze frames). Without it content plays at 1:1 from the clip start.
+    pub time_map: Option<Track<f32>>,
+    // How content shorter than the clip window continues once it ends.
+    pub loop_mode: LoopMode,
+    // Clip-local time from which the clip holds its state (transform, nested
+    // timeline, plugin) until it ends.
+    pub freeze_at: Option<f32>,
+    // Properties driven by external per-frame variables (see `DataSource`).
+    pub bindings: Vec<Binding>,
+    // Per-aspect changes, used when the timeline renders for that aspect
+    // (see `Timeline::set_aspect`).
+    pub responsive: Vec<AspectOverride>,
+    // Fragment shader the object is drawn through.
+    pub shader: Option<ObjectShader>,
 }
 
 impl Clip {
-    pub fn new(
-        start: f32,
-        end: f32,
-        object: Object,
-        transform: AnimatedTransform,
-        duration: f32,
-    ) -> Result<Self> {
-        if duration <= 0.0 {
-            bail!("duration must be > 0");
-        }
-        if start < 0.0 || end <= start || end > duration {
-            bail!("clip bounds must satisfy 0 <= start < end <= duration");
+    pub fn new(start: f32, end: f32, object: Object, transform: AnimatedTransform) -> Result<Self> {
+        // The upper bound is checked against the timeline duration in
+        // `Timeline::add_layer`.
+        if start < 0.0 || end <= start {
+            bail!(ErrorKind::InvalidProject.error("clip bounds must satisfy 0 <= start < end"));
         }
         Ok(Self {
+            name: None,
             start,
             end,
             object,
             transform,
+            transition_in: None,
+            time_map: None,
+            loop_mode: LoopMode::Once,
+            freeze_at: None,
+            bindings: Vec::new(),
+            responsive: Vec::new(),
+            shader: None,
         })
     }
 
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn with_binding(mut self, binding: Binding) -> Self {
+        self.bindings.push(binding);
+        self
+    }
+
+    // Replaces any earlier override for the same aspect.
+    pub fn with_override(mut self, rule: AspectOverride) -> Self {
+        self.responsive.retain(|existing| existing.aspect != rule.aspect);
+        self.responsive.push(rule);
+        self
+    }
+
+    pub fn override_for(&self, aspect: Option<Aspect>) -> Option<&AspectOverride> {
+        let aspect = aspect?;
+        self.responsive.iter().find(|rule| rule.aspect == aspect)
+    }
+
+    // Active at `t` and not hidden for `aspect`.
+    pub fn is_shown(&self, t: f32, aspect: Option<Aspect>) -> bool {
+        self.is_active(t) && self.override_for(aspect).is_none_or(|rule| rule.visible)
+    }
+
+    pub fn with_shader(mut self, shader: ObjectShader) -> Self {
+        self.shader = Some(shader);
+        self
+    }
+
+    pub fn with_transition_in(mut self, transition: Transition) -> Self {
+        self.transition_in = Some(transition);
+        self
+    }
+
+    pub fn with_time_map(mut self, time_map: Track<f32>) -> Self {
+        self.time_map = Some(time_map);
+        self
+    }
+
+    pub fn with_loop_mode(mut self, loop_mode: LoopMode) -> Self {
+        self.loop_mode = loop_mode;
+        self
+    }
+
+    // Holds the state sampled at `local_time` for the rest of the clip, e.g.
+    // to pause a nested composition while a callout animates on top of it.
+    pub fn freeze_at(mut self, local_time: f32) -> Self {
+        self.freeze_at = Some(local_time.max(0.0));
+        self
+    }
+
     pub fn is_active(&self, t: f32) -> bool {
         t >= self.start && t < self.end
     }
@@ -53,4 +155,27 @@ impl Clip {
             t - self.start
         }
     }
+
+    // Time the clip's content (transform, nested timeline, plugin) is sampled at.
+    pub fn content_time(&self, t: f32) -> f32 {
+        let mut local_t = self.clamped_local_time(t);
+        if let Some(freeze_at) = self.freeze_at {
+            local_t = local_t.min(freeze_at);
+        }
+        let content_t = match &self.time_map {
+            Some(time_map) => time_map.sample(local_t).max(0.0),
+            None => local_t,
+        };
+        self.loop_mode.wrap(content_t, self.content_duration())
+    }
+
+    // Length of the clip's own animation: the last transform keyframe, or the
+    // inner timeline's duration for compositions if that is longer.
+    pub fn content_duration(&self) -> f32 {
+        let transform_end = self.transform.end_time();
+        match &self.object {
+            Object::Composition(composition) => transform_end.max(composition.timeline.duration),
+            _ => transform_end,
+        }
+    }
 }
@@ -0,0 +1,183 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::error::ErrorKind;
+use crate::scene::animation::Lerp;
+use crate::scene::{Easing, Keyframe, Track, Vec2};
+
+// Comma-separated data with a header row, e.g. sensor logs, stock prices or
+// curves exported from other animation tools, turned into keyframe tracks so
+// the data drives object properties directly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvTable {
+    pub headers: Vec<String>,
+    // Each row with the line it started on, for error messages.
+    rows: Vec<(usize, Vec<String>)>,
+}
+
+impl CsvTable {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).with_context(|| {
+            ErrorKind::MissingAsset.error(format!("data file not found: {}", path.display()))
+        })?;
+        Self::parse(&source).with_context(|| format!("invalid CSV file: {}", path.display()))
+    }
+
+    // The first row names the columns. Fields may be quoted to hold commas,
+    // quotes (doubled) or line breaks; blank lines are skipped.
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut records = Vec::new();
+        let (mut record, mut field) = (Vec::new(), String::new());
+        let (mut line, mut record_line) = (1, 1);
+        let mut quoted = false;
+        // Spreadsheet exports often start with a byte order mark.
+        let mut chars = source.trim_start_matches('\u{feff}').chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' if quoted => quoted = false,
+                '"' if field.is_empty() => quoted = true,
+                ',' if !quoted => record.push(std::mem::take(&mut field)),
+                '\n' if !quoted => {
+                    record.push(std::mem::take(&mut field));
+                    records.push((record_line, std::mem::take(&mut record)));
+                    line += 1;
+                    record_line = line;
+                }
+                '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+                _ => {
+                    if ch == '\n' {
+                        line += 1;
+                    }
+                    field.push(ch);
+                }
+            }
+        }
+        if quoted {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "line {record_line}: quoted field is never closed"
+            )));
+        }
+        record.push(field);
+        records.push((record_line, record));
+        records.retain(|(_, record)| !(record.len() == 1 && record[0].trim().is_empty()));
+
+        let mut records = records.into_iter();
+        let Some((_, headers)) = records.next() else {
+            bail!(ErrorKind::InvalidProject.error("CSV data has no header row"));
+        };
+        let headers = headers.into_iter().map(|name| name.trim().to_string()).collect();
+        Ok(Self {
+            headers,
+            rows: records.collect(),
+        })
+    }
+
+    pub fn column(&self, name: &str) -> Result<usize> {
+        self.headers.iter().position(|header| header == name).with_context(|| {
+            ErrorKind::InvalidProject.error(format!(
+                "no column \"{name}\" (columns are {})",
+                quoted(&self.headers)
+            ))
+        })
+    }
+
+    // A keyframe per row from the `time` (seconds) and `value` columns, each
+    // easing to the next with `easing`. Rows with either cell empty are
+    // skipped, so gaps in the data are bridged.
+    pub fn track(&self, time: &str, value: &str, easing: Easing) -> Result<Track<f32>> {
+        self.track_with(time, &[value], easing, |values| Ok(values[0]))
+    }
+
+    // As `track`, with `x` and `y` from two columns.
+    pub fn track_vec2(
+        &self,
+        time: &str,
+        x: &str,
+        y: &str,
+        easing: Easing,
+    ) -> Result<Track<Vec2>> {
+        self.track_with(time, &[x, y], easing, |values| {
+            Ok(Vec2 {
+                x: values[0],
+                y: values[1],
+            })
+        })
+    }
+
+    // Keyframes from `time` and the numbers in `columns`, made into values by
+    // `value`.
+    pub(crate) fn track_with<T: Lerp>(
+        &self,
+        time: &str,
+        columns: &[&str],
+        easing: Easing,
+        value: impl Fn(&[f32]) -> Result<T>,
+    ) -> Result<Track<T>> {
+        let time_index = self.column(time)?;
+        let indices = columns
+            .iter()
+            .map(|name| self.column(name))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut keyframes: Vec<Keyframe<T>> = Vec::with_capacity(self.rows.len());
+        let mut numbers = Vec::with_capacity(columns.len());
+        'rows: for (line, row) in &self.rows {
+            let cell = |index: usize| row.get(index).map_or("", |cell| cell.trim());
+            let number = |index: usize| -> Result<Option<f32>> {
+                let text = cell(index);
+                if text.is_empty() {
+                    return Ok(None);
+                }
+                match text.parse::<f32>() {
+                    Ok(number) if number.is_finite() => Ok(Some(number)),
+                    _ => bail!(ErrorKind::InvalidProject.error(format!(
+                        "line {line}: \"{text}\" in column \"{}\" is not a number",
+                        self.headers[index]
+                    ))),
+                }
+            };
+            let Some(t) = number(time_index)? else {
+                continue;
+            };
+            numbers.clear();
+            for &index in &indices {
+                match number(index)? {
+                    Some(number) => numbers.push(number),
+                    None => continue 'rows,
+                }
+            }
+            if let Some(previous) = keyframes.last()
+                && t <= previous.time
+            {
+                bail!(ErrorKind::InvalidProject.error(format!(
+                    "line {line}: time {t} is not after the previous row's {}",
+                    previous.time
+                )));
+            }
+            let value = value(&numbers).with_context(|| format!("line {line}"))?;
+            keyframes.push(Keyframe::new(t, value, easing));
+        }
+        if keyframes.is_empty() {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "no rows with \"{time}\" and {} filled in",
+                quoted(columns)
+            )));
+        }
+        Track::new(keyframes)
+    }
+}
+
+fn quoted(names: &[impl AsRef<str>]) -> String {
+    names
+        .iter()
+        .map(|name| format!("\"{}\"", name.as_ref()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::error::ErrorKind;
+use crate::scene::{Object, Transform};
+
+// Named values supplied from outside the timeline for one frame.
+pub type Variables = BTreeMap<String, f32>;
+
+// Supplies variables per rendered frame (live telemetry, recorded data, ...).
+// `frame` is the frame index on the timeline's own frame rate.
+pub trait DataSource: Send + Sync {
+    fn values(&self, frame: u32, t: f32) -> Result<Variables>;
+}
+
+impl<F> DataSource for F
+where
+    F: Fn(u32, f32) -> Result<Variables> + Send + Sync,
+{
+    fn values(&self, frame: u32, t: f32) -> Result<Variables> {
+        self(frame, t)
+    }
+}
+
+// Data recorded at a fixed rate, one row of variables per frame. Frames past
+// the last row hold its values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameTable {
+    pub rows: Vec<Variables>,
+}
+
+impl FrameTable {
+    // `{"columns": {"speed": [0, 1.5, ...], "price": [...]}}`, one value per
+    // frame. Columns may differ in length; a short column holds its last value.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).with_context(|| {
+            ErrorKind::MissingAsset.error(format!("data file not found: {}", path.display()))
+        })?;
+        Self::from_json(&source)
+            .with_context(|| format!("invalid data file: {}", path.display()))
+    }
+
+    pub fn from_json(source: &str) -> Result<Self> {
+        let invalid = |msg: &str| ErrorKind::InvalidProject.error(msg.to_string());
+        let doc: Value = serde_json::from_str(source).context(invalid("data is not JSON"))?;
+        let Some(columns) = doc.get("columns").and_then(Value::as_object) else {
+            bail!(invalid("data must have a \"columns\" object"));
+        };
+
+        let mut rows: Vec<Variables> = Vec::new();
+        for (name, values) in columns {
+            let Some(values) = values.as_array() else {
+                bail!(invalid(&format!("column \"{name}\" must be an array")));
+            };
+            for (frame, value) in values.iter().enumerate() {
+                let Some(value) = value.as_f64() else {
+                    bail!(invalid(&format!("column \"{name}\"[{frame}] must be a number")));
+                };
+                if rows.len() <= frame {
+                    rows.resize_with(frame + 1, Variables::new);
+                }
+                rows[frame].insert(name.clone(), value as f32);
+            }
+        }
+        // Hold short columns at their last value.
+        for frame in 1..rows.len() {
+            let (done, rest) = rows.split_at_mut(frame);
+            for (name, value) in &done[frame - 1] {
+                rest[0].entry(name.clone()).or_insert(*value);
+            }
+        }
+        Ok(Self { rows })
+    }
+}
+
+impl DataSource for FrameTable {
+    fn values(&self, frame: u32, _t: f32) -> Result<Variables> {
+        let row = self.rows.get(frame as usize).or(self.rows.last());
+        Ok(row.cloned().unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BindTarget {
+    PositionX,
+    PositionY,
+    ScaleX,
+    ScaleY,
+    Rotation,
+    Opacity,
+    // Replaces `{variable}` in a text object's runs with the value, rounded
+    // to `precision` decimals.
+    Text { precision: usize },
+}
+
+// Drives one clip property from a variable: `value * scale + offset`.
+// Properties whose variable is missing for a frame keep their animated value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub variable: String,
+    pub target: BindTarget,
+    pub scale: f32,
+    pub offset: f32,
+}
+
+impl Binding {
+    pub fn new(variable: impl Into<String>, target: BindTarget) -> Self {
+        Self {
+            variable: variable.into(),
+            target,
+            scale: 1.0,
+            offset: 0.0,
+        }
+    }
+
+    pub fn with_mapping(mut self, scale: f32, offset: f32) -> Self {
+        self.scale = scale;
+        self.offset = offset;
+        self
+    }
+
+    pub(crate) fn apply(&self, vars: &Variables, transform: &mut Transform, object: &mut Object) {
+        let Some(raw) = vars.get(&self.variable) else {
+            return;
+        };
+        let value = raw * self.scale + self.offset;
+        match &self.target {
+            BindTarget::PositionX => transform.pos.x = value,
+            BindTarget::PositionY => transform.pos.y = value,
+            BindTarget::ScaleX => transform.scale.x = value,
+            BindTarget::ScaleY => transform.scale.y = value,
+            BindTarget::Rotation => transform.rotation = value,
+            BindTarget::Opacity => transform.opacity = value.clamp(0.0, 1.0),
+            BindTarget::Text { precision } => {
+                if let Object::Text(text) = object {
+                    let placeholder = format!("{{{}}}", self.variable);
+                    let formatted = format!("{value:.precision$}");
+                    for run in &mut text.text.runs {
+                        run.text = run.text.replace(&placeholder, &formatted);
+                    }
+                }
+            }
+        }
+    }
+}
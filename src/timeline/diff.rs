@@ -0,0 +1,151 @@
+use std::fmt::{self, Write};
+
+use crate::timeline::{Clip, Layer, SampledScene, Timeline};
+
+// A span of timeline time, `start..end` in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeRange {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl TimeRange {
+    pub fn overlaps(&self, start: f32, end: f32) -> bool {
+        self.start < end && start < self.end
+    }
+}
+
+impl Timeline {
+    // Time ranges whose frames may differ between `previous` and `self`,
+    // merged and sorted. Clips are compared by value, so an edited asset file
+    // behind an unchanged path is not detected.
+    pub fn changed_ranges(&self, previous: &Timeline) -> Vec<TimeRange> {
+        let whole = TimeRange {
+            start: 0.0,
+            end: self.duration.max(previous.duration),
+        };
+        if self.fps != previous.fps
+            || self.effects != previous.effects
+            || self.post != previous.post
+            || self.aspect != previous.aspect
+        {
+            return vec![whole];
+        }
+
+        // Groups whose settings changed, which touches every frame their
+        // members are visible in.
+        let changed_groups: Vec<&str> = previous
+            .groups
+            .iter()
+            .chain(&self.groups)
+            .map(|group| group.name.as_str())
+            .filter(|name| previous.group(name) != self.group(name))
+            .collect();
+        let in_changed_group = |layer: &Layer| {
+            layer
+                .group
+                .as_deref()
+                .is_some_and(|name| changed_groups.contains(&name))
+        };
+
+        let mut ranges = Vec::new();
+        if self.duration != previous.duration {
+            ranges.push(TimeRange {
+                start: self.duration.min(previous.duration),
+                end: whole.end,
+            });
+        }
+        for index in 0..self.layers.len().max(previous.layers.len()) {
+            let (old, new) = (previous.layers.get(index), self.layers.get(index));
+            let (Some(old), Some(new)) = (old, new) else {
+                ranges.extend(old.or(new).and_then(layer_span));
+                continue;
+            };
+            // Anything applied to the layer as a whole (or its place in the
+            // draw order) touches every frame the layer is visible in.
+            if old.transform != new.transform
+                || old.blend != new.blend
+                || old.matte != new.matte
+                || old.group != new.group
+                || in_changed_group(old)
+                || in_changed_group(new)
+                || previous.layer_z(index) != self.layer_z(index)
+            {
+                ranges.extend(layer_span(old));
+                ranges.extend(layer_span(new));
+                continue;
+            }
+            for clip_index in 0..old.clips.len().max(new.clips.len()) {
+                let (old_clip, new_clip) = (old.clips.get(clip_index), new.clips.get(clip_index));
+                if old_clip != new_clip {
+                    ranges.extend(old_clip.map(clip_span));
+                    ranges.extend(new_clip.map(clip_span));
+                }
+            }
+        }
+        merge_ranges(ranges)
+    }
+}
+
+impl SampledScene {
+    // Stable hash of everything drawn for the frame, for spotting frames that
+    // changed between runs. Assets are identified by path, not contents.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        let _ = write!(hasher, "{self:?}");
+        hasher.0
+    }
+}
+
+// FNV-1a. Unlike `DefaultHasher` it is the same across Rust releases, so
+// hashes can be stored between runs.
+pub(crate) struct Fnv1a(pub u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+impl fmt::Write for Fnv1a {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+fn clip_span(clip: &Clip) -> TimeRange {
+    TimeRange {
+        start: clip.start,
+        end: clip.end,
+    }
+}
+
+fn layer_span(layer: &Layer) -> Option<TimeRange> {
+    let start = layer.clips.iter().map(|clip| clip.start).reduce(f32::min)?;
+    Some(TimeRange {
+        start,
+        end: layer.end_time(),
+    })
+}
+
+fn merge_ranges(mut ranges: Vec<TimeRange>) -> Vec<TimeRange> {
+    ranges.sort_by(|a, b| a.start.total_cmp(&b.start));
+    let mut merged: Vec<TimeRange> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
@@ -1,10 +1,75 @@
-use crate::timeline::Clip;
+use crate::scene::{AnimatedTransform, PluginEffect, Track};
+use crate::timeline::{Aspect, Clip, SampledTransition, TransitionRole};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Normal,
+    Add,
+    Multiply,
+    Screen,
+    Subtract,
+}
+
+// Masks a layer by the layer drawn directly above it, which is then used only
+// as the matte and not drawn itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackMatte {
+    Alpha,
+    AlphaInverted,
+    Luma,
+    LumaInverted,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Layer {
     pub name: String,
     pub z_override: Option<i32>,
     pub clips: Vec<Clip>,
+    // Sampled at timeline time and applied on top of every clip in the layer.
+    pub transform: AnimatedTransform,
+    pub blend: BlendMode,
+    pub matte: Option<TrackMatte>,
+    // Name of the `LayerGroup` this layer belongs to.
+    pub group: Option<String>,
+}
+
+// Layers composited together first and then treated as one: the group's
+// opacity, blend and effects apply to the combined result, e.g. every UI
+// layer at 80% with one drop shadow, rather than to each layer on its own.
+// Members draw together where the lowest of them would.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerGroup {
+    pub name: String,
+    pub opacity: Track<f32>,
+    pub blend: BlendMode,
+    // Plugin effects applied in order to the group's combined pixels.
+    pub effects: Vec<PluginEffect>,
+}
+
+impl LayerGroup {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            opacity: Track::from_constant(1.0),
+            blend: BlendMode::Normal,
+            effects: Vec::new(),
+        }
+    }
+
+    pub fn with_opacity(mut self, opacity: Track<f32>) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn with_effect(mut self, effect: PluginEffect) -> Self {
+        self.effects.push(effect);
+        self
+    }
 }
 
 impl Layer {
@@ -13,15 +78,113 @@ impl Layer {
             name: name.into(),
             z_override: None,
             clips: Vec::new(),
+            transform: AnimatedTransform::default(),
+            blend: BlendMode::Normal,
+            matte: None,
+            group: None,
         }
     }
 
+    pub fn with_transform(mut self, transform: AnimatedTransform) -> Self {
+        self.transform = transform;
+        self
+    }
+
     pub fn with_z_override(mut self, z: i32) -> Self {
         self.z_override = Some(z);
         self
     }
 
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn with_matte(mut self, matte: TrackMatte) -> Self {
+        self.matte = Some(matte);
+        self
+    }
+
+    pub fn with_group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
     pub fn add_clip(&mut self, clip: Clip) {
         self.clips.push(clip);
     }
+
+    // Latest clip end, or 0.0 for an empty layer.
+    pub fn end_time(&self) -> f32 {
+        self.clips.iter().map(|clip| clip.end).fold(0.0, f32::max)
+    }
+
+    // Indices of the clips shown at `t` in the order they're drawn: list
+    // order, except that clips being transitioned out of go under the rest,
+    // so the clips replacing them draw over them.
+    pub fn shown_clips(&self, t: f32, aspect: Option<Aspect>, fps: u32) -> Vec<usize> {
+        let mut shown: Vec<usize> = (0..self.clips.len())
+            .filter(|&index| self.clips[index].is_shown(t, aspect))
+            .collect();
+        shown.sort_by_key(|&index| {
+            let transition = self.transition_at(index, t, fps);
+            transition.is_none_or(|transition| transition.role != TransitionRole::Outgoing)
+        });
+        shown
+    }
+
+    // Transition state for `clips[index]` at time `t`, if it is currently
+    // entering over (or being replaced by) an overlapping clip. Ends less
+    // than half a frame apart at `fps` count as the same.
+    pub fn transition_at(&self, index: usize, t: f32, fps: u32) -> Option<SampledTransition> {
+        let clip = &self.clips[index];
+        let half_frame = 0.5 / fps as f32;
+
+        if let Some(transition) = &clip.transition_in
+            && let Some(end) = self.replaced_end(index)
+            && t < end
+        {
+            let span = end - clip.start;
+            return Some(SampledTransition {
+                kind: transition.kind,
+                role: TransitionRole::Incoming,
+                progress: transition.easing.apply_over((t - clip.start) / span, span),
+            });
+        }
+
+        for (other_index, other) in self.clips.iter().enumerate() {
+            let Some(transition) = &other.transition_in else {
+                continue;
+            };
+            if other_index == index || !other.is_active(t) {
+                continue;
+            }
+            let replaces = self
+                .replaced_end(other_index)
+                .is_some_and(|end| (end - clip.end).abs() < half_frame);
+            if !replaces || clip.start >= other.start {
+                continue;
+            }
+            let span = clip.end - other.start;
+            return Some(SampledTransition {
+                kind: transition.kind,
+                role: TransitionRole::Outgoing,
+                progress: transition.easing.apply_over((t - other.start) / span, span),
+            });
+        }
+
+        None
+    }
+
+    // End time of the clip that `clips[index]` transitions in over: the
+    // latest-ending earlier clip that is still running when it starts.
+    fn replaced_end(&self, index: usize) -> Option<f32> {
+        let clip = &self.clips[index];
+        self.clips
+            .iter()
+            .enumerate()
+            .filter(|(i, other)| *i != index && other.start < clip.start && other.end > clip.start)
+            .map(|(_, other)| other.end)
+            .reduce(f32::max)
+    }
 }
@@ -0,0 +1,13 @@
+#[derive(Debug, Clone, PartialEq)]
+pub struct Marker {
+    pub time: f32,
+    pub name: String,
+}
+
+// A named span of the rendered output, in seconds from the first rendered frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub title: String,
+    pub start: f32,
+    pub end: f32,
+}
@@ -1,7 +1,31 @@
+pub mod assertion;
+pub mod builder;
 pub mod clip;
+pub mod csv;
+pub mod data;
+pub mod diff;
 pub mod layer;
+pub mod marker;
+pub mod responsive;
+pub mod snapshot;
+pub mod subtitles;
 pub mod timeline;
+pub mod transition;
+pub mod validate;
 
-pub use clip::Clip;
-pub use layer::Layer;
-pub use timeline::{SampledClip, SampledLayer, SampledScene, Timeline};
+pub use assertion::{Assertion, AssertionRule};
+pub use builder::TimelineBuilder;
+pub use clip::{Clip, LoopMode};
+pub use csv::CsvTable;
+pub use data::{BindTarget, Binding, DataSource, FrameTable, Variables};
+pub use diff::TimeRange;
+pub use layer::{BlendMode, Layer, LayerGroup, TrackMatte};
+pub use marker::{Chapter, Marker};
+pub use responsive::{Aspect, AspectOverride};
+pub use timeline::{
+    DrawOrderEntry, SampledClip, SampledGroup, SampledLayer, SampledScene, Timeline,
+};
+pub use transition::{
+    SampledTransition, Transition, TransitionDirection, TransitionKind, TransitionRole,
+};
+pub use validate::{IssueKind, Severity, ValidationIssue, ValidationReport};
@@ -0,0 +1,93 @@
+use crate::scene::{Transform, Vec2};
+
+// The output shapes a project can be rendered for, so one project serves
+// landscape, vertical and square social formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aspect {
+    // 16:9, and anything at least 4:3.
+    Landscape,
+    // 9:16, and anything at most 3:4.
+    Portrait,
+    // 1:1, and anything in between (4:5, 5:4).
+    Square,
+}
+
+impl Aspect {
+    // The aspect an output of `width` x `height` renders as: whichever of
+    // 16:9, 9:16 and 1:1 its ratio is closest to.
+    pub fn of(width: u32, height: u32) -> Self {
+        let (width, height) = (width as f32, height.max(1) as f32);
+        if width * 3.0 >= height * 4.0 {
+            Aspect::Landscape
+        } else if width * 4.0 <= height * 3.0 {
+            Aspect::Portrait
+        } else {
+            Aspect::Square
+        }
+    }
+
+    // `16:9` / `landscape`, `9:16` / `portrait` or `1:1` / `square`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "16:9" | "landscape" => Some(Aspect::Landscape),
+            "9:16" | "portrait" => Some(Aspect::Portrait),
+            "1:1" | "square" => Some(Aspect::Square),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aspect::Landscape => "16:9",
+            Aspect::Portrait => "9:16",
+            Aspect::Square => "1:1",
+        }
+    }
+}
+
+// Changes to a clip when rendering for one aspect, e.g. moving a caption up
+// and shrinking it for 9:16, or hiding a side panel that doesn't fit. The
+// offset and scale apply on top of the clip's animation, so it still moves
+// as it does in other formats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AspectOverride {
+    pub aspect: Aspect,
+    // Added to the clip's position, in graph coords.
+    pub offset: Vec2,
+    // Multiplies the clip's scale.
+    pub scale: Vec2,
+    pub visible: bool,
+}
+
+impl AspectOverride {
+    pub fn new(aspect: Aspect) -> Self {
+        Self {
+            aspect,
+            offset: Vec2::ZERO,
+            scale: Vec2::ONE,
+            visible: true,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vec2) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn hidden(mut self) -> Self {
+        self.visible = false;
+        self
+    }
+
+    pub fn apply(&self, transform: &mut Transform) {
+        transform.pos.x += self.offset.x;
+        transform.pos.y += self.offset.y;
+        transform.scale.x *= self.scale.x;
+        transform.scale.y *= self.scale.y;
+    }
+}
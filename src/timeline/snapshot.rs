@@ -0,0 +1,426 @@
+use std::fmt::Write as _;
+
+use anyhow::Result;
+
+use crate::scene::{
+    Color, Object, ParamValue, PostEffect, Shape, Transform, UniformValue, Vec2,
+};
+use crate::timeline::{SampledClip, SampledScene, Timeline};
+
+// Canonical text form of sampled scenes for snapshot tests, so timeline,
+// clip and track changes can be checked without a GPU. Numbers are rounded
+// to 4 decimals so float noise doesn't churn snapshots, and everything is
+// written in scene order.
+impl Timeline {
+    // Snapshots at each of `times`, separated by blank lines.
+    pub fn snapshot(&self, times: &[f32]) -> Result<String> {
+        let mut out = String::new();
+        for (index, t) in times.iter().enumerate() {
+            if index > 0 {
+                out.push('\n');
+            }
+            out.push_str(&self.sample(*t)?.to_snapshot());
+        }
+        Ok(out)
+    }
+}
+
+impl SampledScene {
+    pub fn to_snapshot(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "scene t={}", num(self.time));
+        write_scene(&mut out, self, 1);
+        out
+    }
+}
+
+fn write_scene(out: &mut String, scene: &SampledScene, depth: usize) {
+    let indent = "  ".repeat(depth);
+    for effect in &scene.effects {
+        let _ = writeln!(
+            out,
+            "{indent}effect {:?}{}",
+            effect.kind,
+            params(&effect.params)
+        );
+    }
+    for effect in &scene.post {
+        let _ = writeln!(out, "{indent}post {}", post_effect(effect));
+    }
+    for group in &scene.groups {
+        let _ = writeln!(
+            out,
+            "{indent}group {:?} opacity={} blend={:?}",
+            group.name,
+            num(group.opacity),
+            group.blend
+        );
+        for effect in &group.effects {
+            let _ = writeln!(
+                out,
+                "{indent}  effect {:?}{}",
+                effect.kind,
+                params(&effect.params)
+            );
+        }
+    }
+    for layer in &scene.layers {
+        let _ = write!(
+            out,
+            "{indent}layer {:?} blend={:?}",
+            layer.name, layer.blend
+        );
+        if let Some(matte) = layer.matte {
+            let _ = write!(out, " matte={matte:?}");
+        }
+        if let Some(group) = layer.group {
+            let _ = write!(out, " group={:?}", scene.groups[group].name);
+        }
+        let _ = writeln!(out, " {}", transform(&layer.transform));
+        for clip in &layer.clips {
+            write_clip(out, clip, depth + 1);
+        }
+    }
+}
+
+fn write_clip(out: &mut String, clip: &SampledClip, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let _ = write!(
+        out,
+        "{indent}clip local={} {} {}",
+        num(clip.local_time),
+        transform(&clip.transform),
+        object(&clip.object)
+    );
+    if let Some(transition) = &clip.transition {
+        let _ = write!(
+            out,
+            " transition={:?}/{:?}@{}",
+            transition.kind,
+            transition.role,
+            num(transition.progress)
+        );
+    }
+    if let Some(shader) = &clip.shader {
+        let _ = write!(out, " shader={:?}", shader.path.display().to_string());
+        for (name, value) in &shader.uniforms {
+            let value = match value {
+                UniformValue::Float(value) => num(*value),
+                UniformValue::Vec2(value) => format!("({},{})", num(value.x), num(value.y)),
+                UniformValue::Vec4(value) => {
+                    let parts: Vec<String> = value.iter().map(|part| num(*part)).collect();
+                    format!("({})", parts.join(","))
+                }
+            };
+            let _ = write!(out, " {name}={value}");
+        }
+    }
+    out.push('\n');
+    if let Some(nested) = &clip.nested {
+        write_scene(out, nested, depth + 1);
+    }
+}
+
+fn post_effect(effect: &PostEffect) -> String {
+    match effect {
+        PostEffect::Vignette { amount, softness } => {
+            format!("vignette amount={} softness={}", num(*amount), num(*softness))
+        }
+        PostEffect::Grain { amount } => format!("grain amount={}", num(*amount)),
+        PostEffect::Bloom {
+            threshold,
+            intensity,
+            radius,
+        } => format!(
+            "bloom threshold={} intensity={} radius={}",
+            num(*threshold),
+            num(*intensity),
+            num(*radius)
+        ),
+        PostEffect::Lut { path, amount } => format!(
+            "lut {:?} amount={}",
+            path.display().to_string(),
+            num(*amount)
+        ),
+    }
+}
+
+fn object(object: &Object) -> String {
+    match object {
+        Object::Shape(Shape::Rect {
+            width,
+            height,
+            color: fill,
+        }) => format!("rect {}x{} {}", num(*width), num(*height), color(*fill)),
+        Object::Shape(Shape::Circle {
+            radius,
+            color: fill,
+        }) => format!("circle r={} {}", num(*radius), color(*fill)),
+        Object::Image(image) => format!("image {:?}", image.path.display().to_string()),
+        Object::Text(text) => {
+            let runs: Vec<String> = text
+                .text
+                .runs
+                .iter()
+                .map(|run| {
+                    let style = run.style;
+                    let flags: String = [
+                        (style.bold, 'b'),
+                        (style.italic, 'i'),
+                        (style.underline, 'u'),
+                    ]
+                    .iter()
+                    .filter(|(on, _)| *on)
+                    .map(|(_, flag)| *flag)
+                    .collect();
+                    format!("{flags}{:?}", run.text)
+                })
+                .collect();
+            format!(
+                "text size={} {} [{}]",
+                num(text.font_size),
+                color(text.color),
+                runs.join(" ")
+            )
+        }
+        Object::Composition(composition) => format!(
+            "composition {}x{} bg={}",
+            composition.width,
+            composition.height,
+            color(composition.bg)
+        ),
+        Object::Plugin(plugin) => format!("plugin {:?}{}", plugin.kind, params(&plugin.params)),
+    }
+}
+
+fn params(params: &crate::scene::Params) -> String {
+    let mut out = String::new();
+    for (key, value) in params {
+        let value = match value {
+            ParamValue::Number(value) => num(*value),
+            ParamValue::Bool(value) => value.to_string(),
+            ParamValue::Text(value) => format!("{value:?}"),
+            ParamValue::Color(value) => color(*value),
+            ParamValue::Vec2(value) => vec2(*value),
+        };
+        let _ = write!(out, " {key}={value}");
+    }
+    out
+}
+
+fn transform(transform: &Transform) -> String {
+    format!(
+        "pos={} scale={} rot={} opacity={}",
+        vec2(transform.pos),
+        vec2(transform.scale),
+        num(transform.rotation),
+        num(transform.opacity)
+    )
+}
+
+fn vec2(value: Vec2) -> String {
+    format!("({}, {})", num(value.x), num(value.y))
+}
+
+fn color(color: Color) -> String {
+    format!(
+        "#{:02x}{:02x}{:02x}{:02x}",
+        color.r, color.g, color.b, color.a
+    )
+}
+
+fn num(value: f32) -> String {
+    let text = format!("{value:.4}");
+    // `-0.0000` and `0.0000` are the same snapshot.
+    if text
+        .trim_start_matches('-')
+        .bytes()
+        .all(|b| b == b'0' || b == b'.')
+    {
+        "0.0000".to_string()
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::project::Project;
+    use crate::scene::{
+        AnimatedTransform, BezierPath, Easing, ImageObject, Keyframe, PathMotion, Track,
+    };
+    use crate::timeline::{Clip, Layer, TimelineBuilder};
+
+    // Compares `timeline`'s snapshot at `times` with `snapshots/<name>.snap`.
+    // Run with `BLESS=1` to write the file instead, then review the diff.
+    fn check(name: &str, timeline: &Timeline, times: &[f32]) {
+        let actual = timeline.snapshot(times).unwrap();
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/timeline/snapshots")
+            .join(format!("{name}.snap"));
+        if std::env::var_os("BLESS").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &actual).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_else(|err| {
+            panic!("{}: {err}; run with BLESS=1 to create it", path.display())
+        });
+        assert!(
+            actual == expected,
+            "{name} differs from {}; run with BLESS=1 to update it\n--- actual\n{actual}",
+            path.display()
+        );
+    }
+
+    // The timeline `m0_hello_timeline` plays.
+    #[test]
+    fn hello_timeline() {
+        let timeline = TimelineBuilder::new(30)
+            .duration(10.0)
+            .layer("background")
+            .clip(Object::Shape(Shape::Rect {
+                width: 700.0,
+                height: 420.0,
+                color: Color::rgba(128, 4, 128, 255),
+            }))
+            .at(0.0..8.0)
+            .layer("mid")
+            .clip(Object::Shape(Shape::Circle {
+                radius: 80.0,
+                color: Color::rgba_css(235, 101, 80, 0.7),
+            }))
+            .at(1.0..6.5)
+            .placed(Transform {
+                pos: Vec2 { x: -140.0, y: 60.0 },
+                ..Transform::default()
+            })
+            .clip(Object::Shape(Shape::Rect {
+                width: 200.0,
+                height: 120.0,
+                color: Color::rgb(70, 140, 220),
+            }))
+            .at(2.5..8.0)
+            .placed(Transform {
+                pos: Vec2 { x: 160.0, y: -40.0 },
+                rotation: 12.0,
+                ..Transform::default()
+            })
+            .layer("top")
+            .clip(Object::Image(ImageObject::new("assets/logo.png")))
+            .at(3.0..8.0)
+            .placed(Transform {
+                scale: Vec2 { x: 2.0, y: 2.0 },
+                ..Transform::default()
+            })
+            .build()
+            .unwrap();
+        check(
+            "hello_timeline",
+            &timeline,
+            &[0.0, 1.0, 2.5, 3.0, 6.5, 8.0, 10.0],
+        );
+    }
+
+    // The timeline `m1_animation` plays: path motion and keyframed
+    // rotation and opacity.
+    #[test]
+    fn animation() {
+        let mut timeline = Timeline::new(6.0, 30).unwrap();
+        let mut background = Layer::new("background");
+        let rect = Object::Shape(Shape::Rect {
+            width: 760.0,
+            height: 460.0,
+            color: Color::rgb(18, 18, 22),
+        });
+        background.add_clip(Clip::new(0.0, 6.0, rect, AnimatedTransform::default()).unwrap());
+
+        let mut motion = Layer::new("motion");
+        let wave = BezierPath::new(Vec2 { x: -280.0, y: 0.0 })
+            .with_curve(
+                Vec2 {
+                    x: -190.0,
+                    y: 160.0,
+                },
+                Vec2 { x: -90.0, y: 160.0 },
+                Vec2::ZERO,
+            )
+            .with_curve(
+                Vec2 { x: 90.0, y: -160.0 },
+                Vec2 {
+                    x: 190.0,
+                    y: -160.0,
+                },
+                Vec2 { x: 280.0, y: 0.0 },
+            );
+        let progress = Track::new(vec![
+            Keyframe::new(0.0, 0.0, Easing::EaseInOutQuad),
+            Keyframe::new(4.0, 1.0, Easing::Linear),
+        ])
+        .unwrap();
+        let circle = Object::Shape(Shape::Circle {
+            radius: 60.0,
+            color: Color::rgb(240, 120, 90),
+        });
+        let along_wave =
+            AnimatedTransform::default().with_path(PathMotion::new(wave, progress).unwrap());
+        motion.add_clip(Clip::new(0.0, 5.0, circle, along_wave).unwrap());
+
+        let spin = AnimatedTransform {
+            position: Track::from_constant(Vec2 { x: 0.0, y: -40.0 }),
+            scale: Track::from_constant(Vec2 { x: 2.0, y: 2.0 }),
+            rotation: Track::new(vec![
+                Keyframe::new(0.0, 0.0, Easing::EaseOutCubic),
+                Keyframe::new(3.5, 360.0, Easing::Linear),
+            ])
+            .unwrap(),
+            opacity: Track::new(vec![
+                Keyframe::new(0.0, 0.0, Easing::EaseInOutQuad),
+                Keyframe::new(0.8, 1.0, Easing::Linear),
+                Keyframe::new(3.2, 1.0, Easing::EaseOutCubic),
+                Keyframe::new(4.0, 0.0, Easing::Linear),
+            ])
+            .unwrap(),
+            path: None,
+        };
+        let logo = Object::Image(ImageObject::new("assets/logo.png"));
+        motion.add_clip(Clip::new(0.5, 4.5, logo, spin).unwrap());
+
+        timeline.add_layer(background).unwrap();
+        timeline.add_layer(motion).unwrap();
+        check("animation", &timeline, &[0.0, 0.4, 1.0, 2.3, 3.6, 4.5, 6.0]);
+    }
+
+    // The project file from the README: keyframes with easing on a
+    // ping-pong loop.
+    #[test]
+    fn readme_project() {
+        let project = Project::from_json(
+            r##"{
+              "schema_version": 1,
+              "width": 800, "height": 600, "bg": "#101014",
+              "duration": 6, "fps": 30,
+              "layers": [
+                { "name": "motion", "clips": [
+                  { "start": 0, "end": 6, "loop": "ping_pong",
+                    "object": { "type": "circle", "radius": 40, "color": "#f0b43c" },
+                    "transform": { "position": [
+                      { "time": 0, "value": [-280, 0], "easing": "ease_in_out_quad" },
+                      { "time": 2, "value": [280, 0] }
+                    ] } }
+                ] }
+              ]
+            }"##,
+        )
+        .unwrap();
+        check(
+            "readme_project",
+            &project.timeline,
+            &[0.0, 0.5, 1.0, 2.0, 3.0, 4.5, 6.0],
+        );
+    }
+}
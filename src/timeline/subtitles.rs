@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::error::ErrorKind;
+use crate::scene::{Color, FontSource, Object, StyleFlags, TextObject, Transform};
+use crate::timeline::Timeline;
+
+const STYLE_FORMAT: &str = "Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, \
+OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, \
+BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding";
+const EVENT_FORMAT: &str =
+    "Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text";
+
+impl Timeline {
+    // The text clips on the layer named `layer` as an Advanced SubStation
+    // Alpha script, for soft subtitles alongside (or instead of) the burned-in
+    // captions. Each caption is placed and styled as it looks when its clip
+    // starts; keyframed motion and non-text clips are not exported.
+    pub fn captions_to_ass(&self, layer: &str, width: u32, height: u32) -> Result<String> {
+        let Some(layer) = self.layers.iter().find(|candidate| candidate.name == layer) else {
+            bail!(ErrorKind::InvalidProject.error(format!("no layer named \"{layer}\"")));
+        };
+
+        let mut styles: Vec<String> = Vec::new();
+        let mut events = String::new();
+        for clip in &layer.clips {
+            let Object::Text(text) = &clip.object else {
+                continue;
+            };
+            let local = clip.transform.sample(clip.content_time(clip.start));
+            let transform = layer.transform.sample(clip.start).compose(&local);
+            let style = style_fields(text, &transform);
+            let index = match styles.iter().position(|existing| *existing == style) {
+                Some(index) => index,
+                None => {
+                    styles.push(style);
+                    styles.len() - 1
+                }
+            };
+            // Text blocks hang from their top-left corner, like alignment 7.
+            let x = width as f32 / 2.0 + transform.pos.x;
+            let y = height as f32 / 2.0 - transform.pos.y;
+            events.push_str(&format!(
+                "Dialogue: 0,{},{},Caption{},,0,0,0,,{{\\pos({x:.0},{y:.0})}}{}\n",
+                timestamp(clip.start),
+                timestamp(clip.end),
+                index + 1,
+                dialogue_text(text)
+            ));
+        }
+
+        let mut out = format!(
+            "[Script Info]\nScriptType: v4.00+\nPlayResX: {width}\nPlayResY: {height}\n\
+             ScaledBorderAndShadow: yes\n\n[V4+ Styles]\n{STYLE_FORMAT}\n"
+        );
+        for (index, style) in styles.iter().enumerate() {
+            out.push_str(&format!("Style: Caption{},{style}\n", index + 1));
+        }
+        out.push_str(&format!("\n[Events]\n{EVENT_FORMAT}\n"));
+        out.push_str(&events);
+        Ok(out)
+    }
+
+    pub fn write_captions_ass(
+        &self,
+        layer: &str,
+        width: u32,
+        height: u32,
+        path: impl AsRef<Path>,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.captions_to_ass(layer, width, height)?)
+            .with_context(|| format!("failed to write subtitles: {}", path.display()))
+    }
+}
+
+// Every style field after the name. Bold/italic/underline are set per run.
+fn style_fields(text: &TextObject, transform: &Transform) -> String {
+    let scale = transform.scale.y.max(0.0);
+    let font = match &text.font.regular {
+        FontSource::Path(path) => path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().replace(',', " "))
+            .unwrap_or_else(|| "Arial".to_string()),
+        FontSource::Default => "Arial".to_string(),
+    };
+    let primary = ass_color(text.color, transform.opacity);
+    // Border style 3 draws an opaque box in the outline colour.
+    let (border_style, outline, outline_color) = match (&text.background, &text.stroke) {
+        (Some(background), _) => (3, background.padding * scale, background.color),
+        (None, Some(stroke)) => (1, stroke.width * scale, stroke.color),
+        (None, None) => (1, 0.0, Color::BLACK),
+    };
+    format!(
+        "{font},{:.0},{primary},{primary},{},&H00000000,0,0,0,0,100,100,{},{},\
+         {border_style},{},0,7,0,0,0,1",
+        text.font_size * scale,
+        ass_color(outline_color, transform.opacity),
+        text.spacing,
+        0.0 - transform.rotation,
+        outline
+    )
+}
+
+fn dialogue_text(text: &TextObject) -> String {
+    let mut out = String::new();
+    let mut current = StyleFlags::PLAIN;
+    for run in &text.text.runs {
+        let style = run.style;
+        for (tag, on, was) in [
+            ('b', style.bold, current.bold),
+            ('i', style.italic, current.italic),
+            ('u', style.underline, current.underline),
+        ] {
+            if on != was {
+                out.push_str(&format!("{{\\{tag}{}}}", on as u8));
+            }
+        }
+        current = style;
+        for ch in run.text.chars() {
+            match ch {
+                '\n' => out.push_str("\\N"),
+                // ASS has no escape for override-block braces.
+                '{' => out.push('('),
+                '}' => out.push(')'),
+                _ => out.push(ch),
+            }
+        }
+    }
+    out
+}
+
+// `H:MM:SS.cc`, in centiseconds.
+fn timestamp(seconds: f32) -> String {
+    let cs = (seconds.max(0.0) * 100.0).round() as u64;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        cs / 360_000,
+        cs / 6_000 % 60,
+        cs / 100 % 60,
+        cs % 100
+    )
+}
+
+// `&HAABBGGRR`, where alpha 00 is opaque.
+fn ass_color(color: Color, opacity: f32) -> String {
+    let alpha = 255 - (color.a as f32 * opacity.clamp(0.0, 1.0)).round() as u8;
+    format!("&H{alpha:02X}{:02X}{:02X}{:02X}", color.b, color.g, color.r)
+}
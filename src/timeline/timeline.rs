@@ -1,82 +1,391 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
 use anyhow::{bail, Result};
 
-use crate::timeline::{Clip, Layer};
+use crate::error::ErrorKind;
+use crate::scene::{PluginEffect, PostEffect};
+use crate::timeline::{
+    Aspect, Assertion, BlendMode, Chapter, Clip, DataSource, Layer, LayerGroup, Marker,
+    SampledTransition, TrackMatte, Variables,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Timeline {
     pub duration: f32,
+    // When set, `duration` is the latest clip end and grows as layers are
+    // added instead of being declared up front.
+    pub auto_duration: bool,
     pub fps: u32,
     pub layers: Vec<Layer>,
+    // Referenced by name from `Layer::group`.
+    pub groups: Vec<LayerGroup>,
+    // Plugin effects applied in order to every captured frame.
+    pub effects: Vec<PluginEffect>,
+    // Screen-space passes run in order on every composited frame.
+    pub post: Vec<PostEffect>,
+    // Kept sorted by time.
+    pub markers: Vec<Marker>,
+    // The output shape being rendered, which picks each clip's overrides.
+    // `None` ignores them.
+    pub aspect: Option<Aspect>,
+    // Checked against every rendered frame; see `Assertion`.
+    pub assertions: Vec<Assertion>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct SampledClip {
+    // The clip's name, which identifies it in hit regions.
+    pub name: Option<String>,
     pub object: crate::scene::Object,
     pub transform: crate::scene::Transform,
+    pub local_time: f32,
+    pub transition: Option<SampledTransition>,
+    // Inner scene for `Object::Composition`, sampled at the clip's local time.
+    pub nested: Option<Box<SampledScene>>,
+    pub shader: Option<crate::scene::SampledShader>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct SampledLayer {
     pub name: String,
+    pub transform: crate::scene::Transform,
+    pub blend: BlendMode,
+    // When set, the next layer in the scene is this layer's matte source.
+    pub matte: Option<TrackMatte>,
+    // Index into `SampledScene::groups`. Members of a group are adjacent.
+    pub group: Option<usize>,
     pub clips: Vec<SampledClip>,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampledGroup {
+    pub name: String,
+    pub opacity: f32,
+    pub blend: BlendMode,
+    pub effects: Vec<PluginEffect>,
+}
+
+// One clip in the resolved draw order; indices point into `Timeline::layers`
+// and that layer's `clips`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawOrderEntry {
+    pub layer_index: usize,
+    pub clip_index: usize,
+    pub z: i32,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct SampledScene {
+    pub time: f32,
     pub layers: Vec<SampledLayer>,
+    // The groups the layers use, in the order they're first drawn.
+    pub groups: Vec<SampledGroup>,
+    pub effects: Vec<PluginEffect>,
+    pub post: Vec<PostEffect>,
 }
 
 impl Timeline {
     pub fn new(duration: f32, fps: u32) -> Result<Self> {
         if duration <= 0.0 {
-            bail!("duration must be > 0");
+            bail!(ErrorKind::InvalidProject.error("duration must be > 0"));
         }
         if fps == 0 {
-            bail!("fps must be > 0");
+            bail!(ErrorKind::InvalidProject.error("fps must be > 0"));
         }
         Ok(Self {
             duration,
+            auto_duration: false,
+            fps,
+            layers: Vec::new(),
+            groups: Vec::new(),
+            effects: Vec::new(),
+            post: Vec::new(),
+            markers: Vec::new(),
+            aspect: None,
+            assertions: Vec::new(),
+        })
+    }
+
+    // A timeline whose duration is the latest clip end across its layers.
+    pub fn auto_duration(fps: u32) -> Result<Self> {
+        if fps == 0 {
+            bail!(ErrorKind::InvalidProject.error("fps must be > 0"));
+        }
+        Ok(Self {
+            duration: 0.0,
+            auto_duration: true,
             fps,
             layers: Vec::new(),
+            groups: Vec::new(),
+            effects: Vec::new(),
+            post: Vec::new(),
+            markers: Vec::new(),
+            aspect: None,
+            assertions: Vec::new(),
         })
     }
 
-    pub fn add_layer(&mut self, layer: Layer) {
+    pub fn add_layer(&mut self, layer: Layer) -> Result<()> {
+        let end = layer.end_time();
+        if self.auto_duration {
+            self.duration = self.duration.max(end);
+        } else if end > self.duration {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "layer \"{}\" has a clip ending at {} past the timeline duration {}",
+                layer.name, end, self.duration
+            )));
+        }
         self.layers.push(layer);
+        Ok(())
     }
 
-    pub fn sample(&self, t: f32) -> Result<SampledScene> {
-        if t < 0.0 || t > self.duration {
-            bail!("sample time must be within 0..=duration");
+    pub fn add_group(&mut self, group: LayerGroup) -> Result<()> {
+        if self.group(&group.name).is_some() {
+            bail!(ErrorKind::InvalidProject.error(format!(
+                "more than one layer group is named \"{}\"",
+                group.name
+            )));
+        }
+        self.groups.push(group);
+        Ok(())
+    }
+
+    pub fn group(&self, name: &str) -> Option<&LayerGroup> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    // Renders for `aspect` from now on, nested compositions included.
+    pub fn set_aspect(&mut self, aspect: Option<Aspect>) {
+        self.aspect = aspect;
+        for clip in self.layers.iter_mut().flat_map(|layer| &mut layer.clips) {
+            if let crate::scene::Object::Composition(composition) = &mut clip.object {
+                Arc::make_mut(&mut composition.timeline).set_aspect(aspect);
+            }
+        }
+    }
+
+    pub fn add_effect(&mut self, effect: PluginEffect) {
+        self.effects.push(effect);
+    }
+
+    pub fn add_post_effect(&mut self, effect: PostEffect) {
+        self.post.push(effect);
+    }
+
+    pub fn add_assertion(&mut self, assertion: Assertion) {
+        self.assertions.push(assertion);
+    }
+
+    pub fn add_marker(&mut self, time: f32, name: impl Into<String>) -> Result<()> {
+        // Auto-duration timelines may not have their clips yet, so only the
+        // lower bound is checked; chapters are clipped to the final duration.
+        if time < 0.0 || (!self.auto_duration && time > self.duration) {
+            bail!(ErrorKind::InvalidProject.error("marker time must be within 0..=duration"));
+        }
+        let index = self.markers.partition_point(|marker| marker.time <= time);
+        self.markers.insert(
+            index,
+            Marker {
+                time,
+                name: name.into(),
+            },
+        );
+        Ok(())
+    }
+
+    // A copy holding only the clip named `name`, on a layer with its layer's
+    // transform, for rendering the clip by itself (see `StickerExport`).
+    // Other clips, effects, markers and the layer's blend, matte and group
+    // are left out.
+    pub fn isolate_clip(&self, name: &str) -> Result<Timeline> {
+        let mut found = self.layers.iter().flat_map(|layer| {
+            layer
+                .clips
+                .iter()
+                .filter(|clip| clip.name.as_deref() == Some(name))
+                .map(move |clip| (layer, clip))
+        });
+        let Some((layer, clip)) = found.next() else {
+            bail!(ErrorKind::InvalidProject.error(format!("no clip named \"{name}\"")));
+        };
+        if found.next().is_some() {
+            bail!(
+                ErrorKind::InvalidProject.error(format!("more than one clip is named \"{name}\""))
+            );
+        }
+
+        let mut isolated = Layer::new(layer.name.clone()).with_transform(layer.transform.clone());
+        isolated.add_clip(clip.clone());
+        let mut timeline = Timeline::new(self.duration, self.fps)?;
+        timeline.aspect = self.aspect;
+        timeline.add_layer(isolated)?;
+        Ok(timeline)
+    }
+
+    // Chapters for a render of `start_time..end_time`: each marker opens a
+    // chapter that runs to the next marker. Content before the first marker
+    // in range continues the chapter of the last marker before it, if any.
+    pub fn chapters(&self, start_time: f32, end_time: f32) -> Vec<Chapter> {
+        let mut chapters: Vec<Chapter> = Vec::new();
+        for (index, marker) in self.markers.iter().enumerate() {
+            let next = self
+                .markers
+                .get(index + 1)
+                .map_or(self.duration, |next| next.time);
+            let start = marker.time.max(start_time);
+            let end = next.min(end_time);
+            if end <= start {
+                continue;
+            }
+            chapters.push(Chapter {
+                title: marker.name.clone(),
+                start: start - start_time,
+                end: end - start_time,
+            });
         }
+        chapters
+    }
 
-        let mut ordered: Vec<(usize, &Layer)> = self.layers.iter().enumerate().collect();
-        ordered.sort_by(|(a_idx, a_layer), (b_idx, b_layer)| {
-            let a_z = a_layer.z_override.unwrap_or(*a_idx as i32);
-            let b_z = b_layer.z_override.unwrap_or(*b_idx as i32);
-            a_z.cmp(&b_z).then_with(|| a_idx.cmp(b_idx))
+    // Draw order (back to front): layers ascending by `z_override`, or their
+    // insertion index when unset, with ties broken by insertion index. Clips
+    // within a layer draw in insertion order. Later entries draw on top.
+    // Members of a group draw together, where the lowest of them would, and
+    // in their own order within it.
+    pub fn layer_order(&self) -> Vec<usize> {
+        let mut anchors: BTreeMap<&str, (i32, usize)> = BTreeMap::new();
+        for (index, layer) in self.layers.iter().enumerate() {
+            if let Some(group) = &layer.group {
+                let key = (self.layer_z(index), index);
+                let anchor = anchors.entry(group.as_str()).or_insert(key);
+                *anchor = (*anchor).min(key);
+            }
+        }
+        let mut order: Vec<usize> = (0..self.layers.len()).collect();
+        order.sort_by_key(|&index| {
+            let key = (self.layer_z(index), index);
+            let anchor = self.layers[index]
+                .group
+                .as_deref()
+                .map_or(key, |group| anchors[group]);
+            (anchor, key)
         });
+        order
+    }
+
+    pub fn layer_z(&self, index: usize) -> i32 {
+        self.layers[index].z_override.unwrap_or(index as i32)
+    }
+
+    // The clips active at `t` in the order `sample` returns and backends draw them.
+    pub fn draw_order(&self, t: f32) -> Vec<DrawOrderEntry> {
+        let mut entries = Vec::new();
+        for layer_index in self.layer_order() {
+            let z = self.layer_z(layer_index);
+            let layer = &self.layers[layer_index];
+            for clip_index in layer.shown_clips(t, self.aspect, self.fps) {
+                entries.push(DrawOrderEntry {
+                    layer_index,
+                    clip_index,
+                    z,
+                });
+            }
+        }
+        entries
+    }
+
+    pub fn sample(&self, t: f32) -> Result<SampledScene> {
+        self.sample_with(t, &Variables::new())
+    }
+
+    // Samples with the variables `data` supplies for the frame at `t`.
+    pub fn sample_from(&self, t: f32, data: &dyn DataSource) -> Result<SampledScene> {
+        let frame = (t * self.fps as f32).round() as u32;
+        self.sample_with(t, &data.values(frame, t)?)
+    }
+
+    // Samples with external per-frame variables applied to clip bindings.
+    pub fn sample_with(&self, t: f32, vars: &Variables) -> Result<SampledScene> {
+        if t < 0.0 || t > self.duration {
+            bail!(ErrorKind::InvalidProject.error("sample time must be within 0..=duration"));
+        }
 
+        let ordered = self.layer_order();
         let mut sampled_layers = Vec::with_capacity(ordered.len());
-        for (_, layer) in ordered {
+        let mut sampled_groups: Vec<SampledGroup> = Vec::new();
+        for layer_index in ordered {
+            let layer = &self.layers[layer_index];
+            let group = match &layer.group {
+                Some(name) => {
+                    let Some(group) = self.group(name) else {
+                        bail!(ErrorKind::InvalidProject.error(format!(
+                            "layer \"{}\" is in unknown group \"{name}\"",
+                            layer.name
+                        )));
+                    };
+                    match sampled_groups.iter().position(|sampled| sampled.name == *name) {
+                        Some(index) => Some(index),
+                        None => {
+                            sampled_groups.push(SampledGroup {
+                                name: group.name.clone(),
+                                opacity: group.opacity.sample(t).clamp(0.0, 1.0),
+                                blend: group.blend,
+                                effects: group.effects.clone(),
+                            });
+                            Some(sampled_groups.len() - 1)
+                        }
+                    }
+                }
+                None => None,
+            };
             let mut clips = Vec::new();
-            for clip in &layer.clips {
-                if clip.is_active(t) {
-                    let local_t = clip.clamped_local_time(t);
-                    clips.push(SampledClip {
-                        object: clip.object.clone(),
-                        transform: clip.transform.sample(local_t),
-                    });
+            for index in layer.shown_clips(t, self.aspect, self.fps) {
+                let clip = &layer.clips[index];
+                let local_t = clip.content_time(t);
+                let mut transform = clip.transform.sample(local_t);
+                if let Some(rule) = clip.override_for(self.aspect) {
+                    rule.apply(&mut transform);
+                }
+                let mut object = clip.object.clone();
+                for binding in &clip.bindings {
+                    binding.apply(vars, &mut transform, &mut object);
+                }
+                let transition = layer.transition_at(index, t, self.fps);
+                if let Some(transition) = &transition {
+                    transition.apply(&mut transform);
                 }
+                let nested = match &clip.object {
+                    crate::scene::Object::Composition(composition) => {
+                        Some(Box::new(composition.sample_with(local_t, vars)?))
+                    }
+                    _ => None,
+                };
+                clips.push(SampledClip {
+                    name: clip.name.clone(),
+                    object,
+                    transform,
+                    local_time: local_t,
+                    transition,
+                    nested,
+                    shader: clip.shader.as_ref().map(|shader| shader.sample(local_t)),
+                });
             }
             sampled_layers.push(SampledLayer {
                 name: layer.name.clone(),
+                transform: layer.transform.sample(t),
+                blend: layer.blend,
+                matte: layer.matte,
+                group,
                 clips,
             });
         }
 
         Ok(SampledScene {
+            time: t,
             layers: sampled_layers,
+            groups: sampled_groups,
+            effects: self.effects.clone(),
+            post: self.post.clone(),
         })
     }
 
@@ -88,11 +397,164 @@ impl Timeline {
 impl Clip {
     pub fn validate_against(&self, duration: f32) -> Result<()> {
         if duration <= 0.0 {
-            bail!("duration must be > 0");
+            bail!(ErrorKind::InvalidProject.error("duration must be > 0"));
         }
         if self.start < 0.0 || self.end <= self.start || self.end > duration {
-            bail!("clip bounds must satisfy 0 <= start < end <= duration");
+            bail!(
+                ErrorKind::InvalidProject
+                    .error("clip bounds must satisfy 0 <= start < end <= duration")
+            );
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::{AnimatedTransform, Color, Object, Shape, Transform};
+    use crate::timeline::{Transition, TransitionKind, TransitionRole};
+
+    fn clip(start: f32, end: f32) -> Clip {
+        let circle = Object::Shape(Shape::Circle {
+            radius: 10.0,
+            color: Color::WHITE,
+        });
+        let transform = AnimatedTransform::constant(Transform::default());
+        Clip::new(start, end, circle, transform).unwrap()
+    }
+
+    fn layer(name: &str, z: Option<i32>, clips: Vec<Clip>) -> Layer {
+        let mut layer = Layer::new(name);
+        if let Some(z) = z {
+            layer = layer.with_z_override(z);
+        }
+        for clip in clips {
+            layer.add_clip(clip);
+        }
+        layer
+    }
+
+    // Layers by insertion: back (z 0, implicit), front (z 5), under (z -3),
+    // tied (z 0, same as back, added later) and last (z 4, implicit).
+    fn sample_timeline() -> Timeline {
+        let mut timeline = Timeline::new(4.0, 10).unwrap();
+        let layers = [
+            layer("back", None, vec![clip(0.0, 4.0)]),
+            layer("front", Some(5), vec![clip(0.0, 4.0), clip(0.0, 2.0)]),
+            layer("under", Some(-3), vec![clip(1.0, 4.0)]),
+            layer("tied", Some(0), vec![clip(0.0, 4.0)]),
+            layer("last", None, vec![clip(2.0, 4.0), clip(0.0, 4.0)]),
+        ];
+        for layer in layers {
+            timeline.add_layer(layer).unwrap();
+        }
+        timeline
+    }
+
+    fn names(timeline: &Timeline, order: &[usize]) -> Vec<String> {
+        order
+            .iter()
+            .map(|&index| timeline.layers[index].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn layer_order_sorts_by_z_then_insertion() {
+        let timeline = sample_timeline();
+        let order = timeline.layer_order();
+        assert_eq!(
+            names(&timeline, &order),
+            ["under", "back", "tied", "last", "front"]
+        );
+        let z: Vec<i32> = order.iter().map(|&index| timeline.layer_z(index)).collect();
+        assert_eq!(z, [-3, 0, 0, 4, 5]);
+    }
+
+    #[test]
+    fn draw_order_lists_active_clips_back_to_front() {
+        let timeline = sample_timeline();
+        let entries = |t: f32| -> Vec<(usize, usize, i32)> {
+            let order = timeline.draw_order(t);
+            order
+                .iter()
+                .map(|entry| (entry.layer_index, entry.clip_index, entry.z))
+                .collect()
+        };
+        // "under" starts at 1s and the first clip of "last" at 2s.
+        assert_eq!(
+            entries(0.5),
+            [(0, 0, 0), (3, 0, 0), (4, 1, 4), (1, 0, 5), (1, 1, 5)]
+        );
+        // The second clip of "front" has ended.
+        assert_eq!(
+            entries(3.0),
+            [
+                (2, 0, -3),
+                (0, 0, 0),
+                (3, 0, 0),
+                (4, 0, 4),
+                (4, 1, 4),
+                (1, 0, 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn sample_draws_in_draw_order() {
+        let timeline = sample_timeline();
+        for t in [0.0, 1.5, 3.0] {
+            let scene = timeline.sample(t).unwrap();
+            let sampled: Vec<(&str, usize)> = scene
+                .layers
+                .iter()
+                .map(|layer| (layer.name.as_str(), layer.clips.len()))
+                .collect();
+            let mut expected: Vec<(&str, usize)> = Vec::new();
+            for entry in timeline.draw_order(t) {
+                let name = timeline.layers[entry.layer_index].name.as_str();
+                match expected.last_mut() {
+                    Some((last, count)) if *last == name => *count += 1,
+                    _ => expected.push((name, 1)),
+                }
+            }
+            let drawn: Vec<(&str, usize)> = sampled
+                .into_iter()
+                .filter(|&(_, count)| count > 0)
+                .collect();
+            assert_eq!(drawn, expected, "at {t}s");
+        }
+    }
+
+    #[test]
+    fn crossfade_draws_incoming_over_opaque_outgoing() {
+        let mut timeline = Timeline::new(4.0, 10).unwrap();
+        // Listed before the clips it replaces, which end within a frame of
+        // each other.
+        let crossfade = Transition::new(TransitionKind::Crossfade);
+        let incoming = clip(1.0, 4.0).with_transition_in(crossfade);
+        let clips = vec![incoming, clip(0.0, 1.99), clip(0.5, 2.0)];
+        timeline.add_layer(layer("cut", None, clips)).unwrap();
+
+        let scene = timeline.sample(1.5).unwrap();
+        let drawn: Vec<(f32, f32, Option<TransitionRole>)> = scene.layers[0]
+            .clips
+            .iter()
+            .map(|clip| {
+                let role = clip.transition.map(|transition| transition.role);
+                (clip.local_time, clip.transform.opacity, role)
+            })
+            .collect();
+        assert_eq!(
+            drawn,
+            [
+                (1.5, 1.0, Some(TransitionRole::Outgoing)),
+                (1.0, 1.0, Some(TransitionRole::Outgoing)),
+                (0.5, 0.5, Some(TransitionRole::Incoming)),
+            ]
+        );
+        let order = timeline.draw_order(1.5);
+        let order: Vec<usize> = order.iter().map(|entry| entry.clip_index).collect();
+        assert_eq!(order, [1, 2, 0]);
+    }
+}
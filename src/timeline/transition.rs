@@ -0,0 +1,132 @@
+use crate::scene::{Easing, Transform, Vec2};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransitionKind {
+    Crossfade,
+    // Both clips move together in `direction`, one canvas length over the transition.
+    Slide { direction: TransitionDirection },
+    // A hard edge travels in `direction`, revealing the incoming clip behind it.
+    Wipe { direction: TransitionDirection },
+    // Incoming grows from `1 - amount` while outgoing grows to `1 + amount`, both fading.
+    Zoom { amount: f32 },
+}
+
+// Declared on the incoming clip; it runs across the window where that clip
+// overlaps the clip it replaces on the same layer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    pub easing: Easing,
+}
+
+impl Transition {
+    pub fn new(kind: TransitionKind) -> Self {
+        Self {
+            kind,
+            easing: Easing::Linear,
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionRole {
+    Incoming,
+    Outgoing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledTransition {
+    pub kind: TransitionKind,
+    pub role: TransitionRole,
+    // Eased progress in 0.0..=1.0.
+    pub progress: f32,
+}
+
+impl SampledTransition {
+    // Applies the canvas-independent part (opacity and scale). Slides and
+    // wipes depend on the output size and are resolved by the backend.
+    pub fn apply(&self, transform: &mut Transform) {
+        let p = self.progress;
+        match (self.kind, self.role) {
+            // The outgoing clip stays opaque under the incoming one, so the
+            // frame never shows through halfway.
+            (TransitionKind::Crossfade, TransitionRole::Incoming) => transform.opacity *= p,
+            (TransitionKind::Crossfade, TransitionRole::Outgoing) => {}
+            (TransitionKind::Zoom { amount }, TransitionRole::Incoming) => {
+                let factor = 1.0 - amount * (1.0 - p);
+                transform.scale.x *= factor;
+                transform.scale.y *= factor;
+                transform.opacity *= p;
+            }
+            (TransitionKind::Zoom { amount }, TransitionRole::Outgoing) => {
+                let factor = 1.0 + amount * p;
+                transform.scale.x *= factor;
+                transform.scale.y *= factor;
+                transform.opacity *= 1.0 - p;
+            }
+            (TransitionKind::Slide { .. }, _) | (TransitionKind::Wipe { .. }, _) => {}
+        }
+    }
+
+    // Graph-space offset for slide transitions.
+    pub fn slide_offset(&self, width: f32, height: f32) -> Vec2 {
+        let TransitionKind::Slide { direction } = self.kind else {
+            return Vec2::ZERO;
+        };
+        let travel = match self.role {
+            TransitionRole::Incoming => self.progress - 1.0,
+            TransitionRole::Outgoing => self.progress,
+        };
+        match direction {
+            TransitionDirection::Left => Vec2 {
+                x: -travel * width,
+                y: 0.0,
+            },
+            TransitionDirection::Right => Vec2 {
+                x: travel * width,
+                y: 0.0,
+            },
+            TransitionDirection::Up => Vec2 {
+                x: 0.0,
+                y: travel * height,
+            },
+            TransitionDirection::Down => Vec2 {
+                x: 0.0,
+                y: -travel * height,
+            },
+        }
+    }
+
+    // Screen-space (x, y, w, h) the clip is clipped to during a wipe.
+    pub fn wipe_rect(&self, width: f32, height: f32) -> Option<(f32, f32, f32, f32)> {
+        let TransitionKind::Wipe { direction } = self.kind else {
+            return None;
+        };
+        let p = self.progress;
+        let incoming = self.role == TransitionRole::Incoming;
+        let rect = match (direction, incoming) {
+            (TransitionDirection::Left, true) => (width * (1.0 - p), 0.0, width * p, height),
+            (TransitionDirection::Left, false) => (0.0, 0.0, width * (1.0 - p), height),
+            (TransitionDirection::Right, true) => (0.0, 0.0, width * p, height),
+            (TransitionDirection::Right, false) => (width * p, 0.0, width * (1.0 - p), height),
+            (TransitionDirection::Up, true) => (0.0, height * (1.0 - p), width, height * p),
+            (TransitionDirection::Up, false) => (0.0, 0.0, width, height * (1.0 - p)),
+            (TransitionDirection::Down, true) => (0.0, 0.0, width, height * p),
+            (TransitionDirection::Down, false) => (0.0, height * p, width, height * (1.0 - p)),
+        };
+        Some(rect)
+    }
+}
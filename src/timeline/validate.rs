@@ -0,0 +1,396 @@
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use crate::backend::font_coverage::FontCoverage;
+use crate::error::ErrorKind;
+use crate::scene::animation::Lerp;
+use crate::scene::{Extrapolation, FontSource, Object, PostEffect, Track};
+use crate::timeline::{Clip, LoopMode, Timeline};
+use crate::video::VideoClip;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    // The render will fail (or draw something clearly wrong).
+    Error,
+    // Legal, but probably not what was meant.
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    // Clips on one layer overlap without a transition between them.
+    OverlappingClips,
+    MissingAsset,
+    // A layer with no clips.
+    EmptyLayer,
+    // Keyframes the clip never reaches, so their values never show.
+    KeyframeOutsideClip,
+    // A track set to loop, ping-pong or continue whose keyframes span no
+    // time (one keyframe, or frozen), so it only ever holds one value.
+    ZeroLengthTrack,
+    // Missing, or not a TrueType/OpenType font.
+    UnloadableFont,
+    // A layer in a group the timeline doesn't define.
+    UnknownGroup,
+    // An assertion about a clip name no clip has, which would always pass.
+    UnknownAssertionClip,
+    // Post effects on a nested timeline; they only run over whole frames.
+    NestedPostEffect,
+}
+
+impl IssueKind {
+    pub fn severity(self) -> Severity {
+        match self {
+            IssueKind::MissingAsset
+            | IssueKind::UnloadableFont
+            | IssueKind::UnknownGroup
+            | IssueKind::UnknownAssertionClip
+            | IssueKind::NestedPostEffect => Severity::Error,
+            IssueKind::OverlappingClips
+            | IssueKind::EmptyLayer
+            | IssueKind::KeyframeOutsideClip
+            | IssueKind::ZeroLengthTrack => Severity::Warning,
+        }
+    }
+
+    // Error category a render hitting this issue would fail with.
+    pub fn error_kind(self) -> ErrorKind {
+        match self {
+            IssueKind::MissingAsset | IssueKind::UnloadableFont => ErrorKind::MissingAsset,
+            IssueKind::OverlappingClips
+            | IssueKind::EmptyLayer
+            | IssueKind::KeyframeOutsideClip
+            | IssueKind::ZeroLengthTrack
+            | IssueKind::UnknownGroup
+            | IssueKind::UnknownAssertionClip
+            | IssueKind::NestedPostEffect => ErrorKind::InvalidProject,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    pub kind: IssueKind,
+    // Where in the timeline, e.g. `layers[1].clips[0]`.
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.kind.severity() {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{severity}: {}: {}", self.location, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.kind.severity() == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.kind.severity() == Severity::Warning)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    // Adds the issues with video clips (see `build_base_video`), which live
+    // outside the timeline, under `videos[i]`.
+    pub fn with_video_clips(mut self, clips: &[VideoClip]) -> Self {
+        for (index, clip) in clips.iter().enumerate() {
+            let location = format!("videos[{index}]");
+            if !clip.path.is_file() {
+                push(
+                    &mut self,
+                    IssueKind::MissingAsset,
+                    &location,
+                    format!("video clip not found: {}", clip.path.display()),
+                );
+            }
+            if let Some(time_map) = &clip.time_map {
+                check_track_length(time_map, "time_map", &location, &mut self);
+            }
+        }
+        self
+    }
+
+    // Fails with the first error's kind, listing every error in the message.
+    pub fn check(&self) -> Result<()> {
+        let Some(first) = self.errors().next() else {
+            return Ok(());
+        };
+        let lines: Vec<String> = self.errors().map(|issue| issue.to_string()).collect();
+        bail!(first.kind.error_kind().error(format!(
+            "timeline has {} error(s):\n{}",
+            lines.len(),
+            lines.join("\n")
+        )));
+    }
+}
+
+impl Timeline {
+    // Checks the whole timeline (nested compositions included) up front, so
+    // problems show up before rendering instead of partway through it.
+    pub fn validate(&self) -> ValidationReport {
+        self.validate_with_assets(&|_| false)
+    }
+
+    // As `validate`, with image and font paths `in_memory` accepts counting
+    // as present (see `AssetBytes`).
+    pub fn validate_with_assets(&self, in_memory: &dyn Fn(&Path) -> bool) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        validate_timeline(self, "", in_memory, &mut report);
+        report
+    }
+}
+
+fn validate_timeline(
+    timeline: &Timeline,
+    prefix: &str,
+    in_memory: &dyn Fn(&Path) -> bool,
+    report: &mut ValidationReport,
+) {
+    for (layer_index, layer) in timeline.layers.iter().enumerate() {
+        let location = format!("{prefix}layers[{layer_index}]");
+        if layer.clips.is_empty() {
+            push(
+                report,
+                IssueKind::EmptyLayer,
+                &location,
+                format!("layer \"{}\" has no clips", layer.name),
+            );
+        }
+        if let Some(group) = &layer.group
+            && timeline.group(group).is_none()
+        {
+            push(
+                report,
+                IssueKind::UnknownGroup,
+                &location,
+                format!("layer \"{}\" is in unknown group \"{group}\"", layer.name),
+            );
+        }
+
+        for (index, clip) in layer.clips.iter().enumerate() {
+            let location = format!("{location}.clips[{index}]");
+            // A later clip with a transition is meant to overlap the one it
+            // replaces.
+            let overlapping = layer.clips[..index].iter().position(|earlier| {
+                clip.transition_in.is_none() && earlier.start < clip.end && clip.start < earlier.end
+            });
+            if let Some(earlier) = overlapping {
+                push(
+                    report,
+                    IssueKind::OverlappingClips,
+                    &location,
+                    format!("overlaps clips[{earlier}] without a transition"),
+                );
+            }
+            validate_keyframes(clip, &location, report);
+            validate_object(&clip.object, &location, in_memory, report);
+            if let Some(shader) = &clip.shader
+                && !shader.path.is_file()
+            {
+                push(
+                    report,
+                    IssueKind::MissingAsset,
+                    &location,
+                    format!("shader not found: {}", shader.path.display()),
+                );
+            }
+        }
+    }
+
+    for (index, effect) in timeline.post.iter().enumerate() {
+        if let PostEffect::Lut { path, .. } = effect
+            && !path.is_file()
+        {
+            push(
+                report,
+                IssueKind::MissingAsset,
+                &format!("{prefix}post[{index}]"),
+                format!("lookup table not found: {}", path.display()),
+            );
+        }
+    }
+
+    for (index, assertion) in timeline.assertions.iter().enumerate() {
+        let name = assertion.rule.clip();
+        let known = timeline
+            .layers
+            .iter()
+            .flat_map(|layer| &layer.clips)
+            .any(|clip| clip.name.as_deref() == Some(name));
+        if !known {
+            push(
+                report,
+                IssueKind::UnknownAssertionClip,
+                &format!("{prefix}assertions[{index}]"),
+                format!("no clip named \"{name}\""),
+            );
+        }
+    }
+}
+
+fn validate_keyframes(clip: &Clip, location: &str, report: &mut ValidationReport) {
+    let length = clip.end - clip.start;
+    let transform = &clip.transform;
+    check_track_length(&transform.position, "position", location, report);
+    check_track_length(&transform.scale, "scale", location, report);
+    check_track_length(&transform.rotation, "rotation", location, report);
+    check_track_length(&transform.opacity, "opacity", location, report);
+    if let Some(path) = &transform.path {
+        check_track_length(&path.progress, "path progress", location, report);
+    }
+    if let Some(time_map) = &clip.time_map {
+        check_track_length(time_map, "time_map", location, report);
+        check_track_window(time_map, length, "time_map", location, report);
+    }
+    // Looping or remapped content can reach keyframes past the clip length.
+    if clip.loop_mode != LoopMode::Once || clip.time_map.is_some() {
+        return;
+    }
+    check_track_window(&transform.position, length, "position", location, report);
+    check_track_window(&transform.scale, length, "scale", location, report);
+    check_track_window(&transform.rotation, length, "rotation", location, report);
+    check_track_window(&transform.opacity, length, "opacity", location, report);
+    if let Some(path) = &transform.path {
+        check_track_window(&path.progress, length, "path progress", location, report);
+    }
+}
+
+fn check_track_window<T: Lerp>(
+    track: &Track<T>,
+    length: f32,
+    name: &str,
+    location: &str,
+    report: &mut ValidationReport,
+) {
+    if track.start_time() < 0.0 || track.end_time() > length {
+        push(
+            report,
+            IssueKind::KeyframeOutsideClip,
+            location,
+            format!(
+                "{name} keyframes span {}..{} but the clip is {length}s long",
+                track.start_time(),
+                track.end_time()
+            ),
+        );
+    }
+}
+
+fn check_track_length<T: Lerp>(
+    track: &Track<T>,
+    name: &str,
+    location: &str,
+    report: &mut ValidationReport,
+) {
+    let (pre, post) = track.extrapolation();
+    let repeats = pre != Extrapolation::Hold || post != Extrapolation::Hold;
+    if repeats && track.end_time() <= track.start_time() {
+        push(
+            report,
+            IssueKind::ZeroLengthTrack,
+            location,
+            format!("{name} extrapolates past keyframes that span no time"),
+        );
+    }
+}
+
+fn validate_object(
+    object: &Object,
+    location: &str,
+    in_memory: &dyn Fn(&Path) -> bool,
+    report: &mut ValidationReport,
+) {
+    match object {
+        Object::Image(image) => {
+            if !image.path.is_file() && !in_memory(&image.path) {
+                push(
+                    report,
+                    IssueKind::MissingAsset,
+                    location,
+                    format!("image asset not found: {}", image.path.display()),
+                );
+            }
+        }
+        Object::Text(text) => {
+            let family = &text.font;
+            let sources = [
+                Some(&family.regular),
+                family.bold.as_ref(),
+                family.italic.as_ref(),
+                family.bold_italic.as_ref(),
+            ];
+            for source in sources.into_iter().flatten().chain(family.fallbacks.iter()) {
+                if let FontSource::Path(path) = source {
+                    validate_font(path, location, in_memory, report);
+                }
+            }
+        }
+        Object::Composition(composition) => {
+            if !composition.timeline.post.is_empty() {
+                push(
+                    report,
+                    IssueKind::NestedPostEffect,
+                    location,
+                    "post effects aren't applied inside compositions; use plugin effects"
+                        .to_string(),
+                );
+            }
+            validate_timeline(
+                &composition.timeline,
+                &format!("{location}.composition."),
+                in_memory,
+                report,
+            );
+        }
+        Object::Shape(_) | Object::Plugin(_) => {}
+    }
+}
+
+fn validate_font(
+    path: &Path,
+    location: &str,
+    in_memory: &dyn Fn(&Path) -> bool,
+    report: &mut ValidationReport,
+) {
+    if in_memory(path) {
+        return;
+    }
+    // Parsed as the renderers' glyph fallback does, so a file that isn't a
+    // font fails here rather than mid-render.
+    if let Err(err) = FontCoverage::from_file(path) {
+        push(
+            report,
+            IssueKind::UnloadableFont,
+            location,
+            format!("cannot load font: {err:#}"),
+        );
+    }
+}
+
+fn push(report: &mut ValidationReport, kind: IssueKind, location: &str, message: String) {
+    report.issues.push(ValidationIssue {
+        kind,
+        location: location.to_string(),
+        message,
+    });
+}
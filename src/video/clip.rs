@@ -2,6 +2,9 @@ use std::path::PathBuf;
 
 use anyhow::{bail, Result};
 
+use crate::error::ErrorKind;
+use crate::scene::Track;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct VideoClip {
     pub path: PathBuf,
@@ -9,6 +12,9 @@ pub struct VideoClip {
     pub end_time: f32,
     pub trim_start: Option<f32>,
     pub trim_end: Option<f32>,
+    // Maps clip-local time to time in the trimmed source, as
+    // `Clip::time_map` does for timeline content.
+    pub time_map: Option<Track<f32>>,
 }
 
 impl VideoClip {
@@ -21,28 +27,30 @@ impl VideoClip {
     ) -> Result<Self> {
         let path = path.into();
         if !path.exists() {
-            bail!("video clip not found: {}", path.display());
+            bail!(
+                ErrorKind::MissingAsset.error(format!("video clip not found: {}", path.display()))
+            );
         }
         if start_time < 0.0 || end_time <= start_time {
-            bail!("clip bounds must satisfy 0 <= start < end");
+            bail!(ErrorKind::InvalidProject.error("clip bounds must satisfy 0 <= start < end"));
         }
         if let Some(ts) = trim_start {
             if ts < 0.0 {
-                bail!("trim_start must be >= 0");
+                bail!(ErrorKind::InvalidProject.error("trim_start must be >= 0"));
             }
         }
         if let Some(te) = trim_end {
             if te <= 0.0 {
-                bail!("trim_end must be > 0");
+                bail!(ErrorKind::InvalidProject.error("trim_end must be > 0"));
             }
         }
         if let (Some(ts), Some(te)) = (trim_start, trim_end) {
             if te <= ts {
-                bail!("trim_end must be > trim_start");
+                bail!(ErrorKind::InvalidProject.error("trim_end must be > trim_start"));
             }
             let clip_len = end_time - start_time;
             if te - ts < clip_len {
-                bail!("trim range shorter than clip duration");
+                bail!(ErrorKind::InvalidProject.error("trim range shorter than clip duration"));
             }
         }
 
@@ -52,10 +60,25 @@ impl VideoClip {
             end_time,
             trim_start,
             trim_end,
+            time_map: None,
         })
     }
 
+    pub fn with_time_map(mut self, time_map: Track<f32>) -> Self {
+        self.time_map = Some(time_map);
+        self
+    }
+
     pub fn duration(&self) -> f32 {
         self.end_time - self.start_time
     }
+
+    // Time in the source file shown at clip-local time `local_t`.
+    pub fn source_time(&self, local_t: f32) -> f32 {
+        let mapped = match &self.time_map {
+            Some(time_map) => time_map.sample(local_t).max(0.0),
+            None => local_t,
+        };
+        self.trim_start.unwrap_or(0.0) + mapped
+    }
 }
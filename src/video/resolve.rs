@@ -33,8 +33,7 @@ pub fn resolve_segments(clips: &[VideoClip]) -> Vec<VideoSegment> {
 
         if let Some(clip_index) = chosen {
             let clip = &clips[clip_index];
-            let offset = t0 - clip.start_time;
-            let source_start = clip.trim_start.unwrap_or(0.0) + offset;
+            let source_start = clip.source_time(t0 - clip.start_time);
             segments.push(VideoSegment {
                 clip_index,
                 timeline_start: t0,